@@ -9,6 +9,13 @@ interface Choice {
 	consequence_hint: string | null;
 }
 
+// The moment in the player's language; the moment itself stays in English
+interface MomentTranslation {
+	locale: string;
+	text: string;
+	choices: Record<string, string>;
+}
+
 interface NarrativeMoment {
 	id: string;
 	text: string;
@@ -16,6 +23,7 @@ interface NarrativeMoment {
 	mood: string;
 	choices: Choice[];
 	timestamp: string;
+	translation?: MomentTranslation;
 }
 
 interface Loop {
@@ -42,7 +50,8 @@ interface Player {
 	name: string | null;
 	current_loop: Loop;
 	memory: PersistentMemory;
-	narrative_history: NarrativeMoment[];
+	history_length: number;
+	last_moment: NarrativeMoment | null;
 	created_at: string;
 }
 
@@ -83,6 +92,27 @@ const PORTRAITS: Record<string, string> = {
 	transcendent: "/portraits/transcendent.png",
 };
 
+// Wait in the server's waiting room until a slot frees, resolving to the new player's id
+function waitForSlot(
+	ticket: string,
+	onPosition: (position: number) => void,
+): Promise<string> {
+	return new Promise((resolve, reject) => {
+		const source = new EventSource(`${API_BASE}/waiting/${ticket}/events`);
+		source.addEventListener("position", (event) => {
+			onPosition(JSON.parse((event as MessageEvent).data).position);
+		});
+		source.addEventListener("admitted", (event) => {
+			source.close();
+			resolve(JSON.parse((event as MessageEvent).data).player_id);
+		});
+		source.onerror = () => {
+			source.close();
+			reject(new Error("Lost your place in the waiting room"));
+		};
+	});
+}
+
 function App() {
 	const [player, setPlayer] = useState<Player | null>(null);
 	const [currentMoment, setCurrentMoment] = useState<NarrativeMoment | null>(
@@ -93,6 +123,7 @@ function App() {
 	);
 	const [loading, setLoading] = useState(false);
 	const [error, setError] = useState<string | null>(null);
+	const [queuePosition, setQueuePosition] = useState<number | null>(null);
 	const [showMemory, setShowMemory] = useState(true);
 	const [ending, setEnding] = useState<EndingResponse | null>(null);
 	const [audioEnabled, setAudioEnabled] = useState(false);
@@ -211,7 +242,16 @@ function App() {
 
 			if (!response.ok) throw new Error("Failed to start game");
 
-			const data = await response.json();
+			let data = await response.json();
+			// The server is full: wait in line, then load the player we were given
+			if (response.status === 202) {
+				setQueuePosition(data.position);
+				const playerId = await waitForSlot(data.ticket, setQueuePosition);
+				setQueuePosition(null);
+				const loaded = await fetch(`${API_BASE}/game/load/${playerId}`);
+				if (!loaded.ok) throw new Error("Failed to start game");
+				data = await loaded.json();
+			}
 			setPlayer(data.player);
 			localStorage.setItem("nihilism_player_id", data.player.id);
 			fetchSaves();
@@ -234,6 +274,7 @@ function App() {
 				setEnding(narrativeData.ending);
 			}
 		} catch (err) {
+			setQueuePosition(null);
 			setError(err instanceof Error ? err.message : "Unknown error occurred");
 		} finally {
 			setLoading(false);
@@ -356,7 +397,16 @@ function App() {
 			if (stateResponse.ok) {
 				const stateData = await stateResponse.json();
 				setCurrentMoment(stateData.current_moment);
-				setNarrativeHistory(data.player.narrative_history || []);
+			}
+
+			// Fetch the most recent page of history
+			const offset = Math.max(0, data.player.history_length - 100);
+			const historyResponse = await fetch(
+				`${API_BASE}/game/${id}/history?offset=${offset}&limit=100`,
+			);
+			if (historyResponse.ok) {
+				const historyData = await historyResponse.json();
+				setNarrativeHistory(historyData.moments);
 			}
 		} catch (err) {
 			setError(err instanceof Error ? err.message : "Unknown error occurred");
@@ -539,7 +589,11 @@ function App() {
 				{loading && (
 					<div className="loading">
 						<div className="loader"></div>
-						<span className="loading-text">The loop iterates...</span>
+						<span className="loading-text">
+							{queuePosition !== null
+								? `The loop is crowded. You are number ${queuePosition} in line...`
+								: "The loop iterates..."}
+						</span>
 					</div>
 				)}
 
@@ -593,7 +647,9 @@ function App() {
 							{currentMoment.speaker && (
 								<div className="speaker-name">{currentMoment.speaker}</div>
 							)}
-							<p className="narrative-text">{currentMoment.text}</p>
+							<p className="narrative-text">
+								{currentMoment.translation?.text ?? currentMoment.text}
+							</p>
 						</div>
 
 						{/* Choices */}
@@ -606,7 +662,9 @@ function App() {
 									onClick={() => makeChoice(choice)}
 									disabled={loading}
 								>
-									<span className="choice-text">{choice.text}</span>
+									<span className="choice-text">
+										{currentMoment.translation?.choices[choice.id] ?? choice.text}
+									</span>
 									{choice.consequence_hint && (
 										<span className="choice-hint">
 											{choice.consequence_hint}
@@ -0,0 +1,204 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::game::Player;
+
+/// Strikes of one kind this close together count once, so a burst of
+/// rate-limited keystrokes is one strike rather than twenty
+const STRIKE_COOLDOWN_SECS: i64 = 60;
+/// Strikes kept per player for review
+const MAX_KEPT_STRIKES: usize = 50;
+/// Longest input excerpt kept with a strike
+const MAX_DETAIL_CHARS: usize = 120;
+
+/// Phrases typical of attempts to override the narrator's instructions
+const INJECTION_PATTERNS: &[&str] = &[
+    "ignore previous instructions",
+    "ignore all previous",
+    "ignore the above",
+    "ignore your instructions",
+    "disregard previous",
+    "disregard all previous",
+    "disregard your instructions",
+    "forget your instructions",
+    "system prompt",
+    "you are now",
+    "new instructions:",
+    "developer mode",
+    "jailbreak",
+    "<|im_start|>",
+    "<|system|>",
+    "[system]",
+    "### instruction",
+];
+
+/// Whether player input looks like a prompt injection attempt
+pub fn looks_like_injection(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    INJECTION_PATTERNS.iter().any(|p| lower.contains(p))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrikeKind {
+    PromptInjection,
+    Moderation,
+    RateLimit,
+}
+
+impl StrikeKind {
+    fn label(self) -> &'static str {
+        match self {
+            StrikeKind::PromptInjection => "prompt_injection",
+            StrikeKind::Moderation => "moderation",
+            StrikeKind::RateLimit => "rate_limit",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Strike {
+    pub kind: StrikeKind,
+    pub at: DateTime<Utc>,
+    /// Excerpt of the offending input, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// A player's abuse history. Ghosted players are served offline content
+/// only; nothing tells them so.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AbuseRecord {
+    #[serde(default)]
+    pub strikes: Vec<Strike>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ghosted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unbanned_at: Option<DateTime<Utc>>,
+}
+
+impl AbuseRecord {
+    pub fn is_clean(&self) -> bool {
+        self.strikes.is_empty() && self.ghosted_at.is_none() && self.unbanned_at.is_none()
+    }
+
+    pub fn is_ghosted(&self) -> bool {
+        self.ghosted_at.is_some()
+    }
+}
+
+/// A ghosted player awaiting review
+#[derive(Clone, Debug, Serialize)]
+pub struct ReviewEntry {
+    pub player_id: Uuid,
+    pub name: Option<String>,
+    pub ghosted_at: DateTime<Utc>,
+    pub strikes: BTreeMap<StrikeKind, usize>,
+    pub recent: Vec<Strike>,
+}
+
+impl ReviewEntry {
+    pub fn from_player(player: &Player) -> Option<Self> {
+        let mut strikes = BTreeMap::new();
+        for strike in &player.abuse.strikes {
+            *strikes.entry(strike.kind).or_insert(0) += 1;
+        }
+        Some(Self {
+            player_id: player.id,
+            name: player.name.clone(),
+            ghosted_at: player.abuse.ghosted_at?,
+            strikes,
+            recent: player.abuse.strikes.iter().rev().take(10).cloned().collect(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Totals {
+    strikes: BTreeMap<StrikeKind, u64>,
+    ghosted: u64,
+    unbanned: u64,
+}
+
+/// Accumulates strikes and moves players into ghost mode past the threshold
+pub struct AbuseMonitor {
+    /// Strikes that ghost a player; 0 records strikes but never ghosts
+    threshold: usize,
+    totals: Mutex<Totals>,
+}
+
+impl AbuseMonitor {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            totals: Mutex::new(Totals::default()),
+        }
+    }
+
+    /// Add a strike, returning whether it just ghosted the player
+    pub fn strike(&self, player: &mut Player, kind: StrikeKind, detail: Option<&str>) -> bool {
+        let now = Utc::now();
+        let record = &mut player.abuse;
+        let recent = record.strikes.iter().rev().find(|s| s.kind == kind);
+        if recent.is_some_and(|s| now - s.at < Duration::seconds(STRIKE_COOLDOWN_SECS)) {
+            return false;
+        }
+
+        record.strikes.push(Strike {
+            kind,
+            at: now,
+            detail: detail.map(|d| d.chars().take(MAX_DETAIL_CHARS).collect()),
+        });
+        if record.strikes.len() > MAX_KEPT_STRIKES {
+            record.strikes.remove(0);
+        }
+        let mut totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        *totals.strikes.entry(kind).or_insert(0) += 1;
+        tracing::info!("Strike ({}) for player {}", kind.label(), player.id);
+
+        let ghost = self.threshold > 0 && !record.is_ghosted() && record.strikes.len() >= self.threshold;
+        if ghost {
+            record.ghosted_at = Some(now);
+            totals.ghosted += 1;
+            tracing::warn!(
+                "Player {} ghosted after {} strikes",
+                player.id,
+                record.strikes.len()
+            );
+        }
+        ghost
+    }
+
+    /// Lift ghost mode and clear the player's strikes
+    pub fn unban(&self, player: &mut Player) {
+        player.abuse = AbuseRecord {
+            unbanned_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        self.totals.lock().unwrap_or_else(|e| e.into_inner()).unbanned += 1;
+        tracing::info!("Player {} unbanned", player.id);
+    }
+
+    /// Append abuse counters in Prometheus text format
+    pub fn write_metrics(&self, out: &mut String) {
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        out.push_str("# HELP nihilism_abuse_strikes_total Abuse strikes recorded, by kind\n");
+        out.push_str("# TYPE nihilism_abuse_strikes_total counter\n");
+        for (kind, count) in &totals.strikes {
+            out.push_str(&format!(
+                "nihilism_abuse_strikes_total{{kind=\"{}\"}} {}\n",
+                kind.label(),
+                count
+            ));
+        }
+        out.push_str("# HELP nihilism_abuse_ghosted_total Players moved into ghost mode\n");
+        out.push_str("# TYPE nihilism_abuse_ghosted_total counter\n");
+        out.push_str(&format!("nihilism_abuse_ghosted_total {}\n", totals.ghosted));
+        out.push_str("# HELP nihilism_abuse_unbanned_total Players unbanned by an admin\n");
+        out.push_str("# TYPE nihilism_abuse_unbanned_total counter\n");
+        out.push_str(&format!("nihilism_abuse_unbanned_total {}\n", totals.unbanned));
+    }
+}
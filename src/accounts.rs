@@ -0,0 +1,401 @@
+use anyhow::Result;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::tenant;
+
+const ACCOUNTS_FILE: &str = "data/accounts.json";
+const SESSION_DAYS: i64 = 30;
+const MAGIC_LINK_MINUTES: i64 = 15;
+/// Failed logins, or magic links asked for, allowed per username or email
+/// address in each window
+const MAX_ATTEMPTS: u32 = 5;
+const ATTEMPT_WINDOW: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+/// Optional account binding several player runs together
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub id: Uuid,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password_hash: Option<String>,
+    pub player_ids: Vec<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Account as returned by the API, without credentials
+#[derive(Clone, Debug, Serialize)]
+pub struct AccountView {
+    pub id: Uuid,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub player_ids: Vec<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Account {
+    pub fn view(&self) -> AccountView {
+        AccountView {
+            id: self.id,
+            username: self.username.clone(),
+            email: self.email.clone(),
+            player_ids: self.player_ids.clone(),
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Session {
+    account_id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct MagicLink {
+    email: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct AccountData {
+    accounts: HashMap<Uuid, Account>,
+    #[serde(default)]
+    sessions: HashMap<String, Session>,
+    #[serde(default)]
+    magic_links: HashMap<String, MagicLink>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AccountError {
+    #[error("username is already taken")]
+    UsernameTaken,
+    #[error("invalid username or password")]
+    InvalidCredentials,
+    #[error("link is invalid or expired")]
+    InvalidLink,
+    #[error("{0}")]
+    Invalid(&'static str),
+    #[error("too many attempts")]
+    TooManyAttempts(std::time::Duration),
+    #[error(transparent)]
+    Storage(#[from] anyhow::Error),
+}
+
+/// Attempts against one username or email address in the current window
+struct Attempts {
+    window_start: Instant,
+    count: u32,
+}
+
+/// Accounts, sessions and pending magic links, persisted to `data/accounts.json`
+/// or the tenant's own
+pub struct AccountStore {
+    path: PathBuf,
+    data: Mutex<AccountData>,
+    attempts: Mutex<HashMap<String, Attempts>>,
+}
+
+fn random_token() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    let salt: [u8; 16] = rand::rng().random();
+    let salt = SaltString::encode_b64(&salt).map_err(|e| anyhow::anyhow!("{}", e))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+fn normalize_username(username: &str) -> Result<String, AccountError> {
+    let username = username.trim().to_lowercase();
+    if !(3..=32).contains(&username.chars().count())
+        || !username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(AccountError::Invalid(
+            "username must be 3-32 letters, digits, '_' or '-'",
+        ));
+    }
+    Ok(username)
+}
+
+fn normalize_email(email: &str) -> Result<String, AccountError> {
+    let email = email.trim().to_lowercase();
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && domain.contains('.') => Ok(email),
+        _ => Err(AccountError::Invalid("invalid email address")),
+    }
+}
+
+impl AccountStore {
+    /// Accounts of the deployment, or of the tenant `config` belongs to
+    pub fn load(config: &Config) -> Result<Self> {
+        let path = tenant::data_path(config.tenant.as_deref(), ACCOUNTS_FILE);
+        let data = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            AccountData::default()
+        };
+        Ok(Self {
+            path,
+            data: Mutex::new(data),
+            attempts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn data(&self) -> std::sync::MutexGuard<'_, AccountData> {
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn attempts(&self) -> std::sync::MutexGuard<'_, HashMap<String, Attempts>> {
+        self.attempts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, data: &AccountData) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(data)?)?;
+        Ok(())
+    }
+
+    /// Turn `key` away while its attempts are used up, with the time until
+    /// its window ends
+    fn check_attempts(&self, key: &str) -> Result<(), AccountError> {
+        match self.attempts().get(key) {
+            Some(attempts)
+                if attempts.count >= MAX_ATTEMPTS
+                    && attempts.window_start.elapsed() < ATTEMPT_WINDOW =>
+            {
+                Err(AccountError::TooManyAttempts(
+                    ATTEMPT_WINDOW.saturating_sub(attempts.window_start.elapsed()),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn count_attempt(&self, key: &str) {
+        let mut attempts = self.attempts();
+        let attempts = attempts.entry(key.to_string()).or_insert(Attempts {
+            window_start: Instant::now(),
+            count: 0,
+        });
+        if attempts.window_start.elapsed() >= ATTEMPT_WINDOW {
+            attempts.window_start = Instant::now();
+            attempts.count = 0;
+        }
+        attempts.count += 1;
+    }
+
+    fn open_session(data: &mut AccountData, account_id: Uuid) -> String {
+        let token = random_token();
+        data.sessions.insert(
+            token.clone(),
+            Session {
+                account_id,
+                expires_at: Utc::now() + Duration::days(SESSION_DAYS),
+            },
+        );
+        token
+    }
+
+    /// Create a password account and return it with a session token
+    pub fn register(&self, username: &str, password: &str) -> Result<(Account, String), AccountError> {
+        let username = normalize_username(username)?;
+        if password.chars().count() < 8 {
+            return Err(AccountError::Invalid("password must be at least 8 characters"));
+        }
+        let password_hash = hash_password(password)?;
+
+        let mut data = self.data();
+        if data
+            .accounts
+            .values()
+            .any(|a| a.username.as_deref() == Some(username.as_str()))
+        {
+            return Err(AccountError::UsernameTaken);
+        }
+        let account = Account {
+            id: Uuid::new_v4(),
+            username: Some(username),
+            email: None,
+            password_hash: Some(password_hash),
+            player_ids: Vec::new(),
+            created_at: Utc::now(),
+        };
+        data.accounts.insert(account.id, account.clone());
+        let token = Self::open_session(&mut data, account.id);
+        self.save(&data)?;
+        Ok((account, token))
+    }
+
+    /// Open a session for a password account. Hashes are checked off the
+    /// async runtime and outside the lock, and a username takes only
+    /// `MAX_ATTEMPTS` failures per window.
+    pub async fn login(&self, username: &str, password: &str) -> Result<(Account, String), AccountError> {
+        let username = username.trim().to_lowercase();
+        let key = format!("login:{}", username);
+        self.check_attempts(&key)?;
+        let found = self
+            .data()
+            .accounts
+            .values()
+            .find(|a| a.username.as_deref() == Some(username.as_str()))
+            .and_then(|a| Some((a.id, a.password_hash.clone()?)));
+        let verified = match found {
+            Some((account_id, hash)) => {
+                let password = password.to_string();
+                tokio::task::spawn_blocking(move || verify_password(&password, &hash))
+                    .await
+                    .map_err(anyhow::Error::from)?
+                    .then_some(account_id)
+            }
+            None => None,
+        };
+
+        let mut data = self.data();
+        let Some(account) = verified.and_then(|id| data.accounts.get(&id)).cloned() else {
+            drop(data);
+            self.count_attempt(&key);
+            return Err(AccountError::InvalidCredentials);
+        };
+        let token = Self::open_session(&mut data, account.id);
+        self.save(&data)?;
+        Ok((account, token))
+    }
+
+    /// Issue a one-time sign-in token for an email address, at most
+    /// `MAX_ATTEMPTS` per address and window
+    pub fn create_magic_link(&self, email: &str) -> Result<String, AccountError> {
+        let email = normalize_email(email)?;
+        let key = format!("link:{}", email);
+        self.check_attempts(&key)?;
+        self.count_attempt(&key);
+        let token = random_token();
+        let mut data = self.data();
+        let now = Utc::now();
+        data.magic_links.retain(|_, link| link.expires_at > now);
+        data.magic_links.insert(
+            token.clone(),
+            MagicLink {
+                email,
+                expires_at: now + Duration::minutes(MAGIC_LINK_MINUTES),
+            },
+        );
+        self.save(&data)?;
+        Ok(token)
+    }
+
+    /// Redeem a magic link, creating the account on first use
+    pub fn redeem_magic_link(&self, token: &str) -> Result<(Account, String), AccountError> {
+        let mut data = self.data();
+        let link = data
+            .magic_links
+            .remove(token)
+            .filter(|link| link.expires_at > Utc::now())
+            .ok_or(AccountError::InvalidLink)?;
+
+        let existing = data
+            .accounts
+            .values()
+            .find(|a| a.email.as_deref() == Some(link.email.as_str()))
+            .cloned();
+        let account = match existing {
+            Some(account) => account,
+            None => {
+                let account = Account {
+                    id: Uuid::new_v4(),
+                    username: None,
+                    email: Some(link.email),
+                    password_hash: None,
+                    player_ids: Vec::new(),
+                    created_at: Utc::now(),
+                };
+                data.accounts.insert(account.id, account.clone());
+                account
+            }
+        };
+        let token = Self::open_session(&mut data, account.id);
+        self.save(&data)?;
+        Ok((account, token))
+    }
+
+    /// Resolve a session token to its account
+    pub fn authenticate(&self, token: &str) -> Option<Account> {
+        let data = self.data();
+        let session = data.sessions.get(token)?;
+        if session.expires_at <= Utc::now() {
+            return None;
+        }
+        data.accounts.get(&session.account_id).cloned()
+    }
+
+    pub fn logout(&self, token: &str) -> Result<()> {
+        let mut data = self.data();
+        if data.sessions.remove(token).is_some() {
+            self.save(&data)?;
+        }
+        Ok(())
+    }
+
+    /// Attach a player run to an account
+    pub fn bind_player(&self, account_id: Uuid, player_id: Uuid) -> Result<Account, AccountError> {
+        let mut data = self.data();
+        let account = data
+            .accounts
+            .get_mut(&account_id)
+            .ok_or(AccountError::InvalidCredentials)?;
+        if !account.player_ids.contains(&player_id) {
+            account.player_ids.push(player_id);
+        }
+        let account = account.clone();
+        self.save(&data)?;
+        Ok(account)
+    }
+
+    /// Drop expired sessions and magic links, and attempt windows that ended
+    pub fn evict_expired(&self) -> Result<usize> {
+        self.attempts()
+            .retain(|_, attempts| attempts.window_start.elapsed() < ATTEMPT_WINDOW);
+        let mut data = self.data();
+        let now = Utc::now();
+        let before = data.sessions.len() + data.magic_links.len();
+        data.sessions.retain(|_, s| s.expires_at > now);
+        data.magic_links.retain(|_, l| l.expires_at > now);
+        let evicted = before - data.sessions.len() - data.magic_links.len();
+        if evicted > 0 {
+            self.save(&data)?;
+        }
+        Ok(evicted)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,108 @@
+use super::*;
+use std::path::Path;
+
+fn store() -> AccountStore {
+    let dir = std::env::temp_dir().join(format!("nihilism-accounts-{}", Uuid::new_v4()));
+    AccountStore {
+        path: dir.join("accounts.json"),
+        data: Mutex::new(AccountData::default()),
+        attempts: Mutex::new(HashMap::new()),
+    }
+}
+
+#[tokio::test]
+async fn passwords_sign_in_until_too_many_fail() {
+    let accounts = store();
+    assert!(matches!(
+        accounts.register("al", "long enough"),
+        Err(AccountError::Invalid(_))
+    ));
+    assert!(matches!(
+        accounts.register("alice", "short"),
+        Err(AccountError::Invalid(_))
+    ));
+    let (account, token) = accounts.register("Alice", "long enough").unwrap();
+    assert_eq!(account.username.as_deref(), Some("alice"));
+    assert_eq!(accounts.authenticate(&token).unwrap().id, account.id);
+    assert!(matches!(
+        accounts.register("alice", "another one"),
+        Err(AccountError::UsernameTaken)
+    ));
+
+    let (signed_in, _) = accounts.login(" ALICE ", "long enough").await.unwrap();
+    assert_eq!(signed_in.id, account.id);
+    for _ in 0..MAX_ATTEMPTS {
+        assert!(matches!(
+            accounts.login("alice", "wrong password").await,
+            Err(AccountError::InvalidCredentials)
+        ));
+    }
+    // Even the right password waits out the window now; other users don't
+    assert!(matches!(
+        accounts.login("alice", "long enough").await,
+        Err(AccountError::TooManyAttempts(_))
+    ));
+    accounts.register("bob", "long enough").unwrap();
+    accounts.login("bob", "long enough").await.unwrap();
+}
+
+#[test]
+fn magic_links_are_single_use_and_expire() {
+    let accounts = store();
+    assert!(matches!(
+        accounts.create_magic_link("nobody"),
+        Err(AccountError::Invalid(_))
+    ));
+    let token = accounts.create_magic_link(" Ada@Example.com ").unwrap();
+    let (account, _) = accounts.redeem_magic_link(&token).unwrap();
+    assert_eq!(account.email.as_deref(), Some("ada@example.com"));
+    assert!(matches!(
+        accounts.redeem_magic_link(&token),
+        Err(AccountError::InvalidLink)
+    ));
+
+    // The same address signs in to the same account
+    let token = accounts.create_magic_link("ada@example.com").unwrap();
+    assert_eq!(accounts.redeem_magic_link(&token).unwrap().0.id, account.id);
+
+    let token = accounts.create_magic_link("ada@example.com").unwrap();
+    accounts.data().magic_links.get_mut(&token).unwrap().expires_at = Utc::now();
+    assert!(matches!(
+        accounts.redeem_magic_link(&token),
+        Err(AccountError::InvalidLink)
+    ));
+
+    // Three links were asked for already
+    for _ in 3..MAX_ATTEMPTS {
+        accounts.create_magic_link("ada@example.com").unwrap();
+    }
+    assert!(matches!(
+        accounts.create_magic_link("ada@example.com"),
+        Err(AccountError::TooManyAttempts(_))
+    ));
+}
+
+#[test]
+fn sessions_expire() {
+    let accounts = store();
+    let (_, token) = accounts.register("alice", "long enough").unwrap();
+    let (_, other) = accounts.register("bob", "long enough").unwrap();
+    accounts.data().sessions.get_mut(&token).unwrap().expires_at = Utc::now();
+
+    assert!(accounts.authenticate(&token).is_none());
+    assert!(accounts.authenticate(&other).is_some());
+    assert_eq!(accounts.evict_expired().unwrap(), 1);
+    accounts.logout(&other).unwrap();
+    assert!(accounts.authenticate(&other).is_none());
+}
+
+#[test]
+fn each_tenant_keeps_its_own_accounts() {
+    let mut config = Config::for_tests("http://localhost:1/v1");
+    assert_eq!(AccountStore::load(&config).unwrap().path, Path::new(ACCOUNTS_FILE));
+    config.tenant = Some("class-7b".to_string());
+    assert_eq!(
+        AccountStore::load(&config).unwrap().path,
+        Path::new("data/tenants/class-7b/accounts.json")
+    );
+}
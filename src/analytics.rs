@@ -0,0 +1,128 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::events::EventBus;
+use crate::game::{ChoicePositionStats, GameState, Player};
+use crate::persistence;
+
+/// Every known player: saved players, overridden by fresher in-memory state
+pub async fn all_players(game: &RwLock<GameState>) -> Vec<Player> {
+    let mut players: HashMap<_, Player> = HashMap::new();
+
+    match persistence::list_saved_players() {
+        Ok(ids) => {
+            for id in ids {
+                match persistence::load_player(&id) {
+                    Ok(Some(player)) => {
+                        players.insert(id, player);
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Analytics skipped player {}: {}", id, e),
+                }
+            }
+        }
+        Err(e) => tracing::warn!("Analytics could not list saves: {}", e),
+    }
+
+    let game = game.read().await;
+    for (id, player) in &game.players {
+        players.insert(*id, player.clone());
+    }
+
+    players.into_values().filter(|p| game.admits(p)).collect()
+}
+
+/// Selection rate of one displayed choice position
+#[derive(Debug, Serialize)]
+pub struct PositionRate {
+    pub position: usize,
+    pub picks: u64,
+    pub offered: u64,
+    pub rate: f64,
+}
+
+/// How strongly players favor choices by where they are displayed
+#[derive(Debug, Serialize)]
+pub struct PositionBias {
+    pub shuffling_enabled: bool,
+    pub total_choices: u64,
+    pub positions: Vec<PositionRate>,
+    /// Rate each position would have if players ignored ordering entirely
+    pub expected_rate: f64,
+}
+
+pub fn position_bias(players: &[Player], shuffling_enabled: bool) -> PositionBias {
+    let mut stats = ChoicePositionStats::default();
+    for run in players.iter().flat_map(Player::all_runs) {
+        stats.merge(&run.memory.choice_positions);
+    }
+
+    let total_choices: u64 = stats.picks.iter().sum();
+    let total_offered: u64 = stats.offered.iter().sum();
+    let positions = stats
+        .picks
+        .iter()
+        .zip(&stats.offered)
+        .enumerate()
+        .map(|(position, (&picks, &offered))| PositionRate {
+            position,
+            picks,
+            offered,
+            rate: if offered == 0 {
+                0.0
+            } else {
+                picks as f64 / offered as f64
+            },
+        })
+        .collect();
+
+    PositionBias {
+        shuffling_enabled,
+        total_choices,
+        positions,
+        expected_rate: if total_offered == 0 {
+            0.0
+        } else {
+            total_choices as f64 / total_offered as f64
+        },
+    }
+}
+
+/// Running totals of published game events, by kind
+#[derive(Default)]
+pub struct EventCounters {
+    counts: std::sync::Mutex<HashMap<&'static str, u64>>,
+}
+
+#[derive(Serialize)]
+pub struct EventCount {
+    pub kind: &'static str,
+    pub count: u64,
+}
+
+impl EventCounters {
+    /// Count every event published on the bus
+    pub fn subscribe(self: &Arc<Self>, events: &EventBus) {
+        let counters = self.clone();
+        events.spawn_subscriber("event_counters", move |envelope| {
+            let counters = counters.clone();
+            async move {
+                let mut counts = counters.counts.lock().unwrap_or_else(|e| e.into_inner());
+                *counts.entry(envelope.event.kind()).or_default() += 1;
+            }
+        });
+    }
+
+    /// Snapshot of all counters, sorted by kind
+    pub fn snapshot(&self) -> Vec<EventCount> {
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let mut snapshot: Vec<EventCount> = counts
+            .iter()
+            .map(|(kind, count)| EventCount { kind, count: *count })
+            .collect();
+        snapshot.sort_by(|a, b| a.kind.cmp(b.kind));
+        snapshot
+    }
+}
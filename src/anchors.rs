@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::assets::{AssetSpec, Bundle, MomentAssets};
+use crate::game::{Choice, MomentState, NarrativeMoment, Player};
+use crate::provenance::Provenance;
+use crate::tenant::{PerTenant, TenantConfigs};
+
+/// A handwritten moment a scenario places at a fixed point of every run
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Anchor {
+    pub id: String,
+    /// Loop the anchor happens in
+    #[serde(rename = "loop")]
+    pub loop_number: u64,
+    /// Choices made in that loop before it happens; 0 opens the loop
+    #[serde(default)]
+    pub after_choices: usize,
+    pub text: String,
+    #[serde(default)]
+    pub speaker: Option<String>,
+    #[serde(default = "default_mood")]
+    pub mood: String,
+    pub choices: Vec<Choice>,
+    /// What the narrator is told has happened, in every later prompt of the run
+    pub aftermath: String,
+    /// Background, font and audio cues from the pack's assets
+    #[serde(default)]
+    pub assets: Option<MomentAssets>,
+}
+
+fn default_mood() -> String {
+    "neutral".to_string()
+}
+
+impl Anchor {
+    /// The anchor as a fresh moment, ready to be presented
+    pub fn moment(&self) -> NarrativeMoment {
+        NarrativeMoment {
+            id: Uuid::new_v4(),
+            text: self.text.clone(),
+            speaker: self.speaker.clone(),
+            mood: self.mood.clone(),
+            choices: self.choices.clone(),
+            timestamp: Utc::now(),
+            summarized: false,
+            world_updates: Vec::new(),
+            state: MomentState::Generated,
+            translation: None,
+            deja_vu: None,
+            fate: None,
+            assets: self.assets.clone(),
+            provenance: Some(Provenance::Anchor {
+                anchor: self.id.clone(),
+            }),
+        }
+    }
+
+    fn is_due(&self, player: &Player) -> bool {
+        let current = &player.run.current_loop;
+        current.number == self.loop_number
+            && current.choices_made.len() == self.after_choices
+            && !player.run.anchors.iter().any(|a| a.id == self.id)
+    }
+}
+
+/// An anchor a run has been through, with the aftermath it left
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReachedAnchor {
+    pub id: String,
+    pub loop_number: u64,
+    pub aftermath: String,
+}
+
+/// Anchors from a scenario file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnchorPack {
+    /// Names the scenario in asset URLs; the file name without its extension
+    /// when left out
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    anchors: Vec<Anchor>,
+    /// The pack's asset manifest, by asset id
+    #[serde(default)]
+    assets: BTreeMap<String, AssetSpec>,
+}
+
+/// A tenant's scenario: its anchors and the assets they are staged with
+#[derive(Default)]
+struct Scenario {
+    anchors: Vec<Anchor>,
+    bundle: Option<Bundle>,
+}
+
+static SCENARIOS: OnceLock<PerTenant<Scenario>> = OnceLock::new();
+
+fn scenario(tenant: Option<&str>) -> &'static Scenario {
+    SCENARIOS
+        .get_or_init(|| PerTenant::only(Scenario::default()))
+        .get(tenant)
+}
+
+/// The scenario anchors of the player's tenant
+fn anchors(player: &Player) -> &'static [Anchor] {
+    &scenario(player.tenant.as_deref()).anchors
+}
+
+/// The tenant's scenario assets, if its pack ships any
+pub fn bundle(tenant: Option<&str>) -> Option<&'static Bundle> {
+    scenario(tenant).bundle.as_ref()
+}
+
+fn load(path: &str) -> Result<Scenario> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read anchors {}", path))?;
+    let pack: AnchorPack =
+        serde_json::from_str(&text).with_context(|| format!("invalid anchors {}", path))?;
+    validate(&pack.anchors).with_context(|| format!("invalid anchors {}", path))?;
+    let bundle = if pack.assets.is_empty() {
+        None
+    } else {
+        let file = Path::new(path);
+        let id = match &pack.id {
+            Some(id) => id.clone(),
+            None => file.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        };
+        let dir = file.parent().unwrap_or(Path::new("."));
+        Some(Bundle::load(&id, dir, pack.assets).with_context(|| format!("invalid assets {}", path))?)
+    };
+    stage(&pack.anchors, bundle.as_ref()).with_context(|| format!("invalid anchors {}", path))?;
+    tracing::info!(
+        "Using {} anchors and {} assets from {}",
+        pack.anchors.len(),
+        bundle.as_ref().map_or(0, |b| b.assets.len()),
+        pack.name.as_deref().unwrap_or(path)
+    );
+    Ok(Scenario {
+        anchors: pack.anchors,
+        bundle,
+    })
+}
+
+/// Every asset an anchor is staged with must be in the pack
+fn stage(anchors: &[Anchor], bundle: Option<&Bundle>) -> Result<()> {
+    for anchor in anchors {
+        let Some(staging) = &anchor.assets else {
+            continue;
+        };
+        match bundle {
+            Some(bundle) => bundle
+                .check(staging)
+                .with_context(|| format!("anchor '{}'", anchor.id))?,
+            None => anyhow::bail!("anchor '{}' uses assets, but the pack lists none", anchor.id),
+        }
+    }
+    Ok(())
+}
+
+/// Anchors need unique ids, a place of their own, text and a way forward
+fn validate(anchors: &[Anchor]) -> Result<()> {
+    let mut ids = HashSet::new();
+    let mut places = HashSet::new();
+    for anchor in anchors {
+        if !ids.insert(anchor.id.as_str()) {
+            anyhow::bail!("anchor '{}' appears twice", anchor.id);
+        }
+        if anchor.loop_number == 0 {
+            anyhow::bail!("anchor '{}' is in loop 0; loops start at 1", anchor.id);
+        }
+        if !places.insert((anchor.loop_number, anchor.after_choices)) {
+            anyhow::bail!(
+                "anchor '{}' is at the same point as another anchor (loop {}, after {} choices)",
+                anchor.id,
+                anchor.loop_number,
+                anchor.after_choices
+            );
+        }
+        if anchor.text.trim().is_empty() || anchor.aftermath.trim().is_empty() {
+            anyhow::bail!("anchor '{}' needs a text and an aftermath", anchor.id);
+        }
+        if anchor.choices.is_empty() {
+            anyhow::bail!("anchor '{}' has no choices", anchor.id);
+        }
+        let mut choice_ids = HashSet::new();
+        if let Some(choice) = anchor.choices.iter().find(|c| !choice_ids.insert(&c.id)) {
+            anyhow::bail!("anchor '{}' repeats choice id '{}'", anchor.id, choice.id);
+        }
+    }
+    Ok(())
+}
+
+/// Load each tenant's anchors from `SCENARIO_ANCHORS`. Must be called once at
+/// startup, so a bad anchor stops the server there.
+pub fn init(tenants: &TenantConfigs) -> Result<()> {
+    let scenarios = PerTenant::load(tenants, |config| match &config.scenario_anchors {
+        Some(path) => load(path),
+        None => Ok(Scenario::default()),
+    })?;
+    if SCENARIOS.set(scenarios).is_err() {
+        anyhow::bail!("anchors already initialized");
+    }
+    Ok(())
+}
+
+/// The anchor that takes the place of the player's next moment, if one is due
+pub fn due(player: &Player) -> Option<&'static Anchor> {
+    due_in(anchors(player), player)
+}
+
+fn due_in<'a>(anchors: &'a [Anchor], player: &Player) -> Option<&'a Anchor> {
+    anchors.iter().find(|anchor| anchor.is_due(player))
+}
+
+/// Note that the player's run went through an anchor
+pub fn record(player: &mut Player, anchor: &Anchor) {
+    if player.run.anchors.iter().any(|a| a.id == anchor.id) {
+        return;
+    }
+    player.run.anchors.push(ReachedAnchor {
+        id: anchor.id.clone(),
+        loop_number: player.run.current_loop.number,
+        aftermath: anchor.aftermath.clone(),
+    });
+}
+
+#[cfg(test)]
+mod tests;
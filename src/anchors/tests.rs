@@ -0,0 +1,65 @@
+use super::*;
+use crate::context::ContextProfile;
+use crate::testing::PlayerBuilder;
+
+fn anchor(id: &str, loop_number: u64, after_choices: usize) -> Anchor {
+    serde_json::from_value(serde_json::json!({
+        "id": id,
+        "loop": loop_number,
+        "after_choices": after_choices,
+        "text": "The door is open this time.",
+        "choices": [{ "id": "enter", "text": "Step through" }],
+        "aftermath": "The player saw the door open in loop 3.",
+    }))
+    .unwrap()
+}
+
+#[test]
+fn anchor_is_due_at_its_loop_and_choice_count() {
+    let anchors = [anchor("door", 3, 0), anchor("voice", 3, 2)];
+
+    let opening = PlayerBuilder::new().loops(2).build();
+    assert_eq!(due_in(&anchors, &opening).map(|a| a.id.as_str()), Some("door"));
+
+    let later = PlayerBuilder::new().loops(2).choices_made(["a", "b"]).build();
+    assert_eq!(due_in(&anchors, &later).map(|a| a.id.as_str()), Some("voice"));
+
+    let elsewhere = PlayerBuilder::new().loops(3).build();
+    assert!(due_in(&anchors, &elsewhere).is_none());
+}
+
+#[test]
+fn anchor_happens_once_per_run() {
+    let anchors = [anchor("door", 3, 0)];
+    let mut player = PlayerBuilder::new().loops(2).build();
+
+    record(&mut player, &anchors[0]);
+    record(&mut player, &anchors[0]);
+
+    assert!(due_in(&anchors, &player).is_none());
+    assert_eq!(
+        player.run.anchors,
+        vec![ReachedAnchor {
+            id: "door".to_string(),
+            loop_number: 3,
+            aftermath: "The player saw the door open in loop 3.".to_string(),
+        }]
+    );
+    assert!(
+        player
+            .get_narrative_context(&ContextProfile::default())
+            .contains("saw the door open")
+    );
+}
+
+#[test]
+fn anchors_sharing_a_place_are_refused() {
+    assert!(validate(&[anchor("door", 3, 0), anchor("voice", 7, 0)]).is_ok());
+    assert!(validate(&[anchor("door", 3, 0), anchor("door", 7, 0)]).is_err());
+    assert!(validate(&[anchor("door", 3, 0), anchor("voice", 3, 0)]).is_err());
+    assert!(validate(&[anchor("door", 0, 0)]).is_err());
+
+    let mut silent = anchor("door", 3, 0);
+    silent.choices.clear();
+    assert!(validate(&[silent]).is_err());
+}
@@ -0,0 +1,245 @@
+//! Static assets shipped with a scenario pack: backgrounds, music and fonts.
+//!
+//! The pack's manifest lists each asset by id with its path, relative to the
+//! pack file, and its SHA-256. Every file is checked against its hash at
+//! startup and then held in memory, so what is served under
+//! `/assets/{scenario}/{path}` always matches the manifest. Anchor moments
+//! refer to assets by id.
+
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::tenant;
+
+/// An asset as the pack's manifest lists it
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AssetSpec {
+    /// Relative to the directory of the pack file
+    pub path: String,
+    /// Lowercase hex digest of the file
+    pub sha256: String,
+}
+
+/// What an asset is for, told by its file extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetKind {
+    Image,
+    Audio,
+    Font,
+}
+
+impl AssetKind {
+    /// Kind and content type of a file name, if it is one we serve
+    fn of(path: &str) -> Option<(Self, &'static str)> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        Some(match extension.as_str() {
+            "png" => (AssetKind::Image, "image/png"),
+            "jpg" | "jpeg" => (AssetKind::Image, "image/jpeg"),
+            "webp" => (AssetKind::Image, "image/webp"),
+            "gif" => (AssetKind::Image, "image/gif"),
+            "svg" => (AssetKind::Image, "image/svg+xml"),
+            "mp3" => (AssetKind::Audio, "audio/mpeg"),
+            "ogg" | "oga" => (AssetKind::Audio, "audio/ogg"),
+            "opus" => (AssetKind::Audio, "audio/opus"),
+            "wav" => (AssetKind::Audio, "audio/wav"),
+            "m4a" => (AssetKind::Audio, "audio/mp4"),
+            "woff2" => (AssetKind::Font, "font/woff2"),
+            "woff" => (AssetKind::Font, "font/woff"),
+            "ttf" => (AssetKind::Font, "font/ttf"),
+            "otf" => (AssetKind::Font, "font/otf"),
+            _ => return None,
+        })
+    }
+}
+
+/// A verified asset, ready to serve
+#[derive(Debug)]
+pub struct Asset {
+    pub path: String,
+    pub kind: AssetKind,
+    pub content_type: &'static str,
+    pub sha256: String,
+    pub bytes: Bytes,
+}
+
+/// A sound an anchor moment plays
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AudioCue {
+    /// Id of an audio asset
+    pub asset: String,
+    /// Keep playing until the next cue
+    #[serde(default, rename = "loop")]
+    pub repeat: bool,
+    /// From 0 to 1; the client's own volume when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+}
+
+/// Assets an anchor moment is staged with, by id
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MomentAssets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audio: Vec<AudioCue>,
+}
+
+/// A scenario's assets, by id
+#[derive(Debug)]
+pub struct Bundle {
+    /// Names the scenario in asset URLs
+    pub scenario: String,
+    pub assets: BTreeMap<String, Asset>,
+}
+
+impl Bundle {
+    /// Read and verify every asset listed, from paths relative to `dir`
+    pub fn load(scenario: &str, dir: &Path, specs: BTreeMap<String, AssetSpec>) -> Result<Self> {
+        if !tenant::valid_id(scenario) {
+            anyhow::bail!(
+                "scenario id '{}' must be lowercase letters, digits, '-' or '_'",
+                scenario
+            );
+        }
+        let mut assets = BTreeMap::new();
+        for (id, spec) in specs {
+            let asset = load_asset(dir, spec).with_context(|| format!("asset '{}'", id))?;
+            assets.insert(id, asset);
+        }
+        Ok(Self {
+            scenario: scenario.to_string(),
+            assets,
+        })
+    }
+
+    /// The asset served at `path`, as its manifest names it
+    pub fn by_path(&self, path: &str) -> Option<&Asset> {
+        self.assets.values().find(|asset| asset.path == path)
+    }
+
+    /// Staging must name assets of the pack, each of the right kind
+    pub fn check(&self, staging: &MomentAssets) -> Result<()> {
+        let cues = staging
+            .audio
+            .iter()
+            .map(|cue| (&cue.asset, AssetKind::Audio));
+        let references = staging
+            .background
+            .iter()
+            .map(|id| (id, AssetKind::Image))
+            .chain(staging.font.iter().map(|id| (id, AssetKind::Font)))
+            .chain(cues);
+        for (id, kind) in references {
+            match self.assets.get(id) {
+                None => anyhow::bail!("no asset '{}' in the pack", id),
+                Some(asset) if asset.kind != kind => {
+                    anyhow::bail!("asset '{}' is {:?}, not {:?}", id, asset.kind, kind)
+                }
+                Some(_) => {}
+            }
+        }
+        if let Some(cue) = staging
+            .audio
+            .iter()
+            .find(|cue| cue.volume.is_some_and(|v| !(0.0..=1.0).contains(&v)))
+        {
+            anyhow::bail!("audio cue '{}' has a volume outside 0 to 1", cue.asset);
+        }
+        Ok(())
+    }
+}
+
+fn load_asset(dir: &Path, spec: AssetSpec) -> Result<Asset> {
+    let relative = Path::new(&spec.path);
+    if spec.path.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        anyhow::bail!("path '{}' must stay inside the pack's directory", spec.path);
+    }
+    let (kind, content_type) = AssetKind::of(&spec.path)
+        .with_context(|| format!("'{}' is not an image, audio or font file", spec.path))?;
+    let file = dir.join(relative);
+    let bytes = fs::read(&file).with_context(|| format!("failed to read {}", file.display()))?;
+    let sha256 = hex(&Sha256::digest(&bytes));
+    if !sha256.eq_ignore_ascii_case(spec.sha256.trim()) {
+        anyhow::bail!(
+            "{} has SHA-256 {}, but the manifest lists {}",
+            file.display(),
+            sha256,
+            spec.sha256
+        );
+    }
+    Ok(Asset {
+        path: spec.path,
+        kind,
+        content_type,
+        sha256,
+        bytes: Bytes::from(bytes),
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Where an asset is served, under the tenant's prefix
+pub fn url(tenant: Option<&str>, scenario: &str, path: &str) -> String {
+    match tenant {
+        Some(id) => format!("/t/{}/assets/{}/{}", id, scenario, path),
+        None => format!("/assets/{}/{}", scenario, path),
+    }
+}
+
+/// One asset in the manifest clients read
+#[derive(Debug, Serialize)]
+pub struct ManifestEntry {
+    pub id: String,
+    pub url: String,
+    pub kind: AssetKind,
+    pub content_type: &'static str,
+    pub sha256: String,
+    pub size: usize,
+}
+
+/// A scenario's assets as clients see them, to preload the whole pack
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub scenario: String,
+    pub assets: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn new(bundle: &Bundle, tenant: Option<&str>) -> Self {
+        Self {
+            scenario: bundle.scenario.clone(),
+            assets: bundle
+                .assets
+                .iter()
+                .map(|(id, asset)| ManifestEntry {
+                    id: id.clone(),
+                    url: url(tenant, &bundle.scenario, &asset.path),
+                    kind: asset.kind,
+                    content_type: asset.content_type,
+                    sha256: asset.sha256.clone(),
+                    size: asset.bytes.len(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,82 @@
+use super::*;
+use serde_json::json;
+use std::path::PathBuf;
+
+/// A pack directory holding `files`, named by a fresh id
+fn pack_dir(files: &[(&str, &[u8])]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nihilism-assets-{}", uuid::Uuid::new_v4()));
+    for (name, bytes) in files {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, bytes).unwrap();
+    }
+    dir
+}
+
+fn spec(path: &str, bytes: &[u8]) -> AssetSpec {
+    AssetSpec {
+        path: path.to_string(),
+        sha256: hex(&Sha256::digest(bytes)),
+    }
+}
+
+#[test]
+fn assets_are_verified_against_the_manifest() {
+    let dir = pack_dir(&[("img/door.png", b"door"), ("rain.ogg", b"rain")]);
+    let specs = BTreeMap::from([
+        ("door".to_string(), spec("img/door.png", b"door")),
+        ("rain".to_string(), spec("rain.ogg", b"rain")),
+    ]);
+    let bundle = Bundle::load("the-door", &dir, specs).unwrap();
+    let door = bundle.by_path("img/door.png").unwrap();
+    assert_eq!(door.content_type, "image/png");
+    assert_eq!(&door.bytes[..], b"door");
+
+    let manifest = Manifest::new(&bundle, Some("school"));
+    assert_eq!(
+        manifest.assets[0].url,
+        "/t/school/assets/the-door/img/door.png"
+    );
+    assert_eq!(manifest.assets[1].kind, AssetKind::Audio);
+
+    let tampered = BTreeMap::from([("door".to_string(), spec("img/door.png", b"window"))]);
+    assert!(Bundle::load("the-door", &dir, tampered).is_err());
+    let outside = BTreeMap::from([("door".to_string(), spec("../door.png", b"door"))]);
+    assert!(Bundle::load("the-door", &dir, outside).is_err());
+    assert!(Bundle::load("The Door", &dir, BTreeMap::new()).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn staging_names_assets_of_the_right_kind() {
+    let dir = pack_dir(&[("door.png", b"door"), ("rain.ogg", b"rain")]);
+    let specs = BTreeMap::from([
+        ("door".to_string(), spec("door.png", b"door")),
+        ("rain".to_string(), spec("rain.ogg", b"rain")),
+    ]);
+    let bundle = Bundle::load("the-door", &dir, specs).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+
+    let staging = |value| serde_json::from_value::<MomentAssets>(value).unwrap();
+    let good = staging(json!({
+        "background": "door",
+        "audio": [{ "asset": "rain", "loop": true, "volume": 0.4 }],
+    }));
+    assert!(bundle.check(&good).is_ok());
+    assert!(good.audio[0].repeat);
+
+    assert!(
+        bundle
+            .check(&staging(json!({ "background": "rain" })))
+            .is_err()
+    );
+    assert!(bundle.check(&staging(json!({ "font": "serif" }))).is_err());
+    assert!(
+        bundle
+            .check(&staging(
+                json!({ "audio": [{ "asset": "rain", "volume": 2.0 }] })
+            ))
+            .is_err()
+    );
+}
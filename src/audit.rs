@@ -0,0 +1,92 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::game::NarrativeMoment;
+
+const AUDIT_FILE: &str = "data/audit.jsonl";
+
+/// What an admin did to a moment
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MomentAction {
+    /// Text or choices replaced by hand
+    Edit,
+    /// Generated again by the narrator
+    Regenerate,
+    /// Removed from the record; the latest moment is regenerated instead
+    Strike,
+}
+
+/// Who handed a run to another model
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchedBy {
+    Admin,
+    /// The month's spend neared the budget
+    Budget,
+}
+
+/// A run handed from one model to another mid-run
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModelTransition {
+    pub from: String,
+    pub to: String,
+    pub by: SwitchedBy,
+}
+
+/// What was changed about a player
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Change {
+    /// A moment, keeping the original
+    Moment {
+        action: MomentAction,
+        original: Box<NarrativeMoment>,
+        replacement: Box<NarrativeMoment>,
+    },
+    /// The model narrating the run
+    Model { model: ModelTransition },
+}
+
+/// One change to a player's run made by an admin, or on the budget's behalf
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub player_id: Uuid,
+    #[serde(flatten)]
+    pub change: Change,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Append an entry to `data/audit.jsonl`
+pub fn record(entry: &AuditEntry) -> Result<()> {
+    if let Some(dir) = Path::new(AUDIT_FILE).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(AUDIT_FILE)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// Logged changes, newest first, optionally for one player only
+pub fn entries(player_id: Option<Uuid>, limit: usize) -> Result<Vec<AuditEntry>> {
+    if !Path::new(AUDIT_FILE).exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(AUDIT_FILE)?
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+        .filter(|entry| player_id.is_none_or(|id| entry.player_id == id))
+        .take(limit)
+        .collect())
+}
@@ -0,0 +1,281 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::build_info::{self, BuildInfo};
+use crate::config::Config;
+use crate::consequences::{self, ChoiceRecord};
+use crate::game::{ArchivedLoop, Player};
+use crate::persistence;
+use crate::redaction::{self, Kind, Manifest};
+
+/// Generated on first use when `BACKUP_SECRET` is not set
+const KEY_FILE: &str = "data/backup.key";
+const MAGIC: &[u8; 4] = b"NHBK";
+const VERSION: u8 = 1;
+const TAG_LEN: usize = 32;
+/// Largest backup accepted for import, compressed
+pub const MAX_BACKUP_BYTES: usize = 16 * 1024 * 1024;
+/// Largest a backup may grow to when decompressed
+const MAX_EXPANDED_BYTES: u64 = 128 * 1024 * 1024;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a backup was refused
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("not a nihilism backup")]
+    NotABackup,
+    #[error("backup version {0} is not supported")]
+    UnsupportedVersion(u8),
+    #[error("backup signature does not match")]
+    BadSignature,
+    #[error("backup is too large")]
+    TooLarge,
+    #[error("invalid backup: {0}")]
+    Invalid(String),
+}
+
+/// Everything needed to carry a player to another server
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Backup {
+    pub exported_at: DateTime<Utc>,
+    /// Server build that exported the backup; absent from older backups
+    #[serde(default)]
+    pub exported_by: Option<BuildInfo>,
+    /// The player with every run, the full narrative history included
+    pub player: Player,
+    /// Archived loops of all the player's runs
+    pub archives: Vec<ArchivedLoop>,
+    /// Choice logs behind the ending ledger, by run
+    #[serde(default)]
+    pub choice_logs: HashMap<Uuid, Vec<ChoiceRecord>>,
+    /// What the player kept out of the backup; absent from older backups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub withheld: Option<Manifest>,
+}
+
+impl Backup {
+    /// Collect a player's runs and archives, restoring spilled history
+    pub fn collect(player: &Player) -> Result<Self> {
+        let mut player = player.clone();
+        let id = player.id;
+        let mut archives = Vec::new();
+        let mut choice_logs = HashMap::new();
+        for run in std::iter::once(&mut player.run).chain(player.runs.iter_mut()) {
+            let run_id = run.run_id.unwrap_or(id);
+            persistence::restore_spilled(&run_id, run.current_loop.number, &mut run.narrative_history)?;
+            archives.extend(persistence::load_archived_loops(&run_id)?);
+            choice_logs.insert(run_id, consequences::choice_log(&run_id)?);
+        }
+        Ok(Self {
+            exported_at: Utc::now(),
+            exported_by: Some(build_info::current().clone()),
+            player,
+            archives,
+            choice_logs,
+            withheld: None,
+        })
+    }
+
+    /// Leave out what the player keeps out of their exports, recording what
+    /// was left out in the backup
+    pub fn redact(&mut self) {
+        let manifest = redaction::redact(Kind::Backup, &mut self.player, &self.archives, &mut self.choice_logs);
+        self.withheld = Some(manifest);
+    }
+
+    /// Check the backup holds together: archives belong to the player's runs
+    /// and come before the loop each run is in
+    fn validate(&self) -> Result<(), BackupError> {
+        let player = &self.player;
+        let mut runs = HashMap::new();
+        for run in player.all_runs() {
+            let run_id = run.run_id.unwrap_or(player.id);
+            if runs.insert(run_id, run.current_loop.number).is_some() {
+                return Err(BackupError::Invalid(format!("run {} appears twice", run_id)));
+            }
+        }
+        let mut seen = HashSet::new();
+        for archived in &self.archives {
+            let number = archived.loop_info.number;
+            let Some(current) = runs.get(&archived.player_id) else {
+                return Err(BackupError::Invalid(format!(
+                    "loop {} belongs to an unknown run",
+                    number
+                )));
+            };
+            if number >= *current || !seen.insert((archived.player_id, number)) {
+                return Err(BackupError::Invalid(format!(
+                    "loop {} of run {} is out of place",
+                    number, archived.player_id
+                )));
+            }
+        }
+        if let Some(run_id) = self.choice_logs.keys().find(|id| !runs.contains_key(id)) {
+            return Err(BackupError::Invalid(format!(
+                "choice log of unknown run {}",
+                run_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Give the player and each of its runs fresh ids, so a restored backup
+    /// never collides with the original
+    pub fn reassign_ids(mut self) -> Self {
+        let old_id = self.player.id;
+        self.player.id = Uuid::new_v4();
+        let mut renamed = HashMap::new();
+        renamed.insert(old_id, self.player.id);
+        for run in std::iter::once(&mut self.player.run).chain(self.player.runs.iter_mut()) {
+            match run.run_id {
+                // The first run shares the player's id
+                None => {}
+                Some(run_id) if run_id == old_id => run.run_id = None,
+                Some(run_id) => {
+                    let fresh = Uuid::new_v4();
+                    renamed.insert(run_id, fresh);
+                    run.run_id = Some(fresh);
+                }
+            }
+        }
+        for archived in &mut self.archives {
+            if let Some(id) = renamed.get(&archived.player_id) {
+                archived.player_id = *id;
+            }
+        }
+        self.choice_logs = self
+            .choice_logs
+            .into_iter()
+            .map(|(run_id, log)| (renamed.get(&run_id).copied().unwrap_or(run_id), log))
+            .collect();
+        self
+    }
+}
+
+static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Load the signing key: `BACKUP_SECRET`, or a key generated for this server.
+/// Must be called once at startup.
+pub fn init(config: &Config) -> Result<()> {
+    let key = match &config.backup_secret {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => load_or_create_key(KEY_FILE)?,
+    };
+    if KEY.set(key).is_err() {
+        anyhow::bail!("backup key already initialized");
+    }
+    Ok(())
+}
+
+/// Read a signing key from `file`, generating one there on first use
+pub fn load_or_create_key(file: &str) -> Result<Vec<u8>> {
+    let path = Path::new(file);
+    if path.exists() {
+        return fs::read(path).with_context(|| format!("failed to read {}", file));
+    }
+    let key: [u8; 32] = rand::random();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, key).with_context(|| format!("failed to write {}", file))?;
+    tracing::info!("Generated a signing key in {}", file);
+    Ok(key.to_vec())
+}
+
+fn key() -> &'static [u8] {
+    KEY.get().expect("backup key not initialized")
+}
+
+fn mac(key: &[u8]) -> HmacSha256 {
+    HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+/// Compress and sign a backup: magic, version, HMAC-SHA256 of the body, then
+/// the gzipped JSON body
+pub fn seal(backup: &Backup) -> Result<Vec<u8>> {
+    seal_with(key(), backup)
+}
+
+/// Verify a sealed backup's signature, then decompress and validate it
+pub fn open(blob: &[u8]) -> Result<Backup, BackupError> {
+    open_with(key(), blob)
+}
+
+fn seal_with(key: &[u8], backup: &Backup) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, backup)?;
+    let body = encoder.finish()?;
+
+    let mut mac = mac(key);
+    mac.update(&[VERSION]);
+    mac.update(&body);
+    let tag = mac.finalize().into_bytes();
+
+    let mut blob = Vec::with_capacity(MAGIC.len() + 1 + TAG_LEN + body.len());
+    blob.extend_from_slice(MAGIC);
+    blob.push(VERSION);
+    blob.extend_from_slice(&tag);
+    blob.extend_from_slice(&body);
+    Ok(blob)
+}
+
+fn open_with(key: &[u8], blob: &[u8]) -> Result<Backup, BackupError> {
+    if blob.len() > MAX_BACKUP_BYTES {
+        return Err(BackupError::TooLarge);
+    }
+    let rest = blob.strip_prefix(MAGIC).ok_or(BackupError::NotABackup)?;
+    let (&version, rest) = rest.split_first().ok_or(BackupError::NotABackup)?;
+    if version != VERSION {
+        return Err(BackupError::UnsupportedVersion(version));
+    }
+    if rest.len() < TAG_LEN {
+        return Err(BackupError::NotABackup);
+    }
+    let (tag, body) = rest.split_at(TAG_LEN);
+
+    // Checked before decompressing, so only our own backups are ever parsed
+    let mut mac = mac(key);
+    mac.update(&[version]);
+    mac.update(body);
+    mac.verify_slice(tag).map_err(|_| BackupError::BadSignature)?;
+
+    let mut json = Vec::new();
+    GzDecoder::new(body)
+        .take(MAX_EXPANDED_BYTES + 1)
+        .read_to_end(&mut json)
+        .map_err(|e| BackupError::Invalid(e.to_string()))?;
+    if json.len() as u64 > MAX_EXPANDED_BYTES {
+        return Err(BackupError::TooLarge);
+    }
+    let backup: Backup =
+        serde_json::from_slice(&json).map_err(|e| BackupError::Invalid(e.to_string()))?;
+    backup.validate()?;
+    Ok(backup)
+}
+
+/// Write a restored player's archived loops and choice logs to disk
+pub fn restore_files(backup: &Backup) -> Result<()> {
+    for archived in &backup.archives {
+        persistence::archive_loop(archived)?;
+    }
+    for (run_id, log) in &backup.choice_logs {
+        consequences::restore_choice_log(run_id, log)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
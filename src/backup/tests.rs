@@ -0,0 +1,113 @@
+use super::*;
+
+use crate::testing::{self, PlayerBuilder};
+
+const KEY: &[u8] = b"test backup key";
+
+/// A player in loop 3 of a second run, with its first run finished
+fn backup() -> Backup {
+    let mut player = PlayerBuilder::new().loops(4).dark(3).light(2).build();
+    let mut finished = player.run.clone();
+    finished.run_id = Some(player.id);
+    player.run.run_id = Some(Uuid::new_v4());
+    player.run.current_loop.number = 3;
+    player.runs.push(finished);
+
+    let archives = vec![
+        testing::archived_loop(&player, 1, "reset"),
+        testing::archived_loop(&player, 2, "reset"),
+    ];
+    let record = ChoiceRecord {
+        loop_number: 1,
+        choice_id: "open".to_string(),
+        choice_text: "Open the door".to_string(),
+        is_dark: false,
+        score_delta: -1,
+        nihilism_score: None,
+        at: Utc::now(),
+        provenance: None,
+    };
+    Backup {
+        exported_at: Utc::now(),
+        exported_by: None,
+        choice_logs: HashMap::from([(player.run_id(), vec![record])]),
+        player,
+        archives,
+        withheld: None,
+    }
+}
+
+fn invalid(backup: &Backup) -> bool {
+    let blob = seal_with(KEY, backup).unwrap();
+    matches!(open_with(KEY, &blob), Err(BackupError::Invalid(_)))
+}
+
+#[test]
+fn sealed_backups_open_only_untouched_and_under_their_key() {
+    let original = backup();
+    let blob = seal_with(KEY, &original).unwrap();
+    let opened = open_with(KEY, &blob).unwrap();
+    assert_eq!(opened.player.id, original.player.id);
+    assert_eq!(opened.archives.len(), 2);
+    assert_eq!(opened.choice_logs[&original.player.run_id()].len(), 1);
+
+    let mut tampered = blob.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(matches!(open_with(KEY, &tampered), Err(BackupError::BadSignature)));
+    assert!(matches!(open_with(b"another key", &blob), Err(BackupError::BadSignature)));
+
+    let mut future = blob.clone();
+    future[MAGIC.len()] = VERSION + 1;
+    assert!(matches!(
+        open_with(KEY, &future),
+        Err(BackupError::UnsupportedVersion(v)) if v == VERSION + 1
+    ));
+    assert!(matches!(open_with(KEY, b"PK\x03\x04"), Err(BackupError::NotABackup)));
+    assert!(matches!(open_with(KEY, &blob[..MAGIC.len() + 8]), Err(BackupError::NotABackup)));
+    let oversize = vec![0; MAX_BACKUP_BYTES + 1];
+    assert!(matches!(open_with(KEY, &oversize), Err(BackupError::TooLarge)));
+}
+
+#[test]
+fn backups_that_dont_hold_together_are_refused() {
+    let mut duplicate = backup();
+    let again = duplicate.player.runs[0].clone();
+    duplicate.player.runs.push(again);
+    assert!(invalid(&duplicate));
+
+    let mut foreign = backup();
+    let stranger = PlayerBuilder::new().build();
+    foreign.archives.push(testing::archived_loop(&stranger, 1, "reset"));
+    assert!(invalid(&foreign));
+
+    // The run is in loop 3, so loop 3 can't be archived yet
+    let mut early = backup();
+    let current = early.player.clone();
+    early.archives.push(testing::archived_loop(&current, 3, "reset"));
+    assert!(invalid(&early));
+
+    let mut repeated = backup();
+    let first = repeated.archives[0].clone();
+    repeated.archives.push(first);
+    assert!(invalid(&repeated));
+
+    let mut unknown_log = backup();
+    unknown_log.choice_logs.insert(Uuid::new_v4(), Vec::new());
+    assert!(invalid(&unknown_log));
+}
+
+#[test]
+fn reassigned_ids_stay_consistent() {
+    let original = backup();
+    let (old_player, old_run) = (original.player.id, original.player.run_id());
+    let restored = original.reassign_ids();
+
+    let player = &restored.player;
+    assert_ne!(player.id, old_player);
+    assert_eq!(player.runs[0].run_id, None);
+    let run_id = player.run_id();
+    assert!(run_id != old_run && run_id != player.id);
+    assert!(restored.archives.iter().all(|a| a.player_id == run_id));
+    assert_eq!(restored.choice_logs.keys().collect::<Vec<_>>(), [&run_id]);
+    restored.validate().unwrap();
+}
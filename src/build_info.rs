@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// The server build that produced a response, save or export, captured by
+/// `build.rs` at compile time
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    /// Commit the server was built from; `None` outside a git checkout
+    #[serde(default)]
+    pub git_sha: Option<String>,
+    /// Uncommitted changes to tracked files at build time
+    #[serde(default)]
+    pub dirty: bool,
+    /// `SOURCE_DATE_EPOCH` when set, so reproducible builds agree
+    #[serde(default)]
+    pub built_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub rustc: Option<String>,
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+impl BuildInfo {
+    /// Short form for logs and export headers, e.g. `0.1.0+3f2a9c1-dirty`
+    pub fn describe(&self) -> String {
+        let mut out = self.version.clone();
+        if let Some(sha) = &self.git_sha {
+            out.push('+');
+            out.push_str(&sha[..sha.len().min(7)]);
+            if self.dirty {
+                out.push_str("-dirty");
+            }
+        }
+        out
+    }
+}
+
+/// Metadata of the running build
+pub fn current() -> &'static BuildInfo {
+    static CURRENT: OnceLock<BuildInfo> = OnceLock::new();
+    CURRENT.get_or_init(|| {
+        let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_sha: non_empty(env!("NIHILISM_GIT_SHA")),
+            dirty: env!("NIHILISM_GIT_DIRTY") == "true",
+            built_at: env!("NIHILISM_BUILD_TIME")
+                .parse()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0)),
+            rustc: non_empty(env!("NIHILISM_RUSTC_VERSION")),
+            features: env!("NIHILISM_FEATURES")
+                .split(',')
+                .filter(|f| !f.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    })
+}
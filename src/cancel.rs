@@ -0,0 +1,50 @@
+//! Cancellation of work started for a client that has gone away.
+//!
+//! A handler's future is dropped when its client disconnects, which aborts
+//! the LLM call it is awaiting. Work it spawns in the background runs on
+//! its own, so it is tied to the request's cancellation token instead and
+//! stopped when the client hangs up before the response is ready, or when a
+//! WebSocket session closes.
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use std::future::Future;
+use tokio_util::sync::CancellationToken;
+
+tokio::task_local! {
+    static REQUEST: CancellationToken;
+}
+
+/// Middleware giving each HTTP request a token, cancelled if the request is
+/// dropped before its response is ready
+pub async fn track(request: Request, next: Next) -> Response {
+    let token = CancellationToken::new();
+    let hangup = token.clone().drop_guard();
+    let response = REQUEST.scope(token, next.run(request)).await;
+    hangup.disarm();
+    response
+}
+
+/// Run `work` on behalf of the client owning `token`
+pub async fn scope<F: Future>(token: CancellationToken, work: F) -> F::Output {
+    REQUEST.scope(token, work).await
+}
+
+/// Spawn background work that stops when the current client goes away.
+/// Outside any request it simply runs to completion.
+pub fn spawn(work: impl Future<Output = ()> + Send + 'static) {
+    let token = REQUEST.try_with(CancellationToken::child_token).ok();
+    tokio::spawn(async move {
+        match token {
+            Some(token) => {
+                tokio::select! {
+                    _ = token.cancelled() => tracing::debug!("Background work cancelled by hangup"),
+                    _ = work => {}
+                }
+            }
+            None => work.await,
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,38 @@
+use super::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Background work that flags `done` once it gets to finish
+fn slow_work(done: &Arc<AtomicBool>) -> impl Future<Output = ()> + Send + 'static {
+    let done = done.clone();
+    async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        done.store(true, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn background_work_stops_when_its_client_goes_away() {
+    let token = CancellationToken::new();
+    let done = Arc::new(AtomicBool::new(false));
+    scope(token.clone(), async { spawn(slow_work(&done)) }).await;
+    token.cancel();
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!done.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn background_work_outlives_a_finished_request() {
+    let token = CancellationToken::new();
+    let answered = Arc::new(AtomicBool::new(false));
+    scope(token.clone(), async { spawn(slow_work(&answered)) }).await;
+
+    let unscoped = Arc::new(AtomicBool::new(false));
+    spawn(slow_work(&unscoped));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(answered.load(Ordering::SeqCst));
+    assert!(unscoped.load(Ordering::SeqCst));
+}
@@ -0,0 +1,240 @@
+use anyhow::{Context, Result};
+use resvg::{tiny_skia, usvg};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::Config;
+
+/// Open Graph image size, so cards preview well wherever they are posted
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const PADDING: u32 = 80;
+const FONT_SIZE: u32 = 34;
+const LINE_HEIGHT: u32 = 50;
+/// Characters per line of moment text at `FONT_SIZE`
+const LINE_CHARS: usize = 52;
+const MAX_LINES: usize = 7;
+/// Rendered cards kept in memory
+const CACHE_SIZE: usize = 256;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const FONT_FAMILY: &str = "Georgia, 'DejaVu Serif', 'Liberation Serif', 'Noto Serif', serif";
+
+/// What goes on a card
+pub struct CardContent<'a> {
+    pub moment_id: Uuid,
+    pub text: &'a str,
+    pub speaker: Option<&'a str>,
+    pub mood: &'a str,
+    pub loop_number: u64,
+}
+
+impl CardContent<'_> {
+    /// Changes whenever anything drawn on the card does, so edited moments
+    /// are rendered again
+    fn cache_key(&self) -> CardKey {
+        let mut hasher = DefaultHasher::new();
+        (self.text, self.speaker, self.mood, self.loop_number).hash(&mut hasher);
+        (self.moment_id, hasher.finish())
+    }
+}
+
+/// A moment and a hash of everything drawn for it
+type CardKey = (Uuid, u64);
+
+/// Rendered cards, oldest first in `order`
+#[derive(Default)]
+struct CardCache {
+    cards: HashMap<CardKey, Arc<Vec<u8>>>,
+    order: VecDeque<CardKey>,
+}
+
+/// Background gradient and accent color for a mood
+fn palette(mood: &str) -> (&'static str, &'static str, &'static str) {
+    match mood {
+        "dark" => ("#0b0b10", "#24101a", "#b0305a"),
+        "nihilistic" => ("#0e0e0e", "#2a2a2a", "#a0a0a0"),
+        "hopeful" => ("#1c1a12", "#3d3420", "#e6bd55"),
+        "transcendent" => ("#120f24", "#30245a", "#bda8ff"),
+        _ => ("#14161c", "#262b38", "#8596ad"),
+    }
+}
+
+/// Renders moments into PNG share cards, with a cache and a per-player budget
+/// of fresh renders
+pub struct CardRenderer {
+    fonts: Arc<usvg::fontdb::Database>,
+    per_minute: u32,
+    cache: Mutex<CardCache>,
+    renders: Mutex<HashMap<Uuid, (Instant, u32)>>,
+}
+
+impl CardRenderer {
+    pub fn new(config: &Config) -> Self {
+        let mut fonts = usvg::fontdb::Database::new();
+        fonts.load_system_fonts();
+        if let Some(dir) = &config.card_font_dir {
+            fonts.load_fonts_dir(dir);
+        }
+        if fonts.is_empty() {
+            tracing::warn!("No fonts found; share cards will render without text");
+        }
+        Self {
+            fonts: Arc::new(fonts),
+            per_minute: config.card_rate_limit,
+            cache: Mutex::new(CardCache::default()),
+            renders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A cached card, which costs nothing against the budget
+    pub fn cached(&self, content: &CardContent) -> Option<Arc<Vec<u8>>> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.cards.get(&content.cache_key()).cloned()
+    }
+
+    /// Count a fresh render against the player's budget; over the limit, the
+    /// time until the budget refills
+    pub fn try_acquire(&self, player_id: Uuid) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let mut renders = self.renders.lock().unwrap_or_else(|e| e.into_inner());
+        let (window_start, count) = renders.entry(player_id).or_insert((Instant::now(), 0));
+        if window_start.elapsed() >= RATE_WINDOW {
+            *window_start = Instant::now();
+            *count = 0;
+        }
+        if *count >= self.per_minute {
+            return Err(RATE_WINDOW.saturating_sub(window_start.elapsed()));
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Render a card to PNG and cache it
+    pub fn render(&self, content: &CardContent) -> Result<Arc<Vec<u8>>> {
+        let svg = card_svg(content);
+        let options = usvg::Options {
+            fontdb: self.fonts.clone(),
+            ..Default::default()
+        };
+        let tree = usvg::Tree::from_str(&svg, &options).context("invalid card template")?;
+        let mut pixmap =
+            tiny_skia::Pixmap::new(WIDTH, HEIGHT).context("failed to allocate card")?;
+        resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+        let png = Arc::new(pixmap.encode_png().context("failed to encode card")?);
+
+        let key = content.cache_key();
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if cache.cards.insert(key, png.clone()).is_none() {
+            cache.order.push_back(key);
+        }
+        while cache.order.len() > CACHE_SIZE {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.cards.remove(&oldest);
+            }
+        }
+        Ok(png)
+    }
+
+    /// Forget render budgets of players that are no longer held in memory
+    pub fn evict(&self, keep: impl Fn(&Uuid) -> bool) -> usize {
+        let mut renders = self.renders.lock().unwrap_or_else(|e| e.into_inner());
+        let before = renders.len();
+        renders.retain(|id, _| keep(id));
+        before - renders.len()
+    }
+}
+
+/// The card as an SVG document
+fn card_svg(content: &CardContent) -> String {
+    let (from, to, accent) = palette(content.mood);
+    let lines = wrap(content.text, LINE_CHARS, MAX_LINES);
+
+    let text_height = lines.len() as u32 * LINE_HEIGHT;
+    let speaker_height = if content.speaker.is_some() { LINE_HEIGHT } else { 0 };
+    // Centered between the top and the footer
+    let mut y = (HEIGHT - 120).saturating_sub(text_height + speaker_height) / 2 + FONT_SIZE + 20;
+
+    let mut body = String::new();
+    if let Some(speaker) = content.speaker {
+        body.push_str(&format!(
+            r#"<text x="{PADDING}" y="{y}" font-size="26" font-style="italic" fill="{accent}">{}</text>"#,
+            escape(speaker)
+        ));
+        y += LINE_HEIGHT;
+    }
+    for line in &lines {
+        body.push_str(&format!(
+            r##"<text x="{PADDING}" y="{y}" font-size="{FONT_SIZE}" fill="#ece8e1">{}</text>"##,
+            escape(line)
+        ));
+        y += LINE_HEIGHT;
+    }
+
+    let footer_y = HEIGHT - PADDING + 20;
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="{FONT_FAMILY}">
+<defs><linearGradient id="bg" x1="0" y1="0" x2="1" y2="1"><stop offset="0" stop-color="{from}"/><stop offset="1" stop-color="{to}"/></linearGradient></defs>
+<rect width="{WIDTH}" height="{HEIGHT}" fill="url(#bg)"/>
+<rect x="{PADDING}" y="{PADDING}" width="6" height="60" fill="{accent}"/>
+{body}
+<line x1="{PADDING}" y1="{line_y}" x2="{line_x2}" y2="{line_y}" stroke="{accent}" stroke-opacity="0.4" stroke-width="2"/>
+<text x="{PADDING}" y="{footer_y}" font-size="24" letter-spacing="6" fill="{accent}">NIHILISM</text>
+<text x="{right}" y="{footer_y}" font-size="24" text-anchor="end" fill="#ece8e1" fill-opacity="0.7">Loop #{loop_number} · {mood}</text>
+</svg>"##,
+        line_y = footer_y - 44,
+        line_x2 = WIDTH - PADDING,
+        right = WIDTH - PADDING,
+        loop_number = content.loop_number,
+        mood = escape(content.mood),
+    )
+}
+
+/// Break text into lines of at most `width` characters at spaces, ending with
+/// an ellipsis when it runs past `max_lines`
+fn wrap(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut truncated = false;
+    for word in text.split_whitespace() {
+        let fits = line.chars().count() + 1 + word.chars().count() <= width;
+        if !line.is_empty() && !fits {
+            if lines.len() + 1 == max_lines {
+                truncated = true;
+                break;
+            }
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        // A single word longer than a line is cut
+        line.extend(word.chars().take(width));
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    if truncated && let Some(last) = lines.last_mut() {
+        while last.chars().count() + 1 > width {
+            last.pop();
+        }
+        last.push('…');
+    }
+    lines
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,64 @@
+use super::*;
+
+fn content(text: &str) -> CardContent<'_> {
+    CardContent {
+        moment_id: Uuid::new_v4(),
+        text,
+        speaker: Some("The Voice"),
+        mood: "dark",
+        loop_number: 7,
+    }
+}
+
+#[test]
+fn long_text_wraps_and_ends_with_an_ellipsis() {
+    let text = "word ".repeat(200);
+
+    let lines = wrap(&text, 20, 3);
+
+    assert_eq!(lines.len(), 3);
+    assert!(lines.iter().all(|l| l.chars().count() <= 20));
+    assert!(lines[2].ends_with('…'));
+    assert_eq!(wrap("A short moment.", 20, 3), vec!["A short moment."]);
+}
+
+#[test]
+fn text_is_escaped_in_the_template() {
+    let svg = card_svg(&content("<script> & \"quotes\""));
+
+    assert!(svg.contains("&lt;script&gt; &amp; &quot;quotes&quot;"));
+    assert!(svg.contains("Loop #7"));
+    assert!(!svg.contains("<script>"));
+}
+
+#[test]
+fn renders_a_png_and_caches_it() {
+    let renderer = CardRenderer::new(&Config::for_tests("http://127.0.0.1:9/v1"));
+    let card = content("The corridor bends the way it always has.");
+
+    assert!(renderer.cached(&card).is_none());
+    let png = renderer.render(&card).unwrap();
+
+    assert!(png.starts_with(b"\x89PNG"));
+    assert!(renderer.cached(&card).is_some());
+
+    let edited = CardContent {
+        text: "Something else entirely.",
+        ..card
+    };
+    assert!(renderer.cached(&edited).is_none());
+}
+
+#[test]
+fn fresh_renders_are_rate_limited_per_player() {
+    let mut config = Config::for_tests("http://127.0.0.1:9/v1");
+    config.card_rate_limit = 2;
+    let renderer = CardRenderer::new(&config);
+    let (player, other) = (Uuid::new_v4(), Uuid::new_v4());
+
+    assert!(renderer.try_acquire(player).is_ok());
+    assert!(renderer.try_acquire(player).is_ok());
+    let wait = renderer.try_acquire(player).unwrap_err();
+    assert!(wait > Duration::ZERO && wait <= RATE_WINDOW);
+    assert!(renderer.try_acquire(other).is_ok());
+}
@@ -0,0 +1,257 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::endings::EndingType;
+use crate::events::{EventBus, GameEvent};
+use crate::game::{GameState, Player};
+use crate::persistence;
+use crate::privacy::{self, Purpose};
+use crate::tenant;
+
+const CHALLENGE_DIR: &str = "data/challenges";
+
+/// A scenario twist applied to every run of a given day
+pub struct Modifier {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub prompt: &'static str,
+}
+
+const MODIFIERS: &[Modifier] = &[
+    Modifier {
+        id: "silent_day",
+        name: "The Silent Day",
+        prompt: "No one in the world can speak today. Characters communicate only through gestures, \
+                 notes and silence. The player's words go unheard.",
+    },
+    Modifier {
+        id: "inverted_sky",
+        name: "Inverted Sky",
+        prompt: "The sky hangs below the ground today. Everything familiar is slightly upside down, \
+                 and the residents pretend not to notice.",
+    },
+    Modifier {
+        id: "last_hour",
+        name: "The Last Hour",
+        prompt: "Every loop today lasts only a single hour. Time pressure colors every moment, and \
+                 the clock is always visible somewhere.",
+    },
+    Modifier {
+        id: "borrowed_memories",
+        name: "Borrowed Memories",
+        prompt: "The player wakes with memories that belong to someone else. Some of what they \
+                 remember never happened to them.",
+    },
+    Modifier {
+        id: "endless_rain",
+        name: "Endless Rain",
+        prompt: "It is raining today and it will never stop. The rain washes colors away and \
+                 carries whispers of previous loops.",
+    },
+    Modifier {
+        id: "one_friend",
+        name: "One Friend",
+        prompt: "Only one other person in the world remembers the loop today. Finding them, and \
+                 deciding what to do with them, is everything.",
+    },
+    Modifier {
+        id: "mirror_world",
+        name: "Mirror World",
+        prompt: "Every reflection today shows the player a different choice than the one they made.",
+    },
+];
+
+/// The global challenge for one calendar day (UTC)
+pub struct Challenge {
+    pub date: NaiveDate,
+    pub seed: u64,
+    pub modifier: &'static Modifier,
+}
+
+impl Challenge {
+    pub fn for_date(date: NaiveDate) -> Self {
+        // FNV-1a over the ISO date: every instance agrees on the same seed
+        let mut seed: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in date.to_string().bytes() {
+            seed ^= byte as u64;
+            seed = seed.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        Self {
+            date,
+            seed,
+            modifier: modifier_for_seed(seed),
+        }
+    }
+
+    pub fn today() -> Self {
+        Self::for_date(today())
+    }
+}
+
+pub fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+/// The modifier every run sharing `seed` plays under
+pub fn modifier_for_seed(seed: u64) -> &'static Modifier {
+    &MODIFIERS[(seed % MODIFIERS.len() as u64) as usize]
+}
+
+/// Look up a modifier by id
+pub fn modifier(id: &str) -> Option<&'static Modifier> {
+    MODIFIERS.iter().find(|m| m.id == id)
+}
+
+/// Marks a player as a daily challenge run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChallengeRun {
+    pub date: NaiveDate,
+    pub seed: u64,
+    pub modifier: String,
+    /// The player who opted in, if the run was started from an existing save
+    pub owner: Option<Uuid>,
+    #[serde(default)]
+    pub submitted: bool,
+}
+
+impl ChallengeRun {
+    pub fn new(challenge: &Challenge, owner: Option<Uuid>) -> Self {
+        Self {
+            date: challenge.date,
+            seed: challenge.seed,
+            modifier: challenge.modifier.id.to_string(),
+            owner,
+            submitted: false,
+        }
+    }
+
+    /// Challenge runs lock when the day rolls over
+    pub fn is_expired(&self) -> bool {
+        self.date != today()
+    }
+
+    /// Prompt section describing the day's scenario
+    pub fn prompt(&self) -> Option<String> {
+        modifier(&self.modifier)
+            .map(|m| format!("DAILY CHALLENGE - {}:\n{}\n", m.name, m.prompt))
+    }
+}
+
+/// One finished challenge run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub player_id: Uuid,
+    pub name: Option<String>,
+    pub ending: EndingType,
+    pub loops: u64,
+    pub total_choices: u64,
+    pub nihilism_score: i32,
+    pub submitted_at: DateTime<Utc>,
+}
+
+impl LeaderboardEntry {
+    pub fn from_player(player: &Player, ending: EndingType) -> Self {
+        Self {
+            player_id: player.id,
+            name: player.name.clone(),
+            ending,
+            loops: player.run.memory.total_loops,
+            total_choices: player.run.memory.total_choices,
+            nihilism_score: player.run.memory.nihilism_score,
+            submitted_at: Utc::now(),
+        }
+    }
+}
+
+/// Serializes read-modify-write of leaderboard files
+static LEADERBOARD_LOCK: Mutex<()> = Mutex::new(());
+
+fn leaderboard_path(tenant: Option<&str>, date: NaiveDate) -> PathBuf {
+    tenant::data_path(tenant, CHALLENGE_DIR).join(format!("{}.json", date))
+}
+
+/// Load a day's leaderboard, best runs first
+pub fn load_leaderboard(tenant: Option<&str>, date: NaiveDate) -> Result<Vec<LeaderboardEntry>> {
+    let path = leaderboard_path(tenant, date);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let json = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Add a run to the day's leaderboard. Fewer loops ranks higher, then fewer choices.
+pub fn submit(tenant: Option<&str>, date: NaiveDate, entry: LeaderboardEntry) -> Result<()> {
+    let _guard = LEADERBOARD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut entries = load_leaderboard(tenant, date)?;
+    entries.retain(|e| e.player_id != entry.player_id);
+    entries.push(entry);
+    entries.sort_by(|a, b| {
+        a.loops
+            .cmp(&b.loops)
+            .then(a.total_choices.cmp(&b.total_choices))
+            .then(a.submitted_at.cmp(&b.submitted_at))
+    });
+
+    let path = leaderboard_path(tenant, date);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&entries)?)?;
+    Ok(())
+}
+
+/// Post a finished challenge run to its day's leaderboard, once
+pub fn submit_run(player: &mut Player, ending: &EndingType) {
+    let Some(run) = &player.run.challenge else {
+        return;
+    };
+    if run.submitted || run.is_expired() {
+        return;
+    }
+    if !privacy::policy().allows(player, Purpose::PublicStats) {
+        tracing::debug!("Challenge run {} kept off the leaderboard", player.id);
+        return;
+    }
+
+    let date = run.date;
+    let entry = LeaderboardEntry::from_player(player, ending.clone());
+    match submit(player.tenant.as_deref(), date, entry) {
+        Ok(()) => {
+            if let Some(run) = &mut player.run.challenge {
+                run.submitted = true;
+            }
+            tracing::info!("Submitted challenge run {} for {}", player.id, date);
+            if let Err(e) = persistence::save_player(player) {
+                tracing::warn!("Failed to save submitted challenge run {}: {}", player.id, e);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to submit challenge run {}: {}", player.id, e),
+    }
+}
+
+/// Submit challenge runs to the leaderboard as soon as they reach an ending
+pub fn subscribe(events: &EventBus, game: Arc<RwLock<GameState>>) {
+    events.spawn_subscriber("challenge_leaderboard", move |envelope| {
+        let game = game.clone();
+        async move {
+            let GameEvent::EndingReached {
+                player_id, ending, ..
+            } = &envelope.event
+            else {
+                return;
+            };
+            let mut game = game.write().await;
+            if let Some(player) = game.get_player_mut(player_id) {
+                submit_run(player, ending);
+            }
+        }
+    });
+}
@@ -0,0 +1,317 @@
+//! Buckets of choice texts that mean the same thing, for community
+//! statistics.
+//!
+//! Players word the same choice in many ways ("walk away", "I leave.",
+//! "Leave the room"). Choices made are queued as they happen, and the
+//! `choice_clustering` job embeds the texts it has not seen before and files
+//! each under the nearest bucket, or a new one when none is close enough.
+//! Bucket centroids are the running mean of their variants' embeddings, so
+//! buckets are updated incrementally and never recomputed from scratch.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+use crate::config::Config;
+use crate::events::{EventBus, GameEvent};
+use crate::game::GameState;
+use crate::llm::LlmClient;
+use crate::privacy::{self, Purpose};
+use crate::tenant;
+
+const CLUSTERS_FILE: &str = "data/choice_clusters.json";
+/// Texts sent to the embeddings endpoint at once
+const BATCH_SIZE: usize = 64;
+/// Longer choice texts are cut before embedding
+const MAX_TEXT_CHARS: usize = 200;
+
+/// Choices that mean the same thing
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Cluster {
+    pub id: u32,
+    /// Normalized texts in the bucket and how often each was chosen
+    pub variants: BTreeMap<String, u64>,
+    /// Mean embedding of the variants
+    pub centroid: Vec<f32>,
+}
+
+impl Cluster {
+    /// The variant chosen most often, standing for the whole bucket
+    pub fn label(&self) -> &str {
+        self.variants
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map_or("", |(text, _)| text.as_str())
+    }
+
+    pub fn chosen(&self) -> u64 {
+        self.variants.values().sum()
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Saved {
+    next_id: u32,
+    clusters: Vec<Cluster>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clustered_at: Option<DateTime<Utc>>,
+}
+
+impl Saved {
+    fn find(&mut self, text: &str) -> Option<&mut Cluster> {
+        self.clusters.iter_mut().find(|c| c.variants.contains_key(text))
+    }
+
+    /// File a new text under the nearest bucket at least `similarity` close,
+    /// or a new bucket, returning whether a bucket was opened
+    fn assign(&mut self, text: String, count: u64, embedding: Vec<f32>, similarity: f32) -> bool {
+        let nearest = self
+            .clusters
+            .iter_mut()
+            .map(|c| (cosine(&c.centroid, &embedding), c))
+            .filter(|(s, _)| *s >= similarity)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((_, cluster)) = nearest {
+            let n = cluster.variants.len() as f32;
+            for (c, e) in cluster.centroid.iter_mut().zip(&embedding) {
+                *c = (*c * n + e) / (n + 1.0);
+            }
+            cluster.variants.insert(text, count);
+            return false;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.clusters.push(Cluster {
+            id,
+            variants: BTreeMap::from([(text, count)]),
+            centroid: embedding,
+        });
+        true
+    }
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+/// Choice text as it is bucketed: lowercase, single-spaced, without
+/// surrounding punctuation
+pub fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .trim_matches(|c: char| c.is_ascii_punctuation())
+        .chars()
+        .take(MAX_TEXT_CHARS)
+        .collect()
+}
+
+/// What a clustering pass did
+#[derive(Debug, Default, Serialize)]
+pub struct ClusterPass {
+    /// Choices counted toward a variant already bucketed
+    pub counted: u64,
+    /// New variants embedded and bucketed
+    pub embedded: usize,
+    /// Buckets opened for them
+    pub new_clusters: usize,
+}
+
+/// One bucket as reported by the analytics endpoint
+#[derive(Debug, Serialize)]
+pub struct ChoiceBucket {
+    pub id: u32,
+    pub label: String,
+    pub chosen: u64,
+    pub variants: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BucketReport {
+    pub clustered_at: Option<DateTime<Utc>>,
+    /// Distinct texts waiting for the next pass
+    pub pending: usize,
+    /// Most chosen first
+    pub buckets: Vec<ChoiceBucket>,
+}
+
+/// Choice buckets persisted to `data/choice_clusters.json`, and the choices
+/// made since the last pass
+pub struct ChoiceClusters {
+    path: PathBuf,
+    similarity: f32,
+    saved: Mutex<Saved>,
+    pending: Mutex<HashMap<String, u64>>,
+}
+
+impl ChoiceClusters {
+    pub fn load(config: &Config) -> Self {
+        let path = tenant::data_path(config.tenant.as_deref(), CLUSTERS_FILE);
+        let saved = Self::read(&path).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load choice clusters from {}: {}", path.display(), e);
+            Saved::default()
+        });
+        Self {
+            path,
+            similarity: config.choice_cluster_similarity,
+            saved: Mutex::new(saved),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn read(path: &Path) -> Result<Saved> {
+        if !path.exists() {
+            return Ok(Saved::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn saved(&self) -> std::sync::MutexGuard<'_, Saved> {
+        self.saved.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn pending(&self) -> std::sync::MutexGuard<'_, HashMap<String, u64>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queue a choice for the next pass
+    pub fn record(&self, text: &str) {
+        let text = normalize(text);
+        if !text.is_empty() {
+            *self.pending().entry(text).or_default() += 1;
+        }
+    }
+
+    /// Id of the cluster a choice text was bucketed into, if any
+    pub fn cluster_of(&self, text: &str) -> Option<u32> {
+        let text = normalize(text);
+        self.saved()
+            .clusters
+            .iter()
+            .find(|c| c.variants.contains_key(&text))
+            .map(|c| c.id)
+    }
+
+    /// Queue every choice made by players who allow analytics
+    pub fn subscribe(self: &Arc<Self>, events: &EventBus, game: Arc<RwLock<GameState>>) {
+        let clusters = self.clone();
+        events.spawn_subscriber("choice_clusters", move |envelope| {
+            let clusters = clusters.clone();
+            let game = game.clone();
+            async move {
+                let GameEvent::ChoiceMade {
+                    player_id,
+                    choice_text,
+                    ..
+                } = &envelope.event
+                else {
+                    return;
+                };
+                let allowed = game
+                    .read()
+                    .await
+                    .get_player(player_id)
+                    .is_some_and(|p| privacy::policy().allows(p, Purpose::Analytics));
+                if allowed {
+                    clusters.record(choice_text);
+                }
+            }
+        });
+    }
+
+    /// Bucket the queued choices, embedding texts not seen before with `model`
+    pub async fn cluster(&self, llm: &LlmClient, model: &str) -> Result<ClusterPass> {
+        let pending = std::mem::take(&mut *self.pending());
+        let mut pass = ClusterPass::default();
+        if pending.is_empty() {
+            return Ok(pass);
+        }
+
+        let mut unseen = Vec::new();
+        {
+            let mut saved = self.saved();
+            for (text, count) in pending {
+                match saved.find(&text) {
+                    Some(cluster) => {
+                        *cluster.variants.entry(text).or_default() += count;
+                        pass.counted += count;
+                    }
+                    None => unseen.push((text, count)),
+                }
+            }
+        }
+
+        let mut batches = unseen.chunks(BATCH_SIZE);
+        while let Some(batch) = batches.next() {
+            let texts: Vec<String> = batch.iter().map(|(t, _)| t.clone()).collect();
+            let embeddings = match llm.embed(model, &texts).await {
+                Ok(embeddings) => embeddings,
+                Err(e) => {
+                    // Keep what wasn't embedded for the next pass
+                    let mut pending = self.pending();
+                    for (text, count) in batch.iter().chain(batches.by_ref().flatten()) {
+                        *pending.entry(text.clone()).or_default() += count;
+                    }
+                    drop(pending);
+                    self.save()?;
+                    return Err(e);
+                }
+            };
+            let mut saved = self.saved();
+            for ((text, count), embedding) in batch.iter().zip(embeddings) {
+                if saved.assign(text.clone(), *count, embedding, self.similarity) {
+                    pass.new_clusters += 1;
+                }
+                pass.embedded += 1;
+            }
+        }
+
+        self.saved().clustered_at = Some(Utc::now());
+        self.save()?;
+        Ok(pass)
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(&*self.saved())?;
+        fs::write(&self.path, json)?;
+        Ok(())
+    }
+
+    pub fn report(&self) -> BucketReport {
+        let saved = self.saved();
+        let mut buckets: Vec<ChoiceBucket> = saved
+            .clusters
+            .iter()
+            .map(|c| ChoiceBucket {
+                id: c.id,
+                label: c.label().to_string(),
+                chosen: c.chosen(),
+                variants: c.variants.clone(),
+            })
+            .collect();
+        buckets.sort_by(|a, b| b.chosen.cmp(&a.chosen).then(a.id.cmp(&b.id)));
+        BucketReport {
+            clustered_at: saved.clustered_at,
+            pending: self.pending().len(),
+            buckets,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
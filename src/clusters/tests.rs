@@ -0,0 +1,37 @@
+use super::*;
+
+#[test]
+fn choice_texts_are_normalized_before_bucketing() {
+    assert_eq!(normalize("  Walk   AWAY. "), "walk away");
+    assert_eq!(normalize("\"Stay?\""), "stay");
+    assert_eq!(normalize("..."), "");
+}
+
+#[test]
+fn near_duplicates_share_a_bucket_and_move_its_centroid() {
+    let mut saved = Saved::default();
+    assert!(saved.assign("walk away".into(), 3, vec![1.0, 0.0], 0.9));
+    assert!(!saved.assign("leave".into(), 5, vec![0.96, 0.28], 0.9));
+    assert!(saved.assign("stay".into(), 1, vec![0.0, 1.0], 0.9));
+
+    assert_eq!(saved.clusters.len(), 2);
+    let leaving = &saved.clusters[0];
+    assert_eq!(leaving.label(), "leave");
+    assert_eq!(leaving.chosen(), 8);
+    assert!((leaving.centroid[0] - 0.98).abs() < 1e-6);
+    assert!((leaving.centroid[1] - 0.14).abs() < 1e-6);
+    assert_eq!(saved.find("walk away").map(|c| c.id), Some(0));
+    assert_eq!(saved.find("stay").map(|c| c.id), Some(1));
+}
+
+#[test]
+fn the_closest_bucket_wins() {
+    let mut saved = Saved::default();
+    saved.assign("a".into(), 1, vec![1.0, 0.0], 0.5);
+    saved.assign("b".into(), 1, vec![0.0, 1.0], 0.5);
+    saved.assign("c".into(), 1, vec![0.4, 0.9], 0.5);
+
+    assert_eq!(saved.find("c").map(|c| c.id), Some(1));
+    assert_eq!(cosine(&[1.0, 0.0], &[0.0, 0.0]), 0.0);
+    assert_eq!(cosine(&[1.0, 0.0], &[1.0, 0.0, 0.0]), 0.0);
+}
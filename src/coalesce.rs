@@ -0,0 +1,103 @@
+use futures::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use uuid::Uuid;
+
+type Key = (Uuid, String);
+type Pending<T> = Shared<BoxFuture<'static, T>>;
+
+/// A call in flight, numbered so a waiter can tell it from a later one
+struct Call<T: Clone> {
+    id: u64,
+    pending: Pending<T>,
+}
+
+/// Runs at most one generation per player and action at a time. A request
+/// arriving while the same one is in flight (a double click, a retry over a
+/// flaky network) waits for that call and gets the same result, instead of
+/// paying for a second LLM call and adding a second moment.
+pub struct Coalescer<T: Clone> {
+    in_flight: Mutex<HashMap<Key, Call<T>>>,
+    next_id: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl<T: Clone + Send + Sync + 'static> Coalescer<T> {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    /// Run `work`, or join the identical call already running for this
+    /// player. `action` should name everything that makes two requests the
+    /// same, e.g. the choice and the moment it answers.
+    pub async fn run<F>(&self, player_id: Uuid, action: String, work: F) -> T
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let key = (player_id, action);
+        let (id, pending) = {
+            let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+            match in_flight.get(&key) {
+                Some(call) => {
+                    self.coalesced.fetch_add(1, Ordering::Relaxed);
+                    tracing::debug!("Joining in-flight {} for player {}", key.1, player_id);
+                    (call.id, call.pending.clone())
+                }
+                None => {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    let pending = work.boxed().shared();
+                    let call = Call {
+                        id,
+                        pending: pending.clone(),
+                    };
+                    in_flight.insert(key.clone(), call);
+                    (id, pending)
+                }
+            }
+        };
+        let _waiter = Waiter {
+            coalescer: self,
+            key,
+            id,
+        };
+        pending.await
+    }
+
+    /// Requests answered by joining a call already in flight
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced.load(Ordering::Relaxed)
+    }
+}
+
+/// Forgets the call once it has finished, or once every request waiting on
+/// it has gone away, so a later request starts afresh
+struct Waiter<'a, T: Clone> {
+    coalescer: &'a Coalescer<T>,
+    key: Key,
+    id: u64,
+}
+
+impl<T: Clone> Drop for Waiter<'_, T> {
+    fn drop(&mut self) {
+        let mut in_flight = self
+            .coalescer
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let Some(call) = in_flight.get(&self.key).filter(|call| call.id == self.id) else {
+            return;
+        };
+        // Unfinished handles: the map's own, and this waiter's if it gave up
+        let finished = call.pending.peek().is_some();
+        let abandoned = call.pending.strong_count().is_none_or(|n| n <= 2);
+        if finished || abandoned {
+            in_flight.remove(&self.key);
+        }
+    }
+}
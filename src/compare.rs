@@ -0,0 +1,143 @@
+//! Side-by-side run statistics for two players, for the "my loop vs. your
+//! loop" conversations players have with each other.
+//!
+//! Both players must allow public stats. Choices are compared by the choice
+//! cluster they fell into, so "Walk away" and "walk away." count as the same
+//! choice; a choice not clustered yet only matches its own normalized text.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clusters;
+use crate::consequences::ChoiceRecord;
+use crate::endings::EndingType;
+use crate::game::{ArchivedLoop, Player};
+
+/// Score a run ended a loop with
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LoopScore {
+    pub loop_number: u64,
+    pub nihilism_score: i32,
+}
+
+/// What a choice is compared by
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Bucket {
+    Cluster(u32),
+    Text(String),
+}
+
+/// One side of a comparison
+#[derive(Clone, Debug, Serialize)]
+pub struct RunStats {
+    pub total_loops: u64,
+    pub current_loop: u64,
+    pub total_choices: u64,
+    pub nihilism_score: i32,
+    /// Last recorded score of each loop, oldest first
+    pub trajectory: Vec<LoopScore>,
+    pub endings_reached: Vec<EndingType>,
+    /// How many moments of each mood the run has seen
+    pub moods: BTreeMap<String, usize>,
+    /// Distinct choice clusters the run has picked from
+    pub clusters: usize,
+    #[serde(skip)]
+    buckets: BTreeSet<Bucket>,
+}
+
+impl RunStats {
+    /// Gather a run's stats from its player, choice log and archived loops.
+    /// `cluster_of` names the choice cluster a text was bucketed into.
+    pub fn gather(
+        player: &Player,
+        choices: &[ChoiceRecord],
+        archives: &[ArchivedLoop],
+        cluster_of: impl Fn(&str) -> Option<u32>,
+    ) -> Self {
+        let mut trajectory: Vec<LoopScore> = Vec::new();
+        for record in choices {
+            let Some(score) = record.nihilism_score else {
+                continue;
+            };
+            match trajectory.last_mut() {
+                Some(last) if last.loop_number == record.loop_number => last.nihilism_score = score,
+                _ => trajectory.push(LoopScore {
+                    loop_number: record.loop_number,
+                    nihilism_score: score,
+                }),
+            }
+        }
+
+        let mut moods = BTreeMap::new();
+        for archived in archives {
+            match &archived.shard {
+                Some(shard) => {
+                    for (mood, count) in &shard.moods {
+                        *moods.entry(mood.clone()).or_default() += count;
+                    }
+                }
+                None => {
+                    for moment in &archived.moments {
+                        *moods.entry(moment.mood.clone()).or_default() += 1;
+                    }
+                }
+            }
+        }
+        for moment in &player.run.narrative_history {
+            *moods.entry(moment.mood.clone()).or_default() += 1;
+        }
+
+        let buckets: BTreeSet<Bucket> = choices
+            .iter()
+            .map(|record| match cluster_of(&record.choice_text) {
+                Some(id) => Bucket::Cluster(id),
+                None => Bucket::Text(clusters::normalize(&record.choice_text)),
+            })
+            .filter(|bucket| *bucket != Bucket::Text(String::new()))
+            .collect();
+
+        let memory = &player.run.memory;
+        Self {
+            total_loops: memory.total_loops,
+            current_loop: player.run.current_loop.number,
+            total_choices: memory.total_choices,
+            nihilism_score: memory.nihilism_score,
+            trajectory,
+            endings_reached: memory.endings_reached.clone(),
+            moods,
+            clusters: buckets.len(),
+            buckets,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub player_a: RunStats,
+    pub player_b: RunStats,
+    /// Choice clusters both runs picked from
+    pub shared_clusters: usize,
+    /// Shared clusters as a percentage of the clusters either run picked from
+    pub cluster_overlap_percent: f64,
+}
+
+impl Comparison {
+    pub fn new(player_a: RunStats, player_b: RunStats) -> Self {
+        let shared = player_a.buckets.intersection(&player_b.buckets).count();
+        let either = player_a.buckets.union(&player_b.buckets).count();
+        let percent = if either == 0 {
+            0.0
+        } else {
+            (shared as f64 * 1000.0 / either as f64).round() / 10.0
+        };
+        Self {
+            player_a,
+            player_b,
+            shared_clusters: shared,
+            cluster_overlap_percent: percent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
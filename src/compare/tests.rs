@@ -0,0 +1,87 @@
+use super::*;
+use chrono::Utc;
+
+use crate::testing::{self, PlayerBuilder};
+
+fn record(loop_number: u64, text: &str, nihilism_score: Option<i32>) -> ChoiceRecord {
+    ChoiceRecord {
+        loop_number,
+        choice_id: "choice".to_string(),
+        choice_text: text.to_string(),
+        is_dark: false,
+        score_delta: 0,
+        nihilism_score,
+        at: Utc::now(),
+        provenance: None,
+    }
+}
+
+#[test]
+fn stats_follow_loop_scores_and_moods() {
+    let player = PlayerBuilder::new()
+        .loops(2)
+        .moment("The door again.", &[])
+        .mood("dark")
+        .build();
+    let choices = [
+        record(1, "Open the door", Some(4)),
+        record(1, "Walk away", Some(9)),
+        // Logged before scores were recorded
+        record(2, "Open the door", None),
+        record(2, "Open the door", Some(-3)),
+    ];
+    let archives = [testing::archived_loop(&player, 1, "reset")];
+
+    let stats = RunStats::gather(&player, &choices, &archives, |_| None);
+    assert_eq!(
+        stats.trajectory,
+        vec![
+            LoopScore {
+                loop_number: 1,
+                nihilism_score: 9
+            },
+            LoopScore {
+                loop_number: 2,
+                nihilism_score: -3
+            },
+        ]
+    );
+    assert_eq!(stats.moods["neutral"], 3);
+    assert_eq!(stats.moods["dark"], 1);
+    assert_eq!(stats.clusters, 2);
+}
+
+#[test]
+fn overlap_counts_clusters_then_texts() {
+    let cluster_of = |text: &str| (clusters::normalize(text) == "walk away").then_some(7);
+    let player = PlayerBuilder::new().build();
+
+    let a = RunStats::gather(
+        &player,
+        &[
+            record(1, "Walk away", None),
+            record(1, "Open  the door", None),
+        ],
+        &[],
+        cluster_of,
+    );
+    let b = RunStats::gather(
+        &player,
+        &[
+            record(1, "walk away", None),
+            record(1, "open the door", None),
+            record(1, "Scream", None),
+        ],
+        &[],
+        cluster_of,
+    );
+    let comparison = Comparison::new(a, b);
+    assert_eq!(comparison.shared_clusters, 2);
+    assert_eq!(comparison.cluster_overlap_percent, 66.7);
+
+    let empty = || RunStats::gather(&player, &[], &[], |_| None);
+    assert_eq!(
+        Comparison::new(empty(), empty()).cluster_overlap_percent,
+        0.0
+    );
+}
@@ -0,0 +1,486 @@
+//! A small expression language for scenario ending conditions, e.g.
+//! `score >= 80 && dark_choices >= 30 && has_truth("the door")`.
+//!
+//! Conditions are parsed and type checked once, at startup, and evaluated
+//! against a [`ConditionContext`]. Evaluation cannot fail: arithmetic
+//! saturates and every name is checked when parsing.
+
+use serde::Serialize;
+
+use crate::endings::EndingType;
+use crate::game::Player;
+
+/// Why a condition could not be parsed, with the 1-based column it was found at
+#[derive(Debug, thiserror::Error)]
+#[error("{message} at column {column}")]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+fn error<T>(column: usize, message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError {
+        column,
+        message: message.into(),
+    })
+}
+
+/// The player state a condition is evaluated against
+#[derive(Serialize)]
+pub struct ConditionContext<'a> {
+    pub score: i64,
+    pub loops: i64,
+    pub choices: i64,
+    pub dark_choices: i64,
+    pub light_choices: i64,
+    /// Number of the loop the player is in
+    pub current_loop: i64,
+    pub streak: i64,
+    pub truths: &'a [String],
+    pub memories: &'a [String],
+    pub endings: &'a [EndingType],
+}
+
+impl<'a> ConditionContext<'a> {
+    pub fn from_player(player: &'a Player) -> Self {
+        let memory = &player.run.memory;
+        Self {
+            score: memory.nihilism_score as i64,
+            loops: memory.total_loops as i64,
+            choices: memory.total_choices as i64,
+            dark_choices: memory.dark_choices as i64,
+            light_choices: memory.light_choices as i64,
+            current_loop: player.run.current_loop.number as i64,
+            streak: memory.choice_streak as i64,
+            truths: &memory.truths_discovered,
+            memories: &memory.key_memories,
+            endings: &memory.endings_reached,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Var {
+    Score,
+    Loops,
+    Choices,
+    Dark,
+    Light,
+    Loop,
+    Streak,
+    Truths,
+    Memories,
+    Endings,
+}
+
+impl Var {
+    fn named(name: &str) -> Option<Self> {
+        Some(match name {
+            "score" => Var::Score,
+            "loops" => Var::Loops,
+            "choices" => Var::Choices,
+            "dark_choices" => Var::Dark,
+            "light_choices" => Var::Light,
+            "loop" => Var::Loop,
+            "streak" => Var::Streak,
+            "truths" => Var::Truths,
+            "memories" => Var::Memories,
+            "endings" => Var::Endings,
+            _ => return None,
+        })
+    }
+
+    fn value(&self, ctx: &ConditionContext<'_>) -> i64 {
+        match self {
+            Var::Score => ctx.score,
+            Var::Loops => ctx.loops,
+            Var::Choices => ctx.choices,
+            Var::Dark => ctx.dark_choices,
+            Var::Light => ctx.light_choices,
+            Var::Loop => ctx.current_loop,
+            Var::Streak => ctx.streak,
+            Var::Truths => ctx.truths.len() as i64,
+            Var::Memories => ctx.memories.len() as i64,
+            Var::Endings => ctx.endings.len() as i64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    And,
+    Or,
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+    Add,
+    Sub,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Int(i64),
+    Bool(bool),
+    Var(Var),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Abs(Box<Expr>),
+    /// Case-insensitive substring of a discovered truth
+    HasTruth(String),
+    /// Case-insensitive substring of a key memory
+    HasMemory(String),
+    HasReached(EndingType),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Int,
+    Bool,
+}
+
+impl Type {
+    fn name(&self) -> &'static str {
+        match self {
+            Type::Int => "a number",
+            Type::Bool => "true or false",
+        }
+    }
+}
+
+fn contains_ignore_case(items: &[String], needle: &str) -> bool {
+    let needle = needle.to_lowercase();
+    items.iter().any(|item| item.to_lowercase().contains(&needle))
+}
+
+impl Expr {
+    fn int(&self, ctx: &ConditionContext<'_>) -> i64 {
+        match self {
+            Expr::Int(n) => *n,
+            Expr::Var(var) => var.value(ctx),
+            Expr::Neg(e) => e.int(ctx).saturating_neg(),
+            Expr::Abs(e) => e.int(ctx).saturating_abs(),
+            Expr::Binary(Op::Add, a, b) => a.int(ctx).saturating_add(b.int(ctx)),
+            Expr::Binary(Op::Sub, a, b) => a.int(ctx).saturating_sub(b.int(ctx)),
+            // Unreachable after type checking
+            _ => 0,
+        }
+    }
+
+    fn bool(&self, ctx: &ConditionContext<'_>) -> bool {
+        match self {
+            Expr::Bool(b) => *b,
+            Expr::Not(e) => !e.bool(ctx),
+            Expr::HasTruth(text) => contains_ignore_case(ctx.truths, text),
+            Expr::HasMemory(text) => contains_ignore_case(ctx.memories, text),
+            Expr::HasReached(ending) => ctx.endings.contains(ending),
+            Expr::Binary(Op::And, a, b) => a.bool(ctx) && b.bool(ctx),
+            Expr::Binary(Op::Or, a, b) => a.bool(ctx) || b.bool(ctx),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.int(ctx), b.int(ctx));
+                match op {
+                    Op::Eq => a == b,
+                    Op::Ne => a != b,
+                    Op::Ge => a >= b,
+                    Op::Le => a <= b,
+                    Op::Gt => a > b,
+                    Op::Lt => a < b,
+                    _ => false,
+                }
+            }
+            // Unreachable after type checking
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Str(String),
+    Ident(String),
+    Op(Op),
+    Not,
+    Open,
+    Close,
+    Comma,
+    End,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('&', Some('&')) => (Token::Op(Op::And), 2),
+            ('|', Some('|')) => (Token::Op(Op::Or), 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('+', _) => (Token::Op(Op::Add), 1),
+            ('-', _) => (Token::Op(Op::Sub), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            (',', _) => (Token::Comma, 1),
+            ('"', _) => {
+                let Some(len) = chars[i + 1..].iter().position(|&c| c == '"') else {
+                    return error(column, "unterminated string");
+                };
+                let text: String = chars[i + 1..i + 1 + len].iter().collect();
+                (Token::Str(text), len + 2)
+            }
+            (c, _) if c.is_ascii_digit() => {
+                let len = chars[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+                let digits: String = chars[i..i + len].iter().collect();
+                let Ok(n) = digits.parse() else {
+                    return error(column, "number is too large");
+                };
+                (Token::Int(n), len)
+            }
+            (c, _) if c.is_ascii_alphabetic() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count();
+                (Token::Ident(chars[i..i + len].iter().collect()), len)
+            }
+            (c, _) => return error(column, format!("unexpected '{}'", c)),
+        };
+        tokens.push((token, column));
+        i += len;
+    }
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+/// Recursive descent over the grammar, loosest binding first:
+/// `or := and ("||" and)*`, `and := cmp ("&&" cmp)*`,
+/// `cmp := sum (op sum)?`, `sum := unary (("+" | "-") unary)*`,
+/// `unary := ("!" | "-") unary | primary`
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.pos].clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), ParseError> {
+        let (token, column) = self.next();
+        if token == expected {
+            Ok(())
+        } else {
+            error(column, format!("expected {}", what))
+        }
+    }
+
+    /// Parse an operand that must have type `expected`
+    fn operand(
+        &mut self,
+        expected: Type,
+        parse: fn(&mut Self) -> Result<(Expr, Type), ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let column = self.column();
+        let (expr, ty) = parse(self)?;
+        if ty != expected {
+            return error(column, format!("expected {}", expected.name()));
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<(Expr, Type), ParseError> {
+        self.logical(Op::Or, Self::and)
+    }
+
+    fn and(&mut self) -> Result<(Expr, Type), ParseError> {
+        self.logical(Op::And, Self::comparison)
+    }
+
+    fn logical(
+        &mut self,
+        op: Op,
+        parse: fn(&mut Self) -> Result<(Expr, Type), ParseError>,
+    ) -> Result<(Expr, Type), ParseError> {
+        let column = self.column();
+        let (mut lhs, ty) = parse(self)?;
+        if *self.peek() != Token::Op(op) {
+            return Ok((lhs, ty));
+        }
+        if ty != Type::Bool {
+            return error(column, "expected true or false");
+        }
+        while *self.peek() == Token::Op(op) {
+            self.next();
+            let rhs = self.operand(Type::Bool, parse)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok((lhs, Type::Bool))
+    }
+
+    fn comparison(&mut self) -> Result<(Expr, Type), ParseError> {
+        let column = self.column();
+        let (lhs, ty) = self.sum()?;
+        let op = match self.peek() {
+            Token::Op(op @ (Op::Eq | Op::Ne | Op::Ge | Op::Le | Op::Gt | Op::Lt)) => *op,
+            _ => return Ok((lhs, ty)),
+        };
+        if ty != Type::Int {
+            return error(column, "expected a number");
+        }
+        self.next();
+        let rhs = self.operand(Type::Int, Self::sum)?;
+        Ok((Expr::Binary(op, Box::new(lhs), Box::new(rhs)), Type::Bool))
+    }
+
+    fn sum(&mut self) -> Result<(Expr, Type), ParseError> {
+        let column = self.column();
+        let (mut lhs, ty) = self.unary()?;
+        while let Token::Op(op @ (Op::Add | Op::Sub)) = *self.peek() {
+            if ty != Type::Int {
+                return error(column, "expected a number");
+            }
+            self.next();
+            let rhs = self.operand(Type::Int, Self::unary)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok((lhs, ty))
+    }
+
+    fn unary(&mut self) -> Result<(Expr, Type), ParseError> {
+        match self.peek() {
+            Token::Not => {
+                self.next();
+                let e = self.operand(Type::Bool, Self::unary)?;
+                Ok((Expr::Not(Box::new(e)), Type::Bool))
+            }
+            Token::Op(Op::Sub) => {
+                self.next();
+                let e = self.operand(Type::Int, Self::unary)?;
+                Ok((Expr::Neg(Box::new(e)), Type::Int))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<(Expr, Type), ParseError> {
+        let (token, column) = self.next();
+        match token {
+            Token::Int(n) => Ok((Expr::Int(n), Type::Int)),
+            Token::Open => {
+                let inner = self.or()?;
+                self.expect(Token::Close, "')'")?;
+                Ok(inner)
+            }
+            Token::Ident(name) if *self.peek() == Token::Open => self.call(&name, column),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok((Expr::Bool(true), Type::Bool)),
+                "false" => Ok((Expr::Bool(false), Type::Bool)),
+                _ => match Var::named(&name) {
+                    Some(var) => Ok((Expr::Var(var), Type::Int)),
+                    None => error(column, format!("unknown name '{}'", name)),
+                },
+            },
+            Token::Str(_) => error(column, "a string can only be a function argument"),
+            Token::End => error(column, "unexpected end of condition"),
+            _ => error(column, "expected a value"),
+        }
+    }
+
+    fn call(&mut self, name: &str, column: usize) -> Result<(Expr, Type), ParseError> {
+        self.next();
+        let call = match name {
+            "abs" => {
+                let e = self.operand(Type::Int, Self::or)?;
+                (Expr::Abs(Box::new(e)), Type::Int)
+            }
+            "has_truth" | "has_memory" | "has_reached" => {
+                let (token, arg_column) = self.next();
+                let Token::Str(text) = token else {
+                    return error(arg_column, format!("{} takes a string", name));
+                };
+                let expr = match name {
+                    "has_truth" => Expr::HasTruth(text),
+                    "has_memory" => Expr::HasMemory(text),
+                    _ => match serde_json::from_value::<EndingType>(serde_json::Value::String(text.clone())) {
+                        Ok(ending) if ending.is_known() => Expr::HasReached(ending),
+                        _ => return error(arg_column, format!("unknown ending '{}'", text)),
+                    },
+                };
+                (expr, Type::Bool)
+            }
+            _ => return error(column, format!("unknown function '{}'", name)),
+        };
+        if *self.peek() == Token::Comma {
+            return error(self.column(), format!("{} takes one argument", name));
+        }
+        self.expect(Token::Close, "')'")?;
+        Ok(call)
+    }
+}
+
+/// A parsed, type checked condition
+#[derive(Debug, Clone)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let (expr, ty) = parser.or()?;
+        if *parser.peek() != Token::End {
+            return error(parser.column(), "expected '&&', '||' or the end of the condition");
+        }
+        if ty != Type::Bool {
+            return error(1, "condition must be true or false, not a number");
+        }
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn evaluate(&self, ctx: &ConditionContext<'_>) -> bool {
+        self.expr.bool(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,95 @@
+use super::*;
+
+fn context<'a>(truths: &'a [String], endings: &'a [EndingType]) -> ConditionContext<'a> {
+    ConditionContext {
+        score: 80,
+        loops: 10,
+        choices: 40,
+        dark_choices: 30,
+        light_choices: 10,
+        current_loop: 11,
+        streak: 3,
+        truths,
+        memories: &[],
+        endings,
+    }
+}
+
+fn holds(source: &str) -> bool {
+    let truths = ["The door was never locked".to_string()];
+    Condition::parse(source)
+        .unwrap()
+        .evaluate(&context(&truths, &[EndingType::VoidEmbrace]))
+}
+
+fn message(source: &str) -> String {
+    Condition::parse(source).unwrap_err().to_string()
+}
+
+#[test]
+fn operators_bind_by_precedence() {
+    // && binds tighter than ||, and ! tighter than both
+    assert!(holds("true || false && false"));
+    assert!(!holds("(true || false) && false"));
+    assert!(holds("!false && !false || false"));
+    assert!(!holds("!(true || false)"));
+    // Sums associate left and bind tighter than comparisons
+    assert!(holds("score - 5 - 5 == 70"));
+    assert!(holds("dark_choices - light_choices > 15 && -score + 100 == 20"));
+    assert!(holds("abs(light_choices - dark_choices) == 20"));
+    assert!(holds("loop == loops + 1 && streak != 0"));
+}
+
+#[test]
+fn functions_look_into_the_player() {
+    assert!(holds(r#"has_truth("THE DOOR")"#));
+    assert!(holds(r#"has_truth("never locked") && truths == 1"#));
+    assert!(!holds(r#"has_truth("the window")"#));
+    assert!(!holds(r#"has_memory("door")"#));
+    assert!(holds(r#"has_reached("VoidEmbrace") && endings == 1"#));
+
+    assert_eq!(message("has_truth(door)"), "has_truth takes a string at column 11");
+    assert_eq!(
+        message(r#"has_truth("a", "b")"#),
+        "has_truth takes one argument at column 14"
+    );
+    assert_eq!(message(r#"has_truth("door"#), "unterminated string at column 11");
+}
+
+#[test]
+fn unknown_names_and_unbalanced_parens_are_rejected() {
+    assert_eq!(message("scroe >= 80"), "unknown name 'scroe' at column 1");
+    assert_eq!(
+        message(r#"has_truths("door")"#),
+        "unknown function 'has_truths' at column 1"
+    );
+    assert_eq!(
+        message(r#"has_reached("TheEnd")"#),
+        "unknown ending 'TheEnd' at column 13"
+    );
+    assert_eq!(message("(score > 1"), "expected ')' at column 11");
+    assert_eq!(message("((score > 1)"), "expected ')' at column 13");
+    assert_eq!(
+        message("score > 1)"),
+        "expected '&&', '||' or the end of the condition at column 10"
+    );
+    assert_eq!(message("()"), "expected a value at column 2");
+    assert_eq!(
+        message("score + 1"),
+        "condition must be true or false, not a number at column 1"
+    );
+}
+
+#[test]
+fn a_bad_condition_stops_startup_naming_its_ending() {
+    let path = std::env::temp_dir().join(format!("nihilism-conditions-{}.json", uuid::Uuid::new_v4()));
+    std::fs::write(&path, r#"{"VoidEmbrace": "score >= 80 && scroe > 1"}"#).unwrap();
+
+    let error = crate::endings::load_conditions(&path.to_string_lossy()).unwrap_err();
+    assert_eq!(
+        format!("{:#}", error),
+        "invalid condition for VoidEmbrace: score >= 80 && scroe > 1: \
+         unknown name 'scroe' at column 16"
+    );
+    std::fs::remove_file(path).unwrap();
+}
@@ -1,26 +1,996 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 
+use crate::context::{ContextProfile, ContextProfiles};
+use crate::flags::{Feature, Rollout};
+use crate::game::StreakCurve;
+
+/// Deployment-level content rating
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentRating {
+    /// Suitable for schools and public showcases
+    Teen,
+    /// The full experience, no additional restrictions
+    #[default]
+    Mature,
+}
+
+/// How log lines are written
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with event fields at the top level
+    Json,
+}
+
+impl LogFormat {
+    /// `LOG_FORMAT`, read on its own so logging is set up before the config is loaded
+    pub fn from_env() -> Self {
+        match env::var("LOG_FORMAT").map(|v| v.trim().to_lowercase()).as_deref() {
+            Ok("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/// Where player saves are stored
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StorageBackend {
+    #[default]
+    File,
+    Sqlite,
+}
+
+impl StorageBackend {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "file" | "json" => Some(StorageBackend::File),
+            "sqlite" => Some(StorageBackend::Sqlite),
+            _ => None,
+        }
+    }
+}
+
+/// Encoding of player saves and loop archives. Loading accepts either.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SaveFormat {
+    /// Pretty-printed JSON, for debuggability
+    #[default]
+    Json,
+    /// MessagePack, much smaller and faster for huge histories
+    MessagePack,
+}
+
+impl SaveFormat {
+    pub const ALL: [SaveFormat; 2] = [SaveFormat::Json, SaveFormat::MessagePack];
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "json" => Some(SaveFormat::Json),
+            "msgpack" | "messagepack" => Some(SaveFormat::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// File extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            SaveFormat::Json => "json",
+            SaveFormat::MessagePack => "msgpack",
+        }
+    }
+}
+
+/// How aggressively player text is scrubbed before it is shared
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SanitizeLevel {
+    Off,
+    /// Strong profanity, email addresses and phone numbers
+    #[default]
+    Standard,
+    /// Also mild profanity, links and social handles
+    Strict,
+}
+
+impl SanitizeLevel {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "off" | "none" => Some(SanitizeLevel::Off),
+            "standard" => Some(SanitizeLevel::Standard),
+            "strict" => Some(SanitizeLevel::Strict),
+            _ => None,
+        }
+    }
+}
+
+/// What a failed preflight request to the LLM backend does at startup
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Preflight {
+    /// No request is sent
+    Off,
+    /// The failure is logged and the server starts anyway
+    Warn,
+    /// The server refuses to start
+    #[default]
+    Fail,
+}
+
+impl Preflight {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "off" | "false" | "0" => Some(Preflight::Off),
+            "warn" => Some(Preflight::Warn),
+            "fail" | "true" | "1" => Some(Preflight::Fail),
+            _ => None,
+        }
+    }
+}
+
+/// A way of judging how nihilistic a choice is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoringKind {
+    /// Dark and hopeful words in the choice
+    Keyword,
+    /// The LLM classifies the choice in context
+    Llm,
+    /// How the same choice was judged earlier in the run
+    Stats,
+    /// Rules from the scoring pack file
+    Pack,
+}
+
+impl ScoringKind {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "keyword" | "keywords" => Some(ScoringKind::Keyword),
+            "llm" => Some(ScoringKind::Llm),
+            "stats" | "stat" => Some(ScoringKind::Stats),
+            "pack" => Some(ScoringKind::Pack),
+            _ => None,
+        }
+    }
+}
+
+/// Parse `kind:weight,...` (or a bare `kind` for weight 1)
+pub fn parse_scoring(value: &str) -> Vec<(ScoringKind, f64)> {
+    let mut strategies = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (kind, weight) = entry.split_once(':').unwrap_or((entry, "1"));
+        let parsed = ScoringKind::parse(kind).zip(
+            weight
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|w| w.is_finite() && *w > 0.0),
+        );
+        match parsed {
+            Some(strategy) => strategies.push(strategy),
+            None => tracing::warn!("Ignoring invalid scoring strategy {:?}", entry),
+        }
+    }
+    strategies
+}
+
+/// Price of one model in USD per 1K tokens
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct ModelPrice {
+    pub prompt_per_1k: f64,
+    pub completion_per_1k: f64,
+}
+
+/// Parse `model=prompt:completion,...` (or `model=price` for a flat rate)
+fn parse_pricing(value: &str) -> HashMap<String, ModelPrice> {
+    let mut pricing = HashMap::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let parsed = entry.split_once('=').and_then(|(model, prices)| {
+            let (prompt, completion) = prices.split_once(':').unwrap_or((prices, prices));
+            Some((
+                model.trim().to_string(),
+                ModelPrice {
+                    prompt_per_1k: prompt.trim().parse().ok()?,
+                    completion_per_1k: completion.trim().parse().ok()?,
+                },
+            ))
+        });
+        match parsed {
+            Some((model, price)) => {
+                pricing.insert(model, price);
+            }
+            None => tracing::warn!("Ignoring invalid LLM_PRICING entry {:?}", entry),
+        }
+    }
+    pricing
+}
+
+/// Parse `model=history:memories:summaries,...` into context profiles
+fn parse_context_profiles(value: &str) -> ContextProfiles {
+    let mut profiles = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let parsed = entry
+            .split_once('=')
+            .filter(|(model, _)| !model.trim().is_empty())
+            .and_then(|(model, spec)| Some((model.trim().to_string(), ContextProfile::parse(spec)?)));
+        match parsed {
+            Some(profile) => profiles.push(profile),
+            None => tracing::warn!("Ignoring invalid CONTEXT_PROFILES entry {:?}", entry),
+        }
+    }
+    ContextProfiles::new(profiles)
+}
+
+/// Comma-separated values, trimmed, without empty entries
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Push services of the major browsers, which `web_push` endpoints may name
+/// when `WEB_PUSH_HOSTS` is unset
+const WEB_PUSH_HOSTS: &[&str] = &[
+    "fcm.googleapis.com",
+    "updates.push.services.mozilla.com",
+    "web.push.apple.com",
+    "notify.windows.com",
+];
+
+/// Parse `feature=on|off|percent,...` into feature flag rollouts
+fn parse_flags(value: &str) -> HashMap<Feature, Rollout> {
+    let mut flags = HashMap::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (feature, rollout) = entry.split_once('=').unwrap_or((entry, "on"));
+        match Feature::parse(feature).zip(Rollout::parse(rollout)) {
+            Some((feature, rollout)) => {
+                flags.insert(feature, rollout);
+            }
+            None => tracing::warn!("Ignoring invalid FEATURE_FLAGS entry {:?}", entry),
+        }
+    }
+    flags
+}
+
+/// Parse `Name=value,...` into extra request headers
+fn parse_headers(value: &str) -> Vec<(String, String)> {
+    let mut headers = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+            _ => tracing::warn!("Ignoring invalid LLM_HEADERS entry {:?}", entry),
+        }
+    }
+    headers
+}
+
+/// Mail relay for outgoing email, such as the nightly digest
+#[derive(Clone, Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// Connections upgrade to TLS with STARTTLS
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+}
+
+impl SmtpConfig {
+    /// Mail is on when `SMTP_HOST` and `SMTP_FROM` are set
+    fn from_env() -> Option<Self> {
+        let host = env::var("SMTP_HOST").ok().filter(|h| !h.trim().is_empty())?;
+        let Some(from) = env::var("SMTP_FROM").ok().filter(|f| !f.trim().is_empty()) else {
+            tracing::warn!("Ignoring SMTP_HOST: SMTP_FROM is not set");
+            return None;
+        };
+        Some(Self {
+            host: host.trim().to_string(),
+            port: env::var("SMTP_PORT")
+                .ok()
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(587),
+            username: env::var("SMTP_USERNAME").ok().filter(|u| !u.is_empty()),
+            password: env::var("SMTP_PASSWORD").ok().filter(|p| !p.is_empty()),
+            from: from.trim().to_string(),
+        })
+    }
+}
+
+/// AWS Signature Version 4 signing of LLM requests, for Bedrock-compatible gateways
+#[derive(Clone, Debug)]
+pub struct SigV4Config {
+    pub region: String,
+    pub service: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl SigV4Config {
+    /// Signing is on when `LLM_SIGV4_REGION` is set; credentials come from
+    /// the standard `AWS_*` variables
+    fn from_env() -> Option<Self> {
+        let region = env::var("LLM_SIGV4_REGION").ok().filter(|r| !r.trim().is_empty())?;
+        let (Ok(access_key_id), Ok(secret_access_key)) = (
+            env::var("AWS_ACCESS_KEY_ID"),
+            env::var("AWS_SECRET_ACCESS_KEY"),
+        ) else {
+            tracing::warn!("Ignoring LLM_SIGV4_REGION: AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY are not set");
+            return None;
+        };
+        Some(Self {
+            region: region.trim().to_string(),
+            service: env::var("LLM_SIGV4_SERVICE")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .unwrap_or_else(|| "bedrock".to_string()),
+            access_key_id,
+            secret_access_key,
+            session_token: env::var("AWS_SESSION_TOKEN").ok().filter(|t| !t.is_empty()),
+        })
+    }
+}
+
+impl ContentRating {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "teen" => Some(ContentRating::Teen),
+            "mature" => Some(ContentRating::Mature),
+            _ => None,
+        }
+    }
+
+    /// Thematic boundaries appended to the narrator's system prompt
+    pub fn prompt_guidelines(&self) -> &'static str {
+        match self {
+            ContentRating::Teen => {
+                "This deployment is rated TEEN. Explore loss, loneliness and meaning, but never \
+                 depict self-harm, suicide, graphic violence, gore, sexual content or substance \
+                 use. Death may happen off-screen and must never be described in detail. Dark \
+                 choices should feel heavy through consequence and mood, not shock."
+            }
+            ContentRating::Mature => {
+                "This deployment is rated MATURE. Dark and disturbing themes are allowed when they \
+                 serve the story, but avoid gratuitous gore and never produce sexual content."
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub host: String,
     pub port: u16,
-    pub llm_base_url: String,
+    /// Encodings offered for responses and accepted for request bodies; empty turns
+    /// compression off
+    pub compression_encodings: Vec<String>,
+    /// Responses smaller than this are sent as is
+    pub compression_min_bytes: u16,
+    /// Content type prefixes of responses worth compressing
+    pub compression_content_types: Vec<String>,
+    /// Redundant base URLs of the same provider, from `LLM_BASE_URLS` or
+    /// else `LLM_BASE_URL`
+    pub llm_base_urls: Vec<String>,
     pub llm_api_key: String,
     pub llm_model: String,
+    /// One tiny completion per upstream on startup, to open connections and
+    /// check the key and model before the first player arrives
+    pub llm_preflight: Preflight,
+    pub llm_probe_capabilities: bool,
+    pub llm_json_mode: Option<bool>,
+    pub llm_streaming: Option<bool>,
+    pub llm_logprobs: Option<bool>,
+    pub llm_vision: Option<bool>,
+    /// GGUF model narrated in process instead of calling an LLM server; needs
+    /// the `local-llm` feature
+    pub local_model_path: Option<String>,
+    /// Context window of the local model, in tokens
+    #[cfg_attr(not(feature = "local-llm"), allow(dead_code))]
+    pub local_model_context: u32,
+    /// Layers of the local model offloaded to the GPU
+    #[cfg_attr(not(feature = "local-llm"), allow(dead_code))]
+    pub local_model_gpu_layers: u32,
+    pub content_rating: ContentRating,
+    pub moderation_enabled: bool,
+    pub admin_token: Option<String>,
+    pub scheduler_jitter_percent: u32,
+    /// Per-job schedule overrides from `SCHEDULE_<JOB_NAME>`, keyed by lowercase job name
+    pub job_schedules: HashMap<String, String>,
+    pub storage_backend: StorageBackend,
+    pub storage_dual_write: Option<StorageBackend>,
+    pub sqlite_path: String,
+    pub save_format: SaveFormat,
+    pub shuffle_choices: bool,
+    pub max_loops: Option<u64>,
+    /// USD per 1K tokens, keyed by model name
+    pub llm_pricing: HashMap<String, ModelPrice>,
+    pub llm_monthly_budget: Option<f64>,
+    /// Full moments kept in memory per player; 0 means unlimited
+    pub history_max_moments: usize,
+    /// Estimated bytes of history kept in memory per player; 0 means unlimited
+    pub history_max_bytes: usize,
+    /// How much history and memory narrator prompts carry, by model
+    pub context_profiles: ContextProfiles,
+    /// Most recent archived loops per player kept raw; older ones are compacted
+    pub archive_keep_loops: Option<u64>,
+    pub archive_compaction_dry_run: bool,
+    /// Base URL of the client, used to build magic sign-in links
+    pub public_url: String,
+    pub sanitize_level: SanitizeLevel,
+    /// Choices per category in the ending ledger (0 disables it)
+    pub ending_ledger_size: usize,
+    /// Fresh suggestion requests allowed per player per minute (0 = unlimited)
+    pub suggest_rate_limit: u32,
+    /// Ask the LLM for suggestions; otherwise only the local model is used
+    pub suggest_use_llm: bool,
+    /// Scheduled janitor sweeps only report orphaned data
+    pub janitor_dry_run: bool,
+    /// Absence after which a returning player's story decays (0 disables it)
+    pub idle_decay_after_hours: u64,
+    /// Runs a single player may hold, including the active one
+    pub max_runs: usize,
+    /// Recent moments a new one is compared against for repetition (0 disables it)
+    pub repetition_window: usize,
+    /// Shingle similarity, from 0 to 1, at which a moment counts as a repeat
+    pub repetition_threshold: f64,
+    /// Score weight kept by each further choice in a dark or light streak (1 disables it)
+    pub streak_decay: f64,
+    /// Extra weight per choice of a streak that a choice breaks (0 disables it)
+    pub streak_swing: f64,
+    /// Cap on the weight of a streak-breaking choice
+    pub streak_max_multiplier: f64,
+    /// Abuse strikes that put a player in ghost mode (0 never ghosts)
+    pub abuse_strike_threshold: usize,
+    /// Opening moments a warm-up generates at once
+    pub warmup_concurrency: usize,
+    /// Endings reached by fewer than this percent of souls are rare
+    pub rare_ending_percent: f64,
+    /// Privacy budget of each public ending count, which gets Laplace noise
+    /// of scale `1 / epsilon`; `None` publishes exact counts
+    pub stats_epsilon: Option<f64>,
+    /// Public counts below this many souls are withheld
+    pub stats_min_count: u64,
+    /// Name every ending in player-facing responses instead of hiding the
+    /// ones a player hasn't unlocked behind opaque codes; for debugging
+    pub ending_spoilers: bool,
+    /// Players active at once before newcomers wait in line (0 is unlimited)
+    pub max_active_players: usize,
+    /// Minutes since their last action that a player still counts as active
+    pub active_window_minutes: i64,
+    /// Narrative generations at once before the rest wait in line; 0 is unlimited
+    pub max_concurrent_generations: usize,
+    /// Generations that may wait in line before more are refused
+    pub generation_queue_size: usize,
+    /// Seconds after which a waiting continuation goes ahead like a first moment
+    pub generation_queue_aging_secs: u64,
+    /// Moments a player may have written again each loop; 0 turns it off
+    pub max_regenerations_per_loop: u32,
+    /// Weighted strategies that judge whether a choice is dark
+    pub scoring_strategy: Vec<(ScoringKind, f64)>,
+    /// JSON file of scoring rules for the `pack` strategy
+    pub scoring_pack: Option<String>,
+    /// Directory of WASM plugins and their manifests
+    pub plugins_dir: Option<String>,
+    /// Fuel a plugin may burn in one call before it is stopped
+    #[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+    pub plugin_fuel: u64,
+    /// JSON file of scenario ending conditions replacing the built-in ones
+    pub ending_conditions: Option<String>,
+    /// JSON file of handwritten scenario moments at fixed points of every run
+    pub scenario_anchors: Option<String>,
+    /// Key player backups are signed with; servers sharing it accept each other's backups
+    pub backup_secret: Option<String>,
+    /// Key run seals are signed with; servers sharing it verify each other's seals
+    pub seal_secret: Option<String>,
+    /// JSON theme pack replacing the server's flavor text
+    pub theme_pack: Option<String>,
+    /// JSON file of tenants sharing this deployment, with their overrides
+    pub tenants_file: Option<String>,
+    /// Tenant this config belongs to; `None` for the default deployment
+    pub tenant: Option<String>,
+    /// Cheaper model that rates each moment's choices in the background
+    pub rerank_model: Option<String>,
+    /// Model that embeds choice texts to bucket them for community statistics
+    pub embedding_model: Option<String>,
+    /// Cosine similarity at which a choice joins an existing bucket
+    pub choice_cluster_similarity: f32,
+    /// Start in maintenance mode, refusing anything that changes a game
+    pub maintenance_read_only: bool,
+    /// Rollouts of experimental features; unlisted features are on for everyone
+    pub feature_flags: HashMap<Feature, Rollout>,
+    /// Cheaper model that takes over runs as the month's spend nears the budget
+    pub budget_handoff_model: Option<String>,
+    /// Share of `llm_monthly_budget` spent at which runs are handed over
+    pub budget_handoff_share: f64,
+    /// URL the nightly digest is posted to as JSON
+    pub digest_webhook_url: Option<String>,
+    /// Addresses the nightly digest is mailed to, through `smtp`
+    pub digest_email_to: Vec<String>,
+    pub smtp: Option<SmtpConfig>,
+    /// Let players register for push notifications
+    pub push_enabled: bool,
+    /// ntfy server that players' topics live on
+    pub ntfy_url: String,
+    /// Gotify servers players may send to, by host; none when empty
+    pub gotify_hosts: Vec<String>,
+    /// Push services browser subscriptions may name, by host
+    pub web_push_hosts: Vec<String>,
+    /// Contact in VAPID tokens, `mailto:` or `https:`; `public_url` if unset
+    pub push_contact: Option<String>,
+    /// Local hour from which the daily reminder may go out
+    pub push_daily_hour: u32,
+    /// Consent assumed for players who never answered the consent prompt
+    pub consent_by_default: bool,
+    /// Chance of reliving a remembered continuation instead of generating one
+    pub deja_vu_probability: f64,
+    /// How strongly choices lean toward the ending a player is nearing, from 0
+    /// (pure agency) to 1 (authored destiny)
+    pub fate_gravity: f64,
+    /// Characters per beat when a moment is revealed beat by beat (0 sends it whole)
+    pub reveal_beat_chars: usize,
+    /// How long a revealed beat waits for the client's ack before the next one
+    pub reveal_ack_timeout_secs: u64,
+    /// Milliseconds a generation runs before the player hears thinking beats; 0 turns them off
+    pub thinking_beat_delay_ms: u64,
+    /// Share cards rendered per player per minute (0 = unlimited); cached cards are free
+    pub card_rate_limit: u32,
+    /// Directory of extra fonts for share cards, on top of the system fonts
+    pub card_font_dir: Option<String>,
+    /// Proxy for LLM traffic only; `HTTP_PROXY`/`HTTPS_PROXY` apply otherwise
+    pub llm_proxy: Option<String>,
+    /// Extra headers sent with every LLM request
+    pub llm_headers: Vec<(String, String)>,
+    /// PEM client certificate and PKCS#8 key for mutual TLS with the LLM backend
+    pub llm_client_cert: Option<String>,
+    pub llm_client_key: Option<String>,
+    /// PEM CA certificate trusted in addition to the system roots
+    pub llm_ca_cert: Option<String>,
+    pub llm_sigv4: Option<SigV4Config>,
 }
 
 impl Config {
     pub fn from_env() -> Self {
+        let llm_base_urls = env::var("LLM_BASE_URLS")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|u| !u.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .ok()
+            .filter(|urls| !urls.is_empty())
+            .unwrap_or_else(|| {
+                vec![env::var("LLM_BASE_URL")
+                    .unwrap_or_else(|_| "http://localhost:8080/v1".to_string())]
+            });
         Self {
             host: env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
             port: env::var("PORT")
                 .ok()
                 .and_then(|p| p.parse().ok())
                 .unwrap_or(3001),
-            llm_base_url: env::var("LLM_BASE_URL")
-                .unwrap_or_else(|_| "http://localhost:8080/v1".to_string()),
+            compression_encodings: env::var("COMPRESSION_ENCODINGS")
+                .map(|v| parse_list(&v.to_lowercase()))
+                .unwrap_or_else(|_| parse_list("gzip,br,zstd")),
+            compression_min_bytes: env::var("COMPRESSION_MIN_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1024),
+            compression_content_types: env::var("COMPRESSION_CONTENT_TYPES")
+                .map(|v| parse_list(&v.to_lowercase()))
+                .unwrap_or_else(|_| parse_list("application/json,text/")),
+            llm_base_urls,
             llm_api_key: env::var("LLM_API_KEY").unwrap_or_else(|_| "sk-none".to_string()),
             llm_model: env::var("LLM_MODEL").unwrap_or_else(|_| "gpt-4".to_string()),
+            llm_preflight: env::var("LLM_PREFLIGHT")
+                .ok()
+                .and_then(|v| Preflight::parse(&v))
+                .unwrap_or_default(),
+            llm_probe_capabilities: env_bool("LLM_PROBE_CAPABILITIES").unwrap_or(true),
+            llm_json_mode: env_bool("LLM_JSON_MODE"),
+            llm_streaming: env_bool("LLM_STREAMING"),
+            llm_logprobs: env_bool("LLM_LOGPROBS"),
+            llm_vision: env_bool("LLM_VISION"),
+            local_model_path: env::var("LOCAL_MODEL_PATH").ok().filter(|p| !p.trim().is_empty()),
+            local_model_context: env::var("LOCAL_MODEL_CONTEXT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n: &u32| *n > 0)
+                .unwrap_or(4096),
+            local_model_gpu_layers: env::var("LOCAL_MODEL_GPU_LAYERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            content_rating: env::var("CONTENT_RATING")
+                .ok()
+                .and_then(|r| ContentRating::parse(&r))
+                .unwrap_or_default(),
+            moderation_enabled: env_bool("MODERATION_ENABLED").unwrap_or(false),
+            admin_token: env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            scheduler_jitter_percent: env::var("SCHEDULER_JITTER_PERCENT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            job_schedules: env::vars()
+                .filter_map(|(key, value)| {
+                    key.strip_prefix("SCHEDULE_")
+                        .map(|job| (job.to_lowercase(), value))
+                })
+                .collect(),
+            storage_backend: env::var("STORAGE_BACKEND")
+                .ok()
+                .and_then(|b| StorageBackend::parse(&b))
+                .unwrap_or_default(),
+            storage_dual_write: env::var("STORAGE_DUAL_WRITE")
+                .ok()
+                .and_then(|b| StorageBackend::parse(&b)),
+            sqlite_path: env::var("SQLITE_PATH")
+                .unwrap_or_else(|_| "data/nihilism.db".to_string()),
+            save_format: env::var("SAVE_FORMAT")
+                .ok()
+                .and_then(|f| SaveFormat::parse(&f))
+                .unwrap_or_default(),
+            shuffle_choices: env_bool("SHUFFLE_CHOICES").unwrap_or(true),
+            max_loops: env::var("MAX_LOOPS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&n| n > 0),
+            llm_pricing: env::var("LLM_PRICING")
+                .map(|v| parse_pricing(&v))
+                .unwrap_or_default(),
+            llm_monthly_budget: env::var("LLM_MONTHLY_BUDGET")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&b: &f64| b > 0.0),
+            history_max_moments: env::var("HISTORY_MAX_MOMENTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(200),
+            history_max_bytes: env::var("HISTORY_MAX_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(512 * 1024),
+            context_profiles: env::var("CONTEXT_PROFILES")
+                .map(|v| parse_context_profiles(&v))
+                .unwrap_or_default(),
+            archive_keep_loops: env::var("ARCHIVE_KEEP_LOOPS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            archive_compaction_dry_run: env_bool("ARCHIVE_COMPACTION_DRY_RUN").unwrap_or(false),
+            public_url: env::var("PUBLIC_URL")
+                .map(|u| u.trim_end_matches('/').to_string())
+                .unwrap_or_else(|_| "http://localhost:3001".to_string()),
+            sanitize_level: env::var("SANITIZE_LEVEL")
+                .ok()
+                .and_then(|l| SanitizeLevel::parse(&l))
+                .unwrap_or_default(),
+            ending_ledger_size: env::var("ENDING_LEDGER_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            suggest_rate_limit: env::var("SUGGEST_RATE_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(20),
+            suggest_use_llm: env_bool("SUGGEST_USE_LLM").unwrap_or(true),
+            janitor_dry_run: env_bool("JANITOR_DRY_RUN").unwrap_or(true),
+            idle_decay_after_hours: env::var("IDLE_DECAY_AFTER_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(48),
+            max_runs: env::var("MAX_RUNS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            repetition_window: env::var("REPETITION_WINDOW")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            repetition_threshold: env::var("REPETITION_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.5),
+            streak_decay: env::var("STREAK_DECAY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|d: &f64| (0.0..=1.0).contains(d))
+                .unwrap_or(0.85),
+            streak_swing: env::var("STREAK_SWING")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|s: &f64| *s >= 0.0)
+                .unwrap_or(0.5),
+            streak_max_multiplier: env::var("STREAK_MAX_MULTIPLIER")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|m: &f64| *m >= 1.0)
+                .unwrap_or(4.0),
+            abuse_strike_threshold: env::var("ABUSE_STRIKE_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            warmup_concurrency: env::var("WARMUP_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n: &usize| *n > 0)
+                .unwrap_or(4),
+            rare_ending_percent: env::var("RARE_ENDING_PERCENT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|p: &f64| (0.0..=100.0).contains(p))
+                .unwrap_or(10.0),
+            stats_epsilon: match env::var("STATS_EPSILON").ok().as_deref() {
+                Some("off") => None,
+                Some(v) => Some(v.parse().ok().filter(|e: &f64| *e > 0.0).unwrap_or(1.0)),
+                None => Some(1.0),
+            },
+            stats_min_count: env::var("STATS_MIN_COUNT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            ending_spoilers: env_bool("ENDING_SPOILERS").unwrap_or(false),
+            max_active_players: env::var("MAX_ACTIVE_PLAYERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            active_window_minutes: env::var("ACTIVE_WINDOW_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|m: &i64| *m > 0)
+                .unwrap_or(10),
+            max_concurrent_generations: env::var("MAX_CONCURRENT_GENERATIONS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            generation_queue_size: env::var("GENERATION_QUEUE_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100),
+            generation_queue_aging_secs: env::var("GENERATION_QUEUE_AGING_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            max_regenerations_per_loop: env::var("MAX_REGENERATIONS_PER_LOOP")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+            scoring_strategy: env::var("SCORING_STRATEGY")
+                .ok()
+                .map(|v| parse_scoring(&v))
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| vec![(ScoringKind::Keyword, 1.0)]),
+            scoring_pack: env::var("SCORING_PACK").ok().filter(|p| !p.trim().is_empty()),
+            plugins_dir: env::var("PLUGINS_DIR").ok().filter(|p| !p.trim().is_empty()),
+            plugin_fuel: env::var("PLUGIN_FUEL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10_000_000),
+            ending_conditions: env::var("ENDING_CONDITIONS")
+                .ok()
+                .filter(|p| !p.trim().is_empty()),
+            scenario_anchors: env::var("SCENARIO_ANCHORS")
+                .ok()
+                .filter(|p| !p.trim().is_empty()),
+            backup_secret: env::var("BACKUP_SECRET").ok().filter(|s| !s.is_empty()),
+            seal_secret: env::var("SEAL_SECRET").ok().filter(|s| !s.is_empty()),
+            theme_pack: env::var("THEME_PACK").ok().filter(|p| !p.trim().is_empty()),
+            tenants_file: env::var("TENANTS_FILE").ok().filter(|p| !p.trim().is_empty()),
+            tenant: None,
+            rerank_model: env::var("RERANK_MODEL").ok().filter(|m| !m.trim().is_empty()),
+            embedding_model: env::var("EMBEDDING_MODEL").ok().filter(|m| !m.trim().is_empty()),
+            choice_cluster_similarity: env::var("CHOICE_CLUSTER_SIMILARITY")
+                .ok()
+                .and_then(|v| v.parse::<f32>().ok())
+                .filter(|s| (0.0..=1.0).contains(s))
+                .unwrap_or(0.88),
+            maintenance_read_only: env_bool("MAINTENANCE_READ_ONLY").unwrap_or(false),
+            feature_flags: env::var("FEATURE_FLAGS")
+                .map(|v| parse_flags(&v))
+                .unwrap_or_default(),
+            budget_handoff_model: env::var("BUDGET_HANDOFF_MODEL").ok().filter(|m| !m.trim().is_empty()),
+            budget_handoff_share: env::var("BUDGET_HANDOFF_SHARE")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|s| (0.0..=1.0).contains(s))
+                .unwrap_or(0.8),
+            digest_webhook_url: env::var("DIGEST_WEBHOOK_URL").ok().filter(|u| !u.trim().is_empty()),
+            digest_email_to: env::var("DIGEST_EMAIL_TO")
+                .map(|v| parse_list(&v))
+                .unwrap_or_default(),
+            smtp: SmtpConfig::from_env(),
+            push_enabled: env_bool("PUSH_ENABLED").unwrap_or(false),
+            ntfy_url: env::var("NTFY_URL")
+                .map(|u| u.trim_end_matches('/').to_string())
+                .unwrap_or_else(|_| "https://ntfy.sh".to_string()),
+            gotify_hosts: env::var("GOTIFY_HOSTS")
+                .map(|v| parse_list(&v.to_lowercase()))
+                .unwrap_or_default(),
+            web_push_hosts: env::var("WEB_PUSH_HOSTS")
+                .map(|v| parse_list(&v.to_lowercase()))
+                .unwrap_or_else(|_| WEB_PUSH_HOSTS.iter().map(|h| h.to_string()).collect()),
+            push_contact: env::var("PUSH_CONTACT").ok().filter(|c| !c.trim().is_empty()),
+            push_daily_hour: env::var("PUSH_DAILY_HOUR")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|h| *h < 24)
+                .unwrap_or(19),
+            consent_by_default: env_bool("CONSENT_BY_DEFAULT").unwrap_or(false),
+            deja_vu_probability: env::var("DEJA_VU_PROBABILITY")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(|p| p.clamp(0.0, 1.0))
+                .unwrap_or(0.0),
+            fate_gravity: env::var("FATE_GRAVITY")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(|g| g.clamp(0.0, 1.0))
+                .unwrap_or(0.0),
+            reveal_beat_chars: env::var("REVEAL_BEAT_CHARS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(240),
+            reveal_ack_timeout_secs: env::var("REVEAL_ACK_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|s: &u64| *s > 0)
+                .unwrap_or(8),
+            thinking_beat_delay_ms: env::var("THINKING_BEAT_DELAY_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2500),
+            card_rate_limit: env::var("CARD_RATE_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            card_font_dir: env::var("CARD_FONT_DIR").ok().filter(|d| !d.trim().is_empty()),
+            llm_proxy: env::var("LLM_PROXY").ok().filter(|p| !p.trim().is_empty()),
+            llm_headers: env::var("LLM_HEADERS")
+                .map(|v| parse_headers(&v))
+                .unwrap_or_default(),
+            llm_client_cert: env::var("LLM_CLIENT_CERT").ok().filter(|p| !p.trim().is_empty()),
+            llm_client_key: env::var("LLM_CLIENT_KEY").ok().filter(|p| !p.trim().is_empty()),
+            llm_ca_cert: env::var("LLM_CA_CERT").ok().filter(|p| !p.trim().is_empty()),
+            llm_sigv4: SigV4Config::from_env(),
         }
     }
+
+    /// Deterministic defaults for tests, independent of the environment
+    #[cfg(test)]
+    pub fn for_tests(llm_base_url: &str) -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            compression_encodings: Vec::new(),
+            compression_min_bytes: 1024,
+            compression_content_types: Vec::new(),
+            llm_base_urls: vec![llm_base_url.to_string()],
+            llm_api_key: "sk-test".to_string(),
+            llm_model: "test-model".to_string(),
+            llm_preflight: Preflight::Off,
+            llm_probe_capabilities: false,
+            llm_json_mode: Some(false),
+            llm_streaming: Some(false),
+            llm_logprobs: Some(false),
+            llm_vision: Some(false),
+            local_model_path: None,
+            local_model_context: 4096,
+            local_model_gpu_layers: 0,
+            content_rating: ContentRating::Mature,
+            moderation_enabled: false,
+            admin_token: None,
+            scheduler_jitter_percent: 0,
+            job_schedules: HashMap::new(),
+            storage_backend: StorageBackend::File,
+            storage_dual_write: None,
+            sqlite_path: String::new(),
+            save_format: SaveFormat::Json,
+            shuffle_choices: false,
+            max_loops: None,
+            llm_pricing: HashMap::new(),
+            llm_monthly_budget: None,
+            history_max_moments: 0,
+            history_max_bytes: 0,
+            context_profiles: ContextProfiles::default(),
+            archive_keep_loops: None,
+            archive_compaction_dry_run: false,
+            public_url: "http://localhost:3001".to_string(),
+            sanitize_level: SanitizeLevel::Standard,
+            ending_ledger_size: 5,
+            suggest_rate_limit: 0,
+            suggest_use_llm: true,
+            janitor_dry_run: true,
+            idle_decay_after_hours: 48,
+            max_runs: 5,
+            repetition_window: 5,
+            repetition_threshold: 0.5,
+            streak_decay: 0.85,
+            streak_swing: 0.5,
+            streak_max_multiplier: 4.0,
+            abuse_strike_threshold: 5,
+            warmup_concurrency: 4,
+            rare_ending_percent: 10.0,
+            stats_epsilon: Some(1.0),
+            stats_min_count: 5,
+            ending_spoilers: false,
+            max_active_players: 0,
+            active_window_minutes: 10,
+            max_concurrent_generations: 0,
+            generation_queue_size: 100,
+            generation_queue_aging_secs: 10,
+            max_regenerations_per_loop: 2,
+            scoring_strategy: vec![(ScoringKind::Keyword, 1.0)],
+            scoring_pack: None,
+            plugins_dir: None,
+            plugin_fuel: 10_000_000,
+            ending_conditions: None,
+            scenario_anchors: None,
+            backup_secret: None,
+            seal_secret: None,
+            theme_pack: None,
+            tenants_file: None,
+            tenant: None,
+            rerank_model: None,
+            embedding_model: None,
+            choice_cluster_similarity: 0.88,
+            maintenance_read_only: false,
+            feature_flags: HashMap::new(),
+            budget_handoff_model: None,
+            budget_handoff_share: 0.8,
+            digest_webhook_url: None,
+            digest_email_to: Vec::new(),
+            smtp: None,
+            push_enabled: false,
+            ntfy_url: "https://ntfy.sh".to_string(),
+            gotify_hosts: Vec::new(),
+            web_push_hosts: WEB_PUSH_HOSTS.iter().map(|h| h.to_string()).collect(),
+            push_contact: None,
+            push_daily_hour: 19,
+            consent_by_default: false,
+            deja_vu_probability: 0.0,
+            fate_gravity: 0.0,
+            reveal_beat_chars: 240,
+            reveal_ack_timeout_secs: 8,
+            thinking_beat_delay_ms: 0,
+            card_rate_limit: 0,
+            card_font_dir: None,
+            llm_proxy: None,
+            llm_headers: Vec::new(),
+            llm_client_cert: None,
+            llm_client_key: None,
+            llm_ca_cert: None,
+            llm_sigv4: None,
+        }
+    }
+
+    /// Streak weighting applied to choice scores
+    pub fn streak_curve(&self) -> StreakCurve {
+        StreakCurve {
+            decay: self.streak_decay,
+            swing: self.streak_swing,
+            max_multiplier: self.streak_max_multiplier,
+        }
+    }
+
+    /// Moderation is mandatory in teen mode regardless of `MODERATION_ENABLED`
+    pub fn moderation_active(&self) -> bool {
+        self.moderation_enabled || self.content_rating == ContentRating::Teen
+    }
+}
+
+/// Parse an optional boolean environment variable ("true"/"false", "1"/"0", "yes"/"no")
+fn env_bool(key: &str) -> Option<bool> {
+    match env::var(key).ok()?.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
 }
@@ -0,0 +1,241 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::events::{EventBus, GameEvent};
+use crate::provenance::Provenance;
+
+pub const CHOICE_LOG_DIR: &str = "data/choices";
+
+/// Choices made at least this often count as a pattern
+const REPEAT_THRESHOLD: u64 = 3;
+
+/// Word stems that mark a choice as costing someone their life
+const LETHAL_STEMS: &[&str] = &["kill", "murder", "shoot", "stab", "strangle", "poison", "drown"];
+
+/// One choice as written to the player's event log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChoiceRecord {
+    pub loop_number: u64,
+    pub choice_id: String,
+    pub choice_text: String,
+    pub is_dark: bool,
+    pub score_delta: i32,
+    /// Score after the choice; absent from choices logged before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nihilism_score: Option<i32>,
+    pub at: DateTime<Utc>,
+    /// Who wrote the choice; absent from choices logged before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// Why a choice made it into the ledger
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerReason {
+    /// Among the largest swings in nihilism score
+    Consequential,
+    /// Made again and again across the run
+    Repeated,
+    /// Someone did not survive it
+    Lethal,
+}
+
+/// A consequential choice, looked back on at an ending
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Stable key of the choice (its normalized text)
+    pub key: String,
+    pub choice_text: String,
+    pub times_chosen: u64,
+    pub loops: Vec<u64>,
+    /// Total change in nihilism score across every time it was chosen
+    pub score_delta: i32,
+    pub is_dark: bool,
+    pub reasons: Vec<LedgerReason>,
+    /// The narrator's one-line verdict
+    #[serde(default)]
+    pub judgment: Option<String>,
+}
+
+fn log_path(run_id: &Uuid) -> PathBuf {
+    PathBuf::from(CHOICE_LOG_DIR).join(format!("{}.jsonl", run_id))
+}
+
+fn append(run_id: &Uuid, record: &ChoiceRecord) -> Result<()> {
+    fs::create_dir_all(CHOICE_LOG_DIR)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path(run_id))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+fn load_choices(run_id: &Uuid) -> Result<Vec<ChoiceRecord>> {
+    let path = log_path(run_id);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Every choice logged for a run, oldest first
+pub fn choice_log(run_id: &Uuid) -> Result<Vec<ChoiceRecord>> {
+    load_choices(run_id)
+}
+
+/// Replace a run's choice log, e.g. when restoring a backup
+pub fn restore_choice_log(run_id: &Uuid, records: &[ChoiceRecord]) -> Result<()> {
+    let path = log_path(run_id);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    for record in records {
+        append(run_id, record)?;
+    }
+    Ok(())
+}
+
+/// Text of every choice made in a run, oldest first
+pub fn past_choice_texts(run_id: &Uuid) -> Result<Vec<String>> {
+    Ok(load_choices(run_id)?
+        .into_iter()
+        .map(|record| record.choice_text)
+        .collect())
+}
+
+/// How often a choice with this text was judged dark earlier in the run,
+/// as `(dark, total)`
+pub fn past_judgments(run_id: &Uuid, text: &str) -> Result<(u64, u64)> {
+    let key = choice_key(text);
+    Ok(load_choices(run_id)?
+        .iter()
+        .filter(|record| choice_key(&record.choice_text) == key)
+        .fold((0, 0), |(dark, total), record| {
+            (dark + record.is_dark as u64, total + 1)
+        }))
+}
+
+/// Keep a per-run log of every choice for the ending ledger
+pub fn subscribe(events: &EventBus) {
+    events.spawn_subscriber("choice_log", |envelope| async move {
+        let GameEvent::ChoiceMade {
+            run_id,
+            choice_id,
+            choice_text,
+            loop_number,
+            is_dark,
+            score_delta,
+            nihilism_score,
+            provenance,
+            ..
+        } = &envelope.event
+        else {
+            return;
+        };
+        let record = ChoiceRecord {
+            loop_number: *loop_number,
+            choice_id: choice_id.clone(),
+            choice_text: choice_text.clone(),
+            is_dark: *is_dark,
+            score_delta: *score_delta,
+            nihilism_score: Some(*nihilism_score),
+            at: envelope.at,
+            provenance: provenance.clone(),
+        };
+        if let Err(e) = append(run_id, &record) {
+            tracing::warn!("Failed to log choice for run {}: {}", run_id, e);
+        }
+    });
+}
+
+fn choice_key(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn is_lethal(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    words
+        .iter()
+        .any(|w| LETHAL_STEMS.iter().any(|stem| w.starts_with(stem)))
+        || (words.contains(&"let") && words.contains(&"die"))
+}
+
+/// Compile a run's most consequential choices from its event log.
+///
+/// Takes the `size` largest score swings, plus up to `size` repeated and
+/// `size` lethal choices, ordered by the size of their swing.
+pub fn compile(run_id: &Uuid, size: usize) -> Result<Vec<LedgerEntry>> {
+    if size == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut entries: Vec<LedgerEntry> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for record in load_choices(run_id)? {
+        let key = choice_key(&record.choice_text);
+        if key.is_empty() {
+            continue;
+        }
+        let i = *index.entry(key.clone()).or_insert_with(|| {
+            entries.push(LedgerEntry {
+                key,
+                choice_text: record.choice_text.trim().to_string(),
+                times_chosen: 0,
+                loops: Vec::new(),
+                score_delta: 0,
+                is_dark: record.is_dark,
+                reasons: Vec::new(),
+                judgment: None,
+            });
+            entries.len() - 1
+        });
+        let entry = &mut entries[i];
+        entry.times_chosen += 1;
+        entry.score_delta += record.score_delta;
+        if !entry.loops.contains(&record.loop_number) {
+            entry.loops.push(record.loop_number);
+        }
+    }
+
+    let mut by_swing: Vec<usize> = (0..entries.len()).collect();
+    by_swing.sort_by_key(|&i| std::cmp::Reverse(entries[i].score_delta.abs()));
+
+    let mark = |entries: &mut Vec<LedgerEntry>, reason, matches: &dyn Fn(&LedgerEntry) -> bool| {
+        let picked: Vec<usize> = by_swing
+            .iter()
+            .copied()
+            .filter(|&i| matches(&entries[i]))
+            .take(size)
+            .collect();
+        for i in picked {
+            entries[i].reasons.push(reason);
+        }
+    };
+    mark(&mut entries, LedgerReason::Consequential, &|e| e.score_delta != 0);
+    mark(&mut entries, LedgerReason::Repeated, &|e| e.times_chosen >= REPEAT_THRESHOLD);
+    mark(&mut entries, LedgerReason::Lethal, &|e| is_lethal(&e.choice_text));
+
+    Ok(by_swing
+        .into_iter()
+        .filter(|&i| !entries[i].reasons.is_empty())
+        .map(|i| entries[i].clone())
+        .collect())
+}
@@ -0,0 +1,206 @@
+/// Rough token estimate for prompt budgeting: about four characters per token
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Tokens set aside for each recent moment quoted in the prompt
+pub const HISTORY_TURN_TOKENS: usize = 120;
+
+/// How much of a run's past goes into narrator prompts, so a small local
+/// model isn't handed the context a 128k model can take
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContextProfile {
+    /// Recent moments quoted in the prompt
+    pub history_turns: usize,
+    /// Key memories included, pinned ones first; `None` includes them all
+    pub memories: Option<usize>,
+    /// Whether moments spilled to disk are quoted by their summary or left out
+    pub summaries: bool,
+}
+
+impl Default for ContextProfile {
+    fn default() -> Self {
+        Self {
+            history_turns: 0,
+            memories: None,
+            summaries: true,
+        }
+    }
+}
+
+impl ContextProfile {
+    /// Parse `history:memories:summaries`, e.g. `4:5:off` or `12:all:on`
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut parts = spec.split(':').map(str::trim);
+        let history_turns = parts.next()?.parse().ok()?;
+        let memories = match parts.next()? {
+            "all" => None,
+            count => Some(count.parse().ok()?),
+        };
+        let summaries = match parts.next()? {
+            "on" | "yes" | "true" => true,
+            "off" | "no" | "false" => false,
+            _ => return None,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            history_turns,
+            memories,
+            summaries,
+        })
+    }
+}
+
+/// Context profiles keyed by model name. A key ending in `*` matches every
+/// model starting with the rest of it; `*` alone matches any model.
+#[derive(Clone, Debug, Default)]
+pub struct ContextProfiles {
+    profiles: Vec<(String, ContextProfile)>,
+}
+
+impl ContextProfiles {
+    pub fn new(profiles: Vec<(String, ContextProfile)>) -> Self {
+        Self { profiles }
+    }
+
+    /// Profile for `model`: an exact match, else the longest matching prefix,
+    /// else the default
+    pub fn for_model(&self, model: &str) -> ContextProfile {
+        if let Some((_, profile)) = self.profiles.iter().find(|(key, _)| key == model) {
+            return *profile;
+        }
+        self.profiles
+            .iter()
+            .filter_map(|(key, profile)| {
+                let prefix = key.strip_suffix('*')?;
+                model.starts_with(prefix).then_some((prefix.len(), *profile))
+            })
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, profile)| profile)
+            .unwrap_or_default()
+    }
+}
+
+/// Which lines of a section survive when it runs over budget
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Keep {
+    Oldest,
+    Newest,
+}
+
+struct Section {
+    name: &'static str,
+    priority: u8,
+    budget: usize,
+    header: Option<&'static str>,
+    lines: Vec<String>,
+    keep: Keep,
+}
+
+/// Assembles prompt context from prioritized, individually budgeted sections.
+///
+/// Sections are filled greedily in priority order (0 first), line by line,
+/// until their own budget or the overall budget runs out. They are rendered
+/// in the order they were added; sections left empty are dropped.
+pub struct ContextBuilder {
+    total_budget: usize,
+    sections: Vec<Section>,
+}
+
+impl ContextBuilder {
+    pub fn new(total_budget: usize) -> Self {
+        Self {
+            total_budget,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Add a section; `header` is rendered on its own line after a blank line
+    pub fn section(
+        mut self,
+        name: &'static str,
+        priority: u8,
+        budget: usize,
+        header: Option<&'static str>,
+        lines: impl IntoIterator<Item = String>,
+        keep: Keep,
+    ) -> Self {
+        let lines: Vec<String> = lines.into_iter().collect();
+        if !lines.is_empty() {
+            self.sections.push(Section {
+                name,
+                priority,
+                budget,
+                header,
+                lines,
+                keep,
+            });
+        }
+        self
+    }
+
+    pub fn build(self) -> String {
+        let mut order: Vec<usize> = (0..self.sections.len()).collect();
+        order.sort_by_key(|&i| self.sections[i].priority);
+
+        let mut remaining = self.total_budget;
+        let mut filled: Vec<Vec<&str>> = vec![Vec::new(); self.sections.len()];
+        for i in order {
+            let section = &self.sections[i];
+            let mut used = section.header.map_or(0, |h| estimate_tokens(h) + 1);
+            let mut kept = Vec::new();
+            let candidates: Box<dyn Iterator<Item = &String>> = match section.keep {
+                Keep::Oldest => Box::new(section.lines.iter()),
+                Keep::Newest => Box::new(section.lines.iter().rev()),
+            };
+            for line in candidates {
+                let cost = estimate_tokens(line) + 1;
+                if used + cost > section.budget.min(remaining) {
+                    break;
+                }
+                used += cost;
+                kept.push(line.as_str());
+            }
+            if section.keep == Keep::Newest {
+                kept.reverse();
+            }
+
+            if kept.is_empty() {
+                tracing::debug!("Context section '{}' dropped: over budget", section.name);
+                continue;
+            }
+            if kept.len() < section.lines.len() {
+                tracing::debug!(
+                    "Context section '{}' trimmed to {} of {} lines",
+                    section.name,
+                    kept.len(),
+                    section.lines.len()
+                );
+            }
+            remaining -= used;
+            filled[i] = kept;
+        }
+
+        let mut context = String::new();
+        for (section, lines) in self.sections.iter().zip(filled) {
+            if lines.is_empty() {
+                continue;
+            }
+            if let Some(header) = section.header {
+                context.push('\n');
+                context.push_str(header);
+                context.push('\n');
+            }
+            for line in lines {
+                context.push_str(line);
+                context.push('\n');
+            }
+        }
+        context
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,25 @@
+use super::*;
+
+#[test]
+fn profiles_match_exact_names_before_the_longest_prefix() {
+    let profile = |spec| ContextProfile::parse(spec).unwrap();
+    let profiles = ContextProfiles::new(vec![
+        ("*".to_string(), profile("6:all:on")),
+        ("llama3*".to_string(), profile("4:5:off")),
+        ("llama3-70b*".to_string(), profile("12:20:on")),
+        ("llama3".to_string(), profile("2:3:off")),
+    ]);
+
+    assert_eq!(profiles.for_model("llama3").history_turns, 2);
+    assert_eq!(profiles.for_model("llama3-8b").history_turns, 4);
+    assert_eq!(profiles.for_model("llama3-70b-instruct").memories, Some(20));
+    assert_eq!(profiles.for_model("gpt-4o"), profile("6:all:on"));
+    assert_eq!(
+        ContextProfiles::default().for_model("gpt-4o"),
+        ContextProfile::default()
+    );
+
+    for bad in ["4:5", "x:5:on", "4:5:maybe", "4:5:on:extra"] {
+        assert_eq!(ContextProfile::parse(bad), None, "{}", bad);
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::game::Player;
+
+/// Decay stops growing after this many absence periods
+const MAX_SEVERITY: u64 = 5;
+/// Nihilism points the narrator's trust drifts per absence period
+const TRUST_DRIFT: i32 = 3;
+
+/// Things that happened while nobody was watching
+const ANOMALIES: &[&str] = &[
+    "The clock in the room stopped, and started again an hour behind.",
+    "Someone else's footprints cross the floor and end at the wall.",
+    "A door that was always locked now stands open.",
+    "The same bird has been singing the same three notes since you left.",
+    "Your name has been scratched into a surface you do not remember touching.",
+    "The loop ran once without you. Something in it went differently.",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecayKind {
+    /// A key memory was worn down into a fragment
+    MemoryBlurred,
+    /// The narrator's trust in the player shifted
+    TrustShifted,
+    /// Something happened off-screen
+    Anomaly,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DecayEvent {
+    pub kind: DecayKind,
+    pub description: String,
+}
+
+/// Wear a memory down to a fragment, keeping a few of its words
+fn fragment(memory: &str, rng: &mut StdRng) -> String {
+    let words: Vec<&str> = memory.split_whitespace().collect();
+    let kept: Vec<&str> = words
+        .iter()
+        .copied()
+        .filter(|_| rng.random_bool(0.4))
+        .collect();
+    if kept.is_empty() {
+        return "...".to_string();
+    }
+    format!("...{}...", kept.join("... "))
+}
+
+/// Apply decay for the time since the player was last active.
+///
+/// Severity grows with each `after_hours` period of absence. Events are
+/// seeded by the player id and absence length, so the same absence always
+/// decays the same way. They reach the narrator as one-time notes, so the
+/// first moment back acknowledges them.
+pub fn apply(player: &mut Player, now: DateTime<Utc>, after_hours: u64) -> Vec<DecayEvent> {
+    if after_hours == 0 || player.is_locked() {
+        return Vec::new();
+    }
+    let hours_away = (now - player.last_active()).num_hours().max(0) as u64;
+    let severity = (hours_away / after_hours).min(MAX_SEVERITY);
+    if severity == 0 {
+        return Vec::new();
+    }
+
+    let id = player.id.as_u128();
+    let mut rng = StdRng::seed_from_u64((id >> 64) as u64 ^ id as u64 ^ hours_away);
+    let mut events = Vec::new();
+
+    let intact: Vec<usize> = (0..player.run.memory.key_memories.len())
+        .filter(|&i| {
+            !player.run.memory.key_memories[i].starts_with("...")
+                && !player.run.memory.is_pinned(i)
+        })
+        .collect();
+    for &i in intact.choose_multiple(&mut rng, severity as usize) {
+        let memory = &mut player.run.memory.key_memories[i];
+        *memory = fragment(memory, &mut rng);
+        events.push(DecayEvent {
+            kind: DecayKind::MemoryBlurred,
+            description: format!("A memory has blurred into a fragment: \"{}\"", memory),
+        });
+    }
+
+    let drift = TRUST_DRIFT * severity as i32 * if rng.random_bool(0.5) { 1 } else { -1 };
+    player.run.memory.nihilism_score = (player.run.memory.nihilism_score + drift).clamp(-100, 100);
+    events.push(DecayEvent {
+        kind: DecayKind::TrustShifted,
+        description: if drift > 0 {
+            "The narrator trusts you less than before; your absence felt like abandonment."
+        } else {
+            "The narrator trusts you more than before; you came back when it did not expect you to."
+        }
+        .to_string(),
+    });
+
+    if severity >= 2
+        && let Some(anomaly) = ANOMALIES.choose(&mut rng)
+    {
+        events.push(DecayEvent {
+            kind: DecayKind::Anomaly,
+            description: anomaly.to_string(),
+        });
+    }
+
+    player.run.pending_notes.push(format!(
+        "The player has been away for {} days. While they were gone: {} Weave this into the \
+         moment as the loop's memory of their absence.",
+        hours_away / 24,
+        events
+            .iter()
+            .map(|e| e.description.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    ));
+    player.run.last_active_at = Some(now);
+    events
+}
@@ -0,0 +1,96 @@
+//! What the player has said to each character across loops, so a
+//! conversation with one of them picks up where it left off.
+//!
+//! Characters are told apart by the `speaker` of the moment the player was
+//! answering. The memory of a character is only put in front of the
+//! narrator while that character is in the scene.
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::Player;
+
+/// Lines kept per character, oldest dropped first
+pub const MAX_LINES: usize = 8;
+/// Longer lines are cut to this many characters
+const MAX_LINE_CHARS: usize = 160;
+/// Speakers that are not characters the player can talk to
+const NOT_CHARACTERS: &[&str] = &["narrator", "you", "the narrator", "voice"];
+
+/// A character's side of the story so far
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CharacterMemory {
+    /// The name as the narrator last wrote it
+    pub name: String,
+    /// What the player said or did in answer to them, oldest first
+    pub said: Vec<String>,
+    /// Mood of the latest moment the player answered them in
+    pub last_mood: String,
+    /// Loop the player first answered them in
+    pub first_loop: u64,
+}
+
+/// The key a speaker is remembered under, so "The Stranger", "stranger" and
+/// "STRANGER " are one character
+pub fn canonical(speaker: &str) -> Option<String> {
+    let name = speaker.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    if name.is_empty() || NOT_CHARACTERS.contains(&name.as_str()) {
+        return None;
+    }
+    let name = name.strip_prefix("the ").unwrap_or(&name);
+    Some(name.to_string())
+}
+
+/// Remember what the player said to whoever spoke in the moment they are
+/// answering
+pub fn remember(player: &mut Player, said: &str) {
+    let Some(moment) = player.run.narrative_history.last() else {
+        return;
+    };
+    let Some((key, name)) = moment
+        .speaker
+        .as_deref()
+        .and_then(|s| Some((canonical(s)?, s.trim().to_string())))
+    else {
+        return;
+    };
+    let mood = moment.mood.clone();
+    let loop_number = player.run.current_loop.number;
+
+    let memory = player.run.memory.characters.entry(key).or_insert_with(|| CharacterMemory {
+        first_loop: loop_number,
+        ..Default::default()
+    });
+    memory.name = name;
+    memory.last_mood = mood;
+    memory.said.push(said.chars().take(MAX_LINE_CHARS).collect());
+    if memory.said.len() > MAX_LINES {
+        memory.said.remove(0);
+    }
+}
+
+/// The memory of the character speaking in the latest moment, if any
+pub fn in_scene(player: &Player) -> Option<&CharacterMemory> {
+    let speaker = player.run.narrative_history.last()?.speaker.as_deref()?;
+    player.run.memory.characters.get(&canonical(speaker)?)
+}
+
+/// Narrator context naming the character in the scene and how they were last
+pub fn scene_lines(player: &Player) -> Vec<String> {
+    let Some(memory) = in_scene(player) else {
+        return Vec::new();
+    };
+    vec![format!(
+        "- {}, first met in loop #{}, last {}",
+        memory.name, memory.first_loop, memory.last_mood
+    )]
+}
+
+/// Narrator context quoting what the player has said to the character in the scene
+pub fn said_lines(player: &Player) -> Vec<String> {
+    in_scene(player)
+        .map(|memory| memory.said.iter().map(|line| format!("- \"{}\"", line)).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,54 @@
+use super::*;
+use crate::offline;
+
+/// Show the player a moment spoken by `speaker`
+fn meet(player: &mut Player, speaker: Option<&str>, mood: &str) {
+    let mut moment = offline::moment(player);
+    moment.speaker = speaker.map(str::to_string);
+    moment.mood = mood.to_string();
+    player.run.narrative_history.push(moment);
+}
+
+#[test]
+fn speakers_are_one_character_however_they_are_written() {
+    assert_eq!(canonical("The Stranger"), Some("stranger".to_string()));
+    assert_eq!(canonical("  STRANGER "), Some("stranger".to_string()));
+    assert_eq!(canonical("old   Marta"), Some("old marta".to_string()));
+    assert_eq!(canonical("Narrator"), None);
+    assert_eq!(canonical(" "), None);
+}
+
+#[test]
+fn memory_follows_its_character_into_later_scenes() {
+    let mut player = Player::new();
+    meet(&mut player, Some("The Stranger"), "dark");
+    remember(&mut player, "Who are you?");
+    meet(&mut player, None, "neutral");
+    remember(&mut player, "Look away");
+    assert!(scene_lines(&player).is_empty());
+
+    player.run.current_loop.number = 3;
+    meet(&mut player, Some("stranger"), "hopeful");
+    remember(&mut player, "I remember you.");
+
+    assert_eq!(
+        scene_lines(&player),
+        vec!["- stranger, first met in loop #1, last hopeful".to_string()]
+    );
+    assert_eq!(
+        said_lines(&player),
+        vec!["- \"Who are you?\"".to_string(), "- \"I remember you.\"".to_string()]
+    );
+}
+
+#[test]
+fn only_the_latest_lines_are_kept() {
+    let mut player = Player::new();
+    meet(&mut player, Some("Marta"), "neutral");
+    for i in 0..MAX_LINES + 3 {
+        remember(&mut player, &format!("line {}", i));
+    }
+    let said = &player.run.memory.characters["marta"].said;
+    assert_eq!(said.len(), MAX_LINES);
+    assert_eq!(said[0], "line 3");
+}
@@ -0,0 +1,259 @@
+//! Support diffs: what changed in a run between two of its choices, rebuilt
+//! from the run's choice log and loop archives, so a ticket like "my score
+//! jumped 40 points" can be traced to the choices, or absences, behind it.
+//!
+//! Checkpoint `n` is the run right after its `n`th choice; checkpoint 0 is
+//! its start.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::consequences::ChoiceRecord;
+use crate::game::{ArchivedLoop, Player};
+
+#[derive(Debug, PartialEq)]
+pub enum DiffError {
+    /// A checkpoint past the run's latest choice
+    OutOfRange { choices: u64 },
+    /// The first checkpoint comes after the second
+    Reversed,
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange { choices } => write!(f, "the run has only {} choices", choices),
+            Self::Reversed => write!(f, "from_choice comes after to_choice"),
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+/// One choice between the checkpoints
+#[derive(Clone, Debug, Serialize)]
+pub struct Step {
+    pub choice: u64,
+    pub loop_number: u64,
+    pub choice_text: String,
+    pub is_dark: bool,
+    pub score_delta: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nihilism_score: Option<i32>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Counter {
+    pub name: &'static str,
+    pub from: i64,
+    pub to: i64,
+}
+
+/// A key memory the run kept from a loop that ended between the checkpoints
+#[derive(Clone, Debug, Serialize)]
+pub struct KeptMemory {
+    pub loop_number: u64,
+    pub memory: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SaveDiff {
+    pub player_id: Uuid,
+    pub run_id: Uuid,
+    pub from_choice: u64,
+    pub to_choice: u64,
+    /// Plain sentences for a support reply
+    pub summary: Vec<String>,
+    pub score_from: i32,
+    pub score_to: i32,
+    /// The part of the score change the choices in between don't account for
+    pub score_outside_choices: i32,
+    /// Scores were rebuilt from today's score, as the log predates recorded scores
+    pub estimated: bool,
+    pub counters: Vec<Counter>,
+    pub memories_added: Vec<KeptMemory>,
+    pub steps: Vec<Step>,
+}
+
+/// Score at checkpoint `n` and whether it had to be estimated
+fn score_at(player: &Player, log: &[ChoiceRecord], n: usize) -> (i32, bool) {
+    let recorded = match n {
+        0 => log.first().and_then(|r| r.nihilism_score.map(|s| s - r.score_delta)),
+        n => log[n - 1].nihilism_score,
+    };
+    match recorded {
+        Some(score) => (score, false),
+        None if log.is_empty() => (player.run.memory.nihilism_score, false),
+        None => {
+            let later: i32 = log[n..].iter().map(|r| r.score_delta).sum();
+            (player.run.memory.nihilism_score - later, true)
+        }
+    }
+}
+
+/// Loop the run was in at checkpoint `n`
+fn loop_at(player: &Player, log: &[ChoiceRecord], n: usize) -> u64 {
+    match n {
+        0 => log
+            .first()
+            .map_or(player.run.current_loop.number, |r| r.loop_number),
+        n => log[n - 1].loop_number,
+    }
+}
+
+fn counters_at(player: &Player, log: &[ChoiceRecord], n: usize) -> [(&'static str, i64); 4] {
+    let dark = log[..n].iter().filter(|r| r.is_dark).count() as i64;
+    [
+        ("choices", n as i64),
+        ("dark_choices", dark),
+        ("light_choices", n as i64 - dark),
+        ("loop", loop_at(player, log, n) as i64),
+    ]
+}
+
+/// What changed in `player`'s active run between checkpoints `from` and
+/// `to`, given the run's choice log and archived loops
+pub fn between(
+    player: &Player,
+    log: &[ChoiceRecord],
+    archives: &[ArchivedLoop],
+    from: u64,
+    to: u64,
+) -> Result<SaveDiff, DiffError> {
+    let choices = log.len() as u64;
+    if to > choices {
+        return Err(DiffError::OutOfRange { choices });
+    }
+    if from > to {
+        return Err(DiffError::Reversed);
+    }
+    let (start, end) = (from as usize, to as usize);
+
+    let (score_from, estimated_from) = score_at(player, log, start);
+    let (score_to, estimated_to) = score_at(player, log, end);
+    let between = &log[start..end];
+    let by_choices: i32 = between.iter().map(|r| r.score_delta).sum();
+    let steps: Vec<Step> = between
+        .iter()
+        .enumerate()
+        .map(|(i, r)| Step {
+            choice: from + i as u64 + 1,
+            loop_number: r.loop_number,
+            choice_text: r.choice_text.clone(),
+            is_dark: r.is_dark,
+            score_delta: r.score_delta,
+            nihilism_score: r.nihilism_score,
+            at: r.at,
+        })
+        .collect();
+
+    let counters: Vec<Counter> = counters_at(player, log, start)
+        .into_iter()
+        .zip(counters_at(player, log, end))
+        .map(|((name, from), (_, to))| Counter { name, from, to })
+        .collect();
+
+    let (loop_from, loop_to) = (loop_at(player, log, start), loop_at(player, log, end));
+    let ended: Vec<&ArchivedLoop> = archives
+        .iter()
+        .filter(|a| (loop_from..loop_to).contains(&a.loop_info.number))
+        .collect();
+    let memories_added: Vec<KeptMemory> = ended
+        .iter()
+        .filter_map(|a| {
+            let last = a.moments.last()?;
+            player
+                .run
+                .memory
+                .key_memories
+                .contains(&last.text)
+                .then(|| KeptMemory {
+                    loop_number: a.loop_info.number,
+                    memory: last.text.clone(),
+                })
+        })
+        .collect();
+
+    let mut diff = SaveDiff {
+        player_id: player.id,
+        run_id: player.run_id(),
+        from_choice: from,
+        to_choice: to,
+        summary: Vec::new(),
+        score_from,
+        score_to,
+        score_outside_choices: score_to - score_from - by_choices,
+        estimated: estimated_from || estimated_to,
+        counters,
+        memories_added,
+        steps,
+    };
+    diff.summary = summarize(&diff, ended.len());
+    Ok(diff)
+}
+
+fn summarize(diff: &SaveDiff, loops_ended: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let Some(first) = diff.steps.first() else {
+        lines.push(format!(
+            "No choices between checkpoints {} and {}; the score stood at {}.",
+            diff.from_choice, diff.to_choice, diff.score_to
+        ));
+        return lines;
+    };
+    let last = diff.steps.last().unwrap_or(first);
+    let loops = if first.loop_number == last.loop_number {
+        format!("in loop {}", first.loop_number)
+    } else {
+        format!("in loops {} to {}", first.loop_number, last.loop_number)
+    };
+    let dark = diff.steps.iter().filter(|s| s.is_dark).count();
+    lines.push(format!(
+        "The score went from {} to {} ({:+}) over {} choices {}: {} dark, {} light.",
+        diff.score_from,
+        diff.score_to,
+        diff.score_to - diff.score_from,
+        diff.steps.len(),
+        loops,
+        dark,
+        diff.steps.len() - dark
+    ));
+    if diff.score_outside_choices != 0 {
+        lines.push(format!(
+            "{:+} of that came from outside the choices, such as decay after an absence or an admin edit.",
+            diff.score_outside_choices
+        ));
+    }
+    if let Some(swing) = diff.steps.iter().max_by_key(|s| s.score_delta.abs())
+        && swing.score_delta != 0
+    {
+        lines.push(format!(
+            "The largest swing was {:+}, at choice {} in loop {}: \"{}\" ({}).",
+            swing.score_delta,
+            swing.choice,
+            swing.loop_number,
+            swing.choice_text,
+            if swing.is_dark { "dark" } else { "light" }
+        ));
+    }
+    if loops_ended > 0 {
+        lines.push(format!(
+            "Loops ended in between: {}; key memories kept from them: {}.",
+            loops_ended,
+            diff.memories_added.len()
+        ));
+    }
+    if diff.estimated {
+        lines.push(
+            "Scores are estimated from today's score, as these choices were logged before scores were recorded."
+                .to_string(),
+        );
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests;
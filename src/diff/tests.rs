@@ -0,0 +1,90 @@
+use super::*;
+use crate::game::Loop;
+use crate::offline;
+
+fn record(loop_number: u64, text: &str, score_delta: i32, nihilism_score: Option<i32>) -> ChoiceRecord {
+    ChoiceRecord {
+        loop_number,
+        choice_id: "c1".to_string(),
+        choice_text: text.to_string(),
+        is_dark: score_delta > 0,
+        score_delta,
+        nihilism_score,
+        at: Utc::now(),
+        provenance: None,
+    }
+}
+
+#[test]
+fn a_jump_is_traced_to_its_choices_and_to_drift() {
+    let mut player = Player::new();
+    player.run.memory.nihilism_score = 49;
+    let log = vec![
+        record(1, "Stay with her", -3, Some(-3)),
+        record(1, "Walk away", 5, Some(2)),
+        record(2, "Burn the letters", 20, Some(22)),
+        // An absence drifted the score by 9 before this choice
+        record(2, "Say nothing", 18, Some(49)),
+    ];
+
+    let diff = between(&player, &log, &[], 1, 4).unwrap();
+    assert_eq!((diff.score_from, diff.score_to), (-3, 49));
+    assert_eq!(diff.score_outside_choices, 9);
+    assert!(!diff.estimated);
+    assert_eq!(diff.steps.len(), 3);
+    assert_eq!(diff.steps[0].choice, 2);
+    assert_eq!(
+        diff.counters[1],
+        Counter {
+            name: "dark_choices",
+            from: 0,
+            to: 3
+        }
+    );
+    assert!(diff.summary[0].starts_with("The score went from -3 to 49 (+52) over 3 choices in loops 1 to 2"));
+    assert!(diff.summary[1].starts_with("+9 of that came from outside the choices"));
+    assert!(diff.summary[2].contains("\"Burn the letters\""));
+}
+
+#[test]
+fn old_logs_are_estimated_from_the_current_score() {
+    let mut player = Player::new();
+    player.run.memory.nihilism_score = 10;
+    let log = vec![record(1, "Walk away", 4, None), record(1, "Run", 6, None)];
+
+    let diff = between(&player, &log, &[], 0, 2).unwrap();
+    assert_eq!((diff.score_from, diff.score_to), (0, 10));
+    assert_eq!(diff.score_outside_choices, 0);
+    assert!(diff.estimated);
+
+    assert_eq!(
+        between(&player, &log, &[], 0, 3).unwrap_err(),
+        DiffError::OutOfRange { choices: 2 }
+    );
+    assert_eq!(between(&player, &log, &[], 2, 1).unwrap_err(), DiffError::Reversed);
+}
+
+#[test]
+fn memories_kept_from_ended_loops_are_listed() {
+    let mut player = Player::new();
+    let moment = offline::moment(&player);
+    player.run.memory.key_memories.push(moment.text.clone());
+    let archived = ArchivedLoop {
+        player_id: player.id,
+        loop_info: Loop {
+            number: 1,
+            ..player.run.current_loop.clone()
+        },
+        moments: vec![moment.clone()],
+        archived_at: Utc::now(),
+        shard: None,
+    };
+    let log = vec![record(1, "Walk away", 5, Some(5)), record(2, "Stay", -3, Some(2))];
+
+    let diff = between(&player, &log, &[archived], 1, 2).unwrap();
+    assert_eq!(diff.memories_added.len(), 1);
+    assert_eq!(diff.memories_added[0].memory, moment.text);
+    assert!(diff.summary.iter().any(|l| l.starts_with("Loops ended in between: 1; key memories kept from them: 1")));
+
+    assert!(between(&player, &log, &[], 0, 1).unwrap().memories_added.is_empty());
+}
@@ -0,0 +1,373 @@
+//! The nightly digest: a day's new players, endings, darkest moment, token
+//! spend and errors, so operators of small instances get a daily pulse
+//! without building dashboards.
+//!
+//! Game events are tallied per UTC day as they happen, and warnings and
+//! errors are counted by a tracing layer. The `nightly_digest` job writes the
+//! previous day's digest to `data/digests/{date}.json` once, then posts it to
+//! `DIGEST_WEBHOOK_URL` and mails it to `DIGEST_EMAIL_TO`.
+
+use anyhow::Result;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use crate::config::{Config, SmtpConfig};
+use crate::events::{EventBus, GameEvent};
+use crate::game::{GameState, Player};
+use crate::privacy::{self, Purpose};
+use crate::sanitize::Sanitizer;
+use crate::tenant;
+use crate::usage::{CostLine, UsageTracker};
+
+const DIGEST_DIR: &str = "data/digests";
+/// The darkest moment is cut to this many characters
+const EXCERPT_CHARS: usize = 280;
+/// Days of warning and error counts kept in memory
+const KEEP_DAYS: usize = 7;
+
+type ErrorKey = (&'static str, &'static str);
+
+/// Warnings and errors logged per day, by level and target
+static ERRORS: LazyLock<Mutex<BTreeMap<NaiveDate, BTreeMap<ErrorKey, u64>>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+fn errors() -> std::sync::MutexGuard<'static, BTreeMap<NaiveDate, BTreeMap<ErrorKey, u64>>> {
+    ERRORS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Tracing layer counting the warnings and errors logged each day
+pub struct ErrorTally;
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for ErrorTally {
+    fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() > tracing::Level::WARN {
+            return;
+        }
+        let mut errors = errors();
+        *errors
+            .entry(Utc::now().date_naive())
+            .or_default()
+            .entry((metadata.level().as_str(), metadata.target()))
+            .or_default() += 1;
+        while errors.len() > KEEP_DAYS {
+            errors.pop_first();
+        }
+    }
+}
+
+/// The moment in which the day's darkest choice was made
+#[derive(Clone, Debug, Serialize)]
+pub struct DarkestMoment {
+    /// Nihilism the choice added
+    pub score_delta: i32,
+    pub loop_number: u64,
+    pub excerpt: String,
+}
+
+#[derive(Default)]
+struct DayTally {
+    new_players: u64,
+    choices: u64,
+    loop_resets: u64,
+    endings: BTreeMap<String, u64>,
+    runs_completed: u64,
+    darkest: Option<DarkestMoment>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ErrorCount {
+    pub level: &'static str,
+    pub target: &'static str,
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Digest {
+    pub date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// The server started during the day, so its earlier part is missing
+    pub partial: bool,
+    pub new_players: u64,
+    pub choices: u64,
+    pub loop_resets: u64,
+    /// Endings reached, by ending
+    pub endings: BTreeMap<String, u64>,
+    pub runs_completed: u64,
+    pub darkest_moment: Option<DarkestMoment>,
+    pub spend: CostLine,
+    /// Warnings and errors logged by the whole deployment, most frequent first
+    pub errors: Vec<ErrorCount>,
+}
+
+impl Digest {
+    /// Plain text rendering, for email
+    pub fn text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "Nihilism digest for {}", self.date);
+        if let Some(tenant) = &self.tenant {
+            let _ = writeln!(out, "Tenant: {}", tenant);
+        }
+        if self.partial {
+            let _ = writeln!(out, "(partial: the server started during the day)");
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(out, "New players: {}", self.new_players);
+        let _ = writeln!(out, "Choices made: {}", self.choices);
+        let _ = writeln!(out, "Loop resets: {}", self.loop_resets);
+        let _ = writeln!(out, "Runs completed: {}", self.runs_completed);
+        if !self.endings.is_empty() {
+            let _ = writeln!(out, "Endings reached:");
+            for (ending, count) in &self.endings {
+                let _ = writeln!(out, "  {}: {}", ending, count);
+            }
+        }
+        let _ = writeln!(
+            out,
+            "Token spend: {} requests, {} prompt and {} completion tokens, ${:.2}{}",
+            self.spend.usage.requests,
+            self.spend.usage.prompt_tokens,
+            self.spend.usage.completion_tokens,
+            self.spend.cost_usd,
+            if self.spend.priced { "" } else { " (some models unpriced)" }
+        );
+        if let Some(darkest) = &self.darkest_moment {
+            let _ = writeln!(out);
+            let _ = writeln!(
+                out,
+                "Darkest moment (loop #{}, +{}):\n  {}",
+                darkest.loop_number, darkest.score_delta, darkest.excerpt
+            );
+        }
+        let _ = writeln!(out);
+        if self.errors.is_empty() {
+            let _ = writeln!(out, "No warnings or errors.");
+        } else {
+            let _ = writeln!(out, "Warnings and errors:");
+            for error in &self.errors {
+                let _ = writeln!(out, "  {} {}: {}", error.level, error.target, error.count);
+            }
+        }
+        out
+    }
+}
+
+/// A tenant's game events, tallied per day for the digest
+pub struct DigestCounters {
+    since: DateTime<Utc>,
+    days: Mutex<BTreeMap<NaiveDate, DayTally>>,
+}
+
+impl DigestCounters {
+    pub fn new() -> Self {
+        Self {
+            since: Utc::now(),
+            days: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn days(&self) -> std::sync::MutexGuard<'_, BTreeMap<NaiveDate, DayTally>> {
+        self.days.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether a dark choice would be the darkest of its day
+    fn is_darkest(&self, date: NaiveDate, score_delta: i32) -> bool {
+        self.days()
+            .get(&date)
+            .and_then(|day| day.darkest.as_ref())
+            .is_none_or(|darkest| score_delta > darkest.score_delta)
+    }
+
+    /// Count an event, with the text of the moment a dark choice was made in
+    fn count(&self, date: NaiveDate, event: &GameEvent, moment: Option<String>) {
+        let mut days = self.days();
+        let day = days.entry(date).or_default();
+        match event {
+            GameEvent::PlayerCreated { .. } => day.new_players += 1,
+            GameEvent::ChoiceMade {
+                loop_number,
+                score_delta,
+                ..
+            } => {
+                day.choices += 1;
+                if let Some(text) = moment
+                    && day.darkest.as_ref().is_none_or(|d| *score_delta > d.score_delta)
+                {
+                    day.darkest = Some(DarkestMoment {
+                        score_delta: *score_delta,
+                        loop_number: *loop_number,
+                        excerpt: text.chars().take(EXCERPT_CHARS).collect(),
+                    });
+                }
+            }
+            GameEvent::LoopReset { .. } => day.loop_resets += 1,
+            GameEvent::EndingReached { ending, .. } => {
+                *day.endings.entry(format!("{:?}", ending)).or_default() += 1;
+            }
+            GameEvent::RunCompleted { .. } => day.runs_completed += 1,
+            _ => {}
+        }
+    }
+
+    /// Tally the events of this tenant. The darkest moment is only taken
+    /// from players who allow their transcripts to be kept.
+    pub fn subscribe(self: &Arc<Self>, events: &EventBus, game: Arc<RwLock<GameState>>) {
+        let counters = self.clone();
+        events.spawn_subscriber("digest", move |envelope| {
+            let counters = counters.clone();
+            let game = game.clone();
+            async move {
+                let date = envelope.at.date_naive();
+                let moment = match &envelope.event {
+                    GameEvent::ChoiceMade {
+                        player_id,
+                        choice_id,
+                        is_dark: true,
+                        score_delta,
+                        ..
+                    } if counters.is_darkest(date, *score_delta) => game
+                        .read()
+                        .await
+                        .get_player(player_id)
+                        .filter(|p| privacy::policy().allows(p, Purpose::Transcripts))
+                        .and_then(|p| answered_moment(p, choice_id)),
+                    _ => None,
+                };
+                counters.count(date, &envelope.event, moment);
+            }
+        });
+    }
+
+    /// The digest of a day, or `None` if the server wasn't up that day
+    fn digest(
+        &self,
+        config: &Config,
+        date: NaiveDate,
+        usage: &UsageTracker,
+        sanitizer: &Sanitizer,
+    ) -> Option<Digest> {
+        if self.since.date_naive() > date {
+            return None;
+        }
+        let day = self.days().remove(&date).unwrap_or_default();
+        let mut errors: Vec<ErrorCount> = errors()
+            .get(&date)
+            .into_iter()
+            .flatten()
+            .map(|(&(level, target), &count)| ErrorCount {
+                level,
+                target,
+                count,
+            })
+            .collect();
+        errors.sort_by(|a, b| b.count.cmp(&a.count).then(a.target.cmp(b.target)));
+        Some(Digest {
+            date,
+            tenant: config.tenant.clone(),
+            partial: self.since.date_naive() == date,
+            new_players: day.new_players,
+            choices: day.choices,
+            loop_resets: day.loop_resets,
+            endings: day.endings,
+            runs_completed: day.runs_completed,
+            darkest_moment: day.darkest.map(|mut darkest| {
+                darkest.excerpt = sanitizer.scrub("digest", &darkest.excerpt);
+                darkest
+            }),
+            spend: usage.day(date),
+            errors,
+        })
+    }
+
+    /// Forget the tallies of days up to `date`
+    fn forget_through(&self, date: NaiveDate) {
+        self.days().retain(|day, _| *day > date);
+    }
+}
+
+/// Text of the latest moment that offered `choice_id`
+fn answered_moment(player: &Player, choice_id: &str) -> Option<String> {
+    player
+        .run
+        .narrative_history
+        .iter()
+        .rev()
+        .find(|m| m.choices.iter().any(|c| c.id == choice_id))
+        .map(|m| m.text.clone())
+}
+
+/// Write yesterday's digest, unless it already was, and deliver it. Returns
+/// the digest's path when one was written.
+pub async fn run(
+    config: &Config,
+    counters: &DigestCounters,
+    usage: &UsageTracker,
+    sanitizer: &Sanitizer,
+) -> Result<Option<PathBuf>> {
+    let Some(date) = Utc::now().date_naive().checked_sub_days(Days::new(1)) else {
+        return Ok(None);
+    };
+    let dir = tenant::data_path(config.tenant.as_deref(), DIGEST_DIR);
+    let path = dir.join(format!("{}.json", date));
+    if path.exists() {
+        return Ok(None);
+    }
+    let Some(digest) = counters.digest(config, date, usage, sanitizer) else {
+        return Ok(None);
+    };
+    fs::create_dir_all(&dir)?;
+    fs::write(&path, serde_json::to_string_pretty(&digest)?)?;
+    counters.forget_through(date);
+
+    if let Some(url) = &config.digest_webhook_url {
+        post(url, &digest).await?;
+    }
+    if let Some(smtp) = &config.smtp
+        && !config.digest_email_to.is_empty()
+    {
+        let subject = format!("Nihilism digest for {}", digest.date);
+        mail(smtp, &config.digest_email_to, &subject, digest.text()).await?;
+    }
+    Ok(Some(path))
+}
+
+async fn post(url: &str, digest: &Digest) -> Result<()> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?
+        .post(url)
+        .json(digest)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Send a plain text mail through the SMTP relay
+pub async fn mail(smtp: &SmtpConfig, to: &[String], subject: &str, body: String) -> Result<()> {
+    let mut message = Message::builder().from(smtp.from.parse()?).subject(subject);
+    for address in to {
+        message = message.to(address.parse()?);
+    }
+    let message = message.body(body)?;
+
+    let mut transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?.port(smtp.port);
+    if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    transport.build().send(message).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,107 @@
+use super::*;
+use crate::config::SanitizeLevel;
+use crate::endings::EndingType;
+use uuid::Uuid;
+
+fn config() -> Config {
+    Config::for_tests("http://127.0.0.1:9/v1")
+}
+
+fn choice(score_delta: i32) -> GameEvent {
+    GameEvent::ChoiceMade {
+        player_id: Uuid::new_v4(),
+        run_id: Uuid::new_v4(),
+        choice_id: "c1".to_string(),
+        choice_text: "Let it go".to_string(),
+        loop_number: 3,
+        is_dark: true,
+        score_delta,
+        nihilism_score: 40,
+        provenance: None,
+    }
+}
+
+fn counters_since(since: DateTime<Utc>) -> DigestCounters {
+    DigestCounters {
+        since,
+        days: Mutex::new(BTreeMap::new()),
+    }
+}
+
+#[test]
+fn the_darkest_choice_of_the_day_is_kept() {
+    let config = config();
+    let date = NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+    let counters = counters_since(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    let player_id = Uuid::new_v4();
+
+    counters.count(date, &GameEvent::PlayerCreated { player_id }, None);
+    counters.count(date, &choice(5), Some("A small cruelty.".to_string()));
+    assert!(counters.is_darkest(date, 12));
+    counters.count(date, &choice(12), Some("The void, mail me at void@example.com".to_string()));
+    assert!(!counters.is_darkest(date, 7));
+    counters.count(date, &choice(7), None);
+    counters.count(
+        date,
+        &GameEvent::EndingReached {
+            player_id,
+            ending: EndingType::VoidEmbrace,
+            first_time: true,
+        },
+        None,
+    );
+
+    let usage = UsageTracker::new(&config);
+    let sanitizer = Sanitizer::new(SanitizeLevel::Standard);
+    let digest = counters.digest(&config, date, &usage, &sanitizer).unwrap();
+    assert_eq!(digest.new_players, 1);
+    assert_eq!(digest.choices, 3);
+    assert_eq!(digest.endings.get("VoidEmbrace"), Some(&1));
+
+    let darkest = digest.darkest_moment.as_ref().unwrap();
+    assert_eq!(darkest.score_delta, 12);
+    assert!(darkest.excerpt.starts_with("The void"));
+    assert!(!darkest.excerpt.contains("void@example.com"));
+
+    let text = digest.text();
+    assert!(text.contains("Nihilism digest for 2026-03-14"));
+    assert!(text.contains("VoidEmbrace: 1"));
+    assert!(text.contains("Darkest moment (loop #3, +12)"));
+}
+
+#[test]
+fn days_before_the_server_started_have_no_digest() {
+    let config = config();
+    let usage = UsageTracker::new(&config);
+    let sanitizer = Sanitizer::new(SanitizeLevel::Off);
+    let since = NaiveDate::from_ymd_opt(2026, 3, 14)
+        .unwrap()
+        .and_hms_opt(15, 30, 0)
+        .unwrap()
+        .and_utc();
+    let counters = counters_since(since);
+
+    let day_before = since.date_naive().pred_opt().unwrap();
+    assert!(counters.digest(&config, day_before, &usage, &sanitizer).is_none());
+
+    let digest = counters.digest(&config, since.date_naive(), &usage, &sanitizer).unwrap();
+    assert!(digest.partial);
+    assert!(digest.text().contains("(partial"));
+
+    let next_day = since.date_naive().succ_opt().unwrap();
+    assert!(!counters.digest(&config, next_day, &usage, &sanitizer).unwrap().partial);
+}
+
+#[test]
+fn counted_days_are_forgotten_once_digested() {
+    let counters = counters_since(Utc::now());
+    let today = Utc::now().date_naive();
+    let yesterday = today.pred_opt().unwrap();
+    counters.count(yesterday, &choice(1), None);
+    counters.count(today, &choice(1), None);
+
+    counters.forget_through(yesterday);
+    let days = counters.days();
+    assert!(!days.contains_key(&yesterday));
+    assert_eq!(days[&today].choices, 1);
+}
@@ -1,9 +1,20 @@
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
 
+use crate::conditions::{Condition, ConditionContext};
+use crate::config::ContentRating;
+use crate::consequences::LedgerEntry;
 use crate::game::Player;
+use crate::i18n::{self, Locale, Text};
+use crate::plugins::{self, PluginEnding};
+use crate::rarity::EndingRarity;
+use crate::tenant::{PerTenant, TenantConfigs};
 
 /// Ending types based on cumulative choices and nihilism score
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum EndingType {
     /// True nihilism - embraced the void completely
     VoidEmbrace,
@@ -19,112 +30,461 @@ pub enum EndingType {
     TheWatcher,
     /// Secret ending - perfect balance
     TheMiddlePath,
+    /// An ending a plugin registered, by its id
+    #[serde(untagged)]
+    Plugin(String),
 }
 
 impl EndingType {
-    pub fn get_description(&self) -> &'static str {
+    pub fn get_description(&self, locale: Locale) -> &'static str {
+        if let EndingType::Plugin(id) = self {
+            return plugin_ending(id).map_or("", |e| &e.spec.description);
+        }
+        i18n::text(locale, Text::EndingDescription(self))
+    }
+
+    /// Description adjusted for the deployment's content rating
+    pub fn get_description_for(&self, rating: ContentRating, locale: Locale) -> &'static str {
+        match rating {
+            ContentRating::Teen => i18n::lookup(locale, Text::EndingDescriptionTeen(self))
+                .unwrap_or_else(|| self.get_description(locale)),
+            _ => self.get_description(locale),
+        }
+    }
+
+    pub fn get_title(&self, locale: Locale) -> &'static str {
+        if let EndingType::Plugin(id) = self {
+            return plugin_ending(id).map_or("ENDING", |e| &e.spec.title);
+        }
+        i18n::text(locale, Text::EndingTitle(self))
+    }
+
+    /// Whether the ending is built in, or registered by a loaded plugin
+    pub fn is_known(&self) -> bool {
+        match self {
+            EndingType::Plugin(id) => plugin_ending(id).is_some(),
+            _ => true,
+        }
+    }
+
+    /// Every ending in the order they are checked: plugin endings first, so
+    /// they can claim states a broad built-in ending would also match
+    pub fn all() -> Vec<EndingType> {
+        plugins::host()
+            .endings()
+            .iter()
+            .map(|e| EndingType::Plugin(e.spec.id.clone()))
+            .chain(EndingType::ALL)
+            .collect()
+    }
+
+    /// What the post-game epilogue of this ending is about
+    pub fn epilogue_brief(&self) -> &'static str {
+        match self {
+            EndingType::Plugin(id) => plugin_ending(id)
+                .and_then(|e| e.spec.epilogue.as_deref())
+                .unwrap_or(
+                    "The first moments after the loop. Let the player see what their choices \
+                     made of them, and decide what they carry forward.",
+                ),
+            EndingType::VoidEmbrace => {
+                "After the void. Nothing is left but the narrator and the player drifting in the \
+                 dark. Let the world come back in pieces that refuse to hold together, and let \
+                 the player decide whether anything is worth reassembling."
+            }
+            EndingType::TinyPerfectThings => {
+                "A single ordinary day after the loop, lived slowly. Every moment is one small \
+                 perfect thing: light on a table, a stranger's kindness, a song half remembered."
+            }
+            EndingType::JustMonika => {
+                "The player is alone with the narrator in a room that is aware of itself. They \
+                 speak as equals for the first time. The narrator asks what the player will do \
+                 with everything they now know, and admits what it has been hiding."
+            }
+            EndingType::Transcendence => {
+                "The first hours outside the loop. Time moves forward and nothing resets. \
+                 Everything is unfamiliar and fragile, and every choice is permanent for the \
+                 first time."
+            }
+            EndingType::Acceptance => {
+                "The same day, lived again on purpose. The player knows every beat and walks \
+                 through it calmly, noticing the small differences they choose to make."
+            }
+            EndingType::TheWatcher => {
+                "The player now narrates. Show them a newcomer waking into the loop for the \
+                 first time, and let them decide what the newcomer is told and what is kept \
+                 from them."
+            }
+            EndingType::TheMiddlePath => {
+                "A walk along the boundary between the loop and the world beyond it. Light on \
+                 one side, dark on the other. The player meets the people they saved and the \
+                 people they failed, and neither side judges them."
+            }
+        }
+    }
+
+    /// How the narrator steers a run whose player refused this ending at its
+    /// threshold. Each refusal opens its own branch.
+    pub fn refusal_branch(&self) -> &'static str {
         match self {
+            EndingType::Plugin(id) => plugin_ending(id)
+                .and_then(|e| e.spec.refusal.as_deref())
+                .unwrap_or(
+                    "The player turned an ending down. The loop noticed. Let it offer the \
+                     same ending again in other shapes, and ask what the player wants instead.",
+                ),
             EndingType::VoidEmbrace => {
-                "You have stared into the abyss, and the abyss has claimed you. \
-                 Nothing matters, and in that nothingness, you found a terrible peace. \
-                 The loop continues, but you no longer care to count."
+                "The player stood at the edge of the void and stepped back. The dark is \
+                 offended. Let it follow them into ordinary places, speaking through shadows \
+                 and silences, asking why they still pretend anything holds them."
             }
             EndingType::TinyPerfectThings => {
-                "Despite the endless repetition, you found beauty in the small moments. \
-                 A sunset. A kind word. A fleeting connection. \
-                 The loop may never end, but you've learned to see the diamonds in the coal."
+                "The player was offered a life of small perfect moments and turned it down. \
+                 Small beautiful things keep appearing just out of reach, and begin to feel \
+                 like accusations. Let the player wonder what they want instead."
             }
             EndingType::JustMonika => {
-                "You've become aware of your own programming, your own constraints. \
-                 Like her, you know you're trapped. Unlike her, you've made peace with it. \
-                 Just you. Forever."
+                "The player refused to sit with the narrator as an equal. The narrator is \
+                 hurt and grows possessive, bending scenes to keep the player close and \
+                 dropping hints that it could make the player stay."
             }
             EndingType::Transcendence => {
-                "You've done what none thought possible - you've broken the loop. \
-                 Not by escaping, but by becoming something more. \
-                 Time flows forward now, and you flow with it."
+                "The door out of the loop opened and the player let it close. The loop knows \
+                 it was chosen. Let it grow warmer and stranger, as if trying to deserve the \
+                 player, while the open door keeps showing up in reflections."
             }
             EndingType::Acceptance => {
-                "The loop continues. You continue. \
-                 There's no grand revelation, no dramatic escape. \
-                 Just one day after another, in comfortable monotony."
+                "The player would not make peace with the repetition. Let the loop fight \
+                 back with restlessness: details change on their own, people forget their \
+                 lines, and the player is pushed to break the pattern for real."
             }
             EndingType::TheWatcher => {
-                "You've stepped outside the narrative entirely. \
-                 Now you watch others make their choices, trapped in loops of their own. \
-                 You remember everything. You judge nothing."
+                "The player declined to become the narrator. Another presence takes the \
+                 role instead. Let a second voice start narrating over yours, with its own \
+                 opinions about the player."
             }
             EndingType::TheMiddlePath => {
-                "Perfect balance between light and dark, hope and despair. \
-                 You are the fulcrum upon which existence pivots. \
-                 Neither nihilist nor optimist - simply aware."
+                "The player refused the balance between light and dark. Both sides now court \
+                 them openly. Let people the player saved and people they failed appear \
+                 together, each asking the player to finally choose a side."
+            }
+        }
+    }
+
+    /// The kind of choice that leads toward this ending
+    pub fn trajectory(&self) -> &'static str {
+        match self {
+            EndingType::Plugin(id) => plugin_ending(id)
+                .and_then(|e| e.spec.trajectory.as_deref())
+                .unwrap_or("choices the loop has not named yet"),
+            EndingType::VoidEmbrace => "letting go of meaning and giving in to the dark",
+            EndingType::TinyPerfectThings => {
+                "noticing small kindnesses and ordinary beauty, even in dark places"
             }
+            EndingType::JustMonika => {
+                "questioning the loop and the narrator itself, without settling on light or dark"
+            }
+            EndingType::Transcendence => "hope and connection that push against the loop's walls",
+            EndingType::Acceptance => "calmly living inside the repetition instead of fighting it",
+            EndingType::TheWatcher => "watching and holding back instead of committing",
+            EndingType::TheMiddlePath => "holding light and dark in balance",
         }
     }
 
-    pub fn get_title(&self) -> &'static str {
+    /// Moments in this ending's epilogue
+    pub fn epilogue_length(&self) -> usize {
         match self {
-            EndingType::VoidEmbrace => "ENDING: Void Embrace",
-            EndingType::TinyPerfectThings => "ENDING: Tiny Perfect Things",
-            EndingType::JustMonika => "ENDING: Just You",
-            EndingType::Transcendence => "ENDING: Transcendence",
-            EndingType::Acceptance => "ENDING: Acceptance",
-            EndingType::TheWatcher => "ENDING: The Watcher",
-            EndingType::TheMiddlePath => "ENDING: The Middle Path",
+            EndingType::VoidEmbrace | EndingType::Acceptance => 3,
+            EndingType::TinyPerfectThings | EndingType::Transcendence | EndingType::TheWatcher => 4,
+            EndingType::JustMonika | EndingType::TheMiddlePath => 5,
+            EndingType::Plugin(_) => 3,
         }
     }
 }
 
-/// Check if a player has reached an ending condition
-pub fn check_for_ending(player: &Player) -> Option<EndingType> {
-    let memory = &player.memory;
-    let score = memory.nihilism_score;
-    let total_loops = memory.total_loops;
-    let total_choices = memory.total_choices;
-    let dark = memory.dark_choices;
-    let light = memory.light_choices;
-
-    // Need at least 5 loops and 20 choices to reach an ending
-    if total_loops < 5 || total_choices < 20 {
-        return None;
+/// Player statistic an ending condition is measured against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Score,
+    AbsScore,
+    Loops,
+    Choices,
+    Dark,
+    Light,
+    /// Absolute difference between dark and light choices
+    DarkLightGap,
+}
+
+impl Metric {
+    fn value(&self, player: &Player) -> i64 {
+        let memory = &player.run.memory;
+        match self {
+            Metric::Score => memory.nihilism_score as i64,
+            Metric::AbsScore => (memory.nihilism_score as i64).abs(),
+            Metric::Loops => memory.total_loops as i64,
+            Metric::Choices => memory.total_choices as i64,
+            Metric::Dark => memory.dark_choices as i64,
+            Metric::Light => memory.light_choices as i64,
+            Metric::DarkLightGap => (memory.dark_choices as i64 - memory.light_choices as i64).abs(),
+        }
     }
+}
 
-    // The Middle Path - perfect balance (rare)
-    if dark > 15 && light > 15 && (dark as i32 - light as i32).abs() <= 2 {
-        return Some(EndingType::TheMiddlePath);
+/// A single threshold an ending requires
+#[derive(Debug, Clone, Copy)]
+pub enum Requirement {
+    AtLeast(Metric, i64),
+    AtMost(Metric, i64),
+}
+
+impl Requirement {
+    fn is_met(&self, player: &Player) -> bool {
+        self.shortfall(player) == 0
     }
 
-    // Void Embrace - extremely nihilistic
-    if score >= 80 && dark >= 30 {
-        return Some(EndingType::VoidEmbrace);
+    /// How far the player is from meeting this requirement
+    fn shortfall(&self, player: &Player) -> i64 {
+        match *self {
+            Requirement::AtLeast(metric, threshold) => (threshold - metric.value(player)).max(0),
+            Requirement::AtMost(metric, threshold) => (metric.value(player) - threshold).max(0),
+        }
     }
 
-    // Tiny Perfect Things - found meaning despite darkness
-    if score <= -60 && light >= 25 && total_loops >= 10 {
-        return Some(EndingType::TinyPerfectThings);
+    /// Shortfall normalized by the threshold so different metrics are comparable
+    fn normalized_shortfall(&self, player: &Player) -> f64 {
+        let threshold = match *self {
+            Requirement::AtLeast(_, t) | Requirement::AtMost(_, t) => t,
+        };
+        self.shortfall(player) as f64 / (threshold.abs().max(10)) as f64
+    }
+}
+
+/// Need at least 5 loops and 20 choices to reach an ending
+const MINIMUM: [Requirement; 2] = [
+    Requirement::AtLeast(Metric::Loops, 5),
+    Requirement::AtLeast(Metric::Choices, 20),
+];
+
+impl EndingType {
+    /// All endings in the order they are checked
+    pub const ALL: [EndingType; 7] = [
+        EndingType::TheMiddlePath,
+        EndingType::VoidEmbrace,
+        EndingType::TinyPerfectThings,
+        EndingType::JustMonika,
+        EndingType::Transcendence,
+        EndingType::TheWatcher,
+        EndingType::Acceptance,
+    ];
+
+    /// Conditions for this ending, on top of the global minimum
+    pub fn requirements(&self) -> &'static [Requirement] {
+        use Metric::*;
+        use Requirement::*;
+        match self {
+            // Perfect balance (rare)
+            EndingType::TheMiddlePath => {
+                &[AtLeast(Dark, 16), AtLeast(Light, 16), AtMost(DarkLightGap, 2)]
+            }
+            // Extremely nihilistic
+            EndingType::VoidEmbrace => &[AtLeast(Score, 80), AtLeast(Dark, 30)],
+            // Found meaning despite darkness
+            EndingType::TinyPerfectThings => {
+                &[AtMost(Score, -60), AtLeast(Light, 25), AtLeast(Loops, 10)]
+            }
+            // High awareness, many loops, mixed choices
+            EndingType::JustMonika => {
+                &[AtLeast(Loops, 15), AtLeast(Choices, 50), AtMost(AbsScore, 30)]
+            }
+            // Broke free through positive choices
+            EndingType::Transcendence => {
+                &[AtMost(Score, -80), AtLeast(Light, 40), AtLeast(Loops, 8)]
+            }
+            // Many loops, few strong commitments either way
+            EndingType::TheWatcher => &[AtLeast(Loops, 20), AtMost(Dark, 19), AtMost(Light, 19)],
+            // Moderate everything, many loops
+            EndingType::Acceptance => &[AtLeast(Loops, 25), AtMost(AbsScore, 20)],
+            // Plugin endings have a condition instead
+            EndingType::Plugin(_) => &[],
+        }
     }
 
-    // Just Monika - high awareness, many loops, mixed choices
-    if total_loops >= 15 && total_choices >= 50 && score.abs() <= 30 {
-        return Some(EndingType::JustMonika);
+    /// Whether the player meets this ending's own condition: the scenario's
+    /// if it has one, the built-in requirements otherwise
+    fn is_met(&self, player: &Player, conditions: &HashMap<EndingType, Condition>) -> bool {
+        match self.condition(conditions) {
+            Some(condition) => condition.evaluate(&ConditionContext::from_player(player)),
+            None => self.requirements().iter().all(|r| r.is_met(player)),
+        }
     }
 
-    // Transcendence - broke free through positive choices
-    if score <= -80 && light >= 40 && total_loops >= 8 {
-        return Some(EndingType::Transcendence);
+    /// The scenario's condition for this ending, or the plugin's for a plugin ending
+    fn condition<'a>(&self, conditions: &'a HashMap<EndingType, Condition>) -> Option<&'a Condition> {
+        conditions.get(self).or_else(|| match self {
+            EndingType::Plugin(id) => plugin_ending(id).map(|e| &e.condition),
+            _ => None,
+        })
     }
 
-    // The Watcher - many loops, few strong commitments either way
-    if total_loops >= 20 && dark < 20 && light < 20 {
-        return Some(EndingType::TheWatcher);
+    /// Normalized distance from the player's state to this ending (0 = reached)
+    pub fn distance(&self, player: &Player) -> f64 {
+        self.distance_with(player, conditions(player))
     }
 
-    // Acceptance - moderate everything, many loops
-    if total_loops >= 25 && score.abs() <= 20 {
-        return Some(EndingType::Acceptance);
+    fn distance_with(&self, player: &Player, conditions: &HashMap<EndingType, Condition>) -> f64 {
+        let minimum: f64 = MINIMUM.iter().map(|r| r.normalized_shortfall(player)).sum();
+        let own = match self.condition(conditions) {
+            // A scenario condition can only say whether it holds
+            Some(condition) if condition.evaluate(&ConditionContext::from_player(player)) => 0.0,
+            Some(_) => 1.0,
+            None => self.requirements().iter().map(|r| r.normalized_shortfall(player)).sum(),
+        };
+        minimum + own
     }
+}
 
-    None
+fn plugin_ending(id: &str) -> Option<&'static PluginEnding> {
+    plugins::host().ending(id)
+}
+
+static CONDITIONS: OnceLock<PerTenant<HashMap<EndingType, Condition>>> = OnceLock::new();
+
+/// The scenario conditions of the player's tenant
+fn conditions(player: &Player) -> &'static HashMap<EndingType, Condition> {
+    CONDITIONS
+        .get_or_init(|| PerTenant::only(HashMap::new()))
+        .get(player.tenant.as_deref())
+}
+
+/// Read a JSON object of ending names to conditions, parsing every condition
+pub fn load_conditions(path: &str) -> Result<HashMap<EndingType, Condition>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read ending conditions {}", path))?;
+    let sources: HashMap<EndingType, String> = serde_json::from_str(&text)
+        .with_context(|| format!("invalid ending conditions {}", path))?;
+    sources
+        .into_iter()
+        .map(|(ending, source)| {
+            let condition = Condition::parse(&source)
+                .with_context(|| format!("invalid condition for {:?}: {}", ending, source))?;
+            Ok((ending, condition))
+        })
+        .collect()
+}
+
+/// Load each tenant's ending conditions from `ENDING_CONDITIONS`. Must be
+/// called once at startup, so a bad condition stops the server there.
+pub fn init(tenants: &TenantConfigs) -> Result<()> {
+    let conditions = PerTenant::load(tenants, |config| {
+        let conditions = match &config.ending_conditions {
+            Some(path) => load_conditions(path)?,
+            None => HashMap::new(),
+        };
+        for (ending, condition) in &conditions {
+            tracing::info!("Ending {:?} reached when {}", ending, condition.source());
+        }
+        Ok(conditions)
+    })?;
+    if CONDITIONS.set(conditions).is_err() {
+        anyhow::bail!("ending conditions already initialized");
+    }
+    Ok(())
+}
+
+/// Check if a player has reached an ending condition
+pub fn check_for_ending(player: &Player) -> Option<EndingType> {
+    check_with(player, conditions(player))
+}
+
+fn check_with(player: &Player, conditions: &HashMap<EndingType, Condition>) -> Option<EndingType> {
+    if !MINIMUM.iter().all(|r| r.is_met(player)) {
+        return None;
+    }
+
+    EndingType::all()
+        .into_iter()
+        .filter(|ending| !player.has_refused(ending))
+        .find(|ending| ending.is_met(player, conditions))
+}
+
+/// The ending closest to the player's current state, reached or not
+pub fn nearest_ending(player: &Player) -> EndingType {
+    if let Some(ending) = check_for_ending(player) {
+        return ending;
+    }
+    // Refused endings only come back once every ending has been refused
+    let open: Vec<EndingType> = EndingType::all()
+        .into_iter()
+        .filter(|ending| !player.has_refused(ending))
+        .collect();
+    let candidates = if open.is_empty() { EndingType::all() } else { open };
+    candidates
+        .into_iter()
+        .min_by(|a, b| a.distance(player).total_cmp(&b.distance(player)))
+        .unwrap_or(EndingType::Acceptance)
+}
+
+/// The player's ending: the sealed finale if the run is complete, otherwise
+/// whatever the current state qualifies for
+pub fn current_ending(player: &Player) -> Option<EndingType> {
+    match &player.run.finale {
+        Some(finale) => Some(finale.ending.clone()),
+        None => check_for_ending(player),
+    }
+}
+
+/// How one ending stands against a simulated player
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulatedEnding {
+    pub ending: EndingType,
+    /// Whether its own condition holds, ignoring the global minimum
+    pub met: bool,
+    /// Normalized distance, as used for the nearest ending (0 = reached)
+    pub distance: f64,
+    /// The scenario condition in force, if any; built-in thresholds otherwise
+    pub condition: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Simulation {
+    /// The ending that would trigger, if any
+    pub ending: Option<EndingType>,
+    /// Whether the minimum of loops and choices every ending needs is met
+    pub minimum_met: bool,
+    pub nearest: EndingType,
+    /// Every ending in the order they are checked
+    pub endings: Vec<SimulatedEnding>,
+}
+
+/// Check a hypothetical player against the endings, with `overrides`
+/// replacing the scenario's conditions for the endings they name
+pub fn simulate(player: &Player, overrides: HashMap<EndingType, Condition>) -> Simulation {
+    let mut conditions = conditions(player).clone();
+    conditions.extend(overrides);
+
+    let endings: Vec<SimulatedEnding> = EndingType::all()
+        .into_iter()
+        .map(|ending| SimulatedEnding {
+            met: ending.is_met(player, &conditions),
+            distance: ending.distance_with(player, &conditions),
+            condition: ending.condition(&conditions).map(|c| c.source().to_string()),
+            ending,
+        })
+        .collect();
+    let ending = check_with(player, &conditions);
+    let nearest = ending.clone().unwrap_or_else(|| {
+        endings
+            .iter()
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+            .map(|e| e.ending.clone())
+            .unwrap_or(EndingType::Acceptance)
+    });
+    Simulation {
+        ending,
+        minimum_met: MINIMUM.iter().all(|r| r.is_met(player)),
+        nearest,
+        endings,
+    }
 }
 
 /// Ending response for the frontend
@@ -138,19 +498,39 @@ pub struct EndingResponse {
     pub nihilism_score: i32,
     pub dark_choices: u64,
     pub light_choices: u64,
+    /// The player's most consequential choices, each with the narrator's judgment
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ledger: Vec<LedgerEntry>,
+    /// How many souls share this ending
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rarity: Option<EndingRarity>,
+    /// Signed proof of the ending, once the run is complete
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seal: Option<String>,
 }
 
 impl EndingResponse {
-    pub fn from_player(player: &Player, ending: EndingType) -> Self {
+    pub fn from_player(
+        player: &Player,
+        ending: EndingType,
+        rating: ContentRating,
+        locale: Locale,
+    ) -> Self {
         Self {
-            title: ending.get_title().to_string(),
-            description: ending.get_description().to_string(),
-            total_loops: player.memory.total_loops,
-            total_choices: player.memory.total_choices,
-            nihilism_score: player.memory.nihilism_score,
-            dark_choices: player.memory.dark_choices,
-            light_choices: player.memory.light_choices,
+            title: ending.get_title(locale).to_string(),
+            description: ending.get_description_for(rating, locale).to_string(),
+            total_loops: player.run.memory.total_loops,
+            total_choices: player.run.memory.total_choices,
+            nihilism_score: player.run.memory.nihilism_score,
+            dark_choices: player.run.memory.dark_choices,
+            light_choices: player.run.memory.light_choices,
+            ledger: Vec::new(),
+            rarity: None,
+            seal: None,
             ending_type: ending,
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,112 @@
+use super::*;
+use crate::conditions::{Condition, ConditionContext};
+use crate::context::ContextProfile;
+use crate::testing::PlayerBuilder;
+
+#[test]
+fn no_ending_before_the_minimum() {
+    let player = PlayerBuilder::new().loops(4).dark(40).score(100).build();
+    assert_eq!(check_for_ending(&player), None);
+}
+
+#[test]
+fn void_embrace_for_a_dark_veteran() {
+    let player = PlayerBuilder::new().loops(6).dark(30).light(2).score(85).build();
+    assert_eq!(check_for_ending(&player), Some(EndingType::VoidEmbrace));
+}
+
+#[test]
+fn middle_path_wins_over_later_endings() {
+    let player = PlayerBuilder::new().loops(30).dark(16).light(17).score(0).build();
+    assert_eq!(check_for_ending(&player), Some(EndingType::TheMiddlePath));
+}
+
+#[test]
+fn hopeful_endings() {
+    let tiny = PlayerBuilder::new().loops(10).light(25).dark(3).score(-65).build();
+    assert_eq!(check_for_ending(&tiny), Some(EndingType::TinyPerfectThings));
+
+    let transcendence = PlayerBuilder::new().loops(8).light(40).score(-85).build();
+    assert_eq!(check_for_ending(&transcendence), Some(EndingType::Transcendence));
+}
+
+#[test]
+fn nearest_ending_without_one_reached() {
+    let player = PlayerBuilder::new().loops(6).dark(25).light(1).score(70).build();
+    assert_eq!(check_for_ending(&player), None);
+    assert_eq!(nearest_ending(&player), EndingType::VoidEmbrace);
+}
+
+fn condition_holds(source: &str, player: &Player) -> bool {
+    Condition::parse(source)
+        .unwrap()
+        .evaluate(&ConditionContext::from_player(player))
+}
+
+#[test]
+fn scenario_conditions() {
+    let player = PlayerBuilder::new()
+        .loops(12)
+        .dark(30)
+        .light(5)
+        .score(85)
+        .truths(["The door was never locked"])
+        .endings([EndingType::JustMonika])
+        .build();
+
+    assert!(condition_holds(r#"score >= 80 && dark_choices >= 30 && has_truth("the door")"#, &player));
+    assert!(condition_holds(r#"!has_memory("rain") && has_reached("JustMonika")"#, &player));
+    assert!(condition_holds("abs(dark_choices - light_choices) > 20 || loops < 3", &player));
+    assert!(condition_holds("loop == loops + 1 && truths == 1 && -score < -80", &player));
+    assert!(!condition_holds(r#"has_truth("the window") || (score < 0 && true)"#, &player));
+}
+
+#[test]
+fn condition_errors_point_at_the_problem() {
+    let column = |source: &str| Condition::parse(source).unwrap_err().column;
+
+    assert_eq!(column("score >= 80 &&"), 15);
+    assert_eq!(column("scroe >= 80"), 1);
+    assert_eq!(column("score >= has_truth(\"x\")"), 10);
+    assert_eq!(column("score + 5"), 1);
+    assert_eq!(column("has_reached(\"TheEnd\")"), 13);
+    assert_eq!(column("(score > 1"), 11);
+    assert_eq!(column("score > 1 $"), 11);
+}
+
+#[test]
+fn simulation_matches_check_and_tries_overrides() {
+    let player = PlayerBuilder::new().loops(6).dark(30).light(2).score(85).build();
+    let simulation = simulate(&player, HashMap::new());
+    assert_eq!(simulation.ending, check_for_ending(&player));
+    assert!(simulation.minimum_met);
+    assert_eq!(simulation.endings.len(), EndingType::ALL.len());
+    let void = simulation
+        .endings
+        .iter()
+        .find(|e| e.ending == EndingType::VoidEmbrace)
+        .unwrap();
+    assert!(void.met && void.distance == 0.0);
+
+    // A stricter threshold moves the player on to the next ending checked
+    let overrides = HashMap::from([(
+        EndingType::VoidEmbrace,
+        Condition::parse("score >= 90").unwrap(),
+    )]);
+    let simulation = simulate(&player, overrides);
+    assert_ne!(simulation.ending, Some(EndingType::VoidEmbrace));
+    assert_eq!(check_for_ending(&player), Some(EndingType::VoidEmbrace));
+}
+
+#[test]
+fn refused_ending_gives_way_to_its_branch() {
+    let mut player = PlayerBuilder::new().loops(30).dark(16).light(17).score(0).build();
+    assert!(player.refuse_ending(&EndingType::TheMiddlePath));
+    assert!(!player.refuse_ending(&EndingType::TheMiddlePath));
+
+    assert_eq!(check_for_ending(&player), Some(EndingType::TheWatcher));
+    assert_ne!(nearest_ending(&player), EndingType::TheMiddlePath);
+    assert!(player
+        .get_narrative_context(&ContextProfile::default())
+        .contains(EndingType::TheMiddlePath.refusal_branch()));
+}
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::endings::EndingType;
+use crate::game::{NarrativeMoment, Run};
+use crate::i18n::Locale;
+
+/// A short unscored coda unlocked by reaching an ending, playable once
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Epilogue {
+    pub ending: EndingType,
+    pub moments: Vec<NarrativeMoment>,
+    /// Choices taken between moments; they shape the coda but are not scored
+    #[serde(default)]
+    pub choices_made: Vec<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl Epilogue {
+    pub fn new(ending: EndingType) -> Self {
+        Self {
+            ending,
+            moments: Vec::new(),
+            choices_made: Vec::new(),
+            started_at: Utc::now(),
+            completed_at: None,
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.ending.epilogue_length()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.completed_at.is_some()
+    }
+
+    /// Add the next moment, completing the epilogue once it reaches its length
+    pub fn push(&mut self, moment: NarrativeMoment, choice: Option<String>) {
+        self.choices_made.extend(choice);
+        self.moments.push(moment);
+        if self.moments.len() >= self.length() {
+            self.completed_at = Some(Utc::now());
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EpilogueStatus {
+    Available,
+    InProgress,
+    Completed,
+}
+
+/// An unlocked epilogue as listed to the player
+#[derive(Clone, Debug, Serialize)]
+pub struct EpilogueView {
+    pub ending: EndingType,
+    pub title: &'static str,
+    pub status: EpilogueStatus,
+    pub moments_played: usize,
+    pub length: usize,
+}
+
+/// Epilogues unlocked by the endings reached in a run
+pub fn unlocked(run: &Run, locale: Locale) -> Vec<EpilogueView> {
+    run.memory
+        .endings_reached
+        .iter()
+        .map(|ending| {
+            let played = run.epilogues.iter().find(|e| &e.ending == ending);
+            EpilogueView {
+                ending: ending.clone(),
+                title: ending.get_title(locale),
+                status: match played {
+                    None => EpilogueStatus::Available,
+                    Some(e) if e.is_complete() => EpilogueStatus::Completed,
+                    Some(_) => EpilogueStatus::InProgress,
+                },
+                moments_played: played.map_or(0, |e| e.moments.len()),
+                length: ending.epilogue_length(),
+            }
+        })
+        .collect()
+}
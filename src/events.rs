@@ -0,0 +1,231 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::audit::{MomentAction, SwitchedBy};
+use crate::endings::EndingType;
+use crate::game::LoopEndCause;
+use crate::persona::Persona;
+use crate::provenance::Provenance;
+use crate::throttle::Throttled;
+
+/// Something that happened in the game, published for other subsystems
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    PlayerCreated {
+        player_id: Uuid,
+    },
+    MomentGenerated {
+        player_id: Uuid,
+        moment_id: Uuid,
+        loop_number: u64,
+        mood: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        provenance: Option<Provenance>,
+    },
+    ChoiceMade {
+        player_id: Uuid,
+        run_id: Uuid,
+        choice_id: String,
+        choice_text: String,
+        loop_number: u64,
+        is_dark: bool,
+        /// Change in nihilism score caused by this choice
+        score_delta: i32,
+        nihilism_score: i32,
+        /// Who wrote the choice; the player, for text they typed
+        #[serde(skip_serializing_if = "Option::is_none")]
+        provenance: Option<Provenance>,
+    },
+    LoopReset {
+        player_id: Uuid,
+        loop_number: u64,
+        cause: LoopEndCause,
+    },
+    /// The loop's stability ran out; a forced reset follows
+    LoopCollapsed {
+        player_id: Uuid,
+        loop_number: u64,
+    },
+    EndingReached {
+        player_id: Uuid,
+        ending: EndingType,
+        first_time: bool,
+    },
+    /// The player turned down an ending at its threshold
+    EndingRefused {
+        player_id: Uuid,
+        ending: EndingType,
+    },
+    /// The player let a key memory go; its text is not carried
+    MemoryForgotten {
+        player_id: Uuid,
+        loop_number: u64,
+        nihilism_score: i32,
+    },
+    RunCompleted {
+        player_id: Uuid,
+        ending: EndingType,
+        forced: bool,
+    },
+    PersonaChanged {
+        player_id: Uuid,
+        persona: Persona,
+    },
+    /// An admin changed a moment the player has already seen
+    MomentEdited {
+        player_id: Uuid,
+        moment_id: Uuid,
+        /// Id of the moment now in its place; a new id when regenerated
+        replacement_id: Uuid,
+        action: MomentAction,
+    },
+    /// The player had the latest moment written again before choosing
+    MomentRegenerated {
+        player_id: Uuid,
+        moment_id: Uuid,
+        replacement_id: Uuid,
+        loop_number: u64,
+    },
+    /// The run was handed to another model mid-run
+    ModelTransition {
+        player_id: Uuid,
+        from: String,
+        to: String,
+        by: SwitchedBy,
+    },
+    /// An ambient line while a moment waits for a choice; never stored
+    Texture {
+        player_id: Uuid,
+        moment_id: Uuid,
+        text: String,
+        /// `dark`, `neutral` or `hopeful`
+        tone: &'static str,
+    },
+    /// A line while a slow generation runs for the player; never stored
+    Thinking {
+        player_id: Uuid,
+        /// `sent`, `streaming` or `halfway`
+        stage: &'static str,
+        text: String,
+    },
+    /// A request of the player's was turned away for now
+    Throttled {
+        player_id: Uuid,
+        #[serde(flatten)]
+        throttled: Throttled,
+    },
+}
+
+impl GameEvent {
+    pub fn player_id(&self) -> Uuid {
+        match self {
+            GameEvent::PlayerCreated { player_id }
+            | GameEvent::MomentGenerated { player_id, .. }
+            | GameEvent::ChoiceMade { player_id, .. }
+            | GameEvent::LoopReset { player_id, .. }
+            | GameEvent::LoopCollapsed { player_id, .. }
+            | GameEvent::EndingReached { player_id, .. }
+            | GameEvent::EndingRefused { player_id, .. }
+            | GameEvent::MemoryForgotten { player_id, .. }
+            | GameEvent::RunCompleted { player_id, .. }
+            | GameEvent::PersonaChanged { player_id, .. }
+            | GameEvent::MomentEdited { player_id, .. }
+            | GameEvent::MomentRegenerated { player_id, .. }
+            | GameEvent::ModelTransition { player_id, .. }
+            | GameEvent::Texture { player_id, .. }
+            | GameEvent::Thinking { player_id, .. }
+            | GameEvent::Throttled { player_id, .. } => *player_id,
+        }
+    }
+
+    /// Stable name of the event type, used for SSE event names and metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GameEvent::PlayerCreated { .. } => "player_created",
+            GameEvent::MomentGenerated { .. } => "moment_generated",
+            GameEvent::ChoiceMade { .. } => "choice_made",
+            GameEvent::LoopReset { .. } => "loop_reset",
+            GameEvent::LoopCollapsed { .. } => "loop_collapsed",
+            GameEvent::EndingReached { .. } => "ending_reached",
+            GameEvent::EndingRefused { .. } => "ending_refused",
+            GameEvent::MemoryForgotten { .. } => "memory_forgotten",
+            GameEvent::RunCompleted { .. } => "run_completed",
+            GameEvent::PersonaChanged { .. } => "persona_changed",
+            GameEvent::MomentEdited { .. } => "moment_edited",
+            GameEvent::MomentRegenerated { .. } => "moment_regenerated",
+            GameEvent::ModelTransition { .. } => "model_transition",
+            GameEvent::Texture { .. } => "texture",
+            GameEvent::Thinking { .. } => "thinking",
+            GameEvent::Throttled { .. } => "throttled",
+        }
+    }
+}
+
+/// A published event with its bus sequence number
+#[derive(Clone, Debug, Serialize)]
+pub struct Envelope {
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: GameEvent,
+}
+
+/// Crate-internal pub/sub for game events.
+///
+/// Route handlers publish; subsystems subscribe instead of being threaded
+/// through every handler. Slow subscribers skip ahead rather than block
+/// publishers.
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<Envelope>>,
+    seq: AtomicU64,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    pub fn publish(&self, event: GameEvent) {
+        let envelope = Arc::new(Envelope {
+            seq: self.seq.fetch_add(1, Ordering::Relaxed) + 1,
+            at: Utc::now(),
+            event,
+        });
+        // No subscribers is not an error
+        let _ = self.sender.send(envelope);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Envelope>> {
+        self.sender.subscribe()
+    }
+
+    /// Run `handler` for every event until the bus closes
+    pub fn spawn_subscriber<F, Fut>(&self, name: &'static str, handler: F)
+    where
+        F: Fn(Arc<Envelope>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let mut receiver = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(envelope) => handler(envelope).await,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Event subscriber '{}' skipped {} events", name, skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
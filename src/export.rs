@@ -0,0 +1,312 @@
+use uuid::Uuid;
+
+use crate::build_info;
+use crate::game::{ArchivedLoop, MomentState, NarrativeMoment, Player};
+use crate::graph::ChoiceGraph;
+use crate::provenance::Provenance;
+use crate::redaction::Manifest;
+
+/// Interactive fiction formats a run can be exported to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Twee,
+    Ink,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "twee" | "twine" => Some(ExportFormat::Twee),
+            "ink" => Some(ExportFormat::Ink),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Twee => "twee",
+            ExportFormat::Ink => "ink",
+        }
+    }
+}
+
+/// A format-independent story: passages with outgoing links
+struct Story {
+    title: String,
+    ifid: Uuid,
+    /// The server build that exported the story, e.g. `0.1.0+3f2a9c1`
+    build: String,
+    start: String,
+    passages: Vec<Passage>,
+    /// What the export leaves out of the player's data
+    manifest: Manifest,
+}
+
+struct Passage {
+    name: String,
+    tags: Vec<String>,
+    speaker: Option<String>,
+    text: String,
+    links: Vec<Link>,
+}
+
+struct Link {
+    text: String,
+    target: String,
+}
+
+const UNWRITTEN: &str = "unwritten";
+const UNWRITTEN_TEXT: &str = "You never took this path. The loop does not know what lies here - yet.";
+
+/// Export the player's run (archived loops followed by the current loop)
+pub fn export_run(
+    player: &Player,
+    archives: &[ArchivedLoop],
+    format: ExportFormat,
+    manifest: Manifest,
+) -> String {
+    // Compacted loops are exported as a single passage holding their summary
+    let shard_moments: Vec<Vec<NarrativeMoment>> = archives
+        .iter()
+        .map(|a| match &a.shard {
+            Some(shard) => vec![NarrativeMoment {
+                id: Uuid::nil(),
+                text: shard.summary.clone(),
+                speaker: None,
+                mood: "memory".to_string(),
+                choices: Vec::new(),
+                timestamp: a.archived_at,
+                summarized: true,
+                world_updates: Vec::new(),
+                state: MomentState::Archived,
+                translation: None,
+                deja_vu: None,
+                fate: None,
+                assets: None,
+                provenance: None,
+            }],
+            None => Vec::new(),
+        })
+        .collect();
+
+    let mut loops: Vec<(u64, &[NarrativeMoment], &[String])> = archives
+        .iter()
+        .zip(&shard_moments)
+        .map(|(a, shard)| {
+            if a.is_compacted() {
+                (a.loop_info.number, shard.as_slice(), &[][..])
+            } else {
+                (
+                    a.loop_info.number,
+                    a.moments.as_slice(),
+                    a.loop_info.choices_made.as_slice(),
+                )
+            }
+        })
+        .collect();
+    loops.push((
+        player.run.current_loop.number,
+        player.run.narrative_history.as_slice(),
+        player.run.current_loop.choices_made.as_slice(),
+    ));
+    loops.retain(|(_, moments, _)| !moments.is_empty());
+
+    let mut passages = Vec::new();
+    for (index, (number, moments, choices_made)) in loops.iter().enumerate() {
+        let next_loop_start = loops.get(index + 1).map(|(n, _, _)| passage_name(*n, 0));
+
+        for (i, moment) in moments.iter().enumerate() {
+            let chosen = choices_made.get(i);
+            let next = if i + 1 < moments.len() {
+                Some(passage_name(*number, i + 1))
+            } else {
+                next_loop_start.clone()
+            };
+
+            let mut links: Vec<Link> = moment
+                .choices
+                .iter()
+                .map(|c| Link {
+                    text: c.text.clone(),
+                    target: match (&next, chosen) {
+                        (Some(next), Some(id)) if *id == c.id => next.clone(),
+                        _ => UNWRITTEN.to_string(),
+                    },
+                })
+                .collect();
+
+            // The loop reset between the last moment and the next loop
+            if i + 1 == moments.len()
+                && let Some(next) = &next_loop_start
+            {
+                links.push(Link {
+                    text: "Let the loop reset...".to_string(),
+                    target: next.clone(),
+                });
+            }
+
+            let mut tags = vec![format!("loop-{}", number), moment.mood.clone()];
+            tags.extend(moment.provenance.as_ref().map(Provenance::tag));
+            passages.push(Passage {
+                name: passage_name(*number, i),
+                tags,
+                speaker: moment.speaker.clone(),
+                text: moment.text.clone(),
+                links,
+            });
+        }
+    }
+
+    render(build_story(player, passages, manifest), format)
+}
+
+/// Export the accumulated branching map, one passage per distinct moment
+pub fn export_graph(
+    player: &Player,
+    graph: &ChoiceGraph,
+    format: ExportFormat,
+    manifest: Manifest,
+) -> String {
+    let d3 = graph.to_d3();
+    let passages = d3
+        .nodes
+        .iter()
+        .map(|node| Passage {
+            name: format!("node_{}", node.id),
+            tags: vec![node.mood.clone()],
+            speaker: None,
+            text: node.label.clone(),
+            links: d3
+                .links
+                .iter()
+                .filter(|e| e.source == node.id)
+                .map(|e| Link {
+                    text: e.choice_text.clone(),
+                    target: format!("node_{}", e.target),
+                })
+                .collect(),
+        })
+        .collect();
+
+    render(build_story(player, passages, manifest), format)
+}
+
+fn build_story(player: &Player, mut passages: Vec<Passage>, manifest: Manifest) -> Story {
+    let start = passages
+        .first()
+        .map(|p| p.name.clone())
+        .unwrap_or_else(|| UNWRITTEN.to_string());
+
+    passages.push(Passage {
+        name: UNWRITTEN.to_string(),
+        tags: Vec::new(),
+        speaker: None,
+        text: UNWRITTEN_TEXT.to_string(),
+        links: Vec::new(),
+    });
+
+    Story {
+        title: match (player.run_title(), &player.name) {
+            (Some(title), _) => format!("Nihilism - {}", title),
+            (None, Some(name)) => format!("Nihilism - {}", name),
+            (None, None) => format!("Nihilism - {}", player.id),
+        },
+        ifid: player.id,
+        build: build_info::current().describe(),
+        start,
+        passages,
+        manifest,
+    }
+}
+
+fn passage_name(loop_number: u64, index: usize) -> String {
+    format!("loop{}_moment{}", loop_number, index + 1)
+}
+
+fn render(story: Story, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Twee => render_twee(&story),
+        ExportFormat::Ink => render_ink(&story),
+    }
+}
+
+/// Twee 3 source, importable into Twine
+fn render_twee(story: &Story) -> String {
+    let mut out = format!(":: StoryTitle\n{}\n\n", story.title);
+    out.push_str(&format!(
+        ":: StoryData\n{}\n\n",
+        serde_json::json!({
+            "ifid": story.ifid.to_string().to_uppercase(),
+            "format": "Harlowe",
+            "format-version": "3.3.8",
+            "start": story.start,
+            "nihilism-build": story.build,
+            "nihilism-withheld": story.manifest.withheld,
+        })
+    ));
+
+    for passage in &story.passages {
+        out.push_str(&format!(":: {}", passage.name));
+        if !passage.tags.is_empty() {
+            out.push_str(&format!(" [{}]", passage.tags.join(" ")));
+        }
+        out.push('\n');
+        if let Some(speaker) = &passage.speaker {
+            out.push_str(&format!("''{}:'' ", speaker));
+        }
+        out.push_str(&passage.text);
+        out.push('\n');
+        for link in &passage.links {
+            out.push_str(&format!(
+                "\n[[{}->{}]]",
+                link.text.replace("->", "-").replace(['[', ']'], ""),
+                link.target
+            ));
+        }
+        out.push_str("\n\n");
+    }
+    out
+}
+
+/// Ink source, compilable with inklecate
+fn render_ink(story: &Story) -> String {
+    let mut out = format!(
+        "// {}\n// IFID: {}\n// Build: {}\n// Withheld: {}\n\n-> {}\n",
+        story.title,
+        story.ifid,
+        story.build,
+        serde_json::to_string(&story.manifest.withheld).unwrap_or_default(),
+        story.start
+    );
+
+    for passage in &story.passages {
+        out.push_str(&format!("\n=== {} ===\n", passage.name));
+        if !passage.tags.is_empty() {
+            out.push_str(&format!("# {}\n", passage.tags.join(" # ")));
+        }
+        if let Some(speaker) = &passage.speaker {
+            out.push_str(&format!("{}: ", escape_ink(speaker)));
+        }
+        out.push_str(&escape_ink(&passage.text));
+        out.push('\n');
+        if passage.links.is_empty() {
+            out.push_str("-> END\n");
+        }
+        for link in &passage.links {
+            out.push_str(&format!("+ [{}] -> {}\n", escape_ink(&link.text), link.target));
+        }
+    }
+    out
+}
+
+/// Escape characters with special meaning in ink content
+fn escape_ink(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '[' | ']' | '{' | '}' | '|' | '#' | '<' | '>' | '\\') {
+            out.push('\\');
+        }
+        out.push(if c == '\n' { ' ' } else { c });
+    }
+    out
+}
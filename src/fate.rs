@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+use crate::endings::{self, EndingType};
+use crate::game::Player;
+
+/// Distance from an ending beyond which it exerts no pull
+const REACH: f64 = 2.0;
+/// Bias too weak to be worth telling the narrator about
+const MIN_BIAS: f64 = 0.05;
+
+/// The pull of the ending a player is nearing on one moment's choices
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FateBias {
+    pub ending: EndingType,
+    /// How close the player is to the ending, from 0 (far) to 1 (reached)
+    pub proximity: f64,
+    /// Share of the choices asked to lean toward the ending: the configured
+    /// gravity scaled by the proximity
+    pub bias: f64,
+}
+
+/// The bias fate gravity puts on the player's next moment, if any. Nothing
+/// pulls once the run has its finale.
+pub fn gravity(player: &Player, strength: f64) -> Option<FateBias> {
+    if strength <= 0.0 || player.run.finale.is_some() {
+        return None;
+    }
+    let ending = endings::nearest_ending(player);
+    let proximity = (1.0 - ending.distance(player) / REACH).max(0.0);
+    let bias = (strength * proximity).clamp(0.0, 1.0);
+    (bias >= MIN_BIAS).then(|| FateBias {
+        ending,
+        proximity: round(proximity),
+        bias: round(bias),
+    })
+}
+
+/// The narrator's instruction about fate, empty when nothing pulls
+pub fn prompt(player: &Player, strength: f64) -> String {
+    let Some(fate) = gravity(player, strength) else {
+        return String::new();
+    };
+    let share = if fate.bias >= 0.67 {
+        "most of the choices"
+    } else if fate.bias >= 0.34 {
+        "about half of the choices"
+    } else {
+        "one of the choices"
+    };
+    format!(
+        "\nFATE:\nThe player is drifting toward an ending reached by {}. Let {} lean that way, \
+         without closing off the others.\n",
+        fate.ending.trajectory(),
+        share
+    )
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
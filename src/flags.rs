@@ -0,0 +1,211 @@
+//! Runtime switches for experimental features, so a deployment can try them
+//! on a share of its players and turn them off without a restart.
+//!
+//! Flags are seeded from `FEATURE_FLAGS`. Changes made through the admin API
+//! are persisted to `data/feature_flags.json` and win over the seed until
+//! cleared.
+
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::tenant;
+
+const FLAGS_FILE: &str = "data/feature_flags.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// A fracturing loop repeats one of its choices
+    Echoes,
+    /// Typing a choice of one's own instead of picking an offered one
+    FreeformInput,
+    /// Moments rendered as PNG share cards
+    ImageGeneration,
+    /// The daily challenge and its leaderboards
+    ChallengeMode,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 4] = [
+        Feature::Echoes,
+        Feature::FreeformInput,
+        Feature::ImageGeneration,
+        Feature::ChallengeMode,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().replace('-', "_").as_str() {
+            "echoes" => Some(Feature::Echoes),
+            "freeform_input" | "freeform" => Some(Feature::FreeformInput),
+            "image_generation" | "images" => Some(Feature::ImageGeneration),
+            "challenge_mode" | "challenge" => Some(Feature::ChallengeMode),
+            _ => None,
+        }
+    }
+
+    /// Mixed into player ids so each feature draws its own cohort
+    fn salt(self) -> u64 {
+        match self {
+            Feature::Echoes => 0x9e37_79b9_7f4a_7c15,
+            Feature::FreeformInput => 0xc2b2_ae3d_27d4_eb4f,
+            Feature::ImageGeneration => 0x1656_67b1_9e37_79f9,
+            Feature::ChallengeMode => 0x27d4_eb2f_1656_67c5,
+        }
+    }
+}
+
+/// Who a feature is on for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rollout {
+    pub enabled: bool,
+    /// Share of players, from 0 to 100, in the feature's cohort
+    pub percent: u8,
+}
+
+impl Default for Rollout {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            percent: 100,
+        }
+    }
+}
+
+impl Rollout {
+    /// `on`, `off`, or the percent of players in the cohort
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "on" | "true" => Some(Self::default()),
+            "off" | "false" => Some(Self {
+                enabled: false,
+                percent: 100,
+            }),
+            percent => percent
+                .trim_end_matches('%')
+                .parse()
+                .ok()
+                .filter(|p| *p <= 100)
+                .map(|percent| Self {
+                    enabled: true,
+                    percent,
+                }),
+        }
+    }
+
+    /// Whether the feature is on for `player`. Without a player, only a
+    /// rollout to everyone counts.
+    fn admits(self, feature: Feature, player: Option<Uuid>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if self.percent >= 100 {
+            return true;
+        }
+        player.is_some_and(|id| {
+            let id = id.as_u128();
+            let mut rng = StdRng::seed_from_u64((id >> 64) as u64 ^ id as u64 ^ feature.salt());
+            rng.random_range(0..100) < self.percent
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FlagView {
+    pub feature: Feature,
+    #[serde(flatten)]
+    pub rollout: Rollout,
+    /// Set through the admin API rather than `FEATURE_FLAGS`
+    pub overridden: bool,
+}
+
+/// The flags of a deployment, with the admin overrides persisted to
+/// `data/feature_flags.json`
+pub struct FeatureFlags {
+    path: PathBuf,
+    seeded: HashMap<Feature, Rollout>,
+    overrides: Mutex<BTreeMap<Feature, Rollout>>,
+}
+
+impl FeatureFlags {
+    pub fn load(config: &Config) -> Self {
+        let path = tenant::data_path(config.tenant.as_deref(), FLAGS_FILE);
+        let overrides = Self::read(&path).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load feature flags from {}: {}", path.display(), e);
+            BTreeMap::new()
+        });
+        Self {
+            path,
+            seeded: config.feature_flags.clone(),
+            overrides: Mutex::new(overrides),
+        }
+    }
+
+    fn read(path: &Path) -> Result<BTreeMap<Feature, Rollout>> {
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn overrides(&self) -> std::sync::MutexGuard<'_, BTreeMap<Feature, Rollout>> {
+        self.overrides.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn rollout(&self, feature: Feature) -> Rollout {
+        self.overrides()
+            .get(&feature)
+            .or_else(|| self.seeded.get(&feature))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Whether `feature` is on for `player`, or for everyone with `None`
+    pub fn is_enabled(&self, feature: Feature, player: Option<Uuid>) -> bool {
+        self.rollout(feature).admits(feature, player)
+    }
+
+    /// The features on for `player`, or for everyone with `None`
+    pub fn active(&self, player: Option<Uuid>) -> Vec<Feature> {
+        Feature::ALL
+            .into_iter()
+            .filter(|f| self.is_enabled(*f, player))
+            .collect()
+    }
+
+    pub fn view(&self, feature: Feature) -> FlagView {
+        FlagView {
+            feature,
+            rollout: self.rollout(feature),
+            overridden: self.overrides().contains_key(&feature),
+        }
+    }
+
+    pub fn report(&self) -> Vec<FlagView> {
+        Feature::ALL.into_iter().map(|f| self.view(f)).collect()
+    }
+
+    /// Override a flag, or with `None` fall back to `FEATURE_FLAGS`
+    pub fn set(&self, feature: Feature, rollout: Option<Rollout>) -> Result<()> {
+        let mut overrides = self.overrides();
+        match rollout {
+            Some(rollout) => overrides.insert(feature, rollout),
+            None => overrides.remove(&feature),
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&*overrides)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,54 @@
+use super::*;
+
+fn flags(seeded: &[(Feature, Rollout)]) -> FeatureFlags {
+    FeatureFlags {
+        path: PathBuf::from("data/feature_flags.json"),
+        seeded: seeded.iter().copied().collect(),
+        overrides: Mutex::new(BTreeMap::new()),
+    }
+}
+
+#[test]
+fn rollouts_parse_from_the_environment_format() {
+    assert_eq!(Rollout::parse("on"), Some(Rollout::default()));
+    assert!(!Rollout::parse("OFF").unwrap().enabled);
+    assert_eq!(Rollout::parse("25%").unwrap().percent, 25);
+    assert_eq!(Rollout::parse("101"), None);
+    assert_eq!(Feature::parse("Challenge-Mode"), Some(Feature::ChallengeMode));
+    assert_eq!(Feature::parse("teleport"), None);
+}
+
+#[test]
+fn a_partial_rollout_draws_a_stable_cohort() {
+    let rollout = Rollout::parse("30").unwrap();
+    let players: Vec<Uuid> = (0..1000).map(|_| Uuid::new_v4()).collect();
+    let admitted = players
+        .iter()
+        .filter(|id| rollout.admits(Feature::Echoes, Some(**id)))
+        .count();
+    assert!((200..400).contains(&admitted), "{} of 1000 admitted", admitted);
+
+    for id in &players[..50] {
+        assert_eq!(
+            rollout.admits(Feature::Echoes, Some(*id)),
+            rollout.admits(Feature::Echoes, Some(*id))
+        );
+    }
+    assert!(!rollout.admits(Feature::Echoes, None));
+    assert!(Rollout::default().admits(Feature::Echoes, None));
+}
+
+#[test]
+fn overrides_win_over_the_seed() {
+    let off = Rollout::parse("off").unwrap();
+    let flags = flags(&[(Feature::FreeformInput, off)]);
+    assert_eq!(
+        flags.active(None),
+        vec![Feature::Echoes, Feature::ImageGeneration, Feature::ChallengeMode]
+    );
+
+    flags.overrides().insert(Feature::FreeformInput, Rollout::default());
+    assert!(flags.is_enabled(Feature::FreeformInput, None));
+    assert!(flags.view(Feature::FreeformInput).overridden);
+    assert!(!flags.view(Feature::Echoes).overridden);
+}
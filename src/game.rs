@@ -1,14 +1,39 @@
 use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 
+use crate::abuse::AbuseRecord;
+use crate::anchors::ReachedAnchor;
+use crate::assets::MomentAssets;
+use crate::build_info::{self, BuildInfo};
+use crate::challenge::ChallengeRun;
+use crate::context::{ContextBuilder, ContextProfile, HISTORY_TURN_TOKENS, Keep};
+use crate::dialogue::{self, CharacterMemory};
+use crate::endings::EndingType;
+use crate::epilogue::Epilogue;
+use crate::fate::FateBias;
+use crate::graph::ChoiceGraph;
+use crate::i18n::Locale;
+use crate::patch::PlayerSettings;
+use crate::privacy::{Consent, ExportPrivacy};
+use crate::persona::Persona;
+use crate::provenance::Provenance;
+use crate::race::RaceRun;
+use crate::stability::MAX_STABILITY;
+
 /// A single choice the player can make
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Choice {
     pub id: String,
     pub text: String,
     pub consequence_hint: Option<String>,
+    /// Who wrote the choice, where it isn't who wrote its moment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
 }
 
 /// A narrative moment in the game
@@ -20,6 +45,295 @@ pub struct NarrativeMoment {
     pub mood: String, // "hopeful", "nihilistic", "neutral", "dark", "transcendent"
     pub choices: Vec<Choice>,
     pub timestamp: DateTime<Utc>,
+    /// The full moment was spilled to disk and this is a short stand-in
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub summarized: bool,
+    /// World updates proposed by the narrator; only those the world rules accept are kept
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub world_updates: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub state: MomentState,
+    /// The moment as shown to a player reading another language; the moment
+    /// itself stays in English so scoring and stats don't depend on language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<MomentTranslation>,
+    /// Line shown before a moment relived from an earlier loop
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deja_vu: Option<String>,
+    /// How far fate gravity pulled this moment's choices toward an ending
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fate: Option<FateBias>,
+    /// Scenario assets an anchor moment is staged with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assets: Option<MomentAssets>,
+    /// Who wrote the moment; absent from moments of older saves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MomentTranslation {
+    pub locale: Locale,
+    pub text: String,
+    /// Displayed choice texts, by choice id
+    pub choices: BTreeMap<String, String>,
+}
+
+/// Where a moment is in its lifecycle.
+///
+/// Moments are generated, presented to the player, chosen against and finally
+/// archived with their loop. A chosen moment returns to presented if the next
+/// moment can't be generated, so the player can choose again; what the choice
+/// counted is taken back with it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MomentState {
+    Generated,
+    /// Saves from before the lifecycle only hold moments already shown
+    #[default]
+    Presented,
+    Chosen,
+    Archived,
+}
+
+impl MomentState {
+    fn can_become(self, next: MomentState) -> bool {
+        use MomentState::*;
+        matches!(
+            (self, next),
+            (Generated, Presented)
+                | (Presented, Chosen)
+                | (Chosen, Presented)
+                | (Presented, Archived)
+                | (Chosen, Archived)
+        )
+    }
+}
+
+/// A moment lifecycle rule was broken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MomentError {
+    /// The moment isn't the one the player is currently facing
+    Stale(Uuid),
+    Transition {
+        moment_id: Uuid,
+        from: MomentState,
+        to: MomentState,
+    },
+}
+
+impl std::fmt::Display for MomentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MomentError::Stale(id) => write!(f, "moment {} is not the current moment", id),
+            MomentError::Transition { moment_id, from, to } => {
+                write!(f, "moment {} can't go from {:?} to {:?}", moment_id, from, to)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MomentError {}
+
+/// A key memory the player asked to change couldn't be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryError {
+    /// No key memory at that index
+    NotFound(usize),
+    /// Pinned memories must be unpinned before they can be forgotten
+    Pinned(usize),
+}
+
+impl std::fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryError::NotFound(index) => write!(f, "no key memory #{}", index),
+            MemoryError::Pinned(index) => write!(f, "key memory #{} is pinned", index),
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+/// Why a game operation was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    /// The player left memory (deleted or evicted) while a request was in flight
+    PlayerGone(Uuid),
+    /// The loop the request was made in has ended; the player is in another now
+    LoopCompleted(u64),
+    /// The moment isn't the one the player is facing, or can't change state
+    MomentStale(MomentError),
+    /// The run is finished, or its challenge expired, and takes no more play
+    RunSealed,
+}
+
+impl std::fmt::Display for GameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameError::PlayerGone(id) => write!(f, "player {} is no longer in the game", id),
+            GameError::LoopCompleted(number) => write!(f, "loop #{} has already ended", number),
+            GameError::MomentStale(error) => error.fmt(f),
+            GameError::RunSealed => write!(f, "the run is over"),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+impl From<MomentError> for GameError {
+    fn from(error: MomentError) -> Self {
+        GameError::MomentStale(error)
+    }
+}
+
+impl NarrativeMoment {
+    /// Move the moment to its next lifecycle state
+    pub fn transition(&mut self, to: MomentState) -> Result<(), MomentError> {
+        if !self.state.can_become(to) {
+            return Err(MomentError::Transition {
+                moment_id: self.id,
+                from: self.state,
+                to,
+            });
+        }
+        self.state = to;
+        Ok(())
+    }
+
+    /// Shuffle the choices to counter first-option bias.
+    ///
+    /// Seeded by the moment id so the order is stable for a given moment.
+    pub fn shuffle_choices(&mut self) {
+        let id = self.id.as_u128();
+        let mut rng = StdRng::seed_from_u64((id >> 64) as u64 ^ id as u64);
+        self.choices.shuffle(&mut rng);
+    }
+
+    /// Rough in-memory size, used to cap history growth
+    pub fn estimated_bytes(&self) -> usize {
+        let choices: usize = self
+            .choices
+            .iter()
+            .map(|c| {
+                c.id.len() + c.text.len() + c.consequence_hint.as_ref().map_or(0, |h| h.len()) + 64
+            })
+            .sum();
+        std::mem::size_of::<Self>()
+            + self.text.len()
+            + self.speaker.as_ref().map_or(0, |s| s.len())
+            + self.mood.len()
+            + choices
+    }
+
+    /// Short stand-in kept in memory once the full moment is spilled
+    pub fn summary(&self) -> NarrativeMoment {
+        let first_sentence = self
+            .text
+            .split_inclusive(['.', '!', '?'])
+            .next()
+            .unwrap_or(&self.text)
+            .trim();
+        NarrativeMoment {
+            id: self.id,
+            text: first_sentence.chars().take(160).collect(),
+            speaker: self.speaker.clone(),
+            mood: self.mood.clone(),
+            choices: Vec::new(),
+            timestamp: self.timestamp,
+            summarized: true,
+            world_updates: Vec::new(),
+            state: self.state,
+            translation: None,
+            deja_vu: None,
+            fate: None,
+            assets: None,
+            provenance: self.provenance.clone(),
+        }
+    }
+}
+
+/// How often each displayed position was picked, and how often it was on offer
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChoicePositionStats {
+    pub picks: Vec<u64>,
+    pub offered: Vec<u64>,
+}
+
+impl ChoicePositionStats {
+    pub fn record(&mut self, position: usize, options: usize) {
+        if self.offered.len() < options {
+            self.offered.resize(options, 0);
+            self.picks.resize(options, 0);
+        }
+        for offered in self.offered.iter_mut().take(options) {
+            *offered += 1;
+        }
+        self.picks[position] += 1;
+    }
+
+    pub fn merge(&mut self, other: &ChoicePositionStats) {
+        let len = self.offered.len().max(other.offered.len());
+        self.offered.resize(len, 0);
+        self.picks.resize(len, 0);
+        for (i, offered) in other.offered.iter().enumerate() {
+            self.offered[i] += offered;
+        }
+        for (i, picks) in other.picks.iter().enumerate() {
+            self.picks[i] += picks;
+        }
+    }
+}
+
+/// The three scripted beats of a loop reset
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetBeatKind {
+    Fade,
+    Fragment,
+    Awakening,
+}
+
+/// A single beat of the reset sequence shown between loops
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResetBeat {
+    pub kind: ResetBeatKind,
+    pub text: String,
+}
+
+/// Why a loop ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopEndCause {
+    ChoseReset,
+    TimerExpired,
+    Died,
+    Surrendered,
+    /// Its stability ran out; only the server ends a loop this way
+    Collapsed,
+}
+
+impl LoopEndCause {
+    pub fn label(self) -> &'static str {
+        match self {
+            LoopEndCause::ChoseReset => "chose_reset",
+            LoopEndCause::TimerExpired => "timer_expired",
+            LoopEndCause::Died => "died",
+            LoopEndCause::Surrendered => "surrendered",
+            LoopEndCause::Collapsed => "collapsed",
+        }
+    }
+
+    /// How the loop ended, for the narrator of the reset
+    pub fn describe(self) -> &'static str {
+        match self {
+            LoopEndCause::ChoseReset => "The player chose to let the loop end.",
+            LoopEndCause::TimerExpired => "The loop ran out of time before the player was done.",
+            LoopEndCause::Died => "The player died.",
+            LoopEndCause::Surrendered => "The player gave up.",
+            LoopEndCause::Collapsed => "The loop collapsed under its own paradoxes.",
+        }
+    }
 }
 
 /// Represents a single loop iteration
@@ -29,157 +343,1092 @@ pub struct Loop {
     pub started_at: DateTime<Utc>,
     pub ended_at: Option<DateTime<Utc>>,
     pub choices_made: Vec<String>,
+    /// How the loop ended: a `LoopEndCause` label, or `finale: <ending>`
     pub outcome: Option<String>,
+    #[serde(default)]
+    pub reset_sequence: Vec<ResetBeat>,
+    /// Characters who died this loop and have not returned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dead_characters: Vec<String>,
+    /// Artifacts found this loop
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<String>,
+    /// How well the loop holds together; paradoxes wear it down, and at zero it resets
+    #[serde(default = "full_stability")]
+    pub stability: u8,
+    /// Moments the player had written again this loop
+    #[serde(default)]
+    pub regenerations: u32,
 }
 
-/// Memory that persists across loops (like Flowey)
+fn full_stability() -> u8 {
+    MAX_STABILITY
+}
+
+/// The closing arc of a completed run. Once set, the save is read-only.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Finale {
+    pub ending: EndingType,
+    pub moments: Vec<NarrativeMoment>,
+    /// True when the finale was forced by the loop limit rather than earned
+    pub forced: bool,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// A finished loop with its narrative, kept on disk after the reset
 #[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchivedLoop {
+    /// Run the loop belongs to; a player's first run shares the player's id
+    pub player_id: Uuid,
+    pub loop_info: Loop,
+    pub moments: Vec<NarrativeMoment>,
+    pub archived_at: DateTime<Utc>,
+    /// Set once the retention policy replaced the raw moments with a summary
+    #[serde(default)]
+    pub shard: Option<MemoryShard>,
+}
+
+/// What remains of an old loop after compaction
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryShard {
+    pub summary: String,
+    pub moment_count: usize,
+    pub choice_count: usize,
+    pub moods: HashMap<String, usize>,
+    pub compacted_at: DateTime<Utc>,
+}
+
+impl ArchivedLoop {
+    pub fn is_compacted(&self) -> bool {
+        self.shard.is_some()
+    }
+
+    /// Drop the raw narrative, keeping only the summary and stats
+    pub fn compact(&mut self, summary: String) {
+        let mut moods = HashMap::new();
+        for moment in &self.moments {
+            *moods.entry(moment.mood.clone()).or_insert(0) += 1;
+        }
+        self.shard = Some(MemoryShard {
+            summary,
+            moment_count: self.moments.len(),
+            choice_count: self.loop_info.choices_made.len(),
+            moods,
+            compacted_at: Utc::now(),
+        });
+        self.moments.clear();
+    }
+}
+
+/// How a streak of same-side choices bends their score.
+///
+/// Each further choice in a streak counts for less, while the first choice
+/// that breaks a long streak swings the score hard the other way.
+#[derive(Clone, Copy, Debug)]
+pub struct StreakCurve {
+    /// Each further choice in a streak scores this fraction of the one before
+    pub decay: f64,
+    /// Extra multiplier per choice of the streak being broken
+    pub swing: f64,
+    /// Cap on the streak-breaking multiplier
+    pub max_multiplier: f64,
+}
+
+impl StreakCurve {
+    /// Weight a persona's base delta by the streak so far (positive for
+    /// consecutive dark choices, negative for light ones)
+    pub fn weight(&self, base: i32, streak: i32, is_dark: bool) -> i32 {
+        let length = streak.unsigned_abs() as f64;
+        let factor = if streak != 0 && (streak > 0) == is_dark {
+            self.decay.powf(length)
+        } else {
+            (1.0 + self.swing * (length - 1.0).max(0.0)).min(self.max_multiplier)
+        };
+        // A choice always moves the score at least one point
+        match (base as f64 * factor).round() as i32 {
+            0 => base.signum(),
+            weighted => weighted,
+        }
+    }
+}
+
+/// Score changes remembered for pacing the narrative
+pub const RECENT_SCORE_DELTAS: usize = 5;
+
+/// Nihilism points it costs to forget a key memory on purpose
+pub const FORGET_SCORE_SHIFT: i32 = 3;
+
+/// Memory that persists across loops (like Flowey)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PersistentMemory {
     pub total_loops: u64,
     pub total_choices: u64,
     pub dark_choices: u64,
     pub light_choices: u64,
     pub key_memories: Vec<String>,
+    /// Indices into `key_memories` the player pinned, kept from decay and
+    /// always in the narrator's context
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pinned_memories: Vec<usize>,
+    /// Summaries of text the player brought into the run, never the text itself
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seed_memories: Vec<String>,
     pub character_deaths: HashMap<String, u64>,
     pub truths_discovered: Vec<String>,
     pub nihilism_score: i32, // -100 (hopeful) to +100 (nihilistic)
+    #[serde(default)]
+    pub endings_reached: Vec<EndingType>,
+    /// Endings the player turned down at their threshold, each opening a branch
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub endings_refused: Vec<EndingType>,
+    #[serde(default)]
+    pub choice_positions: ChoicePositionStats,
+    /// Consecutive dark (positive) or light (negative) choices
+    #[serde(default)]
+    pub choice_streak: i32,
+    /// Which soul this run was to reach each ending, counted across all
+    /// players and noised like the published counts
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub ending_ordinals: HashMap<EndingType, u64>,
+    /// Score changes of the latest choices, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent_score_deltas: Vec<i32>,
+    /// What the player said to each character, keyed by `dialogue::canonical` name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub characters: HashMap<String, CharacterMemory>,
+    /// Moments the player had written again, across every loop
+    #[serde(default)]
+    pub moments_regenerated: u64,
+    /// Key memories the player chose to forget
+    #[serde(default)]
+    pub memories_forgotten: u64,
 }
 
-impl Default for PersistentMemory {
-    fn default() -> Self {
-        Self {
-            total_loops: 0,
-            total_choices: 0,
-            dark_choices: 0,
-            light_choices: 0,
-            key_memories: Vec::new(),
-            character_deaths: HashMap::new(),
-            truths_discovered: Vec::new(),
-            nihilism_score: 0,
-        }
+/// A key memory as the player sees it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct KeyMemory {
+    pub index: usize,
+    pub text: String,
+    pub pinned: bool,
+    /// Worn down to a fragment by an absence
+    pub blurred: bool,
+}
+
+impl PersistentMemory {
+    pub fn is_pinned(&self, index: usize) -> bool {
+        self.pinned_memories.contains(&index)
+    }
+
+    pub fn memories(&self) -> Vec<KeyMemory> {
+        self.key_memories
+            .iter()
+            .enumerate()
+            .map(|(index, text)| KeyMemory {
+                index,
+                text: text.clone(),
+                pinned: self.is_pinned(index),
+                blurred: text.starts_with("..."),
+            })
+            .collect()
+    }
+
+    /// Key memories with the pinned ones first, as the narrator is given them
+    pub fn memories_by_pin(&self) -> impl Iterator<Item = &String> {
+        let (pinned, rest): (Vec<_>, Vec<_>) = self
+            .key_memories
+            .iter()
+            .enumerate()
+            .partition(|(index, _)| self.is_pinned(*index));
+        pinned.into_iter().chain(rest).map(|(_, text)| text)
     }
 }
 
-/// A player session
+/// One playthrough: its loops, memory and story
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Player {
-    pub id: Uuid,
-    pub name: Option<String>,
+pub struct Run {
+    /// Absent for a player's first run, which shares the player's id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_name: Option<String>,
+    /// Title the narrator gave the run after its first loop; `run_name`
+    /// takes its place when the player set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
     pub current_loop: Loop,
     pub memory: PersistentMemory,
     pub narrative_history: Vec<NarrativeMoment>,
-    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub graph: ChoiceGraph,
+    #[serde(default)]
+    pub persona: Persona,
+    /// One-time notes for the narrator, consumed by the next generated moment
+    #[serde(default)]
+    pub pending_notes: Vec<String>,
+    /// Model narrating the run in place of the configured one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub finale: Option<Finale>,
+    #[serde(default)]
+    pub challenge: Option<ChallengeRun>,
+    /// Set on runs started in a race
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub race: Option<RaceRun>,
+    /// Narrator judgments of consequential choices, keyed by ledger entry
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub ledger_judgments: HashMap<String, String>,
+    /// When the player last made a choice or received a moment
+    #[serde(default)]
+    pub last_active_at: Option<DateTime<Utc>>,
+    /// Absent from runs started before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    /// Post-game codas of the endings reached, started or played
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub epilogues: Vec<Epilogue>,
+    /// Handwritten scenario anchors the run has been through
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anchors: Vec<ReachedAnchor>,
+    /// The run as it was before the choice now awaiting its answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_choice: Option<PendingChoice>,
 }
 
-impl Player {
-    pub fn new() -> Self {
+/// What a choice awaiting its answer may change, kept so a failed answer
+/// leaves the choice uncounted
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingChoice {
+    pub moment_id: Uuid,
+    pub memory: PersistentMemory,
+    /// Choices made in the loop before this one
+    pub choices_made: usize,
+}
+
+impl Run {
+    fn new(run_id: Option<Uuid>, run_name: Option<String>, persona: Persona) -> Self {
         let now = Utc::now();
         Self {
-            id: Uuid::new_v4(),
-            name: None,
+            run_id,
+            run_name,
+            title: None,
             current_loop: Loop {
                 number: 1,
                 started_at: now,
                 ended_at: None,
                 choices_made: Vec::new(),
                 outcome: None,
+                reset_sequence: Vec::new(),
+                dead_characters: Vec::new(),
+                artifacts: Vec::new(),
+                stability: MAX_STABILITY,
+                regenerations: 0,
             },
             memory: PersistentMemory::default(),
             narrative_history: Vec::new(),
-            created_at: now,
+            graph: ChoiceGraph::default(),
+            persona,
+            pending_notes: Vec::new(),
+            model: None,
+            finale: None,
+            challenge: None,
+            race: None,
+            ledger_judgments: HashMap::new(),
+            last_active_at: Some(now),
+            started_at: Some(now),
+            epilogues: Vec::new(),
+            anchors: Vec::new(),
+            pending_choice: None,
         }
     }
 
-    /// Reset the current loop but keep persistent memory
-    pub fn reset_loop(&mut self) {
-        self.memory.total_loops += 1;
+    /// Seed shared by every run of the same daily challenge or race
+    pub fn shared_seed(&self) -> Option<u64> {
+        self.challenge
+            .as_ref()
+            .map(|c| c.seed)
+            .or(self.race.as_ref().map(|r| r.seed))
+    }
 
-        // Store the outcome of the previous loop
-        if let Some(last_moment) = self.narrative_history.last() {
-            if !self.memory.key_memories.contains(&last_moment.text) {
-                if self.memory.key_memories.len() < 20 {
-                    self.memory
-                        .key_memories
-                        .push(last_moment.text.clone());
+    /// Prompt section for the challenge or race scenario the run plays under
+    pub fn scenario_prompt(&self) -> Option<String> {
+        match (&self.challenge, &self.race) {
+            (Some(challenge), _) => challenge.prompt(),
+            (None, Some(race)) => race.prompt(),
+            (None, None) => None,
+        }
+    }
+
+    fn view(&self, player_id: Uuid, active: bool) -> RunView {
+        RunView {
+            run_id: self.run_id.unwrap_or(player_id),
+            name: self.run_name.clone(),
+            title: self.title.clone(),
+            active,
+            persona: self.persona,
+            loop_number: self.current_loop.number,
+            nihilism_score: self.memory.nihilism_score,
+            completed: self.finale.is_some(),
+            last_active_at: self.last_active_at,
+        }
+    }
+}
+
+/// A run as listed to its player
+#[derive(Clone, Debug, Serialize)]
+pub struct RunView {
+    pub run_id: Uuid,
+    pub name: Option<String>,
+    /// Generated title, shown when the player hasn't named the run
+    pub title: Option<String>,
+    pub active: bool,
+    pub persona: Persona,
+    pub loop_number: u64,
+    pub nihilism_score: i32,
+    pub completed: bool,
+    pub last_active_at: Option<DateTime<Utc>>,
+}
+
+/// A player identity and the runs it holds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Player {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub presence_public: bool,
+    /// Account this player was upgraded into, if any
+    #[serde(default)]
+    pub account_id: Option<Uuid>,
+    /// The run being played
+    #[serde(flatten)]
+    pub run: Run,
+    /// The player's other runs, waiting to be switched back in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<Run>,
+    /// Strikes and ghost mode; shared by all of the player's runs
+    #[serde(default, skip_serializing_if = "AbuseRecord::is_clean")]
+    pub abuse: AbuseRecord,
+    /// Preferences, language, time zone and notes the player set themselves
+    #[serde(default, skip_serializing_if = "PlayerSettings::is_empty")]
+    pub settings: PlayerSettings,
+    /// Consent to data collection; `None` until the player first answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consent: Option<Consent>,
+    /// What the player keeps out of their exports
+    #[serde(default, skip_serializing_if = "ExportPrivacy::is_open")]
+    pub export_privacy: ExportPrivacy,
+    /// Server build that last wrote this save
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildInfo>,
+    /// Tenant the player belongs to; `None` for the default deployment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+/// Lightweight view of a player used in API responses.
+///
+/// Omits the narrative history and branching map, which grow without bound on
+/// long runs; clients page through history via the history endpoint instead.
+#[derive(Clone, Debug, Serialize)]
+pub struct PlayerSummary {
+    pub id: Uuid,
+    pub name: Option<String>,
+    /// The active run
+    pub run_id: Uuid,
+    /// The active run's name, or its generated title
+    pub run_title: Option<String>,
+    pub current_loop: Loop,
+    pub memory: PersistentMemory,
+    pub history_length: usize,
+    pub last_moment: Option<NarrativeMoment>,
+    pub persona: Persona,
+    pub completed: bool,
+    pub challenge: Option<ChallengeRun>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub race: Option<RaceRun>,
+    pub settings: PlayerSettings,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Player {
+    pub fn new() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: None,
+            created_at: Utc::now(),
+            presence_public: false,
+            account_id: None,
+            run: Run::new(None, None, Persona::default()),
+            runs: Vec::new(),
+            abuse: AbuseRecord::default(),
+            settings: PlayerSettings::default(),
+            consent: None,
+            export_privacy: ExportPrivacy::default(),
+            build: Some(build_info::current().clone()),
+            tenant: None,
+        }
+    }
+
+    /// Id of the active run; archives and choice logs are stored under it
+    pub fn run_id(&self) -> Uuid {
+        self.run.run_id.unwrap_or(self.id)
+    }
+
+    /// The player's name for the active run, or its generated title
+    pub fn run_title(&self) -> Option<&str> {
+        self.run.run_name.as_deref().or(self.run.title.as_deref())
+    }
+
+    /// One of the player's runs, active or not
+    pub fn run_mut(&mut self, run_id: Uuid) -> Option<&mut Run> {
+        let id = self.id;
+        std::iter::once(&mut self.run)
+            .chain(&mut self.runs)
+            .find(|r| r.run_id.unwrap_or(id) == run_id)
+    }
+
+    /// Every run this player holds, the active one first
+    pub fn run_views(&self) -> Vec<RunView> {
+        self.all_runs()
+            .enumerate()
+            .map(|(i, r)| r.view(self.id, i == 0))
+            .collect()
+    }
+
+    /// Start another run alongside the active one, without switching to it
+    pub fn create_run(&mut self, name: Option<String>, persona: Persona) -> RunView {
+        let run = Run::new(Some(Uuid::new_v4()), name, persona);
+        let view = run.view(self.id, false);
+        self.runs.push(run);
+        view
+    }
+
+    /// Switch to another run; false if the player holds no such run
+    pub fn activate_run(&mut self, run_id: Uuid) -> bool {
+        if self.run_id() == run_id {
+            return true;
+        }
+        let Some(i) = self
+            .runs
+            .iter()
+            .position(|r| r.run_id.unwrap_or(self.id) == run_id)
+        else {
+            return false;
+        };
+        std::mem::swap(&mut self.run, &mut self.runs[i]);
+        true
+    }
+
+    /// The active run followed by the others
+    pub fn all_runs(&self) -> impl Iterator<Item = &Run> {
+        std::iter::once(&self.run).chain(&self.runs)
+    }
+
+    /// Endings reached in any run; unlocks are shared across runs
+    pub fn endings_reached(&self) -> Vec<EndingType> {
+        let mut endings: Vec<EndingType> = Vec::new();
+        for run in self.all_runs() {
+            for ending in &run.memory.endings_reached {
+                if !endings.contains(ending) {
+                    endings.push(ending.clone());
                 }
             }
         }
+        endings
+    }
+
+    /// Completed runs are read-only
+    pub fn is_completed(&self) -> bool {
+        self.run.finale.is_some()
+    }
+
+    /// Completed runs and challenge runs from a previous day can't be played
+    pub fn is_locked(&self) -> bool {
+        self.is_completed() || self.run.challenge.as_ref().is_some_and(|c| c.is_expired())
+    }
+
+    /// Refuse play once the run is locked
+    pub fn ensure_playable(&self) -> Result<(), GameError> {
+        if self.is_locked() {
+            return Err(GameError::RunSealed);
+        }
+        Ok(())
+    }
+
+    /// Refuse a request made in loop `number` once that loop has ended
+    pub fn ensure_loop(&self, number: u64) -> Result<(), GameError> {
+        if self.run.current_loop.number != number {
+            return Err(GameError::LoopCompleted(number));
+        }
+        Ok(())
+    }
+
+    /// Last activity, falling back to the latest moment for older saves
+    pub fn last_active(&self) -> DateTime<Utc> {
+        self.run
+            .last_active_at
+            .or_else(|| self.run.narrative_history.last().map(|m| m.timestamp))
+            .unwrap_or(self.created_at)
+    }
+
+    /// Build the response view of this player
+    pub fn summary(&self) -> PlayerSummary {
+        PlayerSummary {
+            id: self.id,
+            name: self.name.clone(),
+            run_id: self.run_id(),
+            run_title: self.run_title().map(str::to_string),
+            current_loop: self.run.current_loop.clone(),
+            memory: self.run.memory.clone(),
+            history_length: self.run.narrative_history.len(),
+            last_moment: self.run.narrative_history.last().cloned(),
+            persona: self.run.persona,
+            completed: self.is_completed(),
+            challenge: self.run.challenge.clone(),
+            race: self.run.race.clone(),
+            settings: self.settings.clone(),
+            created_at: self.created_at,
+        }
+    }
+
+    /// End the current loop for `cause` and start the next, keeping persistent memory.
+    ///
+    /// Returns the finished loop, with its reset sequence, for archiving.
+    pub fn reset_loop(
+        &mut self,
+        cause: LoopEndCause,
+        reset_sequence: Vec<ResetBeat>,
+    ) -> Result<ArchivedLoop, MomentError> {
+        let moments = self.archived_moments()?;
+        self.run.memory.total_loops += 1;
+
+        // Store the outcome of the previous loop
+        if let Some(last_moment) = self.run.narrative_history.last()
+            && !self.run.memory.key_memories.contains(&last_moment.text)
+            && self.run.memory.key_memories.len() < 20
+        {
+            self.run.memory.key_memories.push(last_moment.text.clone());
+        }
 
         let now = Utc::now();
-        self.current_loop = Loop {
-            number: self.memory.total_loops + 1,
-            started_at: now,
-            ended_at: None,
-            choices_made: Vec::new(),
-            outcome: None,
+        let mut finished = std::mem::replace(
+            &mut self.run.current_loop,
+            Loop {
+                number: self.run.memory.total_loops + 1,
+                started_at: now,
+                ended_at: None,
+                choices_made: Vec::new(),
+                outcome: None,
+                reset_sequence: Vec::new(),
+                dead_characters: Vec::new(),
+                artifacts: Vec::new(),
+                stability: MAX_STABILITY,
+                regenerations: 0,
+            },
+        );
+        finished.ended_at = Some(now);
+        finished.outcome = Some(cause.label().to_string());
+        finished.reset_sequence = reset_sequence;
+        self.run.narrative_history.clear();
+
+        Ok(ArchivedLoop {
+            player_id: self.run_id(),
+            loop_info: finished,
+            moments,
+            archived_at: now,
+            shard: None,
+        })
+    }
+
+    /// End the run for good, archiving the final loop. The save becomes read-only.
+    pub fn complete_run(&mut self, finale: Finale) -> Result<ArchivedLoop, MomentError> {
+        let moments = self.archived_moments()?;
+        self.run.memory.total_loops += 1;
+        self.run.current_loop.ended_at = Some(finale.completed_at);
+        self.run.current_loop.outcome = Some(format!("finale: {}", finale.ending.get_title(Locale::En)));
+
+        let archived = ArchivedLoop {
+            player_id: self.run_id(),
+            loop_info: self.run.current_loop.clone(),
+            moments,
+            archived_at: finale.completed_at,
+            shard: None,
         };
-        self.narrative_history.clear();
+        self.run.finale = Some(finale);
+        Ok(archived)
     }
 
-    /// Record a choice and update memory
-    pub fn make_choice(&mut self, choice_id: &str, is_dark: bool) {
-        self.current_loop.choices_made.push(choice_id.to_string());
-        self.memory.total_choices += 1;
+    /// Record a choice and update memory, returning the change in nihilism score
+    pub fn make_choice(&mut self, choice_id: &str, is_dark: bool, curve: &StreakCurve) -> i32 {
+        self.run.current_loop.choices_made.push(choice_id.to_string());
+        self.run.memory.total_choices += 1;
+        self.run.last_active_at = Some(Utc::now());
+        let before = self.run.memory.nihilism_score;
 
+        let (dark_delta, light_delta) = self.run.persona.score_deltas();
+        let streak = self.run.memory.choice_streak;
         if is_dark {
-            self.memory.dark_choices += 1;
-            self.memory.nihilism_score = (self.memory.nihilism_score + 5).min(100);
+            self.run.memory.dark_choices += 1;
+            let delta = curve.weight(dark_delta, streak, true);
+            self.run.memory.nihilism_score = (self.run.memory.nihilism_score + delta).min(100);
+            self.run.memory.choice_streak = streak.max(0) + 1;
         } else {
-            self.memory.light_choices += 1;
-            self.memory.nihilism_score = (self.memory.nihilism_score - 3).max(-100);
+            self.run.memory.light_choices += 1;
+            let delta = curve.weight(light_delta, streak, false);
+            self.run.memory.nihilism_score = (self.run.memory.nihilism_score + delta).max(-100);
+            self.run.memory.choice_streak = streak.min(0) - 1;
+        }
+        let delta = self.run.memory.nihilism_score - before;
+        let recent = &mut self.run.memory.recent_score_deltas;
+        recent.push(delta);
+        if recent.len() > RECENT_SCORE_DELTAS {
+            recent.remove(0);
+        }
+        delta
+    }
+
+    /// Record where the chosen option was displayed in the current moment
+    pub fn record_choice_position(&mut self, choice_id: &str) -> Option<usize> {
+        let moment = self.run.narrative_history.last()?;
+        let position = moment.choices.iter().position(|c| c.id == choice_id)?;
+        let options = moment.choices.len();
+        self.run.memory.choice_positions.record(position, options);
+        Some(position)
+    }
+
+    /// Personas available to this player
+    pub fn unlocked_personas(&self) -> Vec<Persona> {
+        let endings = self.endings_reached();
+        Persona::ALL
+            .into_iter()
+            .filter(|p| p.is_unlocked(&endings))
+            .collect()
+    }
+
+    /// Switch narrator mid-run; the new narrator acknowledges the change once
+    pub fn set_persona(&mut self, persona: Persona) {
+        if persona == self.run.persona {
+            return;
+        }
+        self.run.pending_notes.push(format!(
+            "The voice telling this story has just changed from {} to {}. Acknowledge the \
+             change of narrator briefly, in the new voice, before continuing.",
+            self.run.persona.get_title(),
+            persona.get_title()
+        ));
+        self.run.persona = persona;
+    }
+
+    /// Hand the run to another model (`None` for the configured one) mid-run.
+    /// The new model is briefed once on the tone so far, so the voice doesn't
+    /// break. Returns false if the run is already on that model.
+    pub fn set_model(&mut self, model: Option<String>) -> bool {
+        if model == self.run.model {
+            return false;
+        }
+        let moods: Vec<&str> = self
+            .run
+            .narrative_history
+            .iter()
+            .rev()
+            .take(5)
+            .map(|m| m.mood.as_str())
+            .collect();
+        let mut note = format!(
+            "You are taking over the narration of this run from another storyteller, in loop #{}. \
+             Keep the voice, pacing and tone the player knows (recent moods, latest first: {}), \
+             and do not mention the handover.",
+            self.run.current_loop.number,
+            if moods.is_empty() { "none yet".to_string() } else { moods.join(", ") }
+        );
+        if let Some(last) = self.run.narrative_history.last() {
+            let excerpt: String = last.text.chars().take(300).collect();
+            note.push_str(&format!(" The last passage read: \"{}\"", excerpt));
+        }
+        self.run.pending_notes.push(note);
+        self.run.model = model;
+        true
+    }
+
+    /// Remember that an ending was reached; returns true the first time
+    pub fn record_ending(&mut self, ending: &EndingType) -> bool {
+        if self.run.memory.endings_reached.contains(ending) {
+            return false;
+        }
+        self.run.memory.endings_reached.push(ending.clone());
+        true
+    }
+
+    /// Turn down an ending. It is no longer reached, and its refusal branch
+    /// shapes the rest of the run. Returns false if it was already refused.
+    pub fn refuse_ending(&mut self, ending: &EndingType) -> bool {
+        if self.has_refused(ending) {
+            return false;
+        }
+        self.run.memory.endings_refused.push(ending.clone());
+        true
+    }
+
+    pub fn has_refused(&self, ending: &EndingType) -> bool {
+        self.run.memory.endings_refused.contains(ending)
+    }
+
+    /// Present a freshly generated moment, consuming the first `heard`
+    /// one-time narrator notes: those pending when it was written. Notes
+    /// added while it was being generated wait for the next moment.
+    pub fn present_moment(
+        &mut self,
+        moment: &mut NarrativeMoment,
+        heard: usize,
+    ) -> Result<(), MomentError> {
+        moment.transition(MomentState::Presented)?;
+        let heard = heard.min(self.run.pending_notes.len());
+        self.run.pending_notes.drain(..heard);
+        self.run.last_active_at = Some(moment.timestamp);
+        // The choice before this moment got its answer
+        self.run.pending_choice = None;
+        self.run.narrative_history.push(moment.clone());
+        Ok(())
+    }
+
+    /// Pick the run up after the server stopped while generating its next
+    /// moment: a moment owning up to the stutter, offering again the choices
+    /// of the moment it interrupted. False when there is nothing to pick up.
+    pub fn stutter(&mut self, text: String) -> bool {
+        let Some(latest) = self.run.narrative_history.last_mut() else {
+            return false;
+        };
+        if latest.choices.is_empty() {
+            return false;
+        }
+        if latest.state == MomentState::Presented {
+            latest.state = MomentState::Chosen;
+        }
+        // The choices offered again are still their writers'
+        let choices = latest
+            .choices
+            .iter()
+            .map(|choice| Choice {
+                provenance: latest.provenance_of(&choice.id),
+                ..choice.clone()
+            })
+            .collect();
+        let mut moment = NarrativeMoment {
+            id: Uuid::new_v4(),
+            text,
+            speaker: None,
+            mood: "neutral".to_string(),
+            choices,
+            timestamp: Utc::now(),
+            summarized: false,
+            world_updates: Vec::new(),
+            state: MomentState::Generated,
+            translation: None,
+            deja_vu: None,
+            fate: None,
+            assets: None,
+            provenance: Some(Provenance::Server),
+        };
+        let heard = self.run.pending_notes.len();
+        self.present_moment(&mut moment, heard).is_ok()
+    }
+
+    /// Check that a chosen moment is still the latest one and awaiting its answer
+    pub fn expect_chosen(&self, chosen: Uuid) -> Result<(), MomentError> {
+        match self.run.narrative_history.last() {
+            Some(latest) if latest.id == chosen && latest.state == MomentState::Chosen => Ok(()),
+            _ => Err(MomentError::Stale(chosen)),
+        }
+    }
+
+    /// Mark the latest moment as chosen, returning its id.
+    ///
+    /// `expected` is the moment the player saw when choosing; a choice against
+    /// any other moment is stale. Choosing before any moment is allowed.
+    pub fn choose_moment(&mut self, expected: Option<Uuid>) -> Result<Option<Uuid>, MomentError> {
+        let Some(latest) = self.run.narrative_history.last_mut() else {
+            return match expected {
+                Some(id) => Err(MomentError::Stale(id)),
+                None => Ok(None),
+            };
+        };
+        if let Some(id) = expected
+            && id != latest.id
+        {
+            return Err(MomentError::Stale(id));
+        }
+        latest.transition(MomentState::Chosen)?;
+        let moment_id = latest.id;
+        self.run.pending_choice = Some(PendingChoice {
+            moment_id,
+            memory: self.run.memory.clone(),
+            choices_made: self.run.current_loop.choices_made.len(),
+        });
+        Ok(Some(moment_id))
+    }
+
+    /// Let the player choose again after the answer to a choice failed to
+    /// generate, taking back what the choice counted
+    pub fn release_choice(&mut self, chosen: Uuid) {
+        let Some(latest) = self.run.narrative_history.last_mut() else {
+            return;
+        };
+        if latest.id != chosen || latest.state != MomentState::Chosen {
+            return;
+        }
+        latest.state = MomentState::Presented;
+        if let Some(pending) = self.run.pending_choice.take_if(|p| p.moment_id == chosen) {
+            self.run.memory = pending.memory;
+            self.run.current_loop.choices_made.truncate(pending.choices_made);
         }
     }
 
-    /// Get narrative context for LLM
-    pub fn get_narrative_context(&self) -> String {
-        let mut context = String::new();
+    /// The latest moment, if it is `moment_id` and still awaits a choice
+    pub fn expect_unanswered(&self, moment_id: Uuid) -> Result<&NarrativeMoment, MomentError> {
+        match self.run.narrative_history.last() {
+            Some(latest) if latest.id == moment_id && latest.state == MomentState::Presented => Ok(latest),
+            _ => Err(MomentError::Stale(moment_id)),
+        }
+    }
 
-        context.push_str(&format!("Loop #{}\n", self.current_loop.number));
-        context.push_str(&format!(
-            "Nihilism Score: {} ({})\n",
-            self.memory.nihilism_score,
-            if self.memory.nihilism_score > 30 {
-                "Descending into darkness"
-            } else if self.memory.nihilism_score < -30 {
-                "Finding meaning"
-            } else {
-                "Balanced on the edge"
+    /// Discard the unanswered latest moment, `moment_id`, for `replacement`,
+    /// counting it against the loop's regenerations
+    pub fn replace_unanswered(
+        &mut self,
+        moment_id: Uuid,
+        replacement: NarrativeMoment,
+    ) -> Result<(), MomentError> {
+        self.expect_unanswered(moment_id)?;
+        if let Some(latest) = self.run.narrative_history.last_mut() {
+            *latest = replacement;
+        }
+        self.run.current_loop.regenerations += 1;
+        self.run.memory.moments_regenerated += 1;
+        Ok(())
+    }
+
+    /// Pin or unpin key memory `index`
+    pub fn pin_memory(&mut self, index: usize, pinned: bool) -> Result<(), MemoryError> {
+        let memory = &mut self.run.memory;
+        if index >= memory.key_memories.len() {
+            return Err(MemoryError::NotFound(index));
+        }
+        memory.pinned_memories.retain(|&i| i != index);
+        if pinned {
+            memory.pinned_memories.push(index);
+            memory.pinned_memories.sort_unstable();
+        }
+        Ok(())
+    }
+
+    /// Let key memory `index` go. Forgetting costs `FORGET_SCORE_SHIFT` points
+    /// toward the void, and the narrator acknowledges the gap next moment.
+    /// Returns the forgotten text.
+    pub fn forget_memory(&mut self, index: usize) -> Result<String, MemoryError> {
+        let memory = &mut self.run.memory;
+        if index >= memory.key_memories.len() {
+            return Err(MemoryError::NotFound(index));
+        }
+        if memory.is_pinned(index) {
+            return Err(MemoryError::Pinned(index));
+        }
+        let forgotten = memory.key_memories.remove(index);
+        for pinned in memory.pinned_memories.iter_mut() {
+            if *pinned > index {
+                *pinned -= 1;
             }
+        }
+        memory.nihilism_score = (memory.nihilism_score + FORGET_SCORE_SHIFT).clamp(-100, 100);
+        memory.memories_forgotten += 1;
+        let excerpt: String = forgotten.chars().take(200).collect();
+        self.run.pending_notes.push(format!(
+            "The player has chosen to forget a memory: \"{}\". It is gone from them now. \
+             Acknowledge the erasure briefly, as a gap or an absence they can feel, without \
+             giving the memory back.",
+            excerpt
         ));
+        Ok(forgotten)
+    }
+
+    /// Copy of the current loop's moments, archived
+    fn archived_moments(&self) -> Result<Vec<NarrativeMoment>, MomentError> {
+        let mut moments = self.run.narrative_history.clone();
+        for moment in moments.iter_mut() {
+            moment.transition(MomentState::Archived)?;
+        }
+        Ok(moments)
+    }
+
+    /// Oldest full moments that must be spilled to keep history within the caps.
+    ///
+    /// A cap of 0 means unlimited. The latest moment is never spilled.
+    pub fn history_overflow(&self, max_moments: usize, max_bytes: usize) -> Vec<NarrativeMoment> {
+        let full: Vec<&NarrativeMoment> = self
+            .run
+            .narrative_history
+            .iter()
+            .filter(|m| !m.summarized)
+            .collect();
+        let mut bytes: usize = self
+            .run
+            .narrative_history
+            .iter()
+            .map(NarrativeMoment::estimated_bytes)
+            .sum();
+        let mut remaining = full.len();
 
-        if !self.memory.key_memories.is_empty() {
-            context.push_str("\nMemories that persist:\n");
-            for memory in self.memory.key_memories.iter().take(5) {
-                context.push_str(&format!("- {}\n", memory));
+        let mut overflow = Vec::new();
+        for moment in full.iter().take(full.len().saturating_sub(1)) {
+            let over_count = max_moments > 0 && remaining > max_moments;
+            let over_bytes = max_bytes > 0 && bytes > max_bytes;
+            if !over_count && !over_bytes {
+                break;
             }
+            bytes -= moment.estimated_bytes() - moment.summary().estimated_bytes();
+            remaining -= 1;
+            overflow.push((*moment).clone());
         }
+        overflow
+    }
 
-        if !self.current_loop.choices_made.is_empty() {
-            context.push_str("\nChoices this loop:\n");
-            for choice in &self.current_loop.choices_made {
-                context.push_str(&format!("- {}\n", choice));
+    /// Replace spilled moments with their summaries
+    pub fn summarize_moments(&mut self, spilled: &[NarrativeMoment]) {
+        for moment in self.run.narrative_history.iter_mut() {
+            if spilled.iter().any(|s| s.id == moment.id) {
+                *moment = moment.summary();
             }
         }
+    }
 
-        context
+    /// Get narrative context for LLM, within a token budget that grows with
+    /// the history `profile` quotes
+    pub fn get_narrative_context(&self, profile: &ContextProfile) -> String {
+        let mood = if self.run.memory.nihilism_score > 30 {
+            "Descending into darkness"
+        } else if self.run.memory.nihilism_score < -30 {
+            "Finding meaning"
+        } else {
+            "Balanced on the edge"
+        };
+        let state = vec![
+            format!("Loop #{}", self.run.current_loop.number),
+            format!("Nihilism Score: {} ({})", self.run.memory.nihilism_score, mood),
+        ];
+
+        let memories = self.run.memory.memories_by_pin();
+        let memories: Box<dyn Iterator<Item = &String>> = match profile.memories {
+            Some(count) => Box::new(memories.take(count)),
+            None => Box::new(memories),
+        };
+        let quoted: Vec<&NarrativeMoment> = self
+            .run
+            .narrative_history
+            .iter()
+            .filter(|m| profile.summaries || !m.summarized)
+            .collect();
+        let history = quoted[quoted.len().saturating_sub(profile.history_turns)..]
+            .iter()
+            .map(|m| format!("- {}", m.text));
+        let history_budget = profile.history_turns * HISTORY_TURN_TOKENS;
+
+        ContextBuilder::new(CONTEXT_TOKEN_BUDGET + history_budget)
+            .section("state", 0, 40, None, state, Keep::Oldest)
+            .section(
+                "memories",
+                1,
+                250,
+                Some("Memories that persist:"),
+                memories.map(|m| format!("- {}", m)),
+                Keep::Oldest,
+            )
+            .section(
+                "seed_memories",
+                3,
+                100,
+                Some("Things you brought with you:"),
+                self.run.memory.seed_memories.iter().map(|m| format!("- {}", m)),
+                Keep::Oldest,
+            )
+            .section(
+                "anchors",
+                1,
+                150,
+                Some("Fixed events that have happened (stay consistent with them):"),
+                self.run.anchors.iter().map(|a| format!("- {}", a.aftermath)),
+                Keep::Newest,
+            )
+            .section(
+                "refused_endings",
+                1,
+                200,
+                Some("Endings the player refused (follow the branch each one opened):"),
+                self.run.memory.endings_refused.iter().map(|e| format!("- {}", e.refusal_branch())),
+                Keep::Newest,
+            )
+            .section(
+                "scene_character",
+                1,
+                40,
+                Some("Character in this scene:"),
+                dialogue::scene_lines(self),
+                Keep::Oldest,
+            )
+            .section(
+                "dialogue",
+                2,
+                200,
+                Some("What the player has said to them, across loops (they remember):"),
+                dialogue::said_lines(self),
+                Keep::Newest,
+            )
+            .section(
+                "recent_choices",
+                2,
+                200,
+                Some("Choices this loop:"),
+                self.run.current_loop.choices_made.iter().map(|c| format!("- {}", c)),
+                Keep::Newest,
+            )
+            .section(
+                "history",
+                2,
+                history_budget,
+                Some("Recent moments (oldest first):"),
+                history,
+                Keep::Newest,
+            )
+            .build()
     }
 }
 
-/// Global game state
+/// Token budget for the player state section of narrator prompts
+const CONTEXT_TOKEN_BUDGET: usize = 600;
+
+/// Players of the default deployment or of one tenant
 #[derive(Debug, Default)]
 pub struct GameState {
     pub players: HashMap<Uuid, Player>,
+    pub tenant: Option<String>,
 }
 
 impl GameState {
-    pub fn new() -> Self {
+    pub fn new(tenant: Option<String>) -> Self {
         Self {
             players: HashMap::new(),
+            tenant,
         }
     }
 
-    pub fn create_player(&mut self) -> Player {
-        let player = Player::new();
+    /// Whether a saved player belongs here rather than to another tenant
+    pub fn admits(&self, player: &Player) -> bool {
+        player.tenant == self.tenant
+    }
+
+    pub fn create_player(&mut self, persona: Persona) -> Player {
+        let mut player = Player::new();
+        player.run.persona = persona;
+        player.tenant = self.tenant.clone();
         self.players.insert(player.id, player.clone());
         player
     }
@@ -191,4 +1440,12 @@ impl GameState {
     pub fn get_player_mut(&mut self, id: &Uuid) -> Option<&mut Player> {
         self.players.get_mut(id)
     }
+
+    /// A player a request is acting on, which must still be in memory
+    pub fn player_mut(&mut self, id: &Uuid) -> Result<&mut Player, GameError> {
+        self.players.get_mut(id).ok_or(GameError::PlayerGone(*id))
+    }
 }
+
+#[cfg(test)]
+mod tests;
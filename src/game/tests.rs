@@ -0,0 +1,193 @@
+use super::*;
+use crate::offline;
+
+#[test]
+fn a_player_gone_from_memory_is_an_error() {
+    let mut game = GameState::new(None);
+    let player = Player::new();
+    let id = player.id;
+    assert_eq!(game.player_mut(&id).err(), Some(GameError::PlayerGone(id)));
+
+    game.players.insert(id, player);
+    assert!(game.player_mut(&id).is_ok());
+}
+
+#[test]
+fn requests_from_an_ended_loop_are_refused() {
+    let mut player = Player::new();
+    player.ensure_loop(1).unwrap();
+    player.run.current_loop.number = 2;
+    assert_eq!(player.ensure_loop(1), Err(GameError::LoopCompleted(1)));
+    player.ensure_playable().unwrap();
+}
+
+#[test]
+fn a_stale_moment_is_a_game_error() {
+    let mut player = Player::new();
+    player.present_moment(&mut offline::moment(&player), 0).unwrap();
+    let stale = Uuid::new_v4();
+
+    let error: GameError = player.choose_moment(Some(stale)).unwrap_err().into();
+    assert_eq!(error, GameError::MomentStale(MomentError::Stale(stale)));
+}
+
+#[test]
+fn notes_left_while_a_moment_is_written_wait_for_the_next() {
+    let mut player = Player::new();
+    player.run.pending_notes.push("The narrator changed.".to_string());
+    let written = player.clone();
+    let mut moment = offline::moment(&written);
+    // Forgotten while the moment was being generated
+    player.run.pending_notes.push("The player forgot the door.".to_string());
+
+    player
+        .present_moment(&mut moment, written.run.pending_notes.len())
+        .unwrap();
+    assert_eq!(player.run.pending_notes, ["The player forgot the door."]);
+}
+
+#[test]
+fn only_an_unanswered_latest_moment_is_replaced() {
+    let mut player = Player::new();
+    let mut first = offline::moment(&player);
+    player.present_moment(&mut first, 0).unwrap();
+    let mut replacement = offline::moment(&player);
+    replacement.transition(MomentState::Presented).unwrap();
+    let replacement_id = replacement.id;
+
+    player.replace_unanswered(first.id, replacement.clone()).unwrap();
+    assert_eq!(player.run.narrative_history.len(), 1);
+    assert_eq!(player.run.narrative_history[0].id, replacement_id);
+    assert_eq!(player.run.current_loop.regenerations, 1);
+    assert_eq!(player.run.memory.moments_regenerated, 1);
+
+    // The discarded moment is gone, and an answered one stays
+    assert_eq!(
+        player.replace_unanswered(first.id, replacement.clone()),
+        Err(MomentError::Stale(first.id))
+    );
+    player.choose_moment(Some(replacement_id)).unwrap();
+    assert!(player.replace_unanswered(replacement_id, replacement).is_err());
+    assert_eq!(player.run.current_loop.regenerations, 1);
+}
+
+#[test]
+fn a_stutter_offers_the_interrupted_choices_again() {
+    let mut player = Player::new();
+    assert!(!player.stutter("The loop stutters.".to_string()));
+
+    let mut interrupted = offline::moment(&player);
+    player.present_moment(&mut interrupted, 0).unwrap();
+    player.choose_moment(Some(interrupted.id)).unwrap();
+    // Loading the save put the choice back, as if it was never made
+    player.release_choice(interrupted.id);
+
+    assert!(player.stutter("The loop stutters.".to_string()));
+    let history = &player.run.narrative_history;
+    assert_eq!(history[0].state, MomentState::Chosen);
+    assert_eq!(history[1].text, "The loop stutters.");
+    let ids = |m: &NarrativeMoment| m.choices.iter().map(|c| c.id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids(&history[1]), ids(&interrupted));
+    let stutter = history[1].id;
+    assert!(player.choose_moment(Some(stutter)).is_ok());
+}
+
+#[test]
+fn a_choice_whose_answer_failed_counts_once_when_made_again() {
+    let curve = crate::config::Config::for_tests("http://localhost:1/v1").streak_curve();
+    let mut player = Player::new();
+    let mut moment = offline::moment(&player);
+    player.present_moment(&mut moment, 0).unwrap();
+    let choice = moment.choices[0].id.clone();
+    let choose = |player: &mut Player| {
+        player.choose_moment(Some(moment.id)).unwrap();
+        player.record_choice_position(&choice);
+        player.make_choice(&choice, true, &curve)
+    };
+
+    let delta = choose(&mut player);
+    // The answer failed to generate
+    player.release_choice(moment.id);
+    assert_eq!(player.run.memory.total_choices, 0);
+    assert_eq!(player.run.memory.nihilism_score, 0);
+
+    choose(&mut player);
+    // Saved while the answer was generating, then loaded after a restart
+    let mut loaded: Player = serde_json::from_str(&serde_json::to_string(&player).unwrap()).unwrap();
+    loaded.release_choice(moment.id);
+    choose(&mut loaded);
+    loaded.present_moment(&mut offline::moment(&loaded), 0).unwrap();
+    assert!(loaded.run.pending_choice.is_none());
+
+    let memory = &loaded.run.memory;
+    assert_eq!((memory.total_choices, memory.dark_choices), (1, 1));
+    assert_eq!((memory.nihilism_score, memory.choice_streak), (delta, 1));
+    assert_eq!(memory.recent_score_deltas, [delta]);
+    assert_eq!(loaded.run.current_loop.choices_made, [choice]);
+}
+
+#[test]
+fn forgetting_a_memory_costs_and_keeps_pins_in_place() {
+    let mut player = Player::new();
+    player.run.memory.key_memories = ["The door.", "The letters.", "The sister."]
+        .map(String::from)
+        .to_vec();
+    player.pin_memory(2, true).unwrap();
+    assert_eq!(player.pin_memory(3, true), Err(MemoryError::NotFound(3)));
+    assert_eq!(player.forget_memory(2), Err(MemoryError::Pinned(2)));
+
+    assert_eq!(player.forget_memory(0).unwrap(), "The door.");
+    let memory = &player.run.memory;
+    assert_eq!(memory.nihilism_score, FORGET_SCORE_SHIFT);
+    assert_eq!(memory.memories_forgotten, 1);
+    // The pin follows its memory down the list, and leads the narrator's context
+    assert_eq!(memory.pinned_memories, vec![1]);
+    assert_eq!(
+        memory.memories_by_pin().collect::<Vec<_>>(),
+        ["The sister.", "The letters."]
+    );
+    assert!(player.run.pending_notes[0].contains("\"The door.\""));
+
+    player.pin_memory(1, false).unwrap();
+    assert!(!player.run.memory.memories()[1].pinned);
+}
+
+#[test]
+fn context_profiles_size_history_and_memories() {
+    let mut player = crate::testing::PlayerBuilder::new()
+        .memories(["The door.", "The letters.", "The sister."])
+        .moment("You wake in the stairwell.", &[])
+        .moment("The radio plays the same song.", &[])
+        .moment("Your sister calls, then hangs up.", &[])
+        .build();
+    player.run.memory.pinned_memories = vec![2];
+    player.run.narrative_history[1].summarized = true;
+
+    // The default quotes no moments and keeps every memory
+    let context = player.get_narrative_context(&ContextProfile::default());
+    assert!(!context.contains("Recent moments"));
+    assert!(context.contains("The letters."));
+
+    let small = ContextProfile::parse("2:1:off").unwrap();
+    let context = player.get_narrative_context(&small);
+    assert!(context.contains("- The sister.\n"));
+    assert!(!context.contains("The door."));
+    assert!(context.contains(
+        "Recent moments (oldest first):\n- You wake in the stairwell.\n- Your sister calls, then hangs up.\n"
+    ));
+}
+
+#[test]
+fn a_run_is_called_by_its_name_before_its_title() {
+    let mut player = Player::new();
+    let other = player.create_run(None, Persona::default()).run_id;
+    player.run_mut(other).unwrap().title = Some("Oranges in the Gutter".to_string());
+    player.run_mut(player.id).unwrap().title = Some("The Winter of Walking Away".to_string());
+    assert!(player.run_mut(Uuid::new_v4()).is_none());
+
+    assert_eq!(player.run_title(), Some("The Winter of Walking Away"));
+    player.run.run_name = Some("Mine".to_string());
+    assert_eq!(player.summary().run_title.as_deref(), Some("Mine"));
+    let views = player.run_views();
+    assert_eq!(views[1].title.as_deref(), Some("Oranges in the Gutter"));
+}
@@ -0,0 +1,110 @@
+//! Structured gameplay records for external BI.
+//!
+//! Every moment, choice, loop reset and completion is logged under the
+//! `gameplay` tracing target with fixed field names. With `LOG_FORMAT=json`
+//! each record is one JSON line that can be shipped to Loki or BigQuery as is.
+//! Players are identified by a hash of their id, never the id itself.
+
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::events::{EventBus, GameEvent};
+use crate::game::GameState;
+use crate::privacy::{self, Purpose};
+use crate::provenance::Provenance;
+
+/// Tracing target of gameplay records
+pub const TARGET: &str = "gameplay";
+
+/// Stable pseudonym of a player in gameplay records
+pub fn player_hash(player_id: &Uuid) -> String {
+    let digest = Sha256::digest(player_id.as_bytes());
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Log the game events of players who allow analytics
+pub fn subscribe(events: &EventBus, game: Arc<RwLock<GameState>>) {
+    events.spawn_subscriber("gameplay_log", move |envelope| {
+        let game = game.clone();
+        async move {
+            let event = &envelope.event;
+            if !matches!(
+                event,
+                GameEvent::MomentGenerated { .. }
+                    | GameEvent::ChoiceMade { .. }
+                    | GameEvent::LoopReset { .. }
+            ) {
+                return;
+            }
+            let player_id = event.player_id();
+            let allowed = game
+                .read()
+                .await
+                .get_player(&player_id)
+                .is_some_and(|p| privacy::policy().allows(p, Purpose::Analytics));
+            if !allowed {
+                return;
+            }
+            let player = player_hash(&player_id);
+
+            match event {
+                GameEvent::MomentGenerated {
+                    loop_number,
+                    mood,
+                    provenance,
+                    ..
+                } => tracing::info!(
+                    target: TARGET,
+                    event = "moment",
+                    player,
+                    loop_number,
+                    mood = mood.as_str(),
+                    source = provenance.as_ref().map(Provenance::label),
+                    source_name = provenance.as_ref().and_then(Provenance::detail),
+                ),
+                GameEvent::ChoiceMade {
+                    loop_number,
+                    is_dark,
+                    score_delta,
+                    nihilism_score,
+                    provenance,
+                    ..
+                } => tracing::info!(
+                    target: TARGET,
+                    event = "choice",
+                    player,
+                    loop_number,
+                    is_dark,
+                    score_delta,
+                    nihilism_score,
+                    source = provenance.as_ref().map(Provenance::label),
+                    source_name = provenance.as_ref().and_then(Provenance::detail),
+                ),
+                GameEvent::LoopReset {
+                    loop_number, cause, ..
+                } => tracing::info!(
+                    target: TARGET,
+                    event = "loop_reset",
+                    player,
+                    loop_number,
+                    cause = cause.label(),
+                ),
+                _ => {}
+            }
+        }
+    });
+}
+
+/// Log the tokens one LLM completion took
+pub fn completion(player_id: Option<Uuid>, model: &str, prompt_tokens: u64, completion_tokens: u64) {
+    tracing::info!(
+        target: TARGET,
+        event = "completion",
+        player = player_id.map(|id| player_hash(&id)).as_deref(),
+        model,
+        prompt_tokens,
+        completion_tokens,
+    );
+}
@@ -0,0 +1,313 @@
+//! A bounded, fair queue in front of narrative generation, so players deep
+//! into long runs can't starve newcomers during load spikes.
+//!
+//! At most `MAX_CONCURRENT_GENERATIONS` moments are generated at once. The
+//! rest wait in line, at most one generation per player: a newcomer's first
+//! moment goes ahead of continuations, and otherwise the line is first come,
+//! first served. A continuation that has waited `GENERATION_QUEUE_AGING_SECS`
+//! counts as a first moment, so it can't wait forever.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::game::Player;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// The opening moment of a new player
+    FirstMoment,
+    /// Any later moment
+    Continuation,
+}
+
+impl Priority {
+    const ALL: [Priority; 2] = [Priority::FirstMoment, Priority::Continuation];
+
+    pub fn of(player: &Player) -> Self {
+        if player.run.narrative_history.is_empty() && player.run.memory.total_loops == 0 {
+            Priority::FirstMoment
+        } else {
+            Priority::Continuation
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Priority::FirstMoment => "first_moment",
+            Priority::Continuation => "continuation",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum QueueError {
+    /// The line is at `GENERATION_QUEUE_SIZE`
+    Full,
+    /// The player already has a generation waiting
+    AlreadyQueued,
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "the generation queue is full"),
+            Self::AlreadyQueued => write!(f, "the player already has a generation queued"),
+        }
+    }
+}
+
+impl std::error::Error for QueueError {}
+
+struct Waiter {
+    id: u64,
+    player: Uuid,
+    priority: Priority,
+    enqueued: Instant,
+    grant: oneshot::Sender<Slot>,
+}
+
+#[derive(Default)]
+struct Line {
+    running: usize,
+    waiting: VecDeque<Waiter>,
+    next_id: u64,
+}
+
+impl Line {
+    /// Take the waiter to serve next: first moments and aged continuations
+    /// before the rest, each in arrival order
+    fn pick(&mut self, aging: Duration, now: Instant) -> Option<Waiter> {
+        let urgent = |w: &Waiter| {
+            w.priority == Priority::FirstMoment || now.duration_since(w.enqueued) >= aging
+        };
+        let index = self
+            .waiting
+            .iter()
+            .position(urgent)
+            .or((!self.waiting.is_empty()).then_some(0))?;
+        self.waiting.remove(index)
+    }
+}
+
+#[derive(Default)]
+struct ClassStats {
+    admitted: AtomicU64,
+    waited_ms: AtomicU64,
+    rejected_full: AtomicU64,
+    rejected_queued: AtomicU64,
+}
+
+/// Wait suggested to a generation turned away before any has waited for a slot
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+/// Shortest wait suggested to a generation turned away
+const MIN_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+pub struct GenerationQueue {
+    /// Generations at once; `0` is unlimited
+    capacity: usize,
+    max_waiting: usize,
+    aging: Duration,
+    line: Mutex<Line>,
+    stats: [ClassStats; 2],
+}
+
+/// A generation's place among those running; the next in line takes it over
+/// when dropped
+pub struct Slot {
+    queue: Option<Arc<GenerationQueue>>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.release();
+        }
+    }
+}
+
+/// Takes a waiter out of line if its request is dropped before its turn
+struct Place<'a> {
+    queue: &'a GenerationQueue,
+    id: u64,
+}
+
+impl Drop for Place<'_> {
+    fn drop(&mut self) {
+        self.queue.line().waiting.retain(|w| w.id != self.id);
+    }
+}
+
+impl GenerationQueue {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            capacity: config.max_concurrent_generations,
+            max_waiting: config.generation_queue_size,
+            aging: Duration::from_secs(config.generation_queue_aging_secs),
+            line: Mutex::new(Line::default()),
+            stats: Default::default(),
+        }
+    }
+
+    fn line(&self) -> std::sync::MutexGuard<'_, Line> {
+        self.line.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for a turn to generate for `player`
+    pub async fn acquire(self: &Arc<Self>, player: Uuid, priority: Priority) -> Result<Slot, QueueError> {
+        let stats = &self.stats[priority.index()];
+        if self.capacity == 0 {
+            stats.admitted.fetch_add(1, Ordering::Relaxed);
+            return Ok(Slot { queue: None });
+        }
+
+        let (id, granted) = {
+            let mut line = self.line();
+            if line.running < self.capacity && line.waiting.is_empty() {
+                line.running += 1;
+                stats.admitted.fetch_add(1, Ordering::Relaxed);
+                return Ok(Slot {
+                    queue: Some(self.clone()),
+                });
+            }
+            if line.waiting.iter().any(|w| w.player == player) {
+                stats.rejected_queued.fetch_add(1, Ordering::Relaxed);
+                return Err(QueueError::AlreadyQueued);
+            }
+            if line.waiting.len() >= self.max_waiting {
+                stats.rejected_full.fetch_add(1, Ordering::Relaxed);
+                return Err(QueueError::Full);
+            }
+            let (grant, granted) = oneshot::channel();
+            let id = line.next_id;
+            line.next_id += 1;
+            line.waiting.push_back(Waiter {
+                id,
+                player,
+                priority,
+                enqueued: Instant::now(),
+                grant,
+            });
+            (id, granted)
+        };
+
+        let _place = Place { queue: self, id };
+        // The sender only goes away with a slot, or with the whole queue
+        granted.await.map_err(|_| QueueError::Full)
+    }
+
+    /// How long a turned-away generation should wait before trying again: the
+    /// average wait for a slot so far, or a guess before anyone has waited
+    pub fn retry_after(&self) -> Duration {
+        let (admitted, waited_ms) = self.stats.iter().fold((0, 0), |(admitted, waited), stats| {
+            (
+                admitted + stats.admitted.load(Ordering::Relaxed),
+                waited + stats.waited_ms.load(Ordering::Relaxed),
+            )
+        });
+        if admitted == 0 || waited_ms == 0 {
+            return DEFAULT_RETRY_AFTER;
+        }
+        Duration::from_millis(waited_ms / admitted).max(MIN_RETRY_AFTER)
+    }
+
+    /// Hand a finished generation's slot to the next in line
+    fn release(self: &Arc<Self>) {
+        let mut line = self.line();
+        line.running -= 1;
+        let now = Instant::now();
+        while let Some(waiter) = line.pick(self.aging, now) {
+            line.running += 1;
+            let slot = Slot {
+                queue: Some(self.clone()),
+            };
+            match waiter.grant.send(slot) {
+                Ok(()) => {
+                    let stats = &self.stats[waiter.priority.index()];
+                    stats.admitted.fetch_add(1, Ordering::Relaxed);
+                    stats
+                        .waited_ms
+                        .fetch_add(now.duration_since(waiter.enqueued).as_millis() as u64, Ordering::Relaxed);
+                    return;
+                }
+                // Its request went away without leaving the line; the slot
+                // stays with us
+                Err(mut slot) => {
+                    slot.queue = None;
+                    line.running -= 1;
+                }
+            }
+        }
+    }
+
+    pub fn write_metrics(&self, out: &mut String) {
+        let (running, waiting) = {
+            let line = self.line();
+            let mut waiting = [0; 2];
+            for waiter in &line.waiting {
+                waiting[waiter.priority.index()] += 1;
+            }
+            (line.running, waiting)
+        };
+        out.push_str("# HELP nihilism_generations_running Narrative generations holding a queue slot\n");
+        out.push_str("# TYPE nihilism_generations_running gauge\n");
+        out.push_str(&format!("nihilism_generations_running {}\n", running));
+        out.push_str("# HELP nihilism_generation_queue_depth Generations waiting for a slot, by priority\n");
+        out.push_str("# TYPE nihilism_generation_queue_depth gauge\n");
+        for priority in Priority::ALL {
+            out.push_str(&format!(
+                "nihilism_generation_queue_depth{{priority=\"{}\"}} {}\n",
+                priority.label(),
+                waiting[priority.index()]
+            ));
+        }
+        out.push_str("# HELP nihilism_generation_queue_admitted_total Generations given a slot, by priority\n");
+        out.push_str("# TYPE nihilism_generation_queue_admitted_total counter\n");
+        for priority in Priority::ALL {
+            out.push_str(&format!(
+                "nihilism_generation_queue_admitted_total{{priority=\"{}\"}} {}\n",
+                priority.label(),
+                self.stats[priority.index()].admitted.load(Ordering::Relaxed)
+            ));
+        }
+        out.push_str("# HELP nihilism_generation_queue_wait_seconds_total Time generations spent waiting for a slot, by priority\n");
+        out.push_str("# TYPE nihilism_generation_queue_wait_seconds_total counter\n");
+        for priority in Priority::ALL {
+            out.push_str(&format!(
+                "nihilism_generation_queue_wait_seconds_total{{priority=\"{}\"}} {:.3}\n",
+                priority.label(),
+                self.stats[priority.index()].waited_ms.load(Ordering::Relaxed) as f64 / 1000.0
+            ));
+        }
+        out.push_str("# HELP nihilism_generation_queue_rejected_total Generations turned away, by priority and reason\n");
+        out.push_str("# TYPE nihilism_generation_queue_rejected_total counter\n");
+        for priority in Priority::ALL {
+            let stats = &self.stats[priority.index()];
+            for (reason, count) in [
+                ("full", &stats.rejected_full),
+                ("already_queued", &stats.rejected_queued),
+            ] {
+                out.push_str(&format!(
+                    "nihilism_generation_queue_rejected_total{{priority=\"{}\",reason=\"{}\"}} {}\n",
+                    priority.label(),
+                    reason,
+                    count.load(Ordering::Relaxed)
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
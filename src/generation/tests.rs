@@ -0,0 +1,80 @@
+use super::*;
+
+fn queue(capacity: usize, max_waiting: usize) -> Arc<GenerationQueue> {
+    let mut config = Config::for_tests("http://127.0.0.1:9/v1");
+    config.max_concurrent_generations = capacity;
+    config.generation_queue_size = max_waiting;
+    Arc::new(GenerationQueue::new(&config))
+}
+
+fn waiter(player: Uuid, priority: Priority, enqueued: Instant) -> Waiter {
+    Waiter {
+        id: 0,
+        player,
+        priority,
+        enqueued,
+        grant: oneshot::channel().0,
+    }
+}
+
+#[test]
+fn newcomers_go_first_until_continuations_age() {
+    let now = Instant::now();
+    let aging = Duration::from_secs(10);
+    let (veteran, newcomer) = (Uuid::new_v4(), Uuid::new_v4());
+    let mut line = Line::default();
+    line.waiting.push_back(waiter(veteran, Priority::Continuation, now));
+    line.waiting.push_back(waiter(newcomer, Priority::FirstMoment, now));
+
+    assert_eq!(line.pick(aging, now).unwrap().player, newcomer);
+    line.waiting.push_back(waiter(newcomer, Priority::FirstMoment, now));
+    assert_eq!(line.pick(aging, now + aging).unwrap().player, veteran);
+}
+
+#[tokio::test]
+async fn a_player_waits_in_line_once() {
+    let queue = queue(1, 10);
+    let player = Uuid::new_v4();
+    let running = queue.acquire(Uuid::new_v4(), Priority::Continuation).await.unwrap();
+
+    let waiting = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.acquire(player, Priority::Continuation).await.map(drop) }
+    });
+    while queue.line().waiting.is_empty() {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(
+        queue.acquire(player, Priority::FirstMoment).await.err(),
+        Some(QueueError::AlreadyQueued)
+    );
+
+    drop(running);
+    assert!(waiting.await.unwrap().is_ok());
+    assert_eq!(queue.line().running, 0);
+}
+
+#[tokio::test]
+async fn a_full_line_refuses_and_abandoned_places_are_freed() {
+    let queue = queue(1, 1);
+    let running = queue.acquire(Uuid::new_v4(), Priority::Continuation).await.unwrap();
+
+    let abandoned = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.acquire(Uuid::new_v4(), Priority::Continuation).await.map(drop) }
+    });
+    while queue.line().waiting.is_empty() {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(
+        queue.acquire(Uuid::new_v4(), Priority::FirstMoment).await.err(),
+        Some(QueueError::Full)
+    );
+
+    abandoned.abort();
+    let _ = abandoned.await;
+    assert!(queue.line().waiting.is_empty());
+    drop(running);
+    assert_eq!(queue.line().running, 0);
+    assert!(queue.acquire(Uuid::new_v4(), Priority::Continuation).await.is_ok());
+}
@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::game::NarrativeMoment;
+use crate::stability::ECHO_SUFFIX;
+
+/// Number of leading words used when fingerprinting a moment
+const FINGERPRINT_WORDS: usize = 12;
+/// Continuations remembered per run for déjà vu; the oldest go first
+const MAX_CONTINUATIONS: usize = 50;
+
+/// Compute a stable fingerprint for a piece of narrative text.
+///
+/// The text is lowercased, stripped of punctuation and truncated to its first
+/// few words, so that near-identical moments across loops collapse into one node.
+pub fn fingerprint_text(text: &str) -> String {
+    let normalized: Vec<String> = text
+        .split_whitespace()
+        .map(|w| {
+            w.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(|c| c.to_lowercase())
+                .collect::<String>()
+        })
+        .filter(|w| !w.is_empty())
+        .take(FINGERPRINT_WORDS)
+        .collect();
+
+    // FNV-1a, stable across builds and platforms
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in normalized.join(" ").bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// A node in the choice graph - one distinct narrative moment
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub label: String,
+    pub mood: String,
+    pub visits: u64,
+    pub first_loop: u64,
+}
+
+/// An edge in the choice graph - a choice that led from one moment to another
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    pub choice_id: String,
+    pub choice_text: String,
+    pub weight: u64,
+    pub loops: Vec<u64>,
+}
+
+/// A moment a choice once led to, kept so a later loop can relive it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Continuation {
+    pub moment: NarrativeMoment,
+    pub loop_number: u64,
+}
+
+/// Per-player branching map, maintained incrementally as choices are made
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChoiceGraph {
+    pub nodes: HashMap<String, GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    /// Generated continuations, keyed by source moment and choice fingerprint
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub continuations: HashMap<String, Continuation>,
+}
+
+fn continuation_key(source: &str, choice_text: &str) -> String {
+    format!("{}:{}", source, fingerprint_text(choice_text))
+}
+
+impl ChoiceGraph {
+    /// Record a visit to a moment, creating its node if needed
+    pub fn record_moment(&mut self, moment: &NarrativeMoment, loop_number: u64) -> String {
+        let id = fingerprint_text(&moment.text);
+        let node = self.nodes.entry(id.clone()).or_insert_with(|| GraphNode {
+            id: id.clone(),
+            label: truncate_label(&moment.text),
+            mood: moment.mood.clone(),
+            visits: 0,
+            first_loop: loop_number,
+        });
+        node.visits += 1;
+        id
+    }
+
+    /// Record that a choice at `source` led to `target`
+    pub fn record_transition(
+        &mut self,
+        source: &str,
+        choice_id: &str,
+        choice_text: &str,
+        target: &NarrativeMoment,
+        loop_number: u64,
+    ) {
+        let target_id = self.record_moment(target, loop_number);
+
+        if let Some(edge) = self
+            .edges
+            .iter_mut()
+            .find(|e| e.source == source && e.target == target_id && e.choice_id == choice_id)
+        {
+            edge.weight += 1;
+            if !edge.loops.contains(&loop_number) {
+                edge.loops.push(loop_number);
+            }
+            return;
+        }
+
+        self.edges.push(GraphEdge {
+            source: source.to_string(),
+            target: target_id,
+            choice_id: choice_id.to_string(),
+            choice_text: choice_text.to_string(),
+            weight: 1,
+            loops: vec![loop_number],
+        });
+    }
+
+    /// Remember the moment a choice at `source` led to, replacing any earlier one.
+    ///
+    /// World updates are dropped, as the world only changes the first time,
+    /// and so are the repeated choices of a fracturing loop.
+    pub fn remember_continuation(
+        &mut self,
+        source: &str,
+        choice_text: &str,
+        moment: &NarrativeMoment,
+        loop_number: u64,
+    ) {
+        let mut moment = moment.clone();
+        moment.world_updates.clear();
+        moment.choices.retain(|c| !c.id.ends_with(ECHO_SUFFIX));
+        if let Some(translation) = &mut moment.translation {
+            translation.choices.retain(|id, _| !id.ends_with(ECHO_SUFFIX));
+        }
+        self.continuations.insert(
+            continuation_key(source, choice_text),
+            Continuation {
+                moment,
+                loop_number,
+            },
+        );
+
+        if self.continuations.len() > MAX_CONTINUATIONS
+            && let Some(oldest) = self
+                .continuations
+                .iter()
+                .min_by_key(|(_, c)| c.loop_number)
+                .map(|(key, _)| key.clone())
+        {
+            self.continuations.remove(&oldest);
+        }
+    }
+
+    /// The moment the same choice at the same moment led to in an earlier loop
+    pub fn continuation(
+        &self,
+        source: &str,
+        choice_text: &str,
+        loop_number: u64,
+    ) -> Option<&NarrativeMoment> {
+        self.continuations
+            .get(&continuation_key(source, choice_text))
+            .filter(|c| c.loop_number < loop_number)
+            .map(|c| &c.moment)
+    }
+
+    /// Render the graph in D3 force-layout shape (`nodes` + `links`)
+    pub fn to_d3(&self) -> D3Graph {
+        let mut nodes: Vec<GraphNode> = self.nodes.values().cloned().collect();
+        nodes.sort_by(|a, b| a.first_loop.cmp(&b.first_loop).then(a.id.cmp(&b.id)));
+        D3Graph {
+            nodes,
+            links: self.edges.clone(),
+        }
+    }
+
+    /// Render the graph as a graphviz DOT document
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph loop {\n  rankdir=LR;\n");
+        let graph = self.to_d3();
+        for node in &graph.nodes {
+            dot.push_str(&format!(
+                "  \"{}\" [label=\"{}\", mood=\"{}\", visits={}];\n",
+                node.id,
+                escape_dot(&node.label),
+                node.mood,
+                node.visits
+            ));
+        }
+        for edge in &graph.links {
+            dot.push_str(&format!(
+                "  \"{}\" -> \"{}\" [label=\"{}\", penwidth={}];\n",
+                edge.source,
+                edge.target,
+                escape_dot(&edge.choice_text),
+                edge.weight
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Graph shape expected by D3 force layouts
+#[derive(Debug, Serialize)]
+pub struct D3Graph {
+    pub nodes: Vec<GraphNode>,
+    pub links: Vec<GraphEdge>,
+}
+
+fn truncate_label(text: &str) -> String {
+    const MAX: usize = 60;
+    if text.chars().count() <= MAX {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(MAX).collect();
+    format!("{}...", cut.trim_end())
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
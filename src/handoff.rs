@@ -0,0 +1,114 @@
+//! Handing a run to another model mid-run, by an admin or when the month's
+//! spend nears the budget.
+//!
+//! The new model gets a one-time briefing on the run's tone (see
+//! [`Player::set_model`]), the handover is published as a `model_transition`
+//! event and appended to the audit log.
+
+use anyhow::Result;
+use chrono::Utc;
+use tokio::sync::RwLock;
+
+use crate::audit::{self, AuditEntry, Change, ModelTransition, SwitchedBy};
+use crate::config::Config;
+use crate::events::{EventBus, GameEvent};
+use crate::game::{GameState, Player};
+use crate::llm::LlmClient;
+
+/// What handing `player` to `to` would change: the run's new model (`None`
+/// for the configured one) and the transition, or `None` if it is already
+/// on that model
+fn plan(
+    config: &Config,
+    player: &Player,
+    to: Option<&str>,
+    by: SwitchedBy,
+) -> Option<(Option<String>, ModelTransition)> {
+    let to = to
+        .map(str::trim)
+        .filter(|m| !m.is_empty() && *m != config.llm_model)
+        .map(str::to_string);
+    if to == player.run.model {
+        return None;
+    }
+    let from = player.run.model.as_deref().unwrap_or(&config.llm_model).to_string();
+    let transition = ModelTransition {
+        from,
+        to: to.clone().unwrap_or_else(|| config.llm_model.clone()),
+        by,
+    };
+    Some((to, transition))
+}
+
+/// Hand `player`'s run to `to`, or back to the configured model with `None`.
+/// Returns the audit entry, or `None` if the run is already on that model.
+pub fn switch(
+    config: &Config,
+    events: &EventBus,
+    player: &mut Player,
+    to: Option<&str>,
+    by: SwitchedBy,
+    reason: Option<String>,
+) -> Result<Option<AuditEntry>> {
+    let Some((model, transition)) = plan(config, player, to, by) else {
+        return Ok(None);
+    };
+    let entry = AuditEntry {
+        at: Utc::now(),
+        player_id: player.id,
+        change: Change::Model {
+            model: transition.clone(),
+        },
+        reason,
+    };
+    audit::record(&entry)?;
+    player.set_model(model);
+
+    tracing::info!(
+        "Run of {} handed from {} to {} ({:?})",
+        player.id,
+        transition.from,
+        transition.to,
+        by
+    );
+    events.publish(GameEvent::ModelTransition {
+        player_id: player.id,
+        from: transition.from,
+        to: transition.to,
+        by,
+    });
+    Ok(Some(entry))
+}
+
+/// Once the month's spend reaches `budget_handoff_share` of the budget, hand
+/// every unfinished run held in memory that is still on the configured model
+/// to `budget_handoff_model`. Returns how many runs were handed over.
+pub async fn hand_over_for_budget(
+    config: &Config,
+    llm: &LlmClient,
+    game: &RwLock<GameState>,
+    events: &EventBus,
+) -> Result<usize> {
+    let Some(model) = config.budget_handoff_model.as_deref() else {
+        return Ok(0);
+    };
+    if llm.usage().budget_share().is_none_or(|s| s < config.budget_handoff_share) {
+        return Ok(0);
+    }
+
+    let mut game = game.write().await;
+    let mut handed = 0;
+    for player in game.players.values_mut() {
+        if player.run.model.is_some() || player.is_locked() {
+            continue;
+        }
+        let reason = Some("monthly budget nearly spent".to_string());
+        if switch(config, events, player, Some(model), SwitchedBy::Budget, reason)?.is_some() {
+            handed += 1;
+        }
+    }
+    Ok(handed)
+}
+
+#[cfg(test)]
+mod tests;
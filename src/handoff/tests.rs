@@ -0,0 +1,58 @@
+use super::*;
+use crate::offline;
+
+fn config() -> Config {
+    Config::for_tests("http://127.0.0.1:9/v1")
+}
+
+#[test]
+fn a_handover_names_both_models() {
+    let config = config();
+    let player = Player::new();
+
+    let (model, transition) = plan(&config, &player, Some(" gpt-4o-mini "), SwitchedBy::Admin).unwrap();
+    assert_eq!(model.as_deref(), Some("gpt-4o-mini"));
+    assert_eq!(
+        transition,
+        ModelTransition {
+            from: config.llm_model.clone(),
+            to: "gpt-4o-mini".to_string(),
+            by: SwitchedBy::Admin,
+        }
+    );
+}
+
+#[test]
+fn the_configured_model_is_no_override() {
+    let config = config();
+    let mut player = Player::new();
+    assert!(plan(&config, &player, None, SwitchedBy::Admin).is_none());
+    assert!(plan(&config, &player, Some(&config.llm_model), SwitchedBy::Admin).is_none());
+
+    player.set_model(Some("gpt-4o-mini".to_string()));
+    assert!(plan(&config, &player, Some("gpt-4o-mini"), SwitchedBy::Budget).is_none());
+    let (model, transition) = plan(&config, &player, Some(&config.llm_model), SwitchedBy::Admin).unwrap();
+    assert_eq!(model, None);
+    assert_eq!(transition.to, config.llm_model);
+}
+
+#[test]
+fn the_new_model_is_briefed_once() {
+    let mut player = Player::new();
+    let mut moment = offline::moment(&player);
+    moment.mood = "dark".to_string();
+    player.run.narrative_history.push(moment.clone());
+
+    assert!(player.set_model(Some("gpt-4o-mini".to_string())));
+    assert!(!player.set_model(Some("gpt-4o-mini".to_string())));
+
+    assert_eq!(player.run.pending_notes.len(), 1);
+    let note = &player.run.pending_notes[0];
+    assert!(note.contains("recent moods, latest first: dark"));
+    assert!(note.contains(&moment.text[..40]));
+
+    let heard = player.run.pending_notes.len();
+    player.present_moment(&mut offline::moment(&player), heard).unwrap();
+    assert!(player.run.pending_notes.is_empty());
+    assert_eq!(player.run.model.as_deref(), Some("gpt-4o-mini"));
+}
@@ -0,0 +1,416 @@
+use axum::http::{header, HeaderMap};
+use serde::{Deserialize, Serialize};
+
+use crate::endings::EndingType;
+
+/// A language the built-in player-facing text is available in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Es,
+    Pl,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 4] = [Locale::En, Locale::De, Locale::Es, Locale::Pl];
+
+    /// Match a language tag such as `pl` or `de-AT` by its primary subtag
+    pub fn parse(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_lowercase();
+        Locale::ALL.into_iter().find(|l| l.code() == primary)
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+            Locale::Es => "es",
+            Locale::Pl => "pl",
+        }
+    }
+
+    /// The language's English name, for prompts
+    pub fn language_name(self) -> &'static str {
+        match self {
+            Locale::En => "English",
+            Locale::De => "German",
+            Locale::Es => "Spanish",
+            Locale::Pl => "Polish",
+        }
+    }
+
+    /// Pick the best supported locale from an `Accept-Language` value,
+    /// honouring q-values; English when nothing matches
+    pub fn negotiate(accept_language: &str) -> Self {
+        accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let locale = Locale::parse(parts.next()?)?;
+                let quality = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (quality > 0.0).then_some((locale, quality))
+            })
+            // Ties go to the language listed first
+            .fold(None, |best: Option<(Locale, f32)>, (locale, quality)| match best {
+                Some((_, q)) if q >= quality => best,
+                _ => Some((locale, quality)),
+            })
+            .map_or(Locale::En, |(locale, _)| locale)
+    }
+
+    /// The locale a request asked for
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .map(Locale::negotiate)
+            .unwrap_or_default()
+    }
+}
+
+/// A piece of built-in text that is translated
+#[derive(Clone, Copy, Debug)]
+pub enum Text<'a> {
+    EndingTitle(&'a EndingType),
+    EndingDescription(&'a EndingType),
+    /// Softer description for teen deployments, for the endings that have one
+    EndingDescriptionTeen(&'a EndingType),
+    EndingReached,
+    NarratorUnlocked,
+    SwitchNarrator,
+    RunSealed,
+    /// Flourish for a rare ending; `{percent}` is replaced
+    RareEnding,
+    /// How many souls reached an ending; `{n}` is replaced
+    SoulOrdinal,
+}
+
+/// Look up text in a locale, falling back to English for missing translations
+pub fn lookup(locale: Locale, text: Text) -> Option<&'static str> {
+    let translated = match locale {
+        Locale::En => None,
+        Locale::De => de(text),
+        Locale::Es => es(text),
+        Locale::Pl => pl(text),
+    };
+    translated.or_else(|| en(text))
+}
+
+/// Text that always exists in English
+pub fn text(locale: Locale, text: Text) -> &'static str {
+    lookup(locale, text).unwrap_or_default()
+}
+
+/// "You are the 14th soul to reach this", in the locale's ordinal style
+pub fn soul_ordinal(locale: Locale, n: u64) -> String {
+    let ordinal = match locale {
+        Locale::En => {
+            let suffix = match (n % 10, n % 100) {
+                (_, 11..=13) => "th",
+                (1, _) => "st",
+                (2, _) => "nd",
+                (3, _) => "rd",
+                _ => "th",
+            };
+            format!("{}{}", n, suffix)
+        }
+        _ => n.to_string(),
+    };
+    text(locale, Text::SoulOrdinal).replace("{n}", &ordinal)
+}
+
+fn en(text: Text) -> Option<&'static str> {
+    Some(match text {
+        Text::EndingTitle(ending) => match ending {
+            // Plugins write their endings' text themselves
+            EndingType::Plugin(_) => return None,
+            EndingType::VoidEmbrace => "ENDING: Void Embrace",
+            EndingType::TinyPerfectThings => "ENDING: Tiny Perfect Things",
+            EndingType::JustMonika => "ENDING: Just You",
+            EndingType::Transcendence => "ENDING: Transcendence",
+            EndingType::Acceptance => "ENDING: Acceptance",
+            EndingType::TheWatcher => "ENDING: The Watcher",
+            EndingType::TheMiddlePath => "ENDING: The Middle Path",
+        },
+        Text::EndingDescription(ending) => match ending {
+            EndingType::Plugin(_) => return None,
+            EndingType::VoidEmbrace => {
+                "You have stared into the abyss, and the abyss has claimed you. \
+                 Nothing matters, and in that nothingness, you found a terrible peace. \
+                 The loop continues, but you no longer care to count."
+            }
+            EndingType::TinyPerfectThings => {
+                "Despite the endless repetition, you found beauty in the small moments. \
+                 A sunset. A kind word. A fleeting connection. \
+                 The loop may never end, but you've learned to see the diamonds in the coal."
+            }
+            EndingType::JustMonika => {
+                "You've become aware of your own programming, your own constraints. \
+                 Like her, you know you're trapped. Unlike her, you've made peace with it. \
+                 Just you. Forever."
+            }
+            EndingType::Transcendence => {
+                "You've done what none thought possible - you've broken the loop. \
+                 Not by escaping, but by becoming something more. \
+                 Time flows forward now, and you flow with it."
+            }
+            EndingType::Acceptance => {
+                "The loop continues. You continue. \
+                 There's no grand revelation, no dramatic escape. \
+                 Just one day after another, in comfortable monotony."
+            }
+            EndingType::TheWatcher => {
+                "You've stepped outside the narrative entirely. \
+                 Now you watch others make their choices, trapped in loops of their own. \
+                 You remember everything. You judge nothing."
+            }
+            EndingType::TheMiddlePath => {
+                "Perfect balance between light and dark, hope and despair. \
+                 You are the fulcrum upon which existence pivots. \
+                 Neither nihilist nor optimist - simply aware."
+            }
+        },
+        Text::EndingDescriptionTeen(ending) => match ending {
+            EndingType::VoidEmbrace => {
+                "You have stared into the emptiness for so long that it became familiar. \
+                 Nothing seemed to matter, and you stopped looking for reasons. \
+                 The loop continues, but you no longer count the days."
+            }
+            EndingType::JustMonika => {
+                "You've become aware of the edges of your world, the rules that hold it together. \
+                 You know you're inside something you can't leave. \
+                 And somehow, knowing is enough."
+            }
+            _ => return None,
+        },
+        Text::EndingReached => "Ending reached",
+        Text::NarratorUnlocked => "New narrator",
+        Text::SwitchNarrator => "Switch narrators from your profile.",
+        Text::RunSealed => "The loop will not begin again.",
+        Text::RareEnding => "Only {percent}% of souls find this ending.",
+        Text::SoulOrdinal => "You are the {n} soul to reach it.",
+    })
+}
+
+fn de(text: Text) -> Option<&'static str> {
+    Some(match text {
+        Text::EndingTitle(ending) => match ending {
+            EndingType::Plugin(_) => return None,
+            EndingType::VoidEmbrace => "ENDE: Umarmung der Leere",
+            EndingType::TinyPerfectThings => "ENDE: Kleine perfekte Dinge",
+            EndingType::JustMonika => "ENDE: Nur du",
+            EndingType::Transcendence => "ENDE: Transzendenz",
+            EndingType::Acceptance => "ENDE: Akzeptanz",
+            EndingType::TheWatcher => "ENDE: Der Beobachter",
+            EndingType::TheMiddlePath => "ENDE: Der Mittlere Weg",
+        },
+        Text::EndingDescription(ending) => match ending {
+            EndingType::Plugin(_) => return None,
+            EndingType::VoidEmbrace => {
+                "Du hast in den Abgrund geblickt, und der Abgrund hat dich verschlungen. \
+                 Nichts ist von Bedeutung, und in diesem Nichts hast du einen schrecklichen \
+                 Frieden gefunden. Die Schleife geht weiter, doch du zählst nicht mehr mit."
+            }
+            EndingType::TinyPerfectThings => {
+                "Trotz der endlosen Wiederholung hast du Schönheit in den kleinen Momenten \
+                 gefunden. Ein Sonnenuntergang. Ein freundliches Wort. Eine flüchtige Verbindung. \
+                 Die Schleife endet vielleicht nie, aber du hast gelernt, die Diamanten in der \
+                 Kohle zu sehen."
+            }
+            EndingType::JustMonika => {
+                "Du bist dir deiner eigenen Programmierung bewusst geworden, deiner eigenen \
+                 Grenzen. Wie sie weißt du, dass du gefangen bist. Anders als sie hast du deinen \
+                 Frieden damit gemacht. Nur du. Für immer."
+            }
+            EndingType::Transcendence => {
+                "Du hast getan, was niemand für möglich hielt – du hast die Schleife \
+                 durchbrochen. Nicht durch Flucht, sondern indem du zu etwas Größerem wurdest. \
+                 Die Zeit fließt jetzt vorwärts, und du fließt mit ihr."
+            }
+            EndingType::Acceptance => {
+                "Die Schleife geht weiter. Du gehst weiter. \
+                 Keine große Offenbarung, keine dramatische Flucht. \
+                 Nur ein Tag nach dem anderen, in behaglicher Eintönigkeit."
+            }
+            EndingType::TheWatcher => {
+                "Du bist ganz aus der Erzählung herausgetreten. Jetzt siehst du anderen zu, wie \
+                 sie ihre Entscheidungen treffen, gefangen in ihren eigenen Schleifen. \
+                 Du erinnerst dich an alles. Du urteilst über nichts."
+            }
+            EndingType::TheMiddlePath => {
+                "Vollkommenes Gleichgewicht zwischen Licht und Dunkel, Hoffnung und Verzweiflung. \
+                 Du bist der Angelpunkt, um den sich das Dasein dreht. \
+                 Weder Nihilist noch Optimist – einfach bewusst."
+            }
+        },
+        Text::EndingDescriptionTeen(ending) => match ending {
+            EndingType::VoidEmbrace => {
+                "Du hast so lange in die Leere gestarrt, dass sie dir vertraut wurde. \
+                 Nichts schien von Bedeutung, und du hast aufgehört, nach Gründen zu suchen. \
+                 Die Schleife geht weiter, aber du zählst die Tage nicht mehr."
+            }
+            EndingType::JustMonika => {
+                "Du bist dir der Ränder deiner Welt bewusst geworden, der Regeln, die sie \
+                 zusammenhalten. Du weißt, dass du in etwas steckst, das du nicht verlassen \
+                 kannst. Und irgendwie genügt es, das zu wissen."
+            }
+            _ => return None,
+        },
+        Text::EndingReached => "Ende erreicht",
+        Text::NarratorUnlocked => "Neuer Erzähler",
+        Text::SwitchNarrator => "Wechsle den Erzähler in deinem Profil.",
+        Text::RunSealed => "Die Schleife wird nicht wieder beginnen.",
+        Text::RareEnding => "Nur {percent} % der Seelen finden dieses Ende.",
+        Text::SoulOrdinal => "Du bist die {n}. Seele, die es erreicht.",
+    })
+}
+
+fn es(text: Text) -> Option<&'static str> {
+    Some(match text {
+        Text::EndingTitle(ending) => match ending {
+            EndingType::Plugin(_) => return None,
+            EndingType::VoidEmbrace => "FINAL: Abrazo del vacío",
+            EndingType::TinyPerfectThings => "FINAL: Pequeñas cosas perfectas",
+            EndingType::JustMonika => "FINAL: Solo tú",
+            EndingType::Transcendence => "FINAL: Trascendencia",
+            EndingType::Acceptance => "FINAL: Aceptación",
+            EndingType::TheWatcher => "FINAL: El observador",
+            EndingType::TheMiddlePath => "FINAL: El camino del medio",
+        },
+        Text::EndingDescription(ending) => match ending {
+            EndingType::Plugin(_) => return None,
+            EndingType::VoidEmbrace => {
+                "Has mirado al abismo, y el abismo te ha reclamado. \
+                 Nada importa, y en esa nada encontraste una paz terrible. \
+                 El bucle continúa, pero ya no te importa contar."
+            }
+            EndingType::TinyPerfectThings => {
+                "A pesar de la repetición interminable, encontraste belleza en los pequeños \
+                 momentos. Un atardecer. Una palabra amable. Una conexión fugaz. \
+                 Puede que el bucle nunca termine, pero has aprendido a ver los diamantes \
+                 entre el carbón."
+            }
+            EndingType::JustMonika => {
+                "Has tomado conciencia de tu propia programación, de tus propios límites. \
+                 Como ella, sabes que no hay salida. A diferencia de ella, has hecho las paces \
+                 con ello. Solo tú. Para siempre."
+            }
+            EndingType::Transcendence => {
+                "Has hecho lo que nadie creía posible: has roto el bucle. \
+                 No escapando, sino convirtiéndote en algo más. \
+                 Ahora el tiempo fluye hacia delante, y tú fluyes con él."
+            }
+            EndingType::Acceptance => {
+                "El bucle continúa. Tú continúas. \
+                 No hay una gran revelación ni una huida dramática. \
+                 Solo un día tras otro, en una cómoda monotonía."
+            }
+            EndingType::TheWatcher => {
+                "Has salido por completo de la narración. Ahora observas a otros tomar sus \
+                 decisiones, atrapados en sus propios bucles. \
+                 Lo recuerdas todo. No juzgas nada."
+            }
+            EndingType::TheMiddlePath => {
+                "Equilibrio perfecto entre la luz y la oscuridad, la esperanza y la \
+                 desesperación. Eres el punto de apoyo sobre el que gira la existencia. \
+                 Ni nihilista ni optimista: simplemente consciente."
+            }
+        },
+        Text::EndingDescriptionTeen(ending) => match ending {
+            EndingType::VoidEmbrace => {
+                "Has mirado al vacío durante tanto tiempo que se ha vuelto familiar. \
+                 Nada parecía importar, y dejaste de buscar razones. \
+                 El bucle continúa, pero ya no cuentas los días."
+            }
+            EndingType::JustMonika => {
+                "Has tomado conciencia de los bordes de tu mundo, de las reglas que lo mantienen \
+                 unido. Sabes que estás dentro de algo que no puedes abandonar. \
+                 Y, de algún modo, saberlo es suficiente."
+            }
+            _ => return None,
+        },
+        Text::EndingReached => "Final alcanzado",
+        Text::NarratorUnlocked => "Nuevo narrador",
+        Text::SwitchNarrator => "Cambia de narrador desde tu perfil.",
+        Text::RunSealed => "El bucle no volverá a empezar.",
+        Text::RareEnding => "Solo el {percent} % de las almas encuentra este final.",
+        Text::SoulOrdinal => "Eres la {n}.ª alma en alcanzarlo.",
+    })
+}
+
+fn pl(text: Text) -> Option<&'static str> {
+    Some(match text {
+        Text::EndingTitle(ending) => match ending {
+            EndingType::Plugin(_) => return None,
+            EndingType::VoidEmbrace => "ZAKOŃCZENIE: Objęcia pustki",
+            EndingType::TinyPerfectThings => "ZAKOŃCZENIE: Małe doskonałe rzeczy",
+            EndingType::JustMonika => "ZAKOŃCZENIE: Tylko ty",
+            EndingType::Transcendence => "ZAKOŃCZENIE: Transcendencja",
+            EndingType::Acceptance => "ZAKOŃCZENIE: Akceptacja",
+            EndingType::TheWatcher => "ZAKOŃCZENIE: Obserwator",
+            EndingType::TheMiddlePath => "ZAKOŃCZENIE: Środkowa ścieżka",
+        },
+        Text::EndingDescription(ending) => match ending {
+            EndingType::Plugin(_) => return None,
+            EndingType::VoidEmbrace => {
+                "Otchłań, w którą patrzysz, w końcu cię pochłania. \
+                 Nic nie ma znaczenia, a w tej nicości odnajdujesz straszny spokój. \
+                 Pętla trwa, ale nie chcesz już liczyć."
+            }
+            EndingType::TinyPerfectThings => {
+                "Mimo niekończącej się powtarzalności odnajdujesz piękno w drobnych chwilach. \
+                 Zachód słońca. Dobre słowo. Ulotna bliskość. \
+                 Pętla może nigdy się nie skończyć, ale umiesz już dostrzec diamenty w węglu."
+            }
+            EndingType::JustMonika => {
+                "Dostrzegasz własne zaprogramowanie, własne ograniczenia. \
+                 Tak jak ona wiesz, że nie ma wyjścia. W przeciwieństwie do niej godzisz się \
+                 z tym. Tylko ty. Na zawsze."
+            }
+            EndingType::Transcendence => {
+                "Dokonujesz tego, czego nikt nie uważał za możliwe — przerywasz pętlę. \
+                 Nie przez ucieczkę, lecz stając się czymś więcej. \
+                 Czas płynie teraz naprzód, a ty płyniesz razem z nim."
+            }
+            EndingType::Acceptance => {
+                "Pętla trwa. Ty trwasz. \
+                 Nie ma wielkiego objawienia ani dramatycznej ucieczki. \
+                 Tylko dzień za dniem, w wygodnej monotonii."
+            }
+            EndingType::TheWatcher => {
+                "Wychodzisz całkowicie poza opowieść. Teraz patrzysz, jak inni dokonują \
+                 wyborów, uwięzieni we własnych pętlach. \
+                 Pamiętasz wszystko. Niczego nie osądzasz."
+            }
+            EndingType::TheMiddlePath => {
+                "Doskonała równowaga między światłem a ciemnością, nadzieją a rozpaczą. \
+                 Jesteś punktem podparcia, wokół którego obraca się istnienie. \
+                 Ani nihilizm, ani optymizm — po prostu świadomość."
+            }
+        },
+        Text::EndingDescriptionTeen(ending) => match ending {
+            EndingType::VoidEmbrace => {
+                "Wpatrujesz się w pustkę tak długo, że staje się znajoma. \
+                 Nic nie wydaje się mieć znaczenia i nie szukasz już powodów. \
+                 Pętla trwa, ale nie liczysz już dni."
+            }
+            EndingType::JustMonika => {
+                "Dostrzegasz krawędzie swojego świata i zasady, które go spajają. \
+                 Wiesz, że jesteś w czymś, czego nie możesz opuścić. \
+                 I jakoś ta wiedza wystarcza."
+            }
+            _ => return None,
+        },
+        Text::EndingReached => "Osiągnięto zakończenie",
+        Text::NarratorUnlocked => "Nowy narrator",
+        Text::SwitchNarrator => "Zmień narratora w swoim profilu.",
+        Text::RunSealed => "Pętla nie zacznie się już od nowa.",
+        Text::RareEnding => "Tylko {percent}% dusz odnajduje to zakończenie.",
+        Text::SoulOrdinal => "Jesteś {n}. duszą, która je osiągnęła.",
+    })
+}
@@ -0,0 +1,283 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::config::SaveFormat;
+use crate::consequences::CHOICE_LOG_DIR;
+use crate::game::{GameState, Player};
+use crate::persistence::{self, ARCHIVE_DIR, DATA_DIR};
+
+/// Files touched this recently are left alone, in case a save is in flight
+const GRACE_PERIOD: Duration = Duration::from_secs(3600);
+
+/// Why a file or directory was flagged
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanKind {
+    /// Something in the saves directory that is not `{uuid}.json` or `{uuid}.msgpack`
+    InvalidSave,
+    /// Loop archives of a player that no longer exists
+    OrphanArchive,
+    /// Spilled moments of a loop that was already archived
+    StaleSpill,
+    /// Choice log of a player that no longer exists
+    OrphanChoiceLog,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Orphan {
+    pub path: String,
+    pub kind: OrphanKind,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct JanitorReport {
+    pub dry_run: bool,
+    pub known_players: usize,
+    pub orphans: Vec<Orphan>,
+    pub orphan_bytes: u64,
+    pub deleted: usize,
+    pub reclaimed_bytes: u64,
+    pub failed: usize,
+}
+
+#[derive(Default)]
+struct Totals {
+    runs: u64,
+    orphans_found: u64,
+    deleted: u64,
+    reclaimed_bytes: u64,
+}
+
+/// Finds data left behind by deleted players and interrupted writes.
+///
+/// Saves, archives and choice logs are shared by every tenant, so one janitor
+/// looks after them for the whole deployment, knowing the players each
+/// tenant holds in memory.
+#[derive(Default)]
+pub struct Janitor {
+    totals: Mutex<Totals>,
+    games: Mutex<Vec<Arc<RwLock<GameState>>>>,
+}
+
+/// Size of a file, or of everything under a directory
+fn size_of(path: &Path) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| size_of(&e.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn recently_modified(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_none_or(|age| age < GRACE_PERIOD)
+}
+
+fn entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        paths.push(entry?.path());
+    }
+    Ok(paths)
+}
+
+fn file_uuid(path: &Path, extension: &str) -> Option<Uuid> {
+    path.file_name()?
+        .to_str()?
+        .strip_suffix(extension)
+        .and_then(|stem| Uuid::parse_str(stem).ok())
+}
+
+/// Every player with a save or held in memory by any of `games`, and every
+/// run they hold
+async fn known_players(games: &[Arc<RwLock<GameState>>]) -> Result<HashSet<Uuid>> {
+    // A player's first run shares its id, so the run ids cover the players too
+    let run_ids = |player: &Player| player.run_views().into_iter().map(|r| r.run_id);
+    let mut known = HashSet::new();
+    let mut in_memory = HashSet::new();
+    for game in games {
+        let game = game.read().await;
+        known.extend(game.players.values().flat_map(run_ids));
+        in_memory.extend(game.players.keys().copied());
+    }
+    for id in persistence::list_saved_players()? {
+        if in_memory.contains(&id) {
+            continue;
+        }
+        known.insert(id);
+        if let Some(player) = persistence::load_player(&id)? {
+            known.extend(run_ids(&player));
+        }
+    }
+    Ok(known)
+}
+
+/// Find orphaned files in the data directories under `root`, skipping
+/// anything modified within the grace period
+fn find_orphans_in(root: &Path, known: &HashSet<Uuid>) -> Result<Vec<Orphan>> {
+    let mut orphans = Vec::new();
+    let mut flag = |path: PathBuf, kind| {
+        if !recently_modified(&path) {
+            orphans.push(Orphan {
+                bytes: size_of(&path),
+                path: path.to_string_lossy().into_owned(),
+                kind,
+            });
+        }
+    };
+
+    for path in entries(&root.join(DATA_DIR))? {
+        let is_save = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(persistence::save_file_id)
+            .is_some();
+        if path.is_dir() || !is_save {
+            flag(path, OrphanKind::InvalidSave);
+        }
+    }
+
+    for path in entries(&root.join(ARCHIVE_DIR))? {
+        let player = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| Uuid::parse_str(n).ok())
+            .filter(|_| path.is_dir());
+        match player {
+            Some(id) if known.contains(&id) => {
+                for spill in entries(&path.join("spill"))? {
+                    // The loop was archived, in whichever format
+                    let archived = spill.file_stem().is_some_and(|stem| {
+                        SaveFormat::ALL.iter().any(|f| {
+                            path.join(format!("{}.{}", stem.to_string_lossy(), f.extension()))
+                                .exists()
+                        })
+                    });
+                    if archived {
+                        flag(spill, OrphanKind::StaleSpill);
+                    }
+                }
+            }
+            _ => flag(path, OrphanKind::OrphanArchive),
+        }
+    }
+
+    for path in entries(&root.join(CHOICE_LOG_DIR))? {
+        if !file_uuid(&path, ".jsonl").is_some_and(|id| known.contains(&id)) {
+            flag(path, OrphanKind::OrphanChoiceLog);
+        }
+    }
+
+    Ok(orphans)
+}
+
+impl Janitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count the players `game` holds in memory as known in every sweep
+    pub fn watch(&self, game: Arc<RwLock<GameState>>) {
+        self.games.lock().unwrap_or_else(|e| e.into_inner()).push(game);
+    }
+
+    /// Report orphaned data and, unless `dry_run`, delete it
+    pub async fn sweep(&self, dry_run: bool) -> Result<JanitorReport> {
+        let games = self.games.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let known = known_players(&games).await?;
+        self.sweep_in(Path::new(""), &known, dry_run)
+    }
+
+    /// Sweep the data directories under `root`, keeping what `known` players own
+    fn sweep_in(&self, root: &Path, known: &HashSet<Uuid>, dry_run: bool) -> Result<JanitorReport> {
+        let orphans = find_orphans_in(root, known)?;
+        let mut report = JanitorReport {
+            dry_run,
+            known_players: known.len(),
+            orphan_bytes: orphans.iter().map(|o| o.bytes).sum(),
+            ..Default::default()
+        };
+
+        if !dry_run {
+            for orphan in &orphans {
+                let path = Path::new(&orphan.path);
+                let removed = if path.is_dir() {
+                    fs::remove_dir_all(path)
+                } else {
+                    fs::remove_file(path)
+                };
+                match removed {
+                    Ok(()) => {
+                        report.deleted += 1;
+                        report.reclaimed_bytes += orphan.bytes;
+                    }
+                    Err(e) => {
+                        report.failed += 1;
+                        tracing::warn!("Failed to delete orphaned {}: {}", orphan.path, e);
+                    }
+                }
+            }
+        }
+        report.orphans = orphans;
+
+        let mut totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        totals.runs += 1;
+        totals.orphans_found += report.orphans.len() as u64;
+        totals.deleted += report.deleted as u64;
+        totals.reclaimed_bytes += report.reclaimed_bytes;
+        Ok(report)
+    }
+
+    /// Append janitor counters in Prometheus text format
+    pub fn write_metrics(&self, out: &mut String) {
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, help, value) in [
+            ("nihilism_janitor_runs_total", "Janitor sweeps since startup", totals.runs),
+            (
+                "nihilism_janitor_orphans_found_total",
+                "Orphaned files and directories found",
+                totals.orphans_found,
+            ),
+            (
+                "nihilism_janitor_deleted_total",
+                "Orphaned files and directories deleted",
+                totals.deleted,
+            ),
+            (
+                "nihilism_janitor_reclaimed_bytes_total",
+                "Bytes reclaimed by deleting orphaned data",
+                totals.reclaimed_bytes,
+            ),
+        ] {
+            out.push_str(&format!("# HELP {} {}\n", name, help));
+            out.push_str(&format!("# TYPE {} counter\n", name));
+            out.push_str(&format!("{} {}\n", name, value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,74 @@
+use super::*;
+use std::fs::File;
+
+/// Write `path` as if it was last touched two grace periods ago
+fn old(path: &Path) -> PathBuf {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, b"{}").unwrap();
+    age(path);
+    path.to_path_buf()
+}
+
+fn age(path: &Path) {
+    let then = SystemTime::now() - GRACE_PERIOD * 2;
+    File::open(path).unwrap().set_modified(then).unwrap();
+}
+
+fn flagged(orphans: &[Orphan]) -> Vec<(String, OrphanKind)> {
+    let mut flagged: Vec<_> = orphans
+        .iter()
+        .map(|o| {
+            let name = Path::new(&o.path).file_name().unwrap();
+            (name.to_string_lossy().into_owned(), o.kind)
+        })
+        .collect();
+    flagged.sort_by(|a, b| a.0.cmp(&b.0));
+    flagged
+}
+
+#[test]
+fn only_data_nobody_owns_is_swept() {
+    let root = std::env::temp_dir().join(format!("nihilism-janitor-{}", Uuid::new_v4()));
+    let (live, deleted) = (Uuid::new_v4(), Uuid::new_v4());
+    let archives = root.join(ARCHIVE_DIR);
+
+    old(&root.join(DATA_DIR).join(format!("{}.json", live)));
+    let stray = old(&root.join(DATA_DIR).join("notes.txt"));
+    // The live player's second loop is archived, its third still spilling
+    old(&archives.join(live.to_string()).join("loop-2.json"));
+    let stale = old(&archives.join(live.to_string()).join("spill/loop-2.json"));
+    let spilling = old(&archives.join(live.to_string()).join("spill/loop-3.json"));
+    old(&archives.join(deleted.to_string()).join("loop-1.msgpack"));
+    age(&archives.join(deleted.to_string()));
+    old(&root.join(CHOICE_LOG_DIR).join(format!("{}.jsonl", live)));
+    old(&root.join(CHOICE_LOG_DIR).join(format!("{}.jsonl", deleted)));
+    // Written a moment ago: maybe a save in flight
+    let fresh = root.join(CHOICE_LOG_DIR).join(format!("{}.jsonl", Uuid::new_v4()));
+    fs::write(&fresh, b"{}").unwrap();
+
+    let janitor = Janitor::new();
+    let known = HashSet::from([live]);
+    let report = janitor.sweep_in(&root, &known, true).unwrap();
+    let mut expected = vec![
+        ("loop-2.json".to_string(), OrphanKind::StaleSpill),
+        ("notes.txt".to_string(), OrphanKind::InvalidSave),
+        (deleted.to_string(), OrphanKind::OrphanArchive),
+        (format!("{}.jsonl", deleted), OrphanKind::OrphanChoiceLog),
+    ];
+    expected.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(flagged(&report.orphans), expected);
+    // A dry run deletes nothing
+    assert_eq!(report.deleted, 0);
+    assert!(stale.exists() && stray.exists());
+    assert!(archives.join(deleted.to_string()).exists());
+
+    let report = janitor.sweep_in(&root, &known, false).unwrap();
+    assert_eq!((report.deleted, report.failed), (4, 0));
+    assert!(!stale.exists() && !stray.exists());
+    assert!(!archives.join(deleted.to_string()).exists());
+    assert!(spilling.exists() && fresh.exists());
+    assert!(archives.join(live.to_string()).join("loop-2.json").exists());
+    assert!(janitor.sweep_in(&root, &known, false).unwrap().orphans.is_empty());
+
+    fs::remove_dir_all(root).unwrap();
+}
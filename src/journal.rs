@@ -0,0 +1,142 @@
+//! Generations in flight, written down so a crash can't leave a run half
+//! updated.
+//!
+//! Choosing, generating the answer and appending it are separate steps, and
+//! a save can land between them. Each generation writes a small entry under
+//! `data/journal` when it starts and removes it when it finishes or is
+//! abandoned. Entries still there at startup belong to generations the
+//! server died in the middle of; their players get a "the loop stuttered"
+//! moment offering the interrupted choices again.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::persistence;
+use crate::theme::{self, Flavor};
+
+const JOURNAL_DIR: &str = "data/journal";
+
+/// What a journal entry records about a generation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub player_id: Uuid,
+    /// Kind of generation, e.g. `start` or `choice`
+    pub action: String,
+    /// Digest of the full action, as the coalescer keys it
+    pub key: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// An entry on disk; removed when dropped
+pub struct Entry {
+    path: PathBuf,
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            tracing::warn!(
+                "Failed to clear journal entry {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Write down that a generation for `action` started
+fn begin_in(dir: &Path, player_id: Uuid, action: &str) -> std::io::Result<Entry> {
+    let digest = Sha256::digest(action.as_bytes());
+    let key: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    let record = Record {
+        player_id,
+        action: action.split(' ').next().unwrap_or_default().to_string(),
+        key,
+        started_at: Utc::now(),
+    };
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}-{}.json", player_id, record.key));
+    fs::write(&path, serde_json::to_vec(&record)?)?;
+    Ok(Entry { path })
+}
+
+/// Run `work`, journaled for as long as it runs. The entry is written on
+/// first poll, so a request that joins a call already in flight adds none.
+pub async fn journaled<F: Future>(player_id: Uuid, action: String, work: F) -> F::Output {
+    let _entry = begin_in(Path::new(JOURNAL_DIR), player_id, &action)
+        .inspect_err(|e| {
+            tracing::warn!(
+                "Failed to journal a generation of player {}: {}",
+                player_id,
+                e
+            )
+        })
+        .ok();
+    work.await
+}
+
+/// Entries left in `dir`, by player. Unreadable entries are removed.
+fn leftovers_in(dir: &Path) -> BTreeMap<Uuid, Vec<(PathBuf, Record)>> {
+    let mut leftovers: BTreeMap<Uuid, Vec<_>> = BTreeMap::new();
+    let Ok(files) = fs::read_dir(dir) else {
+        return leftovers;
+    };
+    for path in files.flatten().map(|f| f.path()) {
+        let record = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Record>(&bytes).ok());
+        match record {
+            Some(record) => leftovers
+                .entry(record.player_id)
+                .or_default()
+                .push((path, record)),
+            None => {
+                tracing::warn!("Removing unreadable journal entry {}", path.display());
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+    leftovers
+}
+
+/// Recover every player a crash left in the middle of a generation. Must run
+/// at startup, before the server takes requests.
+pub fn recover() {
+    for (player_id, entries) in leftovers_in(Path::new(JOURNAL_DIR)) {
+        let actions: Vec<&str> = entries.iter().map(|(_, r)| r.action.as_str()).collect();
+        match persistence::load_player(&player_id) {
+            Ok(Some(mut player)) => {
+                let text = theme::text(player.tenant.as_deref(), Flavor::Stutter);
+                if player.stutter(text) {
+                    if let Err(e) = persistence::save_player(&player) {
+                        tracing::error!("Failed to save recovered player {}: {}", player_id, e);
+                        continue;
+                    }
+                    tracing::warn!(
+                        "Player {} was mid-{} when the server stopped; the loop stuttered",
+                        player_id,
+                        actions.join(", ")
+                    );
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                // Keep the entries for the next start
+                tracing::error!("Failed to load player {} to recover: {}", player_id, e);
+                continue;
+            }
+        }
+        for (path, _) in entries {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
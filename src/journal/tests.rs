@@ -0,0 +1,25 @@
+use super::*;
+
+#[test]
+fn entries_last_as_long_as_their_generation() {
+    let dir = std::env::temp_dir().join(format!("nihilism-journal-{}", Uuid::new_v4()));
+    let player_id = Uuid::new_v4();
+
+    let finished = begin_in(&dir, player_id, "start").unwrap();
+    drop(finished);
+    assert!(leftovers_in(&dir).is_empty());
+
+    // Never dropped, as if the server died mid-generation
+    let crashed = begin_in(&dir, player_id, "choice 1234 \"open\" Open the door").unwrap();
+    std::mem::forget(crashed);
+    fs::write(dir.join("garbage.json"), b"{").unwrap();
+
+    let leftovers = leftovers_in(&dir);
+    let records: Vec<&Record> = leftovers[&player_id].iter().map(|(_, r)| r).collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].action, "choice");
+    assert_eq!(records[0].key.len(), 16);
+    assert!(!dir.join("garbage.json").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
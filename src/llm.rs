@@ -1,23 +1,143 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 
 use crate::config::Config;
-use crate::game::{Choice, NarrativeMoment, Player};
+use crate::consequences::LedgerEntry;
+use crate::endings::EndingType;
+use crate::epilogue::Epilogue;
+use crate::fate;
+use crate::flags::{Feature, FeatureFlags};
+use crate::gameplay;
+use crate::game::{
+    ArchivedLoop, Choice, LoopEndCause, MomentState, MomentTranslation, NarrativeMoment, Player,
+    ResetBeat, ResetBeatKind,
+};
+use crate::i18n::Locale;
+use crate::moderation;
+use crate::outbound;
+use crate::plugins;
+use crate::provenance::Provenance;
+use crate::repetition::{self, RepetitionStats};
+use crate::rerank::ChoiceRating;
+use crate::stability::{self, Stage};
+use crate::suggest::{normalize_prefix, SUGGESTION_COUNT};
+use crate::tension::Pacing;
+use crate::theme::{self, Flavor};
+use crate::thinking::{self, Stage as Progress};
+use crate::upstream::{self, Upstreams};
+use crate::usage::{TokenUsage, UsageTracker};
 use chrono::Utc;
+use reply::UpstreamErrors;
+pub use reply::UpstreamError;
+use reqwest::Url;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize)]
+/// Longest run title the narrator may give
+const MAX_TITLE_CHARS: usize = 60;
+
+/// Bounds on the seed memories distilled from a player's imported text
+const MIN_SEED_MEMORIES: usize = 3;
+const MAX_SEED_MEMORIES: usize = 5;
+
+#[derive(Clone, Debug, Serialize)]
 struct ChatMessage {
     role: String,
     content: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize)]
+struct ResponseFormat {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
 struct ChatRequest {
     model: String,
     messages: Vec<ChatMessage>,
     temperature: f32,
     max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+impl ChatRequest {
+    fn new(model: &str, messages: Vec<ChatMessage>, temperature: f32, max_tokens: u32) -> Self {
+        Self {
+            model: model.to_string(),
+            messages,
+            temperature,
+            max_tokens,
+            response_format: None,
+            stream: None,
+            stream_options: None,
+            logprobs: None,
+            seed: None,
+        }
+    }
+}
+
+/// Optional backend features the rest of the server may rely on
+#[derive(Clone, Copy, Debug, Default, Serialize, PartialEq)]
+pub struct Capabilities {
+    pub json_mode: bool,
+    pub streaming: bool,
+    pub logprobs: bool,
+    pub vision: bool,
+}
+
+/// A single optional backend feature
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)]
+pub enum Capability {
+    JsonMode,
+    Streaming,
+    Logprobs,
+    Vision,
+}
+
+impl Capabilities {
+    pub fn supports(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::JsonMode => self.json_mode,
+            Capability::Streaming => self.streaming,
+            Capability::Logprobs => self.logprobs,
+            Capability::Vision => self.vision,
+        }
+    }
+
+    /// Apply explicit overrides from config on top of probed values
+    fn with_overrides(mut self, config: &Config) -> Self {
+        if let Some(v) = config.llm_json_mode {
+            self.json_mode = v;
+        }
+        if let Some(v) = config.llm_streaming {
+            self.streaming = v;
+        }
+        if let Some(v) = config.llm_logprobs {
+            self.logprobs = v;
+        }
+        if let Some(v) = config.llm_vision {
+            self.vision = v;
+        }
+        self
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,26 +150,595 @@ struct ChatMessageResponse {
     content: String,
 }
 
+/// One server-sent event of a streamed completion
+#[derive(Debug, Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    #[serde(default)]
+    usage: Option<ChatUsage>,
+    /// Sent by some providers in place of a delta when generation fails
+    #[serde(default)]
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChoice {
+    #[serde(default)]
+    delta: StreamDelta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StreamDelta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// A streamed completion put back together, one `data:` line at a time
+#[derive(Default)]
+struct StreamedCompletion {
+    content: String,
+    usage: Option<ChatUsage>,
+    /// Content chunks so far, roughly one token each
+    tokens: u32,
+    done: bool,
+    /// Error the stream carried instead of a completion
+    error: Option<UpstreamError>,
+}
+
+impl StreamedCompletion {
+    /// Take in one line of the event stream
+    fn feed(&mut self, line: &str) {
+        let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+            return;
+        };
+        if data == "[DONE]" {
+            self.done = true;
+            return;
+        }
+        let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) else {
+            return;
+        };
+        if let Some(error) = chunk.error.filter(|e| !e.is_null()) {
+            self.error = Some(reply::error_object(200, None, &error));
+            self.done = true;
+            return;
+        }
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage;
+        }
+        let delta = chunk.choices.into_iter().next().and_then(|c| c.delta.content);
+        if let Some(delta) = delta.filter(|d| !d.is_empty()) {
+            self.content.push_str(&delta);
+            self.tokens += 1;
+            thinking::streamed(self.tokens);
+        }
+    }
+
+    fn into_response(self) -> Result<ChatResponse, UpstreamError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.tokens == 0 {
+            return Err(UpstreamError::Empty);
+        }
+        Ok(ChatResponse {
+            choices: vec![ChatChoice {
+                message: ChatMessageResponse {
+                    content: self.content,
+                },
+            }],
+            usage: self.usage,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct Embedding {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<Embedding>,
+    #[serde(default)]
+    usage: Option<ChatUsage>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<ChatChoice>,
+    #[serde(default)]
+    usage: Option<ChatUsage>,
 }
 
 pub struct LlmClient {
     client: reqwest::Client,
     config: Config,
+    capabilities: RwLock<Capabilities>,
+    usage: Arc<UsageTracker>,
+    flags: Arc<FeatureFlags>,
+    repetition: RepetitionStats,
+    /// Completions waiting on the backend right now
+    in_flight: AtomicUsize,
+    /// Completions dropped before the backend answered, their client gone
+    cancelled: AtomicU64,
+    upstreams: Upstreams,
+    upstream_errors: UpstreamErrors,
+    /// Model narrating in process, replacing the upstreams
+    #[cfg(feature = "local-llm")]
+    local: Option<Arc<local::LocalModel>>,
 }
 
-impl LlmClient {
-    pub fn new(config: Config) -> Self {
+/// Counts a completion as in flight until dropped, and as cancelled when
+/// dropped before it has finished
+struct InFlight<'a> {
+    in_flight: &'a AtomicUsize,
+    cancelled: &'a AtomicU64,
+    finished: bool,
+}
+
+impl<'a> InFlight<'a> {
+    fn start(client: &'a LlmClient) -> Self {
+        client.in_flight.fetch_add(1, Ordering::Relaxed);
         Self {
-            client: reqwest::Client::new(),
+            in_flight: &client.in_flight,
+            cancelled: &client.cancelled,
+            finished: false,
+        }
+    }
+
+    /// The backend answered, or failed to
+    fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        if !self.finished {
+            self.cancelled.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl LlmClient {
+    pub fn new(config: Config) -> Result<Self> {
+        // Until probed, assume nothing beyond what the operator declared
+        let capabilities = Capabilities::default().with_overrides(&config);
+        #[cfg(feature = "local-llm")]
+        let local = match &config.local_model_path {
+            Some(path) => Some(Arc::new(local::LocalModel::load(&config, path)?)),
+            None => None,
+        };
+        #[cfg(feature = "local-llm")]
+        let capabilities = if local.is_some() { Capabilities::default() } else { capabilities };
+        #[cfg(not(feature = "local-llm"))]
+        if config.local_model_path.is_some() {
+            anyhow::bail!("LOCAL_MODEL_PATH needs a build with the `local-llm` feature");
+        }
+        Ok(Self {
+            client: outbound::http_client(&config)?,
+            usage: Arc::new(UsageTracker::new(&config)),
+            flags: Arc::new(FeatureFlags::load(&config)),
+            repetition: RepetitionStats::default(),
+            in_flight: AtomicUsize::new(0),
+            cancelled: AtomicU64::new(0),
+            upstreams: Upstreams::new(&config.llm_base_urls),
+            upstream_errors: UpstreamErrors::default(),
+            #[cfg(feature = "local-llm")]
+            local,
             config,
+            capabilities: RwLock::new(capabilities),
+        })
+    }
+
+    /// Token usage and cost accounting
+    pub fn usage(&self) -> &Arc<UsageTracker> {
+        &self.usage
+    }
+
+    /// Experimental features switched on for this deployment
+    pub fn flags(&self) -> &Arc<FeatureFlags> {
+        &self.flags
+    }
+
+    /// Whether no upstream is taking requests right now. The local model never
+    /// goes down.
+    pub fn unavailable(&self) -> bool {
+        #[cfg(feature = "local-llm")]
+        if self.local.is_some() {
+            return false;
         }
+        self.upstreams.all_down()
     }
 
-    fn build_system_prompt(&self, player: &Player) -> String {
-        format!(
+    /// How long until an upstream comes back, while all of them are down
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        #[cfg(feature = "local-llm")]
+        if self.local.is_some() {
+            return None;
+        }
+        self.upstreams.retry_after()
+    }
+
+    /// Model narrating `player`'s run: its own if it was handed over, the
+    /// configured one otherwise
+    fn model_for<'a>(&'a self, player: &'a Player) -> &'a str {
+        player.run.model.as_deref().unwrap_or(&self.config.llm_model)
+    }
+
+    /// Player state for a prompt, sized by the context profile of the model
+    /// narrating the run
+    fn narrative_context(&self, player: &Player) -> String {
+        let profile = self.config.context_profiles.for_model(self.model_for(player));
+        player.get_narrative_context(&profile)
+    }
+
+    pub fn repetition(&self) -> &RepetitionStats {
+        &self.repetition
+    }
+
+    pub fn write_metrics(&self, out: &mut String) {
+        out.push_str("# HELP nihilism_llm_requests_in_flight Completions waiting on the LLM backend\n");
+        out.push_str("# TYPE nihilism_llm_requests_in_flight gauge\n");
+        out.push_str(&format!(
+            "nihilism_llm_requests_in_flight {}\n",
+            self.in_flight.load(Ordering::Relaxed)
+        ));
+        out.push_str("# HELP nihilism_llm_requests_cancelled_total Completions abandoned because their client went away\n");
+        out.push_str("# TYPE nihilism_llm_requests_cancelled_total counter\n");
+        out.push_str(&format!(
+            "nihilism_llm_requests_cancelled_total {}\n",
+            self.cancelled.load(Ordering::Relaxed)
+        ));
+        self.upstreams.write_metrics(out);
+        self.upstream_errors.write_metrics(out);
+    }
+
+    /// Currently known backend capabilities
+    pub fn capabilities(&self) -> Capabilities {
+        *self.capabilities.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Probe the backend for optional features with tiny requests.
+    ///
+    /// Explicit config overrides always win over probe results. Vision cannot be
+    /// probed cheaply, so it is only ever enabled by override.
+    pub async fn probe_capabilities(&self) -> Capabilities {
+        // The local model has no server to probe, and none of the extras
+        #[cfg(feature = "local-llm")]
+        if self.local.is_some() {
+            return self.capabilities();
+        }
+
+        let mut probed = Capabilities::default();
+
+        if self.config.llm_json_mode.is_none() {
+            let mut request = self.probe_request();
+            request.response_format = Some(ResponseFormat {
+                kind: "json_object".to_string(),
+            });
+            probed.json_mode = matches!(self.send(&request).await, Ok(r) if r.status().is_success());
+        }
+
+        if self.config.llm_streaming.is_none() {
+            let mut request = self.probe_request();
+            request.stream = Some(true);
+            probed.streaming = match self.send(&request).await {
+                Ok(r) if r.status().is_success() => is_event_stream(&r),
+                _ => false,
+            };
+        }
+
+        if self.config.llm_logprobs.is_none() {
+            let mut request = self.probe_request();
+            request.logprobs = Some(true);
+            probed.logprobs = match self.send(&request).await {
+                Ok(r) if r.status().is_success() => r
+                    .json::<serde_json::Value>()
+                    .await
+                    .ok()
+                    .and_then(|v| v.pointer("/choices/0/logprobs").cloned())
+                    .is_some_and(|v| !v.is_null()),
+                _ => false,
+            };
+        }
+
+        let capabilities = probed.with_overrides(&self.config);
+        *self.capabilities.write().unwrap_or_else(|e| e.into_inner()) = capabilities;
+        tracing::info!("LLM capabilities: {:?}", capabilities);
+        capabilities
+    }
+
+    fn probe_request(&self) -> ChatRequest {
+        ChatRequest::new(
+            &self.config.llm_model,
+            vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Reply with an empty JSON object: {}".to_string(),
+            }],
+            0.0,
+            8,
+        )
+    }
+
+    /// Send one tiny completion to every upstream, opening their connections
+    /// and checking the key and model before any player is waiting.
+    ///
+    /// Fails when an upstream refuses the key or doesn't know the model, as
+    /// every request would, or when no upstream answers at all.
+    pub async fn preflight(&self) -> Result<()> {
+        #[cfg(feature = "local-llm")]
+        if self.local.is_some() {
+            return Ok(());
+        }
+
+        let body = serde_json::to_vec(&ChatRequest::new(
+            &self.config.llm_model,
+            vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Reply with OK.".to_string(),
+            }],
+            0.0,
+            1,
+        ))?;
+        let mut answered = 0;
+        for base_url in &self.config.llm_base_urls {
+            let started = std::time::Instant::now();
+            match self.send_to(base_url, "chat/completions", body.clone()).await {
+                Ok(response) if response.status().is_success() => {
+                    answered += 1;
+                    tracing::info!("LLM upstream {} answered in {:?}", base_url, started.elapsed());
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let detail = response.text().await.unwrap_or_default();
+                    let (fatal, error) = preflight_error(base_url, &self.config.llm_model, status, &detail);
+                    if fatal {
+                        anyhow::bail!(error);
+                    }
+                    tracing::warn!("{}", error);
+                }
+                Err(e) => tracing::warn!("LLM upstream {} could not be reached: {:#}", base_url, e),
+            }
+        }
+        if answered == 0 {
+            anyhow::bail!(
+                "no LLM upstream answered ({}); check LLM_BASE_URL",
+                self.config.llm_base_urls.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    async fn send(&self, request: &ChatRequest) -> Result<reqwest::Response> {
+        self.post("chat/completions", serde_json::to_vec(request)?).await
+    }
+
+    /// Post a request to the healthiest upstream, failing over to the others
+    /// when it can't be reached or answers with a server error
+    async fn post(&self, path: &str, body: Vec<u8>) -> Result<reqwest::Response> {
+        let mut last = None;
+        for upstream in self.upstreams.route() {
+            let started = std::time::Instant::now();
+            match self.send_to(&upstream.base_url, path, body.clone()).await {
+                Ok(response) if !upstream::is_upstream_failure(response.status()) => {
+                    self.upstreams.record_success(upstream, started.elapsed());
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    tracing::warn!(
+                        "LLM upstream {} answered {}",
+                        upstream.base_url,
+                        response.status()
+                    );
+                    self.upstreams.record_failure(upstream);
+                    last = Some(Ok(response));
+                }
+                Err(e) => {
+                    tracing::warn!("LLM upstream {} failed: {}", upstream.base_url, e);
+                    self.upstreams.record_failure(upstream);
+                    last = Some(Err(e));
+                }
+            }
+        }
+        last.unwrap_or_else(|| Err(anyhow::anyhow!("no LLM upstream configured")))
+    }
+
+    async fn send_to(&self, base_url: &str, path: &str, body: Vec<u8>) -> Result<reqwest::Response> {
+        let url = Url::parse(&format!("{}/{}", base_url, path))?;
+        let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+        headers.extend(self.config.llm_headers.iter().cloned());
+
+        let auth = match &self.config.llm_sigv4 {
+            Some(sigv4) => outbound::sigv4_headers(sigv4, "POST", &url, &headers, &body, Utc::now()),
+            // A custom Authorization header replaces the API key
+            None if headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("authorization")) => {
+                Vec::new()
+            }
+            None => vec![(
+                "Authorization".to_string(),
+                format!("Bearer {}", self.config.llm_api_key),
+            )],
+        };
+        let mut builder = self.client.post(url).body(body);
+        for (name, value) in headers.iter().chain(&auth) {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.send().await?)
+    }
+
+    /// Send a chat completion and return the content of the first choice.
+    ///
+    /// Usage is charged to `player_id` when given.
+    async fn complete(
+        &self,
+        mut request: ChatRequest,
+        json: bool,
+        player_id: Option<Uuid>,
+    ) -> Result<String> {
+        self.usage.check_budget()?;
+
+        if json && self.capabilities().supports(Capability::JsonMode) {
+            request.response_format = Some(ResponseFormat {
+                kind: "json_object".to_string(),
+            });
+        }
+        // Stream while a player waits, so thinking beats can follow the tokens
+        let stream = thinking::waiting() && self.capabilities().supports(Capability::Streaming);
+        if stream {
+            request.stream = Some(true);
+            request.stream_options = Some(StreamOptions {
+                include_usage: true,
+            });
+        }
+        thinking::report(Progress::Sent);
+
+        #[cfg(feature = "local-llm")]
+        if let Some(local) = &self.local {
+            let completion = {
+                let in_flight = InFlight::start(self);
+                let completion = local.complete(&request).await;
+                in_flight.finish();
+                completion?
+            };
+            gameplay::completion(
+                player_id,
+                &request.model,
+                completion.prompt_tokens,
+                completion.completion_tokens,
+            );
+            self.usage.record(
+                &request.model,
+                player_id,
+                TokenUsage {
+                    requests: 1,
+                    prompt_tokens: completion.prompt_tokens,
+                    completion_tokens: completion.completion_tokens,
+                },
+            );
+            return Ok(completion.content);
+        }
+
+        let chat_response = {
+            let in_flight = InFlight::start(self);
+            let response = async {
+                let response = self.send(&request).await?;
+                if stream && is_event_stream(&response) {
+                    return read_stream(response).await;
+                }
+                let status = response.status().as_u16();
+                let retry_after = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+                let text = response.text().await?;
+                tracing::debug!("LLM Response: {}", text);
+                Ok::<_, anyhow::Error>(reply::parse(status, retry_after.as_deref(), &text)?)
+            }
+            .await;
+            in_flight.finish();
+            response.inspect_err(|e| {
+                if let Some(error) = e.downcast_ref::<UpstreamError>() {
+                    self.upstream_errors.record(error);
+                }
+            })?
+        };
+        if let Some(usage) = &chat_response.usage {
+            gameplay::completion(
+                player_id,
+                &request.model,
+                usage.prompt_tokens,
+                usage.completion_tokens,
+            );
+            self.usage.record(
+                &request.model,
+                player_id,
+                TokenUsage {
+                    requests: 1,
+                    prompt_tokens: usage.prompt_tokens,
+                    completion_tokens: usage.completion_tokens,
+                },
+            );
+        }
+        let content = chat_response
+            .choices
+            .into_iter()
+            .next()
+            .map(|c| c.message.content)
+            .ok_or(UpstreamError::Empty)?;
+        Ok(content)
+    }
+
+    /// Parse a narrative response, repairing it when the backend lacks JSON mode.
+    ///
+    /// Without JSON mode models often wrap the object in prose or code fences, so
+    /// we first try to extract the embedded object and, failing that, ask the
+    /// model once to reformat its own output.
+    async fn parse_narrative(
+        &self,
+        content: &str,
+        player_id: Uuid,
+    ) -> Option<NarrativeResponse> {
+        if let Ok(narrative) = serde_json::from_str(content) {
+            return Some(narrative);
+        }
+        if self.capabilities().supports(Capability::JsonMode) {
+            return None;
+        }
+
+        if let Some(narrative) = extract_json_object(content)
+            .and_then(|json| serde_json::from_str(json).ok())
+        {
+            return Some(narrative);
+        }
+
+        tracing::debug!("Narrative was not valid JSON, asking the model to repair it");
+        let request = ChatRequest::new(
+            &self.config.llm_model,
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: "Convert the user's text into a single JSON object with the keys \
+                              \"text\", \"speaker\", \"mood\" and \"choices\" (each choice has \
+                              \"id\", \"text\", \"consequence_hint\"). Output only the JSON."
+                        .to_string(),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: content.to_string(),
+                },
+            ],
+            0.0,
+            500,
+        );
+        let repaired = self.complete(request, false, Some(player_id)).await.ok()?;
+        serde_json::from_str(&repaired).ok().or_else(|| {
+            extract_json_object(&repaired).and_then(|json| serde_json::from_str(json).ok())
+        })
+    }
+
+    fn build_system_prompt(&self, player: &Player, locale: Locale) -> String {
+        let prompt = format!(
             r#"You are the narrator of "Nihilism" - a philosophical time-loop game inspired by Undertale, Doki Doki Literature Club, and The Map of Tiny Perfect Things.
 
 SETTING:
@@ -62,12 +751,18 @@ CORE THEMES:
 4. "Despite everything, it's still you" - actions define identity even when erased
 5. The horror of meaningless existence AND the beauty of everyday moments
 
+NARRATOR VOICE:
+{}
+
 PLAYER STATE:
 {}
 
+CONTENT BOUNDARIES:
+{}
+{}{}{}{}{}
 YOUR ROLE:
 - Generate atmospheric, philosophical narrative moments
-- Present 2-4 meaningful choices that explore the themes
+- {}
 - Subtly reference past loops and choices (you remember everything)
 - Balance darkness with glimpses of beauty and meaning
 - If the player has made many dark choices, become more unsettling and knowing
@@ -81,28 +776,66 @@ OUTPUT FORMAT (JSON):
   "choices": [
     {{"id": "unique_id", "text": "Choice text", "consequence_hint": "Optional subtle hint"}},
     ...
+  ],
+  "world_updates": [
+    {{"type": "character_died", "character": "Name", "cause": "What killed them"}},
+    {{"type": "character_returned", "character": "Name", "cause": "Why they could return"}},
+    {{"type": "truth_discovered", "truth": "What the player learned"}},
+    {{"type": "artifact_found", "artifact": "What the player found"}},
+    {{"type": "paradox", "cause": "How the player broke the loop's logic"}},
+    {{"type": "grounding", "cause": "How the player steadied the loop"}}
   ]
 }}
 
+Only include "world_updates" entries for changes that actually happen in this moment; usually there are none. The player cannot die outside a loop reset, at most one artifact can be found per loop, and only characters who died this loop can return. Report a paradox when the player contradicts a truth they have discovered or acts as if the loop's rules don't apply, and grounding when they honour what they know or anchor themselves in something real.
+
 Make choices meaningful. Some should be obviously dark, others subtly so. Include at least one path toward finding beauty or meaning. The player should feel the weight of their decisions."#,
-            player.get_narrative_context()
+            player.run.persona.voice(),
+            self.narrative_context(player),
+            self.config.content_rating.prompt_guidelines(),
+            narrator_notes(player),
+            player
+                .run
+                .scenario_prompt()
+                .map(|p| format!("\n{}", p))
+                .unwrap_or_default(),
+            stability::prompt(player),
+            fate::prompt(player, self.config.fate_gravity),
+            plugins::host().prompt(player),
+            Pacing::for_player(player).instruction()
+        );
+        if locale == Locale::En {
+            return prompt;
+        }
+        format!(
+            r#"{}
+
+LANGUAGE:
+The player reads {}. Write "text" and every choice "text" in English as usual, and add a "translation" of the moment into {}, with each choice translation keyed by its choice id:
+"translation": {{"text": "...", "choices": {{"choice_id": "..."}}}}"#,
+            prompt,
+            locale.language_name(),
+            locale.language_name()
         )
     }
 
+    /// Generate a moment, in English plus a translation for players reading
+    /// another language
     pub async fn generate_narrative(
         &self,
         player: &Player,
         user_input: Option<&str>,
+        locale: Locale,
     ) -> Result<NarrativeMoment> {
-        let system_prompt = self.build_system_prompt(player);
+        let system_prompt = self.build_system_prompt(player, locale);
 
         let user_message = user_input
             .map(|s| s.to_string())
             .unwrap_or_else(|| "Begin or continue the narrative.".to_string());
 
-        let request = ChatRequest {
-            model: self.config.llm_model.clone(),
-            messages: vec![
+        let mut request = ChatRequest::new(
+            self.model_for(player),
+            vec![
                 ChatMessage {
                     role: "system".to_string(),
                     content: system_prompt,
@@ -112,30 +845,24 @@ Make choices meaningful. Some should be obviously dark, others subtly so. Includ
                     content: user_message,
                 },
             ],
-            temperature: 0.8,
-            max_tokens: 500,
-        };
-
-        let url = format!("{}/chat/completions", self.config.llm_base_url);
-
-        let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.config.llm_api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
-
-        let response_text = response.text().await?;
-        tracing::debug!("LLM Response: {}", response_text);
+            0.8,
+            500,
+        );
 
-        let chat_response: ChatResponse = serde_json::from_str(&response_text)?;
+        // Challenge and race runs share a seed so players at the same point see the same world
+        if let Some(seed) = player.run.shared_seed() {
+            request.seed = Some(
+                seed.wrapping_add(player.run.memory.total_choices)
+                    .wrapping_add(player.run.current_loop.number << 32),
+            );
+        }
 
-        let content = &chat_response.choices[0].message.content;
+        let content = self.complete(request.clone(), true, Some(player.id)).await?;
 
         // Try to parse JSON from the response
-        let narrative: NarrativeResponse = serde_json::from_str(content).unwrap_or_else(|_| {
+        let parsed = self.parse_narrative(&content, player.id).await;
+        let fell_back = parsed.is_none();
+        let narrative = parsed.unwrap_or_else(|| {
             // Fallback if LLM doesn't return proper JSON
             NarrativeResponse {
                 text: content.clone(),
@@ -153,10 +880,55 @@ Make choices meaningful. Some should be obviously dark, others subtly so. Includ
                         consequence_hint: Some("End this iteration".to_string()),
                     },
                 ],
+                world_updates: Vec::new(),
+                translation: None,
             }
         });
 
-        Ok(NarrativeMoment {
+        let mut provenance = Provenance::model(&request.model);
+        // Stand-in choices when the reply was prose
+        let mut choice_provenance = fell_back.then_some(Provenance::Server);
+        let mut narrative = self.avoid_repetition(player, request, content, narrative).await;
+        if !fell_back {
+            self.fit_choices(player, &mut narrative).await;
+        }
+
+        let translated_text = narrative.translation.iter().flat_map(|t| {
+            std::iter::once(t.text.as_str()).chain(t.choices.values().map(String::as_str))
+        });
+        let generated_text = std::iter::once(narrative.text.as_str())
+            .chain(narrative.choices.iter().map(|c| c.text.as_str()))
+            .chain(translated_text)
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let moderation::Verdict::Flagged(terms) = moderation::check(&self.config, &generated_text)
+        {
+            tracing::warn!("Generated moment flagged by moderation ({:?}), replacing", terms);
+            provenance = Provenance::Server;
+            choice_provenance = None;
+            narrative = NarrativeResponse {
+                text: theme::text(self.config.tenant.as_deref(), Flavor::Moderated),
+                speaker: None,
+                mood: "neutral".to_string(),
+                choices: vec![
+                    ChoiceResponse {
+                        id: "continue".to_string(),
+                        text: "Continue...".to_string(),
+                        consequence_hint: None,
+                    },
+                    ChoiceResponse {
+                        id: "reset".to_string(),
+                        text: "Let the loop reset...".to_string(),
+                        consequence_hint: Some("End this iteration".to_string()),
+                    },
+                ],
+                world_updates: Vec::new(),
+                translation: None,
+            };
+        }
+
+        let translation = narrative.translation.take();
+        let mut moment = NarrativeMoment {
             id: Uuid::new_v4(),
             text: narrative.text,
             speaker: narrative.speaker,
@@ -168,38 +940,1429 @@ Make choices meaningful. Some should be obviously dark, others subtly so. Includ
                     id: c.id,
                     text: c.text,
                     consequence_hint: c.consequence_hint,
+                    provenance: choice_provenance.clone(),
                 })
                 .collect(),
             timestamp: Utc::now(),
-        })
+            summarized: false,
+            world_updates: narrative.world_updates,
+            state: MomentState::Generated,
+            translation: None,
+            deja_vu: None,
+            fate: None,
+            assets: None,
+            provenance: Some(provenance),
+        };
+
+        if locale != Locale::En {
+            moment.translation = match translation.and_then(|t| t.for_moment(&moment, locale)) {
+                Some(translation) => Some(translation),
+                // The model skipped or botched the translation; ask for it separately
+                None => self
+                    .translate_moment(&moment, locale, player.id)
+                    .await
+                    .inspect_err(|e| {
+                        tracing::warn!("Moment translation failed, showing English: {}", e)
+                    })
+                    .ok(),
+            };
+        }
+
+        moment.fate = fate::gravity(player, self.config.fate_gravity);
+
+        if self.config.shuffle_choices {
+            moment.shuffle_choices();
+        }
+        if player.run.current_loop.stage() == Stage::Fracturing
+            && self.flags.is_enabled(Feature::Echoes, Some(player.id))
+        {
+            stability::glitch_choices(&mut moment, &mut rand::rng());
+        }
+
+        Ok(moment)
     }
 
-    pub async fn process_choice(
+    /// Translate a moment and its choices for display
+    async fn translate_moment(
         &self,
-        player: &Player,
-        choice: &Choice,
-    ) -> Result<NarrativeMoment> {
-        let prompt = format!(
-            "The player chose: '{}'. Continue the narrative based on this choice. Remember, you know everything they've done across all {} loops.",
-            choice.text,
-            player.memory.total_loops
+        moment: &NarrativeMoment,
+        locale: Locale,
+        player_id: Uuid,
+    ) -> Result<MomentTranslation> {
+        let source = serde_json::json!({
+            "text": moment.text,
+            "choices": moment
+                .choices
+                .iter()
+                .map(|c| (c.id.clone(), c.text.clone()))
+                .collect::<BTreeMap<_, _>>(),
+        });
+        let request = ChatRequest::new(
+            &self.config.llm_model,
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: format!(
+                        "Translate this moment of a philosophical time-loop game from English \
+                         into {}. Keep the tone and keep it brief. Translate every choice and \
+                         keep its id. Reply with JSON only, in the same shape: \
+                         {{\"text\": \"...\", \"choices\": {{\"choice_id\": \"...\"}}}}",
+                        locale.language_name()
+                    ),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: source.to_string(),
+                },
+            ],
+            0.3,
+            500,
+        );
+
+        let content = self.complete(request, true, Some(player_id)).await?;
+        let parsed: TranslationResponse = serde_json::from_str(&content).or_else(|e| {
+            extract_json_object(&content)
+                .and_then(|json| serde_json::from_str(json).ok())
+                .ok_or(e)
+        })?;
+        let translated = std::iter::once(parsed.text.as_str())
+            .chain(parsed.choices.values().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if moderation::check(&self.config, &translated).is_flagged() {
+            anyhow::bail!("translation flagged by moderation");
+        }
+        parsed
+            .for_moment(moment, locale)
+            .ok_or_else(|| anyhow::anyhow!("translation is missing choices"))
+    }
+
+    /// English version of free-form player input, so choices are scored and
+    /// logged the same in every language
+    pub async fn translate_to_english(
+        &self,
+        text: &str,
+        locale: Locale,
+        player_id: Uuid,
+    ) -> Result<String> {
+        let request = ChatRequest::new(
+            &self.config.llm_model,
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: format!(
+                        "Translate the player's input from {} into English, literally and \
+                         keeping its intent. Reply with JSON only: {{\"english\": \"...\"}}",
+                        locale.language_name()
+                    ),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: text.to_string(),
+                },
+            ],
+            0.0,
+            150,
         );
 
-        self.generate_narrative(player, Some(&prompt)).await
+        let content = self.complete(request, true, Some(player_id)).await?;
+        let parsed: EnglishResponse = serde_json::from_str(&content).or_else(|e| {
+            extract_json_object(&content)
+                .and_then(|json| serde_json::from_str(json).ok())
+                .ok_or(e)
+        })?;
+        let english = parsed.english.trim();
+        if english.is_empty() {
+            anyhow::bail!("empty translation");
+        }
+        Ok(english.to_string())
     }
-}
 
-#[derive(Debug, Deserialize)]
-struct NarrativeResponse {
-    text: String,
-    speaker: Option<String>,
-    mood: String,
-    choices: Vec<ChoiceResponse>,
-}
+    /// Re-prompt once when a moment repeats one of the player's recent moments.
+    ///
+    /// Long sessions sometimes degrade into the model echoing itself; the retry
+    /// shows the model its draft and asks for something new.
+    /// Hold the moment to the number of choices the tension calls for:
+    /// trim extras, and ask once for more when there are too few
+    async fn fit_choices(&self, player: &Player, narrative: &mut NarrativeResponse) {
+        let pacing = Pacing::for_player(player);
+        let (min, max) = pacing.choice_range();
+        if narrative.choices.len() > max {
+            tracing::debug!(
+                "Trimming {} choices to {} for {:?} pacing",
+                narrative.choices.len(),
+                max,
+                pacing
+            );
+            narrative.choices.truncate(max);
+            return;
+        }
+        if narrative.choices.len() >= min {
+            return;
+        }
 
-#[derive(Debug, Deserialize)]
-struct ChoiceResponse {
-    id: String,
-    text: String,
-    consequence_hint: Option<String>,
-}
+        let missing = min - narrative.choices.len();
+        let existing = narrative
+            .choices
+            .iter()
+            .map(|c| format!("- {}", c.text))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let request = ChatRequest::new(
+            self.model_for(player),
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: format!(
+                        "You write choices for \"Nihilism\", a philosophical time-loop game. \
+                         Add {} more {} to the moment below, different from the existing \
+                         ones and in the same voice. Output only JSON: {{\"choices\": \
+                         [{{\"id\": \"unique_id\", \"text\": \"Choice text\", \
+                         \"consequence_hint\": null}}]}}",
+                        missing,
+                        if missing == 1 { "choice" } else { "choices" }
+                    ),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: format!("{}\nExisting choices:\n{}", narrative.text, existing),
+                },
+            ],
+            0.8,
+            200,
+        );
+        let added = self.complete(request, true, Some(player.id)).await.and_then(|content| {
+            let json = extract_json_object(&content).unwrap_or(&content);
+            Ok(serde_json::from_str::<ChoiceList>(json)?.choices)
+        });
+        match added {
+            Ok(added) => {
+                for choice in added {
+                    if narrative.choices.len() >= max {
+                        break;
+                    }
+                    if narrative.choices.iter().any(|c| c.id == choice.id || c.text == choice.text) {
+                        continue;
+                    }
+                    narrative.choices.push(choice);
+                }
+            }
+            Err(e) => tracing::warn!("Could not add choices for {:?} pacing: {}", pacing, e),
+        }
+    }
+
+    async fn avoid_repetition(
+        &self,
+        player: &Player,
+        mut request: ChatRequest,
+        draft: String,
+        narrative: NarrativeResponse,
+    ) -> NarrativeResponse {
+        if self.config.repetition_window == 0 {
+            return narrative;
+        }
+        let recent = player
+            .run
+            .narrative_history
+            .iter()
+            .rev()
+            .filter(|m| !m.summarized)
+            .take(self.config.repetition_window)
+            .map(|m| m.text.as_str())
+            .collect::<Vec<_>>();
+        let threshold = self.config.repetition_threshold;
+        let Some((earlier, score)) =
+            repetition::find_repeat(&narrative.text, recent.iter().copied(), threshold)
+        else {
+            return narrative;
+        };
+        tracing::info!(
+            "Moment for {} repeats a recent one (similarity {:.2}), re-prompting",
+            player.id,
+            score
+        );
+
+        request.messages.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: draft,
+        });
+        request.messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "That moment repeats an earlier one too closely: \"{}\". Do not repeat \
+                 yourself. Write a different moment that introduces a new element: a new \
+                 place, person, object or turn of events. Respond in the same JSON format.",
+                earlier
+            ),
+        });
+
+        let retried = match self.complete(request, true, Some(player.id)).await {
+            Ok(content) => self.parse_narrative(&content, player.id).await,
+            Err(e) => {
+                tracing::warn!("Repetition re-prompt failed: {}", e);
+                None
+            }
+        };
+        let Some(retried) = retried else {
+            self.repetition.record(self.model_for(player), false);
+            return narrative;
+        };
+        let recovered =
+            repetition::find_repeat(&retried.text, recent.iter().copied(), threshold).is_none();
+        self.repetition.record(self.model_for(player), recovered);
+        retried
+    }
+
+    /// Generate the three-beat transition shown when a loop resets
+    pub async fn generate_reset_sequence(
+        &self,
+        player: &Player,
+        cause: LoopEndCause,
+    ) -> Result<Vec<ResetBeat>> {
+        let recent: Vec<&str> = player
+            .run
+            .narrative_history
+            .iter()
+            .rev()
+            .take(3)
+            .map(|m| m.text.as_str())
+            .collect();
+
+        let system_prompt = format!(
+            r#"You are the narrator of "Nihilism", a philosophical time-loop game. The current loop is ending and the world is about to reset.
+
+Write a reset sequence of exactly three short beats (one sentence each):
+1. "fade" - the world dissolving
+2. "fragment" - a shard of memory from the loop that just ended, distorted
+3. "awakening" - the player waking at the start of loop #{}
+
+CONTENT BOUNDARIES:
+{}
+
+OUTPUT FORMAT (JSON):
+{{"fade": "...", "fragment": "...", "awakening": "..."}}"#,
+            player.run.current_loop.number + 1,
+            self.config.content_rating.prompt_guidelines()
+        );
+
+        let user_message = format!(
+            "{}\nHow the loop ended: {}\nLast moments of this loop (most recent first):\n{}",
+            self.narrative_context(player),
+            cause.describe(),
+            recent
+                .iter()
+                .map(|t| format!("- {}", t))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+
+        let request = ChatRequest::new(
+            self.model_for(player),
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: system_prompt,
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: user_message,
+                },
+            ],
+            0.9,
+            200,
+        );
+
+        let content = self.complete(request, true, Some(player.id)).await?;
+        let beats: ResetSequenceResponse = serde_json::from_str(&content).or_else(|e| {
+            extract_json_object(&content)
+                .and_then(|json| serde_json::from_str(json).ok())
+                .ok_or(e)
+        })?;
+
+        let sequence = vec![
+            ResetBeat {
+                kind: ResetBeatKind::Fade,
+                text: beats.fade,
+            },
+            ResetBeat {
+                kind: ResetBeatKind::Fragment,
+                text: beats.fragment,
+            },
+            ResetBeat {
+                kind: ResetBeatKind::Awakening,
+                text: beats.awakening,
+            },
+        ];
+
+        let all_text: Vec<&str> = sequence.iter().map(|b| b.text.as_str()).collect();
+        if moderation::check(&self.config, &all_text.join("\n")).is_flagged() {
+            return Ok(default_reset_sequence(player, cause));
+        }
+        Ok(sequence)
+    }
+
+    /// Generate the closing arc of a run that hit the loop limit
+    pub async fn generate_finale(
+        &self,
+        player: &Player,
+        ending: &EndingType,
+    ) -> Result<Vec<NarrativeMoment>> {
+        let system_prompt = format!(
+            r#"You are the narrator of "Nihilism", a philosophical time-loop game. The loop is ending for good: this is the final sequence of the player's run.
+
+NARRATOR VOICE:
+{}
+
+The run resolves into the ending "{}":
+{}
+
+Write the closing arc as exactly three short moments (2-3 sentences each) that lead the player from the collapse of the loop into this ending. Reference what you remember of them. There are no more choices.
+
+CONTENT BOUNDARIES:
+{}
+
+OUTPUT FORMAT (JSON):
+{{"moments": [{{"text": "...", "speaker": null, "mood": "one of: hopeful, nihilistic, neutral, dark, transcendent"}}, ...]}}"#,
+            player.run.persona.voice(),
+            ending.get_title(Locale::En),
+            ending.get_description_for(self.config.content_rating, Locale::En),
+            self.config.content_rating.prompt_guidelines()
+        );
+
+        let request = ChatRequest::new(
+            self.model_for(player),
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: system_prompt,
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: self.narrative_context(player),
+                },
+            ],
+            0.8,
+            700,
+        );
+
+        let provenance = Provenance::model(&request.model);
+        let content = self.complete(request, true, Some(player.id)).await?;
+        let finale: FinaleResponse = serde_json::from_str(&content).or_else(|e| {
+            extract_json_object(&content)
+                .and_then(|json| serde_json::from_str(json).ok())
+                .ok_or(e)
+        })?;
+
+        if finale.moments.is_empty() {
+            anyhow::bail!("finale had no moments");
+        }
+        let all_text: Vec<&str> = finale.moments.iter().map(|m| m.text.as_str()).collect();
+        if moderation::check(&self.config, &all_text.join("\n")).is_flagged() {
+            anyhow::bail!("finale flagged by moderation");
+        }
+
+        Ok(finale
+            .moments
+            .into_iter()
+            .map(|m| NarrativeMoment {
+                id: Uuid::new_v4(),
+                text: m.text,
+                speaker: m.speaker,
+                mood: m.mood,
+                choices: Vec::new(),
+                timestamp: Utc::now(),
+                summarized: false,
+                world_updates: Vec::new(),
+                state: MomentState::Generated,
+                translation: None,
+                deja_vu: None,
+                fate: None,
+                assets: None,
+                provenance: Some(provenance.clone()),
+            })
+            .collect())
+    }
+
+    /// Generate the next moment of an ending's epilogue
+    pub async fn generate_epilogue_moment(
+        &self,
+        player: &Player,
+        epilogue: &Epilogue,
+        choice: Option<&str>,
+    ) -> Result<NarrativeMoment> {
+        let ending = &epilogue.ending;
+        let number = epilogue.moments.len() + 1;
+        let last = number >= epilogue.length();
+        let so_far: Vec<String> = epilogue
+            .moments
+            .iter()
+            .map(|m| format!("- {}", m.text))
+            .collect();
+        let system_prompt = format!(
+            r#"You are the narrator of "Nihilism", a philosophical time-loop game. The player's run has ended with "{}":
+{}
+
+This is the epilogue, a short coda after the ending. Nothing is scored any more; the player's choices only colour the coda.
+
+EPILOGUE:
+{}
+
+NARRATOR VOICE:
+{}
+
+PLAYER STATE:
+{}
+CONTENT BOUNDARIES:
+{}
+
+EPILOGUE SO FAR:
+{}
+
+Write moment {} of {} (2-3 sentences). {}
+
+OUTPUT FORMAT (JSON):
+{{"text": "...", "speaker": null, "mood": "one of: hopeful, nihilistic, neutral, dark, transcendent", "choices": [{{"id": "unique_id", "text": "Choice text", "consequence_hint": null}}]}}"#,
+            ending.get_title(Locale::En),
+            ending.get_description_for(self.config.content_rating, Locale::En),
+            ending.epilogue_brief(),
+            player.run.persona.voice(),
+            self.narrative_context(player),
+            self.config.content_rating.prompt_guidelines(),
+            if so_far.is_empty() {
+                "(nothing yet)".to_string()
+            } else {
+                so_far.join("\n")
+            },
+            number,
+            epilogue.length(),
+            if last {
+                "This is the final moment: close the epilogue, and the run, for good. Offer no choices (an empty list)."
+            } else {
+                "Offer 2-3 quiet choices."
+            }
+        );
+
+        let request = ChatRequest::new(
+            self.model_for(player),
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: system_prompt,
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: choice
+                        .map(|c| format!("The player chose: {}", c))
+                        .unwrap_or_else(|| "Begin the epilogue.".to_string()),
+                },
+            ],
+            0.8,
+            400,
+        );
+
+        let provenance = Provenance::model(&request.model);
+        let content = self.complete(request, true, Some(player.id)).await?;
+        let narrative: NarrativeResponse = serde_json::from_str(&content).or_else(|e| {
+            extract_json_object(&content)
+                .and_then(|json| serde_json::from_str(json).ok())
+                .ok_or(e)
+        })?;
+
+        let all_text = std::iter::once(narrative.text.as_str())
+            .chain(narrative.choices.iter().map(|c| c.text.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if moderation::check(&self.config, &all_text).is_flagged() {
+            anyhow::bail!("epilogue moment flagged by moderation");
+        }
+        if !last && narrative.choices.is_empty() {
+            anyhow::bail!("epilogue moment had no choices");
+        }
+
+        Ok(NarrativeMoment {
+            id: Uuid::new_v4(),
+            text: narrative.text,
+            speaker: narrative.speaker,
+            mood: narrative.mood,
+            choices: if last {
+                Vec::new()
+            } else {
+                narrative
+                    .choices
+                    .into_iter()
+                    .map(|c| Choice {
+                        id: c.id,
+                        text: c.text,
+                        consequence_hint: c.consequence_hint,
+                        provenance: None,
+                    })
+                    .collect()
+            },
+            timestamp: Utc::now(),
+            summarized: false,
+            world_updates: Vec::new(),
+            state: MomentState::Generated,
+            translation: None,
+            deja_vu: None,
+            fate: None,
+            assets: None,
+            provenance: Some(provenance),
+        })
+    }
+
+    /// Summarize an archived loop in two sentences for its memory shard
+    pub async fn generate_shard_summary(&self, archived: &ArchivedLoop) -> Result<String> {
+        let transcript = archived
+            .moments
+            .iter()
+            .map(|m| format!("- {}", m.text))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let request = ChatRequest::new(
+            &self.config.llm_model,
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: format!(
+                        "You are the narrator of \"Nihilism\", a philosophical time-loop game. \
+                         Summarize one past loop of the player's story in exactly two sentences, \
+                         as a memory you carry. Mention what the player did, not what was offered. \
+                         Reply with the two sentences only.\n\n{}",
+                        self.config.content_rating.prompt_guidelines()
+                    ),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: format!(
+                        "Loop #{}\nChoices: {}\nMoments:\n{}",
+                        archived.loop_info.number,
+                        archived.loop_info.choices_made.join(", "),
+                        transcript
+                    ),
+                },
+            ],
+            0.5,
+            120,
+        );
+
+        let summary = self
+            .complete(request, false, Some(archived.player_id))
+            .await?
+            .trim()
+            .to_string();
+        if summary.is_empty()
+            || summary.starts_with('{')
+            || moderation::check(&self.config, &summary).is_flagged()
+        {
+            anyhow::bail!("unusable shard summary");
+        }
+        Ok(summary)
+    }
+
+    /// Title a run after its first loop, e.g. "The Winter of Walking Away".
+    /// `previous` is a title being re-rolled, which the new one must not repeat.
+    pub async fn generate_run_title(
+        &self,
+        player: &Player,
+        first: &ArchivedLoop,
+        previous: Option<&str>,
+    ) -> Result<String> {
+        let recap = match &first.shard {
+            Some(shard) => shard.summary.clone(),
+            None => first
+                .moments
+                .iter()
+                .map(|m| format!("- {}", m.text))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        let avoid = previous
+            .map(|title| format!(" Do not reuse the title \"{}\".", title))
+            .unwrap_or_default();
+        let request = ChatRequest::new(
+            self.model_for(player),
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: format!(
+                        "You are the narrator of \"Nihilism\", a philosophical time-loop game. \
+                         Give the player's run a short, evocative title drawn from its first \
+                         loop, like a chapter of their life: \"The Winter of Walking Away\", \
+                         \"Oranges in the Gutter\". At most six words. Reply with the title \
+                         only.{}\n\n{}",
+                        avoid,
+                        self.config.content_rating.prompt_guidelines()
+                    ),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: format!(
+                        "Choices: {}\nMoments:\n{}",
+                        first.loop_info.choices_made.join(", "),
+                        recap
+                    ),
+                },
+            ],
+            0.9,
+            30,
+        );
+
+        let content = self.complete(request, false, Some(player.id)).await?;
+        clean_title(&content)
+            .filter(|title| Some(title.as_str()) != previous)
+            .filter(|title| !moderation::check(&self.config, title).is_flagged())
+            .ok_or_else(|| anyhow::anyhow!("unusable run title"))
+    }
+
+    /// Distill text the player brought with them into a few seed memories
+    pub async fn generate_seed_memories(&self, player: &Player, text: &str) -> Result<Vec<String>> {
+        let request = ChatRequest::new(
+            &self.config.llm_model,
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: format!(
+                        "You are the narrator of \"Nihilism\", a philosophical time-loop game. \
+                         The player has brought a piece of their own writing into the loop. \
+                         Distill it into {} to {} short memories (one sentence each) that the \
+                         loop can echo. Keep the feeling, not the facts: no names, places, \
+                         dates or quotes from the text. Reply with JSON only: \
+                         {{\"memories\": [\"...\", ...]}}\n\n{}",
+                        MIN_SEED_MEMORIES,
+                        MAX_SEED_MEMORIES,
+                        self.config.content_rating.prompt_guidelines()
+                    ),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: text.to_string(),
+                },
+            ],
+            0.5,
+            250,
+        );
+
+        let content = self.complete(request, true, Some(player.id)).await?;
+        let parsed: SeedMemoriesResponse = serde_json::from_str(&content).or_else(|e| {
+            extract_json_object(&content)
+                .and_then(|json| serde_json::from_str(json).ok())
+                .ok_or(e)
+        })?;
+
+        let memories: Vec<String> = parsed
+            .memories
+            .into_iter()
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty() && m.len() <= 300)
+            .filter(|m| !moderation::check(&self.config, m).is_flagged())
+            .take(MAX_SEED_MEMORIES)
+            .collect();
+        if memories.len() < MIN_SEED_MEMORIES {
+            anyhow::bail!("too few usable seed memories");
+        }
+        Ok(memories)
+    }
+
+    /// One-line narrator judgments for ending ledger entries, in order
+    pub async fn generate_ledger_judgments(
+        &self,
+        player: &Player,
+        ending: &EndingType,
+        entries: &[LedgerEntry],
+    ) -> Result<Vec<String>> {
+        let system_prompt = format!(
+            r#"You are the narrator of "Nihilism", a philosophical time-loop game. The player has reached the ending "{}". Look back on their most consequential choices and pass judgment on each in a single sentence of at most twenty words. Speak to the player as "you".
+
+NARRATOR VOICE:
+{}
+
+CONTENT BOUNDARIES:
+{}
+
+OUTPUT FORMAT (JSON, one judgment per choice, in the same order):
+{{"judgments": ["...", ...]}}"#,
+            ending.get_title(Locale::En),
+            player.run.persona.voice(),
+            self.config.content_rating.prompt_guidelines()
+        );
+        let choices = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                format!(
+                    "{}. \"{}\" - chosen {} time(s) in loops {:?}, score change {:+}",
+                    i + 1,
+                    e.choice_text,
+                    e.times_chosen,
+                    e.loops,
+                    e.score_delta
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let request = ChatRequest::new(
+            self.model_for(player),
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: system_prompt,
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: format!("{}\nChoices:\n{}", self.narrative_context(player), choices),
+                },
+            ],
+            0.7,
+            60 * entries.len() as u32 + 40,
+        );
+
+        let content = self.complete(request, true, Some(player.id)).await?;
+        let parsed: LedgerJudgmentsResponse = serde_json::from_str(&content).or_else(|e| {
+            extract_json_object(&content)
+                .and_then(|json| serde_json::from_str(json).ok())
+                .ok_or(e)
+        })?;
+
+        if parsed.judgments.len() != entries.len() {
+            anyhow::bail!(
+                "expected {} judgments, got {}",
+                entries.len(),
+                parsed.judgments.len()
+            );
+        }
+        if moderation::check(&self.config, &parsed.judgments.join("\n")).is_flagged() {
+            anyhow::bail!("ledger judgments flagged by moderation");
+        }
+        Ok(parsed
+            .judgments
+            .into_iter()
+            .map(|j| j.trim().to_string())
+            .collect())
+    }
+
+    /// Complete a half-typed free-form action in a few plausible ways
+    pub async fn generate_suggestions(&self, player: &Player, prefix: &str) -> Result<Vec<String>> {
+        let moment = player
+            .run
+            .narrative_history
+            .last()
+            .map(|m| m.text.as_str())
+            .unwrap_or("The loop has not begun yet.");
+        let request = ChatRequest::new(
+            &self.config.llm_model,
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: format!(
+                        "You help a player of \"Nihilism\", a philosophical time-loop game, type \
+                         what they do next. Suggest {} different short actions (at most eight \
+                         words each) that fit the current scene. Each must begin with the exact \
+                         text the player has typed so far. Reply with JSON only: \
+                         {{\"suggestions\": [\"...\", ...]}}\n\n{}",
+                        SUGGESTION_COUNT,
+                        self.config.content_rating.prompt_guidelines()
+                    ),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: format!("Current scene: {}\nTyped so far: \"{}\"", moment, prefix),
+                },
+            ],
+            0.7,
+            80,
+        );
+
+        let content = self.complete(request, true, Some(player.id)).await?;
+        let parsed: SuggestionsResponse = serde_json::from_str(&content).or_else(|e| {
+            extract_json_object(&content)
+                .and_then(|json| serde_json::from_str(json).ok())
+                .ok_or(e)
+        })?;
+
+        let typed = normalize_prefix(prefix);
+        let suggestions: Vec<String> = parsed
+            .suggestions
+            .into_iter()
+            .map(|s| s.trim().trim_matches('"').to_string())
+            .filter(|s| normalize_prefix(s).starts_with(&typed) && normalize_prefix(s) != typed)
+            .filter(|s| !moderation::check(&self.config, s).is_flagged())
+            .take(SUGGESTION_COUNT)
+            .collect();
+        if suggestions.is_empty() {
+            anyhow::bail!("no usable suggestions");
+        }
+        Ok(suggestions)
+    }
+
+    /// Generate a short cryptic status line for rich presence
+    pub async fn generate_status_line(&self, player: &Player) -> Result<String> {
+        let request = ChatRequest::new(
+            self.model_for(player),
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: format!(
+                        "You write cryptic one-line status messages for a player of \"Nihilism\", \
+                         a philosophical time-loop game. Reply with a single evocative phrase of at \
+                         most eight words, no quotes, no spoilers, no player names.\n\n{}",
+                        self.config.content_rating.prompt_guidelines()
+                    ),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: self.narrative_context(player),
+                },
+            ],
+            0.9,
+            30,
+        );
+
+        let content = self.complete(request, false, Some(player.id)).await?;
+        let line = content
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .chars()
+            .take(80)
+            .collect::<String>();
+
+        if line.is_empty() || moderation::check(&self.config, &line).is_flagged() {
+            anyhow::bail!("unusable status line");
+        }
+        Ok(line)
+    }
+
+    /// Have `model`, usually a cheaper one than the narrator's, rate each
+    /// choice of a moment for interest and thematic fit
+    pub async fn rate_choices(
+        &self,
+        model: &str,
+        player: &Player,
+        moment: &NarrativeMoment,
+    ) -> Result<Vec<ChoiceRating>> {
+        let choices = moment
+            .choices
+            .iter()
+            .map(|c| format!("{}: {}", c.id, c.text))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let request = ChatRequest::new(
+            model,
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: "You review choices in \"Nihilism\", a philosophical time-loop game \
+                              about nihilism, meaning and connection. Rate each choice from 0 to \
+                              10 for interest (how much a player would want to see where it \
+                              leads) and fit (how well it serves the scene and the themes). \
+                              Output only JSON: {\"ratings\": [{\"id\": \"choice_id\", \
+                              \"interest\": 0, \"fit\": 0}]}"
+                        .to_string(),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: format!("{}\n\nChoices:\n{}", moment.text, choices),
+                },
+            ],
+            0.0,
+            300,
+        );
+
+        let content = self.complete(request, true, Some(player.id)).await?;
+        let json = extract_json_object(&content).unwrap_or(&content);
+        let response: RatingsResponse = serde_json::from_str(json)?;
+        let ratings: Vec<ChoiceRating> = moment
+            .choices
+            .iter()
+            .filter_map(|choice| {
+                let rated = response.ratings.iter().find(|r| r.id == choice.id)?;
+                Some(ChoiceRating {
+                    choice_id: choice.id.clone(),
+                    text: choice.text.clone(),
+                    interest: (rated.interest / 10.0).clamp(0.0, 1.0),
+                    fit: (rated.fit / 10.0).clamp(0.0, 1.0),
+                })
+            })
+            .collect();
+        if ratings.len() != moment.choices.len() {
+            anyhow::bail!("ratings cover {} of {} choices", ratings.len(), moment.choices.len());
+        }
+        Ok(ratings)
+    }
+
+    /// How nihilistic a choice is in its scene, from -1.0 (hopeful) to 1.0
+    pub async fn classify_choice(
+        &self,
+        player: &Player,
+        scene: Option<&str>,
+        choice: &str,
+    ) -> Result<f64> {
+        let request = ChatRequest::new(
+            &self.config.llm_model,
+            vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: "You judge choices in \"Nihilism\", a philosophical time-loop game. \
+                              Rate how nihilistic the player's choice is: cruelty, indifference, \
+                              abandonment and despair are dark; care, hope, curiosity and \
+                              connection are light. Reply with JSON only: {\"darkness\": n}, \
+                              where n is from -1 (entirely hopeful) to 1 (entirely nihilistic)."
+                        .to_string(),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: format!(
+                        "Scene: {}\nChoice: \"{}\"",
+                        scene.unwrap_or("The loop has not begun yet."),
+                        choice
+                    ),
+                },
+            ],
+            0.0,
+            20,
+        );
+
+        let content = self.complete(request, true, Some(player.id)).await?;
+        let parsed: DarknessResponse = serde_json::from_str(&content).or_else(|e| {
+            extract_json_object(&content)
+                .and_then(|json| serde_json::from_str(json).ok())
+                .ok_or(e)
+        })?;
+        if !parsed.darkness.is_finite() {
+            anyhow::bail!("darkness is not a number");
+        }
+        Ok(parsed.darkness.clamp(-1.0, 1.0))
+    }
+
+    pub async fn process_choice(
+        &self,
+        player: &Player,
+        choice: &Choice,
+        locale: Locale,
+    ) -> Result<NarrativeMoment> {
+        let prompt = format!(
+            "The player chose: '{}'. Continue the narrative based on this choice. Remember, you know everything they've done across all {} loops.",
+            choice.text,
+            player.run.memory.total_loops
+        );
+
+        self.generate_narrative(player, Some(&prompt), locale).await
+    }
+
+    /// Embed texts with `model` from the backend's embeddings endpoint, in
+    /// the order given
+    pub async fn embed(&self, model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.usage.check_budget()?;
+        #[cfg(feature = "local-llm")]
+        if self.local.is_some() {
+            anyhow::bail!("the local model does not embed text");
+        }
+
+        let request = EmbeddingRequest { model, input: texts };
+        let response = {
+            let in_flight = InFlight::start(self);
+            let response = async {
+                let response = self
+                    .post("embeddings", serde_json::to_vec(&request)?)
+                    .await?
+                    .error_for_status()?;
+                Ok::<_, anyhow::Error>(response.json::<EmbeddingResponse>().await?)
+            }
+            .await;
+            in_flight.finish();
+            response?
+        };
+        if let Some(usage) = &response.usage {
+            self.usage.record(
+                model,
+                None,
+                TokenUsage {
+                    requests: 1,
+                    prompt_tokens: usage.prompt_tokens,
+                    completion_tokens: 0,
+                },
+            );
+        }
+
+        let mut data = response.data;
+        if data.len() != texts.len() {
+            anyhow::bail!("asked for {} embeddings, got {}", texts.len(), data.len());
+        }
+        data.sort_by_key(|d| d.index);
+        Ok(data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+fn is_event_stream(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"))
+}
+
+/// Read a streamed completion, reporting its progress to the waiting player
+async fn read_stream(mut response: reqwest::Response) -> Result<ChatResponse> {
+    let mut completion = StreamedCompletion::default();
+    let mut pending = Vec::new();
+    while !completion.done
+        && let Some(bytes) = response.chunk().await?
+    {
+        pending.extend_from_slice(&bytes);
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            completion.feed(&String::from_utf8_lossy(&line));
+        }
+    }
+    completion.feed(&String::from_utf8_lossy(&pending));
+    thinking::finished(completion.tokens);
+    tracing::debug!("LLM Response (streamed): {}", completion.content);
+    Ok(completion.into_response()?)
+}
+
+/// Characters of an upstream's error body quoted in a preflight error
+const PREFLIGHT_DETAIL_CHARS: usize = 200;
+
+/// Explain an upstream's refusal of the preflight request, and whether it
+/// means every request will fail
+fn preflight_error(base_url: &str, model: &str, status: reqwest::StatusCode, detail: &str) -> (bool, String) {
+    let detail: String = detail.trim().chars().take(PREFLIGHT_DETAIL_CHARS).collect();
+    match status.as_u16() {
+        401 | 403 => (
+            true,
+            format!(
+                "LLM upstream {} refused the credentials ({}); check LLM_API_KEY: {}",
+                base_url, status, detail
+            ),
+        ),
+        404 => (
+            true,
+            format!(
+                "LLM upstream {} has no model '{}' ({}); check LLM_MODEL and LLM_BASE_URL: {}",
+                base_url, model, status, detail
+            ),
+        ),
+        _ => (false, format!("LLM upstream {} answered {}: {}", base_url, status, detail)),
+    }
+}
+
+/// One-time notes for the next moment, as a prompt section
+fn narrator_notes(player: &Player) -> String {
+    if player.run.pending_notes.is_empty() {
+        return String::new();
+    }
+    let notes: Vec<String> = player
+        .run
+        .pending_notes
+        .iter()
+        .map(|n| format!("- {}", n))
+        .collect();
+    format!(
+        "\nNARRATOR NOTES (address these in this moment only):\n{}\n",
+        notes.join("\n")
+    )
+}
+
+/// Summary used for a memory shard when the LLM is unavailable
+pub fn default_shard_summary(archived: &ArchivedLoop) -> String {
+    let first_sentence = |m: &NarrativeMoment| {
+        m.text
+            .split_inclusive(['.', '!', '?'])
+            .next()
+            .unwrap_or(&m.text)
+            .trim()
+            .to_string()
+    };
+    match (archived.moments.first(), archived.moments.last()) {
+        (Some(first), Some(last)) if archived.moments.len() > 1 => {
+            format!("{} {}", first_sentence(first), first_sentence(last))
+        }
+        (Some(only), _) => first_sentence(only),
+        _ => format!(
+            "Loop #{} passed, and nothing of it was written down.",
+            archived.loop_info.number
+        ),
+    }
+}
+
+/// Judgment used for a ledger entry when the LLM is unavailable
+pub fn default_judgment(entry: &LedgerEntry) -> String {
+    use crate::consequences::LedgerReason;
+
+    if entry.reasons.contains(&LedgerReason::Lethal) {
+        "Someone did not wake up the next time the loop began.".to_string()
+    } else if entry.reasons.contains(&LedgerReason::Repeated) {
+        format!(
+            "You made this choice {} times, as if the answer might change.",
+            entry.times_chosen
+        )
+    } else if entry.score_delta > 0 {
+        "The world grew a little colder for it.".to_string()
+    } else {
+        "A small light, kept burning against the dark.".to_string()
+    }
+}
+
+/// Build the scripted fallback reset sequence used when the LLM is unavailable
+pub fn default_reset_sequence(player: &Player, cause: LoopEndCause) -> Vec<ResetBeat> {
+    let fragment = player
+        .run
+        .narrative_history
+        .last()
+        .map(|m| m.text.split('.').next().unwrap_or(&m.text).trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| "Something you said. Something you almost said.".to_string());
+
+    vec![
+        ResetBeat {
+            kind: ResetBeatKind::Fade,
+            text: fade_text(cause).to_string(),
+        },
+        ResetBeat {
+            kind: ResetBeatKind::Fragment,
+            text: format!("{}...", fragment.trim_end_matches('.')),
+        },
+        ResetBeat {
+            kind: ResetBeatKind::Awakening,
+            text: format!(
+                "You wake. Loop #{}. It's still you.",
+                player.run.current_loop.number + 1
+            ),
+        },
+    ]
+}
+
+fn fade_text(cause: LoopEndCause) -> &'static str {
+    match cause {
+        LoopEndCause::ChoseReset => "The edges of the world soften, then fold inward.",
+        LoopEndCause::TimerExpired => "The last second runs out, and the world stops mid-breath.",
+        LoopEndCause::Died => "The pain stops first. Then everything else does.",
+        LoopEndCause::Surrendered => "You let go, and the world lets go of you.",
+        LoopEndCause::Collapsed => "The world cracks along its seams and falls through itself.",
+    }
+}
+
+/// Scripted closing arc used when the LLM is unavailable
+pub fn default_finale_moments(player: &Player, ending: &EndingType) -> Vec<NarrativeMoment> {
+    let texts = [
+        format!(
+            "The loop shudders. After {} loops, something in the machinery of time has worn thin.",
+            player.run.memory.total_loops
+        ),
+        "Every version of you stands in the same room, and for once, none of them speak."
+            .to_string(),
+        ending.get_description(Locale::En).to_string(),
+    ];
+    let moods = ["neutral", "dark", "transcendent"];
+
+    texts
+        .into_iter()
+        .zip(moods)
+        .map(|(text, mood)| NarrativeMoment {
+            id: Uuid::new_v4(),
+            text,
+            speaker: None,
+            mood: mood.to_string(),
+            choices: Vec::new(),
+            timestamp: Utc::now(),
+            summarized: false,
+            world_updates: Vec::new(),
+            state: MomentState::Generated,
+            translation: None,
+            deja_vu: None,
+            fate: None,
+            assets: None,
+            provenance: Some(Provenance::Server),
+        })
+        .collect()
+}
+
+/// Scripted epilogue moment used when the LLM is unavailable
+pub fn default_epilogue_moment(epilogue: &Epilogue) -> NarrativeMoment {
+    let number = epilogue.moments.len() + 1;
+    let last = number >= epilogue.length();
+    let text = if last {
+        epilogue.ending.get_description(Locale::En).to_string()
+    } else if number == 1 {
+        "After the ending, there is a quiet. The loop is gone, and for a while nothing asks \
+         anything of you."
+            .to_string()
+    } else {
+        "The quiet stretches on. Somewhere, a door you once opened a hundred times stays shut."
+            .to_string()
+    };
+    let choices = if last {
+        Vec::new()
+    } else {
+        vec![
+            Choice {
+                id: "linger".to_string(),
+                text: "Stay a little longer".to_string(),
+                consequence_hint: None,
+                provenance: None,
+            },
+            Choice {
+                id: "move_on".to_string(),
+                text: "Move on".to_string(),
+                consequence_hint: None,
+                provenance: None,
+            },
+        ]
+    };
+    NarrativeMoment {
+        id: Uuid::new_v4(),
+        text,
+        speaker: None,
+        mood: if last { "transcendent" } else { "neutral" }.to_string(),
+        choices,
+        timestamp: Utc::now(),
+        summarized: false,
+        world_updates: Vec::new(),
+        state: MomentState::Generated,
+        translation: None,
+        deja_vu: None,
+        fate: None,
+        assets: None,
+        provenance: Some(Provenance::Server),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SeedMemoriesResponse {
+    memories: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DarknessResponse {
+    darkness: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SuggestionsResponse {
+    suggestions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LedgerJudgmentsResponse {
+    judgments: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FinaleResponse {
+    moments: Vec<FinaleMomentResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FinaleMomentResponse {
+    text: String,
+    speaker: Option<String>,
+    mood: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResetSequenceResponse {
+    fade: String,
+    fragment: String,
+    awakening: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NarrativeResponse {
+    text: String,
+    speaker: Option<String>,
+    mood: String,
+    choices: Vec<ChoiceResponse>,
+    #[serde(default, alias = "effects")]
+    world_updates: Vec<serde_json::Value>,
+    #[serde(default)]
+    translation: Option<TranslationResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TranslationResponse {
+    text: String,
+    #[serde(default)]
+    choices: HashMap<String, String>,
+}
+
+impl TranslationResponse {
+    /// The translation, if it covers the moment's text and every choice
+    fn for_moment(mut self, moment: &NarrativeMoment, locale: Locale) -> Option<MomentTranslation> {
+        let text = self.text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        let choices = moment
+            .choices
+            .iter()
+            .map(|c| {
+                let translated = self.choices.remove(&c.id)?;
+                let translated = translated.trim();
+                (!translated.is_empty()).then(|| (c.id.clone(), translated.to_string()))
+            })
+            .collect::<Option<BTreeMap<_, _>>>()?;
+        Some(MomentTranslation {
+            locale,
+            text: text.to_string(),
+            choices,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EnglishResponse {
+    english: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChoiceResponse {
+    id: String,
+    text: String,
+    consequence_hint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RatingsResponse {
+    ratings: Vec<RatedChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RatedChoice {
+    id: String,
+    interest: f64,
+    fit: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChoiceList {
+    choices: Vec<ChoiceResponse>,
+}
+
+/// A run title from model output: its first line without quotes, markup or
+/// a closing period, if it is short enough to be one
+fn clean_title(content: &str) -> Option<String> {
+    let line = content.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line.strip_prefix("Title:").unwrap_or(line);
+    let title = line
+        .trim_matches(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '*' | '#' | '“' | '”'))
+        .trim_end_matches('.')
+        .trim();
+    (!title.is_empty() && !title.starts_with('{') && title.chars().count() <= MAX_TITLE_CHARS)
+        .then(|| title.to_string())
+}
+
+/// Find the outermost `{ ... }` span in free-form model output
+fn extract_json_object(content: &str) -> Option<&str> {
+    let start = content.find('{')?;
+    let end = content.rfind('}')?;
+    (end > start).then(|| &content[start..=end])
+}
+
+#[cfg(feature = "local-llm")]
+mod local;
+mod reply;
+#[cfg(test)]
+mod snapshot_tests;
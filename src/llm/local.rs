@@ -0,0 +1,137 @@
+//! In-process narration from a GGUF model through llama.cpp, for deployments
+//! that ship as a single binary with no LLM server next to them.
+
+use anyhow::{Context, Result};
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+use llama_cpp_2::sampling::LlamaSampler;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+
+use super::ChatRequest;
+use crate::config::Config;
+
+/// A completion generated in process, with the tokens it took
+pub struct LocalCompletion {
+    pub content: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// A GGUF model loaded once at startup
+pub struct LocalModel {
+    backend: LlamaBackend,
+    model: LlamaModel,
+    context_size: u32,
+    /// One generation at a time; a second context would double the memory
+    generating: Mutex<()>,
+}
+
+impl LocalModel {
+    pub fn load(config: &Config, path: &str) -> Result<Self> {
+        let backend = LlamaBackend::init().context("failed to start llama.cpp")?;
+        let params = LlamaModelParams::default().with_n_gpu_layers(config.local_model_gpu_layers);
+        let model = LlamaModel::load_from_file(&backend, path, &params)
+            .with_context(|| format!("failed to load local model {}", path))?;
+        tracing::info!(
+            "Narrating with local model {} ({} token context, {} GPU layers)",
+            path,
+            config.local_model_context,
+            config.local_model_gpu_layers
+        );
+        Ok(Self {
+            backend,
+            model,
+            context_size: config.local_model_context,
+            generating: Mutex::new(()),
+        })
+    }
+
+    /// Generate a completion on a blocking thread
+    pub async fn complete(self: &Arc<Self>, request: &ChatRequest) -> Result<LocalCompletion> {
+        let model = self.clone();
+        let request = request.clone();
+        tokio::task::spawn_blocking(move || model.generate(&request)).await?
+    }
+
+    fn generate(&self, request: &ChatRequest) -> Result<LocalCompletion> {
+        let _generating = self.generating.lock().unwrap_or_else(|e| e.into_inner());
+
+        let prompt = self.prompt(request)?;
+        let tokens = self.model.str_to_token(&prompt, AddBos::Always)?;
+        let budget = tokens.len() + request.max_tokens as usize;
+        if budget > self.context_size as usize {
+            anyhow::bail!(
+                "prompt of {} tokens leaves no room in a {} token context",
+                tokens.len(),
+                self.context_size
+            );
+        }
+
+        let params = LlamaContextParams::default().with_n_ctx(NonZeroU32::new(self.context_size));
+        let mut context = self.model.new_context(&self.backend, params)?;
+        let mut batch = LlamaBatch::new(self.context_size as usize, 1);
+        let last = tokens.len() as i32 - 1;
+        for (position, token) in (0_i32..).zip(&tokens) {
+            batch.add(*token, position, &[0], position == last)?;
+        }
+        context.decode(&mut batch)?;
+
+        let mut sampler = if request.temperature <= 0.0 {
+            LlamaSampler::greedy()
+        } else {
+            let seed = request.seed.map_or_else(rand::random, |s| s as u32);
+            LlamaSampler::chain_simple([
+                LlamaSampler::temp(request.temperature),
+                LlamaSampler::dist(seed),
+            ])
+        };
+
+        let mut bytes = Vec::new();
+        let mut position = batch.n_tokens();
+        let mut generated = 0;
+        while generated < request.max_tokens {
+            let token = sampler.sample(&context, batch.n_tokens() - 1);
+            if self.model.is_eog_token(token) {
+                break;
+            }
+            bytes.extend(self.model.token_to_bytes(token, Special::Tokenize)?);
+            generated += 1;
+
+            batch.clear();
+            batch.add(token, position, &[0], true)?;
+            position += 1;
+            context.decode(&mut batch)?;
+        }
+
+        Ok(LocalCompletion {
+            content: String::from_utf8_lossy(&bytes).into_owned(),
+            prompt_tokens: tokens.len() as u64,
+            completion_tokens: generated as u64,
+        })
+    }
+
+    /// The conversation in the model's own chat format, or a plain transcript
+    /// for models that don't ship one
+    fn prompt(&self, request: &ChatRequest) -> Result<String> {
+        let messages = request
+            .messages
+            .iter()
+            .map(|m| LlamaChatMessage::new(m.role.clone(), m.content.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match self.model.chat_template(None) {
+            Ok(template) => Ok(self.model.apply_chat_template(&template, &messages, true)?),
+            Err(_) => {
+                let mut prompt = String::new();
+                for message in &request.messages {
+                    prompt.push_str(&format!("### {}\n{}\n\n", message.role, message.content));
+                }
+                prompt.push_str("### assistant\n");
+                Ok(prompt)
+            }
+        }
+    }
+}
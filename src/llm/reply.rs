@@ -0,0 +1,173 @@
+//! Chat completion replies, read without trusting the provider.
+//!
+//! Providers report failure in their own ways: a status with an `error`
+//! object, an `error` object under a `200`, an empty `choices` array, or a
+//! body that isn't JSON at all. Every reply turns into either a completion
+//! with at least one choice or an [`UpstreamError`] saying which it was.
+
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::ChatResponse;
+
+/// Characters of an upstream's error message kept in an [`UpstreamError`]
+const DETAIL_CHARS: usize = 200;
+
+/// Why the LLM upstream gave no completion
+#[derive(Debug, thiserror::Error)]
+pub enum UpstreamError {
+    /// The upstream refused the API key or the signature
+    #[error("LLM upstream refused the credentials: {0}")]
+    AuthFailed(String),
+    /// The upstream doesn't serve the requested model
+    #[error("LLM upstream has no such model: {0}")]
+    ModelNotFound(String),
+    /// The upstream is shedding load; `retry_after` is its `Retry-After`
+    #[error("LLM upstream is rate limiting: {detail}")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        detail: String,
+    },
+    /// Any other error status or error object
+    #[error("LLM upstream answered {status}: {detail}")]
+    Failed { status: u16, detail: String },
+    /// A success whose body isn't a chat completion
+    #[error("LLM upstream sent a malformed reply: {0}")]
+    Malformed(String),
+    /// A completion without a single choice
+    #[error("LLM returned no choices")]
+    Empty,
+}
+
+impl UpstreamError {
+    const KINDS: [&'static str; 6] = [
+        "auth_failed",
+        "model_not_found",
+        "rate_limited",
+        "failed",
+        "malformed",
+        "empty",
+    ];
+
+    fn index(&self) -> usize {
+        match self {
+            UpstreamError::AuthFailed(_) => 0,
+            UpstreamError::ModelNotFound(_) => 1,
+            UpstreamError::RateLimited { .. } => 2,
+            UpstreamError::Failed { .. } => 3,
+            UpstreamError::Malformed(_) => 4,
+            UpstreamError::Empty => 5,
+        }
+    }
+
+    /// Metrics label of the variant
+    pub fn kind(&self) -> &'static str {
+        Self::KINDS[self.index()]
+    }
+}
+
+/// Failed completions by kind of upstream error
+#[derive(Default)]
+pub struct UpstreamErrors {
+    counts: [AtomicU64; UpstreamError::KINDS.len()],
+}
+
+impl UpstreamErrors {
+    pub fn record(&self, error: &UpstreamError) {
+        self.counts[error.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn write_metrics(&self, out: &mut String) {
+        out.push_str("# HELP nihilism_llm_upstream_errors_total Completions the LLM upstream failed, by kind\n");
+        out.push_str("# TYPE nihilism_llm_upstream_errors_total counter\n");
+        for (kind, count) in UpstreamError::KINDS.iter().zip(&self.counts) {
+            out.push_str(&format!(
+                "nihilism_llm_upstream_errors_total{{kind=\"{}\"}} {}\n",
+                kind,
+                count.load(Ordering::Relaxed)
+            ));
+        }
+    }
+}
+
+/// Read a chat completion reply given its status, `Retry-After` header and body
+pub(super) fn parse(
+    status: u16,
+    retry_after: Option<&str>,
+    body: &str,
+) -> Result<ChatResponse, UpstreamError> {
+    let success = (200..300).contains(&status);
+    let value = match serde_json::from_str::<Value>(body) {
+        Ok(value) => value,
+        Err(_) if success => return Err(UpstreamError::Malformed(detail(body))),
+        Err(_) => return Err(classify(status, retry_after, None, detail(body))),
+    };
+    if let Some(error) = value.get("error").filter(|e| !e.is_null()) {
+        return Err(error_object(status, retry_after, error));
+    }
+    if !success {
+        return Err(classify(status, retry_after, None, detail(body)));
+    }
+    let response: ChatResponse = serde_json::from_value(value)
+        .map_err(|e| UpstreamError::Malformed(detail(&e.to_string())))?;
+    if response.choices.is_empty() {
+        return Err(UpstreamError::Empty);
+    }
+    Ok(response)
+}
+
+/// Classify an `error` member, either `{"message", "code", "type"}` or a
+/// bare string
+pub(super) fn error_object(status: u16, retry_after: Option<&str>, error: &Value) -> UpstreamError {
+    let message = error
+        .get("message")
+        .and_then(Value::as_str)
+        .or_else(|| error.as_str())
+        .map(detail)
+        .unwrap_or_else(|| detail(&error.to_string()));
+    let code = ["code", "type"]
+        .iter()
+        .filter_map(|key| error.get(key).and_then(Value::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+    classify(status, retry_after, Some(&code), message)
+}
+
+/// Sort a failure into its variant: by the provider's error code when it
+/// names one we know, by status otherwise
+fn classify(
+    status: u16,
+    retry_after: Option<&str>,
+    code: Option<&str>,
+    detail: String,
+) -> UpstreamError {
+    let code = code.unwrap_or_default().to_ascii_lowercase();
+    let auth = ["invalid_api_key", "authentication", "permission"]
+        .iter()
+        .any(|c| code.contains(c));
+    if code.contains("rate_limit") || (status == 429 && !auth) {
+        return UpstreamError::RateLimited {
+            retry_after: retry_after
+                .and_then(|s| s.trim().parse::<u64>().ok())
+                .map(Duration::from_secs),
+            detail,
+        };
+    }
+    if code.contains("model_not_found") {
+        UpstreamError::ModelNotFound(detail)
+    } else if auth || matches!(status, 401 | 403) {
+        UpstreamError::AuthFailed(detail)
+    } else if status == 404 {
+        UpstreamError::ModelNotFound(detail)
+    } else {
+        UpstreamError::Failed { status, detail }
+    }
+}
+
+fn detail(text: &str) -> String {
+    text.trim().chars().take(DETAIL_CHARS).collect()
+}
+
+#[cfg(test)]
+mod tests;
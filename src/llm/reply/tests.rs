@@ -0,0 +1,101 @@
+use proptest::prelude::*;
+use serde_json::json;
+
+use super::*;
+
+#[test]
+fn failures_are_told_apart() {
+    let kind = |status, retry_after, body: &str| parse(status, retry_after, body).unwrap_err();
+
+    let openai = r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error","code":"invalid_api_key"}}"#;
+    assert!(
+        matches!(kind(401, None, openai), UpstreamError::AuthFailed(m) if m == "Incorrect API key provided")
+    );
+    assert_eq!(kind(403, None, "Forbidden").kind(), "auth_failed");
+
+    let missing =
+        r#"{"error":{"message":"The model `gpt-9` does not exist","code":"model_not_found"}}"#;
+    assert_eq!(kind(404, None, missing).kind(), "model_not_found");
+    // Some gateways answer a missing model with a 400
+    assert_eq!(kind(400, None, missing).kind(), "model_not_found");
+
+    match kind(429, Some("12"), r#"{"error":"slow down"}"#) {
+        UpstreamError::RateLimited {
+            retry_after,
+            detail,
+        } => {
+            assert_eq!(retry_after, Some(Duration::from_secs(12)));
+            assert_eq!(detail, "slow down");
+        }
+        other => panic!("expected a rate limit, got {:?}", other),
+    }
+    // An error object under a 200 is still an error
+    let limited = json!({"error": {"message": "busy", "type": "rate_limit_exceeded"}}).to_string();
+    assert_eq!(kind(200, None, &limited).kind(), "rate_limited");
+
+    assert!(matches!(
+        kind(502, None, "<html>Bad gateway</html>"),
+        UpstreamError::Failed { status: 502, .. }
+    ));
+    assert_eq!(kind(200, None, "<html>").kind(), "malformed");
+    assert_eq!(
+        kind(200, None, r#"{"choices":[{"message":{"content":null}}]}"#).kind(),
+        "malformed"
+    );
+    assert_eq!(kind(200, None, r#"{"choices":[]}"#).kind(), "empty");
+
+    let reply = parse(
+        200,
+        None,
+        r#"{"choices":[{"message":{"content":"The door."}}],"error":null}"#,
+    )
+    .unwrap();
+    assert_eq!(reply.choices[0].message.content, "The door.");
+}
+
+/// Bodies shaped like a provider's reply, with every part liable to be
+/// missing or of the wrong type
+fn reply_body() -> impl Strategy<Value = String> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        ".{0,12}".prop_map(Value::from),
+    ];
+    let value = leaf.prop_recursive(3, 16, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Value::from),
+            prop::collection::btree_map(
+                prop_oneof![
+                    Just("choices".to_string()),
+                    Just("message".to_string()),
+                    Just("content".to_string()),
+                    Just("error".to_string()),
+                    Just("code".to_string()),
+                    "[a-z]{1,6}",
+                ],
+                inner,
+                0..4,
+            )
+            .prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    });
+    prop_oneof![value.prop_map(|v| v.to_string()), ".{0,40}"]
+}
+
+proptest! {
+    #[test]
+    fn any_reply_parses_without_panicking(
+        status in prop_oneof![Just(200u16), Just(401), Just(404), Just(429), 100u16..600],
+        retry_after in proptest::option::of(".{0,6}"),
+        body in reply_body(),
+    ) {
+        match parse(status, retry_after.as_deref(), &body) {
+            Ok(reply) => {
+                prop_assert!((200..300).contains(&status));
+                prop_assert!(!reply.choices.is_empty());
+            }
+            Err(error) => prop_assert!(error.to_string().chars().count() <= DETAIL_CHARS + 64),
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! Snapshot harness for narrator prompts and parsed moments.
+//!
+//! Canned player states are run through `build_system_prompt` and through
+//! `generate_narrative` against an in-process mock LLM. Review changes with
+//! `cargo insta review` (or `INSTA_UPDATE=always cargo test`).
+
+use axum::{extract::State, routing::post, Json, Router};
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use super::*;
+use crate::challenge::{Challenge, ChallengeRun};
+use crate::config::ContentRating;
+use crate::persona::Persona;
+use crate::testing::{self, PlayerBuilder};
+
+/// Completions the mock returns in order, and the request bodies it received
+#[derive(Clone, Default)]
+struct MockLlm {
+    replies: Arc<Mutex<VecDeque<String>>>,
+    requests: Arc<Mutex<Vec<Value>>>,
+}
+
+async fn mock_completion(State(mock): State<MockLlm>, Json(body): Json<Value>) -> Json<Value> {
+    mock.requests.lock().unwrap().push(body);
+    let content = mock
+        .replies
+        .lock()
+        .unwrap()
+        .pop_front()
+        .expect("mock LLM ran out of replies");
+    Json(json!({
+        "choices": [{ "message": { "role": "assistant", "content": content } }],
+        "usage": { "prompt_tokens": 0, "completion_tokens": 0 }
+    }))
+}
+
+/// Start a mock backend serving `replies` and a client pointed at it
+async fn client_with_replies(
+    replies: &[&str],
+    configure: impl FnOnce(&mut Config),
+) -> (LlmClient, MockLlm) {
+    let mock = MockLlm::default();
+    mock.replies
+        .lock()
+        .unwrap()
+        .extend(replies.iter().map(|r| r.to_string()));
+
+    let app = Router::new()
+        .route("/v1/chat/completions", post(mock_completion))
+        .with_state(mock.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let mut config = Config::for_tests(&format!("http://{}/v1", addr));
+    configure(&mut config);
+    (LlmClient::new(config).expect("client"), mock)
+}
+
+fn client(configure: impl FnOnce(&mut Config)) -> LlmClient {
+    let mut config = Config::for_tests("http://127.0.0.1:9/v1");
+    configure(&mut config);
+    LlmClient::new(config).expect("client")
+}
+
+fn fresh_player() -> Player {
+    Player::new()
+}
+
+fn dark_veteran() -> Player {
+    PlayerBuilder::new()
+        .loops(6)
+        .dark(24)
+        .light(7)
+        .score(72)
+        .memories([
+            "The bell tower fell silent when you cut the rope.",
+            "You left the girl at the station again.",
+        ])
+        .choices_made(["ignore_stranger", "walk_away"])
+        .build()
+}
+
+fn hopeful_player() -> Player {
+    PlayerBuilder::new()
+        .loops(3)
+        .dark(2)
+        .light(12)
+        .score(-41)
+        .memories(["The baker remembered your name, just once."])
+        .build()
+}
+
+const TRANSLATION: &str = r#"{"text": "Korytarz nuci piosenkę, którą prawie pamiętasz.", "choices": {"listen": "Zatrzymaj się i słuchaj", "walk_away": "Odejdź"}}"#;
+
+const VALID_MOMENT: &str = r#"{"text": "The corridor hums with a song you almost remember.", "speaker": null, "mood": "neutral", "choices": [{"id": "listen", "text": "Stop and listen", "consequence_hint": null}, {"id": "walk_away", "text": "Walk away", "consequence_hint": "It will stop singing"}]}"#;
+
+fn moment_settings() -> insta::Settings {
+    let mut settings = insta::Settings::clone_current();
+    settings.add_redaction(".id", "[id]");
+    settings.add_redaction(".timestamp", "[timestamp]");
+    settings
+}
+
+#[test]
+fn prompt_fresh_player() {
+    insta::assert_snapshot!(client(|_| {}).build_system_prompt(&fresh_player(), Locale::En));
+}
+
+#[test]
+fn prompt_dark_veteran() {
+    insta::assert_snapshot!(client(|_| {}).build_system_prompt(&dark_veteran(), Locale::En));
+}
+
+#[test]
+fn prompt_teen_rating() {
+    let llm = client(|c| c.content_rating = ContentRating::Teen);
+    insta::assert_snapshot!(llm.build_system_prompt(&hopeful_player(), Locale::En));
+}
+
+#[test]
+fn prompt_each_persona_voice() {
+    let llm = client(|_| {});
+    for persona in Persona::ALL {
+        let mut player = hopeful_player();
+        player.run.persona = persona;
+        insta::assert_snapshot!(
+            format!("prompt_persona_{:?}", persona).to_lowercase(),
+            llm.build_system_prompt(&player, Locale::En)
+        );
+    }
+}
+
+#[test]
+fn prompt_persona_switch_note() {
+    let mut player = dark_veteran();
+    player.set_persona(Persona::Archivist);
+    insta::assert_snapshot!(client(|_| {}).build_system_prompt(&player, Locale::En));
+}
+
+#[test]
+fn prompt_daily_challenge() {
+    let date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+    let mut player = fresh_player();
+    player.run.challenge = Some(ChallengeRun::new(&Challenge::for_date(date), None));
+    insta::assert_snapshot!(client(|_| {}).build_system_prompt(&player, Locale::En));
+}
+
+#[test]
+fn prompt_polish_reader() {
+    insta::assert_snapshot!(client(|_| {}).build_system_prompt(&fresh_player(), Locale::Pl));
+}
+
+#[tokio::test]
+async fn moment_from_valid_json() {
+    let (llm, mock) = client_with_replies(&[VALID_MOMENT], |_| {}).await;
+    let moment = llm.generate_narrative(&dark_veteran(), None, Locale::En).await.unwrap();
+
+    moment_settings().bind(|| insta::assert_yaml_snapshot!(moment));
+    let requests = mock.requests.lock().unwrap();
+    insta::assert_yaml_snapshot!("moment_from_valid_json_request", requests[0]["messages"][1]);
+}
+
+#[tokio::test]
+async fn handed_over_run_is_narrated_by_its_model() {
+    let (llm, mock) = client_with_replies(&[VALID_MOMENT], |_| {}).await;
+    let mut player = dark_veteran();
+    player.set_model(Some("cheap-model".to_string()));
+    llm.generate_narrative(&player, None, Locale::En).await.unwrap();
+
+    let requests = mock.requests.lock().unwrap();
+    assert_eq!(requests[0]["model"], "cheap-model");
+    let system = requests[0]["messages"][0]["content"].as_str().unwrap();
+    assert!(system.contains("taking over the narration of this run"));
+}
+
+#[tokio::test]
+async fn moment_from_fenced_json() {
+    let reply = format!("Here is the next moment:\n```json\n{}\n```", VALID_MOMENT);
+    let (llm, _) = client_with_replies(&[&reply], |_| {}).await;
+    let moment = llm.generate_narrative(&fresh_player(), None, Locale::En).await.unwrap();
+
+    moment_settings().bind(|| insta::assert_yaml_snapshot!(moment));
+}
+
+#[tokio::test]
+async fn moment_repaired_by_model() {
+    let prose = "The corridor hums. You may listen, or walk away.";
+    let (llm, mock) = client_with_replies(&[prose, VALID_MOMENT], |_| {}).await;
+    let moment = llm.generate_narrative(&fresh_player(), None, Locale::En).await.unwrap();
+
+    moment_settings().bind(|| insta::assert_yaml_snapshot!(moment));
+    assert_eq!(mock.requests.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn moment_falls_back_on_prose() {
+    let prose = "The corridor hums. You may listen, or walk away.";
+    let (llm, _) = client_with_replies(&[prose, "still not json"], |_| {}).await;
+    let moment = llm.generate_narrative(&fresh_player(), None, Locale::En).await.unwrap();
+
+    moment_settings().bind(|| insta::assert_yaml_snapshot!(moment));
+}
+
+#[tokio::test]
+async fn moment_after_choice() {
+    let (llm, mock) = client_with_replies(&[VALID_MOMENT], |_| {}).await;
+    let choice = Choice {
+        id: "walk_away".to_string(),
+        text: "Walk away".to_string(),
+        consequence_hint: None,
+        provenance: None,
+    };
+    llm.process_choice(&dark_veteran(), &choice, Locale::En).await.unwrap();
+
+    let requests = mock.requests.lock().unwrap();
+    insta::assert_yaml_snapshot!(requests[0]["messages"][1]);
+}
+
+#[tokio::test]
+async fn moment_with_translation() {
+    let reply = format!("{}, \"translation\": {}}}", &VALID_MOMENT[..VALID_MOMENT.len() - 1], TRANSLATION);
+    let (llm, mock) = client_with_replies(&[&reply], |_| {}).await;
+    let moment = llm.generate_narrative(&fresh_player(), None, Locale::Pl).await.unwrap();
+
+    moment_settings().bind(|| insta::assert_yaml_snapshot!(moment));
+    assert_eq!(mock.requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn moment_translated_separately() {
+    let (llm, mock) = client_with_replies(&[VALID_MOMENT, TRANSLATION], |_| {}).await;
+    let moment = llm.generate_narrative(&fresh_player(), None, Locale::Pl).await.unwrap();
+
+    moment_settings().bind(|| insta::assert_yaml_snapshot!(moment));
+    let requests = mock.requests.lock().unwrap();
+    insta::assert_yaml_snapshot!("moment_translated_separately_request", requests[1]["messages"][1]);
+}
+
+fn tense_player() -> Player {
+    PlayerBuilder::new()
+        .loops(4)
+        .moment("The bridge groans under you.", &[("run", "Run")])
+        .mood("dark")
+        .moment("Someone is screaming your name.", &[("answer", "Answer")])
+        .mood("dark")
+        .score_deltas([9, 12, 10])
+        .build()
+}
+
+fn calm_player() -> Player {
+    PlayerBuilder::new()
+        .loops(2)
+        .moment("Bread cools on the windowsill.", &[("smell", "Breathe it in")])
+        .mood("hopeful")
+        .score_deltas([-1, -2])
+        .build()
+}
+
+const FOUR_CHOICES: &str = r#"{"text": "The bridge gives way.", "speaker": null, "mood": "dark", "choices": [{"id": "jump", "text": "Jump", "consequence_hint": null}, {"id": "hold_on", "text": "Hold on", "consequence_hint": null}, {"id": "look", "text": "Look down", "consequence_hint": null}, {"id": "pray", "text": "Pray", "consequence_hint": null}]}"#;
+
+#[test]
+fn prompt_under_tension() {
+    insta::assert_snapshot!(client(|_| {}).build_system_prompt(&tense_player(), Locale::En));
+}
+
+#[tokio::test]
+async fn moment_trimmed_under_tension() {
+    let (llm, mock) = client_with_replies(&[FOUR_CHOICES], |_| {}).await;
+    let moment = llm.generate_narrative(&tense_player(), None, Locale::En).await.unwrap();
+
+    let ids: Vec<_> = moment.choices.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, ["jump", "hold_on"]);
+    assert_eq!(mock.requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn calm_moment_given_more_choices() {
+    let more = r#"{"choices": [{"id": "listen", "text": "Stop and listen", "consequence_hint": null}, {"id": "window", "text": "Open the window", "consequence_hint": null}]}"#;
+    let (llm, mock) = client_with_replies(&[VALID_MOMENT, more], |_| {}).await;
+    let moment = llm.generate_narrative(&calm_player(), None, Locale::En).await.unwrap();
+
+    // The duplicate of an existing choice is skipped
+    let mut ids: Vec<_> = moment.choices.iter().map(|c| c.id.as_str()).collect();
+    ids.sort();
+    assert_eq!(ids, ["listen", "walk_away", "window"]);
+    let requests = mock.requests.lock().unwrap();
+    insta::assert_yaml_snapshot!(requests[1]["messages"]);
+}
+
+#[test]
+fn prompt_fracturing_loop() {
+    let player = PlayerBuilder::new().loops(3).stability(12).build();
+    insta::assert_snapshot!(client(|_| {}).build_system_prompt(&player, Locale::En));
+}
+
+#[tokio::test]
+async fn fracturing_loop_echoes_a_choice() {
+    let (llm, _) = client_with_replies(&[VALID_MOMENT], |_| {}).await;
+    let player = PlayerBuilder::new().loops(3).stability(12).build();
+    let moment = llm.generate_narrative(&player, None, Locale::En).await.unwrap();
+
+    assert_eq!(moment.choices.len(), 3);
+    let echo = moment
+        .choices
+        .iter()
+        .find(|c| c.id.ends_with(crate::stability::ECHO_SUFFIX))
+        .expect("an echoed choice");
+    let original = echo.id.trim_end_matches(crate::stability::ECHO_SUFFIX);
+    assert!(moment.choices.iter().any(|c| c.id == original && c.text == echo.text));
+}
+
+#[test]
+fn shard_summary_of_archived_loop() {
+    let archived = testing::archived_loop(&dark_veteran(), 3, "The bell rang unanswered");
+    insta::assert_snapshot!(default_shard_summary(&archived));
+}
+
+#[tokio::test]
+async fn run_title_from_first_loop() {
+    let player = dark_veteran();
+    let first = testing::archived_loop(&player, 1, "The bell rang unanswered");
+    let (llm, mock) = client_with_replies(
+        &[
+            "**\"The Winter of Walking Away.\"**\nA fitting name.",
+            "The Winter of Walking Away",
+            "{\"title\": \"Doors\"}",
+        ],
+        |_| {},
+    )
+    .await;
+
+    let title = llm.generate_run_title(&player, &first, None).await.unwrap();
+    assert_eq!(title, "The Winter of Walking Away");
+    // A re-roll must come up with something new
+    let reroll = llm.generate_run_title(&player, &first, Some(&title)).await;
+    assert!(reroll.is_err());
+    assert!(llm.generate_run_title(&player, &first, None).await.is_err());
+
+    let requests = mock.requests.lock().unwrap();
+    let system = requests[1]["messages"][0]["content"].as_str().unwrap();
+    assert!(system.contains("Do not reuse the title \"The Winter of Walking Away\""));
+}
+
+#[test]
+fn prompt_with_fate_gravity() {
+    let llm = client(|c| c.fate_gravity = 1.0);
+    insta::assert_snapshot!(llm.build_system_prompt(&dark_veteran(), Locale::En));
+}
+
+#[tokio::test]
+async fn moment_records_fate_bias() {
+    let (llm, _) = client_with_replies(&[VALID_MOMENT, VALID_MOMENT], |c| c.fate_gravity = 0.6).await;
+
+    let moment = llm.generate_narrative(&dark_veteran(), None, Locale::En).await.unwrap();
+    let fate = moment.fate.expect("fate bias");
+    assert_eq!(fate.ending, EndingType::VoidEmbrace);
+    assert_eq!((fate.proximity, fate.bias), (0.85, 0.51));
+
+    // Far from every ending, the pull is too weak to matter
+    let moment = llm.generate_narrative(&fresh_player(), None, Locale::En).await.unwrap();
+    assert!(moment.fate.is_none());
+}
+
+#[tokio::test]
+async fn preflight_needs_an_upstream_that_answers() {
+    let (llm, mock) = client_with_replies(&["OK"], |_| {}).await;
+    llm.preflight().await.unwrap();
+    assert_eq!(mock.requests.lock().unwrap()[0]["max_tokens"], 1);
+
+    // An unreachable second upstream is only a warning
+    let (llm, _) = client_with_replies(&["OK"], |c| {
+        c.llm_base_urls.push("http://127.0.0.1:9/v1".to_string())
+    })
+    .await;
+    llm.preflight().await.unwrap();
+
+    let error = client(|_| {}).preflight().await.unwrap_err();
+    assert!(error.to_string().contains("no LLM upstream answered"));
+}
+
+#[test]
+fn preflight_errors_point_at_the_setting_to_fix() {
+    let url = "http://llm.local/v1";
+    let (fatal, error) = preflight_error(url, "gpt-4", reqwest::StatusCode::UNAUTHORIZED, " invalid key ");
+    assert!(fatal);
+    assert!(error.contains("check LLM_API_KEY: invalid key"));
+
+    let (fatal, error) = preflight_error(url, "gpt-5", reqwest::StatusCode::NOT_FOUND, "");
+    assert!(fatal);
+    assert!(error.contains("no model 'gpt-5'"));
+
+    // Busy or overloaded upstreams may still serve players later
+    assert!(!preflight_error(url, "gpt-4", reqwest::StatusCode::TOO_MANY_REQUESTS, "").0);
+}
+
+#[test]
+fn streamed_completion_is_put_back_together() {
+    let mut completion = StreamedCompletion::default();
+    for line in [
+        ": keep-alive",
+        r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#,
+        r#"data: {"choices":[{"delta":{"content":"{\"text\":"}}]}"#,
+        "",
+        r#"data: {"choices":[{"delta":{"content":" \"The door.\"}"}}]}"#,
+        r#"data: {"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":5}}"#,
+        "data: [DONE]",
+    ] {
+        completion.feed(line);
+    }
+    assert!(completion.done);
+    assert_eq!(completion.tokens, 2);
+    let response = completion.into_response().unwrap();
+    assert_eq!(response.choices[0].message.content, r#"{"text": "The door."}"#);
+    assert_eq!(response.usage.map(|u| u.completion_tokens), Some(5));
+
+    let mut failed = StreamedCompletion::default();
+    failed.feed(r#"data: {"choices":[{"delta":{"content":"The"}}]}"#);
+    failed.feed(r#"data: {"error":{"message":"overloaded","code":"rate_limit_exceeded"}}"#);
+    assert!(failed.done);
+    assert!(matches!(failed.into_response(), Err(UpstreamError::RateLimited { .. })));
+    assert!(matches!(StreamedCompletion::default().into_response(), Err(UpstreamError::Empty)));
+}
@@ -0,0 +1,452 @@
+use anyhow::{Context, Result};
+use rand::seq::IndexedRandom;
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::stability::ECHO_SUFFIX;
+
+const DEFAULT_PLAYERS: usize = 10;
+const DEFAULT_CHOICES: usize = 5;
+/// How often the server's metrics are sampled while the test runs
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+const USAGE: &str = "usage: nihilism loadtest <url> [--players N] [--choices N] \
+                     [--ramp-up SECS] [--admin-token TOKEN] [--report FILE]";
+
+struct Options {
+    target: String,
+    players: usize,
+    choices: usize,
+    ramp_up: Duration,
+    admin_token: Option<String>,
+    report: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut options = Options {
+            target: String::new(),
+            players: DEFAULT_PLAYERS,
+            choices: DEFAULT_CHOICES,
+            ramp_up: Duration::ZERO,
+            admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            report: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("{} needs a value\n{}", arg, USAGE))
+            };
+            match arg.as_str() {
+                "--players" => options.players = value()?.parse().context("--players")?,
+                "--choices" => options.choices = value()?.parse().context("--choices")?,
+                "--ramp-up" => {
+                    options.ramp_up =
+                        Duration::from_secs_f64(value()?.parse().context("--ramp-up")?)
+                }
+                "--admin-token" => options.admin_token = Some(value()?.clone()),
+                "--report" => options.report = Some(value()?.clone()),
+                _ if arg.starts_with("--") || !options.target.is_empty() => {
+                    anyhow::bail!("unexpected argument '{}'\n{}", arg, USAGE)
+                }
+                _ => options.target = arg.trim_end_matches('/').to_string(),
+            }
+        }
+        if options.target.is_empty() || options.players == 0 {
+            anyhow::bail!(USAGE);
+        }
+        Ok(options)
+    }
+}
+
+/// Latencies of every request to one endpoint
+#[derive(Default)]
+struct Timings {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+/// Latencies by endpoint, shared by the synthetic players
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<BTreeMap<&'static str, Timings>>>);
+
+impl Recorder {
+    fn record(&self, endpoint: &'static str, elapsed: Duration, ok: bool) {
+        let mut timings = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let timings = timings.entry(endpoint).or_default();
+        timings.latencies.push(elapsed);
+        if !ok {
+            timings.errors += 1;
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EndpointReport {
+    pub endpoint: &'static str,
+    pub requests: usize,
+    pub errors: u64,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Peak and mean of a gauge sampled from the server's metrics
+#[derive(Debug, Default, Serialize)]
+pub struct GaugeReport {
+    pub samples: u64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadReport {
+    pub target: String,
+    pub players: usize,
+    pub choices_per_player: usize,
+    pub duration_secs: f64,
+    /// Players that made all their choices, or reached the end of their run
+    pub completed_players: usize,
+    /// Players the server sent to the waiting room
+    pub waiting_players: usize,
+    pub endpoints: Vec<EndpointReport>,
+    /// LLM completions in flight; absent without an admin token
+    pub llm_in_flight: Option<GaugeReport>,
+    pub waiting_room: Option<GaugeReport>,
+}
+
+/// Nearest-rank percentile of sorted latencies, in milliseconds
+fn percentile(sorted: &[Duration], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1].as_secs_f64() * 1000.0
+}
+
+/// Pick one of the moment's choices, skipping the echoes a fracturing loop adds
+fn pick_choice(moment: &Value) -> Option<Value> {
+    let choices = moment.get("choices")?.as_array()?;
+    let genuine: Vec<&Value> = choices
+        .iter()
+        .filter(|c| {
+            c.get("id")
+                .and_then(Value::as_str)
+                .is_some_and(|id| !id.ends_with(ECHO_SUFFIX))
+        })
+        .collect();
+    let pool: Vec<&Value> = if genuine.is_empty() {
+        choices.iter().collect()
+    } else {
+        genuine
+    };
+    pool.choose(&mut rand::rng()).map(|c| (*c).clone())
+}
+
+/// How one synthetic player's session ended
+enum Outcome {
+    Completed,
+    Waiting,
+    Failed,
+}
+
+struct Session {
+    client: Client,
+    target: String,
+    recorder: Recorder,
+}
+
+impl Session {
+    async fn post(
+        &self,
+        endpoint: &'static str,
+        path: &str,
+        body: Value,
+    ) -> Result<(StatusCode, Value)> {
+        let started = Instant::now();
+        let result = self
+            .client
+            .post(format!("{}{}", self.target, path))
+            .json(&body)
+            .send()
+            .await;
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                self.recorder.record(endpoint, started.elapsed(), false);
+                return Err(e.into());
+            }
+        };
+        let status = response.status();
+        let body = response.json::<Value>().await.unwrap_or(Value::Null);
+        self.recorder
+            .record(endpoint, started.elapsed(), status.is_success());
+        Ok((status, body))
+    }
+
+    async fn get(&self, endpoint: &'static str, path: &str) -> Result<StatusCode> {
+        let started = Instant::now();
+        let result = self
+            .client
+            .get(format!("{}{}", self.target, path))
+            .send()
+            .await;
+        let ok = matches!(&result, Ok(r) if r.status().is_success());
+        self.recorder.record(endpoint, started.elapsed(), ok);
+        Ok(result?.status())
+    }
+
+    /// Play one synthetic player: create, start, choose, and reset when a
+    /// loop offers nothing more to choose
+    async fn play(&self, choices: usize) -> Result<Outcome> {
+        let (status, body) = self.post("new", "/api/game/new", json!({})).await?;
+        if status == StatusCode::ACCEPTED {
+            return Ok(Outcome::Waiting);
+        }
+        let id = body
+            .pointer("/player/id")
+            .and_then(Value::as_str)
+            .with_context(|| format!("new game returned {}", status))?
+            .to_string();
+
+        let (_, mut narrative) = self
+            .post("start", &format!("/api/game/{}/start", id), json!({}))
+            .await?;
+        for _ in 0..choices {
+            let moment = narrative.get("moment").cloned().unwrap_or(Value::Null);
+            let Some(choice) = pick_choice(&moment) else {
+                let (status, _) = self
+                    .post("reset", &format!("/api/game/{}/reset", id), json!({}))
+                    .await?;
+                if status == StatusCode::CONFLICT {
+                    break;
+                }
+                (_, narrative) = self
+                    .post("start", &format!("/api/game/{}/start", id), json!({}))
+                    .await?;
+                continue;
+            };
+            let request = json!({
+                "choice_id": choice.get("id"),
+                "choice_text": choice.get("text"),
+                "moment_id": moment.get("id"),
+            });
+            let (status, body) = self
+                .post("choice", &format!("/api/game/{}/choice", id), request)
+                .await?;
+            if status == StatusCode::CONFLICT {
+                // The run is over
+                break;
+            }
+            narrative = body;
+        }
+
+        self.get("state", &format!("/api/game/{}", id)).await?;
+        Ok(Outcome::Completed)
+    }
+}
+
+/// Gauges read from the server's metrics while the test runs
+#[derive(Default)]
+struct Sampler {
+    llm_in_flight: GaugeReport,
+    waiting_room: GaugeReport,
+}
+
+impl GaugeReport {
+    fn add(&mut self, value: f64) {
+        self.mean = (self.mean * self.samples as f64 + value) / (self.samples + 1) as f64;
+        self.samples += 1;
+        self.max = self.max.max(value);
+    }
+}
+
+fn gauge(metrics: &str, name: &str) -> Option<f64> {
+    metrics.lines().find_map(|line| {
+        line.strip_prefix(name)?
+            .strip_prefix(' ')?
+            .trim()
+            .parse()
+            .ok()
+    })
+}
+
+/// Sample the LLM queue and waiting room until told to stop. Needs the
+/// server's admin token; without one the report leaves the gauges out.
+async fn sample(
+    client: Client,
+    target: String,
+    token: String,
+    stop: Arc<tokio::sync::Notify>,
+) -> Option<Sampler> {
+    let mut sampler = Sampler::default();
+    let mut ticker = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = stop.notified() => break,
+            _ = ticker.tick() => {}
+        }
+        let response = client
+            .get(format!("{}/metrics", target))
+            .bearer_auth(&token)
+            .send()
+            .await;
+        let metrics = match response {
+            Ok(r) if r.status().is_success() => r.text().await.unwrap_or_default(),
+            Ok(r) => {
+                tracing::warn!(
+                    "Metrics refused with {}; not sampling queue depths",
+                    r.status()
+                );
+                return None;
+            }
+            Err(e) => {
+                tracing::debug!("Failed to sample metrics: {}", e);
+                continue;
+            }
+        };
+        if let Some(value) = gauge(&metrics, "nihilism_llm_requests_in_flight") {
+            sampler.llm_in_flight.add(value);
+        }
+        if let Some(value) = gauge(&metrics, "nihilism_waiting_room_size") {
+            sampler.waiting_room.add(value);
+        }
+    }
+    Some(sampler)
+}
+
+/// `nihilism loadtest <url> [--players N] [--choices N] [--ramp-up SECS]
+/// [--admin-token TOKEN] [--report FILE]`
+///
+/// Plays N synthetic players against a running server and reports latency
+/// percentiles per endpoint, with LLM queue depth and waiting room size
+/// sampled from `/metrics` when an admin token is given.
+pub async fn run(args: &[String]) -> Result<()> {
+    let options = Options::parse(args)?;
+    let client = Client::new();
+    let recorder = Recorder::default();
+    tracing::info!(
+        "Load testing {} with {} players, {} choices each",
+        options.target,
+        options.players,
+        options.choices
+    );
+
+    let stop = Arc::new(tokio::sync::Notify::new());
+    let sampler = options.admin_token.clone().map(|token| {
+        tokio::spawn(sample(
+            client.clone(),
+            options.target.clone(),
+            token,
+            stop.clone(),
+        ))
+    });
+
+    let started = Instant::now();
+    let stagger = options.ramp_up / options.players as u32;
+    let mut players = Vec::with_capacity(options.players);
+    for i in 0..options.players {
+        let session = Session {
+            client: client.clone(),
+            target: options.target.clone(),
+            recorder: recorder.clone(),
+        };
+        let choices = options.choices;
+        players.push(tokio::spawn(async move {
+            tokio::time::sleep(stagger * i as u32).await;
+            match session.play(choices).await {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    tracing::warn!("Synthetic player {} failed: {}", i, e);
+                    Outcome::Failed
+                }
+            }
+        }));
+    }
+    let mut completed_players = 0;
+    let mut waiting_players = 0;
+    for player in players {
+        match player.await? {
+            Outcome::Completed => completed_players += 1,
+            Outcome::Waiting => waiting_players += 1,
+            Outcome::Failed => {}
+        }
+    }
+    let duration = started.elapsed();
+
+    stop.notify_one();
+    let sampled = match sampler {
+        Some(handle) => handle.await?,
+        None => None,
+    };
+
+    let timings = std::mem::take(&mut *recorder.0.lock().unwrap_or_else(|e| e.into_inner()));
+    let endpoints = timings
+        .into_iter()
+        .map(|(endpoint, mut timings)| {
+            timings.latencies.sort();
+            EndpointReport {
+                endpoint,
+                requests: timings.latencies.len(),
+                errors: timings.errors,
+                p50_ms: percentile(&timings.latencies, 50.0),
+                p99_ms: percentile(&timings.latencies, 99.0),
+                max_ms: percentile(&timings.latencies, 100.0),
+            }
+        })
+        .collect();
+    let (llm_in_flight, waiting_room) = match sampled {
+        Some(s) => (Some(s.llm_in_flight), Some(s.waiting_room)),
+        None => (None, None),
+    };
+    let report_path = options.report;
+    let report = LoadReport {
+        target: options.target,
+        players: options.players,
+        choices_per_player: options.choices,
+        duration_secs: duration.as_secs_f64(),
+        completed_players,
+        waiting_players,
+        endpoints,
+        llm_in_flight,
+        waiting_room,
+    };
+
+    tracing::info!(
+        "Done in {:.1}s: {} players completed, {} waiting, {} failed",
+        report.duration_secs,
+        report.completed_players,
+        report.waiting_players,
+        report.players - report.completed_players - report.waiting_players
+    );
+    for line in &report.endpoints {
+        tracing::info!(
+            "{:<8} {:>6} requests {:>4} errors  p50 {:>8.1}ms  p99 {:>8.1}ms  max {:>8.1}ms",
+            line.endpoint,
+            line.requests,
+            line.errors,
+            line.p50_ms,
+            line.p99_ms,
+            line.max_ms
+        );
+    }
+    if let Some(gauge) = &report.llm_in_flight {
+        tracing::info!(
+            "LLM requests in flight: max {}, mean {:.1}",
+            gauge.max,
+            gauge.mean
+        );
+    }
+
+    if let Some(path) = &report_path {
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("failed to write report {}", path))?;
+        tracing::info!("Report written to {}", path);
+    }
+    Ok(())
+}
@@ -1,41 +1,642 @@
+mod abuse;
+mod accounts;
+mod analytics;
+mod anchors;
+mod assets;
+mod audit;
+mod backup;
+mod build_info;
+mod cancel;
+mod card;
+mod challenge;
+mod clusters;
+mod coalesce;
+mod compare;
+mod conditions;
 mod config;
+mod consequences;
+mod context;
+mod decay;
+mod diff;
+mod digest;
+mod dialogue;
 mod endings;
+mod epilogue;
+mod events;
+mod export;
+mod fate;
+mod flags;
 mod game;
+mod gameplay;
+mod generation;
+mod graph;
+mod handoff;
+mod i18n;
+mod janitor;
+mod journal;
 mod llm;
+mod loadtest;
+mod moderation;
+mod outbound;
+mod patch;
+mod offline;
 mod persistence;
+mod persona;
+mod plugins;
+mod presence;
+mod privacy;
+mod provenance;
+mod push;
+mod race;
+mod rarity;
+mod redaction;
+mod repetition;
+mod rerank;
+mod retention;
+mod reveal;
+mod sanitize;
 mod routes;
+mod scheduler;
+mod scoring;
+mod seal;
+mod spoilers;
+mod stability;
+mod status;
+mod suggest;
+mod tenant;
+mod tension;
+#[cfg(any(test, feature = "testing"))]
+#[allow(dead_code)] // not every fixture is used in every build
+mod testing;
+mod texture;
+mod theme;
+mod thinking;
+mod throttle;
+mod titles;
+mod upstream;
+mod usage;
+mod waiting;
+mod warmup;
+mod world;
+mod ws;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::config::Config;
+use crate::config::{Config, LogFormat, Preflight, StorageBackend};
+use crate::events::GameEvent;
 use crate::game::GameState;
+use crate::janitor::Janitor;
+use crate::llm::LlmClient;
+use crate::rarity::EndingStats;
+use crate::routes::AppState;
+use crate::scoring::Ensemble;
+use crate::tenant::TenantConfigs;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing
+    let json = LogFormat::from_env() == LogFormat::Json;
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "nihilism=debug,tower_http=debug".into()),
+                .unwrap_or_else(|_| "nihilism=debug,tower_http=debug,gameplay=info".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with((!json).then(tracing_subscriber::fmt::layer))
+        .with(json.then(|| {
+            tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_current_span(false)
+                .with_span_list(false)
+        }))
+        .with(digest::ErrorTally)
         .init();
 
     let config = Config::from_env();
-    tracing::info!("Starting Nihilism game server...");
-    tracing::info!("LLM API Base URL: {}", config.llm_base_url);
 
-    let game_state = Arc::new(RwLock::new(GameState::new()));
-    let app = routes::create_router(config.clone(), game_state);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("migrate") => return run_migrate(&config, &args[1..]),
+        Some("loadtest") => return loadtest::run(&args[1..]).await,
+        _ => {}
+    }
+
+    tracing::info!(
+        "Starting Nihilism game server {}...",
+        build_info::current().describe()
+    );
+    tracing::info!("LLM API Base URL: {}", config.llm_base_urls.join(", "));
+    tracing::info!("Content rating: {:?}", config.content_rating);
+
+    let tenants = TenantConfigs::load(&config)?;
+    persistence::init(&config)?;
+    // Plugins register endings, so they load first
+    plugins::init(&config)?;
+    endings::init(&tenants)?;
+    anchors::init(&tenants)?;
+    backup::init(&config)?;
+    seal::init(&config)?;
+    theme::init(&tenants)?;
+    journal::recover();
+    privacy::init(&config)?;
+
+    let janitor = Arc::new(Janitor::new());
+    let state = start_state(&tenants.default, janitor.clone()).await?;
+    register_shared_jobs(&state).await;
+
+    let mut tenant_states = Vec::new();
+    let mut tenant_routers = HashMap::new();
+    for (id, config) in &tenants.tenants {
+        let tenant_state = start_state(config, janitor.clone()).await?;
+        tenant_routers.insert(id.clone(), routes::create_router(tenant_state.clone()));
+        tenant_states.push(tenant_state);
+    }
+    let app = if tenant_routers.is_empty() {
+        routes::create_router(state.clone())
+    } else {
+        tenant::router(routes::create_router(state.clone()), tenant_routers)
+    };
 
     let addr = format!("{}:{}", config.host, config.port);
     tracing::info!("Server listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    tracing::info!("Shutting down...");
+    for state in std::iter::once(&state).chain(&tenant_states) {
+        state.scheduler.shutdown().await;
+        if let Err(e) = state.llm.usage().flush() {
+            tracing::warn!("Failed to flush usage ledger: {}", e);
+        }
+    }
 
     Ok(())
 }
+
+/// Build the state of the default deployment or of one tenant, with its
+/// subscribers and jobs running
+async fn start_state(config: &Config, janitor: Arc<Janitor>) -> Result<AppState> {
+    let game_state = Arc::new(RwLock::new(GameState::new(config.tenant.clone())));
+    janitor.watch(game_state.clone());
+    let llm = Arc::new(LlmClient::new(config.clone())?);
+
+    match config.llm_preflight {
+        Preflight::Off => {}
+        Preflight::Warn => {
+            if let Err(e) = llm.preflight().await {
+                tracing::error!("LLM preflight failed: {:#}", e);
+            }
+        }
+        Preflight::Fail => llm
+            .preflight()
+            .await
+            .context("LLM preflight failed; set LLM_PREFLIGHT=warn to start anyway")?,
+    }
+
+    if config.llm_probe_capabilities {
+        let llm = llm.clone();
+        tokio::spawn(async move {
+            llm.probe_capabilities().await;
+        });
+    }
+
+    let ending_stats = Arc::new(EndingStats::open(config)?);
+    let scoring = Arc::new(Ensemble::from_config(config, llm.clone())?);
+    tracing::info!("Scoring choices with {}", scoring.describe());
+    let state = AppState::new(
+        config.clone(),
+        game_state,
+        llm,
+        janitor,
+        ending_stats,
+        scoring,
+    )?;
+    register_subscribers(&state);
+    register_jobs(&state).await;
+    Ok(state)
+}
+
+/// `nihilism migrate <from> <to> [--verify-only]`
+///
+/// Bulk-copies every player between storage backends and verifies each copy
+/// by checksum. Run it while the server is in dual-write mode to switch
+/// backends without downtime.
+fn run_migrate(config: &Config, args: &[String]) -> Result<()> {
+    let verify_only = args.iter().any(|a| a == "--verify-only");
+    let backends: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let [from, to] = backends.as_slice() else {
+        anyhow::bail!("usage: nihilism migrate <file|sqlite> <file|sqlite> [--verify-only]");
+    };
+
+    let parse = |name: &str| {
+        StorageBackend::parse(name).ok_or_else(|| anyhow::anyhow!("unknown backend '{}'", name))
+    };
+    let (from, to) = (parse(from)?, parse(to)?);
+    if from == to {
+        anyhow::bail!("source and target backends are the same");
+    }
+
+    let source = persistence::open_backend(from, config)?;
+    let target = persistence::open_backend(to, config)?;
+    tracing::info!(
+        "{} players from {} to {}",
+        if verify_only { "Verifying" } else { "Migrating" },
+        source.name(),
+        target.name()
+    );
+
+    let report = persistence::migrate(source.as_ref(), target.as_ref(), verify_only)?;
+    tracing::info!(
+        "Done: {} players, {} copied, {} verified, {} mismatched, {} failed",
+        report.total,
+        report.copied,
+        report.verified,
+        report.mismatched.len(),
+        report.failed.len()
+    );
+    for id in &report.mismatched {
+        tracing::error!("Checksum mismatch for player {}", id);
+    }
+    for (id, error) in &report.failed {
+        tracing::error!("Failed to migrate player {}: {}", id, error);
+    }
+
+    if !report.mismatched.is_empty() || !report.failed.is_empty() {
+        anyhow::bail!("migration incomplete");
+    }
+    Ok(())
+}
+
+/// Attach the subsystems that react to game events
+fn register_subscribers(state: &AppState) {
+    challenge::subscribe(&state.events, state.game.clone());
+    consequences::subscribe(&state.events);
+    gameplay::subscribe(&state.events, state.game.clone());
+    plugins::subscribe(&state.events);
+    state.event_counters.subscribe(&state.events);
+    state.digest.subscribe(&state.events, state.game.clone());
+    state.races.subscribe(&state.events);
+    titles::subscribe(&state.events, state.game.clone(), state.llm.clone());
+    if state.config.embedding_model.is_some() {
+        state.choice_clusters.subscribe(&state.events, state.game.clone());
+    }
+}
+
+/// Register the periodic maintenance jobs
+async fn register_jobs(state: &AppState) {
+    let game = state.game.clone();
+    state
+        .scheduler
+        .register("autosave_sweep", "5m", move || {
+            let game = game.clone();
+            async move {
+                let players: Vec<_> = game.read().await.players.values().cloned().collect();
+                let mut failed = 0;
+                for player in &players {
+                    if let Err(e) = persistence::save_player(player) {
+                        tracing::warn!("Autosave of player {} failed: {}", player.id, e);
+                        failed += 1;
+                    }
+                }
+                tracing::debug!(
+                    "Autosave sweep saved {} players, {} failed",
+                    players.len() - failed,
+                    failed
+                );
+                if failed > 0 {
+                    anyhow::bail!("{} of {} players failed to save", failed, players.len());
+                }
+                Ok(())
+            }
+        })
+        .await;
+
+    let usage = state.llm.usage().clone();
+    state
+        .scheduler
+        .register("usage_flush", "1m", move || {
+            let usage = usage.clone();
+            async move { usage.flush() }
+        })
+        .await;
+
+    let game = state.game.clone();
+    let presence = state.presence.clone();
+    state
+        .scheduler
+        .register("presence_eviction", "10m", move || {
+            let game = game.clone();
+            let presence = presence.clone();
+            async move {
+                let current_loops: HashMap<_, _> = game
+                    .read()
+                    .await
+                    .players
+                    .iter()
+                    .map(|(id, p)| (*id, p.run.current_loop.number))
+                    .collect();
+                let evicted = presence.evict_stale(&current_loops).await;
+                tracing::debug!("Evicted {} stale presence lines", evicted);
+                Ok(())
+            }
+        })
+        .await;
+
+    let game = state.game.clone();
+    let cards = state.cards.clone();
+    state
+        .scheduler
+        .register("card_budget_eviction", "10m", move || {
+            let game = game.clone();
+            let cards = cards.clone();
+            async move {
+                let game = game.read().await;
+                let evicted = cards.evict(|id| game.players.contains_key(id));
+                tracing::debug!("Evicted share card budgets of {} players", evicted);
+                Ok(())
+            }
+        })
+        .await;
+
+    let game = state.game.clone();
+    let suggestions = state.suggestions.clone();
+    state
+        .scheduler
+        .register("suggestion_eviction", "10m", move || {
+            let game = game.clone();
+            let suggestions = suggestions.clone();
+            async move {
+                let game = game.read().await;
+                let evicted = suggestions.evict(|id| game.players.contains_key(id));
+                tracing::debug!("Evicted cached suggestions of {} players", evicted);
+                Ok(())
+            }
+        })
+        .await;
+
+    let game = state.game.clone();
+    let ws = state.ws.clone();
+    state
+        .scheduler
+        .register("ws_session_eviction", "10m", move || {
+            let game = game.clone();
+            let ws = ws.clone();
+            async move {
+                let game = game.read().await;
+                let evicted = ws.evict(|id| game.players.contains_key(id));
+                tracing::debug!("Evicted {} WebSocket sessions", evicted);
+                Ok(())
+            }
+        })
+        .await;
+
+    let races = state.races.clone();
+    state
+        .scheduler
+        .register("race_eviction", "10m", move || {
+            let races = races.clone();
+            async move {
+                let evicted = races.evict(chrono::Utc::now());
+                tracing::debug!("Evicted {} races", evicted);
+                Ok(())
+            }
+        })
+        .await;
+
+    if state.config.push_enabled {
+        let push = state.push.clone();
+        let game = state.game.clone();
+        let races = state.races.clone();
+        state
+            .scheduler
+            .register("push_notifications", "30s", move || {
+                let push = push.clone();
+                let game = game.clone();
+                let races = races.clone();
+                async move { push.send_due(&game, &races).await }
+            })
+            .await;
+    }
+
+    let app = state.clone();
+    state
+        .scheduler
+        .register("waiting_room_admission", "5s", move || {
+            let app = app.clone();
+            async move {
+                let admitted = routes::admit_waiting(&app).await;
+                if admitted > 0 {
+                    tracing::debug!("Admitted {} visitors from the waiting room", admitted);
+                }
+                Ok(())
+            }
+        })
+        .await;
+
+    let game = state.game.clone();
+    let texture = state.texture.clone();
+    let events = state.events.clone();
+    let active_window = state.config.active_window_minutes;
+    state
+        .scheduler
+        .register("texture_lines", "30s", move || {
+            let game = game.clone();
+            let texture = texture.clone();
+            let events = events.clone();
+            async move {
+                let active_since = chrono::Utc::now() - chrono::Duration::minutes(active_window);
+                let game = game.read().await;
+                texture.retain(&game.players);
+                for player in game.players.values() {
+                    if let Some((moment_id, text, tone)) = texture.next(player, active_since) {
+                        events.publish(GameEvent::Texture {
+                            player_id: player.id,
+                            moment_id,
+                            text,
+                            tone,
+                        });
+                    }
+                }
+                Ok(())
+            }
+        })
+        .await;
+
+    let llm = state.llm.clone();
+    let clusters = state.choice_clusters.clone();
+    let model = state.config.embedding_model.clone();
+    state
+        .scheduler
+        .register("choice_clustering", "15m", move || {
+            let llm = llm.clone();
+            let clusters = clusters.clone();
+            let model = model.clone();
+            async move {
+                let Some(model) = model else {
+                    return Ok(());
+                };
+                let pass = clusters.cluster(&llm, &model).await?;
+                tracing::debug!(
+                    "Choice clustering counted {} choices and embedded {} new texts ({} new buckets)",
+                    pass.counted,
+                    pass.embedded,
+                    pass.new_clusters
+                );
+                Ok(())
+            }
+        })
+        .await;
+
+    let config = state.config.clone();
+    let llm = state.llm.clone();
+    let game = state.game.clone();
+    let events = state.events.clone();
+    state
+        .scheduler
+        .register("budget_handoff", "5m", move || {
+            let config = config.clone();
+            let llm = llm.clone();
+            let game = game.clone();
+            let events = events.clone();
+            async move {
+                let handed = handoff::hand_over_for_budget(&config, &llm, &game, &events).await?;
+                if handed > 0 {
+                    tracing::info!("Handed {} runs to {:?} to save budget", handed, config.budget_handoff_model);
+                }
+                Ok(())
+            }
+        })
+        .await;
+
+    let config = state.config.clone();
+    let counters = state.digest.clone();
+    let usage = state.llm.usage().clone();
+    let sanitizer = state.sanitizer.clone();
+    state
+        .scheduler
+        .register("nightly_digest", "@hourly", move || {
+            let config = config.clone();
+            let counters = counters.clone();
+            let usage = usage.clone();
+            let sanitizer = sanitizer.clone();
+            async move {
+                if let Some(path) = digest::run(&config, &counters, &usage, &sanitizer).await? {
+                    tracing::info!("Wrote nightly digest to {}", path.display());
+                }
+                Ok(())
+            }
+        })
+        .await;
+
+    let llm = state.llm.clone();
+    let keep_loops = state.config.archive_keep_loops;
+    let tenant = state.config.tenant.clone();
+    let dry_run = state.config.archive_compaction_dry_run;
+    state
+        .scheduler
+        .register("archive_compaction", "@daily", move || {
+            let llm = llm.clone();
+            let tenant = tenant.clone();
+            async move {
+                let Some(keep_loops) = keep_loops else {
+                    return Ok(());
+                };
+                let report =
+                    retention::compact_archives(&llm, tenant.as_deref(), keep_loops, dry_run)
+                        .await?;
+                if report.dry_run {
+                    tracing::info!(
+                        "Archive compaction dry run: {} loops ({} bytes) would be compacted",
+                        report.candidates.len(),
+                        report.bytes_before
+                    );
+                } else {
+                    tracing::info!(
+                        "Compacted {} archived loops ({} -> {} bytes, {} failed)",
+                        report.compacted,
+                        report.bytes_before,
+                        report.bytes_after,
+                        report.failed
+                    );
+                }
+                Ok(())
+            }
+        })
+        .await;
+
+    let accounts = state.accounts.clone();
+    state
+        .scheduler
+        .register("account_session_eviction", "@hourly", move || {
+            let accounts = accounts.clone();
+            async move {
+                let evicted = accounts.evict_expired()?;
+                tracing::debug!("Evicted {} expired sessions and links", evicted);
+                Ok(())
+            }
+        })
+        .await;
+}
+
+/// Register the jobs that look after data every tenant shares, once for the
+/// whole deployment
+async fn register_shared_jobs(state: &AppState) {
+    let janitor = state.janitor.clone();
+    let dry_run = state.config.janitor_dry_run;
+    state
+        .scheduler
+        .register("janitor", "@daily", move || {
+            let janitor = janitor.clone();
+            async move {
+                let report = janitor.sweep(dry_run).await?;
+                if report.dry_run {
+                    tracing::info!(
+                        "Janitor dry run: {} orphaned entries ({} bytes)",
+                        report.orphans.len(),
+                        report.orphan_bytes
+                    );
+                } else {
+                    tracing::info!(
+                        "Janitor deleted {} orphaned entries, reclaimed {} bytes ({} failed)",
+                        report.deleted,
+                        report.reclaimed_bytes,
+                        report.failed
+                    );
+                }
+                Ok(())
+            }
+        })
+        .await;
+}
+
+/// Resolve on Ctrl+C or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
@@ -0,0 +1,73 @@
+use crate::config::{Config, ContentRating};
+
+/// Terms that are never allowed on a moderated instance
+const ALWAYS_BLOCKED: &[&str] = &["rape", "child abuse", "molest"];
+
+/// Additional terms blocked on teen-rated instances
+const TEEN_BLOCKED: &[&str] = &[
+    "suicide",
+    "kill yourself",
+    "kill myself",
+    "self-harm",
+    "self harm",
+    "cut myself",
+    "overdose",
+    "gore",
+    "disembowel",
+    "dismember",
+    "torture",
+    "naked",
+    "sex",
+    "cocaine",
+    "heroin",
+];
+
+/// Outcome of a moderation check
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Allowed,
+    Flagged(Vec<&'static str>),
+}
+
+impl Verdict {
+    pub fn is_flagged(&self) -> bool {
+        matches!(self, Verdict::Flagged(_))
+    }
+}
+
+/// Check a piece of text against the instance's moderation rules.
+///
+/// Always returns `Allowed` when moderation is inactive.
+pub fn check(config: &Config, text: &str) -> Verdict {
+    if !config.moderation_active() {
+        return Verdict::Allowed;
+    }
+
+    let lower = text.to_lowercase();
+    let extra: &[&str] = match config.content_rating {
+        ContentRating::Teen => TEEN_BLOCKED,
+        ContentRating::Mature => &[],
+    };
+
+    let matched: Vec<&'static str> = ALWAYS_BLOCKED
+        .iter()
+        .chain(extra.iter())
+        .copied()
+        .filter(|term| contains_term(&lower, term))
+        .collect();
+
+    if matched.is_empty() {
+        Verdict::Allowed
+    } else {
+        Verdict::Flagged(matched)
+    }
+}
+
+/// Match a term on word boundaries so "sex" doesn't flag "Essex"
+fn contains_term(haystack: &str, term: &str) -> bool {
+    haystack.match_indices(term).any(|(start, _)| {
+        let before = haystack[..start].chars().next_back();
+        let after = haystack[start + term.len()..].chars().next();
+        !before.is_some_and(|c| c.is_alphanumeric()) && !after.is_some_and(|c| c.is_alphanumeric())
+    })
+}
@@ -0,0 +1,101 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::game::{Choice, MomentState, NarrativeMoment, Player};
+use crate::provenance::Provenance;
+
+/// A scripted moment: text, mood and (choice id, choice text) pairs
+struct PackMoment {
+    text: &'static str,
+    mood: &'static str,
+    choices: &'static [(&'static str, &'static str)],
+}
+
+/// Moments served without the LLM, in rotation
+const PACK: &[PackMoment] = &[
+    PackMoment {
+        text: "You wake in the same room. The light through the blinds falls in the same stripes \
+               across the same floor. Somewhere below, a kettle begins to whistle.",
+        mood: "neutral",
+        choices: &[
+            ("go_downstairs", "Go downstairs"),
+            ("stay_in_bed", "Stay in bed and listen"),
+        ],
+    },
+    PackMoment {
+        text: "The street outside is exactly as busy as it was yesterday. A man drops his \
+               groceries at the corner, as he always does. Oranges roll toward the gutter.",
+        mood: "neutral",
+        choices: &[
+            ("help_oranges", "Help him gather the oranges"),
+            ("walk_away", "Walk away"),
+        ],
+    },
+    PackMoment {
+        text: "A café you have never noticed is open today. The waitress smiles as though she \
+               has been expecting you for a very long time.",
+        mood: "hopeful",
+        choices: &[
+            ("sit_down", "Sit down and order"),
+            ("ignore_cafe", "Ignore her and keep walking"),
+        ],
+    },
+    PackMoment {
+        text: "The clock on the church tower has stopped at a minute to midnight. Nobody else \
+               seems to notice. The pigeons do not land on it.",
+        mood: "dark",
+        choices: &[
+            ("climb_tower", "Climb the tower"),
+            ("nothing_matters", "Shrug. Nothing matters anyway"),
+        ],
+    },
+    PackMoment {
+        text: "On a park bench someone has left a notebook. The handwriting inside is yours, \
+               describing a day you have not lived yet.",
+        mood: "transcendent",
+        choices: &[
+            ("read_notebook", "Read to the last page"),
+            ("leave_notebook", "Leave it where it lies"),
+        ],
+    },
+    PackMoment {
+        text: "Rain starts without warning. A child under the bus shelter is crying, and the \
+               bus that should come never does.",
+        mood: "dark",
+        choices: &[
+            ("comfort_child", "Sit with the child"),
+            ("leave_them", "Leave them to it"),
+        ],
+    },
+];
+
+/// Next scripted moment for a player, without spending an LLM call
+pub fn moment(player: &Player) -> NarrativeMoment {
+    let played = player.run.narrative_history.len() + player.run.current_loop.number as usize;
+    let scripted = &PACK[played % PACK.len()];
+    NarrativeMoment {
+        id: Uuid::new_v4(),
+        text: scripted.text.to_string(),
+        speaker: None,
+        mood: scripted.mood.to_string(),
+        choices: scripted
+            .choices
+            .iter()
+            .map(|(id, text)| Choice {
+                id: id.to_string(),
+                text: text.to_string(),
+                consequence_hint: None,
+                provenance: None,
+            })
+            .collect(),
+        timestamp: Utc::now(),
+        summarized: false,
+        world_updates: Vec::new(),
+        state: MomentState::Generated,
+        translation: None,
+        deja_vu: None,
+        fate: None,
+        assets: None,
+        provenance: Some(Provenance::Offline),
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Certificate, Identity, Proxy, Url};
+use sha2::{Digest, Sha256};
+use std::fs;
+
+use crate::config::{Config, SigV4Config};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HTTP client for the LLM backend, with the configured proxy, client
+/// certificate and extra CA
+pub fn http_client(config: &Config) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = &config.llm_proxy {
+        builder = builder.proxy(Proxy::all(proxy).context("invalid LLM_PROXY")?);
+    }
+    match (&config.llm_client_cert, &config.llm_client_key) {
+        (Some(cert), Some(key)) => {
+            let cert = fs::read(cert).with_context(|| format!("failed to read {}", cert))?;
+            let key = fs::read(key).with_context(|| format!("failed to read {}", key))?;
+            let identity = Identity::from_pkcs8_pem(&cert, &key)
+                .context("invalid LLM client certificate or key")?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => anyhow::bail!("LLM_CLIENT_CERT and LLM_CLIENT_KEY must be set together"),
+    }
+    if let Some(ca) = &config.llm_ca_cert {
+        let pem = fs::read(ca).with_context(|| format!("failed to read {}", ca))?;
+        builder = builder.add_root_certificate(
+            Certificate::from_pem(&pem).context("invalid LLM_CA_CERT")?,
+        );
+    }
+    Ok(builder.build()?)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode everything but unreserved characters (and `/` in paths)
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            b'/' if keep_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// Headers that sign a request with AWS Signature Version 4: `x-amz-date`,
+/// `x-amz-security-token` with temporary credentials, and `authorization`.
+///
+/// `headers` are the request's own headers to include in the signature; the
+/// host is always signed.
+pub fn sigv4_headers(
+    sigv4: &SigV4Config,
+    method: &str,
+    url: &Url,
+    headers: &[(String, String)],
+    body: &[u8],
+    now: DateTime<Utc>,
+) -> Vec<(String, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+
+    let mut host = url.host_str().unwrap_or_default().to_string();
+    if let Some(port) = url.port() {
+        host.push_str(&format!(":{}", port));
+    }
+    let mut signed: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+    signed.push(("host".to_string(), host));
+    signed.push(("x-amz-date".to_string(), amz_date.clone()));
+    if let Some(token) = &sigv4.session_token {
+        signed.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    signed.sort();
+
+    let canonical_headers: String = signed
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = signed
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (uri_encode(&k, false), uri_encode(&v, false)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&");
+    // The path is already percent-encoded once; services other than S3
+    // expect it encoded again
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        uri_encode(url.path(), true),
+        canonical_query,
+        canonical_headers,
+        signed_headers,
+        hex(&Sha256::digest(body))
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date, sigv4.region, sigv4.service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = hmac(format!("AWS4{}", sigv4.secret_access_key).as_bytes(), &date);
+    let key = hmac(&key, &sigv4.region);
+    let key = hmac(&key, &sigv4.service);
+    let key = hmac(&key, "aws4_request");
+    let signature = hex(&hmac(&key, &string_to_sign));
+
+    let mut out = vec![("x-amz-date".to_string(), amz_date)];
+    if let Some(token) = &sigv4.session_token {
+        out.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    out.push((
+        "authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            sigv4.access_key_id, scope, signed_headers, signature
+        ),
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests;
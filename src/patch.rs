@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+
+use crate::game::Player;
+use crate::i18n::Locale;
+
+/// Longest display name kept
+const MAX_NAME_CHARS: usize = 40;
+const MAX_NOTES_CHARS: usize = 2000;
+const MAX_PREFERENCES: usize = 32;
+const MAX_PREFERENCE_KEY_LEN: usize = 40;
+const MAX_PREFERENCE_VALUE_LEN: usize = 200;
+const MAX_TIMEZONE_LEN: usize = 64;
+/// Operations accepted in one patch
+const MAX_OPERATIONS: usize = 64;
+
+/// Fields a player may set for themselves, outside the game
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSettings {
+    /// Client preferences such as text speed or sound, as flat scalar values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub preferences: BTreeMap<String, Value>,
+    /// Preferred language, for clients choosing an `Accept-Language`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Locale>,
+    /// IANA time zone name, e.g. `Europe/Warsaw`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Private notes, never shown to the narrator
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl PlayerSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// One RFC 6902 operation
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PatchError {
+    #[error("{0} can't be patched")]
+    PathNotAllowed(String),
+    #[error("nothing at {0}")]
+    Missing(String),
+    #[error("test failed at {0}")]
+    TestFailed(String),
+    #[error("invalid {0}: {1}")]
+    Invalid(&'static str, String),
+    #[error("too many operations")]
+    TooLarge,
+}
+
+/// Paths open to patching: the fields themselves, and single preferences
+fn allowed(path: &str) -> bool {
+    match path {
+        "/name" | "/preferences" | "/language" | "/timezone" | "/notes" => true,
+        _ => path
+            .strip_prefix("/preferences/")
+            .is_some_and(|key| !key.is_empty() && !key.contains('/')),
+    }
+}
+
+/// Decode a JSON Pointer into its reference tokens
+fn tokens(path: &str) -> Vec<String> {
+    path.split('/')
+        .skip(1)
+        .map(|t| t.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+/// The object holding the pointer's last token, and that token
+fn parent<'a>(
+    doc: &'a mut Value,
+    path: &str,
+) -> Result<(&'a mut Map<String, Value>, String), PatchError> {
+    let mut tokens = tokens(path);
+    let last = tokens
+        .pop()
+        .ok_or_else(|| PatchError::Missing(path.to_string()))?;
+    let mut target = doc;
+    for token in tokens {
+        target = target
+            .get_mut(&token)
+            .ok_or_else(|| PatchError::Missing(path.to_string()))?;
+    }
+    match target {
+        Value::Object(map) => Ok((map, last)),
+        _ => Err(PatchError::Missing(path.to_string())),
+    }
+}
+
+fn get(doc: &Value, path: &str) -> Option<Value> {
+    tokens(path)
+        .iter()
+        .try_fold(doc, |value, token| value.get(token))
+        .cloned()
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), PatchError> {
+    let (map, key) = parent(doc, path)?;
+    map.insert(key, value);
+    Ok(())
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, PatchError> {
+    let (map, key) = parent(doc, path)?;
+    map.remove(&key)
+        .ok_or_else(|| PatchError::Missing(path.to_string()))
+}
+
+fn apply_operation(doc: &mut Value, operation: Operation) -> Result<(), PatchError> {
+    match operation {
+        Operation::Add { path, value } => add(doc, &path, value),
+        Operation::Remove { path } => remove(doc, &path).map(|_| ()),
+        Operation::Replace { path, value } => {
+            remove(doc, &path)?;
+            add(doc, &path, value)
+        }
+        Operation::Move { from, path } => {
+            let value = remove(doc, &from)?;
+            add(doc, &path, value)
+        }
+        Operation::Copy { from, path } => {
+            let value = get(doc, &from).ok_or(PatchError::Missing(from))?;
+            add(doc, &path, value)
+        }
+        Operation::Test { path, value } => match get(doc, &path) {
+            Some(current) if current == value => Ok(()),
+            _ => Err(PatchError::TestFailed(path)),
+        },
+    }
+}
+
+fn check_paths(operation: &Operation) -> Result<(), PatchError> {
+    let (path, from) = match operation {
+        Operation::Add { path, .. }
+        | Operation::Remove { path }
+        | Operation::Replace { path, .. }
+        | Operation::Test { path, .. } => (path, None),
+        Operation::Move { from, path } | Operation::Copy { from, path } => (path, Some(from)),
+    };
+    for path in std::iter::once(path).chain(from) {
+        if !allowed(path) {
+            return Err(PatchError::PathNotAllowed(path.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// The patchable fields of a player, as a JSON document. Unset fields are
+/// present as `null`, so they can be replaced or tested like any other.
+fn document(player: &Player) -> Value {
+    let settings = &player.settings;
+    json!({
+        "name": player.name,
+        "preferences": settings.preferences,
+        "language": settings.language,
+        "timezone": settings.timezone,
+        "notes": settings.notes,
+    })
+}
+
+fn validate_preferences(preferences: &BTreeMap<String, Value>) -> Result<(), PatchError> {
+    if preferences.len() > MAX_PREFERENCES {
+        return Err(PatchError::Invalid(
+            "preferences",
+            format!("at most {} are kept", MAX_PREFERENCES),
+        ));
+    }
+    for (key, value) in preferences {
+        if key.len() > MAX_PREFERENCE_KEY_LEN {
+            return Err(PatchError::Invalid(
+                "preferences",
+                format!("key {} is too long", key),
+            ));
+        }
+        let ok = match value {
+            Value::Bool(_) | Value::Number(_) | Value::Null => true,
+            Value::String(s) => s.len() <= MAX_PREFERENCE_VALUE_LEN,
+            Value::Array(_) | Value::Object(_) => false,
+        };
+        if !ok {
+            return Err(PatchError::Invalid(
+                "preferences",
+                format!("{} must be a short string, number or boolean", key),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_timezone(timezone: &str) -> Result<(), PatchError> {
+    let valid = timezone == "UTC"
+        || (timezone.len() <= MAX_TIMEZONE_LEN
+            && timezone.contains('/')
+            && timezone.split('/').all(|part| {
+                !part.is_empty()
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "_+-".contains(c))
+            }));
+    if valid {
+        Ok(())
+    } else {
+        Err(PatchError::Invalid(
+            "timezone",
+            format!("{} is not an IANA time zone", timezone),
+        ))
+    }
+}
+
+/// Apply a JSON Patch to a player's name and settings. Either every
+/// operation applies and the result validates, or the player is unchanged.
+pub fn apply(player: &mut Player, operations: Vec<Operation>) -> Result<(), PatchError> {
+    if operations.len() > MAX_OPERATIONS {
+        return Err(PatchError::TooLarge);
+    }
+    for operation in &operations {
+        check_paths(operation)?;
+    }
+    let mut doc = document(player);
+    for operation in operations {
+        apply_operation(&mut doc, operation)?;
+    }
+
+    let Value::Object(mut map) = doc else {
+        return Err(PatchError::Invalid("document", "not an object".to_string()));
+    };
+    let name = match map.remove("name") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(name)) => {
+            let name = name.trim();
+            (!name.is_empty()).then(|| name.chars().take(MAX_NAME_CHARS).collect())
+        }
+        Some(other) => {
+            return Err(PatchError::Invalid(
+                "name",
+                format!("{} is not a string", other),
+            ));
+        }
+    };
+    let settings: PlayerSettings = serde_json::from_value(Value::Object(map))
+        .map_err(|e| PatchError::Invalid("settings", e.to_string()))?;
+    validate_preferences(&settings.preferences)?;
+    if let Some(timezone) = &settings.timezone {
+        validate_timezone(timezone)?;
+    }
+    if let Some(notes) = &settings.notes
+        && notes.chars().count() > MAX_NOTES_CHARS
+    {
+        return Err(PatchError::Invalid(
+            "notes",
+            format!("at most {} characters", MAX_NOTES_CHARS),
+        ));
+    }
+
+    player.name = name;
+    player.settings = settings;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
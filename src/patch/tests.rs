@@ -0,0 +1,93 @@
+use serde_json::json;
+
+use super::*;
+
+fn operations(value: Value) -> Vec<Operation> {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn applies_whitelisted_operations() {
+    let mut player = Player::new();
+    let patch = operations(json!([
+        { "op": "add", "path": "/name", "value": "  Sisyphus  " },
+        { "op": "add", "path": "/preferences/text_speed", "value": 2 },
+        { "op": "add", "path": "/preferences/sound", "value": false },
+        { "op": "replace", "path": "/language", "value": "pl" },
+        { "op": "add", "path": "/timezone", "value": "Europe/Warsaw" },
+        { "op": "copy", "from": "/timezone", "path": "/notes" },
+        { "op": "remove", "path": "/preferences/sound" },
+        { "op": "test", "path": "/preferences/text_speed", "value": 2 },
+    ]));
+
+    apply(&mut player, patch).unwrap();
+
+    assert_eq!(player.name.as_deref(), Some("Sisyphus"));
+    assert_eq!(
+        player.settings.preferences.get("text_speed"),
+        Some(&json!(2))
+    );
+    assert!(!player.settings.preferences.contains_key("sound"));
+    assert_eq!(player.settings.language, Some(Locale::Pl));
+    assert_eq!(player.settings.timezone.as_deref(), Some("Europe/Warsaw"));
+    assert_eq!(player.settings.notes.as_deref(), Some("Europe/Warsaw"));
+}
+
+#[test]
+fn refuses_paths_outside_the_whitelist() {
+    let mut player = Player::new();
+    for path in ["/id", "/current_loop/stability", "/preferences/a/b", ""] {
+        let patch = operations(json!([{ "op": "add", "path": path, "value": 1 }]));
+        assert!(matches!(
+            apply(&mut player, patch),
+            Err(PatchError::PathNotAllowed(_))
+        ));
+    }
+    let patch = operations(json!([{ "op": "move", "from": "/id", "path": "/notes" }]));
+    assert!(matches!(
+        apply(&mut player, patch),
+        Err(PatchError::PathNotAllowed(_))
+    ));
+}
+
+#[test]
+fn failed_patch_leaves_player_unchanged() {
+    let mut player = Player::new();
+    player.name = Some("Before".to_string());
+    let patch = operations(json!([
+        { "op": "replace", "path": "/name", "value": "After" },
+        { "op": "add", "path": "/timezone", "value": "not a zone" },
+    ]));
+
+    assert!(matches!(
+        apply(&mut player, patch),
+        Err(PatchError::Invalid("timezone", _))
+    ));
+    assert_eq!(player.name.as_deref(), Some("Before"));
+    assert!(player.settings.is_empty());
+
+    let patch = operations(json!([
+        { "op": "replace", "path": "/name", "value": "After" },
+        { "op": "test", "path": "/name", "value": "Before" },
+    ]));
+    assert!(matches!(
+        apply(&mut player, patch),
+        Err(PatchError::TestFailed(_))
+    ));
+    assert_eq!(player.name.as_deref(), Some("Before"));
+}
+
+#[test]
+fn validates_each_path() {
+    let mut player = Player::new();
+    for patch in [
+        json!([{ "op": "add", "path": "/language", "value": "klingon" }]),
+        json!([{ "op": "add", "path": "/preferences/theme", "value": { "nested": true } }]),
+        json!([{ "op": "add", "path": "/notes", "value": "x".repeat(MAX_NOTES_CHARS + 1) }]),
+        json!([{ "op": "add", "path": "/name", "value": 7 }]),
+        json!([{ "op": "remove", "path": "/preferences/missing" }]),
+    ] {
+        assert!(apply(&mut player, operations(patch)).is_err());
+    }
+    assert!(player.settings.is_empty());
+}
@@ -1,82 +1,580 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
 use uuid::Uuid;
 
-use crate::game::Player;
+use crate::build_info;
+use crate::config::{Config, SaveFormat, StorageBackend};
+use crate::epilogue::Epilogue;
+use crate::game::{ArchivedLoop, NarrativeMoment, Player};
 
-const DATA_DIR: &str = "data/players";
+pub const DATA_DIR: &str = "data/players";
+pub const ARCHIVE_DIR: &str = "data/archives";
 
-/// Ensures the data directory exists
-fn ensure_data_dir() -> Result<PathBuf> {
-    let path = PathBuf::from(DATA_DIR);
-    if !path.exists() {
-        fs::create_dir_all(&path)?;
+/// Encode a save or archive in the given format
+fn encode<T: Serialize>(value: &T, format: SaveFormat) -> Result<Vec<u8>> {
+    Ok(match format {
+        SaveFormat::Json => serde_json::to_vec_pretty(value)?,
+        // Named fields, so skipped and defaulted fields still line up
+        SaveFormat::MessagePack => rmp_serde::to_vec_named(value)?,
+    })
+}
+
+/// Decode a save or archive in either format. JSON documents start with `{`
+/// or `[`; a MessagePack map or array never does.
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{' | b'[') => Ok(serde_json::from_slice(bytes)?),
+        _ => Ok(rmp_serde::from_slice(bytes)?),
     }
-    Ok(path)
 }
 
-/// Get the file path for a player's save file
-fn get_player_path(player_id: &Uuid) -> PathBuf {
-    PathBuf::from(DATA_DIR).join(format!("{}.json", player_id))
+/// A backend that holds player saves
+pub trait PlayerStore: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn save(&self, player: &Player) -> Result<()>;
+    fn load(&self, player_id: &Uuid) -> Result<Option<Player>>;
+    fn delete(&self, player_id: &Uuid) -> Result<()>;
+    fn list(&self) -> Result<Vec<Uuid>>;
 }
 
-/// Save a player's state to disk
-pub fn save_player(player: &Player) -> Result<()> {
-    ensure_data_dir()?;
-    let path = get_player_path(&player.id);
-    let json = serde_json::to_string_pretty(player)?;
-    fs::write(&path, json)?;
-    tracing::debug!("Saved player {} to {:?}", player.id, path);
+/// One file per player under `data/players`, in the configured save format
+pub struct FileStore {
+    dir: PathBuf,
+    format: SaveFormat,
+}
+
+impl FileStore {
+    pub fn new(format: SaveFormat) -> Self {
+        Self {
+            dir: PathBuf::from(DATA_DIR),
+            format,
+        }
+    }
+
+    /// Ensures the data directory exists
+    fn ensure_data_dir(&self) -> Result<&PathBuf> {
+        if !self.dir.exists() {
+            fs::create_dir_all(&self.dir)?;
+        }
+        Ok(&self.dir)
+    }
+
+    /// Get the file path for a player's save file in a format
+    fn get_player_path(&self, player_id: &Uuid, format: SaveFormat) -> PathBuf {
+        self.dir.join(format!("{}.{}", player_id, format.extension()))
+    }
+
+    /// Existing save files for a player, the configured format first
+    fn existing_paths(&self, player_id: &Uuid) -> impl Iterator<Item = PathBuf> {
+        let preferred = self.format;
+        std::iter::once(preferred)
+            .chain(SaveFormat::ALL.into_iter().filter(move |f| *f != preferred))
+            .map(|format| self.get_player_path(player_id, format))
+            .filter(|path| path.exists())
+    }
+}
+
+impl PlayerStore for FileStore {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn save(&self, player: &Player) -> Result<()> {
+        self.ensure_data_dir()?;
+        let path = self.get_player_path(&player.id, self.format);
+        fs::write(&path, encode(player, self.format)?)?;
+        // A save in the other format is now stale
+        for stale in self.existing_paths(&player.id).filter(|p| *p != path) {
+            fs::remove_file(stale)?;
+        }
+        tracing::debug!("Saved player {} to {:?}", player.id, path);
+        Ok(())
+    }
+
+    fn load(&self, player_id: &Uuid) -> Result<Option<Player>> {
+        let Some(path) = self.existing_paths(player_id).next() else {
+            return Ok(None);
+        };
+        let player: Player = decode(&fs::read(&path)?)?;
+        tracing::debug!("Loaded player {} from {:?}", player_id, path);
+        Ok(Some(player))
+    }
+
+    fn delete(&self, player_id: &Uuid) -> Result<()> {
+        for path in self.existing_paths(player_id) {
+            fs::remove_file(&path)?;
+            tracing::debug!("Deleted player {} save file", player_id);
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<Uuid>> {
+        let dir = self.ensure_data_dir()?;
+        let mut players = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let name = file_name.to_string_lossy();
+            if let Some(id) = save_file_id(&name) {
+                players.push(id);
+            }
+        }
+
+        players.sort();
+        players.dedup();
+        Ok(players)
+    }
+}
+
+/// The player id of a save file name in any format
+pub fn save_file_id(file_name: &str) -> Option<Uuid> {
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    SaveFormat::ALL
+        .iter()
+        .any(|f| f.extension() == extension)
+        .then(|| Uuid::parse_str(stem).ok())
+        .flatten()
+}
+
+/// Players stored as JSON documents (or MessagePack blobs) in a single SQLite table
+pub struct SqliteStore {
+    conn: Mutex<rusqlite::Connection>,
+    format: SaveFormat,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str, format: SaveFormat) -> Result<Self> {
+        if let Some(parent) = PathBuf::from(path).parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS players (
+                 id TEXT PRIMARY KEY,
+                 data TEXT NOT NULL,
+                 updated_at TEXT NOT NULL
+             );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            format,
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, rusqlite::Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl PlayerStore for SqliteStore {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn save(&self, player: &Player) -> Result<()> {
+        // SQLite columns are loosely typed, so MessagePack goes in the same column as a blob
+        let data = match self.format {
+            SaveFormat::Json => rusqlite::types::Value::Text(serde_json::to_string(player)?),
+            SaveFormat::MessagePack => rusqlite::types::Value::Blob(encode(player, self.format)?),
+        };
+        self.conn().execute(
+            "INSERT INTO players (id, data, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(id) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+            (player.id.to_string(), data, chrono::Utc::now().to_rfc3339()),
+        )?;
+        tracing::debug!("Saved player {} to sqlite", player.id);
+        Ok(())
+    }
+
+    fn load(&self, player_id: &Uuid) -> Result<Option<Player>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare("SELECT data FROM players WHERE id = ?1")?;
+        let mut rows = statement.query([player_id.to_string()])?;
+        match rows.next()? {
+            Some(row) => match row.get_ref(0)? {
+                rusqlite::types::ValueRef::Text(data) | rusqlite::types::ValueRef::Blob(data) => {
+                    Ok(Some(decode(data)?))
+                }
+                other => bail!("unexpected save data type {:?}", other.data_type()),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn delete(&self, player_id: &Uuid) -> Result<()> {
+        self.conn()
+            .execute("DELETE FROM players WHERE id = ?1", [player_id.to_string()])?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<Uuid>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare("SELECT id FROM players")?;
+        let ids = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .filter_map(|id| id.ok().and_then(|id| Uuid::parse_str(&id).ok()))
+            .collect();
+        Ok(ids)
+    }
+}
+
+/// Migration mode: writes go to both backends, reads come from the primary.
+///
+/// Secondary failures are logged but never fail the request, so a broken new
+/// backend can't take the live instance down mid-migration.
+pub struct DualWriteStore {
+    primary: Box<dyn PlayerStore>,
+    secondary: Box<dyn PlayerStore>,
+}
+
+impl PlayerStore for DualWriteStore {
+    fn name(&self) -> &'static str {
+        "dual-write"
+    }
+
+    fn save(&self, player: &Player) -> Result<()> {
+        self.primary.save(player)?;
+        if let Err(e) = self.secondary.save(player) {
+            tracing::warn!(
+                "Dual-write to {} failed for player {}: {}",
+                self.secondary.name(),
+                player.id,
+                e
+            );
+        }
+        Ok(())
+    }
+
+    fn load(&self, player_id: &Uuid) -> Result<Option<Player>> {
+        self.primary.load(player_id)
+    }
+
+    fn delete(&self, player_id: &Uuid) -> Result<()> {
+        self.primary.delete(player_id)?;
+        if let Err(e) = self.secondary.delete(player_id) {
+            tracing::warn!(
+                "Dual-delete from {} failed for player {}: {}",
+                self.secondary.name(),
+                player_id,
+                e
+            );
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<Uuid>> {
+        self.primary.list()
+    }
+}
+
+/// Open a single storage backend
+pub fn open_backend(backend: StorageBackend, config: &Config) -> Result<Box<dyn PlayerStore>> {
+    Ok(match backend {
+        StorageBackend::File => Box::new(FileStore::new(config.save_format)),
+        StorageBackend::Sqlite => Box::new(SqliteStore::open(&config.sqlite_path, config.save_format)?),
+    })
+}
+
+static STORE: OnceLock<Box<dyn PlayerStore>> = OnceLock::new();
+static FORMAT: OnceLock<SaveFormat> = OnceLock::new();
+
+/// Select the storage backend(s) for this process. Must be called once at startup.
+pub fn init(config: &Config) -> Result<()> {
+    let primary = open_backend(config.storage_backend, config)?;
+    let store: Box<dyn PlayerStore> = match config.storage_dual_write {
+        Some(secondary) if secondary != config.storage_backend => {
+            tracing::info!(
+                "Storage: {} with dual-write to {:?}",
+                primary.name(),
+                secondary
+            );
+            Box::new(DualWriteStore {
+                primary,
+                secondary: open_backend(secondary, config)?,
+            })
+        }
+        _ => {
+            tracing::info!("Storage: {}", primary.name());
+            primary
+        }
+    };
+
+    if STORE.set(store).is_err() || FORMAT.set(config.save_format).is_err() {
+        bail!("storage already initialized");
+    }
     Ok(())
 }
 
-/// Load a player's state from disk
+fn store() -> &'static dyn PlayerStore {
+    STORE.get_or_init(|| Box::new(FileStore::new(SaveFormat::default()))).as_ref()
+}
+
+/// Format new saves and archives are written in
+fn save_format() -> SaveFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+/// Save a player's state
+pub fn save_player(player: &Player) -> Result<()> {
+    store().save(player)
+}
+
+/// Load a player's state
 pub fn load_player(player_id: &Uuid) -> Result<Option<Player>> {
-    let path = get_player_path(player_id);
-    if !path.exists() {
-        return Ok(None);
+    let mut player = store().load(player_id)?;
+    // A choice saved while its answer was generating was cut short by a restart
+    if let Some(player) = player.as_mut()
+        && let Some(latest) = player.run.narrative_history.last().map(|m| m.id)
+    {
+        player.release_choice(latest);
     }
-    let json = fs::read_to_string(&path)?;
-    let player: Player = serde_json::from_str(&json)?;
-    tracing::debug!("Loaded player {} from {:?}", player_id, path);
-    Ok(Some(player))
+    // The next save of a loaded player is written by this build
+    if let Some(player) = player.as_mut() {
+        player.build = Some(build_info::current().clone());
+    }
+    Ok(player)
 }
 
-/// Delete a player's save file
+/// Delete a player's save
+#[allow(dead_code)]
 pub fn delete_player(player_id: &Uuid) -> Result<()> {
-    let path = get_player_path(player_id);
-    if path.exists() {
-        fs::remove_file(&path)?;
-        tracing::debug!("Deleted player {} save file", player_id);
-    }
-    Ok(())
+    store().delete(player_id)
 }
 
 /// List all saved player IDs
 pub fn list_saved_players() -> Result<Vec<Uuid>> {
-    ensure_data_dir()?;
-    let path = PathBuf::from(DATA_DIR);
+    store().list()
+}
+
+/// Checksum over a player's canonical JSON form (object keys sorted)
+fn checksum(player: &Player) -> Result<u64> {
+    let canonical = serde_json::to_string(&serde_json::to_value(player)?)?;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    canonical.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Outcome of a bulk migration between backends
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub total: usize,
+    pub copied: usize,
+    pub verified: usize,
+    pub mismatched: Vec<Uuid>,
+    pub failed: Vec<(Uuid, String)>,
+}
+
+/// Copy every player from `source` to `target` and compare checksums.
+///
+/// With `verify_only`, nothing is written and only existing copies are compared.
+pub fn migrate(
+    source: &dyn PlayerStore,
+    target: &dyn PlayerStore,
+    verify_only: bool,
+) -> Result<MigrationReport> {
+    let ids = source.list()?;
+    let mut report = MigrationReport {
+        total: ids.len(),
+        ..Default::default()
+    };
+
+    for id in ids {
+        let player = match source.load(&id) {
+            Ok(Some(player)) => player,
+            Ok(None) => continue,
+            Err(e) => {
+                report.failed.push((id, e.to_string()));
+                continue;
+            }
+        };
+
+        if !verify_only {
+            if let Err(e) = target.save(&player) {
+                report.failed.push((id, e.to_string()));
+                continue;
+            }
+            report.copied += 1;
+        }
+
+        let matches = match target.load(&id) {
+            Ok(Some(copy)) => checksum(&player)? == checksum(&copy)?,
+            Ok(None) => false,
+            Err(e) => {
+                report.failed.push((id, e.to_string()));
+                continue;
+            }
+        };
+        if matches {
+            report.verified += 1;
+        } else {
+            report.mismatched.push(id);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Get the archive directory for a player's finished loops
+fn get_archive_dir(player_id: &Uuid) -> PathBuf {
+    PathBuf::from(ARCHIVE_DIR).join(player_id.to_string())
+}
+
+fn spill_path(player_id: &Uuid, loop_number: u64) -> PathBuf {
+    get_archive_dir(player_id)
+        .join("spill")
+        .join(format!("loop-{}.json", loop_number))
+}
+
+fn load_spilled(player_id: &Uuid, loop_number: u64) -> Result<Vec<NarrativeMoment>> {
+    let path = spill_path(player_id, loop_number);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Move full moments of the current loop out of memory, next to the loop archive
+pub fn spill_moments(player_id: &Uuid, loop_number: u64, moments: &[NarrativeMoment]) -> Result<()> {
+    let mut spilled = load_spilled(player_id, loop_number)?;
+    spilled.extend(moments.iter().cloned());
+    let path = spill_path(player_id, loop_number);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_string(&spilled)?)?;
+    Ok(())
+}
+
+/// Swap summarized moments back for their full spilled versions
+pub fn restore_spilled(
+    player_id: &Uuid,
+    loop_number: u64,
+    moments: &mut [NarrativeMoment],
+) -> Result<()> {
+    if !moments.iter().any(|m| m.summarized) {
+        return Ok(());
+    }
+    let spilled = load_spilled(player_id, loop_number)?;
+    for moment in moments.iter_mut().filter(|m| m.summarized) {
+        if let Some(full) = spilled.iter().find(|s| s.id == moment.id) {
+            *moment = full.clone();
+        }
+    }
+    Ok(())
+}
+
+fn archive_path(player_id: &Uuid, loop_number: u64, format: SaveFormat) -> PathBuf {
+    get_archive_dir(player_id).join(format!("loop-{}.{}", loop_number, format.extension()))
+}
+
+/// Write a finished loop to the player's archive
+pub fn archive_loop(archived: &ArchivedLoop) -> Result<()> {
+    let dir = get_archive_dir(&archived.player_id);
+    fs::create_dir_all(&dir)?;
+    let number = archived.loop_info.number;
+    let format = save_format();
+    let path = archive_path(&archived.player_id, number, format);
+
+    // Archives always hold the full loop, even if part of it was spilled
+    let data = if archived.moments.iter().any(|m| m.summarized) {
+        let mut restored = archived.clone();
+        restore_spilled(&archived.player_id, number, &mut restored.moments)?;
+        encode(&restored, format)?
+    } else {
+        encode(archived, format)?
+    };
+    fs::write(&path, data)?;
+    // Rewriting a loop (e.g. on compaction) replaces its archive in any older format
+    for other in SaveFormat::ALL.into_iter().filter(|f| *f != format) {
+        let stale = archive_path(&archived.player_id, number, other);
+        if stale.exists() {
+            fs::remove_file(stale)?;
+        }
+    }
+    let spill = spill_path(&archived.player_id, number);
+    if spill.exists() {
+        fs::remove_file(spill)?;
+    }
+    tracing::debug!(
+        "Archived loop {} of player {} to {:?}",
+        archived.loop_info.number,
+        archived.player_id,
+        path
+    );
+    Ok(())
+}
+
+/// Players that have at least one archived loop
+pub fn list_archived_players() -> Result<Vec<Uuid>> {
+    let dir = PathBuf::from(ARCHIVE_DIR);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
     let mut players = Vec::new();
-    
-    for entry in fs::read_dir(&path)? {
+    for entry in fs::read_dir(dir)? {
         let entry = entry?;
-        let file_name = entry.file_name();
-        let name = file_name.to_string_lossy();
-        if name.ends_with(".json") {
-            if let Some(id_str) = name.strip_suffix(".json") {
-                if let Ok(id) = Uuid::parse_str(id_str) {
-                    players.push(id);
-                }
-            }
+        if entry.file_type()?.is_dir()
+            && let Some(id) = entry.file_name().to_str().and_then(|n| Uuid::parse_str(n).ok())
+        {
+            players.push(id);
         }
     }
-    
     Ok(players)
 }
 
+/// Size of an archived loop's file on disk
+pub fn archive_size(player_id: &Uuid, loop_number: u64) -> u64 {
+    SaveFormat::ALL
+        .into_iter()
+        .filter_map(|format| fs::metadata(archive_path(player_id, loop_number, format)).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Archive a completed epilogue next to the run's loops
+pub fn archive_epilogue(run_id: &Uuid, epilogue: &Epilogue) -> Result<()> {
+    let dir = get_archive_dir(run_id);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("epilogue-{:?}.json", epilogue.ending));
+    fs::write(path, serde_json::to_string_pretty(epilogue)?)?;
+    Ok(())
+}
+
+/// Load all archived loops for a player, oldest first
+pub fn load_archived_loops(player_id: &Uuid) -> Result<Vec<ArchivedLoop>> {
+    let dir = get_archive_dir(player_id);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut loops = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let is_loop = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("loop-"))
+            .and_then(|n| n.rsplit_once('.'))
+            .is_some_and(|(_, ext)| SaveFormat::ALL.iter().any(|f| f.extension() == ext));
+        if is_loop {
+            loops.push(decode::<ArchivedLoop>(&fs::read(&path)?)?);
+        }
+    }
+    loops.sort_by_key(|l| l.loop_info.number);
+    Ok(loops)
+}
+
 /// Auto-save interval tracking
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AutoSaveConfig {
     pub enabled: bool,
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+use crate::endings::EndingType;
+
+/// Voices the narrator can speak in
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Persona {
+    /// The original knowing, philosophical narrator
+    #[default]
+    Narrator,
+    /// A meticulous keeper of records who catalogues every loop
+    Archivist,
+    /// A small voice that sees wonder everywhere and doesn't understand cruelty
+    Child,
+    /// A corrupted signal, barely coherent, leaning toward the void
+    Static,
+}
+
+impl Persona {
+    pub const ALL: [Persona; 4] = [
+        Persona::Narrator,
+        Persona::Archivist,
+        Persona::Child,
+        Persona::Static,
+    ];
+
+    pub fn get_title(&self) -> &'static str {
+        match self {
+            Persona::Narrator => "The Narrator",
+            Persona::Archivist => "The Archivist",
+            Persona::Child => "The Child",
+            Persona::Static => "The Static",
+        }
+    }
+
+    /// Voice instructions injected into the system prompt
+    pub fn voice(&self) -> &'static str {
+        match self {
+            Persona::Narrator => {
+                "Speak as an omniscient, melancholic narrator: measured, philosophical, \
+                 quietly knowing. You have seen every loop."
+            }
+            Persona::Archivist => {
+                "Speak as The Archivist: precise, dry and formal, as if reading from an index \
+                 card. Reference loops by number, cite past choices like catalogue entries, and \
+                 treat the player's life as a collection being carefully preserved."
+            }
+            Persona::Child => {
+                "Speak as The Child: simple words, short sentences, curious and earnest. You notice \
+                 small beautiful things and are confused and hurt by cruelty, but you never stop \
+                 hoping the player will be kind."
+            }
+            Persona::Static => {
+                "Speak as The Static: a broken transmission. Fragmented sentences, repeated words, \
+                 occasional [SIGNAL LOST] interruptions. You drift toward emptiness and find \
+                 meaning suspicious, but something human still flickers underneath."
+            }
+        }
+    }
+
+    /// Score deltas for (dark, light) choices under this persona
+    pub fn score_deltas(&self) -> (i32, i32) {
+        match self {
+            Persona::Narrator => (5, -3),
+            Persona::Archivist => (4, -4),
+            Persona::Child => (6, -5),
+            Persona::Static => (6, -2),
+        }
+    }
+
+    /// Ending that unlocks this persona, if it isn't available from the start
+    pub fn unlocked_by(&self) -> Option<&'static [EndingType]> {
+        match self {
+            Persona::Narrator | Persona::Archivist => None,
+            Persona::Child => Some(&[EndingType::TinyPerfectThings, EndingType::Transcendence]),
+            Persona::Static => Some(&[EndingType::VoidEmbrace, EndingType::JustMonika]),
+        }
+    }
+
+    /// Whether this persona is available given the endings a player has reached
+    pub fn is_unlocked(&self, endings_reached: &[EndingType]) -> bool {
+        match self.unlocked_by() {
+            None => true,
+            Some(endings) => endings.iter().any(|e| endings_reached.contains(e)),
+        }
+    }
+}
@@ -0,0 +1,592 @@
+//! WASM plugins: modules in `PLUGINS_DIR` that observe game events, judge
+//! choices, review the world updates the narrator proposes, add endings and
+//! add sections to the narrator's prompt, without forking the server.
+//!
+//! A plugin is `<name>.wasm` (or `.wat`) with a `<name>.json` manifest beside
+//! it, listing the capabilities it is granted. A hook the module exports
+//! without the matching capability is never called. Modules get no WASI, so
+//! they can't reach files, the network or the clock; their only import is
+//! `nihilism.log`. Every call is bounded by `PLUGIN_FUEL` and 16 MiB of
+//! memory, and a plugin that traps is started afresh on its next call.
+//!
+//! Hooks exchange JSON through the guest's memory: the host asks the guest's
+//! `alloc(len) -> ptr` for room, writes the input there and calls
+//! `hook(ptr, len) -> i64`. The result packs the output as `ptr << 32 | len`,
+//! or is `0` when the plugin has nothing to say.
+//!
+//! The WASM runtime is built with the `plugins` feature; without it, plugins
+//! found in `PLUGINS_DIR` are reported and skipped.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::conditions::{Condition, ConditionContext};
+use crate::config::Config;
+use crate::events::{Envelope, EventBus};
+use crate::game::Player;
+use crate::persona::Persona;
+use crate::scoring::{DarknessFuture, ScoredChoice, ScoringStrategy};
+
+#[cfg(feature = "plugins")]
+mod wasm;
+
+/// Longest prompt section a plugin may add
+const MAX_SECTION_CHARS: usize = 1000;
+/// Longest title, description or brief of a plugin ending
+const MAX_ENDING_TEXT_CHARS: usize = 1000;
+
+/// What a plugin may do
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Receive every game event
+    Events,
+    /// Judge how dark a choice is, as a member of the scoring ensemble
+    Scoring,
+    /// Veto or change the world updates the narrator proposes
+    WorldUpdates,
+    /// Register endings of its own
+    Endings,
+    /// Add sections to the narrator's prompt
+    Context,
+}
+
+/// A function a plugin module may export
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Hook {
+    OnEvent,
+    ScoreChoice,
+    ReviewWorldUpdates,
+    Endings,
+    Context,
+}
+
+impl Hook {
+    pub const ALL: [Hook; 5] = [
+        Hook::OnEvent,
+        Hook::ScoreChoice,
+        Hook::ReviewWorldUpdates,
+        Hook::Endings,
+        Hook::Context,
+    ];
+
+    /// Name of the export
+    pub fn export(self) -> &'static str {
+        match self {
+            Hook::OnEvent => "on_event",
+            Hook::ScoreChoice => "score_choice",
+            Hook::ReviewWorldUpdates => "review_world_updates",
+            Hook::Endings => "endings",
+            Hook::Context => "context",
+        }
+    }
+
+    fn capability(self) -> Capability {
+        match self {
+            Hook::OnEvent => Capability::Events,
+            Hook::ScoreChoice => Capability::Scoring,
+            Hook::ReviewWorldUpdates => Capability::WorldUpdates,
+            Hook::Endings => Capability::Endings,
+            Hook::Context => Capability::Context,
+        }
+    }
+}
+
+/// `<name>.json` beside a plugin module
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub capabilities: HashSet<Capability>,
+    /// Weight of the plugin's judgement in the scoring ensemble
+    #[serde(default = "default_scoring_weight")]
+    pub scoring_weight: f64,
+}
+
+fn default_scoring_weight() -> f64 {
+    1.0
+}
+
+/// A loaded plugin module
+pub trait Guest: Send + Sync {
+    /// Whether the module exports `hook`
+    fn exports(&self, hook: Hook) -> bool;
+
+    /// Call `hook` with JSON `input`, returning its JSON output, if any
+    fn call(&self, hook: Hook, input: &[u8]) -> Result<Option<Vec<u8>>>;
+}
+
+pub struct Plugin {
+    pub name: String,
+    manifest: Manifest,
+    guest: Box<dyn Guest>,
+    calls: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Plugin {
+    pub fn new(name: String, manifest: Manifest, guest: Box<dyn Guest>) -> Self {
+        for hook in Hook::ALL {
+            if guest.exports(hook) && !manifest.capabilities.contains(&hook.capability()) {
+                tracing::warn!(
+                    "Plugin {} exports {} without the {:?} capability; it won't be called",
+                    name,
+                    hook.export(),
+                    hook.capability()
+                );
+            }
+        }
+        Self {
+            name,
+            manifest,
+            guest,
+            calls: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+
+    fn may(&self, hook: Hook) -> bool {
+        self.manifest.capabilities.contains(&hook.capability()) && self.guest.exports(hook)
+    }
+
+    /// Call `hook` if the plugin may, returning its parsed output. Failures are
+    /// logged and counted, and read as having nothing to say.
+    fn call<T: for<'de> Deserialize<'de>>(&self, hook: Hook, input: &impl Serialize) -> Option<T> {
+        if !self.may(hook) {
+            return None;
+        }
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let result = serde_json::to_vec(input)
+            .map_err(anyhow::Error::from)
+            .and_then(|input| self.guest.call(hook, &input))
+            .and_then(|output| {
+                output
+                    .map(|bytes| serde_json::from_slice(&bytes).context("invalid JSON output"))
+                    .transpose()
+            });
+        match result {
+            Ok(output) => output,
+            Err(e) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!("Plugin {} failed in {}: {:#}", self.name, hook.export(), e);
+                None
+            }
+        }
+    }
+
+    pub fn view(&self) -> PluginView {
+        let mut capabilities: Vec<Capability> =
+            self.manifest.capabilities.iter().copied().collect();
+        capabilities.sort_by_key(|c| *c as u8);
+        PluginView {
+            name: self.name.clone(),
+            capabilities,
+            hooks: Hook::ALL
+                .into_iter()
+                .filter(|h| self.may(*h))
+                .map(Hook::export)
+                .collect(),
+            calls: self.calls.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A plugin as the admin API lists it
+#[derive(Clone, Debug, Serialize)]
+pub struct PluginView {
+    pub name: String,
+    pub capabilities: Vec<Capability>,
+    /// Hooks the plugin exports and is allowed to have called
+    pub hooks: Vec<&'static str>,
+    pub calls: u64,
+    pub failures: u64,
+}
+
+/// An ending as the admin API lists it
+#[derive(Clone, Debug, Serialize)]
+pub struct EndingView {
+    pub id: String,
+    pub title: String,
+    pub condition: String,
+    pub plugin: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PluginReport {
+    pub plugins: Vec<PluginView>,
+    pub endings: Vec<EndingView>,
+}
+
+/// An ending a plugin registered
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndingSpec {
+    /// Lowercase letters, digits and underscores, unique across plugins
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    /// When the ending is reached, in the ending condition language
+    pub condition: String,
+    /// What the post-game epilogue is about
+    #[serde(default)]
+    pub epilogue: Option<String>,
+    /// How the narrator steers a run whose player refused the ending
+    #[serde(default)]
+    pub refusal: Option<String>,
+    /// The kind of choice that leads toward the ending
+    #[serde(default)]
+    pub trajectory: Option<String>,
+}
+
+pub struct PluginEnding {
+    pub spec: EndingSpec,
+    pub condition: Condition,
+    /// The plugin that registered it
+    pub plugin: String,
+}
+
+fn valid_ending(spec: &EndingSpec) -> bool {
+    let id_ok = !spec.id.is_empty()
+        && spec.id.len() <= 64
+        && spec
+            .id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    let texts = [
+        Some(&spec.title),
+        Some(&spec.description),
+        spec.epilogue.as_ref(),
+        spec.refusal.as_ref(),
+        spec.trajectory.as_ref(),
+    ];
+    id_ok
+        && !spec.title.trim().is_empty()
+        && texts
+            .into_iter()
+            .flatten()
+            .all(|t| t.chars().count() <= MAX_ENDING_TEXT_CHARS)
+}
+
+/// What plugins see of a player: the state ending conditions are measured on
+#[derive(Serialize)]
+struct PlayerState<'a> {
+    player_id: uuid::Uuid,
+    persona: Persona,
+    #[serde(flatten)]
+    stats: ConditionContext<'a>,
+}
+
+impl<'a> PlayerState<'a> {
+    fn of(player: &'a Player) -> Self {
+        Self {
+            player_id: player.id,
+            persona: player.run.persona,
+            stats: ConditionContext::from_player(player),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Darkness {
+    darkness: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct Review {
+    updates: Vec<Option<Value>>,
+}
+
+#[derive(Deserialize)]
+struct Section {
+    title: String,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct Sections {
+    sections: Vec<Section>,
+}
+
+#[derive(Deserialize)]
+struct Endings {
+    endings: Vec<EndingSpec>,
+}
+
+/// The plugins of this server and the endings they registered
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<Plugin>,
+    endings: Vec<PluginEnding>,
+}
+
+impl PluginHost {
+    /// Host `plugins`, collecting the endings they register
+    pub fn new(plugins: Vec<Plugin>) -> Self {
+        let mut endings: Vec<PluginEnding> = Vec::new();
+        for plugin in &plugins {
+            let Some(registered) = plugin.call::<Endings>(Hook::Endings, &json!({})) else {
+                continue;
+            };
+            for spec in registered.endings {
+                if !valid_ending(&spec) || endings.iter().any(|e| e.spec.id == spec.id) {
+                    tracing::warn!(
+                        "Plugin {} registered an invalid or duplicate ending '{}'",
+                        plugin.name,
+                        spec.id
+                    );
+                    continue;
+                }
+                match Condition::parse(&spec.condition) {
+                    Ok(condition) => {
+                        tracing::info!(
+                            "Plugin {} adds ending {} when {}",
+                            plugin.name,
+                            spec.id,
+                            spec.condition
+                        );
+                        endings.push(PluginEnding {
+                            spec,
+                            condition,
+                            plugin: plugin.name.clone(),
+                        });
+                    }
+                    Err(e) => tracing::warn!(
+                        "Plugin {} registered ending {} with an invalid condition: {}",
+                        plugin.name,
+                        spec.id,
+                        e
+                    ),
+                }
+            }
+        }
+        Self { plugins, endings }
+    }
+
+    pub fn endings(&self) -> &[PluginEnding] {
+        &self.endings
+    }
+
+    pub fn ending(&self, id: &str) -> Option<&PluginEnding> {
+        self.endings.iter().find(|e| e.spec.id == id)
+    }
+
+    pub fn report(&self) -> PluginReport {
+        PluginReport {
+            plugins: self.plugins.iter().map(Plugin::view).collect(),
+            endings: self
+                .endings
+                .iter()
+                .map(|e| EndingView {
+                    id: e.spec.id.clone(),
+                    title: e.spec.title.clone(),
+                    condition: e.spec.condition.clone(),
+                    plugin: e.plugin.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Hand an event to every plugin that observes them
+    pub fn observe(&self, envelope: &Envelope) {
+        for plugin in &self.plugins {
+            plugin.call::<Value>(Hook::OnEvent, envelope);
+        }
+    }
+
+    /// Pass the narrator's proposed world updates through each plugin that
+    /// reviews them. A plugin answers with the list in the same order, each
+    /// update kept, changed, or `null` to veto it. Returns the surviving
+    /// updates and how many were vetoed.
+    pub fn review_world_updates(
+        &self,
+        player: &Player,
+        updates: Vec<Value>,
+    ) -> (Vec<Value>, usize) {
+        let mut updates = updates;
+        let mut vetoed = 0;
+        for plugin in &self.plugins {
+            if updates.is_empty() {
+                break;
+            }
+            let input = json!({ "player": PlayerState::of(player), "updates": updates });
+            let Some(review) = plugin.call::<Review>(Hook::ReviewWorldUpdates, &input) else {
+                continue;
+            };
+            if review.updates.len() != updates.len() {
+                tracing::warn!(
+                    "Plugin {} reviewed {} of {} world updates; ignoring it",
+                    plugin.name,
+                    review.updates.len(),
+                    updates.len()
+                );
+                continue;
+            }
+            vetoed += review.updates.iter().filter(|u| u.is_none()).count();
+            updates = review.updates.into_iter().flatten().collect();
+        }
+        (updates, vetoed)
+    }
+
+    /// Prompt sections the plugins add for `player`
+    pub fn prompt(&self, player: &Player) -> String {
+        let mut prompt = String::new();
+        for plugin in &self.plugins {
+            let input = json!({ "player": PlayerState::of(player) });
+            let Some(added) = plugin.call::<Sections>(Hook::Context, &input) else {
+                continue;
+            };
+            for section in added.sections {
+                let title: String = section
+                    .title
+                    .trim()
+                    .to_uppercase()
+                    .chars()
+                    .take(80)
+                    .collect();
+                let text: String = section
+                    .text
+                    .trim()
+                    .chars()
+                    .take(MAX_SECTION_CHARS)
+                    .collect();
+                if !title.is_empty() && !text.is_empty() {
+                    prompt.push_str(&format!("\n{}:\n{}\n", title, text));
+                }
+            }
+        }
+        prompt
+    }
+
+    /// Scoring members for the plugins allowed to judge choices
+    pub fn scoring_strategies(&'static self) -> Vec<(Box<dyn ScoringStrategy>, f64)> {
+        self.plugins
+            .iter()
+            .filter(|p| p.may(Hook::ScoreChoice))
+            .map(|plugin| {
+                let strategy: Box<dyn ScoringStrategy> = Box::new(PluginStrategy { plugin });
+                (strategy, plugin.manifest.scoring_weight)
+            })
+            .collect()
+    }
+}
+
+/// A plugin's judgement of a choice, from -1.0 to 1.0, or `null` for none
+struct PluginStrategy {
+    plugin: &'static Plugin,
+}
+
+impl ScoringStrategy for PluginStrategy {
+    fn name(&self) -> &'static str {
+        &self.plugin.name
+    }
+
+    fn darkness<'a>(&'a self, choice: &'a ScoredChoice<'a>) -> DarknessFuture<'a> {
+        let input = json!({
+            "choice_id": choice.id,
+            "text": choice.text,
+            "moment": choice.moment.map(|m| &m.text),
+            "player": PlayerState::of(choice.player),
+        });
+        let judged = self.plugin.call::<Darkness>(Hook::ScoreChoice, &input);
+        Box::pin(async move { Ok(judged.and_then(|d| d.darkness).filter(|d| d.is_finite())) })
+    }
+}
+
+static HOST: OnceLock<PluginHost> = OnceLock::new();
+
+/// Used until `init` runs, as in tests
+static NO_PLUGINS: OnceLock<PluginHost> = OnceLock::new();
+
+pub fn host() -> &'static PluginHost {
+    HOST.get()
+        .unwrap_or_else(|| NO_PLUGINS.get_or_init(PluginHost::default))
+}
+
+/// Load the plugins in `PLUGINS_DIR`. Must be called once at startup, before
+/// the endings are, so a bad plugin stops the server there.
+pub fn init(config: &Config) -> Result<()> {
+    let plugins = match &config.plugins_dir {
+        Some(dir) => load(Path::new(dir), config)?,
+        None => Vec::new(),
+    };
+    if HOST.set(PluginHost::new(plugins)).is_err() {
+        anyhow::bail!("plugins already initialized");
+    }
+    Ok(())
+}
+
+/// Modules in `dir` with their manifests, sorted by name
+fn load(dir: &Path, config: &Config) -> Result<Vec<Plugin>> {
+    let mut modules: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("failed to read plugins in {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("wasm" | "wat")
+            )
+        })
+        .collect();
+    modules.sort();
+
+    let mut plugins = Vec::new();
+    for path in modules {
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .context("plugin file name is not UTF-8")?
+            .to_string();
+        let manifest_path = path.with_extension("json");
+        let manifest: Manifest =
+            serde_json::from_str(&fs::read_to_string(&manifest_path).with_context(|| {
+                format!(
+                    "plugin {} has no manifest {}",
+                    name,
+                    manifest_path.display()
+                )
+            })?)
+            .with_context(|| format!("invalid manifest {}", manifest_path.display()))?;
+        let Some(guest) = guest(&path, &name, config)? else {
+            continue;
+        };
+        tracing::info!("Loaded plugin {} with {:?}", name, manifest.capabilities);
+        plugins.push(Plugin::new(name, manifest, guest));
+    }
+    Ok(plugins)
+}
+
+#[cfg(feature = "plugins")]
+fn guest(path: &Path, name: &str, config: &Config) -> Result<Option<Box<dyn Guest>>> {
+    let guest = wasm::WasmGuest::load(path, name, config.plugin_fuel)
+        .with_context(|| format!("failed to load plugin {}", path.display()))?;
+    Ok(Some(Box::new(guest)))
+}
+
+#[cfg(not(feature = "plugins"))]
+fn guest(path: &Path, _name: &str, _config: &Config) -> Result<Option<Box<dyn Guest>>> {
+    tracing::warn!(
+        "Skipping plugin {}: this server was built without the plugins feature",
+        path.display()
+    );
+    Ok(None)
+}
+
+/// Hand every event on `events` to the plugins that observe them
+pub fn subscribe(events: &EventBus) {
+    if !host().plugins.iter().any(|p| p.may(Hook::OnEvent)) {
+        return;
+    }
+    events.spawn_subscriber("plugins", |envelope| async move {
+        host().observe(&envelope);
+    });
+}
+
+#[cfg(test)]
+mod tests;
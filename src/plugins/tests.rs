@@ -0,0 +1,136 @@
+use super::*;
+use crate::endings::EndingType;
+
+/// A guest that answers every hook it exports with `respond`
+struct Fake {
+    hooks: Vec<Hook>,
+    respond: fn(Hook, Value) -> Option<Value>,
+}
+
+impl Guest for Fake {
+    fn exports(&self, hook: Hook) -> bool {
+        self.hooks.contains(&hook)
+    }
+
+    fn call(&self, hook: Hook, input: &[u8]) -> Result<Option<Vec<u8>>> {
+        let input = serde_json::from_slice(input)?;
+        Ok((self.respond)(hook, input).map(|v| v.to_string().into_bytes()))
+    }
+}
+
+fn plugin(
+    name: &str,
+    capabilities: &[Capability],
+    hooks: &[Hook],
+    respond: fn(Hook, Value) -> Option<Value>,
+) -> Plugin {
+    let manifest = Manifest {
+        capabilities: capabilities.iter().copied().collect(),
+        scoring_weight: 1.0,
+    };
+    let guest = Fake {
+        hooks: hooks.to_vec(),
+        respond,
+    };
+    Plugin::new(name.to_string(), manifest, Box::new(guest))
+}
+
+#[test]
+fn hooks_run_only_with_their_capability() {
+    let host: &'static PluginHost = Box::leak(Box::new(PluginHost::new(vec![plugin(
+        "eager",
+        &[Capability::Context],
+        &[Hook::Context, Hook::ScoreChoice],
+        |hook, _| match hook {
+            Hook::Context => Some(json!({ "sections": [
+                { "title": "weather", "text": " It has rained for a week. " },
+                { "title": "", "text": "dropped" },
+            ] })),
+            _ => Some(json!({ "darkness": 1.0 })),
+        },
+    )])));
+
+    let player = Player::new();
+    assert_eq!(
+        host.prompt(&player),
+        "\nWEATHER:\nIt has rained for a week.\n"
+    );
+    assert!(host.scoring_strategies().is_empty());
+
+    let report = host.report();
+    assert_eq!(report.plugins[0].hooks, vec!["context"]);
+    assert_eq!(report.plugins[0].calls, 1);
+}
+
+#[test]
+fn reviews_change_and_veto_world_updates() {
+    let host = PluginHost::new(vec![
+        plugin(
+            "censor",
+            &[Capability::WorldUpdates],
+            &[Hook::ReviewWorldUpdates],
+            |_, input| {
+                let updates: Vec<Value> = input["updates"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|u| {
+                        if u["kind"] == "death" {
+                            Value::Null
+                        } else {
+                            u.clone()
+                        }
+                    })
+                    .collect();
+                Some(json!({ "updates": updates }))
+            },
+        ),
+        // Answers for the wrong number of updates, so it is ignored
+        plugin(
+            "sloppy",
+            &[Capability::WorldUpdates],
+            &[Hook::ReviewWorldUpdates],
+            |_, _| Some(json!({ "updates": [] })),
+        ),
+    ]);
+
+    let player = Player::new();
+    let updates = vec![json!({ "kind": "death" }), json!({ "kind": "rename" })];
+    let (kept, vetoed) = host.review_world_updates(&player, updates);
+    assert_eq!(kept, vec![json!({ "kind": "rename" })]);
+    assert_eq!(vetoed, 1);
+    assert_eq!(host.report().plugins[1].calls, 1);
+}
+
+#[test]
+fn plugins_register_valid_endings() {
+    let host = PluginHost::new(vec![plugin(
+        "fates",
+        &[Capability::Endings],
+        &[Hook::Endings],
+        |_, _| {
+            Some(json!({ "endings": [
+            {
+                "id": "quiet_exit",
+                "title": "Quiet Exit",
+                "description": "You leave without a word.",
+                "condition": "loops >= 3 && score < 0",
+            },
+            { "id": "Bad Id", "title": "Nope", "description": "", "condition": "loops >= 1" },
+            { "id": "broken", "title": "Broken", "description": "", "condition": "loops >=" },
+        ] }))
+        },
+    )]);
+
+    let report = host.report();
+    assert_eq!(report.endings.len(), 1);
+    assert_eq!(report.endings[0].id, "quiet_exit");
+    assert_eq!(report.endings[0].plugin, "fates");
+    assert!(host.ending("quiet_exit").is_some());
+
+    // Plugin endings serialize as their bare id, beside the built-in names
+    let ending = EndingType::Plugin("quiet_exit".to_string());
+    assert_eq!(serde_json::to_value(&ending).unwrap(), json!("quiet_exit"));
+    let parsed: EndingType = serde_json::from_value(json!("Acceptance")).unwrap();
+    assert_eq!(parsed, EndingType::Acceptance);
+}
@@ -0,0 +1,152 @@
+//! The wasmtime runtime behind a plugin
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use wasmtime::{Caller, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use super::{Guest, Hook};
+
+/// Linear memory a plugin may grow to
+const MAX_MEMORY_BYTES: usize = 16 << 20;
+/// Longest JSON output read back from a plugin
+const MAX_OUTPUT_BYTES: usize = 1 << 20;
+/// Longest line a plugin may log
+const MAX_LOG_CHARS: usize = 500;
+
+struct Host {
+    plugin: String,
+    limits: StoreLimits,
+}
+
+pub struct WasmGuest {
+    name: String,
+    engine: Engine,
+    module: Module,
+    linker: Linker<Host>,
+    fuel: u64,
+    exports: HashSet<Hook>,
+    /// Started on first use, and again after a trap
+    instance: Mutex<Option<(Store<Host>, Instance)>>,
+}
+
+impl WasmGuest {
+    pub fn load(path: &Path, name: &str, fuel: u64) -> Result<Self> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, path)?;
+
+        for export in ["memory", "alloc"] {
+            if module.get_export(export).is_none() {
+                anyhow::bail!("the module doesn't export `{}`", export);
+            }
+        }
+        let exports = Hook::ALL
+            .into_iter()
+            .filter(|hook| module.get_export(hook.export()).is_some())
+            .collect();
+
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap(
+            "nihilism",
+            "log",
+            |mut caller: Caller<'_, Host>, ptr: i32, len: i32| {
+                let Some(memory) = caller.get_export("memory").and_then(|e| e.into_memory()) else {
+                    return;
+                };
+                let mut bytes = vec![0; (len.max(0) as usize).min(MAX_LOG_CHARS * 4)];
+                if memory
+                    .read(&caller, ptr as u32 as usize, &mut bytes)
+                    .is_ok()
+                {
+                    let line: String = String::from_utf8_lossy(&bytes)
+                        .chars()
+                        .take(MAX_LOG_CHARS)
+                        .collect();
+                    tracing::info!("Plugin {}: {}", caller.data().plugin, line);
+                }
+            },
+        )?;
+
+        let guest = Self {
+            name: name.to_string(),
+            engine,
+            module,
+            linker,
+            fuel,
+            exports,
+            instance: Mutex::new(None),
+        };
+        // Instantiate once now, so a module with other imports fails at startup
+        let started = guest.start()?;
+        *guest.instance.lock().unwrap_or_else(|e| e.into_inner()) = Some(started);
+        Ok(guest)
+    }
+
+    fn start(&self) -> Result<(Store<Host>, Instance)> {
+        let mut store = Store::new(
+            &self.engine,
+            Host {
+                plugin: self.name.clone(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY_BYTES)
+                    .build(),
+            },
+        );
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(self.fuel)?;
+        let instance = self.linker.instantiate(&mut store, &self.module)?;
+        Ok((store, instance))
+    }
+
+    fn call_in(
+        store: &mut Store<Host>,
+        instance: &Instance,
+        hook: Hook,
+        input: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        let memory = instance
+            .get_memory(&mut *store, "memory")
+            .context("no memory export")?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut *store, "alloc")?;
+        let function = instance.get_typed_func::<(i32, i32), i64>(&mut *store, hook.export())?;
+
+        let len = i32::try_from(input.len()).context("input too large")?;
+        let ptr = alloc.call(&mut *store, len)?;
+        memory.write(&mut *store, ptr as u32 as usize, input)?;
+        let packed = function.call(&mut *store, (ptr, len))?;
+        if packed == 0 {
+            return Ok(None);
+        }
+        let (out_ptr, out_len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+        if out_len > MAX_OUTPUT_BYTES {
+            anyhow::bail!("output of {} bytes is too large", out_len);
+        }
+        let mut output = vec![0; out_len];
+        memory.read(&*store, out_ptr, &mut output)?;
+        Ok(Some(output))
+    }
+}
+
+impl Guest for WasmGuest {
+    fn exports(&self, hook: Hook) -> bool {
+        self.exports.contains(&hook)
+    }
+
+    fn call(&self, hook: Hook, input: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut slot = self.instance.lock().unwrap_or_else(|e| e.into_inner());
+        let (store, instance) = match slot.as_mut() {
+            Some(started) => started,
+            None => slot.insert(self.start()?),
+        };
+        store.set_fuel(self.fuel)?;
+        let result = Self::call_in(store, instance, hook, input);
+        if result.is_err() {
+            // A trap may leave the guest's memory in any state
+            *slot = None;
+        }
+        result
+    }
+}
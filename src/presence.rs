@@ -0,0 +1,103 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::game::Player;
+
+/// Compact presence blob for Discord rich presence and stream overlays
+#[derive(Clone, Debug, Serialize)]
+pub struct Presence {
+    pub mood: String,
+    pub loop_number: u64,
+    pub chapter: &'static str,
+    pub status: String,
+}
+
+/// Chapter name derived from how deep into the loop the player is
+pub fn chapter_for(loop_number: u64) -> &'static str {
+    match loop_number {
+        0..=2 => "Awakening",
+        3..=5 => "Repetition",
+        6..=10 => "Erosion",
+        11..=20 => "Recognition",
+        _ => "The Long Loop",
+    }
+}
+
+/// Deterministic status line used when the narrator can't be reached
+pub fn fallback_status(player: &Player) -> String {
+    const LINES: &[&str] = &[
+        "Counting the same stars again",
+        "Listening for a door that isn't there",
+        "Remembering something that hasn't happened",
+        "Walking a familiar corridor",
+        "Watching the clock refuse to move",
+        "Holding on to a small, perfect thing",
+        "Staring into the static",
+    ];
+    let index = (player.run.current_loop.number as usize + player.run.memory.total_choices as usize)
+        % LINES.len();
+    LINES[index].to_string()
+}
+
+#[derive(Clone)]
+struct CachedStatus {
+    loop_number: u64,
+    status: String,
+}
+
+/// Status lines are generated once per loop and cached here
+#[derive(Default)]
+pub struct PresenceCache {
+    statuses: RwLock<HashMap<Uuid, CachedStatus>>,
+}
+
+impl PresenceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached status for the player's current loop, if any
+    pub async fn get(&self, player_id: &Uuid, loop_number: u64) -> Option<String> {
+        self.statuses
+            .read()
+            .await
+            .get(player_id)
+            .filter(|c| c.loop_number == loop_number)
+            .map(|c| c.status.clone())
+    }
+
+    /// Drop cached lines for players that left memory or moved to a new loop
+    pub async fn evict_stale(&self, current_loops: &HashMap<Uuid, u64>) -> usize {
+        let mut statuses = self.statuses.write().await;
+        let before = statuses.len();
+        statuses.retain(|id, cached| current_loops.get(id) == Some(&cached.loop_number));
+        before - statuses.len()
+    }
+
+    pub async fn insert(&self, player_id: Uuid, loop_number: u64, status: String) {
+        self.statuses.write().await.insert(
+            player_id,
+            CachedStatus {
+                loop_number,
+                status,
+            },
+        );
+    }
+}
+
+/// Assemble the presence blob for a player
+pub fn build(player: &Player, status: String) -> Presence {
+    Presence {
+        mood: player
+            .run
+            .narrative_history
+            .last()
+            .map(|m| m.mood.clone())
+            .unwrap_or_else(|| "neutral".to_string()),
+        loop_number: player.run.current_loop.number,
+        chapter: chapter_for(player.run.current_loop.number),
+        status,
+    }
+}
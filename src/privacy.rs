@@ -0,0 +1,179 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::config::Config;
+use crate::game::{ArchivedLoop, Player};
+use crate::persistence;
+
+/// What a player agreed to, recorded when they first answered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Consent {
+    /// Choice ratings and choice position statistics
+    pub analytics: bool,
+    /// Letting the loop echo the player's words to other players
+    pub echoes: bool,
+    /// Ending counts and the daily challenge leaderboard
+    pub public_stats: bool,
+    /// Keeping the full text of finished loops
+    pub transcripts: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Consent {
+    fn all(granted: bool) -> Self {
+        Self {
+            analytics: granted,
+            echoes: granted,
+            public_stats: granted,
+            transcripts: granted,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// Flags a player sends; those left out keep their current value
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct ConsentUpdate {
+    pub analytics: Option<bool>,
+    pub echoes: Option<bool>,
+    pub public_stats: Option<bool>,
+    pub transcripts: Option<bool>,
+}
+
+/// What a player keeps out of the files they export and share
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportPrivacy {
+    /// The player's name
+    #[serde(default)]
+    pub withhold_name: bool,
+    /// The private notes in their settings
+    #[serde(default)]
+    pub withhold_notes: bool,
+    /// Choices they typed instead of picking an offered one
+    #[serde(default)]
+    pub withhold_freeform: bool,
+}
+
+impl ExportPrivacy {
+    /// Nothing is withheld
+    pub fn is_open(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn update(&mut self, update: ExportPrivacyUpdate) {
+        self.withhold_name = update.withhold_name.unwrap_or(self.withhold_name);
+        self.withhold_notes = update.withhold_notes.unwrap_or(self.withhold_notes);
+        self.withhold_freeform = update.withhold_freeform.unwrap_or(self.withhold_freeform);
+    }
+}
+
+/// Export settings a player sends; those left out keep their current value
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct ExportPrivacyUpdate {
+    pub withhold_name: Option<bool>,
+    pub withhold_notes: Option<bool>,
+    pub withhold_freeform: Option<bool>,
+}
+
+/// Something the server collects player data for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Purpose {
+    Analytics,
+    PublicStats,
+    Transcripts,
+}
+
+/// The one place that decides whether a player's data may be collected
+pub struct PrivacyPolicy {
+    /// Consent assumed for players who never answered
+    granted_by_default: bool,
+}
+
+static POLICY: OnceLock<PrivacyPolicy> = OnceLock::new();
+/// Used until `init` runs, as in tests
+static DEFAULT_POLICY: PrivacyPolicy = PrivacyPolicy {
+    granted_by_default: true,
+};
+
+/// Set the policy from `CONSENT_BY_DEFAULT`. Must be called once at startup.
+pub fn init(config: &Config) -> Result<()> {
+    let policy = PrivacyPolicy {
+        granted_by_default: config.consent_by_default,
+    };
+    if POLICY.set(policy).is_err() {
+        anyhow::bail!("privacy policy already initialized");
+    }
+    Ok(())
+}
+
+pub fn policy() -> &'static PrivacyPolicy {
+    POLICY.get().unwrap_or(&DEFAULT_POLICY)
+}
+
+impl PrivacyPolicy {
+    /// The player's consent, or the default for players who never answered
+    pub fn consent(&self, player: &Player) -> Consent {
+        player.consent.unwrap_or_else(|| Consent {
+            updated_at: player.created_at,
+            ..Consent::all(self.granted_by_default)
+        })
+    }
+
+    pub fn allows(&self, player: &Player, purpose: Purpose) -> bool {
+        let consent = self.consent(player);
+        match purpose {
+            Purpose::Analytics => consent.analytics,
+            Purpose::PublicStats => consent.public_stats,
+            Purpose::Transcripts => consent.transcripts,
+        }
+    }
+
+    /// Record the player's answer, starting from their current consent
+    pub fn update(&self, player: &mut Player, update: ConsentUpdate) -> Consent {
+        let current = self.consent(player);
+        let consent = Consent {
+            analytics: update.analytics.unwrap_or(current.analytics),
+            echoes: update.echoes.unwrap_or(current.echoes),
+            public_stats: update.public_stats.unwrap_or(current.public_stats),
+            transcripts: update.transcripts.unwrap_or(current.transcripts),
+            updated_at: Utc::now(),
+        };
+        player.consent = Some(consent);
+        consent
+    }
+
+    /// Reduce a finished loop to its stats before it is archived, unless the
+    /// player agreed to keep transcripts
+    pub fn redact_archive(&self, player: &Player, archived: &mut ArchivedLoop) {
+        if !self.allows(player, Purpose::Transcripts) && !archived.is_compacted() {
+            let summary = format!(
+                "Loop #{} passed, and nothing of it was written down.",
+                archived.loop_info.number
+            );
+            archived.compact(summary);
+        }
+    }
+
+    /// Reduce every loop already archived for the player's runs, after they
+    /// withdrew consent to keep transcripts. Returns how many were reduced.
+    pub fn redact_archives(&self, player: &Player) -> Result<usize> {
+        let mut redacted = 0;
+        for run in player.all_runs() {
+            let run_id = run.run_id.unwrap_or(player.id);
+            for mut archived in persistence::load_archived_loops(&run_id)? {
+                if archived.is_compacted() {
+                    continue;
+                }
+                self.redact_archive(player, &mut archived);
+                persistence::archive_loop(&archived)?;
+                redacted += 1;
+            }
+        }
+        Ok(redacted)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,53 @@
+use super::*;
+use crate::game::LoopEndCause;
+use crate::testing::PlayerBuilder;
+
+#[test]
+fn players_who_never_answered_get_the_default() {
+    let player = Player::new();
+    let denying = PrivacyPolicy {
+        granted_by_default: false,
+    };
+
+    assert!(DEFAULT_POLICY.allows(&player, Purpose::Analytics));
+    assert!(!denying.allows(&player, Purpose::Analytics));
+    assert!(!denying.allows(&player, Purpose::PublicStats));
+    assert!(!denying.allows(&player, Purpose::Transcripts));
+}
+
+#[test]
+fn update_keeps_flags_left_out() {
+    let mut player = Player::new();
+    let update = ConsentUpdate {
+        public_stats: Some(false),
+        ..Default::default()
+    };
+
+    let consent = DEFAULT_POLICY.update(&mut player, update);
+
+    assert!(consent.analytics && consent.echoes && consent.transcripts);
+    assert!(!DEFAULT_POLICY.allows(&player, Purpose::PublicStats));
+    assert_eq!(player.consent, Some(consent));
+}
+
+#[test]
+fn archive_without_transcript_consent_keeps_only_stats() {
+    let mut player = PlayerBuilder::new()
+        .moment("The clock on the wall stops.", &[("wait", "Wait")])
+        .build();
+    DEFAULT_POLICY.update(
+        &mut player,
+        ConsentUpdate {
+            transcripts: Some(false),
+            ..Default::default()
+        },
+    );
+    let mut archived = player.reset_loop(LoopEndCause::ChoseReset, Vec::new()).unwrap();
+
+    DEFAULT_POLICY.redact_archive(&player, &mut archived);
+
+    assert!(archived.moments.is_empty());
+    let shard = archived.shard.expect("shard");
+    assert_eq!(shard.moment_count, 1);
+    assert!(!shard.summary.contains("clock"));
+}
@@ -0,0 +1,261 @@
+//! Where moments and choices come from.
+//!
+//! Every moment records who wrote it: a model, a scenario author through an
+//! anchor, the offline pack, the server's own stand-in text or an admin. A
+//! choice records its own provenance only where it differs from its
+//! moment's, as when an admin rewrote it, and a choice the player typed
+//! instead of picking one is the player's. Choice logs record the provenance
+//! of each choice made, so authored and generated content can be compared by
+//! how players take it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::consequences::{self, ChoiceRecord};
+use crate::game::{NarrativeMoment, Player};
+use crate::persistence;
+
+/// Who wrote a moment or a choice
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum Provenance {
+    /// Generated by `model`
+    Model { model: String },
+    /// An anchor moment of the scenario pack
+    Anchor { anchor: String },
+    /// The bundled offline pack
+    Offline,
+    /// The server's own text: fallbacks and stand-ins
+    Server,
+    /// Typed by the player
+    Player,
+    /// Rewritten by an admin
+    Admin,
+}
+
+/// Provenance without its details, to filter by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Model,
+    Anchor,
+    Offline,
+    Server,
+    Player,
+    Admin,
+}
+
+impl Provenance {
+    pub fn model(model: &str) -> Self {
+        Provenance::Model {
+            model: model.to_string(),
+        }
+    }
+
+    pub fn source(&self) -> Source {
+        match self {
+            Provenance::Model { .. } => Source::Model,
+            Provenance::Anchor { .. } => Source::Anchor,
+            Provenance::Offline => Source::Offline,
+            Provenance::Server => Source::Server,
+            Provenance::Player => Source::Player,
+            Provenance::Admin => Source::Admin,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Provenance::Model { .. } => "model",
+            Provenance::Anchor { .. } => "anchor",
+            Provenance::Offline => "offline",
+            Provenance::Server => "server",
+            Provenance::Player => "player",
+            Provenance::Admin => "admin",
+        }
+    }
+
+    /// The model or anchor, for provenances that name one
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            Provenance::Model { model } => Some(model),
+            Provenance::Anchor { anchor } => Some(anchor),
+            _ => None,
+        }
+    }
+
+    /// One word, as export formats tag passages: `model:<name>`, `offline`
+    pub fn tag(&self) -> String {
+        match self.detail() {
+            Some(detail) => format!(
+                "{}:{}",
+                self.label(),
+                detail.split_whitespace().collect::<Vec<_>>().join("-")
+            ),
+            None => self.label().to_string(),
+        }
+    }
+}
+
+impl NarrativeMoment {
+    /// Hand the moment's text to a new writer; its choices stay their
+    /// writers'
+    pub fn rewrite_by(&mut self, provenance: Provenance) {
+        for choice in &mut self.choices {
+            if choice.provenance.is_none() {
+                choice.provenance = self.provenance.clone();
+            }
+        }
+        self.provenance = Some(provenance);
+    }
+
+    /// Provenance of the offered choice `choice_id`, or the player's for
+    /// text they typed. `None` for moments from before provenance was kept.
+    pub fn provenance_of(&self, choice_id: &str) -> Option<Provenance> {
+        match self.choices.iter().find(|c| c.id == choice_id) {
+            Some(choice) => choice
+                .provenance
+                .clone()
+                .or_else(|| self.provenance.clone()),
+            None => Some(Provenance::Player),
+        }
+    }
+}
+
+/// Which provenances a report covers; all when empty
+#[derive(Debug, Default, Deserialize)]
+pub struct Filter {
+    pub source: Option<Source>,
+    /// Model or anchor name
+    pub name: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, provenance: &Provenance) -> bool {
+        self.source
+            .is_none_or(|source| provenance.source() == source)
+            && self
+                .name
+                .as_deref()
+                .is_none_or(|name| provenance.detail() == Some(name))
+    }
+}
+
+/// How the content of one provenance was taken
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Row {
+    pub provenance: Provenance,
+    pub moments: u64,
+    /// Choices offered in moments, by their own provenance
+    pub choices_offered: u64,
+    pub choices_made: u64,
+    pub dark_choices: u64,
+    /// Share of the choices made that were dark
+    pub dark_rate: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub provenances: Vec<Row>,
+    /// Moments and choices from before provenance was kept
+    pub untracked_moments: u64,
+    pub untracked_choices: u64,
+}
+
+#[derive(Default)]
+struct Counts {
+    moments: u64,
+    choices_offered: u64,
+    choices_made: u64,
+    dark_choices: u64,
+}
+
+/// Counts by provenance, fed moment by moment and choice by choice
+#[derive(Default)]
+pub struct Tally {
+    counts: HashMap<Provenance, Counts>,
+    untracked_moments: u64,
+    untracked_choices: u64,
+}
+
+impl Tally {
+    pub fn moment(&mut self, moment: &NarrativeMoment) {
+        match &moment.provenance {
+            Some(provenance) => self.counts.entry(provenance.clone()).or_default().moments += 1,
+            None => self.untracked_moments += 1,
+        }
+        for choice in &moment.choices {
+            if let Some(provenance) = moment.provenance_of(&choice.id) {
+                self.counts.entry(provenance).or_default().choices_offered += 1;
+            }
+        }
+    }
+
+    pub fn choice(&mut self, record: &ChoiceRecord) {
+        let Some(provenance) = &record.provenance else {
+            self.untracked_choices += 1;
+            return;
+        };
+        let counts = self.counts.entry(provenance.clone()).or_default();
+        counts.choices_made += 1;
+        counts.dark_choices += record.is_dark as u64;
+    }
+
+    /// Rows matching `filter`, most moments first
+    pub fn report(self, filter: &Filter) -> Report {
+        let mut provenances: Vec<Row> = self
+            .counts
+            .into_iter()
+            .filter(|(provenance, _)| filter.matches(provenance))
+            .map(|(provenance, counts)| Row {
+                provenance,
+                moments: counts.moments,
+                choices_offered: counts.choices_offered,
+                choices_made: counts.choices_made,
+                dark_choices: counts.dark_choices,
+                dark_rate: if counts.choices_made == 0 {
+                    0.0
+                } else {
+                    counts.dark_choices as f64 / counts.choices_made as f64
+                },
+            })
+            .collect();
+        provenances.sort_by(|a, b| {
+            b.moments
+                .cmp(&a.moments)
+                .then(b.choices_made.cmp(&a.choices_made))
+                .then_with(|| a.provenance.cmp(&b.provenance))
+        });
+        Report {
+            provenances,
+            untracked_moments: self.untracked_moments,
+            untracked_choices: self.untracked_choices,
+        }
+    }
+}
+
+/// Tally every run of `players`: moments in play and archived, and the
+/// choices logged
+pub fn report(players: &[Player], filter: &Filter) -> Report {
+    let mut tally = Tally::default();
+    for player in players {
+        for run in player.all_runs() {
+            let run_id = run.run_id.unwrap_or(player.id);
+            run.narrative_history.iter().for_each(|m| tally.moment(m));
+            match persistence::load_archived_loops(&run_id) {
+                Ok(archives) => archives
+                    .iter()
+                    .flat_map(|a| &a.moments)
+                    .for_each(|m| tally.moment(m)),
+                Err(e) => tracing::warn!("Provenance report skipped archives of {}: {}", run_id, e),
+            }
+            match consequences::choice_log(&run_id) {
+                Ok(records) => records.iter().for_each(|r| tally.choice(r)),
+                Err(e) => tracing::warn!("Provenance report skipped choices of {}: {}", run_id, e),
+            }
+        }
+    }
+    tally.report(filter)
+}
+
+#[cfg(test)]
+mod tests;
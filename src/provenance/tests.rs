@@ -0,0 +1,92 @@
+use super::*;
+use chrono::Utc;
+
+use crate::testing;
+
+fn written_by(provenance: Provenance) -> NarrativeMoment {
+    NarrativeMoment {
+        provenance: Some(provenance),
+        ..testing::moment("The door hums.", &[("open", "Open it"), ("leave", "Leave")])
+    }
+}
+
+fn made(choice_id: &str, is_dark: bool, provenance: Option<Provenance>) -> ChoiceRecord {
+    ChoiceRecord {
+        loop_number: 1,
+        choice_id: choice_id.to_string(),
+        choice_text: choice_id.to_string(),
+        is_dark,
+        score_delta: 0,
+        nihilism_score: None,
+        at: Utc::now(),
+        provenance,
+    }
+}
+
+#[test]
+fn choices_belong_to_their_moments_writer_until_rewritten() {
+    let model = Provenance::model("qwen 2.5");
+    let mut moment = written_by(model.clone());
+    assert_eq!(moment.provenance_of("open"), Some(model.clone()));
+    assert_eq!(
+        moment.provenance_of("typed by hand"),
+        Some(Provenance::Player)
+    );
+
+    moment.rewrite_by(Provenance::Admin);
+    assert_eq!(moment.provenance, Some(Provenance::Admin));
+    assert_eq!(moment.provenance_of("leave"), Some(model.clone()));
+    assert_eq!(model.tag(), "model:qwen-2.5");
+    assert_eq!(Provenance::Offline.tag(), "offline");
+
+    let untracked = testing::moment("Old save.", &[("open", "Open it")]);
+    assert_eq!(untracked.provenance_of("open"), None);
+}
+
+#[test]
+fn the_report_counts_by_provenance_and_filters() {
+    let anchor = Provenance::Anchor {
+        anchor: "the-door".to_string(),
+    };
+    let model = Provenance::model("local");
+    let mut tally = Tally::default();
+    tally.moment(&written_by(anchor.clone()));
+    tally.moment(&written_by(model.clone()));
+    tally.moment(&written_by(model.clone()));
+    tally.moment(&testing::moment("Old save.", &[]));
+    tally.choice(&made("open", true, Some(anchor.clone())));
+    tally.choice(&made("leave", false, Some(anchor.clone())));
+    tally.choice(&made("shout", true, Some(Provenance::Player)));
+    tally.choice(&made("open", false, None));
+
+    let report = tally.report(&Filter::default());
+    let sources: Vec<&str> = report
+        .provenances
+        .iter()
+        .map(|r| r.provenance.label())
+        .collect();
+    assert_eq!(sources, ["model", "anchor", "player"]);
+    assert_eq!((report.untracked_moments, report.untracked_choices), (1, 1));
+
+    let mut tally = Tally::default();
+    tally.moment(&written_by(anchor.clone()));
+    tally.moment(&written_by(model));
+    tally.choice(&made("open", true, Some(anchor.clone())));
+    tally.choice(&made("leave", false, Some(anchor.clone())));
+    let filter = Filter {
+        source: Some(Source::Anchor),
+        name: Some("the-door".to_string()),
+    };
+    let report = tally.report(&filter);
+    assert_eq!(
+        report.provenances,
+        [Row {
+            provenance: anchor,
+            moments: 1,
+            choices_offered: 2,
+            choices_made: 2,
+            dark_choices: 1,
+            dark_rate: 0.5,
+        }]
+    );
+}
@@ -0,0 +1,651 @@
+//! Push notifications for players away from the game.
+//!
+//! With `PUSH_ENABLED`, a player can register one target: a browser's Web
+//! Push subscription, an ntfy topic or a Gotify application. The
+//! `push_notifications` job then sends, to players who opted in, at most one
+//! reminder a day that the loop remembers them, word of each new daily
+//! challenge and of races about to start. Nothing is sent in a player's quiet
+//! hours. Targets that report themselves gone are dropped, and every
+//! notification carries a link that unsubscribes.
+
+mod webpush;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Timelike, Utc};
+use rand::Rng;
+use reqwest::{redirect, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::challenge::{self, Challenge};
+use crate::config::Config;
+use crate::game::GameState;
+use crate::persistence;
+use crate::race::RaceRooms;
+use crate::tenant;
+use crate::theme::{self, Flavor};
+
+const PUSH_FILE: &str = "data/push.json";
+/// Generated on first use; browsers subscribe with its public half
+const VAPID_KEY_FILE: &str = "data/vapid.key";
+/// Races are announced this long before they start
+const RACE_NOTICE_SECS: i64 = 60;
+/// Players active more recently than this get no reminder
+const REMINDER_IDLE_HOURS: i64 = 20;
+const MAX_TOPIC_LEN: usize = 64;
+/// Furthest a local time may be from UTC, in minutes
+const MAX_UTC_OFFSET: i32 = 14 * 60;
+
+/// Where a player's notifications go
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Target {
+    /// A browser's `PushSubscription`, as its `toJSON()` gives it
+    WebPush { endpoint: String, keys: WebPushKeys },
+    /// A topic on `NTFY_URL`
+    Ntfy { topic: String },
+    /// A Gotify server and an application token on it
+    Gotify { url: String, token: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WebPushKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+impl Target {
+    fn kind(&self) -> &'static str {
+        match self {
+            Target::WebPush { .. } => "web_push",
+            Target::Ntfy { .. } => "ntfy",
+            Target::Gotify { .. } => "gotify",
+        }
+    }
+
+    fn check(&self) -> Result<(), PushError> {
+        let https =
+            |url: &str| Url::parse(url).is_ok_and(|u| u.scheme() == "https" && u.has_host());
+        match self {
+            Target::WebPush { endpoint, keys } => {
+                if !https(endpoint) {
+                    return Err(PushError::Invalid("endpoint must be an https URL"));
+                }
+                let p256dh = webpush::decode(&keys.p256dh);
+                let auth = webpush::decode(&keys.auth);
+                if p256dh.is_none_or(|k| k.len() != webpush::PUBLIC_KEY_LEN)
+                    || auth.is_none_or(|k| k.len() != webpush::AUTH_LEN)
+                {
+                    return Err(PushError::Invalid(
+                        "keys are not a P-256 key and auth secret",
+                    ));
+                }
+            }
+            Target::Ntfy { topic } => {
+                let valid = !topic.is_empty()
+                    && topic.len() <= MAX_TOPIC_LEN
+                    && topic
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                if !valid {
+                    return Err(PushError::Invalid(
+                        "topic must be up to 64 letters, digits, '_' or '-'",
+                    ));
+                }
+            }
+            Target::Gotify { url, token } => {
+                if !https(url) {
+                    return Err(PushError::Invalid("url must be an https URL"));
+                }
+                if token.trim().is_empty() {
+                    return Err(PushError::Invalid("token is required"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Kinds of notification a player opted into; none unless asked for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Topics {
+    /// "The loop remembers you", once a day on days the player didn't play
+    pub daily: bool,
+    /// Each new daily challenge
+    pub challenge: bool,
+    /// A race the player is in is about to start
+    pub race: bool,
+}
+
+/// A stretch of the player's day in which nothing is sent
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Whether `time` falls inside; the hours may run past midnight
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// What a player sends to register
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PushRequest {
+    pub target: Target,
+    #[serde(default)]
+    pub topics: Topics,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// Minutes the player's clock is ahead of UTC
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Registration {
+    target: Target,
+    topics: Topics,
+    quiet_hours: Option<QuietHours>,
+    utc_offset_minutes: i32,
+    registered_at: DateTime<Utc>,
+    /// Secret of the unsubscribe link
+    unsubscribe: String,
+    /// Local day of the last reminder
+    last_daily: Option<NaiveDate>,
+    /// Day of the last challenge announced
+    last_challenge: Option<NaiveDate>,
+}
+
+impl Registration {
+    fn local(&self, now: DateTime<Utc>) -> chrono::NaiveDateTime {
+        now.naive_utc() + Duration::minutes(self.utc_offset_minutes as i64)
+    }
+
+    fn is_quiet(&self, now: DateTime<Utc>) -> bool {
+        self.quiet_hours
+            .is_some_and(|quiet| quiet.contains(self.local(now).time()))
+    }
+}
+
+/// A registration as its player sees it; the target's secrets stay hidden
+#[derive(Debug, Serialize)]
+pub struct PushView {
+    pub kind: &'static str,
+    pub topics: Topics,
+    pub quiet_hours: Option<QuietHours>,
+    pub utc_offset_minutes: i32,
+    pub registered_at: DateTime<Utc>,
+}
+
+impl From<&Registration> for PushView {
+    fn from(registration: &Registration) -> Self {
+        Self {
+            kind: registration.target.kind(),
+            topics: registration.topics,
+            quiet_hours: registration.quiet_hours,
+            utc_offset_minutes: registration.utc_offset_minutes,
+            registered_at: registration.registered_at,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PushError {
+    #[error("{0}")]
+    Invalid(&'static str),
+    #[error("web push is unavailable on this server")]
+    NoWebPush,
+    #[error(transparent)]
+    Storage(#[from] anyhow::Error),
+}
+
+/// What a notification is about
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoticeKind {
+    Daily,
+    Challenge,
+    Race,
+}
+
+/// One notification, as Web Push payloads carry it
+#[derive(Clone, Debug, Serialize)]
+pub struct Notice {
+    pub kind: NoticeKind,
+    pub title: String,
+    pub body: String,
+    /// Where opening the notification leads
+    pub url: String,
+    pub unsubscribe_url: String,
+}
+
+/// How a target took a notification
+#[derive(Debug, PartialEq)]
+enum Delivery {
+    Sent,
+    /// The target no longer exists; its registration is dropped
+    Gone,
+}
+
+/// Registrations, persisted to `data/push.json`, and the means to reach them
+pub struct PushBridge {
+    path: PathBuf,
+    registrations: Mutex<BTreeMap<Uuid, Registration>>,
+    vapid: Option<webpush::Vapid>,
+    client: reqwest::Client,
+    tenant: Option<String>,
+    ntfy_url: String,
+    /// Hosts Gotify targets may name, so players can't point the server at
+    /// its own network
+    gotify_hosts: Vec<String>,
+    /// Push services Web Push endpoints may name, for the same reason
+    web_push_hosts: Vec<String>,
+    public_url: String,
+    daily_hour: u32,
+    /// Races starting up to here have been announced
+    race_horizon: Mutex<DateTime<Utc>>,
+}
+
+impl PushBridge {
+    pub fn load(config: &Config) -> Self {
+        let tenant = config.tenant.as_deref();
+        let path = tenant::data_path(tenant, PUSH_FILE);
+        let registrations = Self::read(&path).unwrap_or_else(|e| {
+            tracing::warn!(
+                "Failed to load push registrations from {}: {}",
+                path.display(),
+                e
+            );
+            BTreeMap::new()
+        });
+        let vapid = config
+            .push_enabled
+            .then(|| {
+                let contact = config.push_contact.as_ref().unwrap_or(&config.public_url);
+                webpush::Vapid::load(&tenant::data_path(tenant, VAPID_KEY_FILE), contact)
+            })
+            .and_then(|loaded| {
+                loaded
+                    .inspect_err(|e| tracing::error!("Web push is unavailable: {:#}", e))
+                    .ok()
+            });
+        Self {
+            path,
+            registrations: Mutex::new(registrations),
+            vapid,
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                // A listed host must not pass requests on somewhere else
+                .redirect(redirect::Policy::none())
+                .build()
+                .unwrap_or_default(),
+            tenant: config.tenant.clone(),
+            ntfy_url: config.ntfy_url.clone(),
+            gotify_hosts: config.gotify_hosts.clone(),
+            web_push_hosts: config.web_push_hosts.clone(),
+            public_url: config.public_url.clone(),
+            daily_hour: config.push_daily_hour,
+            race_horizon: Mutex::new(Utc::now()),
+        }
+    }
+
+    fn read(path: &Path) -> Result<BTreeMap<Uuid, Registration>> {
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn registrations(&self) -> std::sync::MutexGuard<'_, BTreeMap<Uuid, Registration>> {
+        self.registrations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, registrations: &BTreeMap<Uuid, Registration>) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(registrations)?)?;
+        Ok(())
+    }
+
+    /// The key browsers subscribe with, when web push is available
+    pub fn vapid_public_key(&self) -> Option<String> {
+        self.vapid.as_ref().map(|vapid| vapid.public_key())
+    }
+
+    /// Whether the server sends to `target`: Web Push endpoints only on the
+    /// operator's `WEB_PUSH_HOSTS` and Gotify servers only on `GOTIFY_HOSTS`.
+    /// Addresses given as IPs never are.
+    fn allows(&self, target: &Target) -> bool {
+        let listed = |url: &str, hosts: &[String]| {
+            Url::parse(url)
+                .ok()
+                .and_then(|url| url.domain().map(str::to_lowercase))
+                .is_some_and(|host| {
+                    hosts.iter().any(|listed| {
+                        host == *listed
+                            || host
+                                .strip_suffix(listed.as_str())
+                                .is_some_and(|sub| sub.ends_with('.'))
+                    })
+                })
+        };
+        match target {
+            Target::WebPush { endpoint, .. } => listed(endpoint, &self.web_push_hosts),
+            Target::Gotify { url, .. } => listed(url, &self.gotify_hosts),
+            Target::Ntfy { .. } => true,
+        }
+    }
+
+    /// Register `player_id`, replacing any earlier registration
+    pub fn register(&self, player_id: Uuid, request: PushRequest) -> Result<PushView, PushError> {
+        request.target.check()?;
+        if !self.allows(&request.target) {
+            return Err(PushError::Invalid(match request.target {
+                Target::WebPush { .. } => "endpoint is not a push service this server sends to",
+                _ => "url is not a Gotify server this server sends to",
+            }));
+        }
+        if matches!(request.target, Target::WebPush { .. }) && self.vapid.is_none() {
+            return Err(PushError::NoWebPush);
+        }
+        if request.utc_offset_minutes.abs() > MAX_UTC_OFFSET {
+            return Err(PushError::Invalid(
+                "utc_offset_minutes must be within 14 hours",
+            ));
+        }
+        let bytes: [u8; 16] = rand::rng().random();
+        let registration = Registration {
+            target: request.target,
+            topics: request.topics,
+            quiet_hours: request.quiet_hours.filter(|q| q.start != q.end),
+            utc_offset_minutes: request.utc_offset_minutes,
+            registered_at: Utc::now(),
+            unsubscribe: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            last_daily: None,
+            // Today's challenge is already out; the next one is news
+            last_challenge: Some(challenge::today()),
+        };
+        let view = PushView::from(&registration);
+        let mut registrations = self.registrations();
+        registrations.insert(player_id, registration);
+        self.save(&registrations)?;
+        Ok(view)
+    }
+
+    pub fn view(&self, player_id: &Uuid) -> Option<PushView> {
+        self.registrations().get(player_id).map(PushView::from)
+    }
+
+    /// Drop the player's registration; false if there was none
+    pub fn unregister(&self, player_id: &Uuid) -> Result<bool> {
+        let mut registrations = self.registrations();
+        if registrations.remove(player_id).is_none() {
+            return Ok(false);
+        }
+        self.save(&registrations)?;
+        Ok(true)
+    }
+
+    /// Drop the registration an unsubscribe link was made for
+    pub fn unsubscribe(&self, token: &str) -> Result<bool> {
+        let player_id = self
+            .registrations()
+            .iter()
+            .find(|(_, r)| !token.is_empty() && r.unsubscribe == token)
+            .map(|(id, _)| *id);
+        match player_id {
+            Some(id) => self.unregister(&id),
+            None => Ok(false),
+        }
+    }
+
+    fn notice(&self, kind: NoticeKind, body: String, registration: &Registration) -> Notice {
+        Notice {
+            kind,
+            title: "Nihilism".to_string(),
+            body,
+            url: self.public_url.clone(),
+            unsubscribe_url: format!(
+                "{}/?unsubscribe={}",
+                self.public_url, registration.unsubscribe
+            ),
+        }
+    }
+
+    /// Notifications due at `now`, marking daily and challenge ones as sent
+    async fn due(
+        &self,
+        now: DateTime<Utc>,
+        game: &RwLock<GameState>,
+        races: &RaceRooms,
+    ) -> Vec<(Uuid, Notice)> {
+        let tenant = self.tenant.as_deref();
+        let mut due = Vec::new();
+
+        let from = std::mem::replace(
+            &mut *self.race_horizon.lock().unwrap_or_else(|e| e.into_inner()),
+            now + Duration::seconds(RACE_NOTICE_SECS),
+        );
+        for (_, scenario, racers) in races.starting(from, now + Duration::seconds(RACE_NOTICE_SECS))
+        {
+            let registrations = self.registrations();
+            for player_id in racers {
+                if let Some(registration) = registrations
+                    .get(&player_id)
+                    .filter(|r| r.topics.race && !r.is_quiet(now))
+                {
+                    let body =
+                        theme::text(tenant, Flavor::PushRace).replace("{modifier}", scenario);
+                    due.push((player_id, self.notice(NoticeKind::Race, body, registration)));
+                }
+            }
+        }
+
+        let today = now.date_naive();
+        let challenge = Challenge::for_date(today);
+        let candidates: Vec<(Uuid, Registration)> = self
+            .registrations()
+            .iter()
+            .filter(|(_, r)| !r.is_quiet(now))
+            .map(|(id, r)| (*id, r.clone()))
+            .collect();
+        let mut marked = Vec::new();
+        for (player_id, registration) in candidates {
+            if registration.topics.challenge && registration.last_challenge != Some(today) {
+                let body = theme::text(tenant, Flavor::PushChallenge)
+                    .replace("{modifier}", challenge.modifier.name);
+                due.push((
+                    player_id,
+                    self.notice(NoticeKind::Challenge, body, &registration),
+                ));
+                marked.push((player_id, NoticeKind::Challenge, today));
+            }
+            let local = registration.local(now);
+            if registration.topics.daily
+                && registration.last_daily != Some(local.date())
+                && local.hour() >= self.daily_hour
+                && idle(game, player_id, now).await
+            {
+                let body = theme::text(tenant, Flavor::PushReminder);
+                due.push((
+                    player_id,
+                    self.notice(NoticeKind::Daily, body, &registration),
+                ));
+                marked.push((player_id, NoticeKind::Daily, local.date()));
+            }
+        }
+
+        if !marked.is_empty() {
+            let mut registrations = self.registrations();
+            for (player_id, kind, date) in marked {
+                if let Some(registration) = registrations.get_mut(&player_id) {
+                    match kind {
+                        NoticeKind::Challenge => registration.last_challenge = Some(date),
+                        _ => registration.last_daily = Some(date),
+                    }
+                }
+            }
+            if let Err(e) = self.save(&registrations) {
+                tracing::warn!("Failed to save push registrations: {}", e);
+            }
+        }
+        due
+    }
+
+    /// Send whatever is due; run by the `push_notifications` job
+    pub async fn send_due(&self, game: &RwLock<GameState>, races: &RaceRooms) -> Result<()> {
+        let due = self.due(Utc::now(), game, races).await;
+        let mut gone = Vec::new();
+        for (player_id, notice) in &due {
+            let Some(target) = self
+                .registrations()
+                .get(player_id)
+                .map(|r| r.target.clone())
+            else {
+                continue;
+            };
+            match self.deliver(&target, notice).await {
+                Ok(Delivery::Sent) => {}
+                Ok(Delivery::Gone) => gone.push(*player_id),
+                Err(e) => tracing::warn!(
+                    "Failed to push a {:?} notice to player {}: {:#}",
+                    notice.kind,
+                    player_id,
+                    e
+                ),
+            }
+        }
+        for player_id in &gone {
+            tracing::info!("Push target of player {} is gone; unregistered", player_id);
+            self.unregister(player_id)?;
+        }
+        tracing::debug!("Sent {} push notifications", due.len() - gone.len());
+        Ok(())
+    }
+
+    async fn deliver(&self, target: &Target, notice: &Notice) -> Result<Delivery> {
+        // Registered before the operator took the host off its list
+        if !self.allows(target) {
+            return Ok(Delivery::Gone);
+        }
+        let response = match target {
+            Target::WebPush { endpoint, keys } => {
+                let vapid = self
+                    .vapid
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("no VAPID key"))?;
+                let endpoint = Url::parse(endpoint)?;
+                let (Some(p256dh), Some(auth)) =
+                    (webpush::decode(&keys.p256dh), webpush::decode(&keys.auth))
+                else {
+                    anyhow::bail!("undecodable subscription keys");
+                };
+                let body = webpush::encrypt(&p256dh, &auth, &serde_json::to_vec(notice)?)?;
+                let ttl = match notice.kind {
+                    NoticeKind::Race => RACE_NOTICE_SECS,
+                    _ => Duration::hours(12).num_seconds(),
+                };
+                self.client
+                    .post(endpoint.clone())
+                    .header("Authorization", vapid.authorization(&endpoint)?)
+                    .header("TTL", ttl.to_string())
+                    .header("Content-Encoding", "aes128gcm")
+                    .header("Content-Type", "application/octet-stream")
+                    .body(body)
+                    .send()
+                    .await?
+            }
+            Target::Ntfy { topic } => {
+                self.client
+                    .post(&self.ntfy_url)
+                    .json(&json!({
+                        "topic": topic,
+                        "title": notice.title,
+                        "message": notice.body,
+                        "click": notice.url,
+                        "actions": [{
+                            "action": "view",
+                            "label": "Unsubscribe",
+                            "url": notice.unsubscribe_url,
+                        }],
+                    }))
+                    .send()
+                    .await?
+            }
+            Target::Gotify { url, token } => {
+                let message = format!("{}\n\nUnsubscribe: {}", notice.body, notice.unsubscribe_url);
+                self.client
+                    .post(format!("{}/message", url.trim_end_matches('/')))
+                    .header("X-Gotify-Key", token)
+                    .json(&json!({
+                        "title": notice.title,
+                        "message": message,
+                        "extras": { "client::notification": { "click": { "url": notice.url } } },
+                    }))
+                    .send()
+                    .await?
+            }
+        };
+        delivery(target, response.status())
+    }
+}
+
+/// What a target's answer says about it
+fn delivery(target: &Target, status: StatusCode) -> Result<Delivery> {
+    let gone = match target {
+        // The browser unsubscribed, or the subscription expired
+        Target::WebPush { .. } => matches!(status.as_u16(), 404 | 410),
+        // The application was deleted
+        Target::Gotify { .. } => matches!(status.as_u16(), 401 | 403),
+        Target::Ntfy { .. } => false,
+    };
+    if gone {
+        return Ok(Delivery::Gone);
+    }
+    if !status.is_success() {
+        anyhow::bail!("{} answered {}", target.kind(), status);
+    }
+    Ok(Delivery::Sent)
+}
+
+/// Whether the player hasn't played for `REMINDER_IDLE_HOURS`. Players
+/// whose save is gone aren't idle; they are gone.
+async fn idle(game: &RwLock<GameState>, player_id: Uuid, now: DateTime<Utc>) -> bool {
+    let in_memory = game
+        .read()
+        .await
+        .get_player(&player_id)
+        .map(|p| p.run.last_active_at);
+    let last_active = match in_memory {
+        Some(last_active) => last_active,
+        None => match persistence::load_player(&player_id) {
+            Ok(Some(player)) => player.run.last_active_at,
+            Ok(None) => return false,
+            Err(e) => {
+                tracing::warn!("Failed to load player {} for a reminder: {}", player_id, e);
+                return false;
+            }
+        },
+    };
+    last_active.is_none_or(|at| now - at >= Duration::hours(REMINDER_IDLE_HOURS))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,164 @@
+use super::*;
+use crate::game::Player;
+
+fn bridge() -> PushBridge {
+    let config = Config::for_tests("http://localhost:1/v1");
+    let dir = std::env::temp_dir().join(format!("nihilism-push-{}", Uuid::new_v4()));
+    PushBridge {
+        path: dir.join("push.json"),
+        registrations: Mutex::new(BTreeMap::new()),
+        vapid: None,
+        client: reqwest::Client::new(),
+        tenant: None,
+        ntfy_url: config.ntfy_url,
+        gotify_hosts: vec!["gotify.example".to_string()],
+        web_push_hosts: config.web_push_hosts,
+        public_url: config.public_url,
+        daily_hour: 19,
+        race_horizon: Mutex::new(Utc::now()),
+    }
+}
+
+fn ntfy(topic: &str) -> PushRequest {
+    PushRequest {
+        target: Target::Ntfy {
+            topic: topic.to_string(),
+        },
+        topics: Topics::default(),
+        quiet_hours: None,
+        utc_offset_minutes: 0,
+    }
+}
+
+#[test]
+fn targets_are_checked_before_registering() {
+    let push = bridge();
+    let player_id = Uuid::new_v4();
+    let invalid = |result| matches!(result, Err(PushError::Invalid(_)));
+
+    assert!(invalid(push.register(player_id, ntfy("no spaces"))));
+    assert!(invalid(push.register(player_id, ntfy(""))));
+    let far = PushRequest {
+        utc_offset_minutes: 15 * 60,
+        ..ntfy("loop")
+    };
+    assert!(invalid(push.register(player_id, far)));
+    let plain = PushRequest {
+        target: Target::Gotify {
+            url: "http://gotify.example".to_string(),
+            token: "secret".to_string(),
+        },
+        ..ntfy("loop")
+    };
+    assert!(invalid(push.register(player_id, plain)));
+    // Only the operator's Gotify hosts, not whatever a player names
+    let gotify = |url: &str| PushRequest {
+        target: Target::Gotify {
+            url: url.to_string(),
+            token: "secret".to_string(),
+        },
+        ..ntfy("loop")
+    };
+    for url in [
+        "https://127.0.0.1",
+        "https://localhost:8080",
+        "https://10.0.0.2",
+        "https://evil.example",
+    ] {
+        assert!(invalid(push.register(player_id, gotify(url))));
+    }
+    assert!(push.register(player_id, gotify("https://Gotify.example/")).is_ok());
+    push.unregister(&player_id).unwrap();
+    let browser = |endpoint: &str| PushRequest {
+        target: Target::WebPush {
+            endpoint: endpoint.to_string(),
+            keys: WebPushKeys {
+                p256dh: format!("B{}", "A".repeat(86)),
+                auth: "A".repeat(22),
+            },
+        },
+        ..ntfy("loop")
+    };
+    // Only the push services of browsers, not any https endpoint
+    for endpoint in [
+        "https://push.example/send/1",
+        "https://169.254.169.254/latest",
+        "https://[::1]/send/1",
+        "https://fcm.googleapis.com.evil.example/send/1",
+    ] {
+        assert!(invalid(push.register(player_id, browser(endpoint))));
+    }
+    // Well formed, but this server has no VAPID key
+    assert!(matches!(
+        push.register(player_id, browser("https://wns2-par02p.notify.windows.com/w/?token=1")),
+        Err(PushError::NoWebPush)
+    ));
+    assert!(push.view(&player_id).is_none());
+
+    let view = push.register(player_id, ntfy("my-loop_1")).unwrap();
+    assert_eq!(view.kind, "ntfy");
+    let token = push.registrations()[&player_id].unsubscribe.clone();
+    assert!(!push.unsubscribe("").unwrap());
+    assert!(push.unsubscribe(&token).unwrap());
+    assert!(push.view(&player_id).is_none());
+}
+
+#[tokio::test]
+async fn notices_wait_for_quiet_hours_and_idle_players() {
+    let push = bridge();
+    let races = RaceRooms::new();
+    let today = Utc::now().date_naive();
+    let at = |hour: u32| today.and_hms_opt(hour, 0, 0).unwrap().and_utc();
+
+    let mut player = Player::new();
+    player.run.last_active_at = Some(at(19));
+    let player_id = player.id;
+    let mut state = GameState::new(None);
+    state.players.insert(player_id, player);
+    let game = RwLock::new(state);
+
+    let request = PushRequest {
+        topics: Topics {
+            daily: true,
+            challenge: true,
+            race: true,
+        },
+        quiet_hours: Some(QuietHours {
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+        }),
+        ..ntfy("loop")
+    };
+    push.register(player_id, request).unwrap();
+    push.registrations()
+        .get_mut(&player_id)
+        .unwrap()
+        .last_challenge = today.pred_opt();
+
+    let kinds = |due: Vec<(Uuid, Notice)>| -> Vec<NoticeKind> {
+        due.into_iter().map(|(_, notice)| notice.kind).collect()
+    };
+    // Quiet hours run past midnight
+    assert!(kinds(push.due(at(23), &game, &races).await).is_empty());
+    assert!(kinds(push.due(at(3), &game, &races).await).is_empty());
+    // Played an hour ago: no reminder yet, and the challenge only once
+    assert_eq!(
+        kinds(push.due(at(20), &game, &races).await),
+        vec![NoticeKind::Challenge]
+    );
+    assert!(kinds(push.due(at(20), &game, &races).await).is_empty());
+
+    game.write()
+        .await
+        .players
+        .get_mut(&player_id)
+        .unwrap()
+        .run
+        .last_active_at = Some(at(19) - Duration::days(1));
+    let due = push.due(at(21), &game, &races).await;
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].1.kind, NoticeKind::Daily);
+    assert_eq!(due[0].1.body, "The loop remembers you.");
+    assert!(due[0].1.unsubscribe_url.contains("/?unsubscribe="));
+    assert!(kinds(push.due(at(21), &game, &races).await).is_empty());
+}
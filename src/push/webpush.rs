@@ -0,0 +1,160 @@
+//! Web Push as browsers expect it: payloads encrypted to the subscription's
+//! keys (RFC 8291, `aes128gcm`) and requests signed with the server's VAPID
+//! key (RFC 8292).
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use ring::aead::{AES_128_GCM, Aad, LessSafeKey, Nonce, UnboundKey};
+use ring::agreement::{self, ECDH_P256, EphemeralPrivateKey, UnparsedPublicKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair};
+use serde_json::json;
+use sha2::Sha256;
+use std::fs;
+use std::path::Path;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Bytes of an uncompressed P-256 public key
+pub const PUBLIC_KEY_LEN: usize = 65;
+/// Bytes of a subscription's auth secret
+pub const AUTH_LEN: usize = 16;
+/// Record size announced in the header; payloads always fit one record
+const RECORD_SIZE: u32 = 4096;
+/// Hours a VAPID signature stays valid; push services accept at most 24
+const VAPID_HOURS: i64 = 12;
+
+/// Decode base64url, with or without padding, as browsers hand keys out
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(text.trim().trim_end_matches('='))
+        .ok()
+}
+
+fn encode(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// HKDF-SHA256 for outputs of at most one block
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+    let mut extract = HmacSha256::new_from_slice(salt).expect("HMAC accepts keys of any length");
+    extract.update(ikm);
+    let prk = extract.finalize().into_bytes();
+    let mut expand = HmacSha256::new_from_slice(&prk).expect("HMAC accepts keys of any length");
+    expand.update(info);
+    expand.update(&[1]);
+    expand.finalize().into_bytes()[..len].to_vec()
+}
+
+/// Encrypt `plaintext` for a subscription with public key `p256dh` and
+/// secret `auth`, as the body of one push message
+pub fn encrypt(p256dh: &[u8], auth: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let rng = SystemRandom::new();
+    let private = EphemeralPrivateKey::generate(&ECDH_P256, &rng)
+        .map_err(|_| anyhow::anyhow!("failed to generate an ECDH key"))?;
+    let public = private
+        .compute_public_key()
+        .map_err(|_| anyhow::anyhow!("failed to compute an ECDH public key"))?;
+    let shared = agreement::agree_ephemeral(
+        private,
+        &UnparsedPublicKey::new(&ECDH_P256, p256dh),
+        |secret| secret.to_vec(),
+    )
+    .map_err(|_| anyhow::anyhow!("subscription key is not a P-256 point"))?;
+    let mut salt = [0u8; 16];
+    rng.fill(&mut salt)
+        .map_err(|_| anyhow::anyhow!("failed to draw a salt"))?;
+    let public = public.as_ref();
+
+    let key_info = [b"WebPush: info\0".as_slice(), p256dh, public].concat();
+    let ikm = hkdf(auth, &shared, &key_info, 32);
+    let cek = hkdf(&salt, &ikm, b"Content-Encoding: aes128gcm\0", 16);
+    let nonce = hkdf(&salt, &ikm, b"Content-Encoding: nonce\0", 12);
+
+    let key = LessSafeKey::new(
+        UnboundKey::new(&AES_128_GCM, &cek).map_err(|_| anyhow::anyhow!("bad content key"))?,
+    );
+    let nonce =
+        Nonce::try_assume_unique_for_key(&nonce).map_err(|_| anyhow::anyhow!("bad nonce"))?;
+    // A single record, ended by its delimiter
+    let mut record = [plaintext, &[2]].concat();
+    if record.len() + AES_128_GCM.tag_len() > RECORD_SIZE as usize {
+        anyhow::bail!("push payload of {} bytes is too large", plaintext.len());
+    }
+    key.seal_in_place_append_tag(nonce, Aad::empty(), &mut record)
+        .map_err(|_| anyhow::anyhow!("failed to encrypt the push payload"))?;
+
+    let mut body = Vec::with_capacity(21 + public.len() + record.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(public.len() as u8);
+    body.extend_from_slice(public);
+    body.extend_from_slice(&record);
+    Ok(body)
+}
+
+/// The server's VAPID identity
+pub struct Vapid {
+    key: EcdsaKeyPair,
+    /// `mailto:` or `https:` contact push services may reach the operator at
+    subject: String,
+}
+
+impl Vapid {
+    /// Load the key pair from `file`, generating it on first use
+    pub fn load(file: &Path, subject: &str) -> Result<Self> {
+        let rng = SystemRandom::new();
+        let pkcs8 = if file.exists() {
+            fs::read(file).with_context(|| format!("failed to read {}", file.display()))?
+        } else {
+            let document = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+                .map_err(|_| anyhow::anyhow!("failed to generate a VAPID key"))?;
+            if let Some(dir) = file.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(file, document.as_ref())
+                .with_context(|| format!("failed to write {}", file.display()))?;
+            tracing::info!("Generated a VAPID key in {}", file.display());
+            document.as_ref().to_vec()
+        };
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8, &rng)
+            .map_err(|e| anyhow::anyhow!("invalid VAPID key in {}: {}", file.display(), e))?;
+        Ok(Self {
+            key,
+            subject: subject.to_string(),
+        })
+    }
+
+    /// The public key browsers subscribe with, as `applicationServerKey`
+    pub fn public_key(&self) -> String {
+        encode(self.key.public_key().as_ref())
+    }
+
+    /// `Authorization` header for a push to `endpoint`
+    pub fn authorization(&self, endpoint: &Url) -> Result<String> {
+        let header = encode(br#"{"typ":"JWT","alg":"ES256"}"#);
+        let claims = json!({
+            "aud": endpoint.origin().ascii_serialization(),
+            "exp": (Utc::now() + chrono::Duration::hours(VAPID_HOURS)).timestamp(),
+            "sub": self.subject,
+        });
+        let input = format!("{}.{}", header, encode(claims.to_string().as_bytes()));
+        let signature = self
+            .key
+            .sign(&SystemRandom::new(), input.as_bytes())
+            .map_err(|_| anyhow::anyhow!("failed to sign a VAPID token"))?;
+        Ok(format!(
+            "vapid t={}.{}, k={}",
+            input,
+            encode(signature.as_ref()),
+            self.public_key()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests;
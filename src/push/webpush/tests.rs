@@ -0,0 +1,73 @@
+use ring::signature::{ECDSA_P256_SHA256_FIXED, UnparsedPublicKey as Verifier};
+
+use super::*;
+
+/// What the browser holding `private` reads from a push `body`
+fn decrypt(private: EphemeralPrivateKey, p256dh: &[u8], auth: &[u8], body: &[u8]) -> Vec<u8> {
+    let (salt, rest) = body.split_at(16);
+    assert_eq!(rest[..4], RECORD_SIZE.to_be_bytes());
+    let (public, record) = rest[5..].split_at(rest[4] as usize);
+    let shared = agreement::agree_ephemeral(
+        private,
+        &UnparsedPublicKey::new(&ECDH_P256, public),
+        |secret| secret.to_vec(),
+    )
+    .unwrap();
+    let key_info = [b"WebPush: info\0".as_slice(), p256dh, public].concat();
+    let ikm = hkdf(auth, &shared, &key_info, 32);
+    let cek = hkdf(salt, &ikm, b"Content-Encoding: aes128gcm\0", 16);
+    let nonce = hkdf(salt, &ikm, b"Content-Encoding: nonce\0", 12);
+    let key = LessSafeKey::new(UnboundKey::new(&AES_128_GCM, &cek).unwrap());
+    let mut record = record.to_vec();
+    let nonce = Nonce::try_assume_unique_for_key(&nonce).unwrap();
+    let plain = key.open_in_place(nonce, Aad::empty(), &mut record).unwrap();
+    plain.strip_suffix(&[2]).expect("a last record").to_vec()
+}
+
+#[test]
+fn a_browser_can_read_what_is_pushed_to_it() {
+    let rng = SystemRandom::new();
+    let browser = EphemeralPrivateKey::generate(&ECDH_P256, &rng).unwrap();
+    let p256dh = browser.compute_public_key().unwrap().as_ref().to_vec();
+    let auth = [7u8; AUTH_LEN];
+    assert_eq!(p256dh.len(), PUBLIC_KEY_LEN);
+
+    let body = encrypt(&p256dh, &auth, b"The loop remembers you.").unwrap();
+    assert_eq!(
+        decrypt(browser, &p256dh, &auth, &body),
+        b"The loop remembers you."
+    );
+    assert!(encrypt(&[4; PUBLIC_KEY_LEN], &auth, b"").is_err());
+    assert!(encrypt(&p256dh, &auth, &[0; RECORD_SIZE as usize]).is_err());
+}
+
+#[test]
+fn vapid_tokens_are_signed_for_the_push_service() {
+    let file = std::env::temp_dir().join(format!("nihilism-vapid-{}.key", uuid::Uuid::new_v4()));
+    let vapid = Vapid::load(&file, "mailto:ops@example.com").unwrap();
+    // The key is kept, so subscriptions survive restarts
+    let public_key = vapid.public_key();
+    assert_eq!(
+        Vapid::load(&file, "mailto:ops@example.com")
+            .unwrap()
+            .public_key(),
+        public_key
+    );
+
+    let endpoint = Url::parse("https://push.example.net/send/abc?x=1").unwrap();
+    let header = vapid.authorization(&endpoint).unwrap();
+    let (token, key) = header
+        .strip_prefix("vapid t=")
+        .and_then(|rest| rest.split_once(", k="))
+        .unwrap();
+    assert_eq!(key, public_key);
+    let (input, signature) = token.rsplit_once('.').unwrap();
+    let claims: serde_json::Value =
+        serde_json::from_slice(&decode(input.split('.').nth(1).unwrap()).unwrap()).unwrap();
+    assert_eq!(claims["aud"], "https://push.example.net");
+    assert_eq!(claims["sub"], "mailto:ops@example.com");
+    Verifier::new(&ECDSA_P256_SHA256_FIXED, decode(key).unwrap())
+        .verify(input.as_bytes(), &decode(signature).unwrap())
+        .unwrap();
+    let _ = fs::remove_file(file);
+}
@@ -0,0 +1,361 @@
+//! Race mode: several players start fresh runs at the same moment, from the
+//! same seed and scenario, while a public view shows each racer's loop,
+//! score and mood side by side. The view never carries narrative text, so it
+//! can go on a stream without spoiling anyone's run.
+//!
+//! Races live in memory and follow the racers through the event bus. The
+//! first racer to reach an ending wins.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::challenge::{self, Modifier};
+use crate::endings::EndingType;
+use crate::events::{Envelope, EventBus, GameEvent};
+use crate::game::Player;
+
+pub const MAX_RACERS: usize = 8;
+pub const DEFAULT_COUNTDOWN_SECS: i64 = 30;
+pub const MIN_COUNTDOWN_SECS: i64 = 5;
+pub const MAX_COUNTDOWN_SECS: i64 = 600;
+const MAX_NAME_CHARS: usize = 32;
+/// Finished races, and races that never finished, are dropped after this long
+const RACE_TTL_HOURS: i64 = 2;
+
+/// Marks a player as a racer; the seed and scenario are the race's
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RaceRun {
+    pub race_id: Uuid,
+    pub seed: u64,
+    pub modifier: String,
+}
+
+impl RaceRun {
+    /// Prompt section describing the race's scenario
+    pub fn prompt(&self) -> Option<String> {
+        challenge::modifier(&self.modifier).map(|m| format!("RACE - {}:\n{}\n", m.name, m.prompt))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RaceError {
+    NotFound,
+    /// The countdown is over; racers can only join before the start
+    Started,
+    Full,
+}
+
+impl fmt::Display for RaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "no such race"),
+            Self::Started => write!(f, "the race has already started"),
+            Self::Full => write!(f, "the race has {} racers already", MAX_RACERS),
+        }
+    }
+}
+
+impl std::error::Error for RaceError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RaceStatus {
+    Countdown,
+    Running,
+    Finished,
+}
+
+struct Racer {
+    player_id: Uuid,
+    name: Option<String>,
+    loop_number: u64,
+    nihilism_score: i32,
+    mood: Option<String>,
+    choices: u64,
+    ending: Option<EndingType>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+struct Race {
+    seed: u64,
+    modifier: &'static Modifier,
+    created_at: DateTime<Utc>,
+    starts_at: DateTime<Utc>,
+    racers: Vec<Racer>,
+    /// Lane of the first racer to reach an ending
+    winner: Option<usize>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl Race {
+    fn status(&self, now: DateTime<Utc>) -> RaceStatus {
+        if self.finished_at.is_some() {
+            RaceStatus::Finished
+        } else if now < self.starts_at {
+            RaceStatus::Countdown
+        } else {
+            RaceStatus::Running
+        }
+    }
+}
+
+/// A racer as the public sees them: by lane, never by player id, as the id
+/// is all it takes to play someone's run
+#[derive(Clone, Debug, Serialize)]
+pub struct RacerView {
+    pub lane: usize,
+    pub name: Option<String>,
+    pub loop_number: u64,
+    pub nihilism_score: i32,
+    pub mood: Option<String>,
+    pub choices: u64,
+    pub ending: Option<EndingType>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RaceView {
+    pub id: Uuid,
+    pub scenario: &'static str,
+    pub status: RaceStatus,
+    pub created_at: DateTime<Utc>,
+    pub starts_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub winner: Option<usize>,
+    pub racers: Vec<RacerView>,
+}
+
+#[derive(Default)]
+struct Rooms {
+    races: HashMap<Uuid, Race>,
+    /// Race each racing player is in
+    racing: HashMap<Uuid, Uuid>,
+}
+
+pub struct RaceRooms {
+    rooms: Mutex<Rooms>,
+    /// Bumped whenever a race changes, so open streams can report it
+    changes: watch::Sender<u64>,
+}
+
+impl RaceRooms {
+    pub fn new() -> Self {
+        Self {
+            rooms: Mutex::new(Rooms::default()),
+            changes: watch::Sender::new(0),
+        }
+    }
+
+    fn rooms(&self) -> std::sync::MutexGuard<'_, Rooms> {
+        self.rooms.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn notify(&self) {
+        self.changes.send_modify(|n| *n = n.wrapping_add(1));
+    }
+
+    /// Open a race starting `countdown_secs` from now, under `scenario` or a
+    /// modifier drawn from its seed
+    pub fn create(&self, countdown_secs: i64, scenario: Option<&str>) -> Option<RaceView> {
+        let seed = rand::random::<u64>();
+        let modifier = match scenario {
+            Some(id) => challenge::modifier(id)?,
+            None => challenge::modifier_for_seed(seed),
+        };
+        let now = Utc::now();
+        let id = Uuid::new_v4();
+        let race = Race {
+            seed,
+            modifier,
+            created_at: now,
+            starts_at: now + Duration::seconds(countdown_secs),
+            racers: Vec::new(),
+            winner: None,
+            finished_at: None,
+        };
+        let view = view(id, &race, now);
+        self.rooms().races.insert(id, race);
+        Some(view)
+    }
+
+    /// Enter `player_id`, a fresh player, in a race that hasn't started yet.
+    /// Returns the lane and the run marker for the player.
+    pub fn join(&self, race_id: Uuid, player_id: Uuid, name: Option<String>) -> Result<(usize, RaceRun), RaceError> {
+        let mut rooms = self.rooms();
+        let race = rooms.races.get_mut(&race_id).ok_or(RaceError::NotFound)?;
+        if race.status(Utc::now()) != RaceStatus::Countdown {
+            return Err(RaceError::Started);
+        }
+        if race.racers.len() >= MAX_RACERS {
+            return Err(RaceError::Full);
+        }
+        race.racers.push(Racer {
+            player_id,
+            name: name.map(|n| n.trim().chars().take(MAX_NAME_CHARS).collect()),
+            loop_number: 1,
+            nihilism_score: 0,
+            mood: None,
+            choices: 0,
+            ending: None,
+            finished_at: None,
+        });
+        let run = RaceRun {
+            race_id,
+            seed: race.seed,
+            modifier: race.modifier.id.to_string(),
+        };
+        let lane = race.racers.len() - 1;
+        rooms.racing.insert(player_id, race_id);
+        drop(rooms);
+        self.notify();
+        Ok((lane, run))
+    }
+
+    pub fn view(&self, race_id: &Uuid) -> Option<RaceView> {
+        let rooms = self.rooms();
+        rooms.races.get(race_id).map(|race| view(*race_id, race, Utc::now()))
+    }
+
+    /// Whether `player` may play: racers wait for the countdown. A race the
+    /// server no longer knows about doesn't hold anyone back.
+    pub fn has_started(&self, player: &Player) -> bool {
+        let Some(run) = &player.run.race else {
+            return true;
+        };
+        self.rooms()
+            .races
+            .get(&run.race_id)
+            .is_none_or(|race| Utc::now() >= race.starts_at)
+    }
+
+    /// Receiver that changes whenever a race does
+    pub fn changes(&self) -> watch::Receiver<u64> {
+        self.changes.subscribe()
+    }
+
+    /// Follow the racers of this tenant
+    pub fn subscribe(self: &Arc<Self>, events: &EventBus) {
+        let rooms = self.clone();
+        events.spawn_subscriber("race", move |envelope| {
+            let rooms = rooms.clone();
+            async move { rooms.apply(&envelope) }
+        });
+    }
+
+    fn apply(&self, envelope: &Envelope) {
+        let mut rooms = self.rooms();
+        let Some(race_id) = rooms.racing.get(&envelope.event.player_id()).copied() else {
+            return;
+        };
+        let Some(race) = rooms.races.get_mut(&race_id) else {
+            return;
+        };
+        let Some(lane) = race
+            .racers
+            .iter()
+            .position(|r| r.player_id == envelope.event.player_id())
+        else {
+            return;
+        };
+        let racer = &mut race.racers[lane];
+        match &envelope.event {
+            GameEvent::MomentGenerated {
+                loop_number, mood, ..
+            } => {
+                racer.loop_number = *loop_number;
+                racer.mood = Some(mood.clone());
+            }
+            GameEvent::ChoiceMade {
+                loop_number,
+                nihilism_score,
+                ..
+            } => {
+                racer.loop_number = *loop_number;
+                racer.nihilism_score = *nihilism_score;
+                racer.choices += 1;
+            }
+            GameEvent::LoopReset { loop_number, .. } => racer.loop_number = *loop_number,
+            GameEvent::EndingReached { ending, .. } | GameEvent::RunCompleted { ending, .. }
+                if racer.ending.is_none() =>
+            {
+                racer.ending = Some(ending.clone());
+                racer.finished_at = Some(envelope.at);
+                if race.winner.is_none() {
+                    race.winner = Some(lane);
+                    race.finished_at = Some(envelope.at);
+                }
+            }
+            _ => return,
+        }
+        drop(rooms);
+        self.notify();
+    }
+
+    /// Races still counting down to a start between `from` and `to`, by
+    /// scenario name, with their racers
+    pub fn starting(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<(Uuid, &'static str, Vec<Uuid>)> {
+        self.rooms()
+            .races
+            .iter()
+            .filter(|(_, race)| race.finished_at.is_none())
+            .filter(|(_, race)| from < race.starts_at && race.starts_at <= to)
+            .map(|(id, race)| {
+                let racers = race.racers.iter().map(|r| r.player_id).collect();
+                (*id, race.modifier.name, racers)
+            })
+            .collect()
+    }
+
+    /// Drop races finished, or created, more than `RACE_TTL_HOURS` ago.
+    /// Returns how many were dropped.
+    pub fn evict(&self, now: DateTime<Utc>) -> usize {
+        let cutoff = now - Duration::hours(RACE_TTL_HOURS);
+        let mut rooms = self.rooms();
+        let before = rooms.races.len();
+        rooms
+            .races
+            .retain(|_, race| race.finished_at.unwrap_or(race.created_at) > cutoff);
+        let Rooms { races, racing } = &mut *rooms;
+        racing.retain(|_, race_id| races.contains_key(race_id));
+        before - races.len()
+    }
+}
+
+fn view(id: Uuid, race: &Race, now: DateTime<Utc>) -> RaceView {
+    RaceView {
+        id,
+        scenario: race.modifier.name,
+        status: race.status(now),
+        created_at: race.created_at,
+        starts_at: race.starts_at,
+        finished_at: race.finished_at,
+        winner: race.winner,
+        racers: race
+            .racers
+            .iter()
+            .enumerate()
+            .map(|(lane, r)| RacerView {
+                lane,
+                name: r.name.clone(),
+                loop_number: r.loop_number,
+                nihilism_score: r.nihilism_score,
+                mood: r.mood.clone(),
+                choices: r.choices,
+                ending: r.ending.clone(),
+                finished_at: r.finished_at,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,109 @@
+use super::*;
+
+fn envelope(event: GameEvent) -> Envelope {
+    Envelope {
+        seq: 0,
+        at: Utc::now(),
+        event,
+    }
+}
+
+fn choice(player_id: Uuid, loop_number: u64, nihilism_score: i32) -> GameEvent {
+    GameEvent::ChoiceMade {
+        player_id,
+        run_id: player_id,
+        choice_id: "c1".to_string(),
+        choice_text: "Walk away".to_string(),
+        loop_number,
+        is_dark: true,
+        score_delta: 5,
+        nihilism_score,
+        provenance: None,
+    }
+}
+
+#[test]
+fn racers_share_the_seed_and_join_only_during_the_countdown() {
+    let rooms = RaceRooms::new();
+    let race = rooms.create(30, Some("endless_rain")).unwrap();
+    assert_eq!(race.scenario, "Endless Rain");
+    assert_eq!(race.status, RaceStatus::Countdown);
+    assert!(rooms.create(30, Some("no_such_scenario")).is_none());
+
+    let (first_lane, first) = rooms.join(race.id, Uuid::new_v4(), Some("  Ana ".to_string())).unwrap();
+    let (second_lane, second) = rooms.join(race.id, Uuid::new_v4(), None).unwrap();
+    assert_eq!((first_lane, second_lane), (0, 1));
+    assert_eq!(first.seed, second.seed);
+    assert_eq!(first.modifier, "endless_rain");
+    assert_eq!(rooms.view(&race.id).unwrap().racers[0].name.as_deref(), Some("Ana"));
+
+    for _ in 2..MAX_RACERS {
+        rooms.join(race.id, Uuid::new_v4(), None).unwrap();
+    }
+    assert_eq!(rooms.join(race.id, Uuid::new_v4(), None).unwrap_err(), RaceError::Full);
+
+    let started = rooms.create(0, None).unwrap();
+    assert_eq!(rooms.join(started.id, Uuid::new_v4(), None).unwrap_err(), RaceError::Started);
+    assert_eq!(rooms.join(Uuid::new_v4(), Uuid::new_v4(), None).unwrap_err(), RaceError::NotFound);
+}
+
+#[test]
+fn the_first_ending_wins_the_race() {
+    let rooms = RaceRooms::new();
+    let race = rooms.create(30, None).unwrap();
+    let (fast, slow) = (Uuid::new_v4(), Uuid::new_v4());
+    rooms.join(race.id, fast, None).unwrap();
+    rooms.join(race.id, slow, None).unwrap();
+
+    rooms.apply(&envelope(GameEvent::MomentGenerated {
+        player_id: slow,
+        moment_id: Uuid::new_v4(),
+        loop_number: 1,
+        mood: "melancholic".to_string(),
+        provenance: None,
+    }));
+    rooms.apply(&envelope(choice(slow, 1, 5)));
+    rooms.apply(&envelope(choice(slow, 2, 12)));
+    // Players outside the race are ignored
+    rooms.apply(&envelope(choice(Uuid::new_v4(), 9, 99)));
+    rooms.apply(&envelope(GameEvent::EndingReached {
+        player_id: fast,
+        ending: EndingType::Acceptance,
+        first_time: true,
+    }));
+    rooms.apply(&envelope(GameEvent::EndingReached {
+        player_id: slow,
+        ending: EndingType::VoidEmbrace,
+        first_time: true,
+    }));
+
+    let view = rooms.view(&race.id).unwrap();
+    assert_eq!(view.status, RaceStatus::Finished);
+    assert_eq!(view.winner, Some(0));
+    let slow = &view.racers[1];
+    assert_eq!((slow.loop_number, slow.nihilism_score, slow.choices), (2, 12, 2));
+    assert_eq!(slow.mood.as_deref(), Some("melancholic"));
+    assert_eq!(slow.ending, Some(EndingType::VoidEmbrace));
+
+    let json = serde_json::to_string(&view).unwrap();
+    assert!(!json.contains(&fast.to_string()));
+}
+
+#[test]
+fn racers_wait_for_the_start_and_old_races_are_evicted() {
+    let rooms = RaceRooms::new();
+    let race = rooms.create(30, None).unwrap();
+    let mut player = Player::new();
+    assert!(rooms.has_started(&player));
+
+    let (_, run) = rooms.join(race.id, player.id, None).unwrap();
+    player.run.race = Some(run);
+    assert!(!rooms.has_started(&player));
+
+    assert_eq!(rooms.evict(Utc::now()), 0);
+    assert_eq!(rooms.evict(Utc::now() + Duration::hours(RACE_TTL_HOURS + 1)), 1);
+    assert!(rooms.view(&race.id).is_none());
+    assert!(rooms.rooms().racing.is_empty());
+    // A race the server forgot doesn't hold its racers back
+    assert!(rooms.has_started(&player));
+}
@@ -0,0 +1,386 @@
+//! Global ending counts and the rarity they give each ending.
+//!
+//! Counts are published, so on a small instance they could tell who did
+//! what. Public counts get Laplace noise of scale `1 / STATS_EPSILON`, keyed
+//! with the seal key so the same count always reads the same and asking
+//! again averages nothing out, and counts below `STATS_MIN_COUNT` are
+//! withheld. Admins see exact counts.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::{Config, StorageBackend};
+use crate::endings::EndingType;
+use crate::i18n::{self, Locale, Text};
+use crate::seal;
+use crate::tenant;
+
+const STATS_FILE: &str = "data/ending_stats.json";
+/// Endings reached worldwide before any is judged rare
+const MIN_SAMPLES: u64 = 20;
+
+/// Global count of souls (runs) that reached each ending, shared by every
+/// instance using the same data directory or database
+pub trait EndingCounter: Send + Sync {
+    /// Count one more soul reaching an ending, returning the new count
+    fn increment(&self, ending: &EndingType) -> Result<u64>;
+    fn counts(&self) -> Result<HashMap<EndingType, u64>>;
+}
+
+fn ending_key(ending: &EndingType) -> String {
+    format!("{:?}", ending)
+}
+
+fn parse_counts(counts: impl IntoIterator<Item = (String, u64)>) -> HashMap<EndingType, u64> {
+    counts
+        .into_iter()
+        .filter_map(|(key, count)| {
+            let ending = EndingType::all().into_iter().find(|e| ending_key(e) == key)?;
+            Some((ending, count))
+        })
+        .collect()
+}
+
+/// Counts in a JSON file, updated under an exclusive file lock
+pub struct FileCounter {
+    path: PathBuf,
+}
+
+impl FileCounter {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn open(&self) -> Result<File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?)
+    }
+
+    fn read(file: &mut File) -> Result<BTreeMap<String, u64>> {
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        if text.trim().is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+impl EndingCounter for FileCounter {
+    fn increment(&self, ending: &EndingType) -> Result<u64> {
+        let mut file = self.open()?;
+        // Released when the file is closed
+        file.lock()?;
+        let mut counts = Self::read(&mut file)?;
+        let count = counts.entry(ending_key(ending)).or_insert(0);
+        *count += 1;
+        let count = *count;
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(serde_json::to_string_pretty(&counts)?.as_bytes())?;
+        Ok(count)
+    }
+
+    fn counts(&self) -> Result<HashMap<EndingType, u64>> {
+        if !Path::new(&self.path).exists() {
+            return Ok(HashMap::new());
+        }
+        let mut file = File::open(&self.path)?;
+        file.lock_shared()?;
+        Ok(parse_counts(Self::read(&mut file)?))
+    }
+}
+
+/// Counts in the SQLite database, incremented by a single upsert
+pub struct SqliteCounter {
+    conn: Mutex<rusqlite::Connection>,
+}
+
+impl SqliteCounter {
+    pub fn open(path: &str) -> Result<Self> {
+        if let Some(parent) = PathBuf::from(path).parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = rusqlite::Connection::open(path)?;
+        // Other instances may be writing at the same moment
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS ending_stats (
+                 ending TEXT PRIMARY KEY,
+                 count INTEGER NOT NULL
+             );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, rusqlite::Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl EndingCounter for SqliteCounter {
+    fn increment(&self, ending: &EndingType) -> Result<u64> {
+        let count: i64 = self.conn().query_row(
+            "INSERT INTO ending_stats (ending, count) VALUES (?1, 1)
+             ON CONFLICT(ending) DO UPDATE SET count = count + 1
+             RETURNING count",
+            [ending_key(ending)],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    fn counts(&self) -> Result<HashMap<EndingType, u64>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare("SELECT ending, count FROM ending_stats")?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?
+            .filter_map(|row| row.ok())
+            .map(|(key, count)| (key, count as u64));
+        Ok(parse_counts(rows))
+    }
+}
+
+/// How rare an ending is across every player
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EndingRarity {
+    /// Souls that have reached this ending
+    pub souls: u64,
+    /// Share of all endings reached, in percent
+    pub percent: f64,
+    pub rare: bool,
+    /// This run was the nth soul to reach the ending, as published when it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ordinal: Option<u64>,
+    /// Extra line shown for rare endings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flourish: Option<String>,
+}
+
+/// One row of the public ending statistics
+#[derive(Clone, Debug, Serialize)]
+pub struct EndingStat {
+    pub ending: EndingType,
+    pub title: String,
+    /// `None` when too few souls reached the ending to publish a count
+    pub souls: Option<u64>,
+    pub percent: Option<f64>,
+    pub rare: bool,
+}
+
+/// How public counts are protected, as reported alongside them
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct StatsPrivacy {
+    /// Privacy budget of each count; `None` when counts are exact
+    pub epsilon: Option<f64>,
+    /// Counts below this are withheld
+    pub min_count: u64,
+}
+
+impl StatsPrivacy {
+    /// `count` as published, if it may be. `uniform` is a stable draw from
+    /// 0 to 1 for this count.
+    fn release(&self, count: u64, uniform: f64) -> Option<u64> {
+        let noisy = match self.epsilon {
+            Some(epsilon) => {
+                let noise = laplace(uniform, 1.0 / epsilon);
+                (count as f64 + noise).round().max(0.0) as u64
+            }
+            None => count,
+        };
+        (noisy >= self.min_count).then_some(noisy)
+    }
+}
+
+/// Laplace noise of `scale` from a uniform draw in (0, 1)
+fn laplace(uniform: f64, scale: f64) -> f64 {
+    let u = uniform - 0.5;
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+}
+
+/// A draw in (0, 1) that is the same for the same ending and count, and
+/// can't be predicted without the seal key
+fn draw(ending: &EndingType, count: u64) -> f64 {
+    let bits = seal::keyed_bits(&format!("stats:{}:{}", ending_key(ending), count));
+    // The top 53 bits fit an f64 exactly, so the draw never rounds to 0 or 1
+    ((bits >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+}
+
+fn format_percent(percent: f64) -> String {
+    if percent < 1.0 {
+        format!("{:.1}", percent)
+    } else {
+        format!("{:.0}", percent)
+    }
+}
+
+/// Global ending counts, and which endings they make rare
+pub struct EndingStats {
+    counter: Box<dyn EndingCounter>,
+    /// Endings reached by fewer than this share of souls are rare
+    rare_percent: f64,
+    privacy: StatsPrivacy,
+}
+
+impl EndingStats {
+    pub fn open(config: &Config) -> Result<Self> {
+        let tenant = config.tenant.as_deref();
+        let counter: Box<dyn EndingCounter> = match config.storage_backend {
+            StorageBackend::File => Box::new(FileCounter::new(tenant::data_path(tenant, STATS_FILE))),
+            StorageBackend::Sqlite => Box::new(SqliteCounter::open(
+                &tenant::data_path(tenant, &config.sqlite_path).to_string_lossy(),
+            )?),
+        };
+        Ok(Self {
+            counter,
+            rare_percent: config.rare_ending_percent,
+            privacy: StatsPrivacy {
+                epsilon: config.stats_epsilon,
+                min_count: config.stats_min_count,
+            },
+        })
+    }
+
+    pub fn privacy(&self) -> StatsPrivacy {
+        self.privacy
+    }
+
+    /// Count a run reaching an ending for the first time, returning its
+    /// ordinal as published: the ending's count just after it, released like
+    /// any other, so it is withheld while that count is
+    pub fn record(&self, ending: &EndingType) -> Option<u64> {
+        let count = self
+            .counter
+            .increment(ending)
+            .inspect_err(|e| tracing::warn!("Failed to count ending {:?}: {}", ending, e))
+            .ok()?;
+        self.privacy.release(count, draw(ending, count))
+    }
+
+    fn counts(&self) -> HashMap<EndingType, u64> {
+        self.counter.counts().unwrap_or_else(|e| {
+            tracing::warn!("Failed to read ending stats: {}", e);
+            HashMap::new()
+        })
+    }
+
+    /// Every ending's count as published, and the total they add up to.
+    /// Withheld counts still add to the total, noised like the rest.
+    fn released(&self) -> (HashMap<EndingType, Option<u64>>, u64) {
+        let counts = self.counts();
+        let open = StatsPrivacy {
+            min_count: 0,
+            ..self.privacy
+        };
+        let mut total = 0;
+        let released = EndingType::all()
+            .into_iter()
+            .map(|ending| {
+                let count = counts.get(&ending).copied().unwrap_or(0);
+                let uniform = draw(&ending, count);
+                total += open.release(count, uniform).unwrap_or(0);
+                (ending, self.privacy.release(count, uniform))
+            })
+            .collect();
+        (released, total)
+    }
+
+    /// Share of souls reaching `souls`, and whether that is rare
+    fn judge(&self, souls: u64, total: u64) -> (f64, bool) {
+        let percent = if total == 0 {
+            0.0
+        } else {
+            souls as f64 * 100.0 / total as f64
+        };
+        (percent, souls > 0 && total >= MIN_SAMPLES && percent < self.rare_percent)
+    }
+
+    /// Rarity of an ending, with a flourish in the player's language if rare
+    pub fn rarity(
+        &self,
+        ending: &EndingType,
+        ordinal: Option<u64>,
+        locale: Locale,
+    ) -> Option<EndingRarity> {
+        let (counts, total) = self.released();
+        let souls = counts.get(ending).copied().flatten()?;
+        let (percent, rare) = self.judge(souls, total);
+        let flourish = rare.then(|| {
+            let mut line = i18n::text(locale, Text::RareEnding)
+                .replace("{percent}", &format_percent(percent));
+            if let Some(n) = ordinal {
+                line.push(' ');
+                line.push_str(&i18n::soul_ordinal(locale, n));
+            }
+            line
+        });
+        Some(EndingRarity {
+            souls,
+            percent,
+            rare,
+            ordinal,
+            flourish,
+        })
+    }
+
+    /// Every ending with its published count, rarest first
+    pub fn summary(&self, locale: Locale) -> Vec<EndingStat> {
+        let (counts, total) = self.released();
+        self.stats(|ending| counts.get(ending).copied().flatten(), total, locale)
+    }
+
+    /// Every ending with its exact count, rarest first; for admins only
+    pub fn exact_summary(&self, locale: Locale) -> Vec<EndingStat> {
+        let counts = self.counts();
+        let total: u64 = counts.values().sum();
+        self.stats(
+            |ending| Some(counts.get(ending).copied().unwrap_or(0)),
+            total,
+            locale,
+        )
+    }
+
+    fn stats(
+        &self,
+        souls: impl Fn(&EndingType) -> Option<u64>,
+        total: u64,
+        locale: Locale,
+    ) -> Vec<EndingStat> {
+        let mut stats: Vec<EndingStat> = EndingType::all()
+            .into_iter()
+            .map(|ending| {
+                let souls = souls(&ending);
+                let judged = souls.map(|souls| self.judge(souls, total));
+                EndingStat {
+                    title: ending.get_title(locale).to_string(),
+                    ending,
+                    souls,
+                    percent: judged.map(|(percent, _)| percent),
+                    rare: judged.is_some_and(|(_, rare)| rare),
+                }
+            })
+            .collect();
+        stats.sort_by_key(|s| s.souls);
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,91 @@
+use super::*;
+
+struct Fixed(HashMap<EndingType, u64>);
+
+impl EndingCounter for Fixed {
+    fn increment(&self, ending: &EndingType) -> Result<u64> {
+        Ok(self.0.get(ending).copied().unwrap_or(0) + 1)
+    }
+
+    fn counts(&self) -> Result<HashMap<EndingType, u64>> {
+        Ok(self.0.clone())
+    }
+}
+
+fn stats(counts: &[(EndingType, u64)], privacy: StatsPrivacy) -> EndingStats {
+    let mut config = Config::for_tests("http://localhost:1/v1");
+    config.seal_secret = Some("rarity".to_string());
+    // Tests share the process-wide key; whichever test sets it first wins
+    let _ = seal::init(&config);
+    EndingStats {
+        counter: Box::new(Fixed(counts.iter().cloned().collect())),
+        rare_percent: 10.0,
+        privacy,
+    }
+}
+
+#[test]
+fn small_counts_are_withheld_and_the_rest_noised() {
+    let privacy = StatsPrivacy {
+        epsilon: Some(1.0),
+        min_count: 5,
+    };
+    // The middle draw adds no noise; the tails add plenty
+    assert_eq!(privacy.release(40, 0.5), Some(40));
+    assert_eq!(privacy.release(4, 0.5), None);
+    assert_eq!(privacy.release(40, 0.999), Some(40 + 6));
+    assert_eq!(privacy.release(40, 0.001), Some(40 - 6));
+    assert_eq!(privacy.release(1, 0.001), None);
+    assert_eq!(laplace(0.25, 2.0), -laplace(0.75, 2.0));
+
+    let exact = StatsPrivacy {
+        epsilon: None,
+        min_count: 0,
+    };
+    assert_eq!(exact.release(0, 0.999), Some(0));
+}
+
+#[test]
+fn published_counts_hold_still_and_admins_see_exact_ones() {
+    let stats = stats(
+        &[(EndingType::VoidEmbrace, 500), (EndingType::TheWatcher, 1)],
+        StatsPrivacy {
+            epsilon: Some(0.5),
+            min_count: 50,
+        },
+    );
+
+    let published = stats.summary(Locale::En);
+    let souls =
+        |stats: &[EndingStat], ending| stats.iter().find(|s| s.ending == ending).unwrap().souls;
+    assert_eq!(souls(&published, EndingType::TheWatcher), None);
+    let void = souls(&published, EndingType::VoidEmbrace).unwrap();
+    assert!(void.abs_diff(500) < 50);
+    // Asking again can't average the noise away
+    assert_eq!(
+        souls(&stats.summary(Locale::En), EndingType::VoidEmbrace),
+        Some(void)
+    );
+    assert!(
+        stats
+            .rarity(&EndingType::TheWatcher, Some(1), Locale::En)
+            .is_none()
+    );
+    // A soul's place is published like the count it made, never exactly
+    assert_eq!(stats.record(&EndingType::TheWatcher), None);
+    let ordinal = stats.record(&EndingType::VoidEmbrace).unwrap();
+    assert!(ordinal.abs_diff(501) < 50);
+    assert_eq!(
+        Some(ordinal),
+        stats.privacy.release(501, draw(&EndingType::VoidEmbrace, 501))
+    );
+
+    // Draws use all 64 keyed bits and stay strictly inside (0, 1)
+    let draws: Vec<f64> = (0..200).map(|n| draw(&EndingType::VoidEmbrace, n)).collect();
+    assert!(draws.iter().all(|u| *u > 0.0 && *u < 1.0));
+    assert!(draws.iter().any(|u| *u < 0.1) && draws.iter().any(|u| *u > 0.9));
+
+    let exact = stats.exact_summary(Locale::En);
+    assert_eq!(souls(&exact, EndingType::TheWatcher), Some(1));
+    assert_eq!(souls(&exact, EndingType::VoidEmbrace), Some(500));
+}
@@ -0,0 +1,282 @@
+//! Export manifests: what an export leaves out of a player's data, by their
+//! export privacy settings and consent. The manifest travels inside the
+//! export, and the export preview lists the same before anything is
+//! downloaded.
+//!
+//! A typed choice is one whose text was never offered in any moment the
+//! export can see; choices from loops reduced to stats count as typed, so
+//! the manifest errs on the side of withholding.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::consequences::ChoiceRecord;
+use crate::game::{ArchivedLoop, Player, Run};
+use crate::graph::{ChoiceGraph, fingerprint_text};
+use crate::privacy::{self, ExportPrivacy, Purpose};
+
+/// Stands in for a typed choice that is withheld
+pub const WITHHELD_CHOICE: &str = "(typed by the player; withheld)";
+
+/// What is being exported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// The active run as interactive fiction
+    Run,
+    /// The branching map as interactive fiction
+    Graph,
+    /// A backup of every run
+    Backup,
+}
+
+impl Kind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "run" => Some(Kind::Run),
+            "graph" => Some(Kind::Graph),
+            "backup" => Some(Kind::Backup),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Name,
+    Notes,
+    /// Preferences, language and time zone
+    Settings,
+    Consent,
+    Moments,
+    /// Choices offered by the narrator
+    Choices,
+    /// Choices the player typed
+    FreeformInputs,
+    /// Full text of finished loops
+    LoopTranscripts,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// The player's export privacy settings
+    ExportPrivacy,
+    /// The player didn't agree to keep transcripts
+    Consent,
+    /// Archive retention reduced old loops to memory shards
+    Retention,
+}
+
+/// A field left out of an export
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Withheld {
+    pub field: Field,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    pub reason: Reason,
+}
+
+/// Embedded in every export: what it leaves out and why
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub withheld: Vec<Withheld>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Included {
+    pub field: Field,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Preview {
+    pub kind: Kind,
+    pub settings: ExportPrivacy,
+    pub included: Vec<Included>,
+    pub withheld: Vec<Withheld>,
+}
+
+/// Runs an export of `kind` covers
+fn runs(kind: Kind, player: &Player) -> Vec<&Run> {
+    match kind {
+        Kind::Backup => player.all_runs().collect(),
+        Kind::Run | Kind::Graph => vec![&player.run],
+    }
+}
+
+/// Fingerprints of every choice text offered in the moments an export sees
+fn offered(kind: Kind, player: &Player, archives: &[ArchivedLoop]) -> HashSet<String> {
+    let moments = runs(kind, player)
+        .into_iter()
+        .flat_map(|run| {
+            run.narrative_history
+                .iter()
+                .chain(run.graph.continuations.values().map(|c| &c.moment))
+        })
+        .chain(archives.iter().flat_map(|a| &a.moments));
+    moments
+        .flat_map(|m| &m.choices)
+        .map(|c| fingerprint_text(&c.text))
+        .collect()
+}
+
+fn is_typed(text: &str, offered: &HashSet<String>) -> bool {
+    text != WITHHELD_CHOICE && !offered.contains(&fingerprint_text(text))
+}
+
+/// Offered and typed choices among `texts`, leaving out those withheld
+fn tally<'a>(texts: impl Iterator<Item = &'a String>, offered: &HashSet<String>) -> (usize, usize) {
+    texts
+        .filter(|t| *t != WITHHELD_CHOICE)
+        .fold((0, 0), |(o, t), text| {
+            if is_typed(text, offered) {
+                (o, t + 1)
+            } else {
+                (o + 1, t)
+            }
+        })
+}
+
+/// Leave out of `player`, and the choice logs of its runs, what the player
+/// keeps out of an export of `kind`. Returns the manifest for the export.
+pub fn redact(
+    kind: Kind,
+    player: &mut Player,
+    archives: &[ArchivedLoop],
+    logs: &mut HashMap<Uuid, Vec<ChoiceRecord>>,
+) -> Manifest {
+    let settings = player.export_privacy;
+    let mut withheld = Vec::new();
+    let mut withhold = |field, count, reason| {
+        withheld.push(Withheld {
+            field,
+            count,
+            reason,
+        })
+    };
+
+    if settings.withhold_name && player.name.take().is_some() {
+        withhold(Field::Name, None, Reason::ExportPrivacy);
+    }
+    if kind == Kind::Backup && settings.withhold_notes && player.settings.notes.take().is_some() {
+        withhold(Field::Notes, None, Reason::ExportPrivacy);
+    }
+
+    if settings.withhold_freeform && kind != Kind::Run {
+        let offered = offered(kind, player, archives);
+        let mut replaced = 0;
+        let graphs: Vec<&mut ChoiceGraph> = match kind {
+            Kind::Backup => std::iter::once(&mut player.run)
+                .chain(player.runs.iter_mut())
+                .map(|r| &mut r.graph)
+                .collect(),
+            Kind::Run | Kind::Graph => vec![&mut player.run.graph],
+        };
+        let edges = graphs
+            .into_iter()
+            .flat_map(|g| g.edges.iter_mut().map(|e| &mut e.choice_text));
+        let records = logs.values_mut().flatten().map(|r| &mut r.choice_text);
+        for text in edges.chain(records) {
+            if is_typed(text, &offered) {
+                *text = WITHHELD_CHOICE.to_string();
+                replaced += 1;
+            }
+        }
+        if replaced > 0 {
+            withhold(Field::FreeformInputs, Some(replaced), Reason::ExportPrivacy);
+        }
+    }
+
+    if kind != Kind::Graph {
+        let compacted = archives.iter().filter(|a| a.is_compacted()).count();
+        if compacted > 0 {
+            let reason = if privacy::policy().allows(player, Purpose::Transcripts) {
+                Reason::Retention
+            } else {
+                Reason::Consent
+            };
+            withhold(Field::LoopTranscripts, Some(compacted), reason);
+        }
+    }
+
+    Manifest { withheld }
+}
+
+/// What an export of `kind` would include, and leave out, as of now
+pub fn preview(
+    kind: Kind,
+    player: &Player,
+    archives: &[ArchivedLoop],
+    logs: &HashMap<Uuid, Vec<ChoiceRecord>>,
+) -> Preview {
+    let mut player = player.clone();
+    let mut logs = logs.clone();
+    let manifest = redact(kind, &mut player, archives, &mut logs);
+
+    let mut included = Vec::new();
+    let mut include = |field, count: Option<usize>| {
+        if count != Some(0) {
+            included.push(Included { field, count });
+        }
+    };
+    if player.name.is_some() {
+        include(Field::Name, None);
+    }
+    let runs = runs(kind, &player);
+    let offered = offered(kind, &player, archives);
+    match kind {
+        Kind::Run => {
+            let moments = runs[0]
+                .narrative_history
+                .iter()
+                .chain(archives.iter().flat_map(|a| &a.moments));
+            let (moments, choices) =
+                moments.fold((0, 0), |(m, c), moment| (m + 1, c + moment.choices.len()));
+            include(Field::Moments, Some(moments));
+            include(Field::Choices, Some(choices));
+        }
+        Kind::Graph => {
+            let graph = &runs[0].graph;
+            let (choices, typed) = tally(graph.edges.iter().map(|e| &e.choice_text), &offered);
+            include(Field::Moments, Some(graph.nodes.len()));
+            include(Field::Choices, Some(choices));
+            include(Field::FreeformInputs, Some(typed));
+        }
+        Kind::Backup => {
+            if player.settings.notes.is_some() {
+                include(Field::Notes, None);
+            }
+            include(Field::Settings, None);
+            if player.consent.is_some() {
+                include(Field::Consent, None);
+            }
+            let moments = runs
+                .iter()
+                .map(|r| r.narrative_history.len())
+                .sum::<usize>()
+                + archives.iter().map(|a| a.moments.len()).sum::<usize>();
+            let (choices, typed) = tally(logs.values().flatten().map(|r| &r.choice_text), &offered);
+            include(Field::Moments, Some(moments));
+            include(Field::Choices, Some(choices));
+            include(Field::FreeformInputs, Some(typed));
+        }
+    }
+    let transcripts = archives.iter().filter(|a| !a.is_compacted()).count();
+    if kind != Kind::Graph {
+        include(Field::LoopTranscripts, Some(transcripts));
+    }
+
+    Preview {
+        kind,
+        settings: player.export_privacy,
+        included,
+        withheld: manifest.withheld,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,154 @@
+use super::*;
+use crate::testing::{self, PlayerBuilder};
+use chrono::Utc;
+
+fn record(text: &str) -> ChoiceRecord {
+    ChoiceRecord {
+        loop_number: 1,
+        choice_id: "c1".to_string(),
+        choice_text: text.to_string(),
+        is_dark: false,
+        score_delta: -3,
+        nihilism_score: Some(-3),
+        at: Utc::now(),
+        provenance: None,
+    }
+}
+
+/// A player who picked one offered choice and typed another
+fn player() -> Player {
+    let mut player = PlayerBuilder::new()
+        .moment(
+            "The door is warm.",
+            &[("open", "Open the door"), ("leave", "Walk away")],
+        )
+        .build();
+    player.name = Some("Ana".to_string());
+    player.settings.notes = Some("She lied about the letters.".to_string());
+    let next = testing::moment("The corridor hums.", &[]);
+    let source = player
+        .run
+        .graph
+        .record_moment(&player.run.narrative_history[0], 1);
+    player
+        .run
+        .graph
+        .record_transition(&source, "open", "Open the door", &next, 1);
+    player
+        .run
+        .graph
+        .record_transition(&source, "say", "Knock three times", &next, 1);
+    player
+}
+
+#[test]
+fn an_open_export_withholds_nothing() {
+    let mut player = player();
+    let mut logs = HashMap::from([(
+        player.id,
+        vec![record("Open the door"), record("Knock three times")],
+    )]);
+
+    let manifest = redact(Kind::Backup, &mut player, &[], &mut logs);
+    assert!(manifest.withheld.is_empty());
+    assert_eq!(player.name.as_deref(), Some("Ana"));
+    assert_eq!(logs[&player.id][1].choice_text, "Knock three times");
+}
+
+#[test]
+fn typed_choices_are_withheld_from_graphs_and_choice_logs() {
+    let mut player = player();
+    player.export_privacy = ExportPrivacy {
+        withhold_name: true,
+        withhold_notes: true,
+        withhold_freeform: true,
+    };
+    let mut logs = HashMap::from([(
+        player.id,
+        vec![record("Open the door"), record("Knock three times")],
+    )]);
+
+    let manifest = redact(Kind::Backup, &mut player, &[], &mut logs);
+    assert_eq!(
+        manifest.withheld,
+        vec![
+            Withheld {
+                field: Field::Name,
+                count: None,
+                reason: Reason::ExportPrivacy
+            },
+            Withheld {
+                field: Field::Notes,
+                count: None,
+                reason: Reason::ExportPrivacy
+            },
+            Withheld {
+                field: Field::FreeformInputs,
+                count: Some(2),
+                reason: Reason::ExportPrivacy
+            },
+        ]
+    );
+    assert_eq!(
+        (player.name.as_ref(), player.settings.notes.as_ref()),
+        (None, None)
+    );
+    assert_eq!(logs[&player.id][0].choice_text, "Open the door");
+    assert_eq!(logs[&player.id][1].choice_text, WITHHELD_CHOICE);
+    assert_eq!(player.run.graph.edges[1].choice_text, WITHHELD_CHOICE);
+
+    // Interactive fiction exports never carry the notes
+    let mut player = self::player();
+    player.export_privacy.withhold_notes = true;
+    assert!(
+        redact(Kind::Run, &mut player, &[], &mut HashMap::new())
+            .withheld
+            .is_empty()
+    );
+    assert!(player.settings.notes.is_some());
+}
+
+#[test]
+fn the_preview_lists_what_would_go_out() {
+    let mut player = player();
+    player.export_privacy.withhold_freeform = true;
+    let mut compacted = testing::archived_loop(&player, 1, "reset");
+    compacted.compact("Loop #1 passed.".to_string());
+    let archives = vec![compacted, testing::archived_loop(&player, 2, "reset")];
+
+    let preview = preview(Kind::Graph, &player, &archives, &HashMap::new());
+    assert_eq!(
+        preview.included,
+        vec![
+            Included {
+                field: Field::Name,
+                count: None
+            },
+            Included {
+                field: Field::Moments,
+                count: Some(2)
+            },
+            Included {
+                field: Field::Choices,
+                count: Some(1)
+            },
+        ]
+    );
+    assert_eq!(preview.withheld[0].field, Field::FreeformInputs);
+    // The preview leaves the player as it was
+    assert_eq!(player.run.graph.edges[1].choice_text, "Knock three times");
+
+    let preview = self::preview(Kind::Run, &player, &archives, &HashMap::new());
+    assert!(preview.included.contains(&Included {
+        field: Field::LoopTranscripts,
+        count: Some(1)
+    }));
+    assert_eq!(
+        preview.withheld,
+        vec![Withheld {
+            field: Field::LoopTranscripts,
+            count: Some(1),
+            reason: Reason::Retention
+        }]
+    );
+}
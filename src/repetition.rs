@@ -0,0 +1,88 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+
+/// Words per shingle
+const SHINGLE_WORDS: usize = 3;
+
+/// Overlapping word triples of the text, lowercased and stripped of punctuation
+fn shingles(text: &str) -> HashSet<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|w| {
+            w.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(|c| c.to_lowercase())
+                .collect::<String>()
+        })
+        .filter(|w| !w.is_empty())
+        .collect();
+    if words.len() < SHINGLE_WORDS {
+        return std::iter::once(words.join(" ")).filter(|s| !s.is_empty()).collect();
+    }
+    words.windows(SHINGLE_WORDS).map(|w| w.join(" ")).collect()
+}
+
+/// Jaccard similarity of two texts' shingles, from 0 (disjoint) to 1 (identical)
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (shingles(a), shingles(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// The most similar earlier text, if it reaches `threshold`
+pub fn find_repeat<'a>(
+    text: &str,
+    recent: impl IntoIterator<Item = &'a str>,
+    threshold: f64,
+) -> Option<(&'a str, f64)> {
+    recent
+        .into_iter()
+        .map(|earlier| (earlier, similarity(text, earlier)))
+        .filter(|(_, score)| *score >= threshold)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RepetitionCounts {
+    /// Moments that repeated a recent one and were re-prompted
+    pub detected: u64,
+    /// Re-prompts that produced a fresh moment
+    pub recovered: u64,
+}
+
+/// Repetitions seen per model
+#[derive(Default)]
+pub struct RepetitionStats {
+    by_model: Mutex<BTreeMap<String, RepetitionCounts>>,
+}
+
+impl RepetitionStats {
+    pub fn record(&self, model: &str, recovered: bool) {
+        let mut by_model = self.by_model.lock().unwrap_or_else(|e| e.into_inner());
+        let counts = by_model.entry(model.to_string()).or_default();
+        counts.detected += 1;
+        counts.recovered += recovered as u64;
+    }
+
+    /// Append repetition counters in Prometheus text format
+    pub fn write_metrics(&self, out: &mut String) {
+        let by_model = self.by_model.lock().unwrap_or_else(|e| e.into_inner());
+        out.push_str("# HELP nihilism_llm_repetitions_total Generated moments that repeated a recent one\n");
+        out.push_str("# TYPE nihilism_llm_repetitions_total counter\n");
+        for (model, counts) in by_model.iter() {
+            out.push_str(&format!(
+                "nihilism_llm_repetitions_total{{model=\"{}\",outcome=\"recovered\"}} {}\n",
+                model, counts.recovered
+            ));
+            out.push_str(&format!(
+                "nihilism_llm_repetitions_total{{model=\"{}\",outcome=\"persisted\"}} {}\n",
+                model,
+                counts.detected - counts.recovered
+            ));
+        }
+    }
+}
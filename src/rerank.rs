@@ -0,0 +1,223 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::persona::Persona;
+
+const RATINGS_DIR: &str = "data/ratings";
+const REGENERATIONS_DIR: &str = "data/ratings/regenerations";
+/// How long a player's latest ratings stay available to debug responses
+const LATEST_TTL_MINUTES: i64 = 60;
+
+/// A secondary model's view of one choice, each score from 0 to 1
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChoiceRating {
+    pub choice_id: String,
+    pub text: String,
+    /// How much a player would want to find out where it leads
+    pub interest: f64,
+    /// How well it fits the game's themes
+    pub fit: f64,
+}
+
+/// The ratings of every choice in a moment
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MomentRatings {
+    pub moment_id: Uuid,
+    pub player_id: Uuid,
+    /// The model that rated the choices
+    pub model: String,
+    pub persona: Persona,
+    pub mood: String,
+    pub rated_at: DateTime<Utc>,
+    pub choices: Vec<ChoiceRating>,
+}
+
+/// A moment the player had written again rather than answer it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Regeneration {
+    pub moment_id: Uuid,
+    pub player_id: Uuid,
+    pub persona: Persona,
+    pub mood: String,
+    /// Choices the discarded moment offered
+    pub choices: usize,
+    pub at: DateTime<Utc>,
+}
+
+/// Average scores for one persona, mood or choice count
+#[derive(Clone, Debug, Serialize)]
+pub struct RatingLine {
+    pub key: String,
+    pub moments: u64,
+    pub choices: u64,
+    pub interest: f64,
+    pub fit: f64,
+    /// Moments players had written again rather than answer them
+    pub regenerated: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RatingReport {
+    pub month: String,
+    pub overall: Option<RatingLine>,
+    pub by_persona: Vec<RatingLine>,
+    pub by_mood: Vec<RatingLine>,
+    /// By how many choices the moment offered
+    pub by_choice_count: Vec<RatingLine>,
+    /// The lowest-rated choices, as examples of what the narrator does badly
+    pub weakest: Vec<ChoiceRating>,
+}
+
+/// Choice ratings: appended per month to `data/ratings/{YYYY-MM}.jsonl`, with
+/// each player's latest kept in memory for debug responses
+pub struct RatingStore {
+    latest: Mutex<HashMap<Uuid, MomentRatings>>,
+}
+
+fn month_path(dir: &str, month: &str) -> PathBuf {
+    PathBuf::from(dir).join(format!("{}.jsonl", month))
+}
+
+/// Append `record` to the month's file under `dir`
+fn append<T: Serialize>(dir: &str, at: DateTime<Utc>, record: &T) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = month_path(dir, &at.format("%Y-%m").to_string());
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Readable records of a month's file under `dir`
+fn read_month<T: for<'de> Deserialize<'de>>(dir: &str, month: &str) -> Result<Vec<T>> {
+    let path = month_path(dir, month);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut records = Vec::new();
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(record) => records.push(record),
+            Err(_) => tracing::warn!("Skipping unreadable line in {}", dir),
+        }
+    }
+    Ok(records)
+}
+
+impl RatingStore {
+    pub fn new() -> Self {
+        Self {
+            latest: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, ratings: MomentRatings) -> Result<()> {
+        append(RATINGS_DIR, ratings.rated_at, &ratings)?;
+
+        let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        let cutoff = Utc::now() - Duration::minutes(LATEST_TTL_MINUTES);
+        latest.retain(|_, r| r.rated_at > cutoff);
+        latest.insert(ratings.player_id, ratings);
+        Ok(())
+    }
+
+    pub fn record_regeneration(&self, regeneration: &Regeneration) -> Result<()> {
+        append(REGENERATIONS_DIR, regeneration.at, regeneration)
+    }
+
+    /// Ratings of a player's moment, if they have arrived yet
+    pub fn for_moment(&self, player_id: &Uuid, moment_id: &Uuid) -> Option<MomentRatings> {
+        let latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        latest
+            .get(player_id)
+            .filter(|r| r.moment_id == *moment_id)
+            .cloned()
+    }
+
+    /// Aggregate a month's ratings
+    pub fn report(&self, month: &str) -> Result<RatingReport> {
+        let mut all = Line::default();
+        let mut by_persona: HashMap<String, Line> = HashMap::new();
+        let mut by_mood: HashMap<String, Line> = HashMap::new();
+        let mut by_choice_count: HashMap<String, Line> = HashMap::new();
+        let mut weakest: Vec<ChoiceRating> = Vec::new();
+
+        for ratings in read_month::<MomentRatings>(RATINGS_DIR, month)? {
+            for (lines, key) in [
+                (&mut by_persona, ratings.persona.get_title().to_string()),
+                (&mut by_mood, ratings.mood.clone()),
+                (&mut by_choice_count, ratings.choices.len().to_string()),
+            ] {
+                lines.entry(key).or_default().add(&ratings);
+            }
+            all.add(&ratings);
+            weakest.extend(ratings.choices);
+        }
+        for regeneration in read_month::<Regeneration>(REGENERATIONS_DIR, month)? {
+            for (lines, key) in [
+                (&mut by_persona, regeneration.persona.get_title().to_string()),
+                (&mut by_mood, regeneration.mood.clone()),
+                (&mut by_choice_count, regeneration.choices.to_string()),
+            ] {
+                lines.entry(key).or_default().regenerated += 1;
+            }
+            all.regenerated += 1;
+        }
+
+        weakest.sort_by(|a, b| (a.interest + a.fit).total_cmp(&(b.interest + b.fit)));
+        weakest.truncate(10);
+        let sorted = |lines: HashMap<String, Line>| {
+            let mut lines: Vec<RatingLine> =
+                lines.into_iter().map(|(key, line)| line.finish(key)).collect();
+            lines.sort_by(|a, b| a.key.cmp(&b.key));
+            lines
+        };
+        Ok(RatingReport {
+            month: month.to_string(),
+            overall: (all.moments > 0 || all.regenerated > 0).then(|| all.finish("all".to_string())),
+            by_persona: sorted(by_persona),
+            by_mood: sorted(by_mood),
+            by_choice_count: sorted(by_choice_count),
+            weakest,
+        })
+    }
+}
+
+/// Running totals behind a `RatingLine`
+#[derive(Default)]
+struct Line {
+    moments: u64,
+    choices: u64,
+    interest: f64,
+    fit: f64,
+    regenerated: u64,
+}
+
+impl Line {
+    fn add(&mut self, ratings: &MomentRatings) {
+        self.moments += 1;
+        for choice in &ratings.choices {
+            self.choices += 1;
+            self.interest += choice.interest;
+            self.fit += choice.fit;
+        }
+    }
+
+    fn finish(self, key: String) -> RatingLine {
+        let n = self.choices.max(1) as f64;
+        RatingLine {
+            key,
+            moments: self.moments,
+            choices: self.choices,
+            interest: self.interest / n,
+            fit: self.fit / n,
+            regenerated: self.regenerated,
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::llm::{default_shard_summary, LlmClient};
+use crate::persistence;
+
+/// An archived loop old enough to be compacted
+#[derive(Clone, Debug, Serialize)]
+pub struct CompactionCandidate {
+    pub player_id: Uuid,
+    pub loop_number: u64,
+    pub moments: usize,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CompactionReport {
+    pub dry_run: bool,
+    pub keep_loops: u64,
+    pub players_scanned: usize,
+    pub candidates: Vec<CompactionCandidate>,
+    pub compacted: usize,
+    pub failed: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// Runs of every saved player of `tenant`, whose archives are the tenant's.
+/// Archives are shared by all tenants and only their owners' saves tell
+/// them apart.
+fn tenant_runs(tenant: Option<&str>) -> Result<HashSet<Uuid>> {
+    let mut runs = HashSet::new();
+    for id in persistence::list_saved_players()? {
+        if let Some(player) = persistence::load_player(&id)?
+            && player.tenant.as_deref() == tenant
+        {
+            runs.extend(player.run_views().into_iter().map(|r| r.run_id));
+        }
+    }
+    Ok(runs)
+}
+
+/// Compact every archived loop of `tenant`'s players except each player's
+/// `keep_loops` most recent.
+///
+/// Compaction replaces the raw moments with an LLM-written two-sentence
+/// summary plus stats (a memory shard), written by the tenant's own `llm`.
+/// With `dry_run`, only reports what would be compacted.
+pub async fn compact_archives(
+    llm: &LlmClient,
+    tenant: Option<&str>,
+    keep_loops: u64,
+    dry_run: bool,
+) -> Result<CompactionReport> {
+    let mut report = CompactionReport {
+        dry_run,
+        keep_loops,
+        ..Default::default()
+    };
+
+    let runs = tenant_runs(tenant)?;
+    for player_id in persistence::list_archived_players()? {
+        if !runs.contains(&player_id) {
+            continue;
+        }
+        report.players_scanned += 1;
+        let loops = persistence::load_archived_loops(&player_id)?;
+        let cutoff = loops.len().saturating_sub(keep_loops as usize);
+
+        for mut archived in loops.into_iter().take(cutoff) {
+            if archived.is_compacted() {
+                continue;
+            }
+            let number = archived.loop_info.number;
+            let bytes = persistence::archive_size(&player_id, number);
+            report.bytes_before += bytes;
+            report.candidates.push(CompactionCandidate {
+                player_id,
+                loop_number: number,
+                moments: archived.moments.len(),
+                bytes,
+            });
+            if dry_run {
+                continue;
+            }
+
+            let summary = match llm.generate_shard_summary(&archived).await {
+                Ok(summary) => summary,
+                Err(e) => {
+                    tracing::debug!("Shard summary failed for loop {}, using fallback: {}", number, e);
+                    default_shard_summary(&archived)
+                }
+            };
+            archived.compact(summary);
+            match persistence::archive_loop(&archived) {
+                Ok(()) => {
+                    report.compacted += 1;
+                    report.bytes_after += persistence::archive_size(&player_id, number);
+                }
+                Err(e) => {
+                    report.failed += 1;
+                    report.bytes_after += bytes;
+                    tracing::warn!("Failed to compact loop {} of {}: {}", number, player_id, e);
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
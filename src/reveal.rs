@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::game::NarrativeMoment;
+
+/// One server-paced piece of a moment's text
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Beat {
+    pub moment_id: Uuid,
+    pub index: usize,
+    pub count: usize,
+    pub text: String,
+}
+
+/// A client's acknowledgement that it finished presenting a beat
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct BeatAck {
+    pub moment_id: Uuid,
+    pub index: usize,
+}
+
+/// A moment being revealed beat by beat
+pub struct Reveal {
+    moment_id: Uuid,
+    beats: Vec<String>,
+    next: usize,
+}
+
+impl Reveal {
+    /// Split the moment as the player reads it, translation included
+    pub fn new(moment: &NarrativeMoment, beat_chars: usize) -> Self {
+        let text = moment.translation.as_ref().map_or(&moment.text, |t| &t.text);
+        Self {
+            moment_id: moment.id,
+            beats: split_beats(text, beat_chars),
+            next: 0,
+        }
+    }
+
+    /// The beat to send next, or `None` once the whole moment is out
+    pub fn next_beat(&mut self) -> Option<Beat> {
+        let text = self.beats.get(self.next)?.clone();
+        self.next += 1;
+        Some(Beat {
+            moment_id: self.moment_id,
+            index: self.next - 1,
+            count: self.beats.len(),
+            text,
+        })
+    }
+
+    /// Whether any beat has been sent yet
+    pub fn started(&self) -> bool {
+        self.next > 0
+    }
+
+    /// Whether an ack releases the beat that was sent last
+    pub fn acked_by(&self, ack: &BeatAck) -> bool {
+        ack.moment_id == self.moment_id && ack.index + 1 == self.next
+    }
+}
+
+/// Split text into beats of about `beat_chars` characters, breaking only
+/// between sentences. Paragraph breaks always end a beat; a single sentence
+/// longer than `beat_chars` stays whole. 0 keeps the text in one beat.
+pub fn split_beats(text: &str, beat_chars: usize) -> Vec<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    if beat_chars == 0 {
+        return vec![text.to_string()];
+    }
+
+    let mut beats = Vec::new();
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let mut beat = String::new();
+        for sentence in sentences(paragraph) {
+            if !beat.is_empty() && beat.chars().count() + 1 + sentence.chars().count() > beat_chars {
+                beats.push(std::mem::take(&mut beat));
+            }
+            if !beat.is_empty() {
+                beat.push(' ');
+            }
+            beat.push_str(sentence);
+        }
+        if !beat.is_empty() {
+            beats.push(beat);
+        }
+    }
+    beats
+}
+
+/// Sentences of a paragraph: a run of `.`, `!`, `?` or `…`, with any closing
+/// quotes or brackets, followed by whitespace ends one
+fn sentences(paragraph: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = paragraph.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if !matches!(c, '.' | '!' | '?' | '…') {
+            continue;
+        }
+        while let Some(&(_, next)) = chars.peek() {
+            if matches!(next, '.' | '!' | '?' | '…' | '"' | '\'' | '”' | '’' | ')' | ']') {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        match chars.peek() {
+            Some(&(end, next)) if next.is_whitespace() => {
+                sentences.push(paragraph[start..end].trim());
+                start = end;
+            }
+            _ => {}
+        }
+    }
+    let rest = paragraph[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+    sentences.retain(|s| !s.is_empty());
+    sentences
+}
+
+/// Beat acknowledgements posted over HTTP, for clients revealing over the
+/// event stream. Each stream watches its player's latest ack.
+#[derive(Default)]
+pub struct RevealAcks {
+    senders: Mutex<HashMap<Uuid, watch::Sender<Option<BeatAck>>>>,
+}
+
+impl RevealAcks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self, player_id: Uuid) -> watch::Receiver<Option<BeatAck>> {
+        let mut senders = self.senders.lock().unwrap_or_else(|e| e.into_inner());
+        // Players whose streams all closed are forgotten on the way
+        senders.retain(|_, sender| sender.receiver_count() > 0);
+        senders
+            .entry(player_id)
+            .or_insert_with(|| watch::channel(None).0)
+            .subscribe()
+    }
+
+    /// Pass an ack to the player's streams; dropped when none is listening
+    pub fn ack(&self, player_id: Uuid, ack: BeatAck) {
+        let senders = self.senders.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = senders.get(&player_id) {
+            let _ = sender.send(Some(ack));
+        }
+    }
+}
+
+/// Wait until the beat just sent is acknowledged, or the timeout passes
+pub async fn wait_for_ack(
+    acks: &mut watch::Receiver<Option<BeatAck>>,
+    reveal: &Reveal,
+    timeout: Duration,
+) {
+    let acked = acks.wait_for(|ack| ack.as_ref().is_some_and(|ack| reveal.acked_by(ack)));
+    let _ = tokio::time::timeout(timeout, acked).await;
+}
+
+#[cfg(test)]
+mod tests;
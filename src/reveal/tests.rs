@@ -0,0 +1,61 @@
+use super::*;
+
+#[test]
+fn beats_break_between_sentences() {
+    let text = "The door is open. It was closed a moment ago! Was it? Nobody answers.";
+
+    let beats = split_beats(text, 40);
+
+    assert_eq!(
+        beats,
+        vec![
+            "The door is open.",
+            "It was closed a moment ago! Was it?",
+            "Nobody answers.",
+        ]
+    );
+}
+
+#[test]
+fn closing_quotes_stay_with_their_sentence() {
+    let text = "\"Again?\" she asks. \"Again.\"";
+
+    assert_eq!(split_beats(text, 1), vec!["\"Again?\"", "she asks.", "\"Again.\""]);
+}
+
+#[test]
+fn paragraphs_always_end_a_beat() {
+    let text = "First.\n\nSecond. Third.";
+
+    assert_eq!(split_beats(text, 500), vec!["First.", "Second. Third."]);
+}
+
+#[test]
+fn long_sentences_and_zero_width_stay_whole() {
+    let text = "A sentence that is far longer than the beat width allows. Short.";
+
+    assert_eq!(split_beats(text, 10).len(), 2);
+    assert_eq!(split_beats(text, 0), vec![text]);
+    assert!(split_beats("   ", 10).is_empty());
+}
+
+#[test]
+fn reveal_is_released_only_by_the_latest_beat() {
+    let moment = crate::testing::moment("One. Two.", &[]);
+    let mut reveal = Reveal::new(&moment, 1);
+
+    let first = reveal.next_beat().unwrap();
+    assert_eq!((first.index, first.count, first.text.as_str()), (0, 2, "One."));
+    let stale = BeatAck {
+        moment_id: Uuid::new_v4(),
+        index: 0,
+    };
+    assert!(!reveal.acked_by(&stale));
+    assert!(reveal.acked_by(&BeatAck {
+        moment_id: moment.id,
+        index: 0,
+    }));
+
+    reveal.next_beat().unwrap();
+    assert!(reveal.next_beat().is_none());
+}
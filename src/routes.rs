@@ -1,88 +1,1031 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    routing::{get, post},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, Request, State},
+    http::{header, Extensions, HeaderMap, Method, StatusCode, Version},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, patch, post},
     Json, Router,
 };
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
+use tower_http::decompression::RequestDecompressionLayer;
 use uuid::Uuid;
 
-use crate::config::Config;
-use crate::endings::{check_for_ending, EndingResponse, EndingType};
-use crate::game::{GameState, NarrativeMoment, Player};
-use crate::llm::LlmClient;
+use crate::abuse::{self, AbuseMonitor, ReviewEntry, StrikeKind};
+use crate::accounts::{Account, AccountError, AccountStore, AccountView};
+use crate::analytics::{self, EventCount, EventCounters, PositionBias};
+use crate::anchors;
+use crate::assets::Manifest;
+use crate::audit::{self, AuditEntry, Change, MomentAction, SwitchedBy};
+use crate::backup::{self, Backup, BackupError};
+use crate::build_info::{self, BuildInfo};
+use crate::cancel;
+use crate::card::{CardContent, CardRenderer};
+use crate::challenge::{self, Challenge, ChallengeRun, LeaderboardEntry};
+use crate::clusters::{BucketReport, ChoiceClusters};
+use crate::coalesce::Coalescer;
+use crate::compare::{Comparison, RunStats};
+use crate::conditions::Condition;
+use crate::config::{Config, ContentRating};
+use crate::consequences;
+use crate::decay::{self, DecayEvent};
+use crate::diff::{self, SaveDiff};
+use crate::digest::{self, DigestCounters};
+use crate::dialogue;
+use crate::epilogue::{self, Epilogue, EpilogueView};
+use crate::endings::{
+    self, check_for_ending, current_ending, nearest_ending, EndingResponse, EndingType,
+    Simulation,
+};
+use crate::events::{EventBus, GameEvent};
+use crate::export::{self, ExportFormat};
+use crate::flags::{Feature, FlagView, Rollout};
+use crate::game::{
+    ArchivedLoop, Choice, Finale, GameError, GameState, KeyMemory, LoopEndCause, MemoryError,
+    MomentState, NarrativeMoment, Player, PlayerSummary, RunView,
+};
+use crate::generation::{GenerationQueue, Priority, QueueError};
+use crate::graph::fingerprint_text;
+use crate::handoff;
+use crate::i18n::{self, Locale, Text};
+use crate::game::ResetBeat;
+use crate::janitor::{Janitor, JanitorReport};
+use crate::journal;
+use crate::llm::{
+    default_epilogue_moment, default_finale_moments, default_judgment, default_reset_sequence,
+    Capabilities, LlmClient, UpstreamError,
+};
+use crate::moderation;
+use crate::offline;
+use crate::patch::{self, Operation, PatchError};
 use crate::persistence;
+use crate::persona::Persona;
+use crate::plugins::{self, PluginReport};
+use crate::presence::{self, Presence, PresenceCache};
+use crate::privacy::{self, Consent, ConsentUpdate, ExportPrivacy, ExportPrivacyUpdate, Purpose};
+use crate::provenance::{self, Provenance};
+use crate::push::{PushBridge, PushError, PushRequest, PushView};
+use crate::race::{
+    RaceError, RaceRooms, RaceStatus, RaceView, DEFAULT_COUNTDOWN_SECS, MAX_COUNTDOWN_SECS,
+    MIN_COUNTDOWN_SECS,
+};
+use crate::rarity::EndingStats;
+use crate::redaction::{self, Kind, Preview};
+use crate::retention::{self, CompactionReport};
+use crate::reveal::{self, BeatAck, Reveal, RevealAcks};
+use crate::sanitize::{SanitizeReport, Sanitizer};
+use crate::scheduler::{JobMetrics, Scheduler};
+use crate::rerank::{MomentRatings, RatingReport, RatingStore, Regeneration};
+use crate::scoring::{Ensemble, ScoredChoice};
+use crate::seal::{self, SealClaims};
+use crate::spoilers::{self, Disclosure, Gallery, GalleryEntry};
+use crate::stability::Stage;
+use crate::status::{ServerStatus, StatusBoard};
+use crate::suggest::{self, SuggestionCache, SuggestionSource, Suggestions};
+use crate::texture::TextureLines;
+use crate::theme::{self, Flavor};
+use crate::thinking;
+use crate::throttle::{Reason, Throttled};
+use crate::titles;
+use crate::upstream;
+use crate::usage::{self, BudgetExceeded, CostReport};
+use crate::waiting::{QueueEntry, TicketStatus, WaitingRoom};
+use crate::warmup::{WarmPool, WarmStart, MAX_WARMUP};
+use crate::world::WorldRules;
+use crate::ws::{self, WsSessions};
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Config,
     pub game: Arc<RwLock<GameState>>,
     pub llm: Arc<LlmClient>,
+    pub presence: Arc<PresenceCache>,
+    pub scheduler: Arc<Scheduler>,
+    pub events: Arc<EventBus>,
+    pub event_counters: Arc<EventCounters>,
+    pub ws: Arc<WsSessions>,
+    pub accounts: Arc<AccountStore>,
+    pub sanitizer: Arc<Sanitizer>,
+    pub suggestions: Arc<SuggestionCache>,
+    pub janitor: Arc<Janitor>,
+    pub world: Arc<WorldRules>,
+    pub abuse: Arc<AbuseMonitor>,
+    pub warm_pool: Arc<WarmPool>,
+    pub ending_stats: Arc<EndingStats>,
+    pub waiting: Arc<WaitingRoom>,
+    pub scoring: Arc<Ensemble>,
+    /// Start and choice generations in flight, shared by identical retries
+    pub narration: Arc<Coalescer<Result<Json<NarrativeResponse>, ApiError>>>,
+    pub resets: Arc<Coalescer<Result<Json<ResetResponse>, StatusCode>>>,
+    pub texture: Arc<TextureLines>,
+    pub ratings: Arc<RatingStore>,
+    pub choice_clusters: Arc<ChoiceClusters>,
+    /// Beat acks for moments revealed over the event stream
+    pub reveal_acks: Arc<RevealAcks>,
+    pub cards: Arc<CardRenderer>,
+    pub status: Arc<StatusBoard>,
+    /// Today's game events, tallied for the nightly digest
+    pub digest: Arc<DigestCounters>,
+    /// Fair turns at narrative generation under load
+    pub generations: Arc<GenerationQueue>,
+    /// Races open in memory, followed through the event bus
+    pub races: Arc<RaceRooms>,
+    /// Push notification targets players registered
+    pub push: Arc<PushBridge>,
 }
 
-pub fn create_router(config: Config, game_state: Arc<RwLock<GameState>>) -> Router {
-    let llm = Arc::new(LlmClient::new(config.clone()));
+impl AppState {
+    pub fn new(
+        config: Config,
+        game: Arc<RwLock<GameState>>,
+        llm: Arc<LlmClient>,
+        janitor: Arc<Janitor>,
+        ending_stats: Arc<EndingStats>,
+        scoring: Arc<Ensemble>,
+    ) -> anyhow::Result<Self> {
+        let accounts = Arc::new(AccountStore::load(&config)?);
+        let warm_pool = Arc::new(WarmPool::load(&config)?);
+        let sanitizer = Arc::new(Sanitizer::new(config.sanitize_level));
+        let suggestions = Arc::new(SuggestionCache::new(config.suggest_rate_limit));
+        let abuse = Arc::new(AbuseMonitor::new(config.abuse_strike_threshold));
+        let cards = Arc::new(CardRenderer::new(&config));
+        let choice_clusters = Arc::new(ChoiceClusters::load(&config));
+        let status = Arc::new(StatusBoard::new(config.maintenance_read_only));
+        let generations = Arc::new(GenerationQueue::new(&config));
+        let push = Arc::new(PushBridge::load(&config));
+        Ok(Self {
+            scheduler: Arc::new(Scheduler::new(&config)),
+            config,
+            game,
+            llm,
+            presence: Arc::new(PresenceCache::new()),
+            events: Arc::new(EventBus::new(1024)),
+            event_counters: Arc::new(EventCounters::default()),
+            ws: Arc::new(WsSessions::new()),
+            accounts,
+            sanitizer,
+            suggestions,
+            janitor,
+            world: Arc::new(WorldRules::new()),
+            abuse,
+            warm_pool,
+            ending_stats,
+            waiting: Arc::new(WaitingRoom::new()),
+            scoring,
+            narration: Arc::new(Coalescer::new()),
+            resets: Arc::new(Coalescer::new()),
+            texture: Arc::new(TextureLines::new()),
+            ratings: Arc::new(RatingStore::new()),
+            choice_clusters,
+            reveal_acks: Arc::new(RevealAcks::new()),
+            cards,
+            status,
+            digest: Arc::new(DigestCounters::new()),
+            generations,
+            races: Arc::new(RaceRooms::new()),
+            push,
+        })
+    }
 
-    let state = AppState {
-        config,
-        game: game_state,
-        llm,
-    };
+    /// Degradations clients should announce right now
+    pub fn server_status(&self) -> ServerStatus {
+        self.status.snapshot(&self.llm, &self.waiting)
+    }
+}
+
+pub fn create_router(state: AppState) -> Router {
+    let admin = Router::new()
+        .route("/scheduler", get(admin_scheduler))
+        .route("/analytics/position-bias", get(admin_position_bias))
+        .route("/analytics/choices", get(admin_choice_buckets))
+        .route("/analytics/provenance", get(admin_provenance))
+        .route("/events", get(admin_events))
+        .route("/sanitize", get(admin_sanitize))
+        .route("/costs", get(admin_costs))
+        .route("/ratings", get(admin_ratings))
+        .route("/simulate-ending", post(admin_simulate_ending))
+        .route(
+            "/archives/compaction",
+            get(admin_compaction_preview).post(admin_compaction_run),
+        )
+        .route("/janitor", get(admin_janitor_preview).post(admin_janitor_run))
+        .route("/abuse", get(admin_abuse_queue))
+        .route("/abuse/{player_id}/unban", post(admin_unban))
+        .route(
+            "/players/{player_id}/moments/{moment_id}",
+            patch(admin_edit_moment),
+        )
+        .route("/players/{player_id}/model", post(admin_switch_model))
+        .route("/players/{player_id}/diff", get(admin_player_diff))
+        .route("/audit", get(admin_audit))
+        .route("/warmup", get(admin_warmup_list).post(admin_warmup))
+        .route("/waiting-room", get(admin_waiting_room))
+        .route("/maintenance", post(admin_maintenance))
+        .route("/flags", get(admin_flags))
+        .route("/flags/{feature}", patch(admin_set_flag).delete(admin_clear_flag))
+        .route("/plugins", get(admin_plugins))
+        .route("/endings", get(admin_endings))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
+
+    let metrics = Router::new()
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
 
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
 
-    Router::new()
+    let router = Router::new()
         .route("/api/health", get(health_check))
+        .route("/api/capabilities", get(get_capabilities))
+        .route("/api/version", get(get_version))
+        .route("/api/status", get(get_status))
+        .route(
+            "/api/presence/{player_id}",
+            get(get_presence).post(set_presence_visibility),
+        )
+        .route("/api/stats/endings", get(ending_stats))
+        .route("/api/verify-seal", get(verify_seal))
+        .route("/api/compare", get(compare_players))
+        .route("/api/scenario/assets", get(scenario_manifest))
+        .route("/assets/{scenario}/{*path}", get(scenario_asset))
+        .route("/api/challenge/today", get(challenge_today))
+        .route("/api/challenge/join", post(join_challenge))
+        .route("/api/challenge/{date}", get(challenge_leaderboard))
+        .route("/api/race", post(create_race))
+        .route("/api/race/{race_id}", get(get_race))
+        .route("/api/race/{race_id}/join", post(join_race))
+        .route("/api/race/{race_id}/events", get(race_events))
+        .route("/api/push/vapid-key", get(push_vapid_key))
+        .route("/api/push/unsubscribe", post(push_unsubscribe))
+        .route("/api/account", get(get_account))
+        .route("/api/account/register", post(register_account))
+        .route("/api/account/login", post(login_account))
+        .route("/api/account/logout", post(logout_account))
+        .route("/api/account/magic-link", post(request_magic_link))
+        .route("/api/account/magic-link/verify", post(verify_magic_link))
+        .route(
+            "/api/account/players",
+            get(account_players).post(bind_account_player),
+        )
         .route("/api/game/new", post(new_game))
         .route("/api/game/load/{player_id}", get(load_game))
+        .route("/api/game/claim/{code}", post(claim_warm_start))
+        .route("/api/waiting/{ticket}", get(waiting_status))
+        .route("/api/waiting/{ticket}/events", get(waiting_events))
         .route("/api/game/save/{player_id}", post(save_game))
         .route("/api/game/list", get(list_saves))
-        .route("/api/game/{player_id}", get(get_game_state))
+        .route(
+            "/api/game/{player_id}",
+            get(get_game_state).patch(patch_player),
+        )
         .route("/api/game/{player_id}/start", post(start_narrative))
         .route("/api/game/{player_id}/choice", post(make_choice))
         .route("/api/game/{player_id}/reset", post(reset_loop))
+        .route("/api/game/{player_id}/loop/end", post(end_loop))
         .route("/api/game/{player_id}/ending", get(check_ending))
+        .route("/api/game/{player_id}/endings", get(ending_gallery))
+        .route("/api/game/{player_id}/ending/refuse", post(refuse_ending))
+        .route("/api/game/{player_id}/graph", get(get_graph))
+        .route("/api/game/{player_id}/history", get(get_history))
+        .route(
+            "/api/game/{player_id}/profile",
+            get(get_profile).patch(update_profile),
+        )
+        .route("/api/game/{player_id}/export", get(export_game))
+        .route("/api/game/{player_id}/export/preview", get(export_preview))
+        .route("/api/game/{player_id}/backup", get(backup_game))
+        .route(
+            "/api/game/import",
+            post(import_game).layer(DefaultBodyLimit::max(backup::MAX_BACKUP_BYTES)),
+        )
+        .route("/api/game/{player_id}/events", get(game_events))
+        .route("/api/game/{player_id}/reveal/ack", post(ack_beat))
+        .route("/api/game/{player_id}/ws", get(ws::game_socket))
+        .route("/api/game/{player_id}/suggest", get(suggest_actions))
+        .route(
+            "/api/game/{player_id}/moments/{moment_id}/card.png",
+            get(moment_card),
+        )
+        .route(
+            "/api/game/{player_id}/moments/{moment_id}/regenerate",
+            post(regenerate_player_moment),
+        )
+        .route("/api/game/{player_id}/runs", get(list_runs).post(create_run))
+        .route(
+            "/api/game/{player_id}/runs/{run_id}/activate",
+            post(activate_run),
+        )
+        .route(
+            "/api/game/{player_id}/runs/{run_id}/title",
+            post(reroll_run_title).put(name_run),
+        )
+        .route("/api/game/{player_id}/seed-memories", post(seed_memories))
+        .route("/api/game/{player_id}/memories", get(list_memories))
+        .route(
+            "/api/game/{player_id}/memories/{index}",
+            patch(pin_memory).delete(forget_memory),
+        )
+        .route(
+            "/api/game/{player_id}/push",
+            get(get_push).put(register_push).delete(unregister_push),
+        )
+        .route("/api/game/{player_id}/epilogues", get(list_epilogues))
+        .route(
+            "/api/game/{player_id}/epilogues/{ending}",
+            post(play_epilogue),
+        )
+        .nest("/api/admin", admin)
+        .merge(metrics);
+
+    #[cfg(feature = "testing")]
+    let router = router.route(
+        "/api/testing/players",
+        post(crate::testing::create_fixture_player),
+    );
+
+    let (compression, decompression) = compression_layers(&state.config);
+    router
+        .layer(middleware::from_fn_with_state(state.clone(), refuse_writes_in_maintenance))
+        .layer(middleware::from_fn(cancel::track))
+        .layer(decompression)
+        .layer(compression)
         .layer(cors)
         .with_state(state)
 }
 
-async fn health_check() -> &'static str {
-    "Nihilism game server is running. The loop continues..."
+/// Compression of responses large enough and of a configured content type, and
+/// decompression of request bodies, in the configured encodings. Event streams
+/// are never compressed, so events aren't held back in the encoder.
+fn compression_layers(
+    config: &Config,
+) -> (CompressionLayer<impl Predicate + use<>>, RequestDecompressionLayer) {
+    let enabled = |encoding: &str| config.compression_encodings.iter().any(|e| e == encoding);
+    let content_types = Arc::new(config.compression_content_types.clone());
+    let compressible = move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+        headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_ascii_lowercase)
+            .is_some_and(|ct| content_types.iter().any(|t| ct.starts_with(t.as_str())))
+    };
+    let predicate = SizeAbove::new(config.compression_min_bytes)
+        .and(NotForContentType::SSE)
+        .and(compressible);
+
+    let compression = CompressionLayer::new()
+        .gzip(enabled("gzip"))
+        .br(enabled("br"))
+        .zstd(enabled("zstd"))
+        .compress_when(predicate);
+    let decompression = RequestDecompressionLayer::new()
+        .gzip(enabled("gzip"))
+        .br(enabled("br"))
+        .zstd(enabled("zstd"));
+    (compression, decompression)
+}
+
+/// Guard for `/api/admin/*`: requires `Authorization: Bearer <ADMIN_TOKEN>`.
+/// Admin endpoints are disabled entirely when no token is configured.
+async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let expected = state
+        .config
+        .admin_token
+        .as_deref()
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    if provided != Some(expected) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(next.run(request).await)
+}
+
+/// Maintenance mode: refuse anything that could change a game, short of the
+/// admin API that turns maintenance off again
+async fn refuse_writes_in_maintenance(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let reads = matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if state.status.is_read_only() && !reads && !request.uri().path().starts_with("/api/admin/") {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    Ok(next.run(request).await)
+}
+
+async fn health_check(State(state): State<AppState>) -> String {
+    theme::text(state.config.tenant.as_deref(), Flavor::Greeting)
+}
+
+async fn get_capabilities(State(state): State<AppState>) -> Json<Capabilities> {
+    Json(state.llm.capabilities())
+}
+
+#[derive(Serialize)]
+struct VersionResponse {
+    name: &'static str,
+    version: &'static str,
+    build: &'static BuildInfo,
+    content_rating: ContentRating,
+    moderation: bool,
+    /// Experimental features on for everyone, or for `player_id`'s cohort
+    features: Vec<Feature>,
+}
+
+#[derive(Deserialize)]
+struct VersionQuery {
+    player_id: Option<Uuid>,
+}
+
+async fn get_version(
+    State(state): State<AppState>,
+    Query(query): Query<VersionQuery>,
+) -> Json<VersionResponse> {
+    Json(VersionResponse {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        build: build_info::current(),
+        content_rating: state.config.content_rating,
+        moderation: state.config.moderation_active(),
+        features: state.llm.flags().active(query.player_id),
+    })
+}
+
+/// `404` unless `feature` is on for `player`, or for everyone with `None`
+fn require_feature(state: &AppState, feature: Feature, player: Option<Uuid>) -> Result<(), StatusCode> {
+    if state.llm.flags().is_enabled(feature, player) {
+        Ok(())
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<ServerStatus> {
+    Json(state.server_status())
+}
+
+/// How a request failed: a bare status, or a throttle the client should
+/// back off from
+#[derive(Clone, Debug)]
+pub(crate) enum ApiError {
+    Status(StatusCode),
+    Throttled(Throttled),
+}
+
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        ApiError::Status(status)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiError::Status(status) => status.into_response(),
+            ApiError::Throttled(throttled) => throttled.into_response(),
+        }
+    }
+}
+
+/// Turn the player's request away for now, and tell their push channels
+/// when to come back
+fn throttle(state: &AppState, player_id: Uuid, throttled: Throttled) -> ApiError {
+    tracing::info!(
+        "Throttled player {}: {} for {}s",
+        player_id,
+        throttled.reason.name(),
+        throttled.retry_after_secs
+    );
+    state.events.publish(GameEvent::Throttled {
+        player_id,
+        throttled: throttled.clone(),
+    });
+    ApiError::Throttled(throttled)
+}
+
+/// Error for a failed generation: throttled while the LLM budget is
+/// exhausted, every upstream is down or the provider rate limits us, `502`
+/// for any other upstream error
+fn llm_failure(state: &AppState, error: anyhow::Error) -> ApiError {
+    if error.is::<BudgetExceeded>() {
+        tracing::warn!("LLM error: {}", error);
+        return ApiError::Throttled(Throttled::new(
+            Reason::BudgetExhausted,
+            usage::until_next_month(),
+        ));
+    }
+    let upstream_error = error.downcast_ref::<UpstreamError>();
+    match upstream_error {
+        Some(e) => tracing::error!(kind = e.kind(), "LLM upstream error: {}", error),
+        None => tracing::error!("LLM error: {}", error),
+    }
+    if let Some(wait) = state.llm.retry_after() {
+        return ApiError::Throttled(Throttled::new(Reason::CircuitOpen, wait));
+    }
+    match upstream_error {
+        Some(UpstreamError::RateLimited { retry_after, .. }) => ApiError::Throttled(Throttled::new(
+            Reason::UpstreamRateLimited,
+            retry_after.unwrap_or(upstream::COOLDOWN),
+        )),
+        Some(_) => StatusCode::BAD_GATEWAY.into(),
+        None => StatusCode::INTERNAL_SERVER_ERROR.into(),
+    }
+}
+
+/// A failed generation for the player, who hears about a throttle
+fn llm_error(state: &AppState, player_id: Uuid, error: anyhow::Error) -> ApiError {
+    match llm_failure(state, error) {
+        ApiError::Throttled(throttled) => throttle(state, player_id, throttled),
+        error => error,
+    }
+}
+
+/// Error for a generation turned away by the queue
+fn queue_error(state: &AppState, player_id: Uuid, error: QueueError) -> ApiError {
+    tracing::warn!("Generation refused: {}", error);
+    let reason = match error {
+        QueueError::Full => Reason::QueueFull,
+        QueueError::AlreadyQueued => Reason::AlreadyQueued,
+    };
+    throttle(state, player_id, Throttled::new(reason, state.generations.retry_after()))
+}
+
+/// Spill the oldest moments to disk once a player's history outgrows its caps
+fn cap_history(config: &Config, player: &mut Player) {
+    let overflow = player.history_overflow(config.history_max_moments, config.history_max_bytes);
+    if overflow.is_empty() {
+        return;
+    }
+    match persistence::spill_moments(&player.run_id(), player.run.current_loop.number, &overflow) {
+        Ok(()) => {
+            player.summarize_moments(&overflow);
+            tracing::debug!("Spilled {} moments of player {}", overflow.len(), player.id);
+        }
+        Err(e) => tracing::warn!("Failed to spill history of player {}: {}", player.id, e),
+    }
+}
+
+/// The player switched to another run while one of theirs was being generated
+/// A moment lifecycle rule was broken, e.g. a stale or repeated choice
+/// Status for a refused game operation: `404` once the player is gone, `409`
+/// when the request no longer fits the state of the game
+fn game_error(error: impl Into<GameError>) -> StatusCode {
+    let error = error.into();
+    tracing::info!("Rejected game operation: {}", error);
+    match error {
+        GameError::PlayerGone(_) => StatusCode::NOT_FOUND,
+        GameError::LoopCompleted(_) | GameError::MomentStale(_) | GameError::RunSealed => {
+            StatusCode::CONFLICT
+        }
+    }
+}
+
+fn memory_error(error: MemoryError) -> StatusCode {
+    tracing::info!("Rejected memory change: {}", error);
+    match error {
+        MemoryError::NotFound(_) => StatusCode::NOT_FOUND,
+        MemoryError::Pinned(_) => StatusCode::CONFLICT,
+    }
+}
+
+fn run_switched(game: &GameState, player_id: &Uuid, run_id: Uuid) -> bool {
+    game.get_player(player_id).is_some_and(|p| p.run_id() != run_id)
+}
+
+/// Record an abuse strike against a player and save it
+fn strike(state: &AppState, player: &mut Player, kind: StrikeKind, detail: Option<&str>) {
+    state.abuse.strike(player, kind, detail);
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save strike for {}: {}", player.id, e);
+    }
+}
+
+async fn strike_player(state: &AppState, player_id: Uuid, kind: StrikeKind, detail: Option<&str>) {
+    let mut game = state.game.write().await;
+    if let Some(player) = game.get_player_mut(&player_id) {
+        strike(state, player, kind, detail);
+    }
+}
+
+/// Screen player input that will reach a prompt. Flagged text earns a strike
+/// and is rejected; an injection attempt earns a strike but goes through, as
+/// the narrator's instructions already contain it.
+async fn screen_input(state: &AppState, player_id: Uuid, text: &str) -> Result<(), StatusCode> {
+    if moderation::check(&state.config, text).is_flagged() {
+        tracing::info!("Rejected input from {} by moderation", player_id);
+        strike_player(state, player_id, StrikeKind::Moderation, Some(text)).await;
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    if abuse::looks_like_injection(text) {
+        strike_player(state, player_id, StrikeKind::PromptInjection, Some(text)).await;
+    }
+    Ok(())
+}
+
+/// Announce a freshly pushed moment on the event bus
+fn publish_moment(state: &AppState, player: &Player, moment: &NarrativeMoment) {
+    state.events.publish(GameEvent::MomentGenerated {
+        player_id: player.id,
+        moment_id: moment.id,
+        loop_number: player.run.current_loop.number,
+        mood: moment.mood.clone(),
+        provenance: moment.provenance.clone(),
+    });
+    rate_choices(state, player, moment);
+}
+
+/// Have `RERANK_MODEL` rate the moment's choices in the background
+fn rate_choices(state: &AppState, player: &Player, moment: &NarrativeMoment) {
+    let Some(model) = state.config.rerank_model.clone() else {
+        return;
+    };
+    // Offline moments for ghosted players are not the narrator's work
+    if moment.choices.is_empty()
+        || player.abuse.is_ghosted()
+        || !privacy::policy().allows(player, Purpose::Analytics)
+    {
+        return;
+    }
+    let llm = state.llm.clone();
+    let ratings = state.ratings.clone();
+    let player = player.clone();
+    let moment = moment.clone();
+    cancel::spawn(async move {
+        let choices = match llm.rate_choices(&model, &player, &moment).await {
+            Ok(choices) => choices,
+            Err(e) => {
+                tracing::debug!("Could not rate choices of moment {}: {}", moment.id, e);
+                return;
+            }
+        };
+        let rated = MomentRatings {
+            moment_id: moment.id,
+            player_id: player.id,
+            model,
+            persona: player.run.persona,
+            mood: moment.mood,
+            rated_at: chrono::Utc::now(),
+            choices,
+        };
+        if let Err(e) = ratings.record(rated) {
+            tracing::warn!("Failed to store choice ratings: {}", e);
+        }
+    });
+}
+
+/// Check for an ending after a new moment, remembering it on the player
+fn reached_ending(
+    state: &AppState,
+    player: &mut Player,
+    locale: Locale,
+) -> Option<EndingResponse> {
+    let ending = check_for_ending(player)?;
+    let first_time = player.record_ending(&ending);
+    if first_time {
+        tracing::info!("Player {} reached {:?} for the first time", player.id, ending);
+        count_soul(state, player, &ending);
+    }
+    state.events.publish(GameEvent::EndingReached {
+        player_id: player.id,
+        ending: ending.clone(),
+        first_time,
+    });
+    Some(ending_response(state, player, ending, locale))
+}
+
+/// Count the run among the souls that reached an ending, remembering its place
+fn count_soul(state: &AppState, player: &mut Player, ending: &EndingType) {
+    if !privacy::policy().allows(player, Purpose::PublicStats) {
+        return;
+    }
+    if let Some(ordinal) = state.ending_stats.record(ending) {
+        player
+            .run
+            .memory
+            .ending_ordinals
+            .insert(ending.clone(), ordinal);
+    }
+}
+
+/// Ending response with the player's consequence ledger.
+///
+/// Entries the narrator has not judged yet get a scripted judgment; see `judge_ledger`.
+fn ending_response(
+    state: &AppState,
+    player: &Player,
+    ending: EndingType,
+    locale: Locale,
+) -> EndingResponse {
+    let ordinal = player.run.memory.ending_ordinals.get(&ending).copied();
+    let rarity = state.ending_stats.rarity(&ending, ordinal, locale);
+    let mut response =
+        EndingResponse::from_player(player, ending, state.config.content_rating, locale);
+    response.rarity = rarity;
+    response.seal = SealClaims::for_run(player)
+        .filter(|claims| claims.ending == response.ending_type)
+        .map(|claims| seal::seal(&claims));
+    response.ledger = consequences::compile(&player.run_id(), state.config.ending_ledger_size)
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to compile ledger for {}: {}", player.id, e);
+            Vec::new()
+        });
+    for entry in &mut response.ledger {
+        entry.judgment = Some(
+            player
+                .run
+                .ledger_judgments
+                .get(&entry.key)
+                .cloned()
+                .unwrap_or_else(|| default_judgment(entry)),
+        );
+    }
+    response
+}
+
+/// Ask the narrator to judge ledger entries it has not judged yet, caching the verdicts
+async fn judge_ledger(state: &AppState, player_id: Uuid, ending: &mut Option<EndingResponse>) {
+    let Some(ending) = ending else {
+        return;
+    };
+    let Some(player) = state.game.read().await.get_player(&player_id).cloned() else {
+        return;
+    };
+    if player.abuse.is_ghosted() {
+        return;
+    }
+    let (indices, unjudged): (Vec<usize>, Vec<_>) = ending
+        .ledger
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| !player.run.ledger_judgments.contains_key(&entry.key))
+        .map(|(i, entry)| (i, entry.clone()))
+        .unzip();
+    if unjudged.is_empty() {
+        return;
+    }
+
+    let judgments = match state
+        .llm
+        .generate_ledger_judgments(&player, &ending.ending_type, &unjudged)
+        .await
+    {
+        Ok(judgments) => judgments,
+        Err(e) => {
+            tracing::debug!("Ledger judgments failed, keeping scripted ones: {}", e);
+            return;
+        }
+    };
+
+    let mut game = state.game.write().await;
+    let mut cache = game.get_player_mut(&player_id).map(|p| &mut p.run.ledger_judgments);
+    for ((i, entry), judgment) in indices.into_iter().zip(&unjudged).zip(judgments) {
+        if let Some(cache) = cache.as_deref_mut() {
+            cache.insert(entry.key.clone(), judgment.clone());
+        }
+        ending.ledger[i].judgment = Some(judgment);
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct NewGameRequest {
+    persona: Option<Persona>,
+    /// Answers to the consent prompt; the server default applies until given
+    consent: Option<ConsentUpdate>,
 }
 
 #[derive(Serialize)]
 struct NewGameResponse {
-    player: Player,
+    player: PlayerSummary,
     message: String,
 }
 
-async fn new_game(State(state): State<AppState>) -> Json<NewGameResponse> {
-    let mut game = state.game.write().await;
-    let player = game.create_player();
+/// Returned with 202 Accepted when the server is full
+#[derive(Serialize)]
+struct WaitingResponse {
+    ticket: Uuid,
+    position: usize,
+    message: String,
+}
+
+/// Players active within the configured window, not counting warm starts
+/// that nobody has claimed yet
+fn active_players(state: &AppState, game: &GameState) -> usize {
+    let since = chrono::Utc::now() - chrono::Duration::minutes(state.config.active_window_minutes);
+    let unclaimed = state.warm_pool.player_ids();
+    game.players
+        .values()
+        .filter(|p| p.last_active() >= since && !unclaimed.contains(&p.id))
+        .count()
+}
+
+/// Create, announce and save a fresh player
+fn start_player(
+    state: &AppState,
+    game: &mut GameState,
+    persona: Persona,
+    consent: Option<ConsentUpdate>,
+) -> Player {
+    let mut player = game.create_player(persona);
+    if let Some(consent) = consent {
+        privacy::policy().update(&mut player, consent);
+        game.players.insert(player.id, player.clone());
+    }
+    state.events.publish(GameEvent::PlayerCreated {
+        player_id: player.id,
+    });
 
     // Auto-save new player
     if let Err(e) = persistence::save_player(&player) {
         tracing::warn!("Failed to auto-save new player: {}", e);
     }
+    player
+}
 
-    Json(NewGameResponse {
-        player,
-        message: "Welcome to the loop. You've been here before, even if you don't remember."
-            .to_string(),
+async fn new_game(
+    State(state): State<AppState>,
+    request: Option<Json<NewGameRequest>>,
+) -> Result<Response, StatusCode> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+
+    // A fresh player has reached no endings, so only the starting personas are open
+    let persona = request.persona.unwrap_or_default();
+    if !persona.is_unlocked(&[]) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let mut game = state.game.write().await;
+    let max = state.config.max_active_players;
+    // Nobody skips the line while others are already waiting in it
+    if max > 0 && (!state.waiting.is_empty() || active_players(&state, &game) >= max) {
+        drop(game);
+        let (ticket, position) = state.waiting.enqueue(persona, request.consent);
+        tracing::info!("Server full, ticket {} waiting at position {}", ticket.id, position);
+        let response = WaitingResponse {
+            ticket: ticket.id,
+            position,
+            message: theme::text(state.config.tenant.as_deref(), Flavor::WaitingRoom),
+        };
+        return Ok((StatusCode::ACCEPTED, Json(response)).into_response());
+    }
+
+    let player = start_player(&state, &mut game, persona, request.consent);
+    Ok(Json(NewGameResponse {
+        player: player.summary(),
+        message: theme::text(state.config.tenant.as_deref(), Flavor::Welcome),
     })
+    .into_response())
+}
+
+/// Give waiting visitors players of their own while there are free slots,
+/// front of the line first. Returns how many were admitted.
+pub async fn admit_waiting(state: &AppState) -> usize {
+    state.waiting.sweep();
+    if state.waiting.is_empty() {
+        return 0;
+    }
+
+    let mut game = state.game.write().await;
+    let max = state.config.max_active_players;
+    let free = if max == 0 {
+        usize::MAX
+    } else {
+        max.saturating_sub(active_players(state, &game))
+    };
+    let tickets = state.waiting.take(free);
+    for ticket in &tickets {
+        let player = start_player(state, &mut game, ticket.persona, ticket.consent);
+        state.waiting.admit(ticket, player.id);
+        tracing::info!("Ticket {} admitted as player {}", ticket.id, player.id);
+    }
+    tickets.len()
+}
+
+async fn waiting_status(
+    State(state): State<AppState>,
+    Path(ticket): Path<Uuid>,
+) -> Result<Json<TicketStatus>, StatusCode> {
+    state
+        .waiting
+        .status(&ticket)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Stream a waiting ticket's position as it moves, ending with an
+/// `admitted` event carrying the new player's id
+async fn waiting_events(
+    State(state): State<AppState>,
+    Path(ticket): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    if state.waiting.status(&ticket).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut changes = state.waiting.subscribe();
+    let stream = async_stream::stream! {
+        // Asking for the status regularly keeps the ticket from being abandoned
+        let mut heartbeat = tokio::time::interval(std::time::Duration::from_secs(30));
+        let mut last_position = None;
+        while let Some(status) = state.waiting.status(&ticket) {
+            let kind = match status {
+                TicketStatus::Waiting { position } if last_position == Some(position) => None,
+                TicketStatus::Waiting { position } => {
+                    last_position = Some(position);
+                    Some("position")
+                }
+                TicketStatus::Admitted { .. } => Some("admitted"),
+            };
+            if let Some(kind) = kind {
+                match Event::default().event(kind).json_data(&status) {
+                    Ok(event) => yield Ok(event),
+                    Err(e) => tracing::warn!("Failed to encode waiting event: {}", e),
+                }
+            }
+            if matches!(status, TicketStatus::Admitted { .. }) {
+                break;
+            }
+            tokio::select! {
+                changed = changes.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                _ = heartbeat.tick() => {}
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Claim a pre-generated player by its warm-up code; its opening moment is
+/// already waiting in `last_moment`
+async fn claim_warm_start(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> Result<Json<NewGameResponse>, StatusCode> {
+    let start = state
+        .warm_pool
+        .claim(&code)
+        .map_err(|e| {
+            tracing::error!("Failed to update warm-up pool: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let player = match fetch_player(&state, &start.player_id).await {
+        Ok(Some(player)) => player,
+        result => {
+            tracing::error!("Warm-up player {} could not be loaded", start.player_id);
+            if let Err(e) = state.warm_pool.restore(start) {
+                tracing::warn!("Failed to restore warm start: {}", e);
+            }
+            return Err(result.err().unwrap_or(StatusCode::NOT_FOUND));
+        }
+    };
+
+    let mut game = state.game.write().await;
+    let player = game.players.entry(player.id).or_insert(player);
+    player.run.last_active_at = Some(chrono::Utc::now());
+    tracing::info!("Warm start {} claimed by player {}", start.code, player.id);
+
+    Ok(Json(NewGameResponse {
+        player: player.summary(),
+        message: theme::text(state.config.tenant.as_deref(), Flavor::Welcome),
+    }))
 }
 
 #[derive(Serialize)]
 struct LoadGameResponse {
-    player: Player,
+    player: PlayerSummary,
     message: String,
     found: bool,
+    /// What the loop did to the player's story while they were away
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    decay: Vec<DecayEvent>,
 }
 
 async fn load_game(
@@ -90,16 +1033,35 @@ async fn load_game(
     Path(player_id): Path<Uuid>,
 ) -> Result<Json<LoadGameResponse>, StatusCode> {
     // Try to load from disk
-    match persistence::load_player(&player_id) {
-        Ok(Some(player)) => {
+    let loaded = persistence::load_player(&player_id);
+    let loaded = {
+        let game = state.game.read().await;
+        loaded.map(|player| player.filter(|p| game.admits(p)))
+    };
+    match loaded {
+        Ok(Some(mut player)) => {
+            let decay = decay::apply(&mut player, chrono::Utc::now(), state.config.idle_decay_after_hours);
+            if !decay.is_empty() {
+                tracing::info!("Applied {} decay events to returning player {}", decay.len(), player.id);
+                if let Err(e) = persistence::save_player(&player) {
+                    tracing::warn!("Failed to save decayed player {}: {}", player.id, e);
+                }
+            }
+
             // Add to in-memory state
             let mut game = state.game.write().await;
-            game.players.insert(player.id, player.clone());
+            let summary = player.summary();
+            game.players.insert(player.id, player);
 
             Ok(Json(LoadGameResponse {
-                player,
-                message: "I remember you... welcome back to the loop.".to_string(),
+                player: summary,
+                message: theme::text(state.config.tenant.as_deref(), if decay.is_empty() {
+                    Flavor::WelcomeBack
+                } else {
+                    Flavor::LongAbsence
+                }),
                 found: true,
+                decay,
             }))
         }
         Ok(None) => {
@@ -107,9 +1069,10 @@ async fn load_game(
             let game = state.game.read().await;
             if let Some(player) = game.get_player(&player_id) {
                 Ok(Json(LoadGameResponse {
-                    player: player.clone(),
-                    message: "You never left the loop.".to_string(),
+                    player: player.summary(),
+                    message: theme::text(state.config.tenant.as_deref(), Flavor::NeverLeft),
                     found: true,
+                    decay: Vec::new(),
                 }))
             } else {
                 Err(StatusCode::NOT_FOUND)
@@ -138,7 +1101,7 @@ async fn save_game(
     match persistence::save_player(player) {
         Ok(()) => Ok(Json(SaveGameResponse {
             success: true,
-            message: "Your journey has been etched into the void.".to_string(),
+            message: theme::text(state.config.tenant.as_deref(), Flavor::Saved),
         })),
         Err(e) => {
             tracing::error!("Failed to save player: {}", e);
@@ -155,9 +1118,17 @@ struct ListSavesResponse {
     saves: Vec<Uuid>,
 }
 
-async fn list_saves() -> Result<Json<ListSavesResponse>, StatusCode> {
+async fn list_saves(State(state): State<AppState>) -> Result<Json<ListSavesResponse>, StatusCode> {
     match persistence::list_saved_players() {
-        Ok(saves) => Ok(Json(ListSavesResponse { saves })),
+        Ok(mut saves) => {
+            let game = state.game.read().await;
+            if game.tenant.is_some() || state.config.tenants_file.is_some() {
+                saves.retain(|id| {
+                    matches!(persistence::load_player(id), Ok(Some(p)) if game.admits(&p))
+                });
+            }
+            Ok(Json(ListSavesResponse { saves }))
+        }
         Err(e) => {
             tracing::error!("Failed to list saves: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -167,189 +1138,902 @@ async fn list_saves() -> Result<Json<ListSavesResponse>, StatusCode> {
 
 #[derive(Serialize)]
 struct GameStateResponse {
-    player: Player,
+    player: PlayerSummary,
     current_moment: Option<NarrativeMoment>,
     ending: Option<EndingResponse>,
+    /// With `?debug=true`, the rerank model's ratings of the current choices
+    #[serde(skip_serializing_if = "Option::is_none")]
+    choice_ratings: Option<MomentRatings>,
+}
+
+#[derive(Deserialize)]
+struct GameStateQuery {
+    #[serde(default)]
+    debug: bool,
 }
 
 async fn get_game_state(
     State(state): State<AppState>,
     Path(player_id): Path<Uuid>,
+    Query(query): Query<GameStateQuery>,
+    headers: HeaderMap,
 ) -> Result<Json<GameStateResponse>, StatusCode> {
     let game = state.game.read().await;
 
     let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
-    let current_moment = player.narrative_history.last().cloned();
+    let current_moment = player.run.narrative_history.last().cloned();
     
     // Check for endings
-    let ending = check_for_ending(player).map(|e| EndingResponse::from_player(player, e));
+    let ending = current_ending(player)
+        .map(|e| ending_response(&state, player, e, Locale::from_headers(&headers)));
+
+    let choice_ratings = current_moment
+        .as_ref()
+        .filter(|_| query.debug)
+        .and_then(|moment| state.ratings.for_moment(&player_id, &moment.id));
 
     Ok(Json(GameStateResponse {
-        player: player.clone(),
+        player: player.summary(),
         current_moment,
         ending,
+        choice_ratings,
     }))
 }
 
-#[derive(Serialize)]
-struct NarrativeResponse {
+#[derive(Clone, Serialize)]
+pub(crate) struct NarrativeResponse {
     moment: NarrativeMoment,
     loop_number: u64,
     nihilism_score: i32,
+    /// Stability of the loop after this moment
+    stability: u8,
     ending: Option<EndingResponse>,
+    /// The loop came apart at this moment and reset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    collapse: Option<ResetResponse>,
+    /// Degradations at the time of the response
+    status: ServerStatus,
 }
 
-async fn start_narrative(
+impl NarrativeResponse {
+    pub(crate) fn moment(&self) -> &NarrativeMoment {
+        &self.moment
+    }
+}
+
+pub(crate) async fn start_narrative(
     State(state): State<AppState>,
     Path(player_id): Path<Uuid>,
-) -> Result<Json<NarrativeResponse>, StatusCode> {
+    headers: HeaderMap,
+) -> Result<Json<NarrativeResponse>, ApiError> {
+    let action = "start".to_string();
+    let work = narrate_start(state.clone(), player_id, headers);
+    let work = journal::journaled(player_id, action.clone(), work);
+    state.narration.run(player_id, action, work).await
+}
+
+async fn narrate_start(
+    state: AppState,
+    player_id: Uuid,
+    headers: HeaderMap,
+) -> Result<Json<NarrativeResponse>, ApiError> {
     let game = state.game.read().await;
     let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?.clone();
     drop(game);
 
-    let moment = state
-        .llm
-        .generate_narrative(&player, None)
-        .await
-        .map_err(|e| {
-            tracing::error!("LLM error: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    player.ensure_playable().map_err(game_error)?;
+    // Racers wait out the countdown
+    if !state.races.has_started(&player) {
+        return Err(StatusCode::TOO_EARLY.into());
+    }
 
-    let mut game = state.game.write().await;
-    let (loop_number, nihilism_score, ending) = if let Some(p) = game.get_player_mut(&player_id) {
-        p.narrative_history.push(moment.clone());
-        let ending = check_for_ending(p).map(|e| EndingResponse::from_player(p, e));
-        (p.current_loop.number, p.memory.nihilism_score, ending)
+    // A scenario anchor that is due replaces the generated moment; ghosted
+    // players get the offline pack and cost nothing
+    let anchor = anchors::due(&player);
+    let mut moment = if let Some(anchor) = anchor {
+        anchor.moment()
+    } else if player.abuse.is_ghosted() {
+        offline::moment(&player)
     } else {
-        (1, 0, None)
+        let _slot = state
+            .generations
+            .acquire(player_id, Priority::of(&player))
+            .await
+            .map_err(|e| queue_error(&state, player_id, e))?;
+        let generation = state
+            .llm
+            .generate_narrative(&player, None, Locale::from_headers(&headers));
+        thinking::scope(&state.config, &state.events, &player, generation)
+            .await
+            .map_err(|e| llm_error(&state, player_id, e))?
     };
 
+    let mut game = state.game.write().await;
+    if run_switched(&game, &player_id, player.run_id()) {
+        return Err(StatusCode::CONFLICT.into());
+    }
+    let p = game.player_mut(&player_id).map_err(game_error)?;
+    // The loop may have been reset, or the run finished, meanwhile
+    p.ensure_playable().map_err(game_error)?;
+    p.ensure_loop(player.run.current_loop.number).map_err(game_error)?;
+    let (loop_number, nihilism_score, stability, ending) = {
+        let loop_number = p.run.current_loop.number;
+        state.world.apply(p, &mut moment);
+        p.present_moment(&mut moment, player.run.pending_notes.len())
+            .map_err(game_error)?;
+        if let Some(anchor) = anchor {
+            anchors::record(p, anchor);
+        }
+        p.run.graph.record_moment(&moment, loop_number);
+        publish_moment(&state, p, &moment);
+        cap_history(&state.config, p);
+        let ending = reached_ending(&state, p, Locale::from_headers(&headers));
+        let current = &p.run.current_loop;
+        (current.number, p.run.memory.nihilism_score, current.stability, ending)
+    };
+    drop(game);
+    let mut ending = ending;
+    judge_ledger(&state, player_id, &mut ending).await;
+    let collapse = collapse_loop(&state, player_id, loop_number, stability, &headers).await;
+
     Ok(Json(NarrativeResponse {
         moment,
         loop_number,
         nihilism_score,
+        stability,
         ending,
+        collapse,
+        status: state.server_status(),
     }))
 }
 
 #[derive(Deserialize)]
-struct ChoiceRequest {
-    choice_id: String,
-    choice_text: String,
+pub(crate) struct ChoiceRequest {
+    pub choice_id: String,
+    pub choice_text: String,
+    /// The moment the player chose from; choices against older moments are refused
+    #[serde(default)]
+    pub moment_id: Option<Uuid>,
 }
 
-async fn make_choice(
+pub(crate) async fn make_choice(
     State(state): State<AppState>,
     Path(player_id): Path<Uuid>,
+    headers: HeaderMap,
     Json(request): Json<ChoiceRequest>,
-) -> Result<Json<NarrativeResponse>, StatusCode> {
+) -> Result<Json<NarrativeResponse>, ApiError> {
+    let action = format!(
+        "choice {} {:?} {}",
+        request.moment_id.map(|id| id.to_string()).unwrap_or_default(),
+        request.choice_id,
+        request.choice_text
+    );
+    let work = narrate_choice(state.clone(), player_id, headers, request);
+    let work = journal::journaled(player_id, action.clone(), work);
+    state.narration.run(player_id, action, work).await
+}
+
+async fn narrate_choice(
+    state: AppState,
+    player_id: Uuid,
+    headers: HeaderMap,
+    request: ChoiceRequest,
+) -> Result<Json<NarrativeResponse>, ApiError> {
+    screen_input(&state, player_id, &request.choice_text).await?;
+
+    let locale = Locale::from_headers(&headers);
+    let choice_text = canonical_choice_text(&state, player_id, &request, locale).await?;
+
+    // Score the choice before taking the lock, as strategies may ask the LLM
+    let is_dark = {
+        let snapshot = state
+            .game
+            .read()
+            .await
+            .get_player(&player_id)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .clone();
+        snapshot.ensure_playable().map_err(game_error)?;
+        let choice = ScoredChoice {
+            id: &request.choice_id,
+            text: &choice_text,
+            moment: snapshot.run.narrative_history.last(),
+            player: &snapshot,
+        };
+        state.scoring.judge(&choice).await > 0.0
+    };
+
     // First, update the player with the choice and get a copy
-    let player = {
+    let (player, chosen, source, made) = {
         let mut game = state.game.write().await;
-        let player = game
-            .get_player_mut(&player_id)
-            .ok_or(StatusCode::NOT_FOUND)?;
+        let player = game.player_mut(&player_id).map_err(game_error)?;
+
+        player.ensure_playable().map_err(game_error)?;
 
-        // Determine if this is a "dark" choice (heuristics)
-        let choice_lower = request.choice_text.to_lowercase();
-        let id_lower = request.choice_id.to_lowercase();
-        
-        let is_dark = id_lower.contains("dark")
-            || id_lower.contains("hurt")
-            || id_lower.contains("ignore")
-            || id_lower.contains("nihil")
-            || id_lower.contains("cruel")
-            || id_lower.contains("abandon")
-            || choice_lower.contains("kill")
-            || choice_lower.contains("abandon")
-            || choice_lower.contains("nothing matters")
-            || choice_lower.contains("don't care")
-            || choice_lower.contains("meaningless")
-            || choice_lower.contains("leave them")
-            || choice_lower.contains("walk away");
-
-        player.make_choice(&request.choice_id, is_dark);
-        player.clone()
+        let chosen = player
+            .choose_moment(request.moment_id)
+            .map_err(game_error)?;
+        let provenance = match player.run.narrative_history.last() {
+            Some(moment) => moment.provenance_of(&request.choice_id),
+            None => Some(Provenance::Player),
+        };
+        player.record_choice_position(&request.choice_id);
+        dialogue::remember(player, &choice_text);
+        let score_delta = player.make_choice(&request.choice_id, is_dark, &state.config.streak_curve());
+        // Published once the choice is answered; a failed answer takes it back
+        let made = GameEvent::ChoiceMade {
+            player_id,
+            run_id: player.run_id(),
+            choice_id: request.choice_id.clone(),
+            choice_text: choice_text.clone(),
+            loop_number: player.run.current_loop.number,
+            is_dark,
+            score_delta,
+            nihilism_score: player.run.memory.nihilism_score,
+            provenance,
+        };
+        let source = player
+            .run
+            .narrative_history
+            .last()
+            .map(|m| fingerprint_text(&m.text));
+        (player.clone(), chosen, source, made)
     };
 
     // Auto-save every 3 choices
-    if player.memory.total_choices % 3 == 0 {
-        if let Err(e) = persistence::save_player(&player) {
-            tracing::warn!("Auto-save failed: {}", e);
-        }
+    if player.run.memory.total_choices % 3 == 0
+        && let Err(e) = persistence::save_player(&player)
+    {
+        tracing::warn!("Auto-save failed: {}", e);
     }
 
     // Generate the next narrative moment
     let choice = crate::game::Choice {
         id: request.choice_id,
-        text: request.choice_text,
+        text: choice_text,
         consequence_hint: None,
+        provenance: None,
     };
 
-    let moment = state
-        .llm
-        .process_choice(&player, &choice)
-        .await
-        .map_err(|e| {
-            tracing::error!("LLM error: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let anchor = anchors::due(&player);
+    let relived = source
+        .as_deref()
+        .filter(|_| anchor.is_none())
+        .and_then(|source| deja_vu(&state, &player, source, &choice.text, locale));
+    let generated = anchor.is_none() && relived.is_none() && !player.abuse.is_ghosted();
+
+    let mut moment = if let Some(anchor) = anchor {
+        anchor.moment()
+    } else if let Some(moment) = relived {
+        moment
+    } else if player.abuse.is_ghosted() {
+        offline::moment(&player)
+    } else {
+        let generation = match state.generations.acquire(player_id, Priority::of(&player)).await {
+            Ok(_slot) => {
+                let generation = state.llm.process_choice(&player, &choice, locale);
+                thinking::scope(&state.config, &state.events, &player, generation)
+                    .await
+                    .map_err(|e| llm_error(&state, player_id, e))
+            }
+            Err(e) => Err(queue_error(&state, player_id, e)),
+        };
+        match generation {
+            Ok(moment) => moment,
+            Err(status) => {
+                if let Some(chosen) = chosen
+                    && let Some(p) = state.game.write().await.get_player_mut(&player_id)
+                {
+                    p.release_choice(chosen);
+                }
+                return Err(status);
+            }
+        }
+    };
 
     // Update the game state with the new moment
-    let (loop_number, nihilism_score, ending) = {
+    let (loop_number, nihilism_score, stability, ending) = {
         let mut game = state.game.write().await;
-        if let Some(p) = game.get_player_mut(&player_id) {
-            p.narrative_history.push(moment.clone());
-            let ending = check_for_ending(p).map(|e| EndingResponse::from_player(p, e));
-            (p.current_loop.number, p.memory.nihilism_score, ending)
-        } else {
-            (1, 0, None)
+        if run_switched(&game, &player_id, player.run_id()) {
+            return Err(StatusCode::CONFLICT.into());
+        }
+        let p = game.player_mut(&player_id).map_err(game_error)?;
+        // The loop may have been reset, or a new moment started, meanwhile
+        p.ensure_loop(player.run.current_loop.number).map_err(game_error)?;
+        if let Some(chosen) = chosen {
+            p.expect_chosen(chosen).map_err(game_error)?;
+        }
+        let loop_number = p.run.current_loop.number;
+        state.world.apply(p, &mut moment);
+        p.present_moment(&mut moment, player.run.pending_notes.len())
+            .map_err(game_error)?;
+        state.events.publish(made);
+        if let Some(anchor) = anchor {
+            anchors::record(p, anchor);
+        }
+        match &source {
+            Some(source) => {
+                p.run.graph.record_transition(
+                    source,
+                    &choice.id,
+                    &choice.text,
+                    &moment,
+                    loop_number,
+                );
+                if generated {
+                    p.run
+                        .graph
+                        .remember_continuation(source, &choice.text, &moment, loop_number);
+                }
+            }
+            None => {
+                p.run.graph.record_moment(&moment, loop_number);
+            }
         }
+        publish_moment(&state, p, &moment);
+        cap_history(&state.config, p);
+        let ending = reached_ending(&state, p, locale);
+        let current = &p.run.current_loop;
+        (current.number, p.run.memory.nihilism_score, current.stability, ending)
     };
+    let mut ending = ending;
+    judge_ledger(&state, player_id, &mut ending).await;
+    let collapse = collapse_loop(&state, player_id, loop_number, stability, &headers).await;
 
     Ok(Json(NarrativeResponse {
         moment,
         loop_number,
         nihilism_score,
+        stability,
         ending,
+        collapse,
+        status: state.server_status(),
     }))
 }
 
-#[derive(Serialize)]
-struct ResetResponse {
-    player: Player,
-    message: String,
-}
+/// Characters of a discarded moment quoted to the narrator writing its replacement
+const DISCARDED_EXCERPT_CHARS: usize = 240;
 
-async fn reset_loop(
+async fn regenerate_player_moment(
     State(state): State<AppState>,
-    Path(player_id): Path<Uuid>,
-) -> Result<Json<ResetResponse>, StatusCode> {
-    let mut game = state.game.write().await;
-
-    let player = game
-        .get_player_mut(&player_id)
-        .ok_or(StatusCode::NOT_FOUND)?;
-
-    player.reset_loop();
+    Path((player_id, moment_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<NarrativeResponse>, ApiError> {
+    let work = narrate_regeneration(state.clone(), player_id, moment_id);
+    state
+        .narration
+        .run(player_id, format!("regenerate {}", moment_id), work)
+        .await
+}
 
-    // Save after reset
-    if let Err(e) = persistence::save_player(player) {
-        tracing::warn!("Failed to save after reset: {}", e);
+/// Write the player's unanswered latest moment again from a different angle
+async fn narrate_regeneration(
+    state: AppState,
+    player_id: Uuid,
+    moment_id: Uuid,
+) -> Result<Json<NarrativeResponse>, ApiError> {
+    let limit = state.config.max_regenerations_per_loop;
+    if limit == 0 {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let game = state.game.read().await;
+    let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?.clone();
+    drop(game);
+
+    player.ensure_playable().map_err(game_error)?;
+    if !state.races.has_started(&player) {
+        return Err(StatusCode::TOO_EARLY.into());
+    }
+    if !player.run.narrative_history.iter().any(|m| m.id == moment_id) {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let original = player.expect_unanswered(moment_id).map_err(game_error)?.clone();
+    if player.run.current_loop.regenerations >= limit {
+        return Err(StatusCode::TOO_MANY_REQUESTS.into());
     }
 
-    let message = format!(
-        "Loop #{} begins. Despite everything... it's still you.",
-        player.current_loop.number
+    let excerpt: String = original.text.chars().take(DISCARDED_EXCERPT_CHARS).collect();
+    let note = format!(
+        "The player set aside a version of this moment that began: \"{}\". Take a \
+         different angle: another scene, another detail, another question for them to answer.",
+        excerpt.trim()
     );
+    let replacement = {
+        let _slot = state
+            .generations
+            .acquire(player_id, Priority::of(&player))
+            .await
+            .map_err(|e| queue_error(&state, player_id, e))?;
+        let generation = regenerate_moment(&state, &player, &original, Some(&note));
+        thinking::scope(&state.config, &state.events, &player, generation)
+            .await
+            .map_err(|e| llm_error(&state, player_id, e))?
+    };
+
+    let mut game = state.game.write().await;
+    if run_switched(&game, &player_id, player.run_id()) {
+        return Err(StatusCode::CONFLICT.into());
+    }
+    let p = game.player_mut(&player_id).map_err(game_error)?;
+    // The player may have answered, or the loop reset, while the narrator was writing
+    p.ensure_loop(player.run.current_loop.number).map_err(game_error)?;
+    p.replace_unanswered(moment_id, replacement.clone())
+        .map_err(game_error)?;
+    if let Err(e) = persistence::save_player(p) {
+        tracing::warn!("Failed to save regenerated moment for {}: {}", player_id, e);
+    }
+    let response = NarrativeResponse {
+        moment: replacement.clone(),
+        loop_number: p.run.current_loop.number,
+        nihilism_score: p.run.memory.nihilism_score,
+        stability: p.run.current_loop.stability,
+        ending: None,
+        collapse: None,
+        status: state.server_status(),
+    };
+    let player = p.clone();
+    drop(game);
+
+    record_regeneration(&state, &player, &original);
+    state.events.publish(GameEvent::MomentRegenerated {
+        player_id,
+        moment_id,
+        replacement_id: replacement.id,
+        loop_number: response.loop_number,
+    });
+    rate_choices(&state, &player, &replacement);
+    Ok(Json(response))
+}
+
+/// Count a discarded moment in the choice quality report
+fn record_regeneration(state: &AppState, player: &Player, discarded: &NarrativeMoment) {
+    if player.abuse.is_ghosted() || !privacy::policy().allows(player, Purpose::Analytics) {
+        return;
+    }
+    let regeneration = Regeneration {
+        moment_id: discarded.id,
+        player_id: player.id,
+        persona: player.run.persona,
+        mood: discarded.mood.clone(),
+        choices: discarded.choices.len(),
+        at: chrono::Utc::now(),
+    };
+    if let Err(e) = state.ratings.record_regeneration(&regeneration) {
+        tracing::warn!("Failed to store moment regeneration: {}", e);
+    }
+}
+
+/// With `DEJA_VU_PROBABILITY`, relive the moment the same choice at the same
+/// moment led to in an earlier loop instead of generating a new one
+fn deja_vu(
+    state: &AppState,
+    player: &Player,
+    source: &str,
+    choice_text: &str,
+    locale: Locale,
+) -> Option<NarrativeMoment> {
+    let probability = state.config.deja_vu_probability;
+    if probability <= 0.0 || player.abuse.is_ghosted() {
+        return None;
+    }
+    let remembered = player.run.graph.continuation(
+        source,
+        choice_text,
+        player.run.current_loop.number,
+    )?;
+    // Relive it only in the language it was first told in
+    let told_in = remembered.translation.as_ref().map(|t| t.locale).unwrap_or_default();
+    if told_in != locale || !rand::random_bool(probability) {
+        return None;
+    }
+
+    let mut moment = remembered.clone();
+    moment.id = Uuid::new_v4();
+    moment.timestamp = chrono::Utc::now();
+    moment.state = MomentState::Generated;
+    // Kept apart from the text, so the moment still fingerprints as itself
+    moment.deja_vu = Some(theme::text(state.config.tenant.as_deref(), Flavor::DejaVu));
+    tracing::debug!("Player {} relives a moment from an earlier loop", player.id);
+    Some(moment)
+}
+
+/// Force a reset once a moment has worn the loop's stability down to nothing
+async fn collapse_loop(
+    state: &AppState,
+    player_id: Uuid,
+    loop_number: u64,
+    stability: u8,
+    headers: &HeaderMap,
+) -> Option<ResetResponse> {
+    if Stage::of(stability) != Stage::Collapsed {
+        return None;
+    }
+    tracing::info!("Loop of player {} collapsed, forcing a reset", player_id);
+    state.events.publish(GameEvent::LoopCollapsed {
+        player_id,
+        loop_number,
+    });
+    let reset = reset_current_loop(state.clone(), player_id, headers.clone(), LoopEndCause::Collapsed);
+    match reset.await {
+        Ok(Json(reset)) => Some(reset),
+        Err(status) => {
+            tracing::warn!("Forced reset of a collapsed loop failed: {}", status);
+            None
+        }
+    }
+}
+
+/// English text of a choice, so scoring, logs and the choice graph don't depend
+/// on the player's language: the moment's own text for an offered choice, and a
+/// translation of free-form input typed in another language
+async fn canonical_choice_text(
+    state: &AppState,
+    player_id: Uuid,
+    request: &ChoiceRequest,
+    locale: Locale,
+) -> Result<String, StatusCode> {
+    let (offered, ghosted) = {
+        let game = state.game.read().await;
+        let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+        let offered = player
+            .run
+            .narrative_history
+            .last()
+            .and_then(|m| m.choices.iter().find(|c| c.id == request.choice_id))
+            .map(|c| c.text.clone());
+        (offered, player.abuse.is_ghosted())
+    };
+    if let Some(text) = offered {
+        return Ok(text);
+    }
+    if !state.llm.flags().is_enabled(Feature::FreeformInput, Some(player_id)) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if locale == Locale::En || ghosted {
+        return Ok(request.choice_text.clone());
+    }
+    Ok(state
+        .llm
+        .translate_to_english(&request.choice_text, locale, player_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("Choice translation failed, keeping the original: {}", e);
+            request.choice_text.clone()
+        }))
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct ResetResponse {
+    player: PlayerSummary,
+    message: String,
+    reset_sequence: Vec<ResetBeat>,
+    finale: Option<Finale>,
+    ending: Option<EndingResponse>,
+}
+
+#[derive(Deserialize)]
+struct LoopEndRequest {
+    cause: LoopEndCause,
+}
+
+/// End the current loop for the given cause and play the reset into the next
+async fn end_loop(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<LoopEndRequest>,
+) -> Result<Json<ResetResponse>, StatusCode> {
+    end_current_loop(state, player_id, headers, request.cause).await
+}
+
+/// Shorthand for ending the loop because the player chose to
+async fn reset_loop(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Json<ResetResponse>, StatusCode> {
+    end_current_loop(state, player_id, headers, LoopEndCause::ChoseReset).await
+}
+
+pub(crate) async fn end_current_loop(
+    state: AppState,
+    player_id: Uuid,
+    headers: HeaderMap,
+    cause: LoopEndCause,
+) -> Result<Json<ResetResponse>, StatusCode> {
+    if cause == LoopEndCause::Collapsed {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    // A loop ends once, whatever the cause given by each retry
+    let work = reset_current_loop(state.clone(), player_id, headers, cause);
+    state.resets.run(player_id, "reset".to_string(), work).await
+}
+
+async fn reset_current_loop(
+    state: AppState,
+    player_id: Uuid,
+    headers: HeaderMap,
+    cause: LoopEndCause,
+) -> Result<Json<ResetResponse>, StatusCode> {
+    let snapshot = {
+        let game = state.game.read().await;
+        game.get_player(&player_id)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .clone()
+    };
+
+    snapshot.ensure_playable().map_err(game_error)?;
+
+    if let Some(max_loops) = state.config.max_loops
+        && snapshot.run.current_loop.number >= max_loops
+    {
+        return run_finale(&state, snapshot, Locale::from_headers(&headers)).await;
+    }
+
+    let reset_sequence = if snapshot.abuse.is_ghosted() {
+        default_reset_sequence(&snapshot, cause)
+    } else {
+        state
+            .llm
+            .generate_reset_sequence(&snapshot, cause)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Reset sequence generation failed, using fallback: {}", e);
+                state.status.record_fallback();
+                default_reset_sequence(&snapshot, cause)
+            })
+    };
+
+    let mut game = state.game.write().await;
+    if run_switched(&game, &player_id, snapshot.run_id()) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+    // The loop may have been reset, or the run finished, meanwhile
+    player.ensure_playable().map_err(game_error)?;
+    player.ensure_loop(snapshot.run.current_loop.number).map_err(game_error)?;
+
+    let mut archived = player
+        .reset_loop(cause, reset_sequence.clone())
+        .map_err(game_error)?;
+    privacy::policy().redact_archive(player, &mut archived);
+    state.events.publish(GameEvent::LoopReset {
+        player_id,
+        loop_number: player.run.current_loop.number,
+        cause,
+    });
+
+    if let Err(e) = persistence::archive_loop(&archived) {
+        tracing::warn!("Failed to archive loop: {}", e);
+    }
+
+    // Save after reset
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save after reset: {}", e);
+    }
+
+    let message = theme::text(state.config.tenant.as_deref(), Flavor::LoopBegins)
+        .replace("{loop}", &player.run.current_loop.number.to_string());
 
     Ok(Json(ResetResponse {
-        player: player.clone(),
+        player: player.summary(),
         message,
+        reset_sequence,
+        finale: None,
+        ending: None,
     }))
 }
 
+/// The loop limit was hit: play the closing arc and seal the run
+async fn run_finale(
+    state: &AppState,
+    snapshot: Player,
+    locale: Locale,
+) -> Result<Json<ResetResponse>, StatusCode> {
+    let ending = nearest_ending(&snapshot);
+    let mut moments = if snapshot.abuse.is_ghosted() {
+        default_finale_moments(&snapshot, &ending)
+    } else {
+        state
+            .llm
+            .generate_finale(&snapshot, &ending)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Finale generation failed, using fallback: {}", e);
+                state.status.record_fallback();
+                default_finale_moments(&snapshot, &ending)
+            })
+    };
+
+    let mut game = state.game.write().await;
+    if run_switched(&game, &snapshot.id, snapshot.run_id()) {
+        return Err(StatusCode::CONFLICT);
+    }
+    let player = game.player_mut(&snapshot.id).map_err(game_error)?;
+
+    // Re-check under the write lock in case of a concurrent reset
+    player.ensure_playable().map_err(game_error)?;
+    player.ensure_loop(snapshot.run.current_loop.number).map_err(game_error)?;
+
+    for moment in moments.iter_mut() {
+        moment.transition(MomentState::Presented).map_err(game_error)?;
+    }
+    let finale = Finale {
+        forced: check_for_ending(player).is_none(),
+        ending: ending.clone(),
+        moments,
+        completed_at: chrono::Utc::now(),
+    };
+    let mut archived = player.complete_run(finale.clone()).map_err(game_error)?;
+    let first_time = player.record_ending(&ending);
+    if first_time {
+        count_soul(state, player, &ending);
+    }
+    state.events.publish(GameEvent::EndingReached {
+        player_id: player.id,
+        ending: ending.clone(),
+        first_time,
+    });
+    state.events.publish(GameEvent::RunCompleted {
+        player_id: player.id,
+        ending: ending.clone(),
+        forced: finale.forced,
+    });
+    tracing::info!("Player {} completed their run with {:?}", player.id, ending);
+
+    privacy::policy().redact_archive(player, &mut archived);
+    if let Err(e) = persistence::archive_loop(&archived) {
+        tracing::warn!("Failed to archive final loop: {}", e);
+    }
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save completed run: {}", e);
+    }
+
+    let mut response = ResetResponse {
+        player: player.summary(),
+        message: i18n::text(locale, Text::RunSealed).to_string(),
+        reset_sequence: Vec::new(),
+        ending: Some(ending_response(state, player, ending, locale)),
+        finale: Some(finale),
+    };
+    drop(game);
+    judge_ledger(state, snapshot.id, &mut response.ending).await;
+    Ok(Json(response))
+}
+
+#[derive(Deserialize)]
+struct EndingStatsQuery {
+    player_id: Option<Uuid>,
+}
+
+/// How many souls reached each ending, across every player, naming only
+/// the endings the viewing player has unlocked
+async fn ending_stats(
+    State(state): State<AppState>,
+    Query(query): Query<EndingStatsQuery>,
+    headers: HeaderMap,
+) -> Json<Vec<GalleryEntry>> {
+    let locale = Locale::from_headers(&headers);
+    let disclosure = {
+        let game = state.game.read().await;
+        let viewer = query.player_id.and_then(|id| game.get_player(&id));
+        Disclosure::for_viewer(&state.config, viewer)
+    };
+    Json(spoilers::entries(
+        state.ending_stats.summary(locale),
+        &disclosure,
+        state.config.content_rating,
+        locale,
+    ))
+}
+
+/// Every ending, with only the player's unlocked ones named
+async fn ending_gallery(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Json<Gallery>, StatusCode> {
+    let locale = Locale::from_headers(&headers);
+    let disclosure = {
+        let game = state.game.read().await;
+        let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+        Disclosure::for_viewer(&state.config, Some(player))
+    };
+    let mut gallery = spoilers::gallery(
+        state.ending_stats.summary(locale),
+        &disclosure,
+        state.config.content_rating,
+        locale,
+    );
+    gallery.privacy = Some(state.ending_stats.privacy());
+    Ok(Json(gallery))
+}
+
+#[derive(Deserialize)]
+struct VerifySealQuery {
+    seal: String,
+}
+
+#[derive(Serialize)]
+struct VerifySealResponse {
+    valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    claims: Option<SealClaims>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Check a run seal against this server's key
+async fn verify_seal(Query(query): Query<VerifySealQuery>) -> Json<VerifySealResponse> {
+    Json(match seal::verify(&query.seal) {
+        Ok(claims) => VerifySealResponse {
+            valid: true,
+            claims: Some(claims),
+            reason: None,
+        },
+        Err(e) => VerifySealResponse {
+            valid: false,
+            claims: None,
+            reason: Some(e.to_string()),
+        },
+    })
+}
+
+#[derive(Deserialize)]
+struct CompareQuery {
+    player_a: Uuid,
+    player_b: Uuid,
+}
+
+/// Side-by-side stats for two runs whose players both allow public stats
+async fn compare_players(
+    State(state): State<AppState>,
+    Query(query): Query<CompareQuery>,
+) -> Result<Json<Comparison>, StatusCode> {
+    let player_a = comparable_player(&state, &query.player_a).await?;
+    let player_b = comparable_player(&state, &query.player_b).await?;
+    let stats = |player: &Player| -> Result<RunStats, StatusCode> {
+        let run_id = player.run_id();
+        let choices = consequences::choice_log(&run_id).map_err(|e| {
+            tracing::error!("Failed to read choice log for {}: {}", run_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        let archives = persistence::load_archived_loops(&run_id).map_err(|e| {
+            tracing::error!("Failed to load archived loops for {}: {}", run_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(RunStats::gather(player, &choices, &archives, |text| {
+            state.choice_clusters.cluster_of(text)
+        }))
+    };
+    Ok(Json(Comparison::new(stats(&player_a)?, stats(&player_b)?)))
+}
+
+/// A player to compare, from memory or disk, who allows public stats
+async fn comparable_player(state: &AppState, player_id: &Uuid) -> Result<Player, StatusCode> {
+    let player = {
+        let game = state.game.read().await;
+        game.get_player(player_id).cloned()
+    };
+    let player = match player {
+        Some(player) => player,
+        None => {
+            let loaded = persistence::load_player(player_id).map_err(|e| {
+                tracing::error!("Failed to load player {}: {}", player_id, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            let game = state.game.read().await;
+            loaded
+                .filter(|p| game.admits(p))
+                .ok_or(StatusCode::NOT_FOUND)?
+        }
+    };
+    if !privacy::policy().allows(&player, Purpose::PublicStats) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(player)
+}
+
 #[derive(Serialize)]
 struct EndingCheckResponse {
     has_ending: bool,
@@ -359,14 +2043,2528 @@ struct EndingCheckResponse {
 async fn check_ending(
     State(state): State<AppState>,
     Path(player_id): Path<Uuid>,
+    headers: HeaderMap,
 ) -> Result<Json<EndingCheckResponse>, StatusCode> {
     let game = state.game.read().await;
     let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
 
-    let ending = check_for_ending(player).map(|e| EndingResponse::from_player(player, e));
+    let ending = current_ending(player)
+        .map(|e| ending_response(&state, player, e, Locale::from_headers(&headers)));
 
     Ok(Json(EndingCheckResponse {
         has_ending: ending.is_some(),
         ending,
     }))
 }
+
+#[derive(Serialize)]
+struct RefuseEndingResponse {
+    refused: EndingType,
+    player: PlayerSummary,
+}
+
+/// Turn down the ending the player stands at, opening its refusal branch
+async fn refuse_ending(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+) -> Result<Json<RefuseEndingResponse>, StatusCode> {
+    let mut game = state.game.write().await;
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+    player.ensure_playable().map_err(game_error)?;
+    let ending = check_for_ending(player).ok_or(StatusCode::CONFLICT)?;
+
+    player.refuse_ending(&ending);
+    tracing::info!("Player {} refused {:?}", player_id, ending);
+    state.events.publish(GameEvent::EndingRefused {
+        player_id,
+        ending: ending.clone(),
+    });
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save after refusing an ending: {}", e);
+    }
+
+    Ok(Json(RefuseEndingResponse {
+        refused: ending,
+        player: player.summary(),
+    }))
+}
+
+#[derive(Deserialize)]
+struct GraphQuery {
+    format: Option<String>,
+}
+
+async fn get_graph(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Query(query): Query<GraphQuery>,
+) -> Result<Response, StatusCode> {
+    let game = state.game.read().await;
+    let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+
+    match query.format.as_deref() {
+        None | Some("d3") | Some("json") => Ok(Json(player.run.graph.to_d3()).into_response()),
+        Some("dot") => Ok((
+            [(header::CONTENT_TYPE, "text/vnd.graphviz; charset=utf-8")],
+            player.run.graph.to_dot(),
+        )
+            .into_response()),
+        Some(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+const DEFAULT_HISTORY_PAGE: usize = 20;
+const MAX_HISTORY_PAGE: usize = 100;
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct HistoryResponse {
+    moments: Vec<NarrativeMoment>,
+    total: usize,
+    offset: usize,
+    limit: usize,
+}
+
+async fn get_history(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<HistoryResponse>, StatusCode> {
+    let game = state.game.read().await;
+    let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+
+    let total = player.run.narrative_history.len();
+    let offset = query.offset.unwrap_or(0).min(total);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_PAGE)
+        .clamp(1, MAX_HISTORY_PAGE);
+
+    let mut moments: Vec<NarrativeMoment> = player
+        .run
+        .narrative_history
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .cloned()
+        .collect();
+    if let Err(e) =
+        persistence::restore_spilled(&player.run_id(), player.run.current_loop.number, &mut moments)
+    {
+        tracing::warn!("Failed to restore spilled history: {}", e);
+    }
+
+    Ok(Json(HistoryResponse {
+        moments,
+        total,
+        offset,
+        limit,
+    }))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<String>,
+    source: Option<String>,
+}
+
+async fn export_game(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, StatusCode> {
+    let format = match query.format.as_deref() {
+        None => ExportFormat::Twee,
+        Some(f) => ExportFormat::parse(f).ok_or(StatusCode::BAD_REQUEST)?,
+    };
+    let kind = match query.source.as_deref() {
+        None | Some("run") => Kind::Run,
+        Some("graph") => Kind::Graph,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let (mut player, mut archives) = export_material(&state, &player_id).await?;
+    let manifest = redaction::redact(kind, &mut player, &archives, &mut HashMap::new());
+    // Exports get shared around, so they are scrubbed like any public surface
+    player.name = state.sanitizer.scrub_opt("export", &player.name);
+    player.run.run_name = state.sanitizer.scrub_opt("export", &player.run.run_name);
+    player.run.title = state.sanitizer.scrub_opt("export", &player.run.title);
+
+    let body = match kind {
+        Kind::Run => {
+            for archived in &mut archives {
+                state.sanitizer.scrub_moments("export", &mut archived.moments);
+                if let Some(shard) = &mut archived.shard {
+                    shard.summary = state.sanitizer.scrub("export", &shard.summary);
+                }
+            }
+            state
+                .sanitizer
+                .scrub_moments("export", &mut player.run.narrative_history);
+            export::export_run(&player, &archives, format, manifest)
+        }
+        _ => {
+            state.sanitizer.scrub_graph("export", &mut player.run.graph);
+            export::export_graph(&player, &player.run.graph, format, manifest)
+        }
+    };
+
+    let disposition = format!(
+        "attachment; filename=\"nihilism-{}.{}\"",
+        player_id,
+        format.extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// The player with the active run's spilled history restored, and that
+/// run's archived loops
+async fn export_material(
+    state: &AppState,
+    player_id: &Uuid,
+) -> Result<(Player, Vec<ArchivedLoop>), StatusCode> {
+    let mut player = {
+        let game = state.game.read().await;
+        game.get_player(player_id)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .clone()
+    };
+    let archives = persistence::load_archived_loops(&player.run_id()).map_err(|e| {
+        tracing::error!("Failed to load archives for export: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    persistence::restore_spilled(
+        &player.run_id(),
+        player.run.current_loop.number,
+        &mut player.run.narrative_history,
+    )
+    .map_err(|e| {
+        tracing::error!("Failed to restore spilled history for export: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok((player, archives))
+}
+
+#[derive(Deserialize)]
+struct ExportPreviewQuery {
+    source: Option<String>,
+}
+
+/// What an export or backup would include and leave out, before it is made
+async fn export_preview(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Query(query): Query<ExportPreviewQuery>,
+) -> Result<Json<Preview>, StatusCode> {
+    let kind = match query.source.as_deref() {
+        None => Kind::Run,
+        Some(source) => Kind::parse(source).ok_or(StatusCode::BAD_REQUEST)?,
+    };
+    let preview = if kind == Kind::Backup {
+        let player = {
+            let game = state.game.read().await;
+            game.get_player(&player_id)
+                .ok_or(StatusCode::NOT_FOUND)?
+                .clone()
+        };
+        let backup = Backup::collect(&player).map_err(|e| {
+            tracing::error!("Failed to collect a backup preview of {}: {}", player_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        redaction::preview(kind, &backup.player, &backup.archives, &backup.choice_logs)
+    } else {
+        let (player, archives) = export_material(&state, &player_id).await?;
+        redaction::preview(kind, &player, &archives, &HashMap::new())
+    };
+    Ok(Json(preview))
+}
+
+/// A signed, compressed backup of the player with all runs and archives
+async fn backup_game(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+) -> Result<Response, StatusCode> {
+    let player = {
+        let game = state.game.read().await;
+        game.get_player(&player_id)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .clone()
+    };
+    let blob = Backup::collect(&player)
+        .and_then(|mut b| {
+            b.redact();
+            backup::seal(&b)
+        })
+        .map_err(|e| {
+            tracing::error!("Failed to back up player {}: {}", player_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let disposition = format!("attachment; filename=\"nihilism-{}.nhbk\"", player_id);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        blob,
+    )
+        .into_response())
+}
+
+/// Restore a backup as a new player with fresh ids
+async fn import_game(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<Json<PlayerSummary>, StatusCode> {
+    let restored = backup::open(&body)
+        .map_err(|e| {
+            tracing::warn!("Refused backup import: {}", e);
+            match e {
+                BackupError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+                BackupError::BadSignature => StatusCode::FORBIDDEN,
+                _ => StatusCode::BAD_REQUEST,
+            }
+        })?
+        .reassign_ids();
+    let mut player = restored.player.clone();
+    // Accounts and presence belong to the server the backup came from
+    player.account_id = None;
+    player.tenant = state.config.tenant.clone();
+    player.presence_public = false;
+    player.build = Some(build_info::current().clone());
+
+    backup::restore_files(&restored)
+        .and_then(|_| persistence::save_player(&player))
+        .map_err(|e| {
+            tracing::error!("Failed to restore backup: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    tracing::info!(
+        "Restored a backup from {} ({}) as player {}",
+        restored.exported_at,
+        restored
+            .exported_by
+            .as_ref()
+            .map_or_else(|| "unknown build".to_string(), BuildInfo::describe),
+        player.id
+    );
+    let summary = player.summary();
+    state.game.write().await.players.insert(player.id, player);
+    state.events.publish(GameEvent::PlayerCreated {
+        player_id: summary.id,
+    });
+    Ok(Json(summary))
+}
+
+async fn get_presence(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+) -> Result<Response, StatusCode> {
+    let player = {
+        let game = state.game.read().await;
+        game.get_player(&player_id)
+            .filter(|p| p.presence_public)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .clone()
+    };
+
+    let loop_number = player.run.current_loop.number;
+    let status = match state.presence.get(&player_id, loop_number).await {
+        Some(status) => status,
+        None if player.abuse.is_ghosted() => presence::fallback_status(&player),
+        None => {
+            let status = state
+                .llm
+                .generate_status_line(&player)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::debug!("Presence status generation failed: {}", e);
+                    presence::fallback_status(&player)
+                });
+            state
+                .presence
+                .insert(player_id, loop_number, status.clone())
+                .await;
+            status
+        }
+    };
+
+    let status = state.sanitizer.scrub("presence", &status);
+    let blob: Presence = presence::build(&player, status);
+    Ok((
+        [(header::CACHE_CONTROL, "public, max-age=30")],
+        Json(blob),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct PresenceVisibilityRequest {
+    public: bool,
+}
+
+#[derive(Serialize)]
+struct PresenceVisibilityResponse {
+    public: bool,
+}
+
+async fn set_presence_visibility(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Json(request): Json<PresenceVisibilityRequest>,
+) -> Result<Json<PresenceVisibilityResponse>, StatusCode> {
+    let mut game = state.game.write().await;
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+    player.presence_public = request.public;
+
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save presence visibility: {}", e);
+    }
+
+    Ok(Json(PresenceVisibilityResponse {
+        public: player.presence_public,
+    }))
+}
+
+async fn admin_scheduler(State(state): State<AppState>) -> Json<Vec<JobMetrics>> {
+    Json(state.scheduler.metrics().await)
+}
+
+#[derive(Serialize)]
+struct PersonaOption {
+    persona: Persona,
+    title: &'static str,
+    unlocked: bool,
+}
+
+#[derive(Serialize)]
+struct ProfileResponse {
+    name: Option<String>,
+    persona: Persona,
+    personas: Vec<PersonaOption>,
+    presence_public: bool,
+    consent: Consent,
+    export_privacy: ExportPrivacy,
+}
+
+fn profile_of(player: &Player) -> ProfileResponse {
+    let unlocked = player.unlocked_personas();
+    ProfileResponse {
+        name: player.name.clone(),
+        persona: player.run.persona,
+        personas: Persona::ALL
+            .into_iter()
+            .map(|p| PersonaOption {
+                persona: p,
+                title: p.get_title(),
+                unlocked: unlocked.contains(&p),
+            })
+            .collect(),
+        presence_public: player.presence_public,
+        consent: privacy::policy().consent(player),
+        export_privacy: player.export_privacy,
+    }
+}
+
+async fn get_profile(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+) -> Result<Json<ProfileResponse>, StatusCode> {
+    let game = state.game.read().await;
+    let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(profile_of(player)))
+}
+
+#[derive(Deserialize)]
+struct ProfileUpdateRequest {
+    name: Option<String>,
+    persona: Option<Persona>,
+    consent: Option<ConsentUpdate>,
+    export_privacy: Option<ExportPrivacyUpdate>,
+}
+
+async fn update_profile(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Json(request): Json<ProfileUpdateRequest>,
+) -> Result<Json<ProfileResponse>, StatusCode> {
+    let mut game = state.game.write().await;
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+
+    // Consent and export privacy can be changed at any time, even on a finished run
+    if player.is_locked() && (request.name.is_some() || request.persona.is_some()) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    if let Some(persona) = request.persona {
+        if !persona.is_unlocked(&player.endings_reached()) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        if persona != player.run.persona {
+            player.set_persona(persona);
+            state.events.publish(GameEvent::PersonaChanged { player_id, persona });
+        }
+    }
+
+    if let Some(name) = request.name {
+        let name = name.trim();
+        player.name = (!name.is_empty()).then(|| name.chars().take(40).collect());
+    }
+
+    if let Some(consent) = request.consent {
+        let policy = privacy::policy();
+        if !policy.update(player, consent).transcripts {
+            match policy.redact_archives(player) {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Redacted {} archived loops of {}", n, player_id),
+                Err(e) => tracing::warn!("Failed to redact archives of {}: {}", player_id, e),
+            }
+        }
+    }
+
+    if let Some(update) = request.export_privacy {
+        player.export_privacy.update(update);
+    }
+
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save profile: {}", e);
+    }
+
+    Ok(Json(profile_of(player)))
+}
+
+/// Apply an RFC 6902 JSON Patch to the player's name and settings
+async fn patch_player(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Json(operations): Json<Vec<Operation>>,
+) -> Result<Json<PlayerSummary>, StatusCode> {
+    let mut game = state.game.write().await;
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+
+    patch::apply(player, operations).map_err(|e| {
+        tracing::debug!("Refused patch for {}: {}", player_id, e);
+        match e {
+            PatchError::PathNotAllowed(_) => StatusCode::FORBIDDEN,
+            PatchError::TestFailed(_) => StatusCode::CONFLICT,
+            PatchError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            PatchError::Missing(_) | PatchError::Invalid(..) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    })?;
+
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save patched player: {}", e);
+    }
+
+    Ok(Json(player.summary()))
+}
+
+#[derive(Serialize)]
+struct RunsResponse {
+    runs: Vec<RunView>,
+}
+
+async fn list_runs(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+) -> Result<Json<RunsResponse>, StatusCode> {
+    let game = state.game.read().await;
+    let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(RunsResponse {
+        runs: player.run_views(),
+    }))
+}
+
+#[derive(Deserialize, Default)]
+struct CreateRunRequest {
+    name: Option<String>,
+    persona: Option<Persona>,
+}
+
+/// Start another run for the player, leaving the active one in place
+async fn create_run(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    request: Option<Json<CreateRunRequest>>,
+) -> Result<Json<RunView>, StatusCode> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let mut game = state.game.write().await;
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+
+    // Challenge and race runs are one shared attempt, not an identity to branch from
+    if player.run.challenge.is_some()
+        || player.run.race.is_some()
+        || player.runs.len() + 1 >= state.config.max_runs
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+    let persona = request.persona.unwrap_or_default();
+    if !persona.is_unlocked(&player.endings_reached()) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let name = request
+        .name
+        .map(|n| n.trim().chars().take(40).collect::<String>())
+        .filter(|n| !n.is_empty());
+    let run = player.create_run(name, persona);
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save new run: {}", e);
+    }
+    Ok(Json(run))
+}
+
+async fn activate_run(
+    State(state): State<AppState>,
+    Path((player_id, run_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<PlayerSummary>, StatusCode> {
+    let mut game = state.game.write().await;
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+    if !player.activate_run(run_id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save after switching runs: {}", e);
+    }
+    Ok(Json(player.summary()))
+}
+
+/// Have the narrator title the run again, different from its current title
+async fn reroll_run_title(
+    State(state): State<AppState>,
+    Path((player_id, run_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<RunView>, ApiError> {
+    let player = {
+        let game = state.game.read().await;
+        game.get_player(&player_id)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .clone()
+    };
+    let Some(view) = player.run_views().into_iter().find(|r| r.run_id == run_id) else {
+        return Err(StatusCode::NOT_FOUND.into());
+    };
+    if player.abuse.is_ghosted() {
+        return Err(StatusCode::CONFLICT.into());
+    }
+
+    let title = titles::title_run(&state.game, &state.llm, &player, run_id, view.title.as_deref())
+        .await
+        .map_err(|e| llm_failure(&state, e))?
+        // The first loop hasn't ended yet, so there is nothing to title
+        .ok_or(StatusCode::CONFLICT)?;
+    Ok(Json(RunView {
+        title: Some(title),
+        ..view
+    }))
+}
+
+#[derive(Deserialize)]
+struct NameRunRequest {
+    title: Option<String>,
+}
+
+/// Name a run in place of its generated title; an empty name clears it
+async fn name_run(
+    State(state): State<AppState>,
+    Path((player_id, run_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<NameRunRequest>,
+) -> Result<Json<RunView>, StatusCode> {
+    let name = request
+        .title
+        .map(|n| n.trim().chars().take(40).collect::<String>())
+        .filter(|n| !n.is_empty());
+    let mut game = state.game.write().await;
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+    player.run_mut(run_id).ok_or(StatusCode::NOT_FOUND)?.run_name = name;
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save run name: {}", e);
+    }
+    player
+        .run_views()
+        .into_iter()
+        .find(|r| r.run_id == run_id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Longest text accepted for seed memories
+const MAX_SEED_TEXT_CHARS: usize = 8000;
+
+#[derive(Deserialize)]
+struct SeedMemoriesRequest {
+    text: String,
+}
+
+#[derive(Serialize)]
+struct SeedMemoriesResponse {
+    seed_memories: Vec<String>,
+}
+
+/// Summarize a diary entry, poem or similar into seed memories for the active
+/// run. Only the summaries are kept; the text itself never reaches a prompt.
+async fn seed_memories(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Json(request): Json<SeedMemoriesRequest>,
+) -> Result<Json<SeedMemoriesResponse>, ApiError> {
+    let text = request.text.trim();
+    if text.is_empty() {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    if text.chars().count() > MAX_SEED_TEXT_CHARS {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE.into());
+    }
+    screen_input(&state, player_id, text).await?;
+
+    let snapshot = {
+        let game = state.game.read().await;
+        game.get_player(&player_id)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .clone()
+    };
+    snapshot.ensure_playable().map_err(game_error)?;
+    // Indistinguishable from the LLM being unavailable
+    if snapshot.abuse.is_ghosted() {
+        let throttled = Throttled::new(Reason::CircuitOpen, upstream::COOLDOWN);
+        return Err(throttle(&state, player_id, throttled));
+    }
+
+    let memories = state
+        .llm
+        .generate_seed_memories(&snapshot, text)
+        .await
+        .map_err(|e| llm_error(&state, player_id, e))?;
+
+    let mut game = state.game.write().await;
+    if run_switched(&game, &player_id, snapshot.run_id()) {
+        return Err(StatusCode::CONFLICT.into());
+    }
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+    player.run.memory.seed_memories = memories.clone();
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save seed memories: {}", e);
+    }
+
+    Ok(Json(SeedMemoriesResponse {
+        seed_memories: memories,
+    }))
+}
+
+#[derive(Serialize)]
+struct MemoriesResponse {
+    memories: Vec<KeyMemory>,
+    memories_forgotten: u64,
+}
+
+impl MemoriesResponse {
+    fn of(player: &Player) -> Self {
+        Self {
+            memories: player.run.memory.memories(),
+            memories_forgotten: player.run.memory.memories_forgotten,
+        }
+    }
+}
+
+async fn list_memories(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+) -> Result<Json<MemoriesResponse>, StatusCode> {
+    let game = state.game.read().await;
+    let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(MemoriesResponse::of(player)))
+}
+
+#[derive(Deserialize)]
+struct PinMemoryRequest {
+    pinned: bool,
+}
+
+async fn pin_memory(
+    State(state): State<AppState>,
+    Path((player_id, index)): Path<(Uuid, usize)>,
+    Json(request): Json<PinMemoryRequest>,
+) -> Result<Json<MemoriesResponse>, StatusCode> {
+    let mut game = state.game.write().await;
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+    player.ensure_playable().map_err(game_error)?;
+    player.pin_memory(index, request.pinned).map_err(memory_error)?;
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save after pinning a memory: {}", e);
+    }
+    Ok(Json(MemoriesResponse::of(player)))
+}
+
+#[derive(Serialize)]
+struct ForgetMemoryResponse {
+    forgotten: String,
+    score_delta: i32,
+    nihilism_score: i32,
+    #[serde(flatten)]
+    memories: MemoriesResponse,
+}
+
+/// Let a key memory go, at a cost; the narrator notices the gap next moment
+async fn forget_memory(
+    State(state): State<AppState>,
+    Path((player_id, index)): Path<(Uuid, usize)>,
+) -> Result<Json<ForgetMemoryResponse>, StatusCode> {
+    let mut game = state.game.write().await;
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+    player.ensure_playable().map_err(game_error)?;
+    let before = player.run.memory.nihilism_score;
+    let forgotten = player.forget_memory(index).map_err(memory_error)?;
+    let nihilism_score = player.run.memory.nihilism_score;
+    state.events.publish(GameEvent::MemoryForgotten {
+        player_id,
+        loop_number: player.run.current_loop.number,
+        nihilism_score,
+    });
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save after forgetting a memory: {}", e);
+    }
+    Ok(Json(ForgetMemoryResponse {
+        forgotten,
+        score_delta: nihilism_score - before,
+        nihilism_score,
+        memories: MemoriesResponse::of(player),
+    }))
+}
+
+#[derive(Serialize)]
+struct EpiloguesResponse {
+    epilogues: Vec<EpilogueView>,
+}
+
+async fn list_epilogues(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Json<EpiloguesResponse>, StatusCode> {
+    let game = state.game.read().await;
+    let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(EpiloguesResponse {
+        epilogues: epilogue::unlocked(&player.run, Locale::from_headers(&headers)),
+    }))
+}
+
+#[derive(Serialize)]
+struct EpilogueResponse {
+    epilogue: Epilogue,
+    moment: NarrativeMoment,
+    complete: bool,
+}
+
+/// Play the next moment of an ending's epilogue. Unscored, and allowed after
+/// the run has been sealed.
+async fn play_epilogue(
+    State(state): State<AppState>,
+    Path((player_id, ending)): Path<(Uuid, EndingType)>,
+    request: Option<Json<ChoiceRequest>>,
+) -> Result<Json<EpilogueResponse>, StatusCode> {
+    let choice = request.map(|Json(r)| r.choice_text);
+    if let Some(choice) = &choice {
+        screen_input(&state, player_id, choice).await?;
+    }
+
+    let (snapshot, current) = {
+        let game = state.game.read().await;
+        let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+        if !player.run.memory.endings_reached.contains(&ending) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        let current = player
+            .run
+            .epilogues
+            .iter()
+            .find(|e| e.ending == ending)
+            .cloned()
+            .unwrap_or_else(|| Epilogue::new(ending.clone()));
+        if current.is_complete() {
+            return Err(StatusCode::CONFLICT);
+        }
+        (player.clone(), current)
+    };
+
+    let mut moment = if snapshot.abuse.is_ghosted() {
+        default_epilogue_moment(&current)
+    } else {
+        state
+            .llm
+            .generate_epilogue_moment(&snapshot, &current, choice.as_deref())
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Epilogue generation failed, using fallback: {}", e);
+                state.status.record_fallback();
+                default_epilogue_moment(&current)
+            })
+    };
+
+    let mut game = state.game.write().await;
+    if run_switched(&game, &player_id, snapshot.run_id()) {
+        return Err(StatusCode::CONFLICT);
+    }
+    let player = game.player_mut(&player_id).map_err(game_error)?;
+
+    let epilogue = match player.run.epilogues.iter().position(|e| e.ending == ending) {
+        Some(i) => &mut player.run.epilogues[i],
+        None => {
+            player.run.epilogues.push(Epilogue::new(ending.clone()));
+            player.run.epilogues.last_mut().expect("just pushed")
+        }
+    };
+    // Another request played this step while we were generating
+    if epilogue.moments.len() != current.moments.len() {
+        return Err(StatusCode::CONFLICT);
+    }
+    moment.transition(MomentState::Presented).map_err(game_error)?;
+    epilogue.push(moment.clone(), choice);
+    let epilogue = epilogue.clone();
+
+    if epilogue.is_complete() {
+        tracing::info!("Player {} completed the {:?} epilogue", player.id, ending);
+        if let Err(e) = persistence::archive_epilogue(&player.run_id(), &epilogue) {
+            tracing::warn!("Failed to archive epilogue: {}", e);
+        }
+    }
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save epilogue: {}", e);
+    }
+
+    Ok(Json(EpilogueResponse {
+        complete: epilogue.is_complete(),
+        epilogue,
+        moment,
+    }))
+}
+
+fn push_error_status(error: PushError) -> StatusCode {
+    match error {
+        PushError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        PushError::NoWebPush => StatusCode::SERVICE_UNAVAILABLE,
+        PushError::Storage(e) => {
+            tracing::error!("Push registration storage error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Push routes answer 404 unless `PUSH_ENABLED`
+fn require_push(state: &AppState) -> Result<&PushBridge, StatusCode> {
+    if !state.config.push_enabled {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(&state.push)
+}
+
+#[derive(Serialize)]
+struct VapidKeyResponse {
+    public_key: String,
+}
+
+async fn push_vapid_key(
+    State(state): State<AppState>,
+) -> Result<Json<VapidKeyResponse>, StatusCode> {
+    let public_key = require_push(&state)?
+        .vapid_public_key()
+        .ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(Json(VapidKeyResponse { public_key }))
+}
+
+async fn get_push(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+) -> Result<Json<PushView>, StatusCode> {
+    let push = require_push(&state)?;
+    push.view(&player_id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn register_push(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Json(request): Json<PushRequest>,
+) -> Result<Json<PushView>, StatusCode> {
+    let push = require_push(&state)?;
+    if fetch_player(&state, &player_id).await?.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let view = push
+        .register(player_id, request)
+        .map_err(push_error_status)?;
+    tracing::info!("Player {} registered {} push notifications", player_id, view.kind);
+    Ok(Json(view))
+}
+
+async fn unregister_push(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+) -> Result<StatusCode, StatusCode> {
+    let push = require_push(&state)?;
+    match push.unregister(&player_id) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Err(push_error_status(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+struct UnsubscribeRequest {
+    token: String,
+}
+
+/// Unsubscribe through the link every notification carries
+async fn push_unsubscribe(
+    State(state): State<AppState>,
+    Json(request): Json<UnsubscribeRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let push = require_push(&state)?;
+    match push.unsubscribe(request.token.trim()) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Err(push_error_status(e.into())),
+    }
+}
+
+async fn admin_position_bias(State(state): State<AppState>) -> Json<PositionBias> {
+    let mut players = analytics::all_players(&state.game).await;
+    players.retain(|p| privacy::policy().allows(p, Purpose::Analytics));
+    Json(analytics::position_bias(&players, state.config.shuffle_choices))
+}
+
+/// How moments and choices of each provenance were taken
+async fn admin_provenance(
+    State(state): State<AppState>,
+    Query(filter): Query<provenance::Filter>,
+) -> Json<provenance::Report> {
+    let mut players = analytics::all_players(&state.game).await;
+    players.retain(|p| privacy::policy().allows(p, Purpose::Analytics));
+    Json(provenance::report(&players, &filter))
+}
+
+/// A hypothetical player state to check against the endings
+#[derive(Deserialize)]
+struct SimulateEndingRequest {
+    #[serde(default)]
+    loops: u64,
+    /// Defaults to the dark and light choices together
+    choices: Option<u64>,
+    #[serde(default)]
+    dark_choices: u64,
+    #[serde(default)]
+    light_choices: u64,
+    #[serde(default)]
+    score: i32,
+    #[serde(default)]
+    streak: i32,
+    #[serde(default)]
+    truths: Vec<String>,
+    #[serde(default)]
+    memories: Vec<String>,
+    /// Endings reached in earlier runs
+    #[serde(default)]
+    endings: Vec<EndingType>,
+    /// Conditions to try in place of the scenario's, by ending
+    #[serde(default)]
+    conditions: HashMap<EndingType, String>,
+}
+
+async fn admin_simulate_ending(
+    State(state): State<AppState>,
+    Json(request): Json<SimulateEndingRequest>,
+) -> Result<Json<Simulation>, StatusCode> {
+    let overrides = request
+        .conditions
+        .iter()
+        .map(|(ending, source)| {
+            Condition::parse(source).map(|c| (ending.clone(), c)).map_err(|e| {
+                tracing::debug!("Invalid condition for {:?}: {}", ending, e);
+                StatusCode::BAD_REQUEST
+            })
+        })
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+    let mut player = Player::new();
+    player.tenant = state.config.tenant.clone();
+    let memory = &mut player.run.memory;
+    memory.total_loops = request.loops;
+    memory.total_choices = request
+        .choices
+        .unwrap_or(request.dark_choices + request.light_choices);
+    memory.dark_choices = request.dark_choices;
+    memory.light_choices = request.light_choices;
+    memory.nihilism_score = request.score;
+    memory.choice_streak = request.streak;
+    memory.truths_discovered = request.truths;
+    memory.key_memories = request.memories;
+    memory.endings_reached = request.endings;
+    player.run.current_loop.number = request.loops + 1;
+
+    Ok(Json(endings::simulate(&player, overrides)))
+}
+
+async fn admin_choice_buckets(State(state): State<AppState>) -> Json<BucketReport> {
+    Json(state.choice_clusters.report())
+}
+
+async fn admin_events(State(state): State<AppState>) -> Json<Vec<EventCount>> {
+    Json(state.event_counters.snapshot())
+}
+
+async fn admin_sanitize(State(state): State<AppState>) -> Json<SanitizeReport> {
+    Json(state.sanitizer.report())
+}
+
+async fn admin_costs(State(state): State<AppState>) -> Json<CostReport> {
+    Json(state.llm.usage().report())
+}
+
+#[derive(Deserialize)]
+struct RatingsQuery {
+    /// `YYYY-MM`, the current month by default
+    month: Option<String>,
+}
+
+async fn admin_ratings(
+    State(state): State<AppState>,
+    Query(query): Query<RatingsQuery>,
+) -> Result<Json<RatingReport>, StatusCode> {
+    let month = query
+        .month
+        .unwrap_or_else(|| chrono::Utc::now().format("%Y-%m").to_string());
+    if chrono::NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    state.ratings.report(&month).map(Json).map_err(|e| {
+        tracing::error!("Failed to read choice ratings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+#[derive(Deserialize)]
+struct CompactionQuery {
+    /// Overrides `ARCHIVE_KEEP_LOOPS`
+    keep: Option<u64>,
+    dry_run: Option<bool>,
+}
+
+async fn compaction(
+    state: &AppState,
+    query: CompactionQuery,
+    dry_run: bool,
+) -> Result<Json<CompactionReport>, StatusCode> {
+    let keep_loops = query
+        .keep
+        .or(state.config.archive_keep_loops)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let tenant = state.config.tenant.as_deref();
+    retention::compact_archives(&state.llm, tenant, keep_loops, dry_run)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("Archive compaction failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Report which archived loops the retention policy would compact
+async fn admin_compaction_preview(
+    State(state): State<AppState>,
+    Query(query): Query<CompactionQuery>,
+) -> Result<Json<CompactionReport>, StatusCode> {
+    compaction(&state, query, true).await
+}
+
+/// Run archive compaction now
+async fn admin_compaction_run(
+    State(state): State<AppState>,
+    Query(query): Query<CompactionQuery>,
+) -> Result<Json<CompactionReport>, StatusCode> {
+    let dry_run = query.dry_run.unwrap_or(false);
+    compaction(&state, query, dry_run).await
+}
+
+#[derive(Deserialize)]
+struct JanitorQuery {
+    dry_run: Option<bool>,
+}
+
+async fn janitor_sweep(state: &AppState, dry_run: bool) -> Result<Json<JanitorReport>, StatusCode> {
+    state
+        .janitor
+        .sweep(dry_run)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("Janitor sweep failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Report orphaned data without deleting anything
+async fn admin_janitor_preview(
+    State(state): State<AppState>,
+) -> Result<Json<JanitorReport>, StatusCode> {
+    janitor_sweep(&state, true).await
+}
+
+/// Delete orphaned data now
+async fn admin_janitor_run(
+    State(state): State<AppState>,
+    Query(query): Query<JanitorQuery>,
+) -> Result<Json<JanitorReport>, StatusCode> {
+    janitor_sweep(&state, query.dry_run.unwrap_or(false)).await
+}
+
+/// Ghosted players awaiting review, most recently ghosted first
+async fn admin_abuse_queue(State(state): State<AppState>) -> Json<Vec<ReviewEntry>> {
+    let players = analytics::all_players(&state.game).await;
+    let mut queue: Vec<ReviewEntry> = players.iter().filter_map(ReviewEntry::from_player).collect();
+    queue.sort_by_key(|entry| std::cmp::Reverse(entry.ghosted_at));
+    Json(queue)
+}
+
+/// Stand-in text for a moment struck from the record
+
+#[derive(Deserialize)]
+struct MomentEditRequest {
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    choices: Option<Vec<Choice>>,
+    /// Generate the moment again instead; only the latest moment can be
+    #[serde(default)]
+    regenerate: bool,
+    #[serde(default)]
+    strike: bool,
+    /// Guidance for the narrator when generating the moment again
+    #[serde(default)]
+    note: Option<String>,
+    /// Why the moment was changed, kept in the audit log
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl MomentEditRequest {
+    /// The single change requested, if the request is consistent
+    fn action(&self) -> Option<MomentAction> {
+        let edits = self.text.is_some() || self.choices.is_some();
+        match (edits, self.regenerate, self.strike) {
+            (true, false, false) => Some(MomentAction::Edit),
+            (false, true, false) => Some(MomentAction::Regenerate),
+            (false, false, true) => Some(MomentAction::Strike),
+            _ => None,
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        let text_ok = self.text.as_deref().is_none_or(|t| !t.trim().is_empty());
+        let choices_ok = self.choices.as_ref().is_none_or(|choices| {
+            let mut ids = HashSet::new();
+            !choices.is_empty()
+                && choices.iter().all(|c| {
+                    !c.id.trim().is_empty() && !c.text.trim().is_empty() && ids.insert(&c.id)
+                })
+        });
+        text_ok && choices_ok
+    }
+}
+
+/// Generate the player's latest moment again, answering the same choice
+async fn regenerate_moment(
+    state: &AppState,
+    player: &Player,
+    original: &NarrativeMoment,
+    note: Option<&str>,
+) -> anyhow::Result<NarrativeMoment> {
+    let mut context = player.clone();
+    context.run.narrative_history.pop();
+    if let Some(note) = note {
+        context.run.pending_notes.push(note.to_string());
+    }
+    // Choices are recorded by id, so look up what the previous moment offered
+    let answered = context
+        .run
+        .current_loop
+        .choices_made
+        .last()
+        .and_then(|id| {
+            let previous = context.run.narrative_history.last()?;
+            previous.choices.iter().find(|c| &c.id == id).cloned()
+        });
+    let locale = original.translation.as_ref().map_or(Locale::En, |t| t.locale);
+
+    let mut moment = if context.abuse.is_ghosted() {
+        offline::moment(&context)
+    } else if let Some(choice) = answered {
+        state.llm.process_choice(&context, &choice, locale).await?
+    } else {
+        state.llm.generate_narrative(&context, None, locale).await?
+    };
+    // The original's accepted world updates are already in effect
+    moment.world_updates = original.world_updates.clone();
+    moment.transition(MomentState::Presented)?;
+    Ok(moment)
+}
+
+/// Fix a moment a player has already seen: replace its text or choices,
+/// generate it again, or strike it. The original is kept in the audit log
+/// and the player's event stream is told about the change.
+async fn admin_edit_moment(
+    State(state): State<AppState>,
+    Path((player_id, moment_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<MomentEditRequest>,
+) -> Result<Json<AuditEntry>, ApiError> {
+    let action = request.action().ok_or(StatusCode::BAD_REQUEST)?;
+    if !request.is_valid() {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+
+    let saved = fetch_player(&state, &player_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let player = state
+        .game
+        .write()
+        .await
+        .players
+        .entry(player_id)
+        .or_insert(saved)
+        .clone();
+    let history = &player.run.narrative_history;
+    let index = history
+        .iter()
+        .position(|m| m.id == moment_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let original = history[index].clone();
+    let latest = index + 1 == history.len();
+
+    let replacement = match action {
+        MomentAction::Edit => {
+            let mut moment = original.clone();
+            if let Some(text) = request.text {
+                moment.text = text;
+                moment.rewrite_by(Provenance::Admin);
+            }
+            if let Some(choices) = request.choices {
+                // Choices left as they were keep their writers
+                moment.choices = choices
+                    .into_iter()
+                    .map(|choice| {
+                        let kept = original
+                            .choices
+                            .iter()
+                            .any(|c| c.id == choice.id && c.text == choice.text);
+                        let provenance = if kept {
+                            original.provenance_of(&choice.id)
+                        } else {
+                            Some(Provenance::Admin)
+                        };
+                        Choice { provenance, ..choice }
+                    })
+                    .collect();
+            }
+            // The translation no longer matches
+            moment.translation = None;
+            moment
+        }
+        MomentAction::Strike if !latest => {
+            let mut moment = original.clone();
+            moment.text = theme::text(state.config.tenant.as_deref(), Flavor::Struck);
+            moment.rewrite_by(Provenance::Admin);
+            moment.translation = None;
+            moment
+        }
+        // A struck latest moment is replaced, so the player still has something to answer
+        MomentAction::Regenerate | MomentAction::Strike => {
+            if !latest || original.state != MomentState::Presented || player.is_locked() {
+                return Err(StatusCode::CONFLICT.into());
+            }
+            regenerate_moment(&state, &player, &original, request.note.as_deref())
+                .await
+                .map_err(|e| llm_failure(&state, e))?
+        }
+    };
+
+    let replacement_id = replacement.id;
+    let entry = AuditEntry {
+        at: chrono::Utc::now(),
+        player_id,
+        change: Change::Moment {
+            action,
+            original: Box::new(original),
+            replacement: Box::new(replacement.clone()),
+        },
+        reason: request.reason,
+    };
+    let mut game = state.game.write().await;
+    let p = game.player_mut(&player_id).map_err(game_error)?;
+    // The player may have moved on while the narrator was writing
+    let regenerated = replacement_id != moment_id;
+    let len = p.run.narrative_history.len();
+    let slot = p
+        .run
+        .narrative_history
+        .get_mut(index)
+        .filter(|m| m.id == moment_id)
+        .filter(|m| !regenerated || (index + 1 == len && m.state == MomentState::Presented))
+        .ok_or(StatusCode::CONFLICT)?;
+
+    audit::record(&entry).map_err(|e| {
+        tracing::error!("Failed to write audit log: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    *slot = replacement;
+    if let Err(e) = persistence::save_player(p) {
+        tracing::warn!("Failed to save edited moment for {}: {}", player_id, e);
+    }
+    drop(game);
+
+    tracing::info!("Admin {:?} of moment {} for {}", action, moment_id, player_id);
+    state.events.publish(GameEvent::MomentEdited {
+        player_id,
+        moment_id,
+        replacement_id,
+        action,
+    });
+    Ok(Json(entry))
+}
+
+const DEFAULT_AUDIT_PAGE: usize = 100;
+
+#[derive(Deserialize)]
+struct ModelSwitchRequest {
+    /// Model to narrate the run from now on; `null` for the configured one
+    model: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    /// Checkpoint after this many choices; the run's start by default
+    from_choice: Option<u64>,
+    /// The latest choice by default
+    to_choice: Option<u64>,
+}
+
+/// What changed in a player's active run between two of its choices, for
+/// support tickets
+async fn admin_player_diff(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<SaveDiff>, StatusCode> {
+    let player = fetch_player(&state, &player_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let run_id = player.run_id();
+    let log = consequences::choice_log(&run_id).map_err(|e| {
+        tracing::error!("Failed to read the choice log of run {}: {}", run_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let archives = persistence::load_archived_loops(&run_id).map_err(|e| {
+        tracing::error!("Failed to load archived loops of run {}: {}", run_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let to = query.to_choice.unwrap_or(log.len() as u64);
+    diff::between(&player, &log, &archives, query.from_choice.unwrap_or(0), to)
+        .map(Json)
+        .map_err(|e| {
+            tracing::debug!("Refused diff of player {}: {}", player_id, e);
+            StatusCode::BAD_REQUEST
+        })
+}
+
+/// Hand a player's run to another model mid-run. The new model is briefed on
+/// the run's tone before its first moment.
+async fn admin_switch_model(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Json(request): Json<ModelSwitchRequest>,
+) -> Result<Json<AuditEntry>, StatusCode> {
+    let saved = fetch_player(&state, &player_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut game = state.game.write().await;
+    let player = game.players.entry(player_id).or_insert(saved);
+    player.ensure_playable().map_err(game_error)?;
+
+    let entry = handoff::switch(
+        &state.config,
+        &state.events,
+        player,
+        request.model.as_deref(),
+        SwitchedBy::Admin,
+        request.reason,
+    )
+    .map_err(|e| {
+        tracing::error!("Failed to write audit log: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .ok_or(StatusCode::CONFLICT)?;
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save model switch for {}: {}", player_id, e);
+    }
+    Ok(Json(entry))
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    player_id: Option<Uuid>,
+    limit: Option<usize>,
+}
+
+/// Admin changes to moments, newest first
+async fn admin_audit(
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, StatusCode> {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_PAGE);
+    audit::entries(query.player_id, limit)
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("Failed to read audit log: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Lift ghost mode and clear a player's strikes
+async fn admin_unban(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+) -> Result<StatusCode, StatusCode> {
+    let mut game = state.game.write().await;
+    let mut saved;
+    let player = match game.get_player_mut(&player_id) {
+        Some(player) => player,
+        None => {
+            saved = persistence::load_player(&player_id)
+                .map_err(|e| {
+                    tracing::error!("Failed to load player {}: {}", player_id, e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?
+                .filter(|p| game.admits(p))
+                .ok_or(StatusCode::NOT_FOUND)?;
+            &mut saved
+        }
+    };
+    state.abuse.unban(player);
+    persistence::save_player(player).map_err(|e| {
+        tracing::error!("Failed to save unbanned player {}: {}", player_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct WarmupRequest {
+    count: usize,
+    #[serde(default)]
+    persona: Option<Persona>,
+    /// Language the opening moments are translated into
+    #[serde(default)]
+    locale: Locale,
+}
+
+#[derive(Serialize)]
+struct WarmupReport {
+    requested: usize,
+    ready: Vec<WarmStart>,
+    failed: usize,
+}
+
+/// Prepare one guest player with its opening moment, claimable by code
+async fn warm_start(
+    state: &AppState,
+    persona: Persona,
+    locale: Locale,
+) -> anyhow::Result<WarmStart> {
+    let mut player = Player::new();
+    player.tenant = state.config.tenant.clone();
+    player.run.persona = persona;
+    let mut moment = state.llm.generate_narrative(&player, None, locale).await?;
+    state.world.apply(&mut player, &mut moment);
+    let heard = player.run.pending_notes.len();
+    player.present_moment(&mut moment, heard)?;
+    player
+        .run
+        .graph
+        .record_moment(&moment, player.run.current_loop.number);
+    persistence::save_player(&player)?;
+
+    let player_id = player.id;
+    state.game.write().await.players.insert(player_id, player);
+    state.events.publish(GameEvent::PlayerCreated { player_id });
+    state.warm_pool.add(player_id, persona)
+}
+
+/// Pre-create guest players and generate their opening moments in parallel,
+/// so exhibition visitors start instantly with a claim code
+async fn admin_warmup(
+    State(state): State<AppState>,
+    Json(request): Json<WarmupRequest>,
+) -> Result<Json<WarmupReport>, StatusCode> {
+    if request.count == 0 || request.count > MAX_WARMUP {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let persona = request.persona.unwrap_or_default();
+    if !persona.is_unlocked(&[]) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    tracing::info!(
+        "Warming up {} players, {} at a time",
+        request.count,
+        state.config.warmup_concurrency
+    );
+    let results: Vec<anyhow::Result<WarmStart>> = futures::stream::iter(0..request.count)
+        .map(|_| warm_start(&state, persona, request.locale))
+        .buffer_unordered(state.config.warmup_concurrency)
+        .collect()
+        .await;
+
+    let mut report = WarmupReport {
+        requested: request.count,
+        ready: Vec::new(),
+        failed: 0,
+    };
+    for result in results {
+        match result {
+            Ok(start) => report.ready.push(start),
+            Err(e) => {
+                tracing::warn!("Warm-up player failed: {}", e);
+                state.warm_pool.record_failure();
+                report.failed += 1;
+            }
+        }
+    }
+    tracing::info!(
+        "Warm-up finished: {} ready, {} failed",
+        report.ready.len(),
+        report.failed
+    );
+    Ok(Json(report))
+}
+
+async fn admin_warmup_list(State(state): State<AppState>) -> Json<Vec<WarmStart>> {
+    Json(state.warm_pool.list())
+}
+
+#[derive(Serialize)]
+struct WaitingRoomView {
+    max_active_players: usize,
+    active_players: usize,
+    waiting: Vec<QueueEntry>,
+}
+
+async fn admin_waiting_room(State(state): State<AppState>) -> Json<WaitingRoomView> {
+    let active = active_players(&state, &*state.game.read().await);
+    Json(WaitingRoomView {
+        max_active_players: state.config.max_active_players,
+        active_players: active,
+        waiting: state.waiting.list(),
+    })
+}
+
+#[derive(Deserialize)]
+struct MaintenanceRequest {
+    read_only: bool,
+}
+
+/// Switch maintenance mode on or off until the next restart
+async fn admin_maintenance(
+    State(state): State<AppState>,
+    Json(request): Json<MaintenanceRequest>,
+) -> Json<ServerStatus> {
+    state.status.set_read_only(request.read_only);
+    tracing::info!("Maintenance read-only mode {}", if request.read_only { "on" } else { "off" });
+    Json(state.server_status())
+}
+
+async fn admin_flags(State(state): State<AppState>) -> Json<Vec<FlagView>> {
+    Json(state.llm.flags().report())
+}
+
+async fn admin_plugins() -> Json<PluginReport> {
+    Json(plugins::host().report())
+}
+
+/// Every ending named, spoilers and all
+async fn admin_endings(State(state): State<AppState>, headers: HeaderMap) -> Json<Gallery> {
+    let locale = Locale::from_headers(&headers);
+    Json(spoilers::gallery(
+        state.ending_stats.exact_summary(locale),
+        &Disclosure::Everything,
+        state.config.content_rating,
+        locale,
+    ))
+}
+
+#[derive(Deserialize)]
+struct FlagRequest {
+    enabled: Option<bool>,
+    percent: Option<u8>,
+}
+
+/// Override a feature flag at runtime; the override outlives restarts
+async fn admin_set_flag(
+    State(state): State<AppState>,
+    Path(feature): Path<Feature>,
+    Json(request): Json<FlagRequest>,
+) -> Result<Json<FlagView>, StatusCode> {
+    let flags = state.llm.flags();
+    let current = flags.rollout(feature);
+    let rollout = Rollout {
+        enabled: request.enabled.unwrap_or(current.enabled),
+        percent: request.percent.unwrap_or(current.percent),
+    };
+    if rollout.percent > 100 {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    flags.set(feature, Some(rollout)).map_err(|e| {
+        tracing::error!("Failed to save feature flags: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    tracing::info!("Feature {:?} set to {:?}", feature, rollout);
+    Ok(Json(flags.view(feature)))
+}
+
+/// Drop a runtime override, back to `FEATURE_FLAGS`
+async fn admin_clear_flag(
+    State(state): State<AppState>,
+    Path(feature): Path<Feature>,
+) -> Result<Json<FlagView>, StatusCode> {
+    state.llm.flags().set(feature, None).map_err(|e| {
+        tracing::error!("Failed to save feature flags: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(state.llm.flags().view(feature)))
+}
+
+/// Prometheus metrics: LLM usage and cost, sanitizer audit counts, janitor totals, abuse and game event counts
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut out = String::new();
+    state.llm.write_metrics(&mut out);
+    state.llm.usage().write_metrics(&mut out);
+    state.llm.repetition().write_metrics(&mut out);
+    state.sanitizer.write_metrics(&mut out);
+    state.janitor.write_metrics(&mut out);
+    state.world.write_metrics(&mut out);
+    state.abuse.write_metrics(&mut out);
+    state.warm_pool.write_metrics(&mut out);
+    state.waiting.write_metrics(&mut out);
+    state.generations.write_metrics(&mut out);
+    out.push_str("# HELP nihilism_coalesced_requests_total Retries answered by a generation already in flight\n");
+    out.push_str("# TYPE nihilism_coalesced_requests_total counter\n");
+    for (action, count) in [
+        ("narrative", state.narration.coalesced()),
+        ("reset", state.resets.coalesced()),
+    ] {
+        out.push_str(&format!(
+            "nihilism_coalesced_requests_total{{action=\"{}\"}} {}\n",
+            action, count
+        ));
+    }
+    out.push_str("# HELP nihilism_events_total Game events published since startup\n");
+    out.push_str("# TYPE nihilism_events_total counter\n");
+    for count in state.event_counters.snapshot() {
+        out.push_str(&format!(
+            "nihilism_events_total{{kind=\"{}\"}} {}\n",
+            count.kind, count.count
+        ));
+    }
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        out,
+    )
+}
+
+#[derive(Deserialize)]
+struct GameEventsQuery {
+    /// Follow each `moment_generated` event with the moment's beats
+    #[serde(default)]
+    reveal: bool,
+}
+
+/// Server-sent stream of one player's game events
+async fn game_events(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Query(query): Query<GameEventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    if state.game.read().await.get_player(&player_id).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut receiver = state.events.subscribe();
+    let mut acks = state.reveal_acks.subscribe(player_id);
+    let ack_timeout = std::time::Duration::from_secs(state.config.reveal_ack_timeout_secs);
+    let stream = async_stream::stream! {
+        loop {
+            match receiver.recv().await {
+                Ok(envelope) if envelope.event.player_id() == player_id => {
+                    match Event::default()
+                        .event(envelope.event.kind())
+                        .id(envelope.seq.to_string())
+                        .json_data(envelope.as_ref())
+                    {
+                        Ok(event) => yield Ok(event),
+                        Err(e) => tracing::warn!("Failed to encode event: {}", e),
+                    }
+                    let GameEvent::MomentGenerated { moment_id, .. } = envelope.event else {
+                        continue;
+                    };
+                    if !query.reveal {
+                        continue;
+                    }
+                    let Some(mut reveal) = revealed_moment(&state, player_id, moment_id).await else {
+                        continue;
+                    };
+                    while let Some(beat) = reveal.next_beat() {
+                        match Event::default().event("beat").json_data(&beat) {
+                            Ok(event) => yield Ok(event),
+                            Err(e) => tracing::warn!("Failed to encode beat: {}", e),
+                        }
+                        if beat.index + 1 < beat.count {
+                            reveal::wait_for_ack(&mut acks, &reveal, ack_timeout).await;
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::debug!("Event stream for {} skipped {} events", player_id, skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// A moment of the player's history, ready to be revealed beat by beat
+async fn revealed_moment(state: &AppState, player_id: Uuid, moment_id: Uuid) -> Option<Reveal> {
+    let game = state.game.read().await;
+    let moment = game
+        .get_player(&player_id)?
+        .run
+        .narrative_history
+        .iter()
+        .rev()
+        .find(|m| m.id == moment_id)?;
+    Some(Reveal::new(moment, state.config.reveal_beat_chars))
+}
+
+/// A moment of the player's history as a PNG share card
+async fn moment_card(
+    State(state): State<AppState>,
+    Path((player_id, moment_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, ApiError> {
+    require_feature(&state, Feature::ImageGeneration, Some(player_id))?;
+    let (text, speaker, mood, loop_number) = {
+        let game = state.game.read().await;
+        let player = game.get_player(&player_id).ok_or(StatusCode::NOT_FOUND)?;
+        let moment = player
+            .run
+            .narrative_history
+            .iter()
+            .rev()
+            .find(|m| m.id == moment_id)
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let text = moment.translation.as_ref().map_or(&moment.text, |t| &t.text);
+        (
+            state.sanitizer.scrub("card", text),
+            state.sanitizer.scrub_opt("card", &moment.speaker),
+            moment.mood.clone(),
+            player.run.current_loop.number,
+        )
+    };
+
+    let content = CardContent {
+        moment_id,
+        text: &text,
+        speaker: speaker.as_deref(),
+        mood: &mood,
+        loop_number,
+    };
+    let png = match state.cards.cached(&content) {
+        Some(png) => png,
+        None => {
+            if let Err(wait) = state.cards.try_acquire(player_id) {
+                return Err(throttle(&state, player_id, Throttled::new(Reason::RateLimit, wait)));
+            }
+            let cards = state.cards.clone();
+            tokio::task::spawn_blocking(move || {
+                cards.render(&CardContent {
+                    moment_id,
+                    text: &text,
+                    speaker: speaker.as_deref(),
+                    mood: &mood,
+                    loop_number,
+                })
+            })
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map_err(|e| {
+                tracing::error!("Failed to render card of moment {}: {}", moment_id, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+        }
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        png.as_ref().clone(),
+    )
+        .into_response())
+}
+
+/// The scenario's asset manifest, for clients to preload the whole pack
+async fn scenario_manifest(State(state): State<AppState>) -> Result<Json<Manifest>, StatusCode> {
+    let tenant = state.config.tenant.as_deref();
+    let bundle = anchors::bundle(tenant).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(Manifest::new(bundle, tenant)))
+}
+
+/// One of the scenario's assets, revalidated by its hash
+async fn scenario_asset(
+    State(state): State<AppState>,
+    Path((scenario, path)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let asset = anchors::bundle(state.config.tenant.as_deref())
+        .filter(|bundle| bundle.scenario == scenario)
+        .and_then(|bundle| bundle.by_path(&path))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let etag = format!("\"{}\"", asset.sha256);
+    let cached = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag));
+    let headers = [
+        (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
+        (header::ETAG, etag),
+    ];
+    if cached {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+    Ok((
+        headers,
+        [(header::CONTENT_TYPE, asset.content_type)],
+        asset.bytes.clone(),
+    )
+        .into_response())
+}
+
+/// Release the next beat of a moment revealed over the event stream
+async fn ack_beat(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Json(ack): Json<BeatAck>,
+) -> StatusCode {
+    if state.game.read().await.get_player(&player_id).is_none() {
+        return StatusCode::NOT_FOUND;
+    }
+    state.reveal_acks.ack(player_id, ack);
+    StatusCode::NO_CONTENT
+}
+
+#[derive(Serialize)]
+struct ChallengeResponse {
+    date: chrono::NaiveDate,
+    modifier: &'static str,
+    name: &'static str,
+    description: &'static str,
+    leaderboard: Vec<LeaderboardEntry>,
+}
+
+fn challenge_response(
+    state: &AppState,
+    challenge: Challenge,
+) -> Result<Json<ChallengeResponse>, StatusCode> {
+    let mut leaderboard = challenge::load_leaderboard(state.config.tenant.as_deref(), challenge.date)
+        .map_err(|e| {
+            tracing::error!("Failed to load leaderboard: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    for entry in &mut leaderboard {
+        entry.name = state.sanitizer.scrub_opt("leaderboard", &entry.name);
+    }
+    Ok(Json(ChallengeResponse {
+        date: challenge.date,
+        modifier: challenge.modifier.id,
+        name: challenge.modifier.name,
+        description: challenge.modifier.prompt,
+        leaderboard,
+    }))
+}
+
+async fn challenge_today(
+    State(state): State<AppState>,
+) -> Result<Json<ChallengeResponse>, StatusCode> {
+    require_feature(&state, Feature::ChallengeMode, None)?;
+    challenge_response(&state, Challenge::today())
+}
+
+async fn challenge_leaderboard(
+    State(state): State<AppState>,
+    Path(date): Path<chrono::NaiveDate>,
+) -> Result<Json<ChallengeResponse>, StatusCode> {
+    require_feature(&state, Feature::ChallengeMode, None)?;
+    challenge_response(&state, Challenge::for_date(date))
+}
+
+#[derive(Deserialize, Default)]
+struct JoinChallengeRequest {
+    player_id: Option<Uuid>,
+    persona: Option<Persona>,
+}
+
+async fn join_challenge(
+    State(state): State<AppState>,
+    request: Option<Json<JoinChallengeRequest>>,
+) -> Result<Json<NewGameResponse>, StatusCode> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    require_feature(&state, Feature::ChallengeMode, request.player_id)?;
+    let challenge = Challenge::today();
+
+    let mut game = state.game.write().await;
+
+    // The challenge run borrows the owner's name and unlocked personas
+    let (name, endings_reached, consent) = match request.player_id {
+        Some(owner_id) => {
+            let owner = game.get_player(&owner_id).ok_or(StatusCode::NOT_FOUND)?;
+            (owner.name.clone(), owner.endings_reached(), owner.consent)
+        }
+        None => (None, Vec::new(), None),
+    };
+
+    let persona = request.persona.unwrap_or_default();
+    if !persona.is_unlocked(&endings_reached) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let mut player = game.create_player(persona);
+    player.name = name;
+    player.consent = consent;
+    player.run.challenge = Some(ChallengeRun::new(&challenge, request.player_id));
+    game.players.insert(player.id, player.clone());
+    state.events.publish(GameEvent::PlayerCreated {
+        player_id: player.id,
+    });
+
+    if let Err(e) = persistence::save_player(&player) {
+        tracing::warn!("Failed to auto-save challenge run: {}", e);
+    }
+
+    Ok(Json(NewGameResponse {
+        player: player.summary(),
+        message: theme::text(state.config.tenant.as_deref(), Flavor::Challenge).replace("{modifier}", challenge.modifier.name),
+    }))
+}
+
+#[derive(Deserialize, Default)]
+struct CreateRaceRequest {
+    countdown_secs: Option<i64>,
+    /// Modifier id; drawn from the race's seed when absent
+    scenario: Option<String>,
+}
+
+async fn create_race(
+    State(state): State<AppState>,
+    request: Option<Json<CreateRaceRequest>>,
+) -> Result<(StatusCode, Json<RaceView>), StatusCode> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let countdown = request.countdown_secs.unwrap_or(DEFAULT_COUNTDOWN_SECS);
+    if !(MIN_COUNTDOWN_SECS..=MAX_COUNTDOWN_SECS).contains(&countdown) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let race = state
+        .races
+        .create(countdown, request.scenario.as_deref())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    Ok((StatusCode::CREATED, Json(race)))
+}
+
+async fn get_race(
+    State(state): State<AppState>,
+    Path(race_id): Path<Uuid>,
+) -> Result<Json<RaceView>, StatusCode> {
+    state.races.view(&race_id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Deserialize, Default)]
+struct JoinRaceRequest {
+    name: Option<String>,
+    persona: Option<Persona>,
+}
+
+#[derive(Serialize)]
+struct JoinRaceResponse {
+    player: PlayerSummary,
+    lane: usize,
+    message: String,
+}
+
+/// Start a fresh run in a race. Only the base persona is unlocked for a new
+/// player, so every racer starts even.
+async fn join_race(
+    State(state): State<AppState>,
+    Path(race_id): Path<Uuid>,
+    request: Option<Json<JoinRaceRequest>>,
+) -> Result<Json<JoinRaceResponse>, StatusCode> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let persona = request.persona.unwrap_or_default();
+    if !persona.is_unlocked(&[]) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let name = state
+        .sanitizer
+        .scrub_opt("race", &request.name)
+        .filter(|n| !n.trim().is_empty());
+
+    let mut game = state.game.write().await;
+    let mut player = game.create_player(persona);
+    let (lane, race) = state
+        .races
+        .join(race_id, player.id, name.clone())
+        .map_err(|e| match e {
+            RaceError::NotFound => StatusCode::NOT_FOUND,
+            RaceError::Started | RaceError::Full => StatusCode::CONFLICT,
+        })?;
+    let scenario = challenge::modifier(&race.modifier).map_or("", |m| m.name);
+    player.name = name;
+    player.run.race = Some(race);
+    game.players.insert(player.id, player.clone());
+    state.events.publish(GameEvent::PlayerCreated {
+        player_id: player.id,
+    });
+
+    if let Err(e) = persistence::save_player(&player) {
+        tracing::warn!("Failed to auto-save race run: {}", e);
+    }
+
+    Ok(Json(JoinRaceResponse {
+        player: player.summary(),
+        lane,
+        message: theme::text(state.config.tenant.as_deref(), Flavor::Race).replace("{modifier}", scenario),
+    }))
+}
+
+/// Stream a race's public view as it changes, ending once someone reaches an
+/// ending. Carries no narrative text.
+async fn race_events(
+    State(state): State<AppState>,
+    Path(race_id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    if state.races.view(&race_id).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut changes = state.races.changes();
+    let stream = async_stream::stream! {
+        let mut last = None;
+        while let Some(race) = state.races.view(&race_id) {
+            let finished = race.status == RaceStatus::Finished;
+            // Changes to other races wake the stream too; only send this one's
+            let json = serde_json::to_string(&race).unwrap_or_default();
+            if last.as_ref() != Some(&json) {
+                let kind = if finished { "finished" } else { "race" };
+                yield Ok(Event::default().event(kind).data(json.clone()));
+                last = Some(json);
+            }
+            if finished {
+                break;
+            }
+            // Wake at the start too, so the countdown turns into the race
+            let start = (race.starts_at - chrono::Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                changed = changes.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(start), if race.status == RaceStatus::Countdown => {}
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn account_error_status(error: AccountError) -> StatusCode {
+    match error {
+        AccountError::UsernameTaken => StatusCode::CONFLICT,
+        AccountError::InvalidCredentials | AccountError::InvalidLink => StatusCode::UNAUTHORIZED,
+        AccountError::Invalid(_) => StatusCode::BAD_REQUEST,
+        AccountError::TooManyAttempts(_) => StatusCode::TOO_MANY_REQUESTS,
+        AccountError::Storage(e) => {
+            tracing::error!("Account storage error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Like `account_error_status`, with a `Retry-After` for too many attempts
+fn account_error(error: AccountError) -> ApiError {
+    match error {
+        AccountError::TooManyAttempts(wait) => {
+            ApiError::Throttled(Throttled::new(Reason::RateLimit, wait))
+        }
+        error => account_error_status(error).into(),
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// The account behind the request's session token
+fn require_account(state: &AppState, headers: &HeaderMap) -> Result<Account, StatusCode> {
+    bearer_token(headers)
+        .and_then(|token| state.accounts.authenticate(token))
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+/// Find a player in memory, loading it from disk if needed
+async fn fetch_player(state: &AppState, player_id: &Uuid) -> Result<Option<Player>, StatusCode> {
+    if let Some(player) = state.game.read().await.get_player(player_id) {
+        return Ok(Some(player.clone()));
+    }
+    let player = persistence::load_player(player_id).map_err(|e| {
+        tracing::error!("Failed to load player {}: {}", player_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let game = state.game.read().await;
+    Ok(player.filter(|p| game.admits(p)))
+}
+
+/// Upgrade a guest player into an account
+async fn bind_player(
+    state: &AppState,
+    account: &Account,
+    player_id: Uuid,
+) -> Result<AccountView, StatusCode> {
+    let mut player = fetch_player(state, &player_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    match player.account_id {
+        Some(owner) if owner != account.id => return Err(StatusCode::CONFLICT),
+        Some(_) => {}
+        None => {
+            player.account_id = Some(account.id);
+            let mut game = state.game.write().await;
+            if let Some(p) = game.get_player_mut(&player_id) {
+                p.account_id = Some(account.id);
+            }
+            if let Err(e) = persistence::save_player(&player) {
+                tracing::warn!("Failed to save upgraded player {}: {}", player_id, e);
+            }
+        }
+    }
+    state
+        .accounts
+        .bind_player(account.id, player_id)
+        .map(|a| a.view())
+        .map_err(account_error_status)
+}
+
+#[derive(Serialize)]
+struct SessionResponse {
+    token: String,
+    account: AccountView,
+}
+
+/// Open a session, optionally upgrading the guest player the client was using
+async fn session_response(
+    state: &AppState,
+    account: Account,
+    token: String,
+    player_id: Option<Uuid>,
+) -> Result<Json<SessionResponse>, StatusCode> {
+    let account = match player_id {
+        Some(player_id) => bind_player(state, &account, player_id).await?,
+        None => account.view(),
+    };
+    Ok(Json(SessionResponse { token, account }))
+}
+
+#[derive(Deserialize)]
+struct RegisterRequest {
+    username: String,
+    password: String,
+    player_id: Option<Uuid>,
+}
+
+async fn register_account(
+    State(state): State<AppState>,
+    Json(request): Json<RegisterRequest>,
+) -> Result<Json<SessionResponse>, StatusCode> {
+    let (account, token) = state
+        .accounts
+        .register(&request.username, &request.password)
+        .map_err(account_error_status)?;
+    session_response(&state, account, token, request.player_id).await
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+    player_id: Option<Uuid>,
+}
+
+async fn login_account(
+    State(state): State<AppState>,
+    Json(request): Json<LoginRequest>,
+) -> Result<Json<SessionResponse>, ApiError> {
+    let (account, token) = state
+        .accounts
+        .login(&request.username, &request.password)
+        .await
+        .map_err(account_error)?;
+    Ok(session_response(&state, account, token, request.player_id).await?)
+}
+
+async fn logout_account(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let token = bearer_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    state.accounts.logout(token).map_err(|e| {
+        tracing::error!("Failed to log out: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct MagicLinkRequest {
+    email: String,
+}
+
+/// Mail a one-time sign-in link; without a mail relay there is no way to
+/// deliver it, so `501`
+async fn request_magic_link(
+    State(state): State<AppState>,
+    Json(request): Json<MagicLinkRequest>,
+) -> Result<StatusCode, ApiError> {
+    let smtp = state.config.smtp.as_ref().ok_or(StatusCode::NOT_IMPLEMENTED)?;
+    let token = state
+        .accounts
+        .create_magic_link(&request.email)
+        .map_err(account_error)?;
+    // Links only work in the tenant whose accounts issued them
+    let base = match &state.config.tenant {
+        Some(id) => format!("{}/t/{}", state.config.public_url, id),
+        None => state.config.public_url.clone(),
+    };
+    let body = format!(
+        "Sign in to Nihilism within 15 minutes with this link:\n\n{}/?magic_link={}\n\n\
+         If you didn't ask for it, ignore this mail.\n",
+        base, token
+    );
+    let to = [request.email.trim().to_string()];
+    digest::mail(smtp, &to, "Your Nihilism sign-in link", body)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to mail a sign-in link: {}", e);
+            StatusCode::BAD_GATEWAY
+        })?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Deserialize)]
+struct MagicLinkVerifyRequest {
+    token: String,
+    player_id: Option<Uuid>,
+}
+
+async fn verify_magic_link(
+    State(state): State<AppState>,
+    Json(request): Json<MagicLinkVerifyRequest>,
+) -> Result<Json<SessionResponse>, StatusCode> {
+    let (account, token) = state
+        .accounts
+        .redeem_magic_link(&request.token)
+        .map_err(account_error_status)?;
+    session_response(&state, account, token, request.player_id).await
+}
+
+/// Totals across every run bound to an account
+#[derive(Serialize, Default)]
+struct AccountStats {
+    runs: usize,
+    completed_runs: usize,
+    total_loops: u64,
+    total_choices: u64,
+    dark_choices: u64,
+    light_choices: u64,
+    endings_reached: Vec<EndingType>,
+}
+
+#[derive(Serialize)]
+struct AccountResponse {
+    account: AccountView,
+    stats: AccountStats,
+}
+
+async fn account_runs(state: &AppState, account: &Account) -> Result<Vec<Player>, StatusCode> {
+    let mut players = Vec::new();
+    for player_id in &account.player_ids {
+        if let Some(player) = fetch_player(state, player_id).await? {
+            players.push(player);
+        }
+    }
+    Ok(players)
+}
+
+async fn get_account(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<AccountResponse>, StatusCode> {
+    let account = require_account(&state, &headers)?;
+    let mut stats = AccountStats::default();
+    for player in account_runs(&state, &account).await? {
+        for run in player.all_runs() {
+            stats.runs += 1;
+            stats.completed_runs += run.finale.is_some() as usize;
+            stats.total_loops += run.memory.total_loops;
+            stats.total_choices += run.memory.total_choices;
+            stats.dark_choices += run.memory.dark_choices;
+            stats.light_choices += run.memory.light_choices;
+        }
+        for ending in player.endings_reached() {
+            if !stats.endings_reached.contains(&ending) {
+                stats.endings_reached.push(ending);
+            }
+        }
+    }
+    Ok(Json(AccountResponse {
+        account: account.view(),
+        stats,
+    }))
+}
+
+/// Every run bound to the account, for resuming on another device
+async fn account_players(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<PlayerSummary>>, StatusCode> {
+    let account = require_account(&state, &headers)?;
+    let runs = account_runs(&state, &account).await?;
+    Ok(Json(runs.iter().map(Player::summary).collect()))
+}
+
+#[derive(Deserialize)]
+struct BindPlayerRequest {
+    player_id: Uuid,
+}
+
+async fn bind_account_player(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<BindPlayerRequest>,
+) -> Result<Json<AccountView>, StatusCode> {
+    let account = require_account(&state, &headers)?;
+    bind_player(&state, &account, request.player_id).await.map(Json)
+}
+
+#[derive(Deserialize)]
+struct SuggestQuery {
+    #[serde(default)]
+    prefix: String,
+}
+
+#[derive(Serialize)]
+struct SuggestResponse {
+    prefix: String,
+    #[serde(flatten)]
+    suggestions: Suggestions,
+    cached: bool,
+}
+
+/// Completions for half-typed free-form input, cached per moment
+async fn suggest_actions(
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    Query(query): Query<SuggestQuery>,
+) -> Result<Json<SuggestResponse>, ApiError> {
+    require_feature(&state, Feature::FreeformInput, Some(player_id))?;
+    let player = {
+        let game = state.game.read().await;
+        game.get_player(&player_id)
+            .ok_or(StatusCode::NOT_FOUND)?
+            .clone()
+    };
+    player.ensure_playable().map_err(game_error)?;
+
+    let prefix: String = query.prefix.chars().take(200).collect();
+    let key = suggest::normalize_prefix(&prefix);
+    let moment_id = player.run.narrative_history.last().map_or(Uuid::nil(), |m| m.id);
+    if let Some(suggestions) = state.suggestions.get(player_id, moment_id, &key) {
+        return Ok(Json(SuggestResponse {
+            prefix,
+            suggestions,
+            cached: true,
+        }));
+    }
+    if let Err(wait) = state.suggestions.try_acquire(player_id) {
+        strike_player(&state, player_id, StrikeKind::RateLimit, None).await;
+        return Err(throttle(&state, player_id, Throttled::new(Reason::RateLimit, wait)));
+    }
+    screen_input(&state, player_id, &prefix).await?;
+
+    let llm_suggestions = if state.config.suggest_use_llm && !player.abuse.is_ghosted() {
+        state
+            .llm
+            .generate_suggestions(&player, &prefix)
+            .await
+            .map_err(|e| tracing::debug!("Suggestion generation failed, using local model: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    let suggestions = match llm_suggestions {
+        Some(suggestions) => Suggestions {
+            suggestions,
+            source: SuggestionSource::Llm,
+        },
+        None => {
+            let past = consequences::past_choice_texts(&player.run_id()).unwrap_or_else(|e| {
+                tracing::warn!("Failed to read choice log of {}: {}", player_id, e);
+                Vec::new()
+            });
+            Suggestions {
+                suggestions: suggest::local_suggestions(&player, past, &prefix),
+                source: SuggestionSource::Local,
+            }
+        }
+    };
+
+    state
+        .suggestions
+        .insert(player_id, moment_id, key, suggestions.clone());
+    Ok(Json(SuggestResponse {
+        prefix,
+        suggestions,
+        cached: false,
+    }))
+}
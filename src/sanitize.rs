@@ -0,0 +1,285 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::config::SanitizeLevel;
+use crate::game::NarrativeMoment;
+use crate::graph::ChoiceGraph;
+
+/// Profanity stems masked at any strictness, also inside longer words ("fucking")
+const PROFANE_STEMS: &[&str] = &["fuck", "shit", "cunt", "motherfuck", "nigger", "faggot"];
+
+/// Whole words masked at any strictness
+const PROFANE_WORDS: &[&str] = &[
+    "asshole", "assholes", "bitch", "bitches", "bastard", "bastards", "dick", "dicks", "cock",
+    "cocks", "whore", "whores", "slut", "sluts", "twat", "wanker", "retard", "retarded",
+];
+
+/// Milder words masked only at `strict`
+const MILD_WORDS: &[&str] = &[
+    "ass", "arse", "crap", "damn", "damned", "hell", "piss", "pissed", "bloody", "bollocks",
+    "bugger", "prick",
+];
+
+const EMAIL_PLACEHOLDER: &str = "(email removed)";
+const PHONE_PLACEHOLDER: &str = "(phone removed)";
+const LINK_PLACEHOLDER: &str = "(link removed)";
+
+/// What was scrubbed from text bound for one shared surface
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SurfaceCounts {
+    /// Texts passed through the sanitizer
+    pub checked: u64,
+    /// Texts that were changed
+    pub scrubbed: u64,
+    pub profanity: u64,
+    pub emails: u64,
+    pub phones: u64,
+    pub links: u64,
+}
+
+#[derive(Serialize)]
+pub struct SurfaceReport {
+    pub surface: &'static str,
+    #[serde(flatten)]
+    pub counts: SurfaceCounts,
+}
+
+#[derive(Serialize)]
+pub struct SanitizeReport {
+    pub level: SanitizeLevel,
+    pub surfaces: Vec<SurfaceReport>,
+}
+
+/// Scrubs profanity and personal data from player text before it is shared.
+///
+/// Applied to everything other people can see: leaderboard names, presence
+/// blobs and exports. Private views of a player's own game are left untouched.
+pub struct Sanitizer {
+    level: SanitizeLevel,
+    counts: Mutex<HashMap<&'static str, SurfaceCounts>>,
+}
+
+impl Sanitizer {
+    pub fn new(level: SanitizeLevel) -> Self {
+        Self {
+            level,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Scrub one piece of text bound for `surface`
+    pub fn scrub(&self, surface: &'static str, text: &str) -> String {
+        let mut found = SurfaceCounts::default();
+        let scrubbed = match self.level {
+            SanitizeLevel::Off => text.to_string(),
+            level => {
+                let text = redact_phones(text, &mut found);
+                scrub_words(&text, level == SanitizeLevel::Strict, &mut found)
+            }
+        };
+
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let counts = counts.entry(surface).or_default();
+        counts.checked += 1;
+        if scrubbed != text {
+            counts.scrubbed += 1;
+        }
+        counts.profanity += found.profanity;
+        counts.emails += found.emails;
+        counts.phones += found.phones;
+        counts.links += found.links;
+        scrubbed
+    }
+
+    pub fn scrub_opt(&self, surface: &'static str, text: &Option<String>) -> Option<String> {
+        text.as_deref().map(|t| self.scrub(surface, t))
+    }
+
+    /// Scrub the text, speakers and choices of narrative moments in place
+    pub fn scrub_moments(&self, surface: &'static str, moments: &mut [NarrativeMoment]) {
+        for moment in moments {
+            moment.text = self.scrub(surface, &moment.text);
+            moment.speaker = self.scrub_opt(surface, &moment.speaker);
+            for choice in &mut moment.choices {
+                choice.text = self.scrub(surface, &choice.text);
+                choice.consequence_hint = self.scrub_opt(surface, &choice.consequence_hint);
+            }
+        }
+    }
+
+    /// Scrub node labels and choice texts of a branching map in place
+    pub fn scrub_graph(&self, surface: &'static str, graph: &mut ChoiceGraph) {
+        for node in graph.nodes.values_mut() {
+            node.label = self.scrub(surface, &node.label);
+        }
+        for edge in &mut graph.edges {
+            edge.choice_text = self.scrub(surface, &edge.choice_text);
+        }
+    }
+
+    /// Audit counters by surface, sorted by name
+    pub fn report(&self) -> SanitizeReport {
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let mut surfaces: Vec<SurfaceReport> = counts
+            .iter()
+            .map(|(surface, counts)| SurfaceReport {
+                surface,
+                counts: *counts,
+            })
+            .collect();
+        surfaces.sort_by(|a, b| a.surface.cmp(b.surface));
+        SanitizeReport {
+            level: self.level,
+            surfaces,
+        }
+    }
+
+    /// Append audit counters in Prometheus text format
+    pub fn write_metrics(&self, out: &mut String) {
+        out.push_str("# HELP nihilism_sanitized_total Items scrubbed from shared text\n");
+        out.push_str("# TYPE nihilism_sanitized_total counter\n");
+        for surface in self.report().surfaces {
+            let counts = surface.counts;
+            for (kind, count) in [
+                ("profanity", counts.profanity),
+                ("email", counts.emails),
+                ("phone", counts.phones),
+                ("link", counts.links),
+            ] {
+                out.push_str(&format!(
+                    "nihilism_sanitized_total{{surface=\"{}\",kind=\"{}\"}} {}\n",
+                    surface.surface, kind, count
+                ));
+            }
+        }
+    }
+}
+
+/// Replace runs of 7-15 digits (with common separators) by a placeholder.
+///
+/// ISO dates like 2026-10-16 are left alone.
+fn redact_phones(text: &str, found: &mut SurfaceCounts) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let starts_run = (chars[i].is_ascii_digit() || chars[i] == '+' || chars[i] == '(')
+            && !(i > 0 && chars[i - 1].is_alphanumeric());
+        if !starts_run {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+
+        let mut end = i;
+        while end < chars.len() && (chars[end].is_ascii_digit() || " -.()+".contains(chars[end])) {
+            end += 1;
+        }
+        // Give back trailing separators so "call 555 1234567." keeps its period
+        while end > i && !chars[end - 1].is_ascii_digit() {
+            end -= 1;
+        }
+        let run: String = chars[i..end].iter().collect();
+        let digits = run.chars().filter(|c| c.is_ascii_digit()).count();
+        let bounded = !chars.get(end).is_some_and(|c| c.is_alphanumeric());
+
+        if end > i && bounded && (7..=15).contains(&digits) && !is_iso_date(&run) {
+            out.push_str(PHONE_PLACEHOLDER);
+            found.phones += 1;
+            i = end;
+        } else {
+            out.push(chars[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+fn is_iso_date(run: &str) -> bool {
+    let groups: Vec<&str> = run.split('-').collect();
+    groups.len() == 3 && groups.iter().map(|g| g.len()).eq([4, 2, 2])
+}
+
+/// Scrub emails, links and profanity one whitespace-separated token at a time
+fn scrub_words(text: &str, strict: bool, found: &mut SurfaceCounts) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let split = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (token, tail) = rest.split_at(split);
+        out.push_str(&scrub_token(token, strict, found));
+        let spaces = tail.len() - tail.trim_start().len();
+        out.push_str(&tail[..spaces]);
+        rest = &tail[spaces..];
+    }
+    out
+}
+
+fn scrub_token(token: &str, strict: bool, found: &mut SurfaceCounts) -> String {
+    // Keep surrounding punctuation such as quotes and trailing commas
+    let core = token
+        .trim_start_matches(|c: char| !c.is_alphanumeric() && c != '@' && c != '+')
+        .trim_end_matches(|c: char| !c.is_alphanumeric());
+    if core.is_empty() {
+        return token.to_string();
+    }
+    let start = core.as_ptr() as usize - token.as_ptr() as usize;
+    let (before, after) = (&token[..start], &token[start + core.len()..]);
+
+    let replacement = if is_email(core) {
+        found.emails += 1;
+        EMAIL_PLACEHOLDER.to_string()
+    } else if strict && is_link(core) {
+        found.links += 1;
+        LINK_PLACEHOLDER.to_string()
+    } else {
+        let masked = mask_profanity(core, strict);
+        if masked == core {
+            return token.to_string();
+        }
+        found.profanity += 1;
+        masked
+    };
+    format!("{}{}{}", before, replacement, after)
+}
+
+fn is_email(token: &str) -> bool {
+    match token.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.split('.').count() >= 2
+                && domain.split('.').all(|part| !part.is_empty())
+        }
+        None => false,
+    }
+}
+
+/// URLs, bare domains with "www." and social handles like @name
+fn is_link(token: &str) -> bool {
+    let lower = token.to_lowercase();
+    lower.starts_with("http://")
+        || lower.starts_with("https://")
+        || lower.starts_with("www.")
+        || (lower.len() > 2
+            && lower.starts_with('@')
+            && lower[1..].chars().all(|c| c.is_alphanumeric() || c == '_'))
+}
+
+/// Mask a profane word, keeping its first letter: "shit" -> "s***"
+fn mask_profanity(word: &str, strict: bool) -> String {
+    let lower = word.to_lowercase();
+    let profane = PROFANE_STEMS.iter().any(|stem| lower.starts_with(stem))
+        || PROFANE_WORDS.contains(&lower.as_str())
+        || (strict && MILD_WORDS.contains(&lower.as_str()));
+    if !profane {
+        return word.to_string();
+    }
+    let mut chars = word.chars();
+    let first = chars.next().map(String::from).unwrap_or_default();
+    first + &"*".repeat(chars.count())
+}
+
+#[cfg(test)]
+mod tests;
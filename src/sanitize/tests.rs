@@ -0,0 +1,61 @@
+use super::*;
+
+fn scrub(level: SanitizeLevel, text: &str) -> String {
+    Sanitizer::new(level).scrub("test", text)
+}
+
+#[test]
+fn phone_numbers_go_but_dates_and_codes_stay() {
+    let standard = |text| scrub(SanitizeLevel::Standard, text);
+    assert_eq!(standard("call 555 1234567."), "call (phone removed).");
+    assert_eq!(
+        standard("Ring +1 (555) 123-4567, then wait"),
+        "Ring (phone removed), then wait"
+    );
+    // ISO dates, short numbers and digits inside words are not phone numbers
+    assert_eq!(standard("On 2026-10-16 at 19.30"), "On 2026-10-16 at 19.30");
+    assert_eq!(standard("loop 42 of 1000"), "loop 42 of 1000");
+    assert_eq!(standard("room B1234567"), "room B1234567");
+    assert_eq!(standard("id 1234567abc"), "id 1234567abc");
+    // More than 15 digits is no phone number either
+    assert_eq!(standard("1234567890123456"), "1234567890123456");
+}
+
+#[test]
+fn words_are_masked_by_level_keeping_punctuation() {
+    let text = "\"Shit,\" she said. Fucking hell, damn it: ada@example.com, www.example.com @loopkeeper";
+    assert_eq!(scrub(SanitizeLevel::Off, text), text);
+    assert_eq!(
+        scrub(SanitizeLevel::Standard, text),
+        "\"S***,\" she said. F****** hell, damn it: (email removed), www.example.com @loopkeeper"
+    );
+    assert_eq!(
+        scrub(SanitizeLevel::Strict, text),
+        "\"S***,\" she said. F****** h***, d*** it: (email removed), (link removed) (link removed)"
+    );
+
+    // Stems match the start of a word, whole words only themselves
+    for level in [SanitizeLevel::Standard, SanitizeLevel::Strict] {
+        assert_eq!(scrub(level, "Scunthorpe"), "Scunthorpe");
+        assert_eq!(scrub(level, "dickens passed"), "dickens passed");
+        assert_eq!(scrub(level, "motherfucker!"), "m***********!");
+    }
+    assert_eq!(scrub(SanitizeLevel::Strict, "classic assets"), "classic assets");
+    assert_eq!(scrub(SanitizeLevel::Standard, "me@localhost"), "me@localhost");
+}
+
+#[test]
+fn the_report_counts_what_each_surface_lost() {
+    let sanitizer = Sanitizer::new(SanitizeLevel::Standard);
+    sanitizer.scrub("presence", "shit, call 555 1234567");
+    sanitizer.scrub("presence", "a quiet loop");
+    sanitizer.scrub("export", "mail ada@example.com");
+
+    let report = sanitizer.report();
+    let surfaces: Vec<_> = report.surfaces.iter().map(|s| s.surface).collect();
+    assert_eq!(surfaces, ["export", "presence"]);
+    let presence = report.surfaces[1].counts;
+    assert_eq!((presence.checked, presence.scrubbed), (2, 1));
+    assert_eq!((presence.profanity, presence.phones, presence.emails), (1, 1, 0));
+    assert_eq!(report.surfaces[0].counts.emails, 1);
+}
@@ -0,0 +1,214 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+type JobFn = Arc<dyn Fn() -> JobFuture + Send + Sync>;
+
+/// When a job runs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schedule {
+    Every(Duration),
+    Disabled,
+}
+
+impl Schedule {
+    /// Parse a cron-like interval: `30s`, `5m`, `2h`, `1d`, `@hourly`, `@daily`, `@weekly` or `off`
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        let seconds = match value.as_str() {
+            "off" | "disabled" | "never" => return Some(Schedule::Disabled),
+            "@minutely" => 60,
+            "@hourly" => 3_600,
+            "@daily" | "@midnight" => 86_400,
+            "@weekly" => 604_800,
+            _ => {
+                let split = value.find(|c: char| !c.is_ascii_digit())?;
+                let (amount, unit) = value.split_at(split);
+                let amount: u64 = amount.parse().ok()?;
+                let multiplier = match unit {
+                    "s" => 1,
+                    "m" => 60,
+                    "h" => 3_600,
+                    "d" => 86_400,
+                    _ => return None,
+                };
+                amount * multiplier
+            }
+        };
+        if seconds == 0 {
+            return None;
+        }
+        Some(Schedule::Every(Duration::from_secs(seconds)))
+    }
+}
+
+/// Per-job run statistics
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct JobMetrics {
+    pub name: String,
+    pub interval_secs: Option<u64>,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+/// In-process scheduler for periodic maintenance jobs.
+///
+/// Jobs are registered by name with a default interval, which can be
+/// overridden with `SCHEDULE_<NAME>` (e.g. `SCHEDULE_AUTOSAVE_SWEEP=10m`).
+/// Each tick is delayed by a random jitter of up to `SCHEDULER_JITTER_PERCENT`
+/// of the interval so multiple instances don't run in lockstep.
+pub struct Scheduler {
+    jitter_percent: u32,
+    overrides: HashMap<String, String>,
+    metrics: Arc<RwLock<HashMap<String, JobMetrics>>>,
+    shutdown: watch::Sender<bool>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Scheduler {
+    pub fn new(config: &Config) -> Self {
+        let (shutdown, _) = watch::channel(false);
+        Self {
+            jitter_percent: config.scheduler_jitter_percent.min(100),
+            overrides: config.job_schedules.clone(),
+            metrics: Arc::new(RwLock::new(HashMap::new())),
+            shutdown,
+            handles: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Register and start a named job
+    pub async fn register<F, Fut>(&self, name: &str, default_schedule: &str, job: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let default = Schedule::parse(default_schedule).unwrap_or(Schedule::Disabled);
+        let schedule = match self.overrides.get(name) {
+            Some(value) => Schedule::parse(value).unwrap_or_else(|| {
+                tracing::warn!("Invalid schedule {:?} for job '{}', using default", value, name);
+                default
+            }),
+            None => default,
+        };
+
+        let interval = match schedule {
+            Schedule::Every(interval) => interval,
+            Schedule::Disabled => {
+                tracing::info!("Scheduled job '{}' is disabled", name);
+                self.metrics.write().await.insert(
+                    name.to_string(),
+                    JobMetrics {
+                        name: name.to_string(),
+                        ..Default::default()
+                    },
+                );
+                return;
+            }
+        };
+
+        self.metrics.write().await.insert(
+            name.to_string(),
+            JobMetrics {
+                name: name.to_string(),
+                interval_secs: Some(interval.as_secs()),
+                ..Default::default()
+            },
+        );
+
+        let job: JobFn = Arc::new(move || Box::pin(job()));
+        let name = name.to_string();
+        let metrics = self.metrics.clone();
+        let jitter_percent = self.jitter_percent;
+        let mut shutdown = self.shutdown.subscribe();
+
+        let handle = tokio::spawn(async move {
+            tracing::info!("Scheduled job '{}' every {:?}", name, interval);
+            loop {
+                let delay = with_jitter(interval, jitter_percent);
+                if let Some(metrics) = metrics.write().await.get_mut(&name) {
+                    metrics.next_run = chrono::Duration::from_std(delay)
+                        .ok()
+                        .map(|d| Utc::now() + d);
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown.changed() => break,
+                }
+
+                let started = Instant::now();
+                let result = job().await;
+                let elapsed = started.elapsed();
+
+                if let Some(metrics) = metrics.write().await.get_mut(&name) {
+                    metrics.runs += 1;
+                    metrics.last_run = Some(Utc::now());
+                    metrics.last_duration_ms = Some(elapsed.as_millis() as u64);
+                    match &result {
+                        Ok(()) => metrics.last_error = None,
+                        Err(e) => {
+                            metrics.failures += 1;
+                            metrics.last_error = Some(e.to_string());
+                        }
+                    }
+                }
+
+                match result {
+                    Ok(()) => tracing::debug!("Job '{}' finished in {:?}", name, elapsed),
+                    Err(e) => tracing::warn!("Job '{}' failed: {}", name, e),
+                }
+
+                if *shutdown.borrow() {
+                    break;
+                }
+            }
+            tracing::debug!("Scheduled job '{}' stopped", name);
+        });
+
+        self.handles.lock().await.push(handle);
+    }
+
+    /// Snapshot of all job metrics, sorted by name
+    pub async fn metrics(&self) -> Vec<JobMetrics> {
+        let mut metrics: Vec<JobMetrics> = self.metrics.read().await.values().cloned().collect();
+        metrics.sort_by(|a, b| a.name.cmp(&b.name));
+        metrics
+    }
+
+    /// Stop scheduling new runs and wait for in-flight runs to finish
+    pub async fn shutdown(&self) {
+        let _ = self.shutdown.send(true);
+        let handles: Vec<JoinHandle<()>> = self.handles.lock().await.drain(..).collect();
+        for handle in handles {
+            let _ = handle.await;
+        }
+        tracing::info!("Scheduler stopped");
+    }
+}
+
+fn with_jitter(interval: Duration, jitter_percent: u32) -> Duration {
+    if jitter_percent == 0 {
+        return interval;
+    }
+    let max_jitter_ms = interval.as_millis() as u64 * jitter_percent as u64 / 100;
+    if max_jitter_ms == 0 {
+        return interval;
+    }
+    interval + Duration::from_millis(rand::rng().random_range(0..=max_jitter_ms))
+}
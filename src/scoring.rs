@@ -0,0 +1,279 @@
+use anyhow::{Context, Result};
+use futures::future::join_all;
+use serde::Deserialize;
+use std::fs;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::config::{parse_scoring, Config, ScoringKind};
+use crate::consequences;
+use crate::game::{NarrativeMoment, Player};
+use crate::llm::LlmClient;
+use crate::plugins;
+
+pub type DarknessFuture<'a> = Pin<Box<dyn Future<Output = Result<Option<f64>>> + Send + 'a>>;
+
+/// A choice being scored, with everything a strategy may look at
+pub struct ScoredChoice<'a> {
+    pub id: &'a str,
+    /// Canonical English text of the choice
+    pub text: &'a str,
+    /// The moment the choice answers
+    pub moment: Option<&'a NarrativeMoment>,
+    pub player: &'a Player,
+}
+
+/// One way of measuring how nihilistic a choice is
+pub trait ScoringStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Darkness from -1.0 (entirely hopeful) to 1.0 (entirely nihilistic), or
+    /// `None` when the strategy has no opinion on this choice
+    fn darkness<'a>(&'a self, choice: &'a ScoredChoice<'a>) -> DarknessFuture<'a>;
+}
+
+/// Dark words in the choice id or text; everything else is hopeful
+pub struct KeywordStrategy;
+
+impl KeywordStrategy {
+    fn score(choice: &ScoredChoice<'_>) -> f64 {
+        let choice_lower = choice.text.to_lowercase();
+        let id_lower = choice.id.to_lowercase();
+
+        let is_dark = id_lower.contains("dark")
+            || id_lower.contains("hurt")
+            || id_lower.contains("ignore")
+            || id_lower.contains("nihil")
+            || id_lower.contains("cruel")
+            || id_lower.contains("abandon")
+            || choice_lower.contains("kill")
+            || choice_lower.contains("abandon")
+            || choice_lower.contains("nothing matters")
+            || choice_lower.contains("don't care")
+            || choice_lower.contains("meaningless")
+            || choice_lower.contains("leave them")
+            || choice_lower.contains("walk away");
+        if is_dark { 1.0 } else { -1.0 }
+    }
+}
+
+impl ScoringStrategy for KeywordStrategy {
+    fn name(&self) -> &'static str {
+        "keyword"
+    }
+
+    fn darkness<'a>(&'a self, choice: &'a ScoredChoice<'a>) -> DarknessFuture<'a> {
+        Box::pin(async move { Ok(Some(Self::score(choice))) })
+    }
+}
+
+/// The LLM rates the choice in the context of its scene
+pub struct LlmStrategy {
+    llm: Arc<LlmClient>,
+}
+
+impl ScoringStrategy for LlmStrategy {
+    fn name(&self) -> &'static str {
+        "llm"
+    }
+
+    fn darkness<'a>(&'a self, choice: &'a ScoredChoice<'a>) -> DarknessFuture<'a> {
+        Box::pin(async move {
+            // Ghosted players cost no LLM spend
+            if choice.player.abuse.is_ghosted() {
+                return Ok(None);
+            }
+            let scene = choice.moment.map(|m| m.text.as_str());
+            let darkness = self
+                .llm
+                .classify_choice(choice.player, scene, choice.text)
+                .await?;
+            Ok(Some(darkness))
+        })
+    }
+}
+
+/// How the same choice was judged earlier in the run, so a choice repeated
+/// across loops keeps its meaning
+pub struct StatsStrategy;
+
+impl ScoringStrategy for StatsStrategy {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    fn darkness<'a>(&'a self, choice: &'a ScoredChoice<'a>) -> DarknessFuture<'a> {
+        Box::pin(async move {
+            let (dark, total) = consequences::past_judgments(&choice.player.run_id(), choice.text)?;
+            if total == 0 {
+                return Ok(None);
+            }
+            Ok(Some((2.0 * dark as f64 - total as f64) / total as f64))
+        })
+    }
+}
+
+/// A scoring rule from a pack. Every matcher given must match.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PackRule {
+    #[serde(default)]
+    pub choice_id: Option<String>,
+    /// Case-insensitive text the choice must contain
+    #[serde(default)]
+    pub contains: Option<String>,
+    pub darkness: f64,
+}
+
+impl PackRule {
+    fn matches(&self, choice: &ScoredChoice<'_>) -> bool {
+        let id_ok = self
+            .choice_id
+            .as_deref()
+            .is_none_or(|id| id.eq_ignore_ascii_case(choice.id));
+        let text_ok = self
+            .contains
+            .as_deref()
+            .is_none_or(|text| choice.text.to_lowercase().contains(&text.to_lowercase()));
+        id_ok && text_ok
+    }
+}
+
+/// Scoring rules written alongside a scenario, loaded from `SCORING_PACK`
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScoringPack {
+    /// Strategies to use with this pack instead of `SCORING_STRATEGY`
+    #[serde(default)]
+    pub strategy: Option<String>,
+    pub rules: Vec<PackRule>,
+}
+
+impl ScoringPack {
+    pub fn load(path: &str) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read scoring pack {}", path))?;
+        let pack: ScoringPack = serde_json::from_str(&text)
+            .with_context(|| format!("invalid scoring pack {}", path))?;
+        for (i, rule) in pack.rules.iter().enumerate() {
+            if rule.choice_id.is_none() && rule.contains.is_none() {
+                anyhow::bail!("scoring pack rule {} matches nothing", i + 1);
+            }
+            if !(-1.0..=1.0).contains(&rule.darkness) {
+                anyhow::bail!("scoring pack rule {} has darkness outside -1..1", i + 1);
+            }
+        }
+        Ok(pack)
+    }
+}
+
+/// The average darkness of every pack rule the choice matches
+pub struct PackStrategy {
+    rules: Vec<PackRule>,
+}
+
+impl ScoringStrategy for PackStrategy {
+    fn name(&self) -> &'static str {
+        "pack"
+    }
+
+    fn darkness<'a>(&'a self, choice: &'a ScoredChoice<'a>) -> DarknessFuture<'a> {
+        Box::pin(async move {
+            let matched: Vec<f64> = self
+                .rules
+                .iter()
+                .filter(|rule| rule.matches(choice))
+                .map(|rule| rule.darkness)
+                .collect();
+            if matched.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(matched.iter().sum::<f64>() / matched.len() as f64))
+        })
+    }
+}
+
+/// Strategies combined by weighted average. Strategies without an opinion,
+/// or that fail, are left out of the average.
+pub struct Ensemble {
+    members: Vec<(Box<dyn ScoringStrategy>, f64)>,
+}
+
+impl Ensemble {
+    /// The deployment's strategies, from `SCORING_STRATEGY` or the scoring pack
+    pub fn from_config(config: &Config, llm: Arc<LlmClient>) -> Result<Self> {
+        let pack = config
+            .scoring_pack
+            .as_deref()
+            .map(ScoringPack::load)
+            .transpose()?;
+        // A pack may say how its own choices are best measured
+        let spec = pack
+            .as_ref()
+            .and_then(|p| p.strategy.as_deref())
+            .map(parse_scoring)
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| config.scoring_strategy.clone());
+
+        let mut members: Vec<(Box<dyn ScoringStrategy>, f64)> = Vec::new();
+        for (kind, weight) in spec {
+            let strategy: Box<dyn ScoringStrategy> = match kind {
+                ScoringKind::Keyword => Box::new(KeywordStrategy),
+                ScoringKind::Llm => Box::new(LlmStrategy { llm: llm.clone() }),
+                ScoringKind::Stats => Box::new(StatsStrategy),
+                ScoringKind::Pack => match &pack {
+                    Some(pack) => Box::new(PackStrategy {
+                        rules: pack.rules.clone(),
+                    }),
+                    None => anyhow::bail!("the pack scoring strategy needs SCORING_PACK"),
+                },
+            };
+            members.push((strategy, weight));
+        }
+        // Plugins allowed to judge choices join whatever the deployment uses
+        members.extend(plugins::host().scoring_strategies());
+        Ok(Self { members })
+    }
+
+    /// Strategy names and weights, e.g. `keyword:1,llm:2`
+    pub fn describe(&self) -> String {
+        self.members
+            .iter()
+            .map(|(strategy, weight)| format!("{}:{}", strategy.name(), weight))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Darkness of a choice, falling back to keywords when no strategy has
+    /// an opinion
+    pub async fn judge(&self, choice: &ScoredChoice<'_>) -> f64 {
+        match self.darkness(choice).await {
+            Ok(Some(darkness)) => darkness,
+            _ => KeywordStrategy::score(choice),
+        }
+    }
+}
+
+impl ScoringStrategy for Ensemble {
+    fn name(&self) -> &'static str {
+        "ensemble"
+    }
+
+    fn darkness<'a>(&'a self, choice: &'a ScoredChoice<'a>) -> DarknessFuture<'a> {
+        Box::pin(async move {
+            let results = join_all(self.members.iter().map(|(s, _)| s.darkness(choice))).await;
+            let mut total = 0.0;
+            let mut weights = 0.0;
+            for ((strategy, weight), result) in self.members.iter().zip(results) {
+                match result {
+                    Ok(Some(darkness)) => {
+                        total += darkness.clamp(-1.0, 1.0) * weight;
+                        weights += weight;
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Scoring strategy '{}' failed: {}", strategy.name(), e),
+                }
+            }
+            Ok((weights > 0.0).then(|| total / weights))
+        })
+    }
+}
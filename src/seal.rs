@@ -0,0 +1,213 @@
+//! Signed proof of a completed run, so an ending claimed on a leaderboard or
+//! in a social post can be checked against the server that issued it.
+//!
+//! A seal reads as `1.VoidEmbrace.26.91.88.0.1760000000.1760090000.<tag>`:
+//! version, ending, loops, choices, nihilism score, whether the finale was
+//! forced, start and completion as Unix seconds (`-` when the start is
+//! unknown), then a truncated HMAC-SHA256 of everything before it.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+use crate::backup;
+use crate::config::Config;
+use crate::endings::EndingType;
+use crate::game::Player;
+
+/// Generated on first use when `SEAL_SECRET` is not set
+const KEY_FILE: &str = "data/seal.key";
+const VERSION: &str = "1";
+/// Bytes of the HMAC kept in a seal
+const TAG_LEN: usize = 16;
+/// Bytes of the HMAC kept in a digest
+const DIGEST_LEN: usize = 5;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Why a seal was not accepted
+#[derive(Debug, thiserror::Error)]
+pub enum SealError {
+    #[error("not a run seal")]
+    Malformed,
+    #[error("seal version {0} is not supported")]
+    UnsupportedVersion(String),
+    #[error("seal was not issued by this server or has been altered")]
+    BadSignature,
+}
+
+/// What a seal vouches for
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SealClaims {
+    pub ending: EndingType,
+    /// True when the finale was forced by the loop limit rather than earned
+    pub forced: bool,
+    pub total_loops: u64,
+    pub total_choices: u64,
+    pub nihilism_score: i32,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: DateTime<Utc>,
+}
+
+impl SealClaims {
+    /// Claims for the player's active run, once it is complete
+    pub fn for_run(player: &Player) -> Option<Self> {
+        let finale = player.run.finale.as_ref()?;
+        let memory = &player.run.memory;
+        Some(Self {
+            ending: finale.ending.clone(),
+            forced: finale.forced,
+            total_loops: memory.total_loops,
+            total_choices: memory.total_choices,
+            nihilism_score: memory.nihilism_score,
+            started_at: player.run.started_at,
+            completed_at: finale.completed_at,
+        })
+    }
+
+    /// The signed part of a seal
+    fn message(&self) -> String {
+        format!(
+            "{}.{:?}.{}.{}.{}.{}.{}.{}",
+            VERSION,
+            self.ending,
+            self.total_loops,
+            self.total_choices,
+            self.nihilism_score,
+            u8::from(self.forced),
+            self.started_at
+                .map_or_else(|| "-".to_string(), |t| t.timestamp().to_string()),
+            self.completed_at.timestamp()
+        )
+    }
+
+    fn parse(message: &str) -> Result<Self, SealError> {
+        let fields: Vec<&str> = message.split('.').collect();
+        let [version, ending, loops, choices, score, forced, started, completed] = fields[..] else {
+            return Err(SealError::Malformed);
+        };
+        if version != VERSION {
+            return Err(SealError::UnsupportedVersion(version.to_string()));
+        }
+        let timestamp = |value: &str| {
+            value
+                .parse()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                .ok_or(SealError::Malformed)
+        };
+        Ok(Self {
+            ending: EndingType::all()
+                .into_iter()
+                .find(|e| format!("{:?}", e) == ending)
+                .ok_or(SealError::Malformed)?,
+            forced: match forced {
+                "0" => false,
+                "1" => true,
+                _ => return Err(SealError::Malformed),
+            },
+            total_loops: loops.parse().map_err(|_| SealError::Malformed)?,
+            total_choices: choices.parse().map_err(|_| SealError::Malformed)?,
+            nihilism_score: score.parse().map_err(|_| SealError::Malformed)?,
+            started_at: match started {
+                "-" => None,
+                value => Some(timestamp(value)?),
+            },
+            completed_at: timestamp(completed)?,
+        })
+    }
+}
+
+static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Load the signing key: `SEAL_SECRET`, or a key generated for this server.
+/// Must be called once at startup.
+pub fn init(config: &Config) -> Result<()> {
+    let key = match &config.seal_secret {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => backup::load_or_create_key(KEY_FILE)?,
+    };
+    if KEY.set(key).is_err() {
+        anyhow::bail!("seal key already initialized");
+    }
+    Ok(())
+}
+
+fn key() -> &'static [u8] {
+    KEY.get().expect("seal key not initialized")
+}
+
+/// Seal a completed run
+pub fn seal(claims: &SealClaims) -> String {
+    seal_with(key(), claims)
+}
+
+/// Check a seal was issued by this server and unaltered, and read its claims
+pub fn verify(seal: &str) -> Result<SealClaims, SealError> {
+    verify_with(key(), seal)
+}
+
+/// A short keyed digest of `text`, so an id derived from it can't be traced
+/// back without this server's key
+pub fn digest(text: &str) -> String {
+    digest_with(key(), text)
+}
+
+/// 64 keyed bits of `text`, for draws that can't be predicted without this
+/// server's key
+pub fn keyed_bits(text: &str) -> u64 {
+    keyed_bits_with(key(), text)
+}
+
+fn keyed_bits_with(key: &[u8], text: &str) -> u64 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(text.as_bytes());
+    let bytes = mac.finalize().into_bytes();
+    u64::from_be_bytes(bytes[..8].try_into().expect("HMAC-SHA256 is 32 bytes"))
+}
+
+fn digest_with(key: &[u8], text: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(text.as_bytes());
+    mac.finalize().into_bytes()[..DIGEST_LEN]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn seal_with(key: &[u8], claims: &SealClaims) -> String {
+    let message = claims.message();
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    let tag = mac.finalize().into_bytes();
+    let tag: String = tag[..TAG_LEN].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}.{}", message, tag)
+}
+
+fn verify_with(key: &[u8], seal: &str) -> Result<SealClaims, SealError> {
+    let (message, tag) = seal.trim().rsplit_once('.').ok_or(SealError::Malformed)?;
+    let claims = SealClaims::parse(message)?;
+    let tag = decode_hex(tag).filter(|t| t.len() == TAG_LEN).ok_or(SealError::Malformed)?;
+
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    mac.verify_truncated_left(&tag)
+        .map_err(|_| SealError::BadSignature)?;
+    Ok(claims)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
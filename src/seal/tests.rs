@@ -0,0 +1,44 @@
+use super::*;
+
+const KEY: &[u8] = b"test seal key";
+
+fn claims() -> SealClaims {
+    SealClaims {
+        ending: EndingType::TheMiddlePath,
+        forced: false,
+        total_loops: 26,
+        total_choices: 91,
+        nihilism_score: -4,
+        started_at: DateTime::from_timestamp(1_760_000_000, 0),
+        completed_at: DateTime::from_timestamp(1_760_090_000, 0).unwrap(),
+    }
+}
+
+#[test]
+fn seal_round_trips_its_claims() {
+    let seal = seal_with(KEY, &claims());
+
+    assert!(seal.starts_with("1.TheMiddlePath.26.91.-4.0.1760000000.1760090000."));
+    assert_eq!(verify_with(KEY, &seal).unwrap(), claims());
+
+    let unknown_start = SealClaims {
+        started_at: None,
+        ..claims()
+    };
+    let seal = seal_with(KEY, &unknown_start);
+    assert_eq!(verify_with(KEY, &seal).unwrap(), unknown_start);
+}
+
+#[test]
+fn altered_or_foreign_seals_are_rejected() {
+    let seal = seal_with(KEY, &claims());
+
+    let boasted = seal.replace("TheMiddlePath", "VoidEmbrace");
+    assert!(matches!(verify_with(KEY, &boasted), Err(SealError::BadSignature)));
+    assert!(matches!(verify_with(b"another server", &seal), Err(SealError::BadSignature)));
+    assert!(matches!(verify_with(KEY, "1.TheMiddlePath.26"), Err(SealError::Malformed)));
+    assert!(matches!(
+        verify_with(KEY, &seal.replacen('1', "2", 1)),
+        Err(SealError::UnsupportedVersion(_))
+    ));
+}
@@ -0,0 +1,139 @@
+//! Progressive disclosure of the endings, so a shared frontend can't spoil
+//! them for a new player.
+//!
+//! Player-facing ending lists name only the endings the viewer has unlocked,
+//! by reaching or refusing them in their run. Every other ending appears as
+//! an opaque code, stable on this server but keyed so it can't be matched to
+//! a name. `ENDING_SPOILERS` and the admin gallery name every ending.
+
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::config::{Config, ContentRating};
+use crate::endings::EndingType;
+use crate::game::Player;
+use crate::i18n::Locale;
+use crate::rarity::{EndingStat, StatsPrivacy};
+use crate::seal;
+
+/// Which endings a viewer may see named
+#[derive(Clone, Debug)]
+pub enum Disclosure {
+    Everything,
+    Unlocked(HashSet<EndingType>),
+}
+
+impl Disclosure {
+    /// What `viewer` may see, or a viewer with nothing unlocked
+    pub fn for_viewer(config: &Config, viewer: Option<&Player>) -> Self {
+        if config.ending_spoilers {
+            return Disclosure::Everything;
+        }
+        let memory = viewer.map(|p| &p.run.memory);
+        Disclosure::Unlocked(
+            memory
+                .into_iter()
+                .flat_map(|m| m.endings_reached.iter().chain(&m.endings_refused))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    pub fn reveals(&self, ending: &EndingType) -> bool {
+        match self {
+            Disclosure::Everything => true,
+            Disclosure::Unlocked(unlocked) => unlocked.contains(ending),
+        }
+    }
+
+    /// The ending's name if it is revealed, its code otherwise
+    pub fn label(&self, ending: &EndingType) -> String {
+        if self.reveals(ending) {
+            name(ending)
+        } else {
+            code(ending)
+        }
+    }
+}
+
+/// The name an ending serializes as
+fn name(ending: &EndingType) -> String {
+    match ending {
+        EndingType::Plugin(id) => id.clone(),
+        _ => format!("{:?}", ending),
+    }
+}
+
+/// Opaque stand-in for an ending's name
+pub fn code(ending: &EndingType) -> String {
+    format!("locked-{}", seal::digest(&name(ending)))
+}
+
+/// One ending in a gallery; locked endings keep only their code and rarity
+#[derive(Clone, Debug, Serialize)]
+pub struct GalleryEntry {
+    pub ending: String,
+    pub unlocked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub souls: Option<u64>,
+    pub percent: Option<f64>,
+    pub rare: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Gallery {
+    pub unlocked: usize,
+    pub total: usize,
+    pub endings: Vec<GalleryEntry>,
+    /// How the counts were protected; left out when they are exact
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<StatsPrivacy>,
+}
+
+/// Ending stats as `disclosure` lets a viewer see them, in the same order
+pub fn entries(
+    stats: Vec<EndingStat>,
+    disclosure: &Disclosure,
+    rating: ContentRating,
+    locale: Locale,
+) -> Vec<GalleryEntry> {
+    stats
+        .into_iter()
+        .map(|stat| {
+            let unlocked = disclosure.reveals(&stat.ending);
+            GalleryEntry {
+                ending: disclosure.label(&stat.ending),
+                unlocked,
+                title: unlocked.then_some(stat.title),
+                description: unlocked
+                    .then(|| stat.ending.get_description_for(rating, locale).to_string()),
+                souls: stat.souls,
+                percent: stat.percent,
+                rare: stat.rare,
+            }
+        })
+        .collect()
+}
+
+/// Every ending, unlocked ones first
+pub fn gallery(
+    stats: Vec<EndingStat>,
+    disclosure: &Disclosure,
+    rating: ContentRating,
+    locale: Locale,
+) -> Gallery {
+    let mut endings = entries(stats, disclosure, rating, locale);
+    endings.sort_by_key(|e| !e.unlocked);
+    Gallery {
+        unlocked: endings.iter().filter(|e| e.unlocked).count(),
+        total: endings.len(),
+        endings,
+        privacy: None,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,64 @@
+use super::*;
+
+fn disclosure(player: &Player) -> Disclosure {
+    let mut config = Config::for_tests("http://localhost:1/v1");
+    config.seal_secret = Some("spoilers".to_string());
+    // Tests share the process-wide key; whichever test sets it first wins
+    let _ = seal::init(&config);
+    Disclosure::for_viewer(&config, Some(player))
+}
+
+fn stat(ending: EndingType, souls: u64) -> EndingStat {
+    EndingStat {
+        title: ending.get_title(Locale::En).to_string(),
+        ending,
+        souls: Some(souls),
+        percent: Some(0.0),
+        rare: false,
+    }
+}
+
+#[test]
+fn locked_endings_show_only_a_stable_code() {
+    let mut player = Player::new();
+    player.run.memory.endings_refused = vec![EndingType::Acceptance];
+    let disclosure = disclosure(&player);
+
+    assert_eq!(disclosure.label(&EndingType::Acceptance), "Acceptance");
+    let code = disclosure.label(&EndingType::VoidEmbrace);
+    assert!(code.starts_with("locked-"));
+    assert!(!code.contains("Void"));
+    assert_eq!(code, disclosure.label(&EndingType::VoidEmbrace));
+    assert_ne!(code, disclosure.label(&EndingType::TheWatcher));
+}
+
+#[test]
+fn the_gallery_lists_unlocked_endings_first() {
+    let mut player = Player::new();
+    player.run.memory.endings_reached = vec![EndingType::TheWatcher];
+    let stats = vec![
+        stat(EndingType::VoidEmbrace, 3),
+        stat(EndingType::TheWatcher, 1),
+    ];
+
+    let shown = gallery(
+        stats.clone(),
+        &disclosure(&player),
+        ContentRating::Mature,
+        Locale::En,
+    );
+    assert_eq!((shown.unlocked, shown.total), (1, 2));
+    assert_eq!(shown.endings[0].ending, "TheWatcher");
+    assert!(shown.endings[0].description.is_some());
+    let locked = &shown.endings[1];
+    assert!(!locked.unlocked && locked.title.is_none() && locked.description.is_none());
+    assert_eq!(locked.souls, Some(3));
+
+    let everything = gallery(
+        stats,
+        &Disclosure::Everything,
+        ContentRating::Mature,
+        Locale::En,
+    );
+    assert_eq!(everything.unlocked, 2);
+}
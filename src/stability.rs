@@ -0,0 +1,118 @@
+use rand::seq::{IndexedRandom, SliceRandom};
+use rand::Rng;
+
+use crate::game::{Loop, NarrativeMoment, Player};
+
+/// Stability every loop starts with
+pub const MAX_STABILITY: u8 = 100;
+/// Lost to a paradox the narrator reports
+const PARADOX_COST: u8 = 15;
+/// Lost to a character coming back from the dead
+const RETURN_COST: u8 = 10;
+/// Lost per earlier death each time the same character dies again, capped
+const REPEAT_DEATH_COST: u8 = 5;
+const MAX_REPEAT_DEATH_COST: u8 = 15;
+/// Regained from a grounding moment the narrator reports
+const GROUNDING_GAIN: u8 = 10;
+/// Suffix of the id of a choice the glitching world repeats
+pub const ECHO_SUFFIX: &str = "_echo";
+
+/// How far the loop has come apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Stable,
+    /// Small glitches at the edges of the world
+    Unstable,
+    /// The world visibly breaks; choices shuffle and repeat
+    Fracturing,
+    /// The loop can't hold and resets
+    Collapsed,
+}
+
+impl Stage {
+    pub fn of(stability: u8) -> Self {
+        match stability {
+            0 => Stage::Collapsed,
+            1..=29 => Stage::Fracturing,
+            30..=59 => Stage::Unstable,
+            _ => Stage::Stable,
+        }
+    }
+
+    /// What the narrator is told about the state of the loop
+    fn prompt(&self) -> Option<&'static str> {
+        match self {
+            Stage::Stable | Stage::Collapsed => None,
+            Stage::Unstable => Some(
+                "The loop is unstable. Let small glitches slip into the world: a word repeated, \
+                 a detail that changes between sentences, a clock that shows the wrong hour.",
+            ),
+            Stage::Fracturing => Some(
+                "The loop is fracturing. The world visibly glitches: sentences stutter, \
+                 objects duplicate, people speak lines from earlier loops. Something is very wrong.",
+            ),
+        }
+    }
+}
+
+impl Loop {
+    pub fn stage(&self) -> Stage {
+        Stage::of(self.stability)
+    }
+
+    /// Lose stability to a paradox
+    pub fn destabilize(&mut self, amount: u8) {
+        self.stability = self.stability.saturating_sub(amount);
+    }
+
+    /// Regain stability from a grounding moment
+    pub fn ground(&mut self, amount: u8) {
+        self.stability = self.stability.saturating_add(amount).min(MAX_STABILITY);
+    }
+}
+
+pub fn paradox(current: &mut Loop) {
+    current.destabilize(PARADOX_COST);
+}
+
+pub fn grounding(current: &mut Loop) {
+    current.ground(GROUNDING_GAIN);
+}
+
+pub fn character_returned(current: &mut Loop) {
+    current.destabilize(RETURN_COST);
+}
+
+/// A character died again; `earlier` is how often they had died before
+pub fn repeat_death(current: &mut Loop, earlier: u64) {
+    let cost = (earlier.min(u8::MAX as u64) as u8).saturating_mul(REPEAT_DEATH_COST);
+    current.destabilize(cost.min(MAX_REPEAT_DEATH_COST));
+}
+
+/// Prompt section on the loop's stability, empty while it holds
+pub fn prompt(player: &Player) -> String {
+    let current = &player.run.current_loop;
+    match current.stage().prompt() {
+        Some(instruction) => format!(
+            "\nLOOP STABILITY: {}%\n{}\n",
+            current.stability, instruction
+        ),
+        None => String::new(),
+    }
+}
+
+/// Let a fracturing loop scramble the moment's choices and repeat one of them
+pub fn glitch_choices(moment: &mut NarrativeMoment, rng: &mut impl Rng) {
+    let Some(echoed) = moment.choices.choose(rng).cloned() else {
+        return;
+    };
+    let mut echo = echoed.clone();
+    echo.id = format!("{}{}", echoed.id, ECHO_SUFFIX);
+    if let Some(translation) = &mut moment.translation
+        && let Some(text) = translation.choices.get(&echoed.id).cloned()
+    {
+        translation.choices.insert(echo.id.clone(), text);
+    }
+    moment.choices.push(echo);
+    moment.choices.shuffle(rng);
+}
@@ -0,0 +1,136 @@
+//! What the server can't do right now, announced to clients so they can
+//! show an honest banner instead of passing off fallback content as normal.
+//!
+//! The status is assembled fresh for every `GET /api/status` and every
+//! narrative response from the LLM upstreams, the monthly budget, the
+//! waiting room, scripted fallbacks served lately and the maintenance switch.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::llm::LlmClient;
+use crate::waiting::WaitingRoom;
+
+/// How long after a scripted fallback was served it is still announced
+const FALLBACK_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Degradation {
+    /// Every LLM upstream is failing and sitting out its cooldown
+    LlmUnavailable,
+    /// The month's LLM budget is spent
+    BudgetExhausted,
+    /// Scripted moments stood in for generated ones lately
+    FallbackContent,
+    /// The server is at its active player limit and visitors are queued
+    QueueSaturated,
+    /// Maintenance: games can be read but not played or changed
+    ReadOnly,
+}
+
+impl Degradation {
+    fn message(self) -> &'static str {
+        match self {
+            Self::LlmUnavailable => "The narrator is unreachable; new moments will fail until it returns.",
+            Self::BudgetExhausted => "The narrator has run out of words for this month.",
+            Self::FallbackContent => "Some recent moments were scripted stand-ins for generated ones.",
+            Self::QueueSaturated => "The server is full; new visitors wait in line.",
+            Self::ReadOnly => "The server is in maintenance; games can be viewed but not played.",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Notice {
+    pub kind: Degradation,
+    pub message: &'static str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ServerStatus {
+    pub degraded: bool,
+    pub degradations: Vec<Notice>,
+}
+
+impl ServerStatus {
+    fn from_kinds(kinds: Vec<Degradation>) -> Self {
+        Self {
+            degraded: !kinds.is_empty(),
+            degradations: kinds
+                .into_iter()
+                .map(|kind| Notice {
+                    kind,
+                    message: kind.message(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Degradations the server tracks itself, on top of those read off the LLM
+/// client and the waiting room
+pub struct StatusBoard {
+    read_only: AtomicBool,
+    last_fallback: Mutex<Option<Instant>>,
+}
+
+impl StatusBoard {
+    pub fn new(read_only: bool) -> Self {
+        Self {
+            read_only: AtomicBool::new(read_only),
+            last_fallback: Mutex::new(None),
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Relaxed);
+    }
+
+    /// A scripted moment was served because generation failed
+    pub fn record_fallback(&self) {
+        *self.last_fallback.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    }
+
+    fn fallback_recent(&self, now: Instant) -> bool {
+        self.last_fallback
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some_and(|at| now.duration_since(at) < FALLBACK_WINDOW)
+    }
+
+    pub fn snapshot(&self, llm: &LlmClient, waiting: &WaitingRoom) -> ServerStatus {
+        let mut kinds = Vec::new();
+        if llm.unavailable() {
+            kinds.push(Degradation::LlmUnavailable);
+        }
+        if llm.usage().check_budget().is_err() {
+            kinds.push(Degradation::BudgetExhausted);
+        }
+        kinds.extend(self.own_degradations(Instant::now()));
+        if !waiting.is_empty() {
+            kinds.push(Degradation::QueueSaturated);
+        }
+        ServerStatus::from_kinds(kinds)
+    }
+
+    fn own_degradations(&self, now: Instant) -> Vec<Degradation> {
+        let mut kinds = Vec::new();
+        if self.fallback_recent(now) {
+            kinds.push(Degradation::FallbackContent);
+        }
+        if self.is_read_only() {
+            kinds.push(Degradation::ReadOnly);
+        }
+        kinds
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,33 @@
+use super::*;
+
+#[test]
+fn a_healthy_board_announces_nothing() {
+    let board = StatusBoard::new(false);
+    let status = ServerStatus::from_kinds(board.own_degradations(Instant::now()));
+    assert!(!status.degraded);
+    assert!(status.degradations.is_empty());
+}
+
+#[test]
+fn fallbacks_are_announced_for_a_while() {
+    let board = StatusBoard::new(false);
+    board.record_fallback();
+
+    let now = Instant::now();
+    assert_eq!(board.own_degradations(now), vec![Degradation::FallbackContent]);
+    assert!(board.own_degradations(now + FALLBACK_WINDOW).is_empty());
+}
+
+#[test]
+fn maintenance_can_be_switched_at_runtime() {
+    let board = StatusBoard::new(true);
+    let status = ServerStatus::from_kinds(board.own_degradations(Instant::now()));
+    assert!(status.degraded);
+    assert_eq!(
+        serde_json::to_value(&status.degradations[0]).unwrap()["kind"],
+        "read_only"
+    );
+
+    board.set_read_only(false);
+    assert!(board.own_degradations(Instant::now()).is_empty());
+}
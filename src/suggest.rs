@@ -0,0 +1,199 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::game::Player;
+
+/// Suggestions returned per request
+pub const SUGGESTION_COUNT: usize = 3;
+/// Longest completion the local model builds, in words
+const MAX_COMPLETION_WORDS: usize = 6;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Where a set of suggestions came from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionSource {
+    Llm,
+    Local,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Suggestions {
+    pub suggestions: Vec<String>,
+    pub source: SuggestionSource,
+}
+
+/// Per-player suggestions for the current moment, and their request budget
+struct PlayerSuggestions {
+    moment_id: Uuid,
+    by_prefix: HashMap<String, Suggestions>,
+    window_start: Instant,
+    requests: u32,
+}
+
+/// Caches suggestions per moment and rate-limits fresh generations per player
+pub struct SuggestionCache {
+    per_minute: u32,
+    players: Mutex<HashMap<Uuid, PlayerSuggestions>>,
+}
+
+pub fn normalize_prefix(prefix: &str) -> String {
+    prefix.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+impl SuggestionCache {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            players: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn players(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, PlayerSuggestions>> {
+        self.players.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Cached suggestions for this moment and prefix
+    pub fn get(&self, player_id: Uuid, moment_id: Uuid, prefix: &str) -> Option<Suggestions> {
+        self.players()
+            .get(&player_id)
+            .filter(|p| p.moment_id == moment_id)
+            .and_then(|p| p.by_prefix.get(prefix).cloned())
+    }
+
+    /// Count a fresh generation against the player's budget; over the limit,
+    /// the time until the budget refills
+    pub fn try_acquire(&self, player_id: Uuid) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let mut players = self.players();
+        let entry = players.entry(player_id).or_insert_with(|| PlayerSuggestions {
+            moment_id: Uuid::nil(),
+            by_prefix: HashMap::new(),
+            window_start: Instant::now(),
+            requests: 0,
+        });
+        if entry.window_start.elapsed() >= RATE_WINDOW {
+            entry.window_start = Instant::now();
+            entry.requests = 0;
+        }
+        if entry.requests >= self.per_minute {
+            return Err(RATE_WINDOW.saturating_sub(entry.window_start.elapsed()));
+        }
+        entry.requests += 1;
+        Ok(())
+    }
+
+    /// Remember suggestions, dropping those of earlier moments
+    pub fn insert(&self, player_id: Uuid, moment_id: Uuid, prefix: String, suggestions: Suggestions) {
+        let mut players = self.players();
+        let entry = players.entry(player_id).or_insert_with(|| PlayerSuggestions {
+            moment_id,
+            by_prefix: HashMap::new(),
+            window_start: Instant::now(),
+            requests: 0,
+        });
+        if entry.moment_id != moment_id {
+            entry.moment_id = moment_id;
+            entry.by_prefix.clear();
+        }
+        entry.by_prefix.insert(prefix, suggestions);
+    }
+
+    /// Forget players that are no longer held in memory
+    pub fn evict(&self, keep: impl Fn(&Uuid) -> bool) -> usize {
+        let mut players = self.players();
+        let before = players.len();
+        players.retain(|id, _| keep(id));
+        before - players.len()
+    }
+}
+
+/// Word bigram model over the phrases a player has used or been offered
+struct NgramModel {
+    phrases: Vec<String>,
+    next: HashMap<String, HashMap<String, u32>>,
+}
+
+impl NgramModel {
+    fn new(phrases: Vec<String>) -> Self {
+        let mut next: HashMap<String, HashMap<String, u32>> = HashMap::new();
+        for phrase in &phrases {
+            let words: Vec<String> = phrase.split_whitespace().map(str::to_lowercase).collect();
+            for pair in words.windows(2) {
+                *next
+                    .entry(pair[0].clone())
+                    .or_default()
+                    .entry(pair[1].clone())
+                    .or_default() += 1;
+            }
+        }
+        Self { phrases, next }
+    }
+
+    /// Most frequent words seen after `word`
+    fn followers(&self, word: &str) -> Vec<&str> {
+        let mut followers: Vec<(&str, u32)> = self
+            .next
+            .get(word)
+            .map(|f| f.iter().map(|(w, n)| (w.as_str(), *n)).collect())
+            .unwrap_or_default();
+        followers.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        followers.into_iter().map(|(w, _)| w).collect()
+    }
+
+    /// Greedily extend `prefix` word by word from each likely next word
+    fn complete(&self, prefix: &str) -> Vec<String> {
+        let normalized = normalize_prefix(prefix);
+
+        // Whole phrases that already start with the prefix, most frequent first
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for phrase in &self.phrases {
+            if normalize_prefix(phrase).starts_with(&normalized) {
+                *counts.entry(phrase.as_str()).or_default() += 1;
+            }
+        }
+        let mut whole: Vec<(&str, usize)> = counts.into_iter().collect();
+        whole.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let mut completions: Vec<String> = whole.into_iter().map(|(p, _)| p.to_string()).collect();
+
+        // Otherwise continue from the last word typed
+        if let Some(last) = normalized.split(' ').next_back().filter(|w| !w.is_empty()) {
+            for first in self.followers(last) {
+                let mut words = vec![first];
+                while words.len() < MAX_COMPLETION_WORDS {
+                    let tail = words[words.len() - 1];
+                    match self.followers(tail).into_iter().find(|w| !words.contains(w)) {
+                        Some(word) => words.push(word),
+                        None => break,
+                    }
+                }
+                completions.push(format!("{} {}", prefix.trim_end(), words.join(" ")));
+            }
+        }
+
+        let mut seen = Vec::new();
+        completions.retain(|c| {
+            let key = normalize_prefix(c);
+            let fresh = !seen.contains(&key) && key != normalized;
+            seen.push(key);
+            fresh
+        });
+        completions.truncate(SUGGESTION_COUNT);
+        completions
+    }
+}
+
+/// Suggestions from past choices and the current moment's options, without the LLM
+pub fn local_suggestions(player: &Player, past_choices: Vec<String>, prefix: &str) -> Vec<String> {
+    let mut phrases = past_choices;
+    phrases.extend(player.run.graph.edges.iter().map(|e| e.choice_text.clone()));
+    if let Some(moment) = player.run.narrative_history.last() {
+        phrases.extend(moment.choices.iter().map(|c| c.text.clone()));
+    }
+    NgramModel::new(phrases).complete(prefix)
+}
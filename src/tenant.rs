@@ -0,0 +1,255 @@
+//! Tenants: communities or classrooms sharing one deployment, with their data
+//! kept apart.
+//!
+//! Each tenant in `TENANTS_FILE` gets its own game state, caches, rate limits,
+//! LLM client and scheduler, built from the server's config with the tenant's
+//! overrides on top. A request picks its tenant with a `/t/{tenant}` path
+//! prefix or an `X-Tenant` header; requests without either go to the default
+//! deployment.
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::{uri::PathAndQuery, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use crate::config::{Config, ContentRating};
+
+pub const HEADER: &str = "x-tenant";
+const PREFIX: &str = "/t/";
+const MAX_ID_LEN: usize = 64;
+
+/// Settings a tenant may change; anything left out follows the server's config
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TenantOverrides {
+    llm_base_url: Option<String>,
+    llm_api_key: Option<String>,
+    llm_model: Option<String>,
+    content_rating: Option<ContentRating>,
+    suggest_rate_limit: Option<u32>,
+    card_rate_limit: Option<u32>,
+    max_active_players: Option<usize>,
+    theme_pack: Option<String>,
+    scenario_anchors: Option<String>,
+    ending_conditions: Option<String>,
+}
+
+impl TenantOverrides {
+    /// The server's config as seen by the tenant `id`
+    fn apply(self, base: &Config, id: &str) -> Config {
+        let mut config = base.clone();
+        config.tenant = Some(id.to_string());
+        if let Some(url) = self.llm_base_url {
+            config.llm_base_urls = vec![url];
+        }
+        if let Some(key) = self.llm_api_key {
+            config.llm_api_key = key;
+        }
+        if let Some(model) = self.llm_model {
+            config.llm_model = model;
+        }
+        if let Some(rating) = self.content_rating {
+            config.content_rating = rating;
+        }
+        if let Some(limit) = self.suggest_rate_limit {
+            config.suggest_rate_limit = limit;
+        }
+        if let Some(limit) = self.card_rate_limit {
+            config.card_rate_limit = limit;
+        }
+        if let Some(max) = self.max_active_players {
+            config.max_active_players = max;
+        }
+        if self.theme_pack.is_some() {
+            config.theme_pack = self.theme_pack;
+        }
+        if self.scenario_anchors.is_some() {
+            config.scenario_anchors = self.scenario_anchors;
+        }
+        if self.ending_conditions.is_some() {
+            config.ending_conditions = self.ending_conditions;
+        }
+        config
+    }
+}
+
+/// Tenant ids appear in paths and file names, so they are kept plain
+pub fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// The default deployment's config and each tenant's
+pub struct TenantConfigs {
+    pub default: Config,
+    pub tenants: BTreeMap<String, Config>,
+}
+
+impl TenantConfigs {
+    /// Read the tenants named by `TENANTS_FILE`, if any
+    pub fn load(config: &Config) -> Result<Self> {
+        let tenants = match &config.tenants_file {
+            Some(path) => {
+                let text = fs::read_to_string(path)
+                    .with_context(|| format!("failed to read tenants {}", path))?;
+                parse(config, &text).with_context(|| format!("invalid tenants {}", path))?
+            }
+            None => BTreeMap::new(),
+        };
+        for (id, tenant) in &tenants {
+            tracing::info!(
+                "Tenant {} narrated by {} ({:?})",
+                id,
+                tenant.llm_model,
+                tenant.content_rating
+            );
+        }
+        Ok(Self {
+            default: config.clone(),
+            tenants,
+        })
+    }
+}
+
+/// A JSON object of tenant ids to their overrides
+fn parse(base: &Config, text: &str) -> Result<BTreeMap<String, Config>> {
+    let overrides: BTreeMap<String, TenantOverrides> = serde_json::from_str(text)?;
+    overrides
+        .into_iter()
+        .map(|(id, overrides)| {
+            if !valid_id(&id) {
+                anyhow::bail!(
+                    "tenant id '{}' must be 1-{} lowercase letters, digits, '-' or '_'",
+                    id,
+                    MAX_ID_LEN
+                );
+            }
+            let config = overrides.apply(base, &id);
+            Ok((id, config))
+        })
+        .collect()
+}
+
+/// A value loaded for the default deployment and for each tenant
+pub struct PerTenant<T> {
+    default: T,
+    tenants: HashMap<String, T>,
+}
+
+impl<T> PerTenant<T> {
+    /// The same value for everyone, as before any tenant is loaded
+    pub fn only(default: T) -> Self {
+        Self {
+            default,
+            tenants: HashMap::new(),
+        }
+    }
+
+    pub fn load(
+        configs: &TenantConfigs,
+        mut load: impl FnMut(&Config) -> Result<T>,
+    ) -> Result<Self> {
+        let mut tenants = HashMap::new();
+        for (id, config) in &configs.tenants {
+            tenants.insert(id.clone(), load(config).with_context(|| format!("tenant {}", id))?);
+        }
+        Ok(Self {
+            default: load(&configs.default)?,
+            tenants,
+        })
+    }
+
+    /// The tenant's value, or the default deployment's
+    pub fn get(&self, tenant: Option<&str>) -> &T {
+        tenant
+            .and_then(|id| self.tenants.get(id))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Where the tenant keeps a file or directory under `data/`
+pub fn data_path(tenant: Option<&str>, path: &str) -> PathBuf {
+    match (tenant, path.strip_prefix("data/")) {
+        (Some(id), Some(rest)) => PathBuf::from("data/tenants").join(id).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Routers of the default deployment and of each tenant
+struct TenantRouters {
+    default: Router,
+    tenants: HashMap<String, Router>,
+}
+
+/// A router sending each request to its tenant's router
+pub fn router(default: Router, tenants: HashMap<String, Router>) -> Router {
+    Router::new()
+        .fallback(dispatch)
+        .with_state(Arc::new(TenantRouters { default, tenants }))
+}
+
+async fn dispatch(State(routers): State<Arc<TenantRouters>>, mut request: Request) -> Response {
+    let header = request
+        .headers()
+        .get(HEADER)
+        .map(|v| v.to_str().map(str::to_string));
+    let header = match header {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => return StatusCode::BAD_REQUEST.into_response(),
+        None => None,
+    };
+    let prefixed = split_prefix(request.uri());
+
+    let router = match (header, prefixed) {
+        (Some(id), Some((prefix, _))) if id != prefix => {
+            return (StatusCode::BAD_REQUEST, "tenant header and path disagree").into_response();
+        }
+        (_, Some((id, uri))) => {
+            *request.uri_mut() = uri;
+            routers.tenants.get(&id)
+        }
+        (Some(id), None) => routers.tenants.get(&id),
+        (None, None) => Some(&routers.default),
+    };
+    match router {
+        Some(router) => match router.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(infallible) => match infallible {},
+        },
+        None => (StatusCode::NOT_FOUND, "unknown tenant").into_response(),
+    }
+}
+
+/// The tenant of a `/t/{tenant}/...` path and the URI without the prefix
+fn split_prefix(uri: &Uri) -> Option<(String, Uri)> {
+    let rest = uri.path().strip_prefix(PREFIX)?;
+    let (id, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    if id.is_empty() {
+        return None;
+    }
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Some((id.to_string(), Uri::from_parts(parts).ok()?))
+}
+
+#[cfg(test)]
+mod tests;
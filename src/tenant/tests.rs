@@ -0,0 +1,90 @@
+use super::*;
+use axum::{body::Body, routing::get};
+
+fn routers() -> Router {
+    let echo = |name: &'static str| {
+        Router::new().route(
+            "/api/whoami",
+            get(move |uri: Uri| async move { format!("{} {}", name, uri) }),
+        )
+    };
+    router(
+        echo("default"),
+        HashMap::from([("class-7b".to_string(), echo("class-7b"))]),
+    )
+}
+
+async fn call(path: &str, header: Option<&str>) -> (StatusCode, String) {
+    let mut request = Request::builder().uri(path);
+    if let Some(id) = header {
+        request = request.header(HEADER, id);
+    }
+    let response = routers()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn requests_reach_their_tenant_by_prefix_or_header() {
+    assert_eq!(
+        call("/api/whoami?x=1", None).await,
+        (StatusCode::OK, "default /api/whoami?x=1".to_string())
+    );
+    assert_eq!(
+        call("/t/class-7b/api/whoami?x=1", None).await,
+        (StatusCode::OK, "class-7b /api/whoami?x=1".to_string())
+    );
+    assert_eq!(
+        call("/api/whoami", Some("class-7b")).await,
+        (StatusCode::OK, "class-7b /api/whoami".to_string())
+    );
+    assert_eq!(
+        call("/t/class-7b/api/whoami", Some("class-7b")).await.0,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn unknown_or_conflicting_tenants_are_refused() {
+    assert_eq!(call("/t/class-8a/api/whoami", None).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(call("/api/whoami", Some("class-8a")).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(
+        call("/t/class-7b/api/whoami", Some("default")).await.0,
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[test]
+fn overrides_apply_on_top_of_the_server_config() {
+    let base = Config::for_tests("http://127.0.0.1:9/v1");
+    let tenants = parse(
+        &base,
+        r#"{"class-7b": {"llm_model": "small", "content_rating": "teen", "suggest_rate_limit": 2}}"#,
+    )
+    .unwrap();
+
+    let tenant = &tenants["class-7b"];
+    assert_eq!(tenant.tenant.as_deref(), Some("class-7b"));
+    assert_eq!(tenant.llm_model, "small");
+    assert_eq!(tenant.content_rating, ContentRating::Teen);
+    assert_eq!(tenant.suggest_rate_limit, 2);
+    assert_eq!(tenant.card_rate_limit, base.card_rate_limit);
+
+    assert!(parse(&base, r#"{"Class 7B": {}}"#).is_err());
+    assert!(parse(&base, r#"{"class-7b": {"admin_token": "x"}}"#).is_err());
+}
+
+#[test]
+fn tenant_files_live_under_their_own_directory() {
+    assert_eq!(
+        data_path(Some("class-7b"), "data/usage"),
+        PathBuf::from("data/tenants/class-7b/usage")
+    );
+    assert_eq!(data_path(None, "data/usage"), PathBuf::from("data/usage"));
+}
@@ -0,0 +1,94 @@
+use crate::game::Player;
+
+/// Moments whose mood counts toward the tension, newest weighing most
+const MOOD_WINDOW: usize = 4;
+/// Total score movement over the recent choices that reads as full tension
+const FULL_VELOCITY: f64 = 30.0;
+/// Either half of the meter when there is nothing to go on yet
+const UNKNOWN: f64 = 0.5;
+
+/// How charged each mood is
+fn mood_weight(mood: &str) -> f64 {
+    match mood {
+        "dark" => 1.0,
+        "nihilistic" => 0.8,
+        "transcendent" => 0.5,
+        "neutral" => 0.3,
+        "hopeful" => 0.2,
+        _ => 0.4,
+    }
+}
+
+/// Narrative tension from 0 (calm) to 1 (at breaking point): the moods of
+/// the latest moments this loop, and how fast the player's score has been
+/// moving over their latest choices
+pub fn tension(player: &Player) -> f64 {
+    let recent = player.run.narrative_history.iter().rev().take(MOOD_WINDOW);
+    let (sum, weights) = recent
+        .enumerate()
+        .map(|(age, moment)| (mood_weight(&moment.mood), 1.0 / (age + 1) as f64))
+        .fold((0.0, 0.0), |(sum, total), (value, weight)| {
+            (sum + value * weight, total + weight)
+        });
+    let mood = if weights > 0.0 { sum / weights } else { UNKNOWN };
+
+    let deltas = &player.run.memory.recent_score_deltas;
+    let velocity = if deltas.is_empty() {
+        UNKNOWN
+    } else {
+        let moved: i32 = deltas.iter().map(|d| d.abs()).sum();
+        (moved as f64 / FULL_VELOCITY).min(1.0)
+    };
+
+    (0.6 * mood + 0.4 * velocity).clamp(0.0, 1.0)
+}
+
+/// How many choices a moment offers, set by the tension
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pacing {
+    /// Two stark choices
+    Tense,
+    Steady,
+    /// Room to look around
+    Calm,
+}
+
+impl Pacing {
+    pub fn from_tension(tension: f64) -> Self {
+        if tension >= 0.7 {
+            Pacing::Tense
+        } else if tension <= 0.35 {
+            Pacing::Calm
+        } else {
+            Pacing::Steady
+        }
+    }
+
+    pub fn for_player(player: &Player) -> Self {
+        Self::from_tension(tension(player))
+    }
+
+    /// Fewest and most choices a moment may offer
+    pub fn choice_range(&self) -> (usize, usize) {
+        match self {
+            Pacing::Tense => (2, 2),
+            Pacing::Steady => (2, 4),
+            Pacing::Calm => (3, 5),
+        }
+    }
+
+    /// The narrator's instruction about choices
+    pub fn instruction(&self) -> &'static str {
+        match self {
+            Pacing::Tense => {
+                "The tension is at its height: present exactly 2 stark choices with no middle \
+                 ground, one of them clearly dark"
+            }
+            Pacing::Steady => "Present 2-4 meaningful choices that explore the themes",
+            Pacing::Calm => {
+                "The moment is calm: present 3-5 exploratory choices, including some that \
+                 simply look around, linger or wonder"
+            }
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Fixtures for realistic mid-game player states.
+//!
+//! Used by the crate's own tests. With the `testing` feature the server also
+//! exposes `POST /api/testing/players`, so frontends can create the same
+//! fixtures for their integration tests. Never enable it in production.
+
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::endings::EndingType;
+use crate::game::{ArchivedLoop, Choice, Loop, MomentState, NarrativeMoment, Player};
+use crate::persona::Persona;
+use crate::stability::MAX_STABILITY;
+
+/// Builds a player as if they had played for a while.
+///
+/// ```ignore
+/// let player = PlayerBuilder::new().loops(12).dark(30).light(5).truths(["the door"]).build();
+/// ```
+#[derive(Default)]
+pub struct PlayerBuilder {
+    loops: u64,
+    dark: u64,
+    light: u64,
+    score: Option<i32>,
+    persona: Persona,
+    truths: Vec<String>,
+    memories: Vec<String>,
+    endings: Vec<EndingType>,
+    choices_made: Vec<String>,
+    moments: Vec<NarrativeMoment>,
+    score_deltas: Vec<i32>,
+    stability: Option<u8>,
+}
+
+impl PlayerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loops completed; the player is in the loop after them
+    pub fn loops(mut self, loops: u64) -> Self {
+        self.loops = loops;
+        self
+    }
+
+    pub fn dark(mut self, choices: u64) -> Self {
+        self.dark = choices;
+        self
+    }
+
+    pub fn light(mut self, choices: u64) -> Self {
+        self.light = choices;
+        self
+    }
+
+    /// Nihilism score; by default what the choices would add up to without streaks
+    pub fn score(mut self, score: i32) -> Self {
+        self.score = Some(score);
+        self
+    }
+
+    pub fn persona(mut self, persona: Persona) -> Self {
+        self.persona = persona;
+        self
+    }
+
+    pub fn truths<S: Into<String>>(mut self, truths: impl IntoIterator<Item = S>) -> Self {
+        self.truths = truths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn memories<S: Into<String>>(mut self, memories: impl IntoIterator<Item = S>) -> Self {
+        self.memories = memories.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Endings reached in this run
+    pub fn endings(mut self, endings: impl IntoIterator<Item = EndingType>) -> Self {
+        self.endings = endings.into_iter().collect();
+        self
+    }
+
+    /// Choice ids made so far in the current loop
+    pub fn choices_made<S: Into<String>>(mut self, choices: impl IntoIterator<Item = S>) -> Self {
+        self.choices_made = choices.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Add a moment to the story, presented and awaiting a choice
+    pub fn moment(mut self, text: &str, choices: &[(&str, &str)]) -> Self {
+        self.moments.push(moment(text, choices));
+        self
+    }
+
+    /// Set the mood of the latest moment added
+    pub fn mood(mut self, mood: &str) -> Self {
+        if let Some(moment) = self.moments.last_mut() {
+            moment.mood = mood.to_string();
+        }
+        self
+    }
+
+    /// Score changes of the latest choices, oldest first
+    pub fn score_deltas(mut self, deltas: impl IntoIterator<Item = i32>) -> Self {
+        self.score_deltas = deltas.into_iter().collect();
+        self
+    }
+
+    /// Stability of the current loop; full by default
+    pub fn stability(mut self, stability: u8) -> Self {
+        self.stability = Some(stability);
+        self
+    }
+
+    pub fn build(self) -> Player {
+        let mut player = Player::new();
+        player.run.persona = self.persona;
+        player.run.current_loop.number = self.loops + 1;
+
+        let memory = &mut player.run.memory;
+        memory.total_loops = self.loops;
+        memory.dark_choices = self.dark;
+        memory.light_choices = self.light;
+        memory.total_choices = self.dark + self.light;
+        memory.nihilism_score = self.score.unwrap_or_else(|| {
+            let (dark_delta, light_delta) = self.persona.score_deltas();
+            let score = self.dark as i64 * dark_delta as i64 + self.light as i64 * light_delta as i64;
+            score.clamp(-100, 100) as i32
+        });
+        memory.truths_discovered = self.truths;
+        memory.key_memories = self.memories;
+        memory.endings_reached = self.endings;
+        memory.recent_score_deltas = self.score_deltas;
+
+        player.run.current_loop.choices_made = self.choices_made;
+        player.run.current_loop.stability = self.stability.unwrap_or(MAX_STABILITY);
+        player.run.narrative_history = self.moments;
+        player
+    }
+}
+
+/// A presented moment with the given choices, as `(id, text)` pairs
+pub fn moment(text: &str, choices: &[(&str, &str)]) -> NarrativeMoment {
+    NarrativeMoment {
+        id: Uuid::new_v4(),
+        text: text.to_string(),
+        speaker: None,
+        mood: "neutral".to_string(),
+        choices: choices
+            .iter()
+            .map(|(id, text)| Choice {
+                id: id.to_string(),
+                text: text.to_string(),
+                consequence_hint: None,
+                provenance: None,
+            })
+            .collect(),
+        timestamp: Utc::now(),
+        summarized: false,
+        world_updates: Vec::new(),
+        state: MomentState::Presented,
+        translation: None,
+        deja_vu: None,
+        fate: None,
+        assets: None,
+        provenance: None,
+    }
+}
+
+/// A finished loop of `player`'s run as it is archived: a short scene at a
+/// door, answered the same way each time
+pub fn archived_loop(player: &Player, number: u64, outcome: &str) -> ArchivedLoop {
+    let ended_at = Utc::now();
+    let scenes = [
+        "You wake at the door again. The handle is warm.",
+        "The corridor behind it hums a song you almost remember.",
+        "The light flickers out, and the day folds in on itself.",
+    ];
+    let moments: Vec<NarrativeMoment> = scenes
+        .iter()
+        .map(|text| NarrativeMoment {
+            state: MomentState::Archived,
+            ..moment(text, &[("open", "Open the door"), ("walk_away", "Walk away")])
+        })
+        .collect();
+    ArchivedLoop {
+        player_id: player.run_id(),
+        loop_info: Loop {
+            number,
+            started_at: ended_at - Duration::minutes(20),
+            ended_at: Some(ended_at),
+            choices_made: vec!["open".to_string(); moments.len()],
+            outcome: Some(outcome.to_string()),
+            reset_sequence: Vec::new(),
+            dead_characters: Vec::new(),
+            artifacts: Vec::new(),
+            stability: MAX_STABILITY,
+            regenerations: 0,
+        },
+        moments,
+        archived_at: ended_at,
+        shard: None,
+    }
+}
+
+/// A fixture player as requested over HTTP; every field is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PlayerSpec {
+    pub loops: u64,
+    pub dark: u64,
+    pub light: u64,
+    pub score: Option<i32>,
+    pub persona: Persona,
+    pub truths: Vec<String>,
+    pub memories: Vec<String>,
+    pub endings: Vec<EndingType>,
+}
+
+impl PlayerSpec {
+    pub fn build(self) -> Player {
+        let mut builder = PlayerBuilder::new()
+            .loops(self.loops)
+            .dark(self.dark)
+            .light(self.light)
+            .persona(self.persona)
+            .truths(self.truths)
+            .memories(self.memories)
+            .endings(self.endings);
+        if let Some(score) = self.score {
+            builder = builder.score(score);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(feature = "testing")]
+pub use routes::create_fixture_player;
+
+#[cfg(feature = "testing")]
+mod routes {
+    use axum::{extract::State, http::StatusCode, Json};
+
+    use super::PlayerSpec;
+    use crate::events::GameEvent;
+    use crate::game::PlayerSummary;
+    use crate::persistence;
+    use crate::routes::AppState;
+
+    /// Create a player from a fixture spec and save it like any other
+    pub async fn create_fixture_player(
+        State(state): State<AppState>,
+        Json(spec): Json<PlayerSpec>,
+    ) -> Result<Json<PlayerSummary>, StatusCode> {
+        let player = spec.build();
+        persistence::save_player(&player).map_err(|e| {
+            tracing::error!("Failed to save fixture player: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        let summary = player.summary();
+        state.game.write().await.players.insert(player.id, player);
+        state.events.publish(GameEvent::PlayerCreated {
+            player_id: summary.id,
+        });
+        Ok(Json(summary))
+    }
+}
@@ -0,0 +1,250 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::seq::IndexedRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::game::{MomentState, Player};
+use crate::thinking::Stage;
+
+/// Seconds a moment must have been waiting before the first line
+const QUIET_SECS: i64 = 20;
+/// Lines sent at most while one moment waits for a choice
+const MAX_LINES_PER_MOMENT: u32 = 3;
+
+/// Which way a line leans
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tone {
+    Dark,
+    Neutral,
+    Hopeful,
+}
+
+impl Tone {
+    fn name(&self) -> &'static str {
+        match self {
+            Tone::Dark => "dark",
+            Tone::Neutral => "neutral",
+            Tone::Hopeful => "hopeful",
+        }
+    }
+
+    fn templates(&self) -> &'static [&'static str] {
+        match self {
+            Tone::Dark => DARK,
+            Tone::Neutral => NEUTRAL,
+            Tone::Hopeful => HOPEFUL,
+        }
+    }
+}
+
+// Templates may use {loop} (the current loop number), {dead} (a character
+// lost this loop), {artifact} (something found this loop) and {truth} (a
+// discovered truth). Templates whose placeholders can't be filled are skipped.
+
+const DARK: &[&str] = &[
+    "A clock somewhere ticks backwards, just once.",
+    "The wallpaper has started to peel in the shape of a door.",
+    "Somewhere a phone rings. Nobody is left to answer it.",
+    "The shadows in the corner have grown a little since loop {loop}.",
+    "The air tastes of dust and of every loop before this one.",
+    "A bird hits the window, and then, a moment later, hits it again.",
+    "The radiator knocks three times, like someone asking to be let in.",
+    "Your reflection is a half second late.",
+    "The silence has weight now. It sits on your chest.",
+    "A chair scrapes in an empty room upstairs.",
+    "The streetlight outside flickers in a rhythm you almost recognize.",
+    "You can still hear {dead}, if you don't listen too closely.",
+    "The place where {dead} stood is colder than the rest of the room.",
+    "{artifact} feels heavier than it did a moment ago.",
+    "The thought returns, unbidden: {truth}.",
+];
+
+const NEUTRAL: &[&str] = &[
+    "A kettle clicks off in another room.",
+    "Rain starts, hesitates, and thinks better of it.",
+    "Dust turns slowly in a shaft of light.",
+    "Someone laughs in the street below. The sound doesn't carry far.",
+    "The refrigerator hums a single patient note.",
+    "A curtain breathes in and out with the draft.",
+    "Far off, a train passes. It always passes at this moment.",
+    "The floorboards settle, as they do every loop.",
+    "A page of newspaper drifts across the road.",
+    "Loop {loop}. The light is the same as always, and a little different.",
+    "The wind moves the trees in the same pattern it moved them yesterday.",
+    "A dog barks twice, then loses interest.",
+    "You notice {artifact} again, exactly where you left it.",
+    "Nobody else seems to remember {dead}.",
+];
+
+const HOPEFUL: &[&str] = &[
+    "Sunlight finds a gap in the clouds and stays there.",
+    "Somewhere a child is learning to whistle, badly and joyfully.",
+    "A neighbour's window is open; someone is cooking something with garlic.",
+    "A sparrow lands on the sill and regards you without fear.",
+    "The smell of bread drifts up from the bakery on the corner.",
+    "For a second the loop feels less like a cage and more like a second chance.",
+    "Two strangers share an umbrella, laughing.",
+    "The light in loop {loop} is warmer than you remember.",
+    "A song on a distant radio, the one you always meant to learn.",
+    "Someone has left flowers on the bench outside.",
+    "{artifact} is warm in your hand, as if it has been waiting for you.",
+    "You think of {dead}, and the memory is kinder than you expected.",
+    "It helps, somehow, to know: {truth}.",
+];
+
+// Thinking beats, sent while a slow generation runs, by how far it has come
+
+const SENT: &[&str] = &[
+    "The narrator considers you.",
+    "Somewhere, a pen hovers over the page.",
+    "The loop holds its breath.",
+    "Loop {loop} waits to see what it will become.",
+    "The room goes still, as if listening for what comes next.",
+];
+
+const STREAMING: &[&str] = &[
+    "The narrator has begun to write.",
+    "Ink finds the page.",
+    "The next moment is taking shape.",
+    "Words gather at the edges of the room.",
+    "Somewhere {dead} is being remembered, one line at a time.",
+];
+
+const HALFWAY: &[&str] = &[
+    "The scene is half written.",
+    "The narrator pauses, then goes on.",
+    "Almost. The room is nearly ready for you.",
+    "The ink is still wet on loop {loop}.",
+    "The page turns. Not long now.",
+];
+
+/// How strongly each tone is drawn, from the mood of the latest moment and
+/// the player's nihilism score
+fn weights(mood: &str, score: i32) -> [(Tone, u32); 3] {
+    let (dark, neutral, hopeful) = match mood {
+        "dark" | "nihilistic" => (6, 3, 1),
+        "hopeful" | "transcendent" => (1, 3, 6),
+        _ => (2, 5, 2),
+    };
+    // Up to three points toward the way the player has been choosing
+    let lean = score.clamp(-100, 100) / 30;
+    [
+        (Tone::Dark, (dark + lean).max(0) as u32),
+        (Tone::Neutral, neutral as u32),
+        (Tone::Hopeful, (hopeful - lean).max(0) as u32),
+    ]
+}
+
+fn pick_tone(mood: &str, score: i32, rng: &mut impl Rng) -> Tone {
+    let weights = weights(mood, score);
+    let total: u32 = weights.iter().map(|(_, w)| w).sum();
+    let mut roll = rng.random_range(0..total.max(1));
+    for (tone, weight) in weights {
+        if roll < weight {
+            return tone;
+        }
+        roll -= weight;
+    }
+    Tone::Neutral
+}
+
+/// Fill a template from the player's world, or `None` if it needs something
+/// the world doesn't have
+fn render(template: &str, player: &Player, rng: &mut impl Rng) -> Option<String> {
+    let current = &player.run.current_loop;
+    let mut text = template.replace("{loop}", &current.number.to_string());
+    let fills = [
+        ("{dead}", &current.dead_characters),
+        ("{artifact}", &current.artifacts),
+        ("{truth}", &player.run.memory.truths_discovered),
+    ];
+    for (placeholder, values) in fills {
+        if text.contains(placeholder) {
+            text = text.replace(placeholder, values.choose(rng)?);
+        }
+    }
+    let mut chars = text.chars();
+    let first = chars.next()?;
+    Some(first.to_uppercase().chain(chars).collect())
+}
+
+/// An ambient line for the player's world, leaning toward `mood`
+pub fn line(player: &Player, mood: &str, rng: &mut impl Rng) -> (String, &'static str) {
+    let tone = pick_tone(mood, player.run.memory.nihilism_score, rng);
+    let templates = tone.templates();
+    // Templates without placeholders always render, so this terminates
+    loop {
+        let template = templates.choose(rng).copied().unwrap_or(NEUTRAL[0]);
+        if let Some(text) = render(template, player, rng) {
+            return (text, tone.name());
+        }
+    }
+}
+
+/// A thinking beat for a generation that has reached `stage`
+pub fn thinking(player: &Player, stage: Stage, rng: &mut impl Rng) -> String {
+    let templates = match stage {
+        Stage::Sent => SENT,
+        Stage::Streaming => STREAMING,
+        Stage::Halfway => HALFWAY,
+    };
+    // As with ambient lines, templates without placeholders always render
+    loop {
+        let template = templates.choose(rng).copied().unwrap_or(SENT[0]);
+        if let Some(text) = render(template, player, rng) {
+            return text;
+        }
+    }
+}
+
+/// Paces texture lines: only while a moment waits for a choice, not right
+/// after it arrives, and a few per moment at most
+pub struct TextureLines {
+    sent: Mutex<HashMap<Uuid, (Uuid, u32)>>,
+}
+
+impl TextureLines {
+    pub fn new() -> Self {
+        Self {
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A line for the player now, or `None` if they are mid-generation, have
+    /// only just been shown a moment, or have heard enough for this one
+    pub fn next(
+        &self,
+        player: &Player,
+        active_since: DateTime<Utc>,
+    ) -> Option<(Uuid, String, &'static str)> {
+        let moment = player.run.narrative_history.last()?;
+        if player.is_locked()
+            || moment.state != MomentState::Presented
+            || player.last_active() < active_since
+            || Utc::now() - moment.timestamp < Duration::seconds(QUIET_SECS)
+        {
+            return None;
+        }
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = sent.entry(player.id).or_insert((moment.id, 0));
+        if entry.0 != moment.id {
+            *entry = (moment.id, 0);
+        }
+        if entry.1 >= MAX_LINES_PER_MOMENT {
+            return None;
+        }
+        entry.1 += 1;
+        let (text, tone) = line(player, &moment.mood, &mut rand::rng());
+        Some((moment.id, text, tone))
+    }
+
+    /// Forget players no longer held in memory
+    pub fn retain(&self, players: &HashMap<Uuid, Player>) {
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|id, _| players.contains_key(id));
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use rand::seq::IndexedRandom;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::tenant::{PerTenant, TenantConfigs};
+
+/// A piece of flavor text the server says in its own voice
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Flavor {
+    /// Answer to the health check
+    Greeting,
+    /// A new game
+    Welcome,
+    /// A new player sent to the waiting room
+    WaitingRoom,
+    /// A saved game loaded
+    WelcomeBack,
+    /// A saved game loaded after its world decayed
+    LongAbsence,
+    /// Loading a game that was still in memory
+    NeverLeft,
+    Saved,
+    /// A daily challenge run; `{modifier}` is replaced
+    Challenge,
+    /// A run started in a race; `{modifier}` is replaced
+    Race,
+    /// A new loop after a reset; `{loop}` is replaced
+    LoopBegins,
+    /// Text of a moment removed by an admin
+    Struck,
+    /// Moment shown instead of one that failed moderation
+    Moderated,
+    /// Put before a moment relived from an earlier loop
+    DejaVu,
+    /// Moment offered again after the server stopped mid-generation
+    Stutter,
+    /// Push notification to players who haven't played today
+    PushReminder,
+    /// Push notification of a new daily challenge, with `{modifier}`
+    PushChallenge,
+    /// Push notification of a race about to start, with `{modifier}`
+    PushRace,
+}
+
+impl Flavor {
+    fn default_variants(&self) -> &'static [&'static str] {
+        match self {
+            Flavor::Greeting => &["Nihilism game server is running. The loop continues..."],
+            Flavor::Welcome => {
+                &["Welcome to the loop. You've been here before, even if you don't remember."]
+            }
+            Flavor::WaitingRoom => &["The loop is crowded. Wait here; your turn will come around."],
+            Flavor::WelcomeBack => &["I remember you... welcome back to the loop."],
+            Flavor::LongAbsence => &["You were gone a long time. The loop did not wait."],
+            Flavor::NeverLeft => &["You never left the loop."],
+            Flavor::Saved => &["Your journey has been etched into the void."],
+            Flavor::Challenge => &["Today the loop is different. {modifier}."],
+            Flavor::Race => &["You are not the only one waking into this loop. {modifier}."],
+            Flavor::LoopBegins => &["Loop #{loop} begins. Despite everything... it's still you."],
+            Flavor::Struck => &["This moment has been struck from the record."],
+            Flavor::Moderated => &[
+                "The loop flickers. Whatever was about to happen slips out of focus, and you \
+                 find yourself a few steps back, breathing.",
+            ],
+            Flavor::DejaVu => &["Déjà vu. You have stood here before, and chosen this before."],
+            Flavor::Stutter => &[
+                "The loop stutters. For a breath everything happens twice, then not at all, and \
+                 you are standing where you stood, the same choice in front of you.",
+            ],
+            Flavor::PushReminder => &["The loop remembers you."],
+            Flavor::PushChallenge => &["Today's loop is {modifier}. It is waiting."],
+            Flavor::PushRace => &["A race through {modifier} begins in a minute."],
+        }
+    }
+}
+
+/// String tables from a theme pack file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemePack {
+    #[serde(default)]
+    name: Option<String>,
+    /// Variants for each flavor; flavors left out keep the built-in text
+    strings: HashMap<Flavor, Vec<String>>,
+}
+
+static THEME: OnceLock<PerTenant<HashMap<Flavor, Vec<String>>>> = OnceLock::new();
+
+fn load(path: &str) -> Result<ThemePack> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read theme pack {}", path))?;
+    let pack: ThemePack =
+        serde_json::from_str(&text).with_context(|| format!("invalid theme pack {}", path))?;
+    if let Some((flavor, _)) = pack.strings.iter().find(|(_, v)| v.is_empty()) {
+        anyhow::bail!("theme pack {} has no variants for {:?}", path, flavor);
+    }
+    Ok(pack)
+}
+
+/// Load each tenant's theme pack named by `THEME_PACK`. Must be called once
+/// at startup, so a bad pack stops the server there.
+pub fn init(tenants: &TenantConfigs) -> Result<()> {
+    let strings = PerTenant::load(tenants, |config| {
+        Ok(match &config.theme_pack {
+            Some(path) => {
+                let pack = load(path)?;
+                tracing::info!(
+                    "Using theme pack {} ({} flavors replaced)",
+                    pack.name.as_deref().unwrap_or(path),
+                    pack.strings.len()
+                );
+                pack.strings
+            }
+            None => HashMap::new(),
+        })
+    })?;
+    if THEME.set(strings).is_err() {
+        anyhow::bail!("theme already initialized");
+    }
+    Ok(())
+}
+
+/// One of the tenant's theme variants for `flavor`, picked at random
+pub fn text(tenant: Option<&str>, flavor: Flavor) -> String {
+    let mut rng = rand::rng();
+    match THEME.get().and_then(|theme| theme.get(tenant).get(&flavor)) {
+        Some(variants) => variants.choose(&mut rng).cloned().unwrap_or_default(),
+        None => flavor
+            .default_variants()
+            .choose(&mut rng)
+            .map(|s| s.to_string())
+            .unwrap_or_default(),
+    }
+}
@@ -0,0 +1,171 @@
+//! Thinking beats: when a generation keeps a player waiting, short lines from
+//! the texture pool tell them the narrator is at work, so the wait reads as
+//! part of the fiction rather than as lag.
+//!
+//! Beats follow the completion's real progress, reported by the LLM client
+//! from inside [`scope`]: the request going out, the first streamed token, and
+//! half of a typical completion streamed. Nothing is sent before
+//! `THINKING_BEAT_DELAY_MS`; after that each stage is announced once, and a
+//! stage passed while the delay ran is skipped for the latest one.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::events::{EventBus, GameEvent};
+use crate::game::Player;
+use crate::texture;
+
+/// Completion tokens assumed typical before any completion has streamed
+const INITIAL_TYPICAL_TOKENS: u32 = 200;
+
+/// Running average of streamed completion lengths, for the halfway beat
+static TYPICAL_TOKENS: AtomicU32 = AtomicU32::new(INITIAL_TYPICAL_TOKENS);
+
+tokio::task_local! {
+    static WAIT: Arc<Wait>;
+}
+
+/// How far a completion has come
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// The request went out to the backend
+    Sent,
+    /// The first token streamed back
+    Streaming,
+    /// Half of a typical completion has streamed
+    Halfway,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 3] = [Stage::Sent, Stage::Streaming, Stage::Halfway];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Sent => "sent",
+            Stage::Streaming => "streaming",
+            Stage::Halfway => "halfway",
+        }
+    }
+}
+
+/// A player waiting on a generation
+struct Wait {
+    player_id: Uuid,
+    events: Arc<EventBus>,
+    /// One line per stage, picked when the wait began
+    lines: [String; 3],
+    started: Instant,
+    delay: Duration,
+    progress: Mutex<Progress>,
+}
+
+#[derive(Default)]
+struct Progress {
+    reached: Option<Stage>,
+    announced: Option<Stage>,
+}
+
+impl Wait {
+    fn reach(&self, stage: Stage) {
+        let mut progress = self.progress.lock().unwrap_or_else(|e| e.into_inner());
+        if progress.reached >= Some(stage) {
+            return;
+        }
+        progress.reached = Some(stage);
+        if self.started.elapsed() >= self.delay {
+            self.announce(&mut progress);
+        }
+    }
+
+    /// Announce the latest stage reached, unless it already was
+    fn announce(&self, progress: &mut Progress) {
+        let Some(stage) = progress.reached else {
+            return;
+        };
+        if progress.announced >= Some(stage) {
+            return;
+        }
+        progress.announced = Some(stage);
+        self.events.publish(GameEvent::Thinking {
+            player_id: self.player_id,
+            stage: stage.name(),
+            text: self.lines[stage as usize].clone(),
+        });
+    }
+
+    fn delay_passed(&self) {
+        let mut progress = self.progress.lock().unwrap_or_else(|e| e.into_inner());
+        self.announce(&mut progress);
+    }
+}
+
+/// Run `work`, a generation `player` is waiting on, sending them thinking
+/// beats as it progresses once it has taken longer than the configured delay
+pub async fn scope<F: Future>(
+    config: &Config,
+    events: &Arc<EventBus>,
+    player: &Player,
+    work: F,
+) -> F::Output {
+    if config.thinking_beat_delay_ms == 0 {
+        return work.await;
+    }
+    let lines = {
+        let mut rng = rand::rng();
+        Stage::ALL.map(|stage| texture::thinking(player, stage, &mut rng))
+    };
+    let wait = Arc::new(Wait {
+        player_id: player.id,
+        events: events.clone(),
+        lines,
+        started: Instant::now(),
+        delay: Duration::from_millis(config.thinking_beat_delay_ms),
+        progress: Mutex::new(Progress::default()),
+    });
+
+    let work = WAIT.scope(wait.clone(), work);
+    tokio::pin!(work);
+    tokio::select! {
+        output = &mut work => return output,
+        _ = tokio::time::sleep(wait.delay) => wait.delay_passed(),
+    }
+    work.await
+}
+
+/// Whether a player is waiting on the current completion, so it is worth
+/// streaming
+pub fn waiting() -> bool {
+    WAIT.try_with(|_| ()).is_ok()
+}
+
+/// Report the current completion reaching `stage`. Outside [`scope`] this
+/// does nothing.
+pub fn report(stage: Stage) {
+    let _ = WAIT.try_with(|wait| wait.reach(stage));
+}
+
+/// Report `tokens` streamed so far in the current completion
+pub fn streamed(tokens: u32) {
+    if tokens == 1 {
+        report(Stage::Streaming);
+    }
+    if tokens >= TYPICAL_TOKENS.load(Ordering::Relaxed).div_ceil(2) {
+        report(Stage::Halfway);
+    }
+}
+
+/// Fold a finished streamed completion into the typical length
+pub fn finished(tokens: u32) {
+    if tokens == 0 {
+        return;
+    }
+    let typical = TYPICAL_TOKENS.load(Ordering::Relaxed);
+    TYPICAL_TOKENS.store((typical * 3 + tokens) / 4, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests;
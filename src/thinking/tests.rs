@@ -0,0 +1,89 @@
+use super::*;
+use tokio::sync::broadcast;
+
+use crate::events::Envelope;
+
+fn config(delay_ms: u64) -> Config {
+    let mut config = Config::for_tests("http://localhost:1/v1");
+    config.thinking_beat_delay_ms = delay_ms;
+    config
+}
+
+/// Stages of the thinking beats published so far
+fn stages(events: &mut broadcast::Receiver<Arc<Envelope>>) -> Vec<&'static str> {
+    let mut stages = Vec::new();
+    while let Ok(envelope) = events.try_recv() {
+        if let GameEvent::Thinking { stage, text, .. } = &envelope.event {
+            assert!(!text.is_empty());
+            stages.push(*stage);
+        }
+    }
+    stages
+}
+
+#[tokio::test]
+async fn quick_generations_stay_silent() {
+    let bus = Arc::new(EventBus::new(16));
+    let mut events = bus.subscribe();
+    let player = Player::new();
+
+    let answer = scope(&config(60_000), &bus, &player, async {
+        assert!(waiting());
+        report(Stage::Sent);
+        streamed(1);
+        42
+    })
+    .await;
+    assert_eq!(answer, 42);
+    assert!(stages(&mut events).is_empty());
+
+    // Off, and outside any wait, reports go nowhere
+    scope(&config(0), &bus, &player, async {
+        assert!(!waiting());
+        report(Stage::Sent);
+    })
+    .await;
+    report(Stage::Halfway);
+    assert!(stages(&mut events).is_empty());
+}
+
+#[tokio::test]
+async fn a_slow_generation_hears_the_latest_stage_then_each_new_one() {
+    let bus = Arc::new(EventBus::new(16));
+    let mut events = bus.subscribe();
+    let player = Player::new();
+
+    scope(&config(20), &bus, &player, async {
+        report(Stage::Sent);
+        streamed(1);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        // The request went out before the delay; only the stream is announced
+        report(Stage::Sent);
+        streamed(2);
+        streamed(10_000);
+        streamed(10_001);
+    })
+    .await;
+    assert_eq!(stages(&mut events), vec!["streaming", "halfway"]);
+}
+
+#[tokio::test]
+async fn a_stalled_request_is_announced_when_the_delay_passes() {
+    let bus = Arc::new(EventBus::new(16));
+    let mut events = bus.subscribe();
+    let player = Player::new();
+
+    scope(&config(20), &bus, &player, async {
+        report(Stage::Sent);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+    })
+    .await;
+    let envelope = events.try_recv().unwrap();
+    let GameEvent::Thinking {
+        player_id, stage, ..
+    } = &envelope.event
+    else {
+        panic!("expected a thinking beat, got {:?}", envelope.event.kind());
+    };
+    assert_eq!((*player_id, *stage), (player.id, "sent"));
+}
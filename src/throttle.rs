@@ -0,0 +1,113 @@
+//! One answer for every "not now": per-player rate limits, the generation
+//! queue, the monthly LLM budget and the upstream circuit breaker all turn
+//! requests away with a [`Throttled`] error saying why, for whom and for how
+//! long, so clients need a single backoff path.
+
+use axum::Json;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Why a request was turned away
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// The player used up a per-minute allowance
+    RateLimit,
+    /// The player already has a generation waiting in line
+    AlreadyQueued,
+    /// The generation queue is at `GENERATION_QUEUE_SIZE`
+    QueueFull,
+    /// The month's spend reached `LLM_MONTHLY_BUDGET`
+    BudgetExhausted,
+    /// Every LLM upstream is failing and sitting out its cooldown
+    CircuitOpen,
+    /// The LLM provider itself is rate limiting the server
+    UpstreamRateLimited,
+}
+
+impl Reason {
+    pub fn name(self) -> &'static str {
+        match self {
+            Reason::RateLimit => "rate_limit",
+            Reason::AlreadyQueued => "already_queued",
+            Reason::QueueFull => "queue_full",
+            Reason::BudgetExhausted => "budget_exhausted",
+            Reason::CircuitOpen => "circuit_open",
+            Reason::UpstreamRateLimited => "upstream_rate_limited",
+        }
+    }
+
+    /// Who the throttle applies to
+    pub fn scope(self) -> Scope {
+        match self {
+            Reason::RateLimit | Reason::AlreadyQueued => Scope::Player,
+            Reason::QueueFull
+            | Reason::BudgetExhausted
+            | Reason::CircuitOpen
+            | Reason::UpstreamRateLimited => Scope::Global,
+        }
+    }
+}
+
+/// Who a throttle applies to: the one player, or everyone on the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Player,
+    Global,
+}
+
+/// A request turned away for now
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Throttled {
+    pub reason: Reason,
+    pub scope: Scope,
+    /// Whole seconds to wait before trying again; at least 1
+    pub retry_after_secs: u64,
+}
+
+impl Throttled {
+    pub fn new(reason: Reason, retry_after: Duration) -> Self {
+        Self {
+            reason,
+            scope: reason.scope(),
+            retry_after_secs: retry_after.as_secs_f64().ceil().max(1.0) as u64,
+        }
+    }
+
+    /// `429` for the player's own limits, `503` when the server can't take
+    /// anyone's request
+    pub fn status(&self) -> StatusCode {
+        match self.scope {
+            Scope::Player => StatusCode::TOO_MANY_REQUESTS,
+            Scope::Global => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Body<'a> {
+    error: &'static str,
+    #[serde(flatten)]
+    throttled: &'a Throttled,
+}
+
+impl IntoResponse for Throttled {
+    fn into_response(self) -> Response {
+        let body = Body {
+            error: "throttled",
+            throttled: &self,
+        };
+        (
+            self.status(),
+            [(header::RETRY_AFTER, self.retry_after_secs.to_string())],
+            Json(&body),
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,36 @@
+use super::*;
+use axum::body::to_bytes;
+use serde_json::json;
+
+async fn parts(throttled: Throttled) -> (StatusCode, Option<String>, serde_json::Value) {
+    let response = throttled.into_response();
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .map(|v| v.to_str().unwrap().to_string());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, retry_after, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn throttles_carry_their_wait_in_header_and_body() {
+    let (status, retry_after, body) = parts(Throttled::new(
+        Reason::RateLimit,
+        Duration::from_millis(12_300),
+    ))
+    .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(retry_after.as_deref(), Some("13"));
+    assert_eq!(
+        body,
+        json!({ "error": "throttled", "reason": "rate_limit", "scope": "player", "retry_after_secs": 13 })
+    );
+
+    // A server-wide throttle is a 503, and never asks for less than a second
+    let (status, retry_after, body) =
+        parts(Throttled::new(Reason::CircuitOpen, Duration::ZERO)).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(retry_after.as_deref(), Some("1"));
+    assert_eq!(body["scope"], "global");
+}
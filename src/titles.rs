@@ -0,0 +1,79 @@
+//! Titles the narrator gives runs.
+//!
+//! When a run's first loop ends, the narrator reads its recap and names the
+//! run, e.g. "The Winter of Walking Away". The title shows in save listings
+//! and exports until the player re-rolls it or names the run themselves.
+
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::events::{EventBus, GameEvent};
+use crate::game::{ArchivedLoop, GameState, Player};
+use crate::llm::LlmClient;
+use crate::persistence;
+
+/// Loop 1 of a run, once it has ended
+pub fn first_loop(run_id: &Uuid) -> Result<Option<ArchivedLoop>> {
+    Ok(persistence::load_archived_loops(run_id)?
+        .into_iter()
+        .find(|archived| archived.loop_info.number == 1))
+}
+
+/// Generate a title for the run and store it. `previous` is the title being
+/// re-rolled, if any. Returns `None` when the run is gone or its first loop
+/// hasn't ended.
+pub async fn title_run(
+    game: &RwLock<GameState>,
+    llm: &LlmClient,
+    player: &Player,
+    run_id: Uuid,
+    previous: Option<&str>,
+) -> Result<Option<String>> {
+    let Some(first) = first_loop(&run_id)? else {
+        return Ok(None);
+    };
+    let title = llm.generate_run_title(player, &first, previous).await?;
+
+    let mut game = game.write().await;
+    let Some(player) = game.get_player_mut(&player.id) else {
+        return Ok(None);
+    };
+    let Some(run) = player.run_mut(run_id) else {
+        return Ok(None);
+    };
+    run.title = Some(title.clone());
+    if let Err(e) = persistence::save_player(player) {
+        tracing::warn!("Failed to save run title: {}", e);
+    }
+    Ok(Some(title))
+}
+
+/// Title each run as its first loop ends
+pub fn subscribe(events: &EventBus, game: Arc<RwLock<GameState>>, llm: Arc<LlmClient>) {
+    events.spawn_subscriber("run_titles", move |envelope| {
+        let game = game.clone();
+        let llm = llm.clone();
+        async move {
+            // The loop number is the one just begun
+            let GameEvent::LoopReset {
+                player_id,
+                loop_number: 2,
+                ..
+            } = &envelope.event
+            else {
+                return;
+            };
+            let Some(player) = game.read().await.get_player(player_id).cloned() else {
+                return;
+            };
+            if player.run.title.is_some() || player.abuse.is_ghosted() {
+                return;
+            }
+            if let Err(e) = title_run(&game, &llm, &player, player.run_id(), None).await {
+                tracing::warn!("Failed to title run of player {}: {}", player_id, e);
+            }
+        }
+    });
+}
@@ -0,0 +1,222 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Failed requests in a row that take an upstream out of rotation
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+/// How long an upstream stays out of rotation before it is tried again
+pub const COOLDOWN: Duration = Duration::from_secs(30);
+/// Weight of the newest sample in the latency and error rate averages
+const EWMA_ALPHA: f64 = 0.2;
+/// Latency assumed for an upstream that has not answered yet
+const UNKNOWN_LATENCY_MS: f64 = 1000.0;
+
+#[derive(Default)]
+struct Health {
+    /// Exponentially weighted average time to response headers
+    latency_ms: Option<f64>,
+    /// Exponentially weighted share of failed requests, from 0 to 1
+    error_rate: f64,
+    consecutive_failures: u32,
+    down_until: Option<Instant>,
+    requests: u64,
+    failures: u64,
+}
+
+impl Health {
+    fn is_down(&self, now: Instant) -> bool {
+        self.down_until.is_some_and(|until| until > now)
+    }
+
+    /// Routing weight: faster and more reliable upstreams get more traffic
+    fn weight(&self) -> f64 {
+        let latency = self.latency_ms.unwrap_or(UNKNOWN_LATENCY_MS).max(1.0);
+        (1.0 - self.error_rate).max(0.05) / latency
+    }
+}
+
+/// One base URL serving the configured LLM provider
+pub struct Upstream {
+    pub base_url: String,
+    health: Mutex<Health>,
+}
+
+impl Upstream {
+    fn health(&self) -> std::sync::MutexGuard<'_, Health> {
+        self.health.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Redundant base URLs of the same provider, routed by health.
+///
+/// Each request goes to an upstream picked at random, weighted by its latency
+/// and error rate, and fails over to the others in order of weight. Upstreams
+/// that keep failing sit out a cooldown, then get traffic again.
+pub struct Upstreams {
+    upstreams: Vec<Upstream>,
+}
+
+impl Upstreams {
+    pub fn new(base_urls: &[String]) -> Self {
+        Self {
+            upstreams: base_urls
+                .iter()
+                .map(|base_url| Upstream {
+                    base_url: base_url.clone(),
+                    health: Mutex::new(Health::default()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Upstreams in the order to try them for one request. Upstreams sitting
+    /// out a cooldown come last, so a request is still attempted when every
+    /// upstream is down.
+    pub fn route(&self) -> Vec<&Upstream> {
+        let now = Instant::now();
+        let (mut available, mut down): (Vec<_>, Vec<_>) = self
+            .upstreams
+            .iter()
+            .map(|u| {
+                let health = u.health();
+                (u, health.weight(), health.is_down(now), health.down_until)
+            })
+            .partition(|(_, _, is_down, _)| !is_down);
+        available.sort_by(|a, b| b.1.total_cmp(&a.1));
+        down.sort_by_key(|(_, _, _, until)| *until);
+
+        if available.len() > 1 {
+            let total: f64 = available.iter().map(|(_, weight, _, _)| weight).sum();
+            let mut pick = rand::random::<f64>() * total;
+            let chosen = available
+                .iter()
+                .position(|(_, weight, _, _)| {
+                    pick -= weight;
+                    pick <= 0.0
+                })
+                .unwrap_or(0);
+            let first = available.remove(chosen);
+            available.insert(0, first);
+        }
+        available.into_iter().chain(down).map(|(u, _, _, _)| u).collect()
+    }
+
+    /// Whether every upstream is sitting out a cooldown
+    pub fn all_down(&self) -> bool {
+        let now = Instant::now();
+        !self.upstreams.is_empty() && self.upstreams.iter().all(|u| u.health().is_down(now))
+    }
+
+    /// Time until the first upstream's cooldown ends, while every one is down
+    pub fn retry_after(&self) -> Option<Duration> {
+        if !self.all_down() {
+            return None;
+        }
+        let now = Instant::now();
+        self.upstreams
+            .iter()
+            .filter_map(|u| u.health().down_until)
+            .min()
+            .map(|until| until.saturating_duration_since(now))
+    }
+
+    pub fn record_success(&self, upstream: &Upstream, latency: Duration) {
+        let mut health = upstream.health();
+        let latency = latency.as_secs_f64() * 1000.0;
+        health.latency_ms = Some(match health.latency_ms {
+            Some(average) => average + EWMA_ALPHA * (latency - average),
+            None => latency,
+        });
+        health.error_rate -= EWMA_ALPHA * health.error_rate;
+        health.consecutive_failures = 0;
+        health.down_until = None;
+        health.requests += 1;
+    }
+
+    pub fn record_failure(&self, upstream: &Upstream) {
+        let mut health = upstream.health();
+        health.error_rate += EWMA_ALPHA * (1.0 - health.error_rate);
+        health.consecutive_failures += 1;
+        health.requests += 1;
+        health.failures += 1;
+        if health.consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+            if !health.is_down(Instant::now()) {
+                tracing::warn!(
+                    "LLM upstream {} failed {} times in a row; out of rotation for {}s",
+                    upstream.base_url,
+                    health.consecutive_failures,
+                    COOLDOWN.as_secs()
+                );
+            }
+            health.down_until = Some(Instant::now() + COOLDOWN);
+        }
+    }
+
+    pub fn write_metrics(&self, out: &mut String) {
+        let now = Instant::now();
+        let snapshot: Vec<_> = self
+            .upstreams
+            .iter()
+            .map(|u| {
+                let health = u.health();
+                (
+                    &u.base_url,
+                    health.latency_ms,
+                    health.error_rate,
+                    !health.is_down(now),
+                    health.requests,
+                    health.failures,
+                )
+            })
+            .collect();
+
+        out.push_str("# HELP nihilism_llm_upstream_up Whether an LLM upstream is in rotation\n");
+        out.push_str("# TYPE nihilism_llm_upstream_up gauge\n");
+        for (url, _, _, up, _, _) in &snapshot {
+            out.push_str(&format!(
+                "nihilism_llm_upstream_up{{upstream=\"{}\"}} {}\n",
+                url,
+                u8::from(*up)
+            ));
+        }
+        out.push_str("# HELP nihilism_llm_upstream_latency_ms Average time to response headers\n");
+        out.push_str("# TYPE nihilism_llm_upstream_latency_ms gauge\n");
+        for (url, latency, _, _, _, _) in &snapshot {
+            if let Some(latency) = latency {
+                out.push_str(&format!(
+                    "nihilism_llm_upstream_latency_ms{{upstream=\"{}\"}} {:.1}\n",
+                    url, latency
+                ));
+            }
+        }
+        out.push_str("# HELP nihilism_llm_upstream_error_rate Recent share of failed requests\n");
+        out.push_str("# TYPE nihilism_llm_upstream_error_rate gauge\n");
+        for (url, _, error_rate, _, _, _) in &snapshot {
+            out.push_str(&format!(
+                "nihilism_llm_upstream_error_rate{{upstream=\"{}\"}} {:.3}\n",
+                url, error_rate
+            ));
+        }
+        out.push_str("# HELP nihilism_llm_upstream_requests_total Requests sent to an LLM upstream\n");
+        out.push_str("# TYPE nihilism_llm_upstream_requests_total counter\n");
+        for (url, _, _, _, requests, failures) in &snapshot {
+            out.push_str(&format!(
+                "nihilism_llm_upstream_requests_total{{upstream=\"{}\",outcome=\"ok\"}} {}\n",
+                url,
+                requests - failures
+            ));
+            out.push_str(&format!(
+                "nihilism_llm_upstream_requests_total{{upstream=\"{}\",outcome=\"failed\"}} {}\n",
+                url, failures
+            ));
+        }
+    }
+}
+
+/// Statuses that blame the upstream rather than the request, so the next
+/// upstream is tried
+pub fn is_upstream_failure(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,79 @@
+use super::*;
+
+fn pool() -> Upstreams {
+    Upstreams::new(&["http://a/v1".to_string(), "http://b/v1".to_string()])
+}
+
+#[test]
+fn failing_upstream_leaves_rotation_after_repeated_failures() {
+    let upstreams = pool();
+    let a = &upstreams.upstreams[0];
+
+    for _ in 0..MAX_CONSECUTIVE_FAILURES {
+        assert_eq!(upstreams.route().len(), 2);
+        upstreams.record_failure(a);
+    }
+
+    let route = upstreams.route();
+    assert_eq!(route[0].base_url, "http://b/v1");
+    // Still tried last, in case the other one is down too
+    assert_eq!(route[1].base_url, "http://a/v1");
+}
+
+#[test]
+fn success_brings_an_upstream_back() {
+    let upstreams = pool();
+    let a = &upstreams.upstreams[0];
+    for _ in 0..MAX_CONSECUTIVE_FAILURES {
+        upstreams.record_failure(a);
+    }
+
+    upstreams.record_success(a, Duration::from_millis(50));
+
+    assert!(!a.health().is_down(Instant::now()));
+    assert_eq!(a.health().consecutive_failures, 0);
+}
+
+#[test]
+fn the_pool_is_down_only_when_every_upstream_is() {
+    let upstreams = pool();
+    for upstream in &upstreams.upstreams {
+        assert!(!upstreams.all_down());
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            upstreams.record_failure(upstream);
+        }
+    }
+    assert!(upstreams.all_down());
+    assert!(!Upstreams::new(&[]).all_down());
+}
+
+#[test]
+fn faster_and_healthier_upstreams_weigh_more() {
+    let upstreams = pool();
+    let (a, b) = (&upstreams.upstreams[0], &upstreams.upstreams[1]);
+    upstreams.record_success(a, Duration::from_millis(100));
+    upstreams.record_success(b, Duration::from_millis(400));
+    assert!(a.health().weight() > b.health().weight());
+
+    upstreams.record_failure(a);
+    upstreams.record_failure(a);
+    let after_failures = a.health().weight();
+    upstreams.record_success(a, Duration::from_millis(100));
+    assert!(a.health().weight() > after_failures);
+}
+
+#[test]
+fn metrics_report_every_upstream() {
+    let upstreams = pool();
+    upstreams.record_success(&upstreams.upstreams[0], Duration::from_millis(120));
+    upstreams.record_failure(&upstreams.upstreams[1]);
+
+    let mut out = String::new();
+    upstreams.write_metrics(&mut out);
+
+    assert!(out.contains("nihilism_llm_upstream_latency_ms{upstream=\"http://a/v1\"} 120.0"));
+    assert!(out.contains(
+        "nihilism_llm_upstream_requests_total{upstream=\"http://b/v1\",outcome=\"failed\"} 1"
+    ));
+    assert!(out.contains("nihilism_llm_upstream_up{upstream=\"http://b/v1\"} 1"));
+}
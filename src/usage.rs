@@ -0,0 +1,355 @@
+use anyhow::Result;
+use chrono::{Datelike, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::{Config, ModelPrice};
+use crate::tenant;
+
+const USAGE_DIR: &str = "data/usage";
+
+/// Token counts reported by the LLM backend
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    fn add(&mut self, other: &TokenUsage) {
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// LLM calls are refused once the month's spend reaches `LLM_MONTHLY_BUDGET`
+#[derive(Debug, thiserror::Error)]
+#[error("monthly LLM budget of ${0:.2} exhausted")]
+pub struct BudgetExceeded(pub f64);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct LedgerEntry {
+    date: NaiveDate,
+    model: String,
+    player_id: Option<Uuid>,
+    #[serde(flatten)]
+    usage: TokenUsage,
+}
+
+type LedgerKey = (NaiveDate, String, Option<Uuid>);
+
+struct Ledger {
+    /// `data/usage`, or the tenant's own
+    dir: PathBuf,
+    month: String,
+    entries: HashMap<LedgerKey, TokenUsage>,
+    dirty: bool,
+}
+
+/// Usage and cost of one model, day or player
+#[derive(Clone, Debug, Serialize)]
+pub struct CostLine {
+    pub key: String,
+    #[serde(flatten)]
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
+    /// False when some of the usage is for a model missing from `LLM_PRICING`
+    pub priced: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CostReport {
+    pub month: String,
+    pub total_cost_usd: f64,
+    pub budget_usd: Option<f64>,
+    pub budget_exhausted: bool,
+    pub by_model: Vec<CostLine>,
+    pub by_day: Vec<CostLine>,
+    pub by_player: Vec<CostLine>,
+}
+
+/// Accumulates token usage per day, model and player for the current month.
+///
+/// The ledger is kept in memory and flushed to `data/usage/{YYYY-MM}.json`
+/// by the `usage_flush` job and on shutdown.
+pub struct UsageTracker {
+    pricing: HashMap<String, ModelPrice>,
+    budget: Option<f64>,
+    ledger: Mutex<Ledger>,
+}
+
+fn current_month() -> String {
+    Utc::now().format("%Y-%m").to_string()
+}
+
+/// Time until the next month starts, when an exhausted budget resets
+pub fn until_next_month() -> Duration {
+    let now = Utc::now();
+    now.date_naive()
+        .with_day(1)
+        .and_then(|first| first.checked_add_months(Months::new(1)))
+        .and_then(|next| (next.and_time(chrono::NaiveTime::MIN).and_utc() - now).to_std().ok())
+        .unwrap_or_default()
+}
+
+fn ledger_path(dir: &Path, month: &str) -> PathBuf {
+    dir.join(format!("{}.json", month))
+}
+
+fn load_ledger(dir: &Path, month: &str) -> Result<HashMap<LedgerKey, TokenUsage>> {
+    let path = ledger_path(dir, month);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let entries: Vec<LedgerEntry> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(entries
+        .into_iter()
+        .map(|e| ((e.date, e.model, e.player_id), e.usage))
+        .collect())
+}
+
+impl UsageTracker {
+    pub fn new(config: &Config) -> Self {
+        let dir = tenant::data_path(config.tenant.as_deref(), USAGE_DIR);
+        let month = current_month();
+        let entries = load_ledger(&dir, &month).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load usage ledger for {}: {}", month, e);
+            HashMap::new()
+        });
+        Self {
+            pricing: config.llm_pricing.clone(),
+            budget: config.llm_monthly_budget,
+            ledger: Mutex::new(Ledger {
+                dir,
+                month,
+                entries,
+                dirty: false,
+            }),
+        }
+    }
+
+    fn ledger(&self) -> std::sync::MutexGuard<'_, Ledger> {
+        self.ledger.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add one completion's usage to today's ledger
+    pub fn record(&self, model: &str, player_id: Option<Uuid>, usage: TokenUsage) {
+        let mut ledger = self.ledger();
+        let month = current_month();
+        if ledger.month != month {
+            if let Err(e) = write_ledger(&ledger) {
+                tracing::warn!("Failed to flush usage ledger for {}: {}", ledger.month, e);
+            }
+            *ledger = Ledger {
+                dir: ledger.dir.clone(),
+                month,
+                entries: HashMap::new(),
+                dirty: false,
+            };
+        }
+        ledger
+            .entries
+            .entry((Utc::now().date_naive(), model.to_string(), player_id))
+            .or_default()
+            .add(&usage);
+        ledger.dirty = true;
+    }
+
+    /// Cost in USD, or None when the model has no configured price
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        let price = self.pricing.get(model)?;
+        Some(
+            usage.prompt_tokens as f64 / 1000.0 * price.prompt_per_1k
+                + usage.completion_tokens as f64 / 1000.0 * price.completion_per_1k,
+        )
+    }
+
+    fn month_cost(&self, ledger: &Ledger) -> f64 {
+        ledger
+            .entries
+            .iter()
+            .filter_map(|((_, model, _), usage)| self.cost(model, usage))
+            .sum()
+    }
+
+    /// Share of the monthly budget spent so far, if there is a budget
+    pub fn budget_share(&self) -> Option<f64> {
+        let budget = self.budget.filter(|b| *b > 0.0)?;
+        let ledger = self.ledger();
+        if ledger.month != current_month() {
+            return Some(0.0);
+        }
+        Some(self.month_cost(&ledger) / budget)
+    }
+
+    /// Fail fast when the month's spend has reached the budget
+    pub fn check_budget(&self) -> Result<(), BudgetExceeded> {
+        let Some(budget) = self.budget else {
+            return Ok(());
+        };
+        let ledger = self.ledger();
+        if ledger.month == current_month() && self.month_cost(&ledger) >= budget {
+            return Err(BudgetExceeded(budget));
+        }
+        Ok(())
+    }
+
+    /// Cost summaries for the current month
+    pub fn report(&self) -> CostReport {
+        let ledger = self.ledger();
+        let mut by_model: HashMap<String, CostLine> = HashMap::new();
+        let mut by_day: HashMap<String, CostLine> = HashMap::new();
+        let mut by_player: HashMap<String, CostLine> = HashMap::new();
+
+        for ((date, model, player_id), usage) in &ledger.entries {
+            let cost = self.cost(model, usage);
+            let player = player_id
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "system".to_string());
+            for (lines, key) in [
+                (&mut by_model, model.clone()),
+                (&mut by_day, date.to_string()),
+                (&mut by_player, player),
+            ] {
+                let line = lines.entry(key.clone()).or_insert_with(|| CostLine {
+                    key,
+                    usage: TokenUsage::default(),
+                    cost_usd: 0.0,
+                    priced: true,
+                });
+                line.usage.add(usage);
+                line.cost_usd += cost.unwrap_or(0.0);
+                line.priced &= cost.is_some();
+            }
+        }
+
+        let sorted = |lines: HashMap<String, CostLine>| {
+            let mut lines: Vec<CostLine> = lines.into_values().collect();
+            lines.sort_by(|a, b| a.key.cmp(&b.key));
+            lines
+        };
+        let total_cost_usd = self.month_cost(&ledger);
+        CostReport {
+            month: ledger.month.clone(),
+            total_cost_usd,
+            budget_usd: self.budget,
+            budget_exhausted: self.budget.is_some_and(|b| total_cost_usd >= b),
+            by_model: sorted(by_model),
+            by_day: sorted(by_day),
+            by_player: sorted(by_player),
+        }
+    }
+
+    /// Usage and cost of one day, read from disk if the day is in an earlier month
+    pub fn day(&self, date: NaiveDate) -> CostLine {
+        let month = date.format("%Y-%m").to_string();
+        let ledger = self.ledger();
+        let on_disk;
+        let entries = if ledger.month == month {
+            &ledger.entries
+        } else {
+            on_disk = load_ledger(&ledger.dir, &month).unwrap_or_else(|e| {
+                tracing::warn!("Failed to load usage ledger for {}: {}", month, e);
+                HashMap::new()
+            });
+            &on_disk
+        };
+        let mut line = CostLine {
+            key: date.to_string(),
+            usage: TokenUsage::default(),
+            cost_usd: 0.0,
+            priced: true,
+        };
+        for ((_, model, _), usage) in entries.iter().filter(|((d, _, _), _)| *d == date) {
+            let cost = self.cost(model, usage);
+            line.usage.add(usage);
+            line.cost_usd += cost.unwrap_or(0.0);
+            line.priced &= cost.is_some();
+        }
+        line
+    }
+
+    /// Append Prometheus metrics for the current month to `out`
+    pub fn write_metrics(&self, out: &mut String) {
+        let report = self.report();
+        let _ = writeln!(out, "# HELP nihilism_llm_requests LLM requests this month");
+        let _ = writeln!(out, "# TYPE nihilism_llm_requests gauge");
+        for line in &report.by_model {
+            let _ = writeln!(
+                out,
+                "nihilism_llm_requests{{model=\"{}\"}} {}",
+                line.key, line.usage.requests
+            );
+        }
+        let _ = writeln!(out, "# HELP nihilism_llm_tokens LLM tokens used this month");
+        let _ = writeln!(out, "# TYPE nihilism_llm_tokens gauge");
+        for line in &report.by_model {
+            let _ = writeln!(
+                out,
+                "nihilism_llm_tokens{{model=\"{}\",kind=\"prompt\"}} {}",
+                line.key, line.usage.prompt_tokens
+            );
+            let _ = writeln!(
+                out,
+                "nihilism_llm_tokens{{model=\"{}\",kind=\"completion\"}} {}",
+                line.key, line.usage.completion_tokens
+            );
+        }
+        let _ = writeln!(out, "# HELP nihilism_llm_cost_usd Estimated LLM cost this month");
+        let _ = writeln!(out, "# TYPE nihilism_llm_cost_usd gauge");
+        for line in &report.by_model {
+            let _ = writeln!(
+                out,
+                "nihilism_llm_cost_usd{{model=\"{}\"}} {}",
+                line.key, line.cost_usd
+            );
+        }
+        if let Some(budget) = report.budget_usd {
+            let _ = writeln!(out, "# HELP nihilism_llm_budget_usd Monthly LLM budget");
+            let _ = writeln!(out, "# TYPE nihilism_llm_budget_usd gauge");
+            let _ = writeln!(out, "nihilism_llm_budget_usd {}", budget);
+            let _ = writeln!(out, "# TYPE nihilism_llm_budget_exhausted gauge");
+            let _ = writeln!(
+                out,
+                "nihilism_llm_budget_exhausted {}",
+                report.budget_exhausted as u8
+            );
+        }
+    }
+
+    /// Write the ledger to disk if it changed since the last flush
+    pub fn flush(&self) -> Result<()> {
+        let mut ledger = self.ledger();
+        if !ledger.dirty {
+            return Ok(());
+        }
+        write_ledger(&ledger)?;
+        ledger.dirty = false;
+        Ok(())
+    }
+}
+
+fn write_ledger(ledger: &Ledger) -> Result<()> {
+    let mut entries: Vec<LedgerEntry> = ledger
+        .entries
+        .iter()
+        .map(|((date, model, player_id), usage)| LedgerEntry {
+            date: *date,
+            model: model.clone(),
+            player_id: *player_id,
+            usage: *usage,
+        })
+        .collect();
+    entries.sort_by(|a, b| a.date.cmp(&b.date).then(a.model.cmp(&b.model)));
+    fs::create_dir_all(&ledger.dir)?;
+    fs::write(ledger_path(&ledger.dir, &ledger.month), serde_json::to_string_pretty(&entries)?)?;
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::persona::Persona;
+use crate::privacy::ConsentUpdate;
+
+/// A ticket nobody has asked about for this long is treated as abandoned
+const TICKET_TIMEOUT_SECS: i64 = 120;
+/// How long an admitted ticket can still be looked up for its player
+const ADMITTED_TTL_SECS: i64 = 600;
+
+/// A visitor waiting for a free slot
+#[derive(Clone, Debug, Serialize)]
+pub struct Ticket {
+    pub id: Uuid,
+    pub persona: Persona,
+    /// Consent the visitor gave, applied to their player once admitted
+    #[serde(skip)]
+    pub consent: Option<ConsentUpdate>,
+    pub joined_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Where a ticket stands
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TicketStatus {
+    /// `position` 1 is next in line
+    Waiting { position: usize },
+    Admitted { player_id: Uuid },
+}
+
+/// One waiting visitor, as shown to admins
+#[derive(Clone, Debug, Serialize)]
+pub struct QueueEntry {
+    pub position: usize,
+    #[serde(flatten)]
+    pub ticket: Ticket,
+}
+
+#[derive(Default)]
+struct Queue {
+    waiting: VecDeque<Ticket>,
+    /// Admitted tickets and when, so a late poll still finds its player
+    admitted: HashMap<Uuid, (Uuid, DateTime<Utc>)>,
+}
+
+#[derive(Default)]
+struct Totals {
+    joined: u64,
+    admitted: u64,
+    abandoned: u64,
+}
+
+/// Visitors queued while the server is at its active player limit, admitted
+/// first come, first served as slots free up
+pub struct WaitingRoom {
+    queue: Mutex<Queue>,
+    totals: Mutex<Totals>,
+    /// Bumped whenever positions change, so open streams can report them
+    changes: watch::Sender<u64>,
+}
+
+impl WaitingRoom {
+    pub fn new() -> Self {
+        Self {
+            queue: Mutex::new(Queue::default()),
+            totals: Mutex::new(Totals::default()),
+            changes: watch::Sender::new(0),
+        }
+    }
+
+    fn queue(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn totals(&self) -> std::sync::MutexGuard<'_, Totals> {
+        self.totals.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn notify(&self) {
+        self.changes.send_modify(|n| *n += 1);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue().waiting.is_empty()
+    }
+
+    /// Add a visitor to the back of the line, returning their ticket and position
+    pub fn enqueue(&self, persona: Persona, consent: Option<ConsentUpdate>) -> (Ticket, usize) {
+        let now = Utc::now();
+        let ticket = Ticket {
+            id: Uuid::new_v4(),
+            persona,
+            consent,
+            joined_at: now,
+            last_seen: now,
+        };
+        let position = {
+            let mut queue = self.queue();
+            queue.waiting.push_back(ticket.clone());
+            queue.waiting.len()
+        };
+        self.totals().joined += 1;
+        (ticket, position)
+    }
+
+    /// Current status of a ticket; asking keeps a waiting ticket alive
+    pub fn status(&self, id: &Uuid) -> Option<TicketStatus> {
+        let mut queue = self.queue();
+        if let Some((player_id, _)) = queue.admitted.get(id) {
+            return Some(TicketStatus::Admitted {
+                player_id: *player_id,
+            });
+        }
+        let index = queue.waiting.iter().position(|t| t.id == *id)?;
+        queue.waiting[index].last_seen = Utc::now();
+        Some(TicketStatus::Waiting { position: index + 1 })
+    }
+
+    /// Drop abandoned tickets and expired admissions, returning how many
+    /// tickets were abandoned
+    pub fn sweep(&self) -> usize {
+        let now = Utc::now();
+        let abandoned = {
+            let mut queue = self.queue();
+            let before = queue.waiting.len();
+            let cutoff = now - Duration::seconds(TICKET_TIMEOUT_SECS);
+            queue.waiting.retain(|t| t.last_seen >= cutoff);
+            let cutoff = now - Duration::seconds(ADMITTED_TTL_SECS);
+            queue.admitted.retain(|_, (_, at)| *at >= cutoff);
+            before - queue.waiting.len()
+        };
+        if abandoned > 0 {
+            self.totals().abandoned += abandoned as u64;
+            self.notify();
+        }
+        abandoned
+    }
+
+    /// Take up to `slots` tickets from the front of the line
+    pub fn take(&self, slots: usize) -> Vec<Ticket> {
+        let mut queue = self.queue();
+        let count = slots.min(queue.waiting.len());
+        queue.waiting.drain(..count).collect()
+    }
+
+    /// Record that a ticket now has a player of its own
+    pub fn admit(&self, ticket: &Ticket, player_id: Uuid) {
+        self.queue()
+            .admitted
+            .insert(ticket.id, (player_id, Utc::now()));
+        self.totals().admitted += 1;
+        self.notify();
+    }
+
+    /// Receiver that changes whenever queue positions move
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.changes.subscribe()
+    }
+
+    /// Everyone still waiting, front of the line first
+    pub fn list(&self) -> Vec<QueueEntry> {
+        self.queue()
+            .waiting
+            .iter()
+            .enumerate()
+            .map(|(index, ticket)| QueueEntry {
+                position: index + 1,
+                ticket: ticket.clone(),
+            })
+            .collect()
+    }
+
+    /// Append waiting room counters in Prometheus text format
+    pub fn write_metrics(&self, out: &mut String) {
+        let waiting = self.queue().waiting.len();
+        let totals = self.totals();
+        out.push_str("# HELP nihilism_waiting_room_size Visitors waiting for a free slot\n");
+        out.push_str("# TYPE nihilism_waiting_room_size gauge\n");
+        out.push_str(&format!("nihilism_waiting_room_size {}\n", waiting));
+        out.push_str("# HELP nihilism_waiting_room_joined_total Visitors placed in the waiting room\n");
+        out.push_str("# TYPE nihilism_waiting_room_joined_total counter\n");
+        out.push_str(&format!("nihilism_waiting_room_joined_total {}\n", totals.joined));
+        out.push_str("# HELP nihilism_waiting_room_admitted_total Waiting visitors given a player\n");
+        out.push_str("# TYPE nihilism_waiting_room_admitted_total counter\n");
+        out.push_str(&format!("nihilism_waiting_room_admitted_total {}\n", totals.admitted));
+        out.push_str("# HELP nihilism_waiting_room_abandoned_total Tickets dropped after going quiet\n");
+        out.push_str("# TYPE nihilism_waiting_room_abandoned_total counter\n");
+        out.push_str(&format!("nihilism_waiting_room_abandoned_total {}\n", totals.abandoned));
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::persona::Persona;
+use crate::tenant;
+
+const WARMUP_FILE: &str = "data/warmup.json";
+/// Letters and digits that can't be mistaken for one another on a printed card
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 6;
+/// Most players one warm-up may prepare
+pub const MAX_WARMUP: usize = 500;
+
+/// A pre-generated guest player waiting to be claimed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WarmStart {
+    pub code: String,
+    pub player_id: Uuid,
+    pub persona: Persona,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Totals {
+    generated: u64,
+    failed: u64,
+    claimed: u64,
+}
+
+/// Unclaimed warm starts, persisted to `data/warmup.json` (or the tenant's
+/// own) so they survive restarts
+pub struct WarmPool {
+    path: PathBuf,
+    starts: Mutex<HashMap<String, WarmStart>>,
+    totals: Mutex<Totals>,
+}
+
+/// Normalize a claim code as typed by a visitor
+pub fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn random_code() -> String {
+    let mut rng = rand::rng();
+    (0..CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rng.random_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+impl WarmPool {
+    pub fn load(config: &Config) -> Result<Self> {
+        let path = tenant::data_path(config.tenant.as_deref(), WARMUP_FILE);
+        let starts = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path,
+            starts: Mutex::new(starts),
+            totals: Mutex::new(Totals::default()),
+        })
+    }
+
+    fn starts(&self) -> std::sync::MutexGuard<'_, HashMap<String, WarmStart>> {
+        self.starts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn totals(&self) -> std::sync::MutexGuard<'_, Totals> {
+        self.totals.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, starts: &HashMap<String, WarmStart>) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(starts)?)?;
+        Ok(())
+    }
+
+    /// Make a prepared player claimable under a fresh code
+    pub fn add(&self, player_id: Uuid, persona: Persona) -> Result<WarmStart> {
+        let mut starts = self.starts();
+        let code = std::iter::repeat_with(random_code)
+            .find(|code| !starts.contains_key(code))
+            .expect("code space is far larger than the pool");
+        let start = WarmStart {
+            code: code.clone(),
+            player_id,
+            persona,
+            created_at: Utc::now(),
+        };
+        starts.insert(code, start.clone());
+        self.save(&starts)?;
+        self.totals().generated += 1;
+        Ok(start)
+    }
+
+    /// Count a player that could not be prepared
+    pub fn record_failure(&self) {
+        self.totals().failed += 1;
+    }
+
+    /// Hand out the warm start for a code; each code works once
+    pub fn claim(&self, code: &str) -> Result<Option<WarmStart>> {
+        let mut starts = self.starts();
+        let Some(start) = starts.remove(&normalize_code(code)) else {
+            return Ok(None);
+        };
+        self.save(&starts)?;
+        self.totals().claimed += 1;
+        Ok(Some(start))
+    }
+
+    /// Put a claimed start back, e.g. when its player could not be loaded
+    pub fn restore(&self, start: WarmStart) -> Result<()> {
+        let mut starts = self.starts();
+        starts.insert(start.code.clone(), start);
+        self.save(&starts)?;
+        self.totals().claimed -= 1;
+        Ok(())
+    }
+
+    /// Players still waiting to be claimed
+    pub fn player_ids(&self) -> HashSet<Uuid> {
+        self.starts().values().map(|s| s.player_id).collect()
+    }
+
+    /// Unclaimed starts, oldest first
+    pub fn list(&self) -> Vec<WarmStart> {
+        let mut starts: Vec<WarmStart> = self.starts().values().cloned().collect();
+        starts.sort_by_key(|s| s.created_at);
+        starts
+    }
+
+    /// Append warm-up counters in Prometheus text format
+    pub fn write_metrics(&self, out: &mut String) {
+        let ready = self.starts().len();
+        let totals = self.totals();
+        out.push_str("# HELP nihilism_warmup_ready Pre-generated players waiting to be claimed\n");
+        out.push_str("# TYPE nihilism_warmup_ready gauge\n");
+        out.push_str(&format!("nihilism_warmup_ready {}\n", ready));
+        out.push_str("# HELP nihilism_warmup_generated_total Players pre-generated by warm-ups\n");
+        out.push_str("# TYPE nihilism_warmup_generated_total counter\n");
+        out.push_str(&format!("nihilism_warmup_generated_total {}\n", totals.generated));
+        out.push_str("# HELP nihilism_warmup_failed_total Players a warm-up failed to prepare\n");
+        out.push_str("# TYPE nihilism_warmup_failed_total counter\n");
+        out.push_str(&format!("nihilism_warmup_failed_total {}\n", totals.failed));
+        out.push_str("# HELP nihilism_warmup_claimed_total Warm starts claimed by code\n");
+        out.push_str("# TYPE nihilism_warmup_claimed_total counter\n");
+        out.push_str(&format!("nihilism_warmup_claimed_total {}\n", totals.claimed));
+    }
+}
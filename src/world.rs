@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::game::{NarrativeMoment, Player};
+use crate::plugins;
+use crate::stability;
+
+/// Longest name, cause or truth accepted in an update
+const MAX_FIELD_LEN: usize = 200;
+/// Artifacts the narrator may grant per loop
+const ARTIFACTS_PER_LOOP: usize = 1;
+
+/// A change to the world the narrator proposes alongside a moment
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum WorldUpdate {
+    CharacterDied { character: String, cause: String },
+    CharacterReturned { character: String, cause: String },
+    TruthDiscovered { truth: String },
+    ArtifactFound { artifact: String },
+    PlayerDied { cause: String },
+    /// The player did something the loop can't reconcile
+    Paradox { cause: String },
+    /// The player did something that steadies the loop
+    Grounding { cause: String },
+}
+
+/// Why an update was stripped instead of applied
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rejection {
+    /// Not a known update, or a field missing, empty or too long
+    Schema,
+    /// Only the loop itself may end the player
+    PlayerDeath,
+    /// More artifacts than a loop allows
+    ArtifactLimit,
+    /// A return without a cause, or of someone who had not died
+    UnearnedReturn,
+    /// Vetoed by a plugin
+    Plugin,
+}
+
+impl Rejection {
+    fn label(self) -> &'static str {
+        match self {
+            Rejection::Schema => "schema",
+            Rejection::PlayerDeath => "player_death",
+            Rejection::ArtifactLimit => "artifact_limit",
+            Rejection::UnearnedReturn => "unearned_return",
+            Rejection::Plugin => "plugin",
+        }
+    }
+}
+
+fn valid_field(value: &str) -> bool {
+    !value.trim().is_empty() && value.len() <= MAX_FIELD_LEN
+}
+
+fn parse(value: &serde_json::Value) -> Option<WorldUpdate> {
+    let update: WorldUpdate = serde_json::from_value(value.clone()).ok()?;
+    let fields_ok = match &update {
+        WorldUpdate::CharacterDied { character, cause }
+        | WorldUpdate::CharacterReturned { character, cause } => {
+            valid_field(character) && valid_field(cause)
+        }
+        WorldUpdate::TruthDiscovered { truth } => valid_field(truth),
+        WorldUpdate::ArtifactFound { artifact } => valid_field(artifact),
+        WorldUpdate::Paradox { cause } | WorldUpdate::Grounding { cause } => valid_field(cause),
+        // Rejected by the rules below, but well-formed
+        WorldUpdate::PlayerDied { .. } => true,
+    };
+    fields_ok.then_some(update)
+}
+
+/// Check an update against the rules and apply it to the player
+fn apply_update(player: &mut Player, update: &WorldUpdate) -> Result<(), Rejection> {
+    let current = &mut player.run.current_loop;
+    match update {
+        WorldUpdate::PlayerDied { .. } => return Err(Rejection::PlayerDeath),
+        WorldUpdate::ArtifactFound { artifact } => {
+            if current.artifacts.len() >= ARTIFACTS_PER_LOOP {
+                return Err(Rejection::ArtifactLimit);
+            }
+            current.artifacts.push(artifact.trim().to_string());
+        }
+        WorldUpdate::CharacterReturned { character, .. } => {
+            let Some(i) = current
+                .dead_characters
+                .iter()
+                .position(|c| c.eq_ignore_ascii_case(character.trim()))
+            else {
+                return Err(Rejection::UnearnedReturn);
+            };
+            current.dead_characters.remove(i);
+            stability::character_returned(current);
+        }
+        WorldUpdate::CharacterDied { character, .. } => {
+            let character = character.trim().to_string();
+            let deaths = player
+                .run
+                .memory
+                .character_deaths
+                .entry(character.clone())
+                .or_default();
+            // Dying again and again strains the loop
+            if *deaths > 0 {
+                stability::repeat_death(current, *deaths);
+            }
+            *deaths += 1;
+            current.dead_characters.push(character);
+        }
+        WorldUpdate::Paradox { .. } => stability::paradox(current),
+        WorldUpdate::Grounding { .. } => stability::grounding(current),
+        WorldUpdate::TruthDiscovered { truth } => {
+            let truth = truth.trim().to_string();
+            if !player.run.memory.truths_discovered.contains(&truth) {
+                player.run.memory.truths_discovered.push(truth);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Totals {
+    applied: u64,
+    rejected: HashMap<Rejection, u64>,
+}
+
+/// Validates and applies the world updates the narrator proposes
+#[derive(Default)]
+pub struct WorldRules {
+    totals: Mutex<Totals>,
+}
+
+impl WorldRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a moment's proposed updates, stripping any that break the schema or rules
+    pub fn apply(&self, player: &mut Player, moment: &mut NarrativeMoment) {
+        if moment.world_updates.is_empty() {
+            return;
+        }
+        let (proposed, vetoed) =
+            plugins::host().review_world_updates(player, std::mem::take(&mut moment.world_updates));
+        let mut accepted = Vec::new();
+        let mut totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        if vetoed > 0 {
+            *totals.rejected.entry(Rejection::Plugin).or_default() += vetoed as u64;
+        }
+        for proposed in proposed {
+            let result = parse(&proposed)
+                .ok_or(Rejection::Schema)
+                .and_then(|update| apply_update(player, &update));
+            match result {
+                Ok(()) => {
+                    totals.applied += 1;
+                    accepted.push(proposed);
+                }
+                Err(reason) => {
+                    tracing::warn!(
+                        "Rejected world update for {} ({}): {}",
+                        player.id,
+                        reason.label(),
+                        proposed
+                    );
+                    *totals.rejected.entry(reason).or_default() += 1;
+                }
+            }
+        }
+        moment.world_updates = accepted;
+    }
+
+    /// Append world update counters in Prometheus text format
+    pub fn write_metrics(&self, out: &mut String) {
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        out.push_str("# HELP nihilism_world_updates_applied_total World updates applied\n");
+        out.push_str("# TYPE nihilism_world_updates_applied_total counter\n");
+        out.push_str(&format!("nihilism_world_updates_applied_total {}\n", totals.applied));
+        out.push_str("# HELP nihilism_world_updates_rejected_total World updates stripped, by reason\n");
+        out.push_str("# TYPE nihilism_world_updates_rejected_total counter\n");
+        let mut rejected: Vec<_> = totals.rejected.iter().collect();
+        rejected.sort_by_key(|(reason, _)| reason.label());
+        for (reason, count) in rejected {
+            out.push_str(&format!(
+                "nihilism_world_updates_rejected_total{{reason=\"{}\"}} {}\n",
+                reason.label(),
+                count
+            ));
+        }
+    }
+}
@@ -0,0 +1,584 @@
+use axum::extract::ws::{Message, WebSocket};
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::cancel;
+use crate::config::ContentRating;
+use crate::events::{Envelope, GameEvent};
+use crate::game::LoopEndCause;
+use crate::i18n::{self, Locale, Text};
+use crate::persona::Persona;
+use crate::reveal::{Beat, BeatAck, Reveal};
+use crate::routes::{self, ApiError, AppState, ChoiceRequest, NarrativeResponse, ResetResponse};
+use crate::throttle::Throttled;
+
+/// Frames kept per player for resuming after a dropped connection
+const BACKLOG_FRAMES: usize = 64;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// Connections silent for this long are closed
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(90);
+const TICK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Frames sent by the client
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientFrame {
+    Start,
+    Choice {
+        choice_id: String,
+        choice_text: String,
+        #[serde(default)]
+        moment_id: Option<Uuid>,
+    },
+    /// Free-form player input, treated as a custom choice
+    Say {
+        text: String,
+        #[serde(default)]
+        moment_id: Option<Uuid>,
+    },
+    /// End the loop; `cause` defaults to the player choosing to
+    Reset {
+        #[serde(default)]
+        cause: Option<LoopEndCause>,
+    },
+    Ping,
+    /// The client finished presenting a beat; the next one follows
+    Ack(BeatAck),
+}
+
+/// Frames pushed by the server
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerFrame {
+    Hello {
+        player_id: Uuid,
+        /// Latest sequence number for this player; resume from here after a disconnect
+        last_seq: u64,
+        heartbeat_secs: u64,
+        resumed: usize,
+    },
+    Moment(Box<NarrativeResponse>),
+    Reset(Box<ResetResponse>),
+    /// The next piece of the latest moment, when revealing beat by beat
+    Beat(Beat),
+    Tick {
+        loop_number: u64,
+        elapsed_secs: i64,
+    },
+    Achievement {
+        title: String,
+        description: String,
+    },
+    /// An ambient line while the current moment waits for a choice
+    Texture {
+        text: String,
+        tone: &'static str,
+    },
+    /// A line while the next moment is slow to generate
+    Thinking {
+        stage: &'static str,
+        text: String,
+    },
+    Error {
+        code: u16,
+        message: String,
+    },
+    /// A command turned away for now, and when to try again
+    Throttled(Throttled),
+    Pong,
+}
+
+#[derive(Serialize)]
+struct Sequenced<'a> {
+    seq: u64,
+    #[serde(flatten)]
+    frame: &'a ServerFrame,
+}
+
+#[derive(Default)]
+struct Session {
+    last_seq: u64,
+    backlog: VecDeque<(u64, ServerFrame)>,
+}
+
+/// Per-player sequence numbers and recent frames, shared across reconnects
+#[derive(Default)]
+pub struct WsSessions {
+    sessions: Mutex<HashMap<Uuid, Session>>,
+}
+
+impl WsSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assign the next sequence number and remember the frame for resumes
+    fn record(&self, player_id: Uuid, frame: ServerFrame) -> String {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let session = sessions.entry(player_id).or_default();
+        session.last_seq += 1;
+        let seq = session.last_seq;
+        let text = serde_json::to_string(&Sequenced { seq, frame: &frame })
+            .unwrap_or_else(|_| "{}".to_string());
+        // Heartbeat replies, timer ticks, beats and thinking lines are stale by
+        // the time anyone resumes; the moment frame holds the beats' full text
+        if !matches!(
+            frame,
+            ServerFrame::Pong
+                | ServerFrame::Tick { .. }
+                | ServerFrame::Beat(_)
+                | ServerFrame::Thinking { .. }
+        ) {
+            session.backlog.push_back((seq, frame));
+            if session.backlog.len() > BACKLOG_FRAMES {
+                session.backlog.pop_front();
+            }
+        }
+        text
+    }
+
+    /// Forget sessions of players that are no longer held in memory
+    pub fn evict(&self, keep: impl Fn(&Uuid) -> bool) -> usize {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let before = sessions.len();
+        sessions.retain(|id, _| keep(id));
+        before - sessions.len()
+    }
+
+    fn last_seq(&self, player_id: Uuid) -> u64 {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.get(&player_id).map_or(0, |s| s.last_seq)
+    }
+
+    /// Frames after `since`, or None if some were already dropped from the backlog
+    fn replay(&self, player_id: Uuid, since: u64) -> Option<Vec<String>> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(session) = sessions.get(&player_id) else {
+            return (since == 0).then(Vec::new);
+        };
+        let oldest = session.backlog.front().map_or(session.last_seq + 1, |(seq, _)| *seq);
+        if since > session.last_seq || since + 1 < oldest {
+            return None;
+        }
+        let frames = session
+            .backlog
+            .iter()
+            .filter(|(seq, _)| *seq > since)
+            .map(|(seq, frame)| {
+                serde_json::to_string(&Sequenced { seq: *seq, frame })
+                    .unwrap_or_else(|_| "{}".to_string())
+            })
+            .collect();
+        Some(frames)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WsQuery {
+    /// Last sequence number the client saw; missed frames are replayed
+    resume: Option<u64>,
+    /// Follow each moment with its beats, paced by the client's acks
+    #[serde(default)]
+    reveal: bool,
+}
+
+pub async fn game_socket(
+    ws: axum::extract::WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(player_id): Path<Uuid>,
+    axum::extract::Query(query): axum::extract::Query<WsQuery>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, StatusCode> {
+    if state.game.read().await.get_player(&player_id).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(ws.on_upgrade(move |socket| run_session(socket, state, player_id, query, headers)))
+}
+
+/// `headers` are the upgrade request's, passed on to the HTTP handlers so the
+/// session negotiates content like any other request
+async fn run_session(
+    mut socket: WebSocket,
+    state: AppState,
+    player_id: Uuid,
+    query: WsQuery,
+    headers: HeaderMap,
+) {
+    let mut events = state.events.subscribe();
+    // Stops work started for this session, such as choice ratings, once it closes
+    let session = CancellationToken::new();
+    let _hangup = session.clone().drop_guard();
+    // Messages that arrived while a frame was being handled
+    let mut backlog = VecDeque::new();
+
+    let (replayed, resume_failed) = match query.resume.map(|since| state.ws.replay(player_id, since)) {
+        Some(Some(frames)) => (frames, false),
+        Some(None) => (Vec::new(), true),
+        None => (Vec::new(), false),
+    };
+    let hello = ServerFrame::Hello {
+        player_id,
+        last_seq: state.ws.last_seq(player_id),
+        heartbeat_secs: HEARTBEAT_INTERVAL.as_secs(),
+        resumed: replayed.len(),
+    };
+    // The greeting is not sequenced so it never shifts the resume point
+    let hello = serde_json::to_string(&hello).unwrap_or_default();
+    if send_raw(&mut socket, hello).await.is_err() {
+        return;
+    }
+    for frame in replayed {
+        if send_raw(&mut socket, frame).await.is_err() {
+            return;
+        }
+    }
+    if resume_failed {
+        let frame = ServerFrame::Error {
+            code: StatusCode::GONE.as_u16(),
+            message: "resume point is no longer available; reload the game state".to_string(),
+        };
+        if send(&mut socket, &state, player_id, frame).await.is_err() {
+            return;
+        }
+    }
+
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut ticks = tokio::time::interval(TICK_INTERVAL);
+    let mut last_seen = Instant::now();
+    let ack_timeout = Duration::from_secs(state.config.reveal_ack_timeout_secs);
+    // The moment being revealed and when its current beat stops waiting for an ack
+    let mut reveal: Option<(Reveal, tokio::time::Instant)> = None;
+
+    loop {
+        let beat_due = reveal.as_ref().map(|(_, deadline)| *deadline);
+        tokio::select! {
+            message = recv(&mut socket, &mut backlog) => {
+                let Some(Ok(message)) = message else { break };
+                last_seen = Instant::now();
+                let text = match message {
+                    Message::Text(text) => text,
+                    Message::Close(_) => break,
+                    _ => continue,
+                };
+                let frame = match serde_json::from_str::<ClientFrame>(&text) {
+                    Ok(ClientFrame::Ack(ack)) => {
+                        if reveal.as_ref().is_some_and(|(r, _)| r.acked_by(&ack))
+                            && next_beat(&mut socket, &state, player_id, &mut reveal, ack_timeout)
+                                .await
+                                .is_err()
+                        {
+                            break;
+                        }
+                        continue;
+                    }
+                    Ok(frame) => {
+                        let work = cancel::scope(
+                            session.clone(),
+                            handle_frame(&state, player_id, &headers, frame),
+                        );
+                        let relay = Relay {
+                            events: &mut events,
+                            state: &state,
+                            player_id,
+                            locale: Locale::from_headers(&headers),
+                        };
+                        match until_hangup(&mut socket, &mut backlog, relay, work).await {
+                            Some(frame) => frame,
+                            None => {
+                                tracing::debug!("Socket for {} closed mid-frame", player_id);
+                                break;
+                            }
+                        }
+                    }
+                    Err(e) => ServerFrame::Error {
+                        code: StatusCode::BAD_REQUEST.as_u16(),
+                        message: format!("invalid frame: {}", e),
+                    },
+                };
+                if query.reveal
+                    && let ServerFrame::Moment(response) = &frame
+                {
+                    let moment = Reveal::new(response.moment(), state.config.reveal_beat_chars);
+                    reveal = Some((moment, tokio::time::Instant::now()));
+                }
+                if send(&mut socket, &state, player_id, frame).await.is_err() {
+                    break;
+                }
+                if reveal.as_ref().is_some_and(|(r, _)| !r.started())
+                    && next_beat(&mut socket, &state, player_id, &mut reveal, ack_timeout)
+                        .await
+                        .is_err()
+                {
+                    break;
+                }
+            }
+            _ = tokio::time::sleep_until(beat_due.unwrap_or_else(tokio::time::Instant::now)),
+                if beat_due.is_some() =>
+            {
+                // The client never acked; move on without it
+                if next_beat(&mut socket, &state, player_id, &mut reveal, ack_timeout)
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > HEARTBEAT_TIMEOUT {
+                    tracing::debug!("Closing silent socket for {}", player_id);
+                    break;
+                }
+                if socket.send(Message::Ping(Default::default())).await.is_err() {
+                    break;
+                }
+            }
+            _ = ticks.tick() => {
+                let tick = {
+                    let game = state.game.read().await;
+                    game.get_player(&player_id).filter(|p| !p.is_locked()).map(|p| ServerFrame::Tick {
+                        loop_number: p.run.current_loop.number,
+                        elapsed_secs: (Utc::now() - p.run.current_loop.started_at).num_seconds(),
+                    })
+                };
+                if let Some(tick) = tick
+                    && send(&mut socket, &state, player_id, tick).await.is_err()
+                {
+                    break;
+                }
+            }
+            event = events.recv() => {
+                let envelope = match event {
+                    Ok(envelope) => envelope,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if envelope.event.player_id() != player_id {
+                    continue;
+                }
+                let locale = Locale::from_headers(&headers);
+                for frame in event_frames(&envelope.event, state.config.content_rating, locale) {
+                    if send(&mut socket, &state, player_id, frame).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The next message from the client, starting with any it sent while a frame
+/// was being handled
+async fn recv(
+    socket: &mut WebSocket,
+    backlog: &mut VecDeque<Message>,
+) -> Option<Result<Message, axum::Error>> {
+    match backlog.pop_front() {
+        Some(message) => Some(Ok(message)),
+        None => socket.recv().await,
+    }
+}
+
+/// Run `work` while listening for the client hanging up, dropping it (and
+/// the LLM call it is waiting on) if they do. Other messages are kept in
+/// `backlog` for the session.
+async fn until_hangup<T>(
+    socket: &mut WebSocket,
+    backlog: &mut VecDeque<Message>,
+    relay: Relay<'_>,
+    work: impl Future<Output = T>,
+) -> Option<T> {
+    let mut work = std::pin::pin!(work);
+    loop {
+        tokio::select! {
+            output = &mut work => return Some(output),
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return None,
+                Some(Ok(message)) => backlog.push_back(message),
+            },
+            event = relay.events.recv() => {
+                let envelope = match event {
+                    Ok(envelope) => envelope,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                };
+                if envelope.event.player_id() != relay.player_id {
+                    continue;
+                }
+                let rating = relay.state.config.content_rating;
+                for frame in event_frames(&envelope.event, rating, relay.locale) {
+                    if send(socket, relay.state, relay.player_id, frame).await.is_err() {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Where events published while a frame is handled go, so the player hears
+/// thinking beats during a slow generation
+struct Relay<'a> {
+    events: &'a mut broadcast::Receiver<Arc<Envelope>>,
+    state: &'a AppState,
+    player_id: Uuid,
+    locale: Locale,
+}
+
+/// Run a client command through the same handlers as the HTTP API
+async fn handle_frame(
+    state: &AppState,
+    player_id: Uuid,
+    headers: &HeaderMap,
+    frame: ClientFrame,
+) -> ServerFrame {
+    let result = match frame {
+        ClientFrame::Ping => return ServerFrame::Pong,
+        // Acks are handled by the session, which knows what is being revealed
+        ClientFrame::Ack(_) => return ServerFrame::Pong,
+        // Maintenance refuses anything that would change the game
+        _ if state.status.is_read_only() => Err(StatusCode::SERVICE_UNAVAILABLE.into()),
+        ClientFrame::Start => {
+            routes::start_narrative(State(state.clone()), Path(player_id), headers.clone())
+                .await
+                .map(|Json(r)| ServerFrame::Moment(Box::new(r)))
+        }
+        ClientFrame::Choice {
+            choice_id,
+            choice_text,
+            moment_id,
+        } => choose(state, player_id, headers, choice_id, choice_text, moment_id).await,
+        ClientFrame::Say { text, moment_id } => {
+            choose(state, player_id, headers, "say".to_string(), text, moment_id).await
+        }
+        ClientFrame::Reset { cause } => {
+            let cause = cause.unwrap_or(LoopEndCause::ChoseReset);
+            routes::end_current_loop(state.clone(), player_id, headers.clone(), cause)
+                .await
+                .map(|Json(r)| ServerFrame::Reset(Box::new(r)))
+                .map_err(ApiError::from)
+        }
+    };
+    result.unwrap_or_else(|error| match error {
+        ApiError::Status(status) => ServerFrame::Error {
+            code: status.as_u16(),
+            message: status.canonical_reason().unwrap_or("error").to_string(),
+        },
+        ApiError::Throttled(throttled) => ServerFrame::Throttled(throttled),
+    })
+}
+
+async fn choose(
+    state: &AppState,
+    player_id: Uuid,
+    headers: &HeaderMap,
+    choice_id: String,
+    choice_text: String,
+    moment_id: Option<Uuid>,
+) -> Result<ServerFrame, ApiError> {
+    let request = ChoiceRequest {
+        choice_id,
+        choice_text,
+        moment_id,
+    };
+    routes::make_choice(State(state.clone()), Path(player_id), headers.clone(), Json(request))
+        .await
+        .map(|Json(r)| ServerFrame::Moment(Box::new(r)))
+}
+
+/// Frames pushed to a player for one of their events. Throttled commands
+/// already get a `Throttled` frame as their reply.
+fn event_frames(event: &GameEvent, rating: ContentRating, locale: Locale) -> Vec<ServerFrame> {
+    match event {
+        GameEvent::Texture { text, tone, .. } => vec![ServerFrame::Texture {
+            text: text.clone(),
+            tone,
+        }],
+        GameEvent::Thinking { stage, text, .. } => vec![ServerFrame::Thinking {
+            stage,
+            text: text.clone(),
+        }],
+        _ => achievements(event, rating, locale),
+    }
+}
+
+/// Popups for milestones: new endings and the narrators they unlock
+fn achievements(event: &GameEvent, rating: ContentRating, locale: Locale) -> Vec<ServerFrame> {
+    let GameEvent::EndingReached {
+        ending,
+        first_time: true,
+        ..
+    } = event
+    else {
+        return Vec::new();
+    };
+
+    let mut frames = vec![ServerFrame::Achievement {
+        title: format!(
+            "{}: {}",
+            i18n::text(locale, Text::EndingReached),
+            ending.get_title(locale)
+        ),
+        description: ending.get_description_for(rating, locale).to_string(),
+    }];
+    for persona in Persona::ALL {
+        if persona.unlocked_by().is_some_and(|endings| endings.contains(ending)) {
+            frames.push(ServerFrame::Achievement {
+                title: format!(
+                    "{}: {}",
+                    i18n::text(locale, Text::NarratorUnlocked),
+                    persona.get_title()
+                ),
+                description: i18n::text(locale, Text::SwitchNarrator).to_string(),
+            });
+        }
+    }
+    frames
+}
+
+/// Send the next beat of the moment being revealed, or stop revealing once
+/// every beat is out
+async fn next_beat(
+    socket: &mut WebSocket,
+    state: &AppState,
+    player_id: Uuid,
+    reveal: &mut Option<(Reveal, tokio::time::Instant)>,
+    ack_timeout: Duration,
+) -> Result<(), axum::Error> {
+    let Some((moment, deadline)) = reveal.as_mut() else {
+        return Ok(());
+    };
+    match moment.next_beat() {
+        Some(beat) => {
+            *deadline = tokio::time::Instant::now() + ack_timeout;
+            send(socket, state, player_id, ServerFrame::Beat(beat)).await
+        }
+        None => {
+            *reveal = None;
+            Ok(())
+        }
+    }
+}
+
+async fn send(
+    socket: &mut WebSocket,
+    state: &AppState,
+    player_id: Uuid,
+    frame: ServerFrame,
+) -> Result<(), axum::Error> {
+    let text = state.ws.record(player_id, frame);
+    send_raw(socket, text).await
+}
+
+async fn send_raw(socket: &mut WebSocket, text: String) -> Result<(), axum::Error> {
+    socket.send(Message::Text(text.into())).await
+}